@@ -0,0 +1,127 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::joystick::Direction;
+
+/// An action of the joystick.
+///
+/// The 18 combinations of the stick (centered, or in one of the 8 directions)
+/// and the button, in the order of the Arcade Learning Environment; the index
+/// of an action is the one the agents are trained with.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Action, Cartridge, Console, Joystick, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # console.plug_controller(Port::Left, Box::new(Joystick::new()));
+/// # struct Agent;
+/// # impl Agent { fn act(&self, _observation: &[u8]) -> usize { 10 } }
+/// # let (agent, observation) = (Agent, console.frame().grayscale(atari_2600::Region::FULL, 84, 84));
+/// let action = Action::from_index(agent.act(&observation)).unwrap();
+/// console.apply_action(action);
+/// console.run_frame();
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Noop,
+    Fire,
+    Up,
+    Right,
+    Left,
+    Down,
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+    UpFire,
+    RightFire,
+    LeftFire,
+    DownFire,
+    UpRightFire,
+    UpLeftFire,
+    DownRightFire,
+    DownLeftFire
+}
+
+impl Action {
+    /// All the actions, ordered by their index.
+    pub const ALL: [Action; 18] = [
+        Action::Noop, Action::Fire,
+        Action::Up, Action::Right, Action::Left, Action::Down,
+        Action::UpRight, Action::UpLeft, Action::DownRight, Action::DownLeft,
+        Action::UpFire, Action::RightFire, Action::LeftFire, Action::DownFire,
+        Action::UpRightFire, Action::UpLeftFire, Action::DownRightFire, Action::DownLeftFire
+    ];
+
+    /// Action of a given index.
+    ///
+    /// This function returns the action of the given index (from 0 to 17),
+    /// or `None` if the index is out of range.
+    ///
+    pub fn from_index(index: usize) -> Option<Action> {
+        Action::ALL.get(index).copied()
+    }
+
+    /// Index of the action.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Directions the stick is pushed in.
+    ///
+    /// This function returns the directions of the action; none when the
+    /// stick is centered, two for the diagonals.
+    ///
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            Action::Noop | Action::Fire => &[],
+            Action::Up | Action::UpFire => &[Direction::Up],
+            Action::Right | Action::RightFire => &[Direction::Right],
+            Action::Left | Action::LeftFire => &[Direction::Left],
+            Action::Down | Action::DownFire => &[Direction::Down],
+            Action::UpRight | Action::UpRightFire => &[Direction::Up, Direction::Right],
+            Action::UpLeft | Action::UpLeftFire => &[Direction::Up, Direction::Left],
+            Action::DownRight | Action::DownRightFire => &[Direction::Down, Direction::Right],
+            Action::DownLeft | Action::DownLeftFire => &[Direction::Down, Direction::Left]
+        }
+    }
+
+    /// Whether the button is pressed.
+    pub fn is_fire(&self) -> bool {
+        *self == Action::Fire || self.index() >= Action::UpFire.index()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_actions() {
+        for (index, action) in Action::ALL.iter().enumerate() {
+            assert_eq!(action.index(), index);
+            assert_eq!(Action::from_index(index), Some(*action));
+        }
+        assert_eq!(Action::from_index(18), None);
+
+        // The indices of the Arcade Learning Environment.
+        assert_eq!(Action::Fire.index(), 1);
+        assert_eq!(Action::DownLeft.index(), 9);
+        assert_eq!(Action::UpRightFire.index(), 14);
+
+        // Half of the actions press the button.
+        assert_eq!(Action::ALL.iter().filter(|action| action.is_fire()).count(), 9);
+        assert!(!Action::Noop.is_fire());
+        assert!(Action::DownFire.is_fire());
+
+        assert_eq!(Action::Noop.directions(), &[]);
+        assert_eq!(Action::DownLeftFire.directions(), &[Direction::Down, Direction::Left]);
+        assert_eq!(Action::RightFire.directions(), Action::Right.directions());
+    }
+}
@@ -22,24 +22,28 @@
 //! - Indexed Indirect
 //! - Indirect Index
 //!
-//! Note that they're tightly coupled with the instructions and there is no unit
-//! tests as they're indirectly tested with the instructions unit tests.
+//! The functions take the processor and the bus it's connected to (see
+//! `Cpu6507` and `Bus`), they don't depend on the console; they can be reused
+//! by any machine built around a 6502 family processor.
 //!
-use super::console::Console;
+//! Note that they're tightly coupled with the instructions and most of them
+//! are tested indirectly by the unit tests of the instructions; only the
+//! wrapping of the indirect modes is tested here.
+//!
+use crate::cpu::Cpu6507;
+use crate::bus::Bus;
 
 /// Relative addressing mode.
 ///
-/// The relative addressing mode designates the operand as a value in memory
-/// indexed by the pointer counter.
+/// The relative addressing mode designates the operand as a signed offset
+/// from the program counter, in the immediate byte following the opcode; it's
+/// used by the branch instructions.
 ///
-/// This function consumes the relevant bytes following the opcode and returns
-/// the index of the value in memory on which the instruction must operate.
+/// This function consumes the byte following the opcode and returns the
+/// offset.
 ///
-pub fn relative(console: &mut Console) -> i8 {
-    let index = console.pointer_counter;
-    console.advance_pointer();
-
-    *console.memory(index) as i8
+pub fn relative<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> i8 {
+    cpu.fetch(bus) as i8
 }
 
 /// Immediate addressing mode.
@@ -50,9 +54,9 @@ pub fn relative(console: &mut Console) -> i8 {
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn immediate(console: &mut Console) -> u16 {
-    let index = console.pointer_counter;
-    console.advance_pointer();
+pub fn immediate<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    let index = cpu.program_counter;
+    cpu.fetch(bus);
 
     index
 }
@@ -66,11 +70,8 @@ pub fn immediate(console: &mut Console) -> u16 {
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn zero_page(console: &mut Console) -> u16 {
-    let index = *console.pointed_value() as u16;
-    console.advance_pointer();
-
-    index
+pub fn zero_page<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    cpu.fetch(bus) as u16
 }
 
 /// Zero page X addressing mode.
@@ -82,11 +83,8 @@ pub fn zero_page(console: &mut Console) -> u16 {
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn zero_page_x(console: &mut Console) -> u16 {
-    let index = console.pointed_value().wrapping_add(console.x_register) as u16;
-    console.advance_pointer();
-
-    index
+pub fn zero_page_x<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    cpu.fetch(bus).wrapping_add(cpu.x_register) as u16
 }
 
 /// Zero page Y address mode.
@@ -98,52 +96,44 @@ pub fn zero_page_x(console: &mut Console) -> u16 {
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn zero_page_y(console: &mut Console) -> u16 {
-    let index = console.pointed_value().wrapping_add(console.y_register) as u16;
-    console.advance_pointer();
-
-    index
+pub fn zero_page_y<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    cpu.fetch(bus).wrapping_add(cpu.y_register) as u16
 }
 
 /// Absolute addressing mode.
 ///
-/// The zero page addressing mode designates the operand as a a value anywhere
-/// in the memory, which is indexed by the two following bytes. The first byte
-/// corresponds to the page number and the second byte is the index on this the
-/// page.
+/// The absolute addressing mode designates the operand as a value anywhere in
+/// the memory, which is indexed by the two following bytes. The first byte is
+/// the index on the page and the second byte corresponds to the page number.
 ///
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn absolute(console: &mut Console) -> u16 {
-    let ll = *console.pointed_value();
-    console.advance_pointer();
-    let hh = *console.pointed_value();
-    console.advance_pointer();
+pub fn absolute<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    let ll = cpu.fetch(bus);
+    let hh = cpu.fetch(bus);
 
     u16::from_le_bytes([ll, hh])
 }
 
 /// Absolute X addressing mode.
 ///
-/// The zero page X addressing mode designates the operand as a a value anywhere
+/// The absolute X addressing mode designates the operand as a value anywhere
 /// in the memory, which is indexed by the two following bytes and the X
-/// register. The first byte corresponds to the page number and the second byte
-/// **plus** the X register value is the index on this the page. If the addition
-/// of the second byte with the X register value overflows, the page number is
+/// register. The first byte **plus** the X register value is the index on the
+/// page and the second byte corresponds to the page number. If the addition of
+/// the first byte with the X register value overflows, the page number is
 /// incremented and most instructions will add a cycle; this is why a boolean
 /// value is returned.
 ///
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn absolute_x(console: &mut Console) -> (u16, bool) {
-    let ll = *console.pointed_value();
-    console.advance_pointer();
-    let hh = *console.pointed_value();
-    console.advance_pointer();
+pub fn absolute_x<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> (u16, bool) {
+    let ll = cpu.fetch(bus);
+    let hh = cpu.fetch(bus);
 
-    match ll.overflowing_add(console.x_register) {
+    match ll.overflowing_add(cpu.x_register) {
         (value, false) => (u16::from_le_bytes([value, hh]), false),
         (value, true) => {
             (u16::from_le_bytes([value, hh.wrapping_add(1)]), true)
@@ -153,24 +143,22 @@ pub fn absolute_x(console: &mut Console) -> (u16, bool) {
 
 /// Absolute Y addressing mode.
 ///
-/// The zero page Y addressing mode designates the operand as a a value anywhere
+/// The absolute Y addressing mode designates the operand as a value anywhere
 /// in the memory, which is indexed by the two following bytes and the Y
-/// register. The first byte corresponds to the page number and the second byte
-/// **plus** the Y register value is the index on this the page. If the addition
-/// of the second byte with the Y register value overflows, the page number is
+/// register. The first byte **plus** the Y register value is the index on the
+/// page and the second byte corresponds to the page number. If the addition of
+/// the first byte with the Y register value overflows, the page number is
 /// incremented and most instructions will add a cycle; this is why a boolean
 /// value is returned.
 ///
 /// This function consumes the relevant bytes following the opcode and returns
 /// the index of the value in memory on which the instruction must operate.
 ///
-pub fn absolute_y(console: &mut Console) -> (u16, bool) {
-    let ll = *console.pointed_value();
-    console.advance_pointer();
-    let hh = *console.pointed_value();
-    console.advance_pointer();
+pub fn absolute_y<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> (u16, bool) {
+    let ll = cpu.fetch(bus);
+    let hh = cpu.fetch(bus);
 
-    match ll.overflowing_add(console.y_register) {
+    match ll.overflowing_add(cpu.y_register) {
         (value, false) => (u16::from_le_bytes([value, hh]), false),
         (value, true) => {
             (u16::from_le_bytes([value, hh.wrapping_add(1)]), true)
@@ -180,58 +168,104 @@ pub fn absolute_y(console: &mut Console) -> (u16, bool) {
 
 /// Indexed indirect addressing mode.
 ///
-/// The indexed indirect addressing mode designates the operand as foobar.
-///
-/// ```
-/// INDEXED  INDIRECT ADDRESSING  -  In  indexed  indirect  addressing  (referred   to   as  (Indirect,X)), the  second byte  ofthe  instruction  is  added  to  the  contents  of  the  X  index  register,  discarding  the  carry.   The  result of  this  addition  points  to a memory  location  on page  zero whose  contents  is  the  low order  eight  bits of  the  effective  address.   The  next  memory  location  in  page  zero  contains  the high  order  eight  bits of  the  effective  address.   Both memory  locations  specifying  the  high  and  low order  bytes  of  the effective  address must  be  in  page  zero.
-/// ```
+/// The indexed indirect addressing mode, written `(zp,X)`, designates the
+/// operand as a value anywhere in the memory whose address is stored in the
+/// zero page. The immediate byte following the opcode plus the X register
+/// (discarding the carry) is the location of the low byte of the address, and
+/// the next location is the one of its high byte. Both locations are in the
+/// zero page; the location following 0xFF is 0x00.
 ///
-/// TODO; To be written.
+/// This function consumes the relevant bytes following the opcode and returns
+/// the index of the value in memory on which the instruction must operate.
 ///
-pub fn indexed_indirect(console: &mut Console) -> u16 {
-    let index = console.pointed_value().wrapping_add(console.x_register);
-    console.advance_pointer();
-
-    let ll = *console.memory(index as u16);
-    // TODO; Make sure indirect_index + 1 is within page 0, otherwise it's illegal operation I think.
-    let hh = *console.memory(index as u16 + 1);
+pub fn indexed_indirect<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> u16 {
+    let index = cpu.fetch(bus).wrapping_add(cpu.x_register);
 
-    let indirect_index = u16::from_le_bytes([ll, hh]);
+    let ll = bus.read(index as u16);
+    let hh = bus.read(index.wrapping_add(1) as u16);
 
-    indirect_index
+    u16::from_le_bytes([ll, hh])
 }
 
-
 /// Indirect indexed addressing mode.
 ///
-/// The indirect indexed addressing mode designates the operand as foobar.
+/// The indirect indexed addressing mode, written `(zp),Y`, designates the
+/// operand as a value anywhere in the memory whose base address is stored in
+/// the zero page, plus the Y register. The immediate byte following the
+/// opcode is the location of the low byte of the base address, and the next
+/// location (the location following 0xFF is 0x00) is the one of its high
+/// byte. If the addition of the low byte with the Y register value overflows,
+/// the page number is incremented (wrapping around the memory) and most
+/// instructions will add a cycle; this is why a boolean value is returned.
 ///
-/// ```
-/// INDIRECT  INDEXED ADDRESSING  -  In  indirect  indexed  addressing  (referred  to  as (Indirect),Y),  the    second  byteof  the  instruction  points  to  a memory  location  in  page  zero.   The  contents  of  this memory  location is  added  to  the  contents  of  the  Y  index  register,  the  result  being the  low order eight  bits  of  theeffective  address.   The  carry  from  this  addition  is  added  to  the  contents  of  the next     page  zeromemory  location,  the  result  being  the  high  order  eight  bits  of  the  effective  address.
-/// ```
-///
-/// TODO; To be written.
+/// This function consumes the relevant bytes following the opcode and returns
+/// the index of the value in memory on which the instruction must operate.
 ///
-pub fn indirect_indexed(console: &mut Console) -> (u16, bool) {
+pub fn indirect_indexed<B: Bus>(cpu: &mut Cpu6507, bus: &mut B) -> (u16, bool) {
+    let index = cpu.fetch(bus);
 
-    let index = *console.pointed_value() as u16; 
-    console.advance_pointer();
+    let ll = bus.read(index as u16);
+    let hh = bus.read(index.wrapping_add(1) as u16);
 
-    // Not my proudest code, definitively messy.
-    let ll = *console.memory(index);
-    match ll.overflowing_add(console.y_register) {
-        (value, false) => {
-            let hh = *console.memory(index + 1);
-            let indirect_index = u16::from_le_bytes([value, hh]);
+    match ll.overflowing_add(cpu.y_register) {
+        (value, false) => (u16::from_le_bytes([value, hh]), false),
+        (value, true) => (u16::from_le_bytes([value, hh.wrapping_add(1)]), true)
+    }
+}
 
-            (indirect_index, false)
-        },
-        (value, true) => {
-            let hh = *console.memory(index + 1);
-            // TODO; Potential overflow with hh + 1; what to do ?
-            let indirect_index = u16::from_le_bytes([value, hh + 1]);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-            (indirect_index, true)
+    struct Memory([u8; 65536]);
+
+    impl Bus for Memory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.0[address as usize]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.0[address as usize] = value;
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_indexed_indirect() {
+        let mut memory = Memory([0; 65536]);
+        memory.0[0x_1000] = 0x_FE; // The operand of the instruction.
+        memory.0[0x_00FF] = 0x_34;
+        memory.0[0x_0000] = 0x_12;
+        memory.0[0x_0100] = 0x_56;
+
+        let mut cpu = Cpu6507::new();
+        cpu.program_counter = 0x_1000;
+        cpu.x_register = 0x_01;
+
+        // The high byte of the pointer at 0xFF is read from 0x00.
+        assert_eq!(indexed_indirect(&mut cpu, &mut memory), 0x_1234);
+        assert_eq!(cpu.program_counter, 0x_1001);
+    }
+
+    #[test]
+    fn test_indirect_indexed() {
+        let mut memory = Memory([0; 65536]);
+        memory.0[0x_1000] = 0x_FF; // The operand of the instructions.
+        memory.0[0x_1001] = 0x_FF;
+        memory.0[0x_00FF] = 0x_F0;
+        memory.0[0x_0000] = 0x_12;
+        memory.0[0x_0100] = 0x_56;
+
+        let mut cpu = Cpu6507::new();
+        cpu.program_counter = 0x_1000;
+
+        // The high byte of the pointer at 0xFF is read from 0x00.
+        cpu.y_register = 0x_01;
+        assert_eq!(indirect_indexed(&mut cpu, &mut memory), (0x_12F1, false));
+
+        // The page is incremented when the low byte carries, and it wraps
+        // around the memory.
+        memory.0[0x_0000] = 0x_FF;
+        cpu.y_register = 0x_20;
+        assert_eq!(indirect_indexed(&mut cpu, &mut memory), (0x_0010, true));
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::collections::BTreeSet;
+
+use crate::cartridge::Cartridge;
+use crate::database::ControllerKind;
+use crate::location::{INPT0, INPT1, INPT2, INPT3, INPT4, INPT5, SWCHA};
+
+/// The bank switching scheme of a cartridge.
+///
+/// Cartridges of more than 4k swap banks of 4k in and out when some addresses
+/// (the hotspots) are accessed. The scheme is guessed from the size of the
+/// ROM; it's the Atari scheme for that size, which is the most common one.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BankSwitching {
+    /// A 2k or 4k ROM with no bank switching.
+    Standard,
+    /// 8k; 2 banks selected at 0x1FF8 and 0x1FF9.
+    F8,
+    /// 12k; 3 banks selected at 0x1FF8 to 0x1FFA.
+    FA,
+    /// 16k; 4 banks selected at 0x1FF6 to 0x1FF9.
+    F6,
+    /// 32k; 8 banks selected at 0x1FF4 to 0x1FFB.
+    F4,
+    /// Any other size.
+    Unknown
+}
+
+/// The analysis of a bank of a cartridge.
+///
+/// The vectors are read from the last bytes of the bank; the code size is the
+/// number of bytes reached by following the code from the entry points (the
+/// reset and IRQ vectors).
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct BankAnalysis {
+    pub index: usize,
+    pub reset_vector: u16,
+    pub irq_vector: u16,
+    pub code_size: usize
+}
+
+/// The static analysis of a cartridge.
+///
+/// This structure is a report of what can be learned from a ROM without
+/// running it; its banks and their entry points, the bank switching scheme,
+/// the hotspots accessed by the code and the TIA and PIA registers it
+/// references. It's created with `analyze_rom()` and is meant to pick
+/// sensible defaults (the controllers, the bank switching scheme) and to be
+/// displayed as a "ROM info" panel.
+///
+/// Registers are normalized to their canonical address (mirrors are folded);
+/// TIA registers that are read are reported as 0x30 to 0x3D.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct RomAnalysis {
+    pub size: usize,
+    pub md5: String,
+    pub bank_switching: BankSwitching,
+    pub banks: Vec<BankAnalysis>,
+    pub hotspots: Vec<u16>,
+    pub tia_writes: Vec<u16>,
+    pub tia_reads: Vec<u16>,
+    pub pia_registers: Vec<u16>
+}
+
+impl RomAnalysis {
+    /// Guess the controller the game expects.
+    ///
+    /// This function guesses the controller from the input registers the
+    /// code reads; the joystick when it reads the port A or the triggers only.
+    /// There is no guess when it reads the pot ports (paddles, booster grips,
+    /// etc.) as they can't be told apart.
+    ///
+    pub fn controller(&self) -> Option<ControllerKind> {
+        let is_reading = |register: &u16| self.tia_reads.contains(register) || self.pia_registers.contains(register);
+
+        if [INPT0, INPT1, INPT2, INPT3].iter().any(is_reading) {
+            None
+        }
+        else if [SWCHA, INPT4, INPT5].iter().any(is_reading) {
+            Some(ControllerKind::Joystick)
+        }
+        else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for RomAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registers = |registers: &[u16]| registers.iter()
+            .map(|register| format!("{:02X}", register))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        writeln!(f, "Size: {} bytes", self.size)?;
+        writeln!(f, "MD5: {}", self.md5)?;
+        writeln!(f, "Bank switching: {:?}", self.bank_switching)?;
+        for bank in &self.banks {
+            writeln!(
+                f, "Bank {}: reset {:04X}, IRQ {:04X}, {} bytes of code",
+                bank.index, bank.reset_vector, bank.irq_vector, bank.code_size
+            )?;
+        }
+        writeln!(f, "Hotspots: {}", registers(&self.hotspots))?;
+        writeln!(f, "TIA writes: {}", registers(&self.tia_writes))?;
+        writeln!(f, "TIA reads: {}", registers(&self.tia_reads))?;
+        write!(f, "PIA registers: {}", registers(&self.pia_registers))
+    }
+}
+
+// The operand of an instruction, as far as the analysis is concerned.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Operand {
+    Implied,
+    Immediate,
+    ZeroPage,
+    Absolute,
+    Relative,
+    Indirect,
+    IndexedIndirect
+}
+
+impl Operand {
+    pub(crate) fn size(self) -> usize {
+        match self {
+            Operand::Implied => 0,
+            Operand::Absolute | Operand::Indirect => 2,
+            _ => 1
+        }
+    }
+}
+
+pub(crate) fn decode(opcode: u8) -> Option<Operand> {
+    // The official opcodes are encoded as 'aaabbbcc'; 'cc' selects a group of
+    // instructions and 'bbb' mostly selects the addressing mode. The unofficial
+    // opcodes aren't decoded (they aren't emulated either).
+    let (aaa, bbb, cc) = (opcode >> 5, (opcode >> 2) & 0b111, opcode & 0b11);
+
+    let operand = match (cc, bbb) {
+        (0b01, 0b000) | (0b01, 0b100) => Operand::IndexedIndirect,
+        (0b01, 0b010) if opcode != 0x_89 => Operand::Immediate,
+        (0b01, 0b001) | (0b01, 0b101) => Operand::ZeroPage,
+        (0b01, 0b011) | (0b01, 0b110) | (0b01, 0b111) => Operand::Absolute,
+
+        (0b10, 0b000) if opcode == 0x_A2 => Operand::Immediate,
+        (0b10, 0b001) | (0b10, 0b101) => Operand::ZeroPage,
+        (0b10, 0b010) => Operand::Implied,
+        (0b10, 0b110) if opcode == 0x_9A || opcode == 0x_BA => Operand::Implied,
+        (0b10, 0b011) => Operand::Absolute,
+        (0b10, 0b111) if opcode != 0x_9E => Operand::Absolute,
+
+        (0b00, 0b000) if opcode == 0x_20 => Operand::Absolute,
+        (0b00, 0b000) if aaa <= 3 => Operand::Implied,
+        (0b00, 0b000) if aaa >= 5 => Operand::Immediate,
+        (0b00, 0b001) if aaa == 1 || aaa >= 4 => Operand::ZeroPage,
+        (0b00, 0b010) | (0b00, 0b110) => Operand::Implied,
+        (0b00, 0b011) if opcode == 0x_6C => Operand::Indirect,
+        (0b00, 0b011) if aaa >= 1 => Operand::Absolute,
+        (0b00, 0b100) => Operand::Relative,
+        (0b00, 0b101) if aaa == 4 || aaa == 5 => Operand::ZeroPage,
+        (0b00, 0b111) if opcode == 0x_BC => Operand::Absolute,
+
+        _ => return None
+    };
+
+    Some(operand)
+}
+
+pub(crate) fn is_write(opcode: u8) -> bool {
+    // The store instructions, and the read-modify-write instructions (ASL,
+    // ROL, LSR, ROR, DEC and INC) when they operate on memory.
+    let (aaa, bbb, cc) = (opcode >> 5, (opcode >> 2) & 0b111, opcode & 0b11);
+
+    match cc {
+        0b01 => aaa == 4,
+        0b10 => aaa != 5 && bbb & 0b001 != 0,
+        _    => aaa == 4 && bbb & 0b001 != 0
+    }
+}
+
+pub(crate) fn bank_switching(size: usize) -> BankSwitching {
+    match size {
+        0..=4096 => BankSwitching::Standard,
+        8192     => BankSwitching::F8,
+        12288    => BankSwitching::FA,
+        16384    => BankSwitching::F6,
+        32768    => BankSwitching::F4,
+        _        => BankSwitching::Unknown
+    }
+}
+
+#[derive(Default)]
+struct References {
+    hotspots: BTreeSet<u16>,
+    tia_writes: BTreeSet<u16>,
+    tia_reads: BTreeSet<u16>,
+    pia_registers: BTreeSet<u16>
+}
+
+impl References {
+    fn add(&mut self, opcode: u8, address: u16) {
+        // Only 13 address lines are attached; A12 selects the cartridge, then
+        // A7 selects the TIA (when clear), and A9 selects the PIA over the
+        // RAM.
+        let address = address & 0x_1FFF;
+
+        if address & 0x_1000 != 0 {
+            if (0x_1FF4..=0x_1FFB).contains(&address) {
+                self.hotspots.insert(address);
+            }
+        }
+        else if address & 0x_0080 == 0 {
+            if is_write(opcode) {
+                self.tia_writes.insert(address & 0x_003F);
+            }
+            else {
+                self.tia_reads.insert(0x_0030 | (address & 0x_000F));
+            }
+        }
+        else if address & 0x_0200 != 0 {
+            self.pia_registers.insert(address & 0x_0297);
+        }
+    }
+}
+
+fn analyze_bank(index: usize, bank: &[u8], references: &mut References) -> BankAnalysis {
+    let vector = |offset: usize| {
+        let offset = bank.len() - offset;
+        u16::from_le_bytes([bank[offset], bank[offset + 1]])
+    };
+
+    let reset_vector = vector(4);
+    let irq_vector = vector(2);
+
+    // Follow the code from the entry points; every branch and subroutine is
+    // followed, and a path ends on a return, an indirect jump or an unknown
+    // opcode. Code running from the RAM isn't followed.
+    let mut visited = vec![false; bank.len()];
+    let mut pending = vec![reset_vector, irq_vector];
+
+    while let Some(address) = pending.pop() {
+        if address & 0x_1000 == 0 {
+            continue;
+        }
+
+        let mut offset = address as usize % bank.len();
+        while !visited[offset] {
+            let opcode = bank[offset];
+            let operand = match decode(opcode) {
+                Some(operand) => operand,
+                None => break
+            };
+
+            let size = 1 + operand.size();
+            for byte in 0..size {
+                visited[(offset + byte) % bank.len()] = true;
+            }
+
+            let byte = |index: usize| bank[(offset + index) % bank.len()];
+            let next = (address & 0x_F000) | ((offset + size) % bank.len()) as u16;
+
+            match operand {
+                Operand::Relative => {
+                    let target = next.wrapping_add(byte(1) as i8 as u16);
+                    pending.push(target);
+                },
+                Operand::ZeroPage => references.add(opcode, byte(1) as u16),
+                Operand::Absolute => {
+                    let target = u16::from_le_bytes([byte(1), byte(2)]);
+                    match opcode {
+                        0x_20 | 0x_4C => pending.push(target),
+                        _ => references.add(opcode, target)
+                    }
+                },
+                _ => ()
+            }
+
+            // BRK, RTI, RTS and the jumps don't continue with the next
+            // instruction.
+            if let 0x_00 | 0x_40 | 0x_60 | 0x_4C | 0x_6C = opcode {
+                break;
+            }
+
+            offset = (offset + size) % bank.len();
+        }
+    }
+
+    BankAnalysis {
+        index,
+        reset_vector,
+        irq_vector,
+        code_size: visited.iter().filter(|visited| **visited).count()
+    }
+}
+
+/// Analyze a cartridge without running it.
+///
+/// This function scans the ROM of a cartridge; it splits it into banks, reads
+/// their vectors and follows their code to find the hotspots and the TIA and
+/// PIA registers it references. The analysis is static; code reached with
+/// indirect jumps or computed returns isn't seen.
+///
+pub fn analyze_rom(cartridge: &Cartridge) -> RomAnalysis {
+    let mut references = References::default();
+
+    let banks = cartridge.memory
+        .chunks(4096)
+        .filter(|bank| bank.len() >= 4)
+        .enumerate()
+        .map(|(index, bank)| analyze_bank(index, bank, &mut references))
+        .collect();
+
+    RomAnalysis {
+        size: cartridge.memory.len(),
+        md5: cartridge.md5(),
+        bank_switching: bank_switching(cartridge.memory.len()),
+        banks,
+        hotspots: references.hotspots.into_iter().collect(),
+        tia_writes: references.tia_writes.into_iter().collect(),
+        tia_reads: references.tia_reads.into_iter().collect(),
+        pia_registers: references.pia_registers.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::location::{WSYNC, COLUBK, TIM64T, INTIM};
+
+    #[test]
+    fn test_decode() {
+        assert!(decode(0x_EA) == Some(Operand::Implied));
+        assert!(decode(0x_A9) == Some(Operand::Immediate));
+        assert!(decode(0x_85) == Some(Operand::ZeroPage));
+        assert!(decode(0x_BD) == Some(Operand::Absolute));
+        assert!(decode(0x_D0) == Some(Operand::Relative));
+        assert!(decode(0x_6C) == Some(Operand::Indirect));
+        assert!(decode(0x_B1) == Some(Operand::IndexedIndirect));
+        assert!(decode(0x_A0) == Some(Operand::Immediate));
+        assert!(decode(0x_20) == Some(Operand::Absolute));
+        assert!(decode(0x_60) == Some(Operand::Implied));
+        assert!(decode(0x_89).is_none());
+        assert!(decode(0x_02).is_none());
+        assert!(decode(0x_FF).is_none());
+
+        assert!(is_write(0x_85));
+        assert!(is_write(0x_8E));
+        assert!(is_write(0x_E6));
+        assert!(!is_write(0x_A5));
+        assert!(!is_write(0x_2C));
+        assert!(!is_write(0x_EA));
+    }
+
+    #[test]
+    fn test_analyze_rom() {
+        // An 8k ROM whose first bank writes COLUBK and WSYNC, sets the timer,
+        // reads it and the joystick in a subroutine, then switches to the
+        // second bank; the second bank only loops.
+        let mut first_bank = vec![
+            0x_A9, 0x_0E,       // LDA #$0E
+            0x_85, 0x_09,       // STA COLUBK
+            0x_85, 0x_42,       // STA WSYNC (mirror)
+            0x_8D, 0x_96, 0x_02,// STA TIM64T
+            0x_20, 0x_20, 0x_F0,// JSR $F020
+            0x_AD, 0x_F9, 0x_FF,// LDA $FFF9
+            0x_4C, 0x_00, 0x_F0 // JMP $F000
+        ];
+        first_bank.resize(0x_20, 0x_00);
+        first_bank.extend_from_slice(&[
+            0x_AD, 0x_84, 0x_02,// LDA INTIM
+            0x_D0, 0x_FB,       // BNE $F020
+            0x_A5, 0x_0C,       // LDA INPT4 (mirror)
+            0x_2C, 0x_80, 0x_02,// BIT SWCHA
+            0x_60               // RTS
+        ]);
+        first_bank.resize(4096, 0x_FF);
+        first_bank[0x_FFC..].copy_from_slice(&[0x_00, 0x_F0, 0x_00, 0x_F0]);
+
+        let mut second_bank = vec![0x_4C, 0x_00, 0x_D0];
+        second_bank.resize(4096, 0x_FF);
+        second_bank[0x_FFC..].copy_from_slice(&[0x_00, 0x_D0, 0x_00, 0x_D0]);
+
+        let mut rom = first_bank;
+        rom.extend(second_bank);
+
+        let cartridge = Cartridge::new(rom);
+        let analysis = analyze_rom(&cartridge);
+
+        assert_eq!(analysis.size, 8192);
+        assert_eq!(analysis.md5, cartridge.md5());
+        assert_eq!(analysis.bank_switching, BankSwitching::F8);
+        assert_eq!(analysis.banks, vec![
+            BankAnalysis { index: 0, reset_vector: 0x_F000, irq_vector: 0x_F000, code_size: 29 },
+            BankAnalysis { index: 1, reset_vector: 0x_D000, irq_vector: 0x_D000, code_size: 3 }
+        ]);
+        assert_eq!(analysis.hotspots, vec![0x_1FF9]);
+        assert_eq!(analysis.tia_writes, vec![WSYNC, COLUBK]);
+        assert_eq!(analysis.tia_reads, vec![INPT4]);
+        assert_eq!(analysis.pia_registers, vec![SWCHA, INTIM, TIM64T]);
+        assert_eq!(analysis.controller(), Some(ControllerKind::Joystick));
+
+        let report = analysis.to_string();
+        assert!(report.contains("Bank 1: reset D000, IRQ D000, 3 bytes of code"));
+        assert!(report.contains("TIA writes: 02 09"));
+    }
+
+    #[test]
+    fn test_analyze_small_rom() {
+        // A 2k ROM reading the pot ports; the controller can't be guessed.
+        let mut rom = vec![0x_A5, 0x_38, 0x_4C, 0x_00, 0x_F8];
+        rom.resize(2048, 0x_EA);
+        rom[0x_7FC..].copy_from_slice(&[0x_00, 0x_F8, 0x_00, 0x_F8]);
+
+        let analysis = analyze_rom(&Cartridge::new(rom));
+        assert_eq!(analysis.bank_switching, BankSwitching::Standard);
+        assert_eq!(analysis.banks.len(), 1);
+        assert_eq!(analysis.banks[0].code_size, 5);
+        assert_eq!(analysis.tia_reads, vec![INPT0]);
+        assert_eq!(analysis.controller(), None);
+    }
+}
@@ -0,0 +1,608 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::collections::HashMap;
+
+/// The origin of programs that don't set theirs; the beginning of a 4K
+/// cartridge.
+pub const DEFAULT_ORIGIN: u16 = 0x_F000;
+
+/// The addressing modes, as written in the source.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndexedIndirect,
+    IndirectIndexed,
+    Relative
+}
+
+impl Mode {
+    fn size(&self) -> u16 {
+        match self {
+            Mode::Implied | Mode::Accumulator => 1,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 3,
+            _ => 2
+        }
+    }
+}
+
+// The opcodes of the official instructions, for each addressing mode.
+const OPCODES: &[(&str, &[(Mode, u8)])] = &[
+    ("ADC", &[(Mode::Immediate, 0x_69), (Mode::ZeroPage, 0x_65), (Mode::ZeroPageX, 0x_75), (Mode::Absolute, 0x_6D), (Mode::AbsoluteX, 0x_7D), (Mode::AbsoluteY, 0x_79), (Mode::IndexedIndirect, 0x_61), (Mode::IndirectIndexed, 0x_71)]),
+    ("AND", &[(Mode::Immediate, 0x_29), (Mode::ZeroPage, 0x_25), (Mode::ZeroPageX, 0x_35), (Mode::Absolute, 0x_2D), (Mode::AbsoluteX, 0x_3D), (Mode::AbsoluteY, 0x_39), (Mode::IndexedIndirect, 0x_21), (Mode::IndirectIndexed, 0x_31)]),
+    ("ASL", &[(Mode::Accumulator, 0x_0A), (Mode::ZeroPage, 0x_06), (Mode::ZeroPageX, 0x_16), (Mode::Absolute, 0x_0E), (Mode::AbsoluteX, 0x_1E)]),
+    ("BCC", &[(Mode::Relative, 0x_90)]),
+    ("BCS", &[(Mode::Relative, 0x_B0)]),
+    ("BEQ", &[(Mode::Relative, 0x_F0)]),
+    ("BIT", &[(Mode::ZeroPage, 0x_24), (Mode::Absolute, 0x_2C)]),
+    ("BMI", &[(Mode::Relative, 0x_30)]),
+    ("BNE", &[(Mode::Relative, 0x_D0)]),
+    ("BPL", &[(Mode::Relative, 0x_10)]),
+    ("BRK", &[(Mode::Implied, 0x_00)]),
+    ("BVC", &[(Mode::Relative, 0x_50)]),
+    ("BVS", &[(Mode::Relative, 0x_70)]),
+    ("CLC", &[(Mode::Implied, 0x_18)]),
+    ("CLD", &[(Mode::Implied, 0x_D8)]),
+    ("CLI", &[(Mode::Implied, 0x_58)]),
+    ("CLV", &[(Mode::Implied, 0x_B8)]),
+    ("CMP", &[(Mode::Immediate, 0x_C9), (Mode::ZeroPage, 0x_C5), (Mode::ZeroPageX, 0x_D5), (Mode::Absolute, 0x_CD), (Mode::AbsoluteX, 0x_DD), (Mode::AbsoluteY, 0x_D9), (Mode::IndexedIndirect, 0x_C1), (Mode::IndirectIndexed, 0x_D1)]),
+    ("CPX", &[(Mode::Immediate, 0x_E0), (Mode::ZeroPage, 0x_E4), (Mode::Absolute, 0x_EC)]),
+    ("CPY", &[(Mode::Immediate, 0x_C0), (Mode::ZeroPage, 0x_C4), (Mode::Absolute, 0x_CC)]),
+    ("DEC", &[(Mode::ZeroPage, 0x_C6), (Mode::ZeroPageX, 0x_D6), (Mode::Absolute, 0x_CE), (Mode::AbsoluteX, 0x_DE)]),
+    ("DEX", &[(Mode::Implied, 0x_CA)]),
+    ("DEY", &[(Mode::Implied, 0x_88)]),
+    ("EOR", &[(Mode::Immediate, 0x_49), (Mode::ZeroPage, 0x_45), (Mode::ZeroPageX, 0x_55), (Mode::Absolute, 0x_4D), (Mode::AbsoluteX, 0x_5D), (Mode::AbsoluteY, 0x_59), (Mode::IndexedIndirect, 0x_41), (Mode::IndirectIndexed, 0x_51)]),
+    ("INC", &[(Mode::ZeroPage, 0x_E6), (Mode::ZeroPageX, 0x_F6), (Mode::Absolute, 0x_EE), (Mode::AbsoluteX, 0x_FE)]),
+    ("INX", &[(Mode::Implied, 0x_E8)]),
+    ("INY", &[(Mode::Implied, 0x_C8)]),
+    ("JMP", &[(Mode::Absolute, 0x_4C), (Mode::Indirect, 0x_6C)]),
+    ("JSR", &[(Mode::Absolute, 0x_20)]),
+    ("LDA", &[(Mode::Immediate, 0x_A9), (Mode::ZeroPage, 0x_A5), (Mode::ZeroPageX, 0x_B5), (Mode::Absolute, 0x_AD), (Mode::AbsoluteX, 0x_BD), (Mode::AbsoluteY, 0x_B9), (Mode::IndexedIndirect, 0x_A1), (Mode::IndirectIndexed, 0x_B1)]),
+    ("LDX", &[(Mode::Immediate, 0x_A2), (Mode::ZeroPage, 0x_A6), (Mode::ZeroPageY, 0x_B6), (Mode::Absolute, 0x_AE), (Mode::AbsoluteY, 0x_BE)]),
+    ("LDY", &[(Mode::Immediate, 0x_A0), (Mode::ZeroPage, 0x_A4), (Mode::ZeroPageX, 0x_B4), (Mode::Absolute, 0x_AC), (Mode::AbsoluteX, 0x_BC)]),
+    ("LSR", &[(Mode::Accumulator, 0x_4A), (Mode::ZeroPage, 0x_46), (Mode::ZeroPageX, 0x_56), (Mode::Absolute, 0x_4E), (Mode::AbsoluteX, 0x_5E)]),
+    ("NOP", &[(Mode::Implied, 0x_EA)]),
+    ("ORA", &[(Mode::Immediate, 0x_09), (Mode::ZeroPage, 0x_05), (Mode::ZeroPageX, 0x_15), (Mode::Absolute, 0x_0D), (Mode::AbsoluteX, 0x_1D), (Mode::AbsoluteY, 0x_19), (Mode::IndexedIndirect, 0x_01), (Mode::IndirectIndexed, 0x_11)]),
+    ("PHA", &[(Mode::Implied, 0x_48)]),
+    ("PHP", &[(Mode::Implied, 0x_08)]),
+    ("PLA", &[(Mode::Implied, 0x_68)]),
+    ("PLP", &[(Mode::Implied, 0x_28)]),
+    ("ROL", &[(Mode::Accumulator, 0x_2A), (Mode::ZeroPage, 0x_26), (Mode::ZeroPageX, 0x_36), (Mode::Absolute, 0x_2E), (Mode::AbsoluteX, 0x_3E)]),
+    ("ROR", &[(Mode::Accumulator, 0x_6A), (Mode::ZeroPage, 0x_66), (Mode::ZeroPageX, 0x_76), (Mode::Absolute, 0x_6E), (Mode::AbsoluteX, 0x_7E)]),
+    ("RTI", &[(Mode::Implied, 0x_40)]),
+    ("RTS", &[(Mode::Implied, 0x_60)]),
+    ("SBC", &[(Mode::Immediate, 0x_E9), (Mode::ZeroPage, 0x_E5), (Mode::ZeroPageX, 0x_F5), (Mode::Absolute, 0x_ED), (Mode::AbsoluteX, 0x_FD), (Mode::AbsoluteY, 0x_F9), (Mode::IndexedIndirect, 0x_E1), (Mode::IndirectIndexed, 0x_F1)]),
+    ("SEC", &[(Mode::Implied, 0x_38)]),
+    ("SED", &[(Mode::Implied, 0x_F8)]),
+    ("SEI", &[(Mode::Implied, 0x_78)]),
+    ("STA", &[(Mode::ZeroPage, 0x_85), (Mode::ZeroPageX, 0x_95), (Mode::Absolute, 0x_8D), (Mode::AbsoluteX, 0x_9D), (Mode::AbsoluteY, 0x_99), (Mode::IndexedIndirect, 0x_81), (Mode::IndirectIndexed, 0x_91)]),
+    ("STX", &[(Mode::ZeroPage, 0x_86), (Mode::ZeroPageY, 0x_96), (Mode::Absolute, 0x_8E)]),
+    ("STY", &[(Mode::ZeroPage, 0x_84), (Mode::ZeroPageX, 0x_94), (Mode::Absolute, 0x_8C)]),
+    ("TAX", &[(Mode::Implied, 0x_AA)]),
+    ("TAY", &[(Mode::Implied, 0x_A8)]),
+    ("TSX", &[(Mode::Implied, 0x_BA)]),
+    ("TXA", &[(Mode::Implied, 0x_8A)]),
+    ("TXS", &[(Mode::Implied, 0x_9A)]),
+    ("TYA", &[(Mode::Implied, 0x_98)])
+];
+
+fn opcodes(mnemonic: &str) -> Option<&'static [(Mode, u8)]> {
+    OPCODES.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(mnemonic))
+        .map(|(_, opcodes)| *opcodes)
+}
+
+/// Mnemonic of an official opcode.
+#[cfg_attr(not(feature = "debugger"), allow(dead_code))]
+pub(crate) fn mnemonic(opcode: u8) -> Option<&'static str> {
+    OPCODES.iter()
+        .find(|(_, opcodes)| opcodes.iter().any(|(_, other_opcode)| *other_opcode == opcode))
+        .map(|(name, _)| *name)
+}
+
+fn opcode(opcodes: &[(Mode, u8)], mode: Mode) -> Option<u8> {
+    opcodes.iter()
+        .find(|(other_mode, _)| *other_mode == mode)
+        .map(|(_, opcode)| *opcode)
+}
+
+/// An error reported while assembling a program.
+#[derive(Debug)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+// The operand of an instruction, with its expressions not evaluated yet.
+enum Operand<'a> {
+    None,
+    Accumulator,
+    Immediate(&'a str),
+    Address(&'a str, Option<char>),
+    Indirect(&'a str),
+    IndexedIndirect(&'a str),
+    IndirectIndexed(&'a str)
+}
+
+enum Statement<'a> {
+    Origin(&'a str),
+    Constant(&'a str, &'a str),
+    Bytes(Vec<&'a str>),
+    Words(Vec<&'a str>),
+    Instruction(&'static [(Mode, u8)], Operand<'a>)
+}
+
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    statement: Option<Statement<'a>>
+}
+
+/// Assemble a program.
+///
+/// This function assembles a 6507 program written with the usual syntax and
+/// returns its bytes, starting from its origin (0xF000 unless the program
+/// sets it with an `org` directive). It's meant to write the programs of unit
+/// tests and to tinker with the console, not to develop games; it supports
+/// the official instructions with all their addressing modes, labels,
+/// constants, simple expressions (additions and subtractions, `<` and `>` for
+/// the low and high bytes) and the `org`, `.byte` and `.word` directives.
+/// Comments start with a semicolon.
+///
+/// Zero page addressing is used whenever the address is known to fit in the
+/// zero page when the instruction is met (constants and labels defined
+/// earlier), absolute addressing otherwise. Moving the origin forward fills
+/// the gap with NOP instructions.
+///
+/// ```
+/// # use atari_2600::assemble;
+/// let mut rom = assemble("
+///     COLUBK = $09
+///     SWCHA = $0280
+///
+///     org $F000
+/// start:
+///     lda SWCHA
+///     sta COLUBK
+///     jmp start
+/// ")?;
+/// rom.resize(4096, 0x_EA);
+/// # Ok::<(), atari_2600::AssemblerError>(())
+/// ```
+///
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    assemble_at(DEFAULT_ORIGIN, source)
+}
+
+/// Assemble a program at a given origin.
+///
+/// This function is like `assemble()` but the program starts at the given
+/// address unless it sets its own origin.
+///
+pub fn assemble_at(origin: u16, source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let lines = source.lines()
+        .enumerate()
+        .map(|(index, line)| parse_line(index + 1, line))
+        .collect::<Result<Vec<Line>, AssemblerError>>()?;
+
+    let mut symbols = HashMap::new();
+    let modes = resolve_symbols(origin, &lines, &mut symbols)?;
+
+    emit_bytes(origin, &lines, &modes, &symbols)
+}
+
+// Compute the address of the labels and the value of the constants, and
+// choose the addressing modes of the instructions (which decide their size).
+fn resolve_symbols<'a>(origin: u16, lines: &[Line<'a>], symbols: &mut HashMap<&'a str, u16>) -> Result<Vec<Option<Mode>>, AssemblerError> {
+    let mut address = origin;
+    let mut is_empty = true;
+    let mut modes = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        let error = |message: &str| AssemblerError { line: line.number, message: message.to_string() };
+        let defined_value = |expression: &str, symbols: &HashMap<&str, u16>| {
+            evaluate(expression, symbols, line.number)?
+                .ok_or_else(|| error("symbol must be defined before it's used"))
+        };
+
+        let mut mode = None;
+        match &line.statement {
+            Some(Statement::Origin(expression)) => {
+                let value = defined_value(expression, symbols)?;
+                if !is_empty && value < address {
+                    return Err(error("origin can't go backward"));
+                }
+
+                address = value;
+            },
+            Some(Statement::Constant(name, expression)) => {
+                let value = defined_value(expression, symbols)?;
+                define_symbol(symbols, name, value, line.number)?;
+            },
+            _ => {}
+        }
+
+        if let Some(label) = line.label {
+            define_symbol(symbols, label, address, line.number)?;
+        }
+
+        let size = match &line.statement {
+            Some(Statement::Bytes(values)) => values.len() as u16,
+            Some(Statement::Words(values)) => values.len() as u16 * 2,
+            Some(Statement::Instruction(opcodes, operand)) => {
+                let is_zero_page = match operand {
+                    Operand::Address(expression, _) => {
+                        evaluate(expression, symbols, line.number)?.is_some_and(|value| value <= 0x_FF)
+                    },
+                    _ => false
+                };
+
+                let instruction_mode = select_mode(opcodes, operand, is_zero_page)
+                    .ok_or_else(|| error("invalid addressing mode"))?;
+                mode = Some(instruction_mode);
+
+                instruction_mode.size()
+            },
+            _ => 0
+        };
+
+        if size > 0 {
+            is_empty = false;
+        }
+
+        address = address.checked_add(size)
+            .ok_or_else(|| error("program goes beyond the end of the memory"))?;
+        modes.push(mode);
+    }
+
+    Ok(modes)
+}
+
+fn emit_bytes(origin: u16, lines: &[Line], modes: &[Option<Mode>], symbols: &HashMap<&str, u16>) -> Result<Vec<u8>, AssemblerError> {
+    let mut origin = origin;
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for (line, mode) in lines.iter().zip(modes.iter()) {
+        let error = |message: &str| AssemblerError { line: line.number, message: message.to_string() };
+        let word = |expression: &str| {
+            evaluate(expression, symbols, line.number)?
+                .ok_or_else(|| error("unknown symbol"))
+        };
+        let byte = |expression: &str| {
+            let value = word(expression)?;
+            if value > 0x_FF {
+                return Err(error("value doesn't fit in a byte"));
+            }
+
+            Ok(value as u8)
+        };
+
+        let address = origin.wrapping_add(bytes.len() as u16);
+        match &line.statement {
+            Some(Statement::Origin(expression)) => {
+                let value = word(expression)?;
+                if bytes.is_empty() {
+                    origin = value;
+                } else {
+                    bytes.resize((value - origin) as usize, 0x_EA);
+                }
+            },
+            Some(Statement::Bytes(values)) => {
+                for value in values {
+                    bytes.push(byte(value)?);
+                }
+            },
+            Some(Statement::Words(values)) => {
+                for value in values {
+                    bytes.extend_from_slice(&word(value)?.to_le_bytes());
+                }
+            },
+            Some(Statement::Instruction(opcodes, operand)) => {
+                let mode = mode.unwrap();
+                bytes.push(opcode(opcodes, mode).unwrap());
+
+                let expression = match operand {
+                    Operand::None | Operand::Accumulator => continue,
+                    Operand::Immediate(expression) |
+                    Operand::Address(expression, _) |
+                    Operand::Indirect(expression) |
+                    Operand::IndexedIndirect(expression) |
+                    Operand::IndirectIndexed(expression) => expression
+                };
+
+                match mode {
+                    Mode::Relative => {
+                        let offset = word(expression)? as i32 - (address as i32 + 2);
+                        if offset < i8::MIN as i32 || offset > i8::MAX as i32 {
+                            return Err(error("branch is out of range"));
+                        }
+
+                        bytes.push(offset as i8 as u8);
+                    },
+                    _ if mode.size() == 3 => bytes.extend_from_slice(&word(expression)?.to_le_bytes()),
+                    _ => bytes.push(byte(expression)?)
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn select_mode(opcodes: &[(Mode, u8)], operand: &Operand, is_zero_page: bool) -> Option<Mode> {
+    let candidates: &[Mode] = match operand {
+        Operand::None => &[Mode::Implied, Mode::Accumulator],
+        Operand::Accumulator => &[Mode::Accumulator],
+        Operand::Immediate(_) => &[Mode::Immediate],
+        Operand::Address(_, None) if is_zero_page => &[Mode::Relative, Mode::ZeroPage, Mode::Absolute],
+        Operand::Address(_, None) => &[Mode::Relative, Mode::Absolute],
+        Operand::Address(_, Some('X')) if is_zero_page => &[Mode::ZeroPageX, Mode::AbsoluteX],
+        Operand::Address(_, Some('X')) => &[Mode::AbsoluteX],
+        Operand::Address(_, _) if is_zero_page => &[Mode::ZeroPageY, Mode::AbsoluteY],
+        Operand::Address(_, _) => &[Mode::AbsoluteY],
+        Operand::Indirect(_) => &[Mode::Indirect],
+        Operand::IndexedIndirect(_) => &[Mode::IndexedIndirect],
+        Operand::IndirectIndexed(_) => &[Mode::IndirectIndexed]
+    };
+
+    candidates.iter().copied().find(|mode| opcode(opcodes, *mode).is_some())
+}
+
+fn define_symbol<'a>(symbols: &mut HashMap<&'a str, u16>, name: &'a str, value: u16, line: usize) -> Result<(), AssemblerError> {
+    if symbols.insert(name, value).is_some() {
+        return Err(AssemblerError { line, message: format!("symbol '{}' is already defined", name) });
+    }
+
+    Ok(())
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    characters.next().is_some_and(|character| character.is_ascii_alphabetic() || character == '_' || character == '.')
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '.')
+}
+
+fn parse_line(number: usize, line: &str) -> Result<Line<'_>, AssemblerError> {
+    let error = |message: &str| AssemblerError { line: number, message: message.to_string() };
+
+    let mut text = line.split(';').next().unwrap().trim();
+    let mut label = None;
+
+    if let Some(position) = text.find(':') {
+        let name = text[..position].trim();
+        if !is_identifier(name) {
+            return Err(error("invalid label"));
+        }
+
+        label = Some(name);
+        text = text[position + 1..].trim();
+    }
+
+    if text.is_empty() {
+        return Ok(Line { number, label, statement: None });
+    }
+
+    if let Some(position) = text.find('=') {
+        let name = text[..position].trim();
+        if label.is_some() || !is_identifier(name) {
+            return Err(error("invalid constant"));
+        }
+
+        let statement = Statement::Constant(name, text[position + 1..].trim());
+        return Ok(Line { number, label, statement: Some(statement) });
+    }
+
+    let (keyword, operand) = match text.find(char::is_whitespace) {
+        Some(position) => (&text[..position], text[position..].trim()),
+        None => (text, "")
+    };
+    let values = || operand.split(',').map(|value| value.trim()).collect::<Vec<&str>>();
+
+    let statement = match keyword.to_ascii_lowercase().as_str() {
+        "org" | ".org" => Statement::Origin(operand),
+        ".byte" => Statement::Bytes(values()),
+        ".word" => Statement::Words(values()),
+        _ => {
+            let opcodes = opcodes(keyword).ok_or_else(|| error("unknown instruction"))?;
+            Statement::Instruction(opcodes, parse_operand(operand).ok_or_else(|| error("invalid operand"))?)
+        }
+    };
+
+    Ok(Line { number, label, statement: Some(statement) })
+}
+
+fn parse_operand(text: &str) -> Option<Operand<'_>> {
+    let index = |text: &str| text.trim().to_ascii_uppercase();
+
+    if text.is_empty() {
+        Some(Operand::None)
+    } else if text.eq_ignore_ascii_case("A") {
+        Some(Operand::Accumulator)
+    } else if let Some(expression) = text.strip_prefix('#') {
+        Some(Operand::Immediate(expression.trim()))
+    } else if let Some(inner) = text.strip_prefix('(') {
+        // Either (address), (address,X) or (address),Y.
+        let (inner, after) = inner.split_at(inner.rfind(')')?);
+        let after = &after[1..];
+
+        match (inner.rsplit_once(','), after.trim()) {
+            (Some((expression, register)), "") if index(register) == "X" => Some(Operand::IndexedIndirect(expression.trim())),
+            (None, "") => Some(Operand::Indirect(inner.trim())),
+            (None, after) if index(after.strip_prefix(',')?) == "Y" => Some(Operand::IndirectIndexed(inner.trim())),
+            _ => None
+        }
+    } else {
+        match text.rsplit_once(',') {
+            Some((expression, register)) => match index(register).as_str() {
+                "X" => Some(Operand::Address(expression.trim(), Some('X'))),
+                "Y" => Some(Operand::Address(expression.trim(), Some('Y'))),
+                _ => None
+            },
+            None => Some(Operand::Address(text, None))
+        }
+    }
+}
+
+// Evaluate an expression; it returns nothing if it refers to a symbol that
+// isn't defined (yet).
+fn evaluate(expression: &str, symbols: &HashMap<&str, u16>, line: usize) -> Result<Option<u16>, AssemblerError> {
+    let error = |message: String| AssemblerError { line, message };
+
+    let expression = expression.trim();
+    if let Some(expression) = expression.strip_prefix('<') {
+        return Ok(evaluate(expression, symbols, line)?.map(|value| value & 0x_FF));
+    }
+    if let Some(expression) = expression.strip_prefix('>') {
+        return Ok(evaluate(expression, symbols, line)?.map(|value| value >> 8));
+    }
+
+    // Split the terms, keeping their sign.
+    let mut value = Some(0_u16);
+    let mut start = 0;
+    let mut is_negative = false;
+
+    for (position, character) in expression.char_indices().chain(std::iter::once((expression.len(), '+'))) {
+        if character != '+' && character != '-' {
+            continue
+        }
+
+        let term = expression[start..position].trim();
+        if term.is_empty() {
+            return Err(error(format!("invalid expression '{}'", expression)));
+        }
+
+        let term_value = if let Some(digits) = term.strip_prefix('$') {
+            Some(parse_number(digits, 16).ok_or_else(|| error(format!("invalid number '{}'", term)))?)
+        } else if let Some(digits) = term.strip_prefix('%') {
+            Some(parse_number(digits, 2).ok_or_else(|| error(format!("invalid number '{}'", term)))?)
+        } else if term.starts_with(|character: char| character.is_ascii_digit()) {
+            Some(parse_number(term, 10).ok_or_else(|| error(format!("invalid number '{}'", term)))?)
+        } else if is_identifier(term) {
+            symbols.get(term).copied()
+        } else {
+            return Err(error(format!("invalid expression '{}'", expression)));
+        };
+
+        value = match (value, term_value) {
+            (Some(value), Some(term_value)) if is_negative => Some(value.wrapping_sub(term_value)),
+            (Some(value), Some(term_value)) => Some(value.wrapping_add(term_value)),
+            _ => None
+        };
+
+        start = position + 1;
+        is_negative = character == '-';
+    }
+
+    Ok(value)
+}
+
+fn parse_number(digits: &str, radix: u32) -> Option<u16> {
+    u16::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let bytes = assemble("
+            ; Paint the background with the value of the port A.
+            SWCHA = $0280
+            COLUBK = $09
+
+            org $F000
+        start:
+            lda SWCHA        ; Absolute.
+            sta COLUBK       ; Zero page.
+            ldx #%0000_0011
+        loop:
+            dex
+            bne loop
+            asl
+            lsr A
+            lda (ZP_POINTER),y
+            sta (ZP_POINTER, X)
+            ldy ZP_POINTER,x
+            jmp (vector)
+            jmp start+1
+        vector: .word start, >start
+            .byte <vector, 10
+
+            ZP_POINTER = $80
+        ").unwrap();
+
+        assert_eq!(bytes, vec![
+            0x_AD, 0x_80, 0x_02,
+            0x_85, 0x_09,
+            0x_A2, 0x_03,
+            0x_CA,
+            0x_D0, 0x_FD,
+            0x_0A,
+            0x_4A,
+            // The symbol isn't defined yet; absolute addressing is used
+            // whenever it exists.
+            0x_B1, 0x_80,
+            0x_81, 0x_80,
+            0x_BC, 0x_80, 0x_00,
+            0x_6C, 0x_19, 0x_F0,
+            0x_4C, 0x_01, 0x_F0,
+            0x_00, 0x_F0, 0x_F0, 0x_00,
+            0x_19, 0x_0A
+        ]);
+
+        // Moving the origin forward fills the gap.
+        let bytes = assemble_at(0x_80, "
+            nop
+            org $84
+            .byte 1
+        ").unwrap();
+        assert_eq!(bytes, vec![0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_01]);
+
+        // The first origin just moves the program.
+        let bytes = assemble("org $FFFC\n.word $F000").unwrap();
+        assert_eq!(bytes, vec![0x_00, 0x_F0]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let line = |source: &str| assemble(source).unwrap_err().line;
+        let message = |source: &str| assemble(source).unwrap_err().message;
+
+        assert_eq!(message("nop\nfoo"), "unknown instruction");
+        assert_eq!(line("nop\nfoo"), 2);
+        assert_eq!(message("ldx $80,x"), "invalid addressing mode");
+        assert_eq!(message("jmp #1"), "invalid addressing mode");
+        assert_eq!(message("lda #$100"), "value doesn't fit in a byte");
+        assert_eq!(message("jmp nowhere"), "unknown symbol");
+        assert_eq!(message("a:\na: nop"), "symbol 'a' is already defined");
+        assert_eq!(message("nop\norg $EFFF"), "origin can't go backward");
+        assert_eq!(message("org later\nlater:"), "symbol must be defined before it's used");
+        assert_eq!(message("beq far\norg $F100\nfar:"), "branch is out of range");
+        assert_eq!(message("lda 12z"), "invalid number '12z'");
+    }
+}
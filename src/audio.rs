@@ -8,18 +8,295 @@
 
 //! Audio-related enumerations and helpers.
 //!
-//! TODO; Write the description.
+//! The TIA has two identical audio channels; each of them divides the audio
+//! clock (about 31.4 kHz, twice per scanline) by its frequency register
+//! (AUDF0/AUDF1) and uses it to clock a 4-bit pulse counter and a 5-bit noise
+//! counter that are wired according to the control register (AUDC0/AUDC1).
+//! The output of the pulse counter is scaled by the volume register
+//! (AUDV0/AUDV1) and both channels are mixed together.
 //!
-use crate::console::Console;
+//! The audio clock is split in two phases; the first one updates the frequency
+//! divider and latches the feedback of the noise counter, the second one
+//! shifts the counters and produces the sample.
+//!
+//! Stopping or restarting the audio abruptly makes the speakers pop, so the
+//! emulator ramps the gain of the samples when it's paused and resumed (see
+//! `GainEnvelope`).
+//!
+
+use std::time::Duration;
+
+use crate::state::{StateWriter, StateReader, StateError};
+
+/// Color cycles of a scanline at which the first phase of the audio clock
+/// occurs.
+pub(crate) const AUDIO_PHASE0_CYCLES: [u32; 2] = [9, 81];
+
+/// Color cycles of a scanline at which the second phase of the audio clock
+/// occurs (and a sample is produced).
+pub(crate) const AUDIO_PHASE1_CYCLES: [u32; 2] = [37, 149];
+
+/// Number of color cycles per audio sample.
+pub(crate) const COLOR_CYCLES_PER_SAMPLE: f64 = 114.0;
+
+/// An audio channel of the TIA.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub(crate) struct AudioChannel {
+    divider: u8,
+    is_clock_enabled: bool,
+    pulse_counter: u8,
+    is_pulse_counter_held: bool,
+    noise_counter: u8,
+    noise_feedback: bool,
+    noise_counter_bit4: bool
+}
+
+impl AudioChannel {
+    /// First phase of the audio clock.
+    pub(crate) fn phase0(&mut self, control: u8, frequency: u8) {
+        let control = control & 0b1111;
+        let frequency = frequency & 0b1_1111;
+
+        if self.is_clock_enabled {
+            self.noise_counter_bit4 = self.noise_counter & 0b1 != 0;
+
+            self.is_pulse_counter_held = match control & 0b11 {
+                0b10 => self.noise_counter & 0b1_1110 != 0b0_0010,
+                0b11 => !self.noise_counter_bit4,
+                _ => false
+            };
+
+            self.noise_feedback = match control & 0b11 {
+                0b00 => {
+                    (self.pulse_counter ^ self.noise_counter) & 0b1 != 0
+                        || !(self.noise_counter != 0 || self.pulse_counter != 0b1010)
+                        || control & 0b1100 == 0
+                },
+                _ => {
+                    ((self.noise_counter & 0b100 != 0) ^ (self.noise_counter & 0b1 != 0))
+                        || self.noise_counter == 0
+                }
+            };
+        }
+
+        self.is_clock_enabled = self.divider == frequency;
+        if self.divider == frequency || self.divider == 0b1_1111 {
+            self.divider = 0;
+        }
+        else {
+            self.divider += 1;
+        }
+    }
 
-fn _audio_function(_console: &Console) {
-    // TODO; To be implemented.
+    /// Second phase of the audio clock.
+    ///
+    /// This function returns the output of the channel (between 0 and 15).
+    ///
+    pub(crate) fn phase1(&mut self, control: u8, volume: u8) -> u8 {
+        let control = control & 0b1111;
+
+        if self.is_clock_enabled {
+            let pulse_feedback = match control >> 2 {
+                0b00 => {
+                    ((self.pulse_counter & 0b10 != 0) ^ (self.pulse_counter & 0b1 != 0))
+                        && self.pulse_counter != 0b1010
+                        && control & 0b11 != 0
+                },
+                0b01 => self.pulse_counter & 0b1000 == 0,
+                0b10 => !self.noise_counter_bit4,
+                _ => !(self.pulse_counter & 0b10 != 0 || self.pulse_counter & 0b1110 == 0)
+            };
+
+            self.noise_counter >>= 1;
+            if self.noise_feedback {
+                self.noise_counter |= 0b1_0000;
+            }
+
+            if !self.is_pulse_counter_held {
+                self.pulse_counter = !(self.pulse_counter >> 1) & 0b0111;
+                if pulse_feedback {
+                    self.pulse_counter |= 0b1000;
+                }
+            }
+        }
+
+        (self.pulse_counter & 0b1) * (volume & 0b1111)
+    }
+
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.divider);
+        state.write_bool(self.is_clock_enabled);
+        state.write_u8(self.pulse_counter);
+        state.write_bool(self.is_pulse_counter_held);
+        state.write_u8(self.noise_counter);
+        state.write_bool(self.noise_feedback);
+        state.write_bool(self.noise_counter_bit4);
+    }
+
+    pub(crate) fn read_state(reader: &mut StateReader) -> Result<AudioChannel, StateError> {
+        Ok(AudioChannel {
+            divider: reader.read_u8()?,
+            is_clock_enabled: reader.read_bool()?,
+            pulse_counter: reader.read_u8()?,
+            is_pulse_counter_held: reader.read_bool()?,
+            noise_counter: reader.read_u8()?,
+            noise_feedback: reader.read_bool()?,
+            noise_counter_bit4: reader.read_bool()?
+        })
+    }
+}
+
+/// Mix the outputs of the two channels into a sample between 0.0 and 1.0.
+pub(crate) fn mix_channels(left: u8, right: u8) -> f32 {
+    (left + right) as f32 / 30.0
+}
+
+/// The gain envelope applied to the audio when the emulator pauses and
+/// resumes.
+///
+/// When the emulator is paused, the audio fades out from the last sample to
+/// silence, and it fades in when the emulator resumes, instead of stopping and
+/// restarting abruptly. A few milliseconds are enough to avoid the pops; a
+/// duration of zero disables the fade.
+///
+/// See `Emulator::set_gain_envelope()`.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GainEnvelope {
+    /// Duration of the fade out when the emulator is paused.
+    pub fade_out: Duration,
+    /// Duration of the fade in when the emulator resumes.
+    pub fade_in: Duration
+}
+
+impl GainEnvelope {
+    /// An envelope that doesn't fade the audio.
+    pub fn none() -> GainEnvelope {
+        GainEnvelope {
+            fade_out: Duration::from_millis(0),
+            fade_in: Duration::from_millis(0)
+        }
+    }
+}
+
+impl Default for GainEnvelope {
+    fn default() -> Self {
+        GainEnvelope {
+            fade_out: Duration::from_millis(5),
+            fade_in: Duration::from_millis(5)
+        }
+    }
+}
+
+/// A linear ramp of the gain over a number of samples.
+///
+/// The gain of the last sample of the ramp is the target gain, and it stays
+/// there once the ramp is finished.
+///
+#[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+pub(crate) struct GainRamp {
+    from: f32,
+    to: f32,
+    length: usize,
+    position: usize
+}
+
+#[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+impl GainRamp {
+    /// Create a ramp of a duration at the given sample rate.
+    pub(crate) fn new(from: f32, to: f32, duration: Duration, sample_rate: f64) -> GainRamp {
+        GainRamp {
+            from,
+            to,
+            length: (duration.as_secs_f64() * sample_rate).round() as usize,
+            position: 0
+        }
+    }
+
+    /// Number of samples of the ramp.
+    pub(crate) fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Check whether the ramp reached the target gain.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.position >= self.length
+    }
+
+    /// Gain of the next sample.
+    pub(crate) fn next_gain(&mut self) -> f32 {
+        if self.is_finished() {
+            return self.to;
+        }
+
+        self.position += 1;
+        self.from + (self.to - self.from) * self.position as f32 / self.length as f32
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    // Run a channel for a number of audio clocks and collect its output.
+    fn run_channel(control: u8, frequency: u8, count: usize) -> Vec<u8> {
+        let mut channel = AudioChannel::default();
+
+        (0..count)
+            .map(|_| {
+                channel.phase0(control, frequency);
+                channel.phase1(control, 15)
+            })
+            .collect()
+    }
+
+    fn period(output: &[u8]) -> usize {
+        (1..output.len() / 2)
+            .find(|period| output.iter().zip(output[*period..].iter()).all(|(a, b)| a == b))
+            .unwrap()
+    }
 
     #[test]
     fn test_audio() {
+        // The pure tones are divided by 2, 6, 31 and 93 audio clocks, times the
+        // frequency divider.
+        assert_eq!(period(&run_channel(4, 0, 400)[100..]), 2);
+        assert_eq!(period(&run_channel(4, 3, 400)[100..]), 8);
+        assert_eq!(period(&run_channel(12, 0, 400)[100..]), 6);
+        assert_eq!(period(&run_channel(6, 0, 400)[100..]), 31);
+        assert_eq!(period(&run_channel(14, 0, 1000)[100..]), 93);
+
+        // The 4-bit and 5-bit polynomials.
+        assert_eq!(period(&run_channel(1, 0, 400)[100..]), 15);
+        assert_eq!(period(&run_channel(9, 0, 400)[100..]), 31);
+
+        // The output is scaled by the volume.
+        let mut channel = AudioChannel::default();
+        let output = (0..10)
+            .map(|_| {
+                channel.phase0(4, 0);
+                channel.phase1(4, 8)
+            })
+            .collect::<Vec<_>>();
+        assert!(output.iter().all(|value| *value == 0 || *value == 8));
+        assert!(output.contains(&8));
+
+        assert_eq!(mix_channels(15, 15), 1.0);
+        assert_eq!(mix_channels(0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_gain_ramp() {
+        let mut ramp = GainRamp::new(1.0, 0.0, Duration::from_millis(2), 2000.0);
+        assert_eq!(ramp.len(), 4);
+
+        let gains = (0..6).map(|_| ramp.next_gain()).collect::<Vec<_>>();
+        assert_eq!(gains, vec![0.75, 0.5, 0.25, 0.0, 0.0, 0.0]);
+        assert!(ramp.is_finished());
+
+        // An empty ramp is already finished.
+        let mut ramp = GainRamp::new(0.0, 1.0, Duration::from_millis(0), 2000.0);
+        assert!(ramp.is_finished());
+        assert_eq!(ramp.next_gain(), 1.0);
     }
 }
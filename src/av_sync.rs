@@ -0,0 +1,249 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+// Maximum deviation of the resampling ratio from 1.0 when correcting the
+// drift; pitch changes below 0.5% go unnoticed.
+const MAX_CORRECTION: f64 = 0.005;
+
+// The time over which the correction aims to absorb the drift (in seconds).
+const CONVERGENCE_TIME: f64 = 1.0;
+
+/// A monitor of the drift between the audio and the video.
+///
+/// The frontend reports the frames it displays and the audio samples it
+/// queues, and the monitor tells how far the audio is ahead of the video (in
+/// milliseconds); the drift accumulates when the frames aren't all of the
+/// same length, when frames are dropped or repeated, or when the simulation
+/// runs at the refresh rate of the display rather than at the frame rate of
+/// the console (see `RealTimePacer`).
+///
+/// In correction mode, the samples go through the monitor, which resamples
+/// them slightly (by at most 0.5%) so the drift converges to zero.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{AvSyncMonitor, Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn play_samples(_samples: &[f32]) {}
+/// let mut monitor = AvSyncMonitor::new(console.tv_standard().frame_rate(), console.audio_sample_rate());
+/// monitor.set_correction(true);
+///
+/// loop {
+///     console.run_frame();
+///     monitor.record_frame();
+///     play_samples(&monitor.process_audio(&console.take_audio_samples()));
+///
+///     if monitor.drift().abs() > 100.0 {
+///         eprintln!("audio is {:.0} ms off", monitor.drift());
+///     }
+/// #   break;
+/// }
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct AvSyncMonitor {
+    frame_rate: f64,
+    sample_rate: f64,
+    frames: u64,
+    samples: u64,
+    is_correcting: bool,
+
+    // The position of the resampler in the input (relative to the first
+    // sample of the next chunk, it's between the last sample of the previous
+    // chunk and the first one of the next chunk when negative), and the last
+    // sample of the previous chunk.
+    position: f64,
+    previous_sample: f32
+}
+
+impl AvSyncMonitor {
+    /// Create a monitor.
+    ///
+    /// This function creates a monitor for frames displayed at the given
+    /// frame rate, and audio played at the given sample rate (both in Hertz).
+    ///
+    pub fn new(frame_rate: f64, sample_rate: f64) -> AvSyncMonitor {
+        AvSyncMonitor {
+            frame_rate,
+            sample_rate,
+            frames: 0,
+            samples: 0,
+            is_correcting: false,
+            position: 0.0,
+            previous_sample: 0.0
+        }
+    }
+
+    /// Record a displayed frame.
+    pub fn record_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Record queued audio samples.
+    ///
+    /// This function is for the samples that don't go through
+    /// `process_audio()`.
+    ///
+    pub fn record_samples(&mut self, count: usize) {
+        self.samples += count as u64;
+    }
+
+    /// Cumulative drift between the audio and the video (in milliseconds).
+    ///
+    /// This function returns the difference between the duration of the
+    /// recorded audio and the duration of the recorded frames; it's positive
+    /// when the audio is ahead of the video.
+    ///
+    pub fn drift(&self) -> f64 {
+        let audio_time = self.samples as f64 / self.sample_rate;
+        let video_time = self.frames as f64 / self.frame_rate;
+
+        (audio_time - video_time) * 1000.0
+    }
+
+    /// Enable or disable the correction mode.
+    pub fn set_correction(&mut self, is_enabled: bool) {
+        self.is_correcting = is_enabled;
+    }
+
+    /// Check whether the correction mode is enabled.
+    pub fn is_correcting(&self) -> bool {
+        self.is_correcting
+    }
+
+    /// Resampling ratio of the correction.
+    ///
+    /// This function returns the number of output samples per input sample
+    /// the correction currently applies; it's lower than 1.0 when the audio
+    /// is ahead, and 1.0 when the correction mode is disabled.
+    ///
+    pub fn correction_ratio(&self) -> f64 {
+        self.ratio(self.drift())
+    }
+
+    fn ratio(&self, drift: f64) -> f64 {
+        match self.is_correcting {
+            true => {
+                let correction = drift / 1000.0 / CONVERGENCE_TIME;
+                1.0 - correction.clamp(-MAX_CORRECTION, MAX_CORRECTION)
+            },
+            false => 1.0
+        }
+    }
+
+    /// Process audio samples.
+    ///
+    /// This function records the samples and returns them, resampled with the
+    /// ratio of the correction if the correction mode is enabled. The samples
+    /// are interpolated linearly, and continuously from a call to another.
+    ///
+    pub fn process_audio(&mut self, samples: &[f32]) -> Vec<f32> {
+        if samples.is_empty() {
+            return Vec::new();
+        }
+
+        // The correction aims at the drift once these samples are played, as
+        // they usually come along with a frame that was already recorded.
+        let output = match self.is_correcting {
+            true => {
+                let drift = self.drift() + samples.len() as f64 / self.sample_rate * 1000.0;
+                self.resample(samples, self.ratio(drift))
+            },
+            false => samples.to_vec()
+        };
+
+        self.previous_sample = samples[samples.len() - 1];
+        self.record_samples(output.len());
+
+        output
+    }
+
+    /// Forget the recorded frames and samples.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.samples = 0;
+        self.position = 0.0;
+    }
+
+    fn resample(&mut self, samples: &[f32], ratio: f64) -> Vec<f32> {
+        let step = 1.0 / ratio;
+        let last_index = (samples.len() - 1) as f64;
+        let previous_sample = self.previous_sample;
+        let sample = |index: isize| match index {
+            -1 => previous_sample,
+            index => samples[index as usize]
+        };
+
+        let mut output = Vec::with_capacity((samples.len() as f64 * ratio).ceil() as usize + 1);
+        while self.position <= last_index {
+            let index = self.position.floor();
+            let fraction = (self.position - index) as f32;
+
+            let value = match fraction > 0.0 {
+                true => sample(index as isize) * (1.0 - fraction) + sample(index as isize + 1) * fraction,
+                false => sample(index as isize)
+            };
+            output.push(value);
+
+            self.position += step;
+        }
+        self.position -= samples.len() as f64;
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drift() {
+        let mut monitor = AvSyncMonitor::new(60.0, 30_000.0);
+        assert_eq!(monitor.drift(), 0.0);
+
+        // A second of video with 1.01 second of audio.
+        for _ in 0..60 {
+            monitor.record_frame();
+            assert_eq!(monitor.process_audio(&[0.5; 505]).len(), 505);
+        }
+        assert!((monitor.drift() - 10.0).abs() < 1e-9);
+        assert_eq!(monitor.correction_ratio(), 1.0);
+
+        monitor.reset();
+        assert_eq!(monitor.drift(), 0.0);
+    }
+
+    #[test]
+    fn test_correction() {
+        let mut monitor = AvSyncMonitor::new(60.0, 30_000.0);
+        monitor.set_correction(true);
+
+        // The audio is generated 0.2% too fast; the correction keeps the
+        // drift bounded.
+        let samples = (0..501).map(|index| index as f32 / 501.0).collect::<Vec<_>>();
+        for _ in 0..600 {
+            monitor.record_frame();
+            monitor.process_audio(&samples);
+        }
+        assert!(monitor.drift().abs() < 5.0, "{}", monitor.drift());
+        assert!(monitor.correction_ratio() < 1.0);
+
+        // Without a drift, the samples are unchanged.
+        let mut monitor = AvSyncMonitor::new(60.0, 30_000.0);
+        monitor.set_correction(true);
+        monitor.record_frame();
+        assert_eq!(monitor.process_audio(&samples[..500]), &samples[..500]);
+
+        // The interpolation continues across chunks.
+        let mut monitor = AvSyncMonitor::new(60.0, 30_000.0);
+        assert_eq!(monitor.resample(&[0.0, 1.0], 2.0), vec![0.0, 0.5, 1.0]);
+        monitor.previous_sample = 1.0;
+        assert_eq!(monitor.resample(&[0.0, 1.0], 2.0), vec![0.5, 0.0, 0.5, 1.0]);
+    }
+}
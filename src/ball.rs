@@ -11,53 +11,65 @@
 //!
 //! This module defines something that is to be described.
 //!
-use crate::location::ENABL;
-use crate::console::Console;
+use crate::location::{ENABL, VDELBL, CTRLPF};
+use crate::tia::Tia;
 
-enum BallSize {
-    One,
-    Two,
-    Four,
-    Eight
+pub(crate) fn is_ball_delayed(tia: &Tia) -> bool {
+    //   27      VDELBL  .......1  vertical delay ball
+    tia.register(VDELBL) & 0b0000_0001 != 0
 }
 
-fn _is_ball_enabled(console: &Console) -> bool {
+pub(crate) fn is_ball_enabled(tia: &Tia) -> bool {
     //   1F      ENABL   ......1.  graphics (enable) ball
-    *console.memory(ENABL) & 0b0000_00010 > 0
-}
+    //
+    // Like the players, the ball has an old copy of ENABL (latched when GRP1
+    // is written) which is used instead when vertical delay is enabled.
+    let value = match is_ball_delayed(tia) {
+        true  => tia.old_ball_enable,
+        false => tia.register(ENABL)
+    };
 
-// fn ball_size(console: &Console) -> BallSize {
-//     // 0Ah - CTRLPF - Control Playfield and Ball size
+    value & 0b0000_0010 != 0
+}
 
-//     // Bit  Expl.
-//     // 0    Playfield Reflection     (0=Normal, 1=Mirror right half)
-//     // 1    Playfield Color          (0=Normal, 1=Score Mode, only if Bit2=0)
-//     // 2    Playfield/Ball Priority  (0=Normal, 1=Above Players/Missiles)
-//     // 3    Not used
-//     // 4-5  Ball size                (0..3 = 1,2,4,8 pixels width)
-//     // 6-7  Not used
-//     let value = *console.memory(CTRLPF) & 0b0011_0000 >> 4;
+pub(crate) fn ball_width(tia: &Tia) -> u32 {
+    // 0Ah - CTRLPF - Control Playfield and Ball size
 
-//     match value {
-//         0 => BallSize::One,
-//         1 => BallSize::Two,
-//         2 => BallSize::Four,
-//         3 => BallSize::Eight
-//     }
-// }
+    // Bit  Expl.
+    // 0    Playfield Reflection     (0=Normal, 1=Mirror right half)
+    // 1    Playfield Color          (0=Normal, 1=Score Mode, only if Bit2=0)
+    // 2    Playfield/Ball Priority  (0=Normal, 1=Above Players/Missiles)
+    // 3    Not used
+    // 4-5  Ball size                (0..3 = 1,2,4,8 pixels width)
+    // 6-7  Not used
+    1 << ((tia.register(CTRLPF) & 0b0011_0000) >> 4)
+}
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::location::GRP1;
 
     #[test]
     fn test_ball() {
-        // TODO; To be implemented.
-    }
+        let mut console = Console::new(Cartridge::new(vec![]));
+        *console.memory_mut(VDELBL) = 0b0000_0001;
 
-    // #[test]
-    // fn test_is_ball_enabled() {
-    //     // assert_eq!(is_missile0_enabled(console: &Console))
-    // }
+        // The old copy of ENABL is only latched when GRP1 is written.
+        *console.memory_mut(ENABL) = 0b0000_0010;
+        assert!(!is_ball_enabled(&console.tia));
+
+        *console.memory_mut(GRP1) = 0b0000_0000;
+        assert!(is_ball_enabled(&console.tia));
+
+        *console.memory_mut(ENABL) = 0b0000_0000;
+        assert!(is_ball_enabled(&console.tia));
+
+        *console.memory_mut(VDELBL) = 0b0000_0000;
+        assert!(!is_ball_enabled(&console.tia));
+    }
 
     // #[test]
     // fn test_ball_size() {
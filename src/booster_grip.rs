@@ -0,0 +1,193 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::Console;
+use crate::Port;
+use crate::Controller;
+use crate::state::{StateWriter, StateError};
+use crate::Joystick;
+use crate::joystick::Direction;
+
+/// The Booster Grip controller.
+///
+/// The Booster Grip is an adapter that is placed on top of a standard
+/// joystick and adds two extra buttons, the 'trigger' and the 'booster'. The
+/// joystick works as usual and the extra buttons are read from the pot inputs
+/// of the port; INPT0 (booster) and INPT1 (trigger) for the left controller,
+/// and INPT2 (booster) and INPT3 (trigger) for the right controller. Unlike the
+/// joystick button, they read high when they're pressed.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{BoosterGrip, Cartridge, Console, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut booster_grip = BoosterGrip::new();
+/// booster_grip.press_trigger();
+///
+/// console.plug_controller(Port::Left, Box::new(booster_grip));
+/// ```
+///
+pub struct BoosterGrip {
+    joystick: Joystick,
+    booster: bool,
+    trigger: bool
+}
+
+impl BoosterGrip {
+    /// Create a Booster Grip.
+    ///
+    /// The Booster Grip is created with its stick centered and all its buttons
+    /// released.
+    ///
+    pub fn new() -> BoosterGrip {
+        BoosterGrip {
+            joystick: Joystick::new(),
+            booster: false,
+            trigger: false
+        }
+    }
+
+    /// Press a direction of the joystick.
+    pub fn press_direction(&mut self, direction: Direction) {
+        self.joystick.press_direction(direction);
+    }
+
+    /// Release a direction of the joystick.
+    pub fn release_direction(&mut self, direction: Direction) {
+        self.joystick.release_direction(direction);
+    }
+
+    /// Press the button of the joystick.
+    pub fn press_button(&mut self) {
+        self.joystick.press_button();
+    }
+
+    /// Release the button of the joystick.
+    pub fn release_button(&mut self) {
+        self.joystick.release_button();
+    }
+
+    /// Press the booster button.
+    pub fn press_booster(&mut self) {
+        self.booster = true;
+    }
+
+    /// Release the booster button.
+    pub fn release_booster(&mut self) {
+        self.booster = false;
+    }
+
+    /// Press the trigger button.
+    pub fn press_trigger(&mut self) {
+        self.trigger = true;
+    }
+
+    /// Release the trigger button.
+    pub fn release_trigger(&mut self) {
+        self.trigger = false;
+    }
+
+    /// Underlying joystick.
+    ///
+    /// This function returns the joystick the Booster Grip is placed on, to
+    /// query its state.
+    ///
+    pub fn joystick(&self) -> &Joystick {
+        &self.joystick
+    }
+}
+
+impl Default for BoosterGrip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controller for BoosterGrip {
+    fn plugged(&mut self, port: Port) {
+        self.joystick.plugged(port);
+    }
+
+    fn unplugged(&mut self) {
+        self.joystick.unplugged();
+    }
+
+    fn apply(&mut self, console: &mut Console) {
+        self.joystick.apply(console);
+
+        let (booster_input, trigger_input) = match self.joystick.port() {
+            Port::Left => (0, 1),
+            Port::Right => (2, 3)
+        };
+
+        // A pressed button has no resistance and charges the capacitor
+        // immediately, a released button never charges it.
+        for (input, pressed) in [(booster_input, self.booster), (trigger_input, self.trigger)] {
+            if pressed {
+                console.set_input_charge_time(input, Some(0));
+            }
+            else {
+                console.set_input_charge_time(input, None);
+                console.set_input_level(input, false);
+            }
+        }
+    }
+
+    fn save_state(&self) -> StateWriter {
+        let mut state = StateWriter::new();
+        state.write_state(&self.joystick.save_state());
+        state.write_bool(self.booster);
+        state.write_bool(self.trigger);
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        self.joystick.load_state(&reader.read_state()?)?;
+        self.booster = reader.read_bool()?;
+        self.trigger = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    #[test]
+    fn test_booster_grip() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        console.plug_controller(Port::Right, Box::new(BoosterGrip::new()));
+
+        console.controller_mut::<BoosterGrip>(Port::Right).unwrap().press_trigger();
+        console.execute_cycle();
+        assert!(!console.input_level(2));
+        assert!(console.input_level(3));
+
+        let mut booster_grip = console.controller_mut::<BoosterGrip>(Port::Right).unwrap();
+        booster_grip.press_booster();
+        booster_grip.release_trigger();
+        drop(booster_grip);
+        console.execute_cycle();
+        assert!(console.input_level(2));
+        assert!(!console.input_level(3));
+
+        // The extra buttons don't interfere with the joystick.
+        let mut booster_grip = console.controller_mut::<BoosterGrip>(Port::Right).unwrap();
+        booster_grip.press_button();
+        booster_grip.press_direction(Direction::Left);
+        assert!(booster_grip.joystick().is_button_pressed());
+        drop(booster_grip);
+        assert!(!console.input_level(5));
+        assert!(!console.port_a_pin(2));
+    }
+}
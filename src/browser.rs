@@ -0,0 +1,263 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+use crate::database::Database;
+use crate::frame::Frame;
+use crate::menu::MenuInput;
+use crate::overlay::{draw_text, CHARACTER_WIDTH, LINE_HEIGHT};
+
+/// Extensions of the files listed by the ROM browser (in lowercase).
+pub const ROM_EXTENSIONS: [&str; 3] = ["a26", "bin", "rom"];
+
+// The file of the browsed directory where the last selection is remembered.
+const LAST_SELECTION_FILE: &str = ".last-rom";
+
+// The first line of the list and the number of lines that fit in a frame.
+const LIST_TOP: usize = 20;
+const VISIBLE_LINES: usize = (Frame::HEIGHT - LIST_TOP) / (LINE_HEIGHT + 2);
+
+/// A ROM listed by the ROM browser.
+///
+/// The name is the one of the database entry of the game if it's known,
+/// otherwise it's the name of the file (without its extension).
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct BrowserEntry {
+    pub path: PathBuf,
+    pub name: String
+}
+
+/// A browser of the ROMs of a directory.
+///
+/// The browser lists the ROM files of a directory (see `ROM_EXTENSIONS`),
+/// sorted by name, and lets the user pick one with the menu inputs; up and
+/// down move the selection, left and right move it by a page, and select
+/// picks the ROM. The last picked ROM is remembered in the directory and
+/// selected again the next time the directory is browsed.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Database, Emulator, Frame, MenuInput, RomBrowser, TEST_ROM};
+/// # fn read_input() -> MenuInput { MenuInput::Select }
+/// # fn blank_frame() -> Frame { Console::new(Cartridge::new(TEST_ROM.to_vec())).frame() }
+/// # fn display_frame(_frame: &Frame) {}
+/// # fn main() -> std::io::Result<()> {
+/// # let database = Database::builtin();
+/// let mut browser = RomBrowser::open("roms", Some(&database))?;
+/// # let _emulator =
+/// loop {
+///     if let Some(path) = browser.handle_input(read_input()) {
+///         break Emulator::new(Console::new(Cartridge::from_file(path)?));
+///     }
+///
+///     let mut frame = blank_frame();
+///     browser.render(&mut frame);
+///     display_frame(&frame);
+/// };
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct RomBrowser {
+    directory: PathBuf,
+    entries: Vec<BrowserEntry>,
+    selected: usize
+}
+
+impl RomBrowser {
+    /// Browse a directory.
+    ///
+    /// This function lists the ROMs of the directory, naming them with the
+    /// database if any, and selects the last picked one. It fails if the
+    /// directory can't be read; the ROMs that can't be read are listed with
+    /// the name of their file.
+    ///
+    pub fn open<P: AsRef<Path>>(directory: P, database: Option<&Database>) -> io::Result<RomBrowser> {
+        let directory = directory.as_ref().to_path_buf();
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            let is_rom = path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()));
+
+            if !is_rom || !path.is_file() {
+                continue;
+            }
+
+            let file_name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let name = database
+                .and_then(|database| {
+                    let cartridge = Cartridge::from_file(&path).ok()?;
+                    database.lookup(&cartridge).map(|entry| entry.name.clone())
+                })
+                .unwrap_or(file_name);
+
+            entries.push(BrowserEntry { path, name });
+        }
+
+        entries.sort_by_key(|entry| entry.name.to_ascii_lowercase());
+
+        let last_selection = fs::read_to_string(directory.join(LAST_SELECTION_FILE)).ok();
+        let selected = last_selection
+            .and_then(|file_name| {
+                entries.iter().position(|entry| entry.path.file_name().is_some_and(|name| name == file_name.trim()))
+            })
+            .unwrap_or(0);
+
+        Ok(RomBrowser {
+            directory,
+            entries,
+            selected
+        })
+    }
+
+    /// Browsed directory.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Listed ROMs.
+    pub fn entries(&self) -> &[BrowserEntry] {
+        &self.entries
+    }
+
+    /// Index of the selected ROM.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Navigate the list.
+    ///
+    /// This function moves the selection or picks the selected ROM, depending
+    /// on the input, and returns the path of the picked ROM, if any. The
+    /// selection wraps around with up and down, and stops at the ends of the
+    /// list with left and right. Picking a ROM remembers it (if the directory
+    /// is writable); going back is left to the frontend.
+    ///
+    pub fn handle_input(&mut self, input: MenuInput) -> Option<PathBuf> {
+        let count = self.entries.len();
+        if count == 0 {
+            return None;
+        }
+
+        match input {
+            MenuInput::Up => self.selected = (self.selected + count - 1) % count,
+            MenuInput::Down => self.selected = (self.selected + 1) % count,
+            MenuInput::Left => self.selected = self.selected.saturating_sub(VISIBLE_LINES),
+            MenuInput::Right => self.selected = (self.selected + VISIBLE_LINES).min(count - 1),
+            MenuInput::Select => {
+                let path = self.entries[self.selected].path.clone();
+                if let Some(file_name) = path.file_name() {
+                    let _ = fs::write(self.directory.join(LAST_SELECTION_FILE), file_name.to_string_lossy().as_bytes());
+                }
+
+                return Some(path);
+            },
+            MenuInput::Back => ()
+        }
+
+        None
+    }
+
+    /// Draw the list over a frame.
+    ///
+    /// This function draws the page of the list where the selection is; the
+    /// names that don't fit are cut.
+    ///
+    pub fn render(&self, frame: &mut Frame) {
+        let white = (255, 255, 255);
+        let gray = (160, 160, 160);
+
+        draw_text(frame, 8, 8, &format!("ROMS ({})", self.entries.len()), white);
+        if self.entries.is_empty() {
+            draw_text(frame, 8, LIST_TOP, "NO ROMS FOUND", gray);
+            return;
+        }
+
+        let max_characters = (Frame::WIDTH - 8) / CHARACTER_WIDTH - 2;
+        let first = self.selected / VISIBLE_LINES * VISIBLE_LINES;
+        let entries = self.entries.iter().enumerate().skip(first).take(VISIBLE_LINES);
+
+        for (line, (index, entry)) in entries.enumerate() {
+            let name: String = entry.name.chars().take(max_characters).collect();
+
+            let y = LIST_TOP + line * (LINE_HEIGHT + 2);
+            match index == self.selected {
+                true  => draw_text(frame, 8, y, &format!("> {}", name), white),
+                false => draw_text(frame, 8, y, &format!("  {}", name), gray)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::console::Console;
+    use crate::database::{RomEntry, ControllerKind};
+
+    #[test]
+    fn test_rom_browser() {
+        let directory = std::env::temp_dir().join(format!("atari-2600-browser-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        for (index, file_name) in ["zaxxon.bin", "Adventure.A26", "combat.rom", "notes.txt"].iter().enumerate() {
+            fs::write(directory.join(file_name), vec![index as u8; 4096]).unwrap();
+        }
+
+        // The ROMs are sorted by name, with the names of the database.
+        let mut database = Database::new();
+        database.insert(RomEntry {
+            md5: Cartridge::new(vec![0; 4096]).md5(),
+            name: "Boxing".to_string(),
+            manufacturer: "Activision".to_string(),
+            left_controller: ControllerKind::Joystick,
+            right_controller: ControllerKind::Joystick,
+            variant: None,
+            paddle_calibration: None
+        });
+
+        let mut browser = RomBrowser::open(&directory, Some(&database)).unwrap();
+        let names = browser.entries().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Adventure", "Boxing", "combat"]);
+        assert_eq!(browser.selected(), 0);
+
+        // The selection wraps around with up and down, and stops at the ends
+        // with left and right.
+        assert_eq!(browser.handle_input(MenuInput::Up), None);
+        assert_eq!(browser.selected(), 2);
+        browser.handle_input(MenuInput::Right);
+        assert_eq!(browser.selected(), 2);
+        browser.handle_input(MenuInput::Left);
+        assert_eq!(browser.selected(), 0);
+        browser.handle_input(MenuInput::Down);
+        assert_eq!(browser.handle_input(MenuInput::Select), Some(directory.join("zaxxon.bin")));
+
+        // The picked ROM is selected again.
+        let browser = RomBrowser::open(&directory, None).unwrap();
+        assert_eq!(browser.entries()[2].name, "zaxxon");
+        assert_eq!(browser.selected(), 2);
+
+        let console = Console::new(Cartridge::new(vec![]));
+        let mut frame = console.frame();
+        browser.render(&mut frame);
+        assert_eq!(frame.pixel(8, LIST_TOP + 2 * (LINE_HEIGHT + 2)), (255, 255, 255));
+
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(RomBrowser::open(&directory, None).is_err());
+    }
+}
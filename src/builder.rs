@@ -0,0 +1,315 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::cartridge::Cartridge;
+use crate::console::{Console, Port, ConsoleVariant};
+use crate::database::{Database, ControllerKind};
+use crate::paddle::{Paddle, PaddleCalibration};
+
+/// A builder of consoles.
+///
+/// This structure creates a console that is ready to play the game of a
+/// cartridge. If a ROM database is given and it knows the game, the
+/// controllers the game expects are plugged automatically (a trackball or the
+/// right mouse for instance); otherwise, joysticks are plugged. Each of them
+/// can be overridden manually. Likewise, the variant of the console is the one
+/// the database requires for the game, or the original one, and the paddles
+/// are calibrated for the game if the database knows how.
+///
+/// The random number generator of the console can be seeded, and the RAM can
+/// be randomized at power-on (from that generator), so entire runs are
+/// reproducible from a single seed.
+///
+/// Games usually draw garbage for a few frames after power-on, while they
+/// clear the memory and set up the TIA; the console can run a number of
+/// warm-up frames silently when it's built, with the controllers released, so
+/// the first frame the frontend gets is the title screen.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, ConsoleBuilder, ControllerKind, Database, TrackballMode, TEST_ROM};
+/// # let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// # let database = Database::builtin();
+/// let console = ConsoleBuilder::new(cartridge)
+///     .database(&database)
+///     .left_controller(ControllerKind::Trackball(TrackballMode::AtariMouse))
+///     .build();
+/// ```
+///
+pub struct ConsoleBuilder<'a> {
+    cartridge: Cartridge,
+    database: Option<&'a Database>,
+    left_controller: Option<ControllerKind>,
+    right_controller: Option<ControllerKind>,
+    variant: Option<ConsoleVariant>,
+    paddle_calibration: Option<PaddleCalibration>,
+    seed: u64,
+    is_ram_random: bool,
+    warm_up_frames: u32
+}
+
+impl<'a> ConsoleBuilder<'a> {
+    /// Create a builder.
+    ///
+    /// This function creates a builder for a console with the given
+    /// cartridge.
+    ///
+    pub fn new(cartridge: Cartridge) -> ConsoleBuilder<'a> {
+        ConsoleBuilder {
+            cartridge,
+            database: None,
+            left_controller: None,
+            right_controller: None,
+            variant: None,
+            paddle_calibration: None,
+            seed: 0,
+            is_ram_random: false,
+            warm_up_frames: 0
+        }
+    }
+
+    /// Use a ROM database.
+    ///
+    /// This function sets the database used to identify the game and plug the
+    /// controllers it expects.
+    ///
+    pub fn database(mut self, database: &'a Database) -> ConsoleBuilder<'a> {
+        self.database = Some(database);
+        self
+    }
+
+    /// Override the left controller.
+    pub fn left_controller(mut self, controller: ControllerKind) -> ConsoleBuilder<'a> {
+        self.left_controller = Some(controller);
+        self
+    }
+
+    /// Override the right controller.
+    pub fn right_controller(mut self, controller: ControllerKind) -> ConsoleBuilder<'a> {
+        self.right_controller = Some(controller);
+        self
+    }
+
+    /// Override the variant of the console.
+    pub fn variant(mut self, variant: ConsoleVariant) -> ConsoleBuilder<'a> {
+        self.variant = Some(variant);
+        self
+    }
+
+    /// Override the calibration of the paddles.
+    pub fn paddle_calibration(mut self, calibration: PaddleCalibration) -> ConsoleBuilder<'a> {
+        self.paddle_calibration = Some(calibration);
+        self
+    }
+
+    /// Seed the random number generator of the console.
+    pub fn seed(mut self, seed: u64) -> ConsoleBuilder<'a> {
+        self.seed = seed;
+        self
+    }
+
+    /// Randomize the content of the RAM at power-on.
+    pub fn random_ram(mut self) -> ConsoleBuilder<'a> {
+        self.is_ram_random = true;
+        self
+    }
+
+    /// Run frames before the console is handed over.
+    ///
+    /// This function sets the number of frames the console runs when it's
+    /// built, as fast as possible; their audio is dropped, and the
+    /// controllers aren't touched in the meantime. It's meant for screenshot
+    /// generation and the resets of learning environments.
+    ///
+    pub fn warm_up_frames(mut self, frames: u32) -> ConsoleBuilder<'a> {
+        self.warm_up_frames = frames;
+        self
+    }
+
+    /// Build the console.
+    ///
+    /// This function creates the console, sets its variant (which also sets
+    /// its switches, see `Console::set_variant()`), plugs the controllers,
+    /// and runs the warm-up frames, if any.
+    ///
+    pub fn build(self) -> Console {
+        let entry = self.database.and_then(|database| database.lookup(&self.cartridge));
+
+        let left_controller = self.left_controller
+            .or_else(|| entry.map(|entry| entry.left_controller))
+            .unwrap_or(ControllerKind::Joystick);
+        let right_controller = self.right_controller
+            .or_else(|| entry.map(|entry| entry.right_controller))
+            .unwrap_or(ControllerKind::Joystick);
+        let variant = self.variant
+            .or_else(|| entry.and_then(|entry| entry.variant))
+            .unwrap_or_default();
+        let paddle_calibration = self.paddle_calibration
+            .or_else(|| entry.and_then(|entry| entry.paddle_calibration))
+            .unwrap_or_default();
+
+        let mut console = Console::new(self.cartridge);
+        console.set_variant(variant);
+        console.set_seed(self.seed);
+        if self.is_ram_random {
+            console.randomize_ram();
+        }
+
+        console.plug_controller(Port::Left, left_controller.create());
+        console.plug_controller(Port::Right, right_controller.create());
+        for port in [Port::Left, Port::Right] {
+            if let Some(mut paddles) = console.controller_mut::<Paddle>(port) {
+                paddles.set_calibration(paddle_calibration);
+            }
+        }
+
+        for _ in 0..self.warm_up_frames {
+            console.run_frame();
+        }
+        console.take_audio_samples();
+
+        console
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::RomEntry;
+    use crate::console::TiaRevision;
+    use crate::joystick::Joystick;
+    use crate::trackball::{Trackball, TrackballMode};
+    use crate::fixtures;
+
+    #[test]
+    fn test_console_builder() {
+        let cartridge = Cartridge::new(vec![0x_4C, 0x_00, 0x_F0]);
+
+        let mut database = Database::new();
+        database.insert(RomEntry {
+            md5: cartridge.md5(),
+            name: String::from("Test Game"),
+            manufacturer: String::from("Nobody"),
+            left_controller: ControllerKind::Trackball(TrackballMode::Trackball),
+            right_controller: ControllerKind::Joystick,
+            variant: Some(ConsoleVariant::Junior),
+            paddle_calibration: None
+        });
+
+        // Without database, joysticks are plugged.
+        let mut console = ConsoleBuilder::new(Cartridge::new(cartridge.memory.clone())).build();
+        assert!(console.controller_mut::<Joystick>(Port::Left).is_some());
+        assert!(console.controller_mut::<Joystick>(Port::Right).is_some());
+        assert_eq!(console.variant(), ConsoleVariant::Original);
+
+        // The switches are in their default positions.
+        assert_eq!(console.memory(0x_0282) & 0b1100_1011, 0b0000_1011);
+
+        // The database selects the trackball and the 2600 Jr.
+        let mut console = ConsoleBuilder::new(Cartridge::new(cartridge.memory.clone()))
+            .database(&database)
+            .build();
+        assert_eq!(console.controller_mut::<Trackball>(Port::Left).unwrap().mode(), TrackballMode::Trackball);
+        assert!(console.controller_mut::<Joystick>(Port::Right).is_some());
+        assert_eq!(console.variant(), ConsoleVariant::Junior);
+        assert_eq!(console.tia_revision(), TiaRevision::Late);
+
+        // But it can be overridden.
+        let mut console = ConsoleBuilder::new(cartridge)
+            .database(&database)
+            .left_controller(ControllerKind::Trackball(TrackballMode::AtariMouse))
+            .variant(ConsoleVariant::Sears)
+            .build();
+        assert_eq!(console.controller_mut::<Trackball>(Port::Left).unwrap().mode(), TrackballMode::AtariMouse);
+        assert_eq!(console.variant(), ConsoleVariant::Sears);
+        assert_eq!(console.tia_revision(), TiaRevision::Original);
+    }
+
+    #[test]
+    fn test_paddle_calibration() {
+        let cartridge = Cartridge::new(vec![0x_4C, 0x_00, 0x_F0]);
+        let calibration = PaddleCalibration::from_scanlines(8, 150);
+
+        let mut database = Database::new();
+        database.insert(RomEntry {
+            md5: cartridge.md5(),
+            name: String::from("Test Game"),
+            manufacturer: String::from("Nobody"),
+            left_controller: ControllerKind::Paddles,
+            right_controller: ControllerKind::Paddles,
+            variant: None,
+            paddle_calibration: Some(calibration)
+        });
+
+        // The paddles are calibrated for the game, unless it's overridden.
+        let mut console = ConsoleBuilder::new(Cartridge::new(cartridge.memory.clone()))
+            .database(&database)
+            .build();
+        assert_eq!(console.controller_mut::<Paddle>(Port::Left).unwrap().calibration(), calibration);
+        assert_eq!(console.controller_mut::<Paddle>(Port::Right).unwrap().calibration(), calibration);
+
+        let mut console = ConsoleBuilder::new(Cartridge::new(cartridge.memory.clone()))
+            .database(&database)
+            .paddle_calibration(calibration.with_sensitivity(2.0))
+            .build();
+        assert_eq!(console.controller_mut::<Paddle>(Port::Left).unwrap().calibration(), calibration.with_sensitivity(2.0));
+
+        let mut console = ConsoleBuilder::new(cartridge)
+            .left_controller(ControllerKind::Paddles)
+            .build();
+        assert_eq!(console.controller_mut::<Paddle>(Port::Left).unwrap().calibration(), PaddleCalibration::default());
+    }
+
+    #[test]
+    fn test_seed() {
+        let rom = fixtures::counter_rom(0x_80);
+
+        let build = |seed| ConsoleBuilder::new(Cartridge::new(rom.clone())).seed(seed).random_ram().build();
+        let ram = |console: &Console| (0x_80..=0x_FF).map(|address| console.memory(address)).collect::<Vec<_>>();
+
+        // The RAM is zeroed by default.
+        let console = ConsoleBuilder::new(Cartridge::new(rom.clone())).build();
+        assert!(ram(&console).iter().all(|value| *value == 0));
+        assert_eq!(console.rng().seed(), 0);
+
+        // The same seed gives the same run.
+        let mut first = build(42);
+        let mut second = build(42);
+        assert_eq!(ram(&first), ram(&second));
+        assert_ne!(ram(&first), ram(&build(43)));
+
+        first.run_frame();
+        second.run_frame();
+        assert_eq!(ram(&first), ram(&second));
+        assert_eq!(first.rng_mut().next_u64(), second.rng_mut().next_u64());
+
+        // The generator is part of the save states.
+        let state = first.save_state();
+        let value = first.rng_mut().next_u64();
+        first.load_state(&state).unwrap();
+        assert_eq!(first.rng_mut().next_u64(), value);
+    }
+
+    #[test]
+    fn test_warm_up_frames() {
+        let rom = fixtures::counter_rom(0x_80);
+
+        let mut console = ConsoleBuilder::new(Cartridge::new(rom.clone())).build();
+        for _ in 0..10 {
+            console.run_frame();
+        }
+
+        // The console is where it would be after running the frames, but the
+        // audio was dropped.
+        let warmed_up = ConsoleBuilder::new(Cartridge::new(rom)).warm_up_frames(10).build();
+        assert_eq!(warmed_up.frame().number, 10);
+        assert_eq!(warmed_up.memory(0x_80), console.memory(0x_80));
+        assert_eq!(warmed_up.queued_audio_samples(), 0);
+        assert!(console.queued_audio_samples() > 0);
+    }
+}
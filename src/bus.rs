@@ -0,0 +1,259 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+// The address decoding of the bus.
+//
+// The 6507 has 13 address lines and the chips of the console only look at
+// some of them to know whether they're selected; the other lines are ignored,
+// which mirrors each chip many times over the address space.
+//
+//   A12 = 1                    cartridge (A0 to A11)
+//   A12 = 0, A7 = 0            TIA (A0 to A5 on write, A0 to A3 on read)
+//   A12 = 0, A7 = 1, A9 = 0    RAM (A0 to A6)
+//   A12 = 0, A7 = 1, A9 = 1    PIA (A0 to A4)
+//
+// The PIA registers are selected by A2; when it's clear, A0 and A1 select the
+// I/O registers (SWCHA, SWACNT, SWCHB, SWBCNT). When it's set, reading selects
+// the timer (INTIM) or its status (INSTAT) with A0, and writing selects the
+// timer intervals (TIM1T to T1024T) with A0 and A1 if A4 is set, or the edge
+// detect control of PA7 if A4 is clear. A3 enables the timer interrupt when
+// the timer is read or written, therefore it's kept in their addresses.
+//
+// The TIA doesn't drive any bit of the data bus when its last two read
+// addresses (0x_3E and 0x_3F) are read; the value read is whatever was left
+// on the bus, hence they're decoded as open bus.
+//
+// The decoding returns the component along with the canonical address of the
+// location (the one from the location module) the rest of the emulator uses.
+
+/// A component responding on the bus.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Component {
+    Tia,
+    Ram,
+    Pia,
+    Cartridge,
+    OpenBus
+}
+
+/// Decode an address which is read.
+pub(crate) fn decode_read(address: u16) -> (Component, u16) {
+    let address = address & 0x_1FFF;
+
+    if address & 0x_1000 != 0 {
+        (Component::Cartridge, address)
+    }
+    else if address & 0x_0080 == 0 {
+        match 0x_30 | (address & 0x_0F) {
+            index @ 0x_3E..=0x_3F => (Component::OpenBus, index),
+            index => (Component::Tia, index)
+        }
+    }
+    else if address & 0x_0200 == 0 {
+        (Component::Ram, 0x_80 | (address & 0x_7F))
+    }
+    else if address & 0x_0004 == 0 {
+        (Component::Pia, 0x_0280 | (address & 0x_03))
+    }
+    else {
+        (Component::Pia, 0x_0284 | (address & 0x_09))
+    }
+}
+
+/// Decode an address which is written.
+pub(crate) fn decode_write(address: u16) -> (Component, u16) {
+    let address = address & 0x_1FFF;
+
+    if address & 0x_1000 != 0 {
+        (Component::Cartridge, address)
+    }
+    else if address & 0x_0080 == 0 {
+        (Component::Tia, address & 0x_3F)
+    }
+    else if address & 0x_0200 == 0 {
+        (Component::Ram, 0x_80 | (address & 0x_7F))
+    }
+    else if address & 0x_0004 == 0 {
+        (Component::Pia, 0x_0280 | (address & 0x_03))
+    }
+    else if address & 0x_0010 != 0 {
+        (Component::Pia, 0x_0294 | (address & 0x_0B))
+    }
+    else {
+        (Component::Pia, 0x_0284 | (address & 0x_03))
+    }
+}
+
+/// The bus of the MOS 6507.
+///
+/// This trait is what the processor reads and writes the memory through; the
+/// addressing modes and the instructions only see the machine through it. The
+/// console implements it with the decoding above, but any machine built
+/// around a 6502 family processor can implement it to reuse the instructions,
+/// flat memory included.
+///
+/// ```
+/// # use atari_2600::Bus;
+/// struct Memory([u8; 65536]);
+///
+/// impl Bus for Memory {
+///     fn read(&mut self, address: u16) -> u8 {
+///         self.0[address as usize]
+///     }
+///
+///     fn write(&mut self, address: u16, value: u8) {
+///         self.0[address as usize] = value;
+///     }
+/// }
+/// ```
+///
+pub trait Bus {
+    /// Read a byte from the given address.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Write a byte to the given address.
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Read a byte of the instruction stream.
+    ///
+    /// This function is called instead of `read()` when the processor fetches
+    /// an opcode or its operands, for machines that tell them apart (the
+    /// console remembers the byte left on the data bus); it reads the byte by
+    /// default.
+    ///
+    fn fetch(&mut self, address: u16) -> u8 {
+        self.read(address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::console::Console;
+    use crate::cartridge::Cartridge;
+    use crate::location::*;
+
+    // The chip selects of the hardware, written as the equations of the
+    // address lines; the decoding must agree with them for every address.
+    fn selected_component(address: u16, write: bool) -> Component {
+        let line = |n: u16| address & (1 << n) != 0;
+
+        if line(12) {
+            Component::Cartridge
+        }
+        else if !line(7) {
+            if !write && line(3) && line(2) && line(1) {
+                Component::OpenBus
+            }
+            else {
+                Component::Tia
+            }
+        }
+        else if !line(9) {
+            Component::Ram
+        }
+        else {
+            Component::Pia
+        }
+    }
+
+    #[test]
+    fn test_decode_read() {
+        for address in 0..0x_2000 {
+            let (component, index) = decode_read(address);
+            assert_eq!(component, selected_component(address, false), "address {:#06X}", address);
+
+            let expected_index = match component {
+                Component::Tia | Component::OpenBus => 0x_30 + (address & 0x_0F),
+                Component::Ram => 0x_80 + (address & 0x_7F),
+                Component::Pia if address & 0x_04 == 0 => SWCHA + (address & 0x_03),
+                Component::Pia => INTIM + (address & 0x_09),
+                Component::Cartridge => address
+            };
+            assert_eq!(index, expected_index, "address {:#06X}", address);
+        }
+
+        // The 3 upper bits of the address aren't connected.
+        for address in 0x_2000..=0x_FFFF {
+            assert_eq!(decode_read(address), decode_read(address & 0x_1FFF));
+        }
+    }
+
+    #[test]
+    fn test_decode_write() {
+        for address in 0..0x_2000 {
+            let (component, index) = decode_write(address);
+            assert_eq!(component, selected_component(address, true), "address {:#06X}", address);
+
+            let expected_index = match component {
+                Component::Tia | Component::OpenBus => address & 0x_3F,
+                Component::Ram => 0x_80 + (address & 0x_7F),
+                Component::Pia if address & 0x_04 == 0 => SWCHA + (address & 0x_03),
+                Component::Pia if address & 0x_10 != 0 => TIM1T + (address & 0x_0B),
+                Component::Pia => INTIM + (address & 0x_03),
+                Component::Cartridge => address
+            };
+            assert_eq!(index, expected_index, "address {:#06X}", address);
+        }
+
+        for address in 0x_2000..=0x_FFFF {
+            assert_eq!(decode_write(address), decode_write(address & 0x_1FFF));
+        }
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let rom = (0..4096).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        let mut console = Console::new(Cartridge::new(rom));
+
+        // Each mirror of the RAM reaches the same 128 bytes.
+        for address in (0..0x_2000).filter(|&a| selected_component(a, true) == Component::Ram) {
+            *console.memory_mut(address) = address as u8 ^ 0x_5A;
+            assert_eq!(console.memory(0x_80 | (address & 0x_7F)), address as u8 ^ 0x_5A);
+        }
+
+        // Each mirror of the cartridge reads the ROM, and it can't be written.
+        for address in (0..0x_2000).filter(|&a| selected_component(a, false) == Component::Cartridge) {
+            let value = ((address & 0x_0FFF) * 7) as u8;
+
+            assert_eq!(console.memory(address), value);
+            *console.memory_mut(address) = !value;
+            assert_eq!(console.memory(address), value);
+        }
+
+        // Each mirror of the I/O registers of the PIA reaches them.
+        for address in (0..0x_2000).filter(|&a| selected_component(a, true) == Component::Pia && a & 0x_04 == 0) {
+            *console.memory_mut(address) = address as u8;
+            assert_eq!(console.memory(SWCHA + (address & 0x_03)), address as u8);
+        }
+
+        // Each mirror of the timer registers of the PIA sets the timer, which
+        // is read back from the mirrors of INTIM.
+        for address in (0..0x_2000).filter(|&a| selected_component(a, true) == Component::Pia && a & 0x_14 == 0x_14) {
+            *console.memory_mut(address) = 0x_42;
+            assert_eq!(console.memory(address & !0x_0001), 0x_42);
+            assert_eq!(console.pia.timer_interrupt, address & 0x_08 != 0);
+        }
+
+        // Each mirror of the read registers of the TIA reaches them (the fire
+        // buttons are released by default).
+        for address in (0..0x_2000).filter(|&a| selected_component(a, false) == Component::Tia && a > 0x_3F) {
+            let expected_value = match 0x_30 + (address & 0x_0F) {
+                INPT4 | INPT5 => 0b1000_0000,
+                _ => 0b0000_0000
+            };
+            assert_eq!(console.memory(address), expected_value, "address {:#06X}", address);
+        }
+
+        // Each mirror of the write registers of the TIA reaches them.
+        for address in (0..0x_2000).filter(|&a| selected_component(a, true) == Component::Tia && a & 0x_3F == COLUBK) {
+            *console.memory_mut(address) = address as u8 & 0x_FE;
+            assert_eq!(console.memory(COLUBK), address as u8 & 0x_FE);
+        }
+    }
+}
@@ -6,12 +6,53 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, September 2020
 
+use std::fmt;
 use std::io;
 use std::io::Read;
 use std::path::Path;
 use std::fs::File;
 use std::string::String;
 
+use crate::utils::{md5, to_hex_string};
+use crate::patch::Patch;
+use crate::analysis::{BankSwitching, bank_switching};
+
+// The size of a bank (the area of the cartridge memory seen by the CPU).
+const BANK_SIZE: usize = 4096;
+
+// The size of the largest cartridges (32K, with the F4 bank switching).
+pub(crate) const MAX_ROM_SIZE: usize = 32768;
+
+/// An access to the cartridge the mapper can't serve.
+///
+/// The mapper of a cartridge maps the addresses of the cartridge area (in the
+/// selected bank) to the offsets of the ROM; it's a fault when the offset is
+/// out of the ROM (an empty ROM, or a bank the ROM doesn't have). The access
+/// reads the open bus instead, and the fault is reported in the update
+/// summary; a frontend forwards it to its logger, it's a ROM that isn't
+/// supported properly.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MapperFault {
+    /// Address that was accessed.
+    pub address: u16,
+    /// Bank that was selected.
+    pub bank: usize,
+    /// Offset of the ROM the address is mapped to.
+    pub offset: usize,
+    /// Size of the ROM.
+    pub rom_size: usize
+}
+
+impl fmt::Display for MapperFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "${:04X} (bank {}) is mapped to the offset ${:04X}, out of the ROM of {} bytes",
+            self.address, self.bank, self.offset, self.rom_size
+        )
+    }
+}
+
 /// Game cartridge of the Atari 2600 gaming console.
 ///
 /// A cartridge contains up to 4k ROm which is mapped to the RAM from 0x_1000 to
@@ -19,9 +60,10 @@ use std::string::String;
 ///
 /// TODO; To be implemented.
 ///
+/// A ROM of less than 4k is mirrored across the reserved memory.
+///
 /// Pending notes:
 /// --------------
-/// - if the rom is less than 4k, the entire reserved memory isn't filled up
 /// - memory also ROM, or EPROM
 ///
 pub struct Cartridge {
@@ -30,7 +72,10 @@ pub struct Cartridge {
     pub model: String,
     pub rarity: String,
     pub notes: String,
-    pub memory: Vec<u8>
+    pub memory: Vec<u8>,
+
+    // The bank selected by the last access to a hotspot.
+    bank: usize
 }
 
 impl Cartridge {
@@ -41,14 +86,24 @@ impl Cartridge {
             model: String::new(),
             rarity: String::new(),
             notes: String::new(),
-            memory: memory
+            memory,
+            bank: 0
         }
     }
 
+    /// Load a cartridge.
+    ///
+    /// This function reads the ROM until the end of the reader; it fails with
+    /// invalid data if the ROM is empty or larger than the largest cartridges
+    /// (32K). The ROMs smaller than 4K (the 2K games, the 1K diagnostic
+    /// cartridges, test stubs, etc.) are mirrored across the 4K of the
+    /// cartridge area.
+    ///
     pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Cartridge> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes)?;
 
+        check_size(&bytes)?;
         Ok(Cartridge::new(bytes))
     }
 
@@ -56,4 +111,197 @@ impl Cartridge {
         let mut reader = File::open(path)?;
         Self::from_reader(&mut reader)
     }
-}
\ No newline at end of file
+
+    /// Load a cartridge and patch it.
+    ///
+    /// This function reads the ROM and applies an IPS or BPS patch to it
+    /// before anything else looks at its content (the size, the bank
+    /// switching scheme, the MD5 digest, etc.), as if the ROM file had been
+    /// patched beforehand. Errors of the patch are reported as invalid data.
+    ///
+    /// ```no_run
+    /// # use atari_2600::Cartridge;
+    /// let cartridge = Cartridge::from_file_with_patch("game.bin", "translation.ips")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    pub fn from_file_with_patch<P: AsRef<Path>, Q: AsRef<Path>>(path: P, patch_path: Q) -> io::Result<Cartridge> {
+        let cartridge = Self::from_file(path)?;
+        let patch = Patch::from_file(patch_path)?;
+        let bytes = patch.apply(&cartridge.memory)?;
+
+        check_size(&bytes)?;
+        Ok(Cartridge::new(bytes))
+    }
+
+    /// MD5 digest of the ROM.
+    ///
+    /// This function returns the MD5 digest of the ROM as a lowercase
+    /// hexadecimal string. It's how the ROM database identifies games.
+    ///
+    pub fn md5(&self) -> String {
+        to_hex_string(&md5(&self.memory))
+    }
+
+    /// Number of banks of the ROM.
+    ///
+    /// This function returns the number of 4K banks the ROM is made of; the
+    /// ROMs of 4K and less are a single bank.
+    ///
+    pub fn bank_count(&self) -> usize {
+        self.memory.len().div_ceil(BANK_SIZE).max(1)
+    }
+
+    /// Bank selected by the bank switching.
+    ///
+    /// The first bank is selected when the cartridge is created; it's always
+    /// selected by the ROMs without bank switching.
+    ///
+    pub(crate) fn bank(&self) -> usize {
+        self.bank
+    }
+
+    /// Select a bank (restoring a save state).
+    pub(crate) fn set_bank(&mut self, bank: usize) {
+        self.bank = bank;
+    }
+
+    /// Access an address of the cartridge area.
+    ///
+    /// This function selects another bank when the address is a hotspot of
+    /// the bank switching scheme (see `BankSwitching`), whether it's read or
+    /// written; the access itself is served by the newly selected bank. Only
+    /// the F8, F6 and F4 schemes are switched, the other ROMs stay on the
+    /// first bank.
+    ///
+    pub(crate) fn access(&mut self, address: u16) {
+        let first_hotspot = match bank_switching(self.memory.len()) {
+            BankSwitching::F8 => 0x_0FF8,
+            BankSwitching::F6 => 0x_0FF6,
+            BankSwitching::F4 => 0x_0FF4,
+            _ => return
+        };
+
+        let index = address & 0x_0FFF;
+        if (first_hotspot..first_hotspot + self.bank_count() as u16).contains(&index) {
+            self.bank = (index - first_hotspot) as usize;
+        }
+    }
+
+    /// Map an address of the cartridge area to the ROM.
+    ///
+    /// This function returns the offset of the ROM the CPU reads at an
+    /// address of the cartridge area (only the 12 lowest bits matter) when
+    /// the given bank is selected; the ROMs smaller than the area are
+    /// mirrored across it. It fails when the offset is out of the ROM.
+    ///
+    pub(crate) fn map(&self, bank: usize, address: u16) -> Result<usize, MapperFault> {
+        let area_offset = (address & 0x_0FFF) as usize;
+        let offset = match self.memory.len() {
+            length @ 1..BANK_SIZE => area_offset % length,
+            _ => bank * BANK_SIZE + area_offset
+        };
+
+        match offset < self.memory.len() {
+            true => Ok(offset),
+            false => Err(MapperFault { address, bank, offset, rom_size: self.memory.len() })
+        }
+    }
+
+    /// Byte of the ROM seen at an address of the cartridge area (see
+    /// `map()`).
+    pub(crate) fn rom_byte(&self, bank: usize, address: u16) -> Result<&u8, MapperFault> {
+        self.map(bank, address).map(|offset| &self.memory[offset])
+    }
+
+    /// Byte of the ROM seen at an address of the cartridge area, to be
+    /// patched (see `map()`).
+    pub(crate) fn rom_byte_mut(&mut self, bank: usize, address: u16) -> Result<&mut u8, MapperFault> {
+        let offset = self.map(bank, address)?;
+        Ok(&mut self.memory[offset])
+    }
+}
+
+// Check whether a ROM can be loaded in a cartridge.
+fn check_size(bytes: &[u8]) -> io::Result<()> {
+    if bytes.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the ROM is empty"));
+    }
+
+    if bytes.len() > MAX_ROM_SIZE {
+        let message = format!("the ROM is {} bytes, more than the {} bytes of the largest cartridges", bytes.len(), MAX_ROM_SIZE);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::console::Console;
+
+    #[test]
+    fn test_rom_size() {
+        // The ROMs smaller than 4K are mirrored.
+        let rom = (0..=255).collect::<Vec<u8>>();
+        let cartridge = Cartridge::from_reader(&mut rom.as_slice()).unwrap();
+        assert_eq!(cartridge.rom_byte(0, 0x_F000).ok(), Some(&0x_00));
+        assert_eq!(cartridge.rom_byte(0, 0x_F1FC).ok(), Some(&0x_FC));
+        assert_eq!(cartridge.rom_byte(0, 0x_FFFF).ok(), Some(&0x_FF));
+
+        let mut rom = vec![0; 1024];
+        rom[0x_3FC] = 0x_42;
+        let cartridge = Cartridge::new(rom);
+        assert_eq!(cartridge.rom_byte(0, 0x_FFFC).ok(), Some(&0x_42));
+        assert_eq!(cartridge.rom_byte(0, 0x_F7FC).ok(), Some(&0x_42));
+
+        // The empty and oversized ROMs are rejected by the loaders.
+        assert!(Cartridge::new(vec![]).rom_byte(0, 0x_F000).is_err());
+
+        let error = Cartridge::from_reader(&mut [].as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "the ROM is empty");
+
+        let rom = vec![0; 65536];
+        let error = Cartridge::from_reader(&mut rom.as_slice()).err().unwrap();
+        assert_eq!(error.to_string(), "the ROM is 65536 bytes, more than the 32768 bytes of the largest cartridges");
+
+        let rom = vec![0; 32768];
+        assert!(Cartridge::from_reader(&mut rom.as_slice()).is_ok());
+
+        // The console runs the mirrored ROMs, and the empty ones read as an
+        // open bus.
+        let mut rom = assemble("
+            loop:
+                inc $80
+                jmp loop
+        ").unwrap();
+        rom.resize(256, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert_eq!(console.memory(0x_FF00), 0x_E6);
+        console.run_frame();
+        assert!(console.pia.ram[0] > 0);
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+        assert_eq!(console.memory(0x_F000), 0);
+        for _ in 0..10 {
+            console.execute_instruction();
+        }
+    }
+
+    #[test]
+    fn test_mapper_fault() {
+        // The banks are mapped one after the other, and the offsets out of the
+        // ROM are faults.
+        let cartridge = Cartridge::new(vec![0; 6144]);
+        assert_eq!(cartridge.map(0, 0x_F123), Ok(0x_0123));
+        assert_eq!(cartridge.map(1, 0x_F123), Ok(0x_1123));
+
+        let fault = cartridge.map(1, 0x_F823).unwrap_err();
+        assert_eq!(fault, MapperFault { address: 0x_F823, bank: 1, offset: 0x_1823, rom_size: 6144 });
+        assert_eq!(fault.to_string(), "$F823 (bank 1) is mapped to the offset $1823, out of the ROM of 6144 bytes");
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A source of time.
+///
+/// The emulator reads the time from a clock instead of the system directly,
+/// so tests can simulate time precisely and hosts without a usable system
+/// clock (WebAssembly, embedded) can provide their own time base.
+///
+pub trait Clock {
+    /// Current time.
+    fn now(&self) -> Instant;
+}
+
+/// The clock of the system.
+///
+/// This is the default clock; it reads the time with `Instant::now()`.
+///
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that is advanced manually.
+///
+/// The time of this clock only changes when it's advanced, which makes the
+/// timing of the simulation reproducible in tests.
+///
+#[cfg_attr(all(feature = "test-rom", feature = "frontend"), doc = "```")]
+#[cfg_attr(not(all(feature = "test-rom", feature = "frontend")), doc = "```ignore")]
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// # use atari_2600::{Cartridge, Console, Emulator, ManualClock, TEST_ROM};
+/// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let clock = Arc::new(ManualClock::new());
+/// let mut emulator = Emulator::new(console).with_clock(clock.clone());
+///
+/// clock.advance(Duration::from_millis(16));
+/// emulator.tick();
+/// ```
+///
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>
+}
+
+impl ManualClock {
+    /// Create a manual clock.
+    ///
+    /// The clock starts at the current time of the system, then it only
+    /// changes when it's advanced.
+    ///
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Mutex::new(Instant::now())
+        }
+    }
+
+    /// Advance the clock by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_millis(16));
+        assert_eq!(clock.now() - start, Duration::from_millis(16));
+    }
+}
@@ -11,10 +11,9 @@
 //! This module defines the color enumerations for the **NTSC** TV sets and some
 //! helpers to convert them into RGB colors. **PAL** and **SECAM** colors and
 //! luminance are still to be implemented. Note that luminance is the same for
-//! both NTSC and PAL and not used for SECAM.
+//! both NTSC and PAL and not used for SECAM. The colors of the objects are
+//! resolved by the palette module.
 //!
-use crate::location::*;
-use crate::console::Console;
 
 /// Set of the luminance values as defined by the specifications (note that
 /// the naming was made up).
@@ -93,53 +92,13 @@ fn hexadecimal_to_color(value: u8) -> Color {
 /// Dissect a byte and return the color and luminance information (they are
 /// contained on a single byte; 3 bits for the luminance, and 4 bits for the
 /// color).
-fn color_and_luminance(value: u8) -> (Color, Luminance) {
+pub(crate) fn color_and_luminance(value: u8) -> (Color, Luminance) {
     let color = (value & 0b11110000) >> 4;
     let luminance = (value & 0b00001110) >> 1;
 
     (hexadecimal_to_color(color), octal_to_luminance(luminance))
 }
 
-/// Compute the current background color determined by memory location COLUBK).
-pub(crate) fn background_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUBK)))
-}
-
-/// Compute the current playfield color (determined by memory location COLUPF).
-pub(crate) fn playfield_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUPF)))
-}
-
-/// Compute the current color of player 0 (determined by memory location
-/// COLUP0).
-pub(crate) fn player0_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUP0)))
-}
-
-/// Compute the current color of player 1 (determined by memory location
-/// COLUP1).
-pub(crate) fn player1_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUP1)))
-}
-
-/// Compute the current color of missile 0 (determined by memory location
-/// COLUP0).
-pub(crate) fn _missile0_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUP0)))
-}
-
-/// Compute the current color of missile 1 (determined by memory location
-/// COLUP1).
-pub(crate) fn _missile1_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUP1)))
-}
-
-/// Compute the current color of the ball (determined by memory location
-/// COLUPF).
-pub(crate) fn _ball_color(console: &Console) -> (u8, u8, u8) {
-    to_rgb(color_and_luminance(*console.memory(COLUPF)))
-}
-
 /// Convert a color and a luminance into its corresponding RGB value to be
 /// displayed on contemporary screen monitors.
 pub fn to_rgb((color, luminance): (Color, Luminance)) -> (u8, u8, u8) {
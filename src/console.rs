@@ -6,17 +6,67 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, September 2020
 
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::cell::RefCell;
+#[cfg(feature = "debugger")]
+use std::io;
+#[cfg(feature = "debugger")]
+use std::io::Write;
 use std::time::Duration;
-
-use crate::cartridge::Cartridge;
-use crate::controller::Controller;
+#[cfg(feature = "debugger")]
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cartridge::{Cartridge, MapperFault};
+use crate::controller::{Controller, ControllerMut};
+use crate::joystick::Joystick;
+use crate::port_pins::PortPins;
+use crate::action::Action;
 use crate::location::*;
-use crate::location::{VSYNC};
-use crate::instruction::*;
-use crate::video::create_scanline;
-
-const HORIZONTAL_CYCLES: u32 = 228;
-const VERTICAL_LINES: u32 = 262;
+use crate::audio::COLOR_CYCLES_PER_SAMPLE;
+use crate::frame::{Frame, Frames, Orientation, FramePooling};
+use crate::summary::{UpdateSummary, ScanlineWarning, UnknownRegisterWrite};
+use crate::telemetry::{Telemetry, Timecode, RateMeter};
+use crate::clock::{Clock, SystemClock};
+use crate::watch::RamChange;
+use crate::state::{SaveState, StateWriter, StateError};
+use crate::determinism::FrameChecksum;
+use crate::utils::{fnv1a, FNV_OFFSET};
+use crate::tia_state::{TiaState, ObjectState, PixelLayers};
+use crate::sprite::{player_copies, player_width};
+use crate::missile::missile_width;
+use crate::ball::ball_width;
+#[cfg(feature = "debugger")]
+use crate::trace::{Tracer, TraceFilter, TraceEntry};
+use crate::tia_log::{TiaLog, TiaWrite};
+#[cfg(feature = "debugger")]
+use crate::opcode_stats::OpcodeStats;
+#[cfg(feature = "debugger")]
+use crate::crash::{CrashReport, CrashCause};
+use crate::input_timing::{InputTiming, InputRead};
+use crate::rng::Rng;
+use crate::bus::{Bus, Component, decode_read, decode_write};
+use crate::cpu::{Cpu6507, UnknownOpcode};
+use crate::assembler::{assemble_at, AssemblerError};
+use crate::pia::Pia;
+use crate::tia::{Tia, HORIZONTAL_CYCLES};
+use crate::event::{Event, EventBus, SubscriptionId};
+
+// The maximum number of audio samples queued until the frontend takes them;
+// it's about one second worth of samples.
+const MAX_QUEUED_AUDIO_SAMPLES: usize = 32_000;
+
+// How far (in scanlines) the vertical sync can be from the number of scanlines
+// of the TV standard before the TV can't lock on it, and how fast the picture
+// rolls (in scanlines per frame) when there's no vertical sync at all.
+const SYNC_TOLERANCE: i32 = 10;
+const FREE_RUNNING_ROLL: i32 = 7;
+
+// The minimum number of frames between two warnings about the scanline count.
+const SCANLINE_WARNING_INTERVAL: u64 = 60;
+
+// The first scanline of the visible picture.
+const PICTURE_TOP: u32 = 3 + 37;
 
 // TODO; Double-check exact cycle duration because TV runs at 59.94 Hertz, not
 // exactly 60 Hertz, therefore 228 * 262 / 3 * 59.94 results in a bit less than
@@ -33,30 +83,163 @@ const CYCLE_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 1_193_525)
 /// TODO; It's unclear to me if a color TV would be affected by the switch set
 /// to black and white; the description needs to be updated probably.
 ///
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum TvType {
     Mono, // 'W/B'
     Color // 'Colors'
 }
 
+/// The TV standard.
+///
+/// The console was sold in different versions depending on the TV standard of
+/// the region; NTSC in North America and Japan, PAL in most of Europe, and
+/// SECAM in France and the Eastern Bloc. They differ by the number of scanlines
+/// and the frequency of the color clock, and thus, by their frame rate.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TvStandard {
+    Ntsc, Pal, Secam
+}
+
+impl TvStandard {
+    /// Frequency of the color clock (in Hertz).
+    pub fn color_clock_frequency(&self) -> f64 {
+        match self {
+            TvStandard::Ntsc  => 3_579_545.0,
+            TvStandard::Pal   => 3_546_894.0,
+            TvStandard::Secam => 3_562_500.0
+        }
+    }
+
+    /// Number of scanlines per frame.
+    pub fn scanlines(&self) -> u32 {
+        match self {
+            TvStandard::Ntsc => 262,
+            TvStandard::Pal | TvStandard::Secam => 312
+        }
+    }
+
+    /// Number of frames per second.
+    ///
+    /// Note that it's not exactly 60 Hertz (or 50 Hertz) but a bit less with
+    /// NTSC; about 59.92 Hertz.
+    ///
+    pub fn frame_rate(&self) -> f64 {
+        self.color_clock_frequency() / (HORIZONTAL_CYCLES * self.scanlines()) as f64
+    }
+}
+
 /// The identification of the player.
 ///
 /// The Atari 2600 gaming console supports up to 2 players denoted 'player 1'
 /// and 'player 2'.
 ///
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Player {
     One, Two
 }
 
+/// The controller ports of the console.
+///
+/// The Atari 2600 gaming console has two controller ports at the back, the
+/// left one and the right one. A port isn't a player; usually the left port
+/// is for player 1 and the right port for player 2, but a pair of paddles
+/// puts two players on a single port, and adapters can put even more.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Port {
+    Left, Right
+}
+
+impl From<Player> for Port {
+    /// The port of the controller of a player when each player has its own
+    /// controller (joysticks, driving controllers, etc.); player 1 is on the
+    /// left port and player 2 is on the right port.
+    fn from(player: Player) -> Port {
+        match player {
+            Player::One => Port::Left,
+            Player::Two => Port::Right
+        }
+    }
+}
+
 /// The difficulty of the game for a given player.
 ///
 /// The Atari 2600 gaming console has two physical switches to change the level
 /// of difficulty of player 1 and player 2. They're denoted 'amateur' for easy,
 /// and 'pro' for difficult.
 ///
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Difficulty {
     Amateur, Pro
 }
 
+/// The revision of the TIA chip.
+///
+/// The TIA was revised during the production of the console; the later
+/// revisions fixed a few undocumented behaviors of the original chip, which a
+/// handful of games rely on and are known to glitch without. For instance, the
+/// original chip lets the horizontal motion registers be changed while HMOVE
+/// moves the objects, which draws the starfield of Cosmic Ark; the later
+/// revisions latch them when HMOVE is written.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TiaRevision {
+    Original, Late
+}
+
+/// The variant of the console.
+///
+/// The console was sold by Atari (the original model, with six or four
+/// switches), rebranded by Sears as the Video Arcade, and redesigned later as
+/// the cheaper 2600 Jr. They run the same games but they were paired with
+/// different revisions of the TIA, which matters to the few games known to be
+/// incompatible with one of them.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ConsoleVariant {
+    #[default]
+    Original,
+    Sears,
+    Junior
+}
+
+impl ConsoleVariant {
+    /// Revision of the TIA the variant was paired with.
+    pub fn tia_revision(&self) -> TiaRevision {
+        match self {
+            ConsoleVariant::Original | ConsoleVariant::Sears => TiaRevision::Original,
+            ConsoleVariant::Junior => TiaRevision::Late
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<ConsoleVariant> {
+        match name {
+            "original" => Some(ConsoleVariant::Original),
+            "sears" => Some(ConsoleVariant::Sears),
+            "junior" => Some(ConsoleVariant::Junior),
+            _ => None
+        }
+    }
+}
+
+/// The policy for the writes to the unknown registers.
+///
+/// The writes to the locations of the TIA without register are lost (see
+/// `UnknownRegisterWrite`); they're ignored by default, like on the hardware.
+/// Otherwise, they're reported in the update summary, either the first one
+/// of each location only or all of them, and the simulation can also be
+/// stopped right after the instruction that wrote, like a breakpoint.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum UnknownRegisterPolicy {
+    #[default]
+    Ignore,
+    LogOnce,
+    LogAlways,
+    Break
+}
+
 /// A virtual Atari 2600 gaming console.
 ///
 /// This structure represents the physical Atari 2600 console. It's constructed
@@ -66,28 +249,37 @@ pub enum Difficulty {
 /// function to emulate the physical power switch (on/off) that is present on
 /// a real console.
 ///
-/// ```
-/// let cartridge = Cartridge::open("breakout.bin");
-/// let console = Console::new(cartridge);
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// let mut console = Console::new(cartridge);
 /// ```
 ///
-/// After the console instance is created, you must attach controller in either
-/// of the two ports. (explain how attaching/dettaching controllers works)
+/// After the console instance is created, controllers are plugged in either
+/// of the two ports; they can be unplugged and replaced at any time, and the
+/// ports are left empty (nothing pressed) until then.
 ///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Joystick, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// console.plug_controller(Port::Left, Box::new(Joystick::new()));
+/// console.controller_mut::<Joystick>(Port::Left).unwrap().press_button();
 /// ```
-/// // To be written.
-/// ```
-///
-/// - explain how to advance the simulation in time
 ///
-/// ```
-/// // To be written.
-/// ```
+/// The simulation is advanced in time with `update()` (by the time elapsed
+/// since the last update), or a frame at a time with `run_frame()`, and the
+/// video and the audio output are retrieved afterward.
 ///
-/// - explain how to retrieve the audio and video output
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// console.run_frame();
 ///
-/// ```
-/// // To be written.
+/// let frame = console.frame();
+/// let samples = console.take_audio_samples();
 /// ```
 ///
 /// The console has **2 physical buttons** and **4 switches** which can also be changed
@@ -99,46 +291,55 @@ pub enum Difficulty {
 /// you must re-create the console object. The following snippet shows how to
 /// use those functions.
 ///
-/// ```
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TvType, Player, Difficulty, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
 /// // Change the video output to become monochrome.
-/// console.set_tv_type(TvType::Mono);
-/// assert_eq!(console.tv_type(), TvType::Mono);
+/// console.set_tv_type_switch(TvType::Mono);
+/// assert_eq!(console.tv_type_switch(), TvType::Mono);
 ///
 /// // Switch the difficulty of each players.
 /// console.set_difficulty_switch(Player::One, Difficulty::Amateur);
 /// console.set_difficulty_switch(Player::Two, Difficulty::Pro);
 ///
 /// // Press the reset and select buttons.
-/// console.reset_button();
-/// console.select_button();
+/// console.press_reset_button();
+/// console.press_select_button();
 /// ```
 ///
-/// Note that internally, the emulation of each hardware components (CPU, TIA,
-/// PIA, etc.) was all merged into this single structure as they are, on the
-/// hardware-level, very tied together. It would have been hard to split their
-/// implementation without overcomplicating the interface and the overall source
-/// code of the emulator.
+/// Note that internally, each hardware component (CPU, TIA and PIA) has its
+/// own structure which is stepped by the console; the console wires them
+/// together (it's the bus of the CPU) and keeps track of the frames, the
+/// audio samples and the controllers.
+///
+/// Consoles don't share any state; there is no global nor static state in the
+/// crate, and no unsafe code either (the controllers are handed the console
+/// when they need it rather than keeping a pointer to it), so any number of
+/// consoles can run in the same process, for instance to run the rollouts of
+/// an agent in parallel. Consoles are `Send` (so are their controllers and the
+/// subscribers of their events), and so are the cartridges, the frames, the
+/// save states and the checksums; a console can be created on one thread and
+/// moved to a worker thread that runs it. A console is large (it has its
+/// frame buffers), so it's better moved boxed.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::thread;
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let roms = vec![TEST_ROM.to_vec(); 2];
+/// let threads: Vec<_> = roms.into_iter().map(|rom| {
+///     let mut console = Box::new(Console::new(Cartridge::new(rom)));
+///     thread::spawn(move || console.frames().nth(60).unwrap())
+/// }).collect();
+/// # for thread in threads {
+/// #     thread.join().unwrap();
+/// # }
+/// ```
 ///
 pub struct Console {
-    // The pointer counter
-    pub(crate) pointer_counter: u16,
-
-    // The registers
-    pub(crate) accumulator:  u8,
-    pub(crate) x_register:  u8,
-    pub(crate) y_register:  u8,
-
-    // Teh status flags
-    pub(crate) negative_flag: bool,
-    pub(crate) overflow_flag: bool,
-    pub(crate) break_flag: bool,
-    pub(crate) decimal_flag: bool,
-    pub(crate) interrupt_flag: bool,
-    pub(crate) zero_flag: bool,
-    pub(crate) carry_flag: bool,
-
-    // The stack pointer
-    pub(crate) stack_pointer: u8,
+    // The registers and the status flags of the CPU
+    pub(crate) cpu: Cpu6507,
 
     // 0000-002C  TIA Write
     // 0000-000D  TIA Read (sometimes mirrored at 0030-003D)
@@ -146,45 +347,135 @@ pub struct Console {
     // 0280-0297  PIA Ports and Timer
     // F000-FFFF  Cartridge Memory (4 Kbytes area)
 
-    // The memory (will change to intercept read/write)
-    tia: [u8; 62],  // from 0x_00 to 0x_3D
-    ram: [u8; 128], // from 0x_80 to 0x_FF
-    pia: [u8; 4],   // from 0x_0280 to 0x_0297 but timer-related values were taken out.
+    // The video and audio chip.
+    pub(crate) tia: Tia,
+
+    // The number of frames the tapped reset and select switches remain
+    // pressed for.
+    pub(crate) reset_tap_frames: u32,
+    pub(crate) select_tap_frames: u32,
 
     // dummy: u8,        // for when the location isn't mapped to anything,
     dummy: [u8; 8192],
     // pub(crate) memory: [u8; 8192], // 13-bit bus memory on 6507
 
-    // Timer-related values from the PIA.
-    timer_value: u8,
-    timer_status: u8, // only bit 7 and 6 are relevant
-    timer_interval: u32,
-    timer_elapsed_clocks: u32,
+    // The RAM, the I/O ports and the timer.
+    pub(crate) pia: Pia,
 
     // Number of cycles since the beginning of the simulation.
     cycles_count: u128,
     color_cycles_count: u128,
     instructions_count: u128,
-
-    players_position: [u32; 2],
-    missiles_position: [u32; 2],
-    ball_position: u32,
-
-    scanline: u32,
-    scanline_cycle: u32,
-
-    is_vsync: bool,
-    cpu_halt: bool,
-
-    pub framebuffer: [[(u8, u8, u8); 160]; 192],
-    pending_framebuffer: [[(u8, u8, u8); 160]; 192],
-
+    frames_count: u64,
+    rate_meter: RateMeter,
+    clock: Arc<dyn Clock + Send + Sync>,
+
+    // Events that occurred since the last summary was made.
+    unknown_opcodes: Vec<(u16, u8)>,
+    #[cfg(feature = "debugger")]
+    opcode_stats: OpcodeStats,
+    breakpoint_hit: Option<u16>,
+    ram_changes: Vec<RamChange>,
+    unknown_register_writes: Vec<UnknownRegisterWrite>,
+
+    // The policy for the writes to the unknown registers, the locations that
+    // were already reported (one bit per location), and whether the
+    // simulation must stop after the current instruction.
+    unknown_register_policy: UnknownRegisterPolicy,
+    reported_unknown_registers: u64,
+    is_unknown_register_break: bool,
+
+    // The breakpoints, and whether the breakpoint that was just hit must be
+    // skipped when resuming the simulation.
+    breakpoints: HashSet<u16>,
+    is_resuming_breakpoint: bool,
+
+    // The watched RAM locations (their index in the RAM) and their value at
+    // the end of the last frame.
+    watches: Vec<(usize, u8)>,
+
+    // The checksums of the frames when the verification mode is enabled.
+    checksums: Option<Vec<FrameChecksum>>,
+
+    // The timing of the inputs during the frame being drawn and the last
+    // completed frame, when the measurements are enabled; the reads are
+    // recorded while reading the memory, which doesn't borrow the console
+    // mutably.
+    input_timing: RefCell<Option<InputTiming>>,
+    mapper_faults: RefCell<Vec<MapperFault>>,
+    pending_input_timing: Option<InputTiming>,
+
+    // Whether the simulation stops when the CPU jams.
+    is_jam_fatal: bool,
+
+    // Whether the TV couldn't lock on the vertical sync of the last frame, how
+    // far the picture has rolled (in scanlines), and whether the roll is
+    // shown.
+    is_sync_unstable: bool,
+    sync_roll: u32,
+    is_sync_roll_enabled: bool,
+
+    // The last scanline count that was measured, the frame of the last
+    // warning about a change of it and the changes not reported since, and
+    // the warnings since the last summary was made.
+    last_sync_lines: Option<u32>,
+    last_scanline_warning: Option<u64>,
+    suppressed_scanline_warnings: u32,
+    scanline_warnings: Vec<ScanlineWarning>,
+
+    // The last byte fetched by the CPU which is left on the data bus, and
+    // whether it's visible in the undriven bits of the TIA read registers.
+    data_bus: u8,
+    is_open_bus: bool,
+    read_latch: u8,
+
+    // The ongoing trace of the executed instructions, if any.
+    #[cfg(feature = "debugger")]
+    trace: Option<Tracer>,
+    tia_log: Option<(u128, TiaLog)>,
+
+    // The number of instructions kept for the crash reports, the last
+    // instructions, and the report of the last crash, until it's taken.
+    #[cfg(feature = "debugger")]
+    crash_trace_length: usize,
+    #[cfg(feature = "debugger")]
+    crash_trace: VecDeque<TraceEntry>,
+    #[cfg(feature = "debugger")]
+    crash_report: Option<CrashReport>,
+
+    // The subscribers to the events of the emulation.
+    events: EventBus,
+
+    // Whether the frame being run is thrown away afterwards (see
+    // `run_ahead()`); the controllers aren't updated during that frame.
+    is_running_ahead: bool,
+
+    // The generator used by the stochastic features.
+    rng: Rng,
+
+    // Charge-related values of the pot inputs (INPT0 to INPT3); the number of
+    // cycles needed to reach the threshold voltage, and the number of cycles
+    // elapsed since the capacitors were last dumped.
+    input_charge_times: [Option<u32>; 4],
+    input_charges: [u32; 4],
+    is_dumped: bool,
+
+    // The samples produced during the frame being drawn and the last
+    // completed frame, and the samples waiting to be played.
+    audio_buffer: Vec<f32>,
+    pending_audio_buffer: Vec<f32>,
+    audio_samples: VecDeque<f32>,
+
+    // The CPU cycles executed in each bank of the cartridge during the frame
+    // being drawn and the last completed frame.
+    bank_cycles: Vec<u64>,
+    pending_bank_cycles: Vec<u64>,
 
     // Simulation timing variables.
     elapsed_time: Duration,  // Local elapsed time
     remaining_cycles: isize, //
-    timer_block: bool, // tmp
 
+    variant: ConsoleVariant,
     cartridge: Cartridge,
     controller_left: Option<Box<dyn Controller>>,
     controller_right: Option<Box<dyn Controller>>
@@ -199,99 +490,274 @@ impl Console {
     /// cartridge, you must create another console instance.
     ///
     pub fn new(cartridge: Cartridge) -> Console {
+        let bank_count = cartridge.bank_count();
+
+        Console {
+            cpu: Cpu6507::new(),
 
-        let mut console = Console {
-            pointer_counter: 0x_F000, // TODO; double-check this
-            accumulator: 0,
-            x_register: 0,
-            y_register: 0,
-            negative_flag: true,
-            overflow_flag: true,
-            break_flag: true,
-            decimal_flag: true,
-            interrupt_flag: true,
-            zero_flag: true,
-            carry_flag: true,
-            // A well-behaving game will normally initialize the stack pointer.
-            stack_pointer: 0x_FF,
-
-            tia: [0; 62],
-            ram: [0; 128],
-            pia: [0; 4],
+            tia: Tia::new(),
+            reset_tap_frames: 0,
+            select_tap_frames: 0,
             // dummy: 0,
             dummy: [0; 8192],
 
-            timer_value: 0,
-            timer_status: 0,
-            timer_interval: 1,
-            timer_elapsed_clocks: 1,
+            pia: Pia::new(),
 
             cycles_count: 0,
             color_cycles_count: 0,
             instructions_count: 0,
+            frames_count: 0,
+            rate_meter: RateMeter::new(),
+            clock: Arc::new(SystemClock),
+
+            unknown_opcodes: Vec::new(),
+            #[cfg(feature = "debugger")]
+            opcode_stats: OpcodeStats::new(),
+            breakpoint_hit: None,
+            ram_changes: Vec::new(),
+            unknown_register_writes: Vec::new(),
+
+            unknown_register_policy: UnknownRegisterPolicy::Ignore,
+            reported_unknown_registers: 0,
+            is_unknown_register_break: false,
 
-            players_position: [0; 2],
-            missiles_position: [0; 2],
-            ball_position: 0,
+            breakpoints: HashSet::new(),
+            is_resuming_breakpoint: false,
 
-            scanline: 0,
-            scanline_cycle: 0,
+            watches: Vec::new(),
 
-            is_vsync: false,
-            cpu_halt: false,
+            checksums: None,
 
-            framebuffer: [[(0, 0, 0); 160]; 192],
-            pending_framebuffer: [[(0, 0, 0); 160]; 192],
+            input_timing: RefCell::new(None),
+            mapper_faults: RefCell::new(Vec::new()),
+            pending_input_timing: None,
+
+            is_jam_fatal: false,
+
+            is_sync_unstable: false,
+            sync_roll: 0,
+            is_sync_roll_enabled: false,
+
+            last_sync_lines: None,
+            last_scanline_warning: None,
+            suppressed_scanline_warnings: 0,
+            scanline_warnings: Vec::new(),
+
+            data_bus: 0,
+            is_open_bus: false,
+            read_latch: 0,
+
+            #[cfg(feature = "debugger")]
+            trace: None,
+            tia_log: None,
+
+            #[cfg(feature = "debugger")]
+            crash_trace_length: 0,
+            #[cfg(feature = "debugger")]
+            crash_trace: VecDeque::new(),
+            #[cfg(feature = "debugger")]
+            crash_report: None,
+
+            events: EventBus::new(),
+
+            is_running_ahead: false,
+
+            rng: Rng::default(),
+
+            input_charge_times: [None; 4],
+            input_charges: [0; 4],
+            is_dumped: false,
+
+            audio_buffer: Vec::new(),
+            pending_audio_buffer: Vec::new(),
+            audio_samples: VecDeque::new(),
+
+            bank_cycles: vec![0; bank_count],
+            pending_bank_cycles: vec![0; bank_count],
 
             elapsed_time: Duration::new(0, 0),
             remaining_cycles: 0,
-            timer_block: true,
 
-            cartridge: cartridge,
+            variant: ConsoleVariant::Original,
+            cartridge,
 
             controller_left: None,
             controller_right: None,
             // controllers: [Controller::new(), Controller::new()],
-        };
+        }
+    }
 
-        console
+    /// Cartridge inserted in the console.
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
     }
 
-    /// Brief description.
+    /// Press the reset switch.
     ///
-    /// Long description.
+    /// The reset switch is a momentary switch; it stays pressed until it's
+    /// released. It cancels a tap of the switch.
     ///
     pub fn press_reset_button(&mut self) {
+        self.reset_tap_frames = 0;
         *self.memory_mut(SWCHB) &= 0b1111_1110; // Bit 0 of SWCHB must be 0.
     }
 
-    /// Brief description.
+    /// Release the reset switch.
     ///
-    /// Long description.
+    /// It cancels a tap of the switch.
     ///
     pub fn release_reset_button(&mut self) {
+        self.reset_tap_frames = 0;
         *self.memory_mut(SWCHB) |= 0b0000_0001; // Bit 0 of SWCHB must be 1.
-
     }
 
-    /// Brief description.
+    /// Press the select switch.
     ///
-    /// Long description.
+    /// The select switch is a momentary switch; it stays pressed until it's
+    /// released. It cancels a tap of the switch.
     ///
     pub fn press_select_button(&mut self) {
-        // Nothing to do; it's not controlled by the software and is not
-        // relevant in this context as we're not emulating a full-fledged TV
-        // set.
+        self.select_tap_frames = 0;
+        *self.memory_mut(SWCHB) &= 0b1111_1101; // Bit 1 of SWCHB must be 0.
     }
 
-    /// Brief description.
+    /// Release the select switch.
     ///
-    /// Long description.
+    /// It cancels a tap of the switch.
     ///
     pub fn release_select_button(&mut self) {
-        // Nothing to do; it's not controlled by the software and is not
-        // relevant in this context as we're not emulating a full-fledged TV
-        // set.
+        self.select_tap_frames = 0;
+        *self.memory_mut(SWCHB) |= 0b0000_0010; // Bit 1 of SWCHB must be 1.
+    }
+
+    /// Tap the reset switch.
+    ///
+    /// This function presses the reset switch and releases it once the given
+    /// number of frames are completed. Games read the switches once per frame
+    /// (or less) and often wait for the switch to be released, so a press and
+    /// a release made by the frontend in quick succession may go unnoticed;
+    /// the duration of the tap is measured in the emulated time instead.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// // Start a new game.
+    /// console.tap_reset(3);
+    /// ```
+    ///
+    pub fn tap_reset(&mut self, frames: u32) {
+        self.press_reset_button();
+        self.reset_tap_frames = frames.max(1);
+    }
+
+    /// Tap the select switch.
+    ///
+    /// This function is like `tap_reset()` but for the select switch.
+    ///
+    pub fn tap_select(&mut self, frames: u32) {
+        self.press_select_button();
+        self.select_tap_frames = frames.max(1);
+    }
+
+    // Release the tapped switches once their frames are completed.
+    fn update_switch_taps(&mut self) {
+        if self.reset_tap_frames > 0 {
+            self.reset_tap_frames -= 1;
+            if self.reset_tap_frames == 0 {
+                self.release_reset_button();
+            }
+        }
+
+        if self.select_tap_frames > 0 {
+            self.select_tap_frames -= 1;
+            if self.select_tap_frames == 0 {
+                self.release_select_button();
+            }
+        }
+    }
+
+    /// Variant of the console.
+    pub fn variant(&self) -> ConsoleVariant {
+        self.variant
+    }
+
+    /// Change the variant of the console.
+    ///
+    /// This function changes the variant of the console and sets its switches
+    /// to the positions of a console that was just unpacked; the reset and
+    /// select switches are released, the TV type switch is on color and the
+    /// difficulty switches are on amateur, whatever the variant. It's meant
+    /// to be called right after the console is created, which is what
+    /// `ConsoleBuilder` does.
+    ///
+    pub fn set_variant(&mut self, variant: ConsoleVariant) {
+        self.variant = variant;
+        self.tia.revision = variant.tia_revision();
+
+        self.release_reset_button();
+        self.release_select_button();
+        self.set_tv_type_switch(TvType::Color);
+        self.set_difficulty_switch(Player::One, Difficulty::Amateur);
+        self.set_difficulty_switch(Player::Two, Difficulty::Amateur);
+    }
+
+    /// Revision of the TIA of the console.
+    pub fn tia_revision(&self) -> TiaRevision {
+        self.variant.tia_revision()
+    }
+
+    /// TV standard of the console.
+    ///
+    /// The console is the NTSC version unless the TV standard is switched
+    /// (see `set_tv_standard()`).
+    ///
+    pub fn tv_standard(&self) -> TvStandard {
+        self.tia.tv_standard
+    }
+
+    /// Switch the TV standard of the console (for debugging purpose).
+    ///
+    /// This function switches the console to another TV standard on the fly,
+    /// without touching its state; the CPU, the RAM, the TIA and the timer
+    /// carry on, and the number of scanlines per frame, the colors, the
+    /// frequency of the clocks (thus the duration of a cycle, the frame rate
+    /// and the sample rate of the audio) follow the new standard from the
+    /// current scanline on. It's meant to compare how a game behaves with NTSC
+    /// and PAL without restarting and replaying it; a real console can't do
+    /// that, and a game checks the standard at startup at most, so the
+    /// results don't tell how the game runs on the other version of the
+    /// console.
+    ///
+    /// Note that the picture remains the same 192 scanlines (see `Frame`)
+    /// while the PAL games usually draw more, that the PAL colors are
+    /// approximated, and that the TV standard isn't part of the save states.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```no_run")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TvStandard, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// let state = console.save_state();
+    /// console.set_tv_standard(TvStandard::Pal);
+    /// console.run_frame();
+    /// let pal_frame = console.frame();
+    ///
+    /// console.load_state(&state).unwrap();
+    /// console.set_tv_standard(TvStandard::Ntsc);
+    /// console.run_frame();
+    /// let ntsc_frame = console.frame();
+    /// ```
+    ///
+    pub fn set_tv_standard(&mut self, standard: TvStandard) {
+        self.tia.tv_standard = standard;
+    }
+
+    // Duration of a CPU cycle with the TV standard of the console.
+    fn cycle_duration(&self) -> Duration {
+        match self.tv_standard() {
+            TvStandard::Ntsc => CYCLE_DURATION,
+            standard => Duration::from_secs_f64(3.0 / standard.color_clock_frequency())
+        }
     }
 
     /// Brief description.
@@ -351,122 +817,530 @@ impl Console {
         match player {
             Player::One => {
                 match difficulty {
-                    Difficulty::Amateur => *self.memory_mut(SWCHB) |= 0b0100_0000,
-                    Difficulty::Pro     => *self.memory_mut(SWCHB) &= 0b1011_1111
+                    Difficulty::Amateur => *self.memory_mut(SWCHB) &= 0b1011_1111,
+                    Difficulty::Pro     => *self.memory_mut(SWCHB) |= 0b0100_0000
                 }
             },
             Player::Two => {
                 match difficulty {
-                    Difficulty::Amateur => *self.memory_mut(SWCHB) |= 0b1000_0000,
-                    Difficulty::Pro     => *self.memory_mut(SWCHB) &= 0b0111_1111
+                    Difficulty::Amateur => *self.memory_mut(SWCHB) &= 0b0111_1111,
+                    Difficulty::Pro     => *self.memory_mut(SWCHB) |= 0b1000_0000
                 }
             }
         }
     }
 
-    /// Brief description.
+    /// Plug a controller.
     ///
-    /// Long description.
+    /// This function plugs a controller into the given port, replacing the
+    /// controller already plugged, if any. The controller decides which
+    /// players it serves; a joystick serves the player of its port, a pair of
+    /// paddles serves two players.
     ///
-    pub fn plug_controller(&mut self, slot: Player, mut controller: Box<dyn Controller>) {
+    pub fn plug_controller(&mut self, port: Port, mut controller: Box<dyn Controller>) {
 
-        controller.plugged(&mut *self);
+        controller.plugged(port);
+        controller.apply(self);
 
-        match slot {
-            Player::One => self.controller_left = Some(controller),
-            Player::Two => self.controller_right = Some(controller)
+        *self.controller_slot(port) = Some(controller);
+    }
+
+    /// Unplug a controller.
+    ///
+    /// This function unplugs the controller from the given port and returns
+    /// it, if any.
+    ///
+    pub fn unplug_controller(&mut self, port: Port) -> Option<Box<dyn Controller>> {
+        let controller = match port {
+            Port::Left => self.controller_left.take(),
+            Port::Right => self.controller_right.take()
+        };
+
+        controller.map(|mut controller| {
+            controller.unplugged();
+            controller
+        })
+    }
+
+    /// Plugged controller.
+    ///
+    /// This function returns the controller plugged in the given port, if
+    /// any.
+    ///
+    pub fn controller(&self, port: Port) -> Option<&dyn Controller> {
+        match port {
+            Port::Left => self.controller_left.as_deref(),
+            Port::Right => self.controller_right.as_deref()
         }
     }
 
-    // pub fn unplug_controller(&mut self, slot: Player) -> dyn Controller {
+    /// Plugged controller (mutable).
+    ///
+    /// This function returns the controller plugged in the given port if it's
+    /// of the requested type, which is how the frontend manipulates it once
+    /// it's plugged. The changes are written to the port when the returned
+    /// value is dropped (see `ControllerMut`).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Joystick, Port, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # console.plug_controller(Port::Left, Box::new(Joystick::new()));
+    /// let mut joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+    /// joystick.press_button();
+    /// ```
+    ///
+    pub fn controller_mut<T: Controller>(&mut self, port: Port) -> Option<ControllerMut<'_, T>> {
+        ControllerMut::new(self, port)
+    }
 
-    // }
+    pub(crate) fn controller_slot(&mut self, port: Port) -> &mut Option<Box<dyn Controller>> {
+        match port {
+            Port::Left => &mut self.controller_left,
+            Port::Right => &mut self.controller_right
+        }
+    }
+
+    /// Apply an action to the joystick of the left port.
+    ///
+    /// This function sets the stick and the button of the joystick plugged in
+    /// the left port (the first player) according to the action; they remain
+    /// as they are for the next frames until another action is applied. It
+    /// panics if no joystick is plugged in the left port.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Action, Cartridge, Console, Joystick, Port, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # console.plug_controller(Port::Left, Box::new(Joystick::new()));
+    /// console.apply_action(Action::UpFire);
+    /// console.run_frame();
+    /// ```
+    ///
+    pub fn apply_action(&mut self, action: Action) {
+        let joystick = self.controller_mut::<Joystick>(Port::Left);
+        joystick.expect("a joystick must be plugged in the left port").perform_action(action);
+    }
+
+    /// Save the state of the console.
+    ///
+    /// This function returns a snapshot of the entire state of the console,
+    /// including the internal state of the plugged controllers, which can be
+    /// restored later with `load_state()`.
+    ///
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            pointer_counter: self.cpu.program_counter,
+            accumulator: self.cpu.accumulator,
+            x_register: self.cpu.x_register,
+            y_register: self.cpu.y_register,
+            flags: [
+                self.cpu.negative_flag,
+                self.cpu.overflow_flag,
+                self.cpu.break_flag,
+                self.cpu.decimal_flag,
+                self.cpu.interrupt_flag,
+                self.cpu.zero_flag,
+                self.cpu.carry_flag
+            ],
+            stack_pointer: self.cpu.stack_pointer,
+
+            tia: self.tia.registers,
+            ram: self.pia.ram,
+            pia: self.pia.ports,
+
+            timer_value: self.pia.timer_value,
+            timer_status: self.pia.timer_status,
+            timer_interval: self.pia.timer_interval,
+            timer_elapsed_clocks: self.pia.timer_elapsed_clocks,
+
+            cycles_count: self.cycles_count,
+            color_cycles_count: self.color_cycles_count,
+            instructions_count: self.instructions_count,
+            frames_count: self.frames_count,
+
+            old_players_graphics: self.tia.old_players_graphics,
+            old_ball_enable: self.tia.old_ball_enable,
+            players_position: self.tia.players_position,
+            missiles_position: self.tia.missiles_position,
+            ball_position: self.tia.ball_position,
+
+            scanline: self.tia.scanline,
+            scanline_cycle: self.tia.scanline_cycle,
+            is_vsync: self.tia.is_vsync,
+            cpu_halt: self.tia.cpu_halt,
+            motion: self.tia.motion,
+            is_hmove_blank: self.tia.is_hmove_blank,
+            hidden_first_copies: self.tia.hidden_first_copies,
+            bank: self.cartridge.bank(),
+            lines_since_vsync: self.tia.lines_since_vsync,
+            vsync_lines: self.tia.vsync_lines,
+            pending_vsync_lines: self.tia.pending_vsync_lines,
+            sync_roll: self.sync_roll,
+            is_sync_unstable: self.is_sync_unstable,
+            data_bus: self.data_bus,
+
+            input_charge_times: self.input_charge_times,
+            input_charges: self.input_charges,
+            is_dumped: self.is_dumped,
+
+            framebuffer: self.tia.framebuffer.clone(),
+            pending_framebuffer: self.tia.pending_framebuffer.clone(),
+
+            audio_channels: self.tia.audio_channels,
+            rng_state: self.rng.state(),
+            jam_address: self.cpu.jam_address,
+            switch_taps: [self.reset_tap_frames, self.select_tap_frames],
+            pia_control: self.pia.control(),
+
+            elapsed_time: self.elapsed_time,
+            remaining_cycles: self.remaining_cycles,
+            timer_block: self.pia.timer_block,
+
+            controllers: self.controller_states(),
+
+            thumbnail: None
+        }
+    }
+
+    /// Restore the state of the console.
+    ///
+    /// This function restores a state previously saved with `save_state()`,
+    /// including the internal state of the plugged controllers. It fails if
+    /// the state of a controller can't be restored (for instance, if the
+    /// plugged controllers aren't the same), or if the selected bank doesn't
+    /// exist in the cartridge.
+    ///
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), StateError> {
+        if state.bank >= self.cartridge.bank_count() {
+            return Err(StateError::new("bank is out of range"));
+        }
 
-    fn is_horizontal_blank(&self) -> bool {
-        self.scanline_cycle < 68
+        self.cpu.program_counter = state.pointer_counter;
+        self.cpu.accumulator = state.accumulator;
+        self.cpu.x_register = state.x_register;
+        self.cpu.y_register = state.y_register;
+        [
+            self.cpu.negative_flag,
+            self.cpu.overflow_flag,
+            self.cpu.break_flag,
+            self.cpu.decimal_flag,
+            self.cpu.interrupt_flag,
+            self.cpu.zero_flag,
+            self.cpu.carry_flag
+        ] = state.flags;
+        self.cpu.stack_pointer = state.stack_pointer;
+
+        self.tia.registers = state.tia;
+        self.pia.ram = state.ram;
+        self.pia.ports = state.pia;
+        // What was written to the unmapped locations isn't part of the state;
+        // it's forgotten, as a real console would.
+        self.dummy = [0; 8192];
+
+        self.pia.timer_value = state.timer_value;
+        self.pia.timer_status = state.timer_status;
+        self.pia.timer_interval = state.timer_interval;
+        self.pia.timer_elapsed_clocks = state.timer_elapsed_clocks;
+
+        self.cycles_count = state.cycles_count;
+        self.color_cycles_count = state.color_cycles_count;
+        self.instructions_count = state.instructions_count;
+        self.frames_count = state.frames_count;
+
+        self.tia.old_players_graphics = state.old_players_graphics;
+        self.tia.old_ball_enable = state.old_ball_enable;
+        self.tia.players_position = state.players_position;
+        self.tia.missiles_position = state.missiles_position;
+        self.tia.ball_position = state.ball_position;
+
+        self.tia.scanline = state.scanline;
+        self.tia.scanline_cycle = state.scanline_cycle;
+        self.tia.is_vsync = state.is_vsync;
+        self.tia.cpu_halt = state.cpu_halt;
+        self.tia.motion = state.motion;
+        self.tia.is_hmove_blank = state.is_hmove_blank;
+        self.tia.hidden_first_copies = state.hidden_first_copies;
+        self.cartridge.set_bank(state.bank);
+        self.tia.lines_since_vsync = state.lines_since_vsync;
+        self.tia.vsync_lines = state.vsync_lines;
+        self.tia.pending_vsync_lines = state.pending_vsync_lines;
+
+        // The roll is kept within the scanlines, even if the TV standard was
+        // switched since the state was saved.
+        self.sync_roll = state.sync_roll % self.tv_standard().scanlines();
+        self.is_sync_unstable = state.is_sync_unstable;
+
+        // The scanline count of the next frame is compared with the one of
+        // the state, not with the one before the state was loaded.
+        self.last_sync_lines = state.pending_vsync_lines;
+        self.data_bus = state.data_bus;
+
+        self.input_charge_times = state.input_charge_times;
+        self.input_charges = state.input_charges;
+        self.is_dumped = state.is_dumped;
+
+        self.tia.framebuffer = state.framebuffer.clone();
+        self.tia.pending_framebuffer = state.pending_framebuffer.clone();
+
+        // The audio is synthesized again from the restored state; the samples
+        // that weren't played yet belong to the abandoned timeline.
+        self.tia.audio_channels = state.audio_channels;
+        self.rng.set_state(state.rng_state);
+        self.cpu.jam_address = state.jam_address;
+        self.reset_tap_frames = state.switch_taps[0];
+        self.select_tap_frames = state.switch_taps[1];
+        self.pia.set_control(state.pia_control);
+        self.audio_buffer.clear();
+        self.pending_audio_buffer.clear();
+        self.audio_samples.clear();
+        self.bank_cycles.fill(0);
+        self.pending_bank_cycles.fill(0);
+
+        self.elapsed_time = state.elapsed_time;
+        self.remaining_cycles = state.remaining_cycles;
+        self.pia.timer_block = state.timer_block;
+
+        self.load_controller_states(&state.controllers)
     }
 
-    fn is_vertical_sync(&self) -> bool {
-        self.scanline < 3
+    /// State of the plugged controllers.
+    pub(crate) fn controller_states(&self) -> [Option<StateWriter>; 2] {
+        [
+            self.controller_left.as_ref().map(|controller| controller.save_state()),
+            self.controller_right.as_ref().map(|controller| controller.save_state())
+        ]
     }
 
-    fn is_vertical_blank(&self) -> bool {
-        self.scanline >= 3 && self.scanline < 3 + 37
+    /// Restore the state of the plugged controllers.
+    pub(crate) fn load_controller_states(&mut self, states: &[Option<StateWriter>; 2]) -> Result<(), StateError> {
+        let mut controllers = [&mut self.controller_left, &mut self.controller_right];
+        for (controller, state) in controllers.iter_mut().zip(states.iter()) {
+            match (controller.as_mut(), state) {
+                (Some(controller), Some(state)) => controller.load_state(state)?,
+                (None, None) => {},
+                _ => return Err(StateError::new("plugged controllers don't match the state"))
+            }
+        }
+
+        self.with_controllers(|controller, console| controller.apply(console));
+        Ok(())
     }
 
-    fn is_overscan(&self) -> bool {
-        self.scanline >= 3 + 37 + 192
+    fn with_controllers<F: FnMut(&mut dyn Controller, &mut Console)>(&mut self, mut function: F) {
+        // The controllers are temporarily taken out of the console so they can
+        // access it while being called.
+        if let Some(mut controller) = self.controller_left.take() {
+            function(controller.as_mut(), self);
+            self.controller_left = Some(controller);
+        }
+
+        if let Some(mut controller) = self.controller_right.take() {
+            function(controller.as_mut(), self);
+            self.controller_right = Some(controller);
+        }
     }
 
-    fn is_beam_drawing(&self) -> bool {
+    fn update_controllers(&mut self) {
+        self.with_controllers(|controller, console| controller.update(console));
+    }
 
-        // todo; rename this function
-        let a = self.scanline >= 3 + 37 && self.scanline < 3 + 37 + 192;
-        let b = !self.is_horizontal_blank();
+    /// Set the charge time of a pot input.
+    ///
+    /// This function sets the number of cycles it takes for the capacitor
+    /// attached to a pot input (INPT0 to INPT3) to charge and reach the
+    /// threshold voltage once it's no longer dumped to ground (bit 7 of
+    /// VBLANK). It's what determines the value read by the game, and the
+    /// position of a paddle for instance.
+    ///
+    /// If the charge time is `None`, the capacitor never charges and the input
+    /// always reads low; it's the case when nothing is plugged.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// // A homemade light sensor with a low resistance when it's sunny.
+    /// console.set_input_charge_time(0, Some(1500));
+    /// ```
+    ///
+    pub fn set_input_charge_time(&mut self, input: usize, cycles: Option<u32>) {
+        assert!(input < 4, "input must be one of the pot inputs (0 to 3)");
+        self.input_charge_times[input] = cycles;
+    }
 
-        a && b
+    /// Charge time of a pot input.
+    ///
+    /// This function returns the charge time of a pot input as set by
+    /// `set_input_charge_time()`.
+    ///
+    pub fn input_charge_time(&self, input: usize) -> Option<u32> {
+        assert!(input < 4, "input must be one of the pot inputs (0 to 3)");
+        self.input_charge_times[input]
     }
 
-    fn beam_position(&self) -> (usize, usize) { // return current normalized line and "pixel"
+    /// Drive the level of an input port.
+    ///
+    /// This function sets the level of the bit 7 of an input port (INPT0 to
+    /// INPT5) as seen by the game. It's mostly meant for the digital inputs
+    /// INPT4 and INPT5 (fire buttons) which are high when nothing grounds
+    /// them. Note that the level of a pot input is overwritten by its charge
+    /// if it has a charge time.
+    ///
+    pub fn set_input_level(&mut self, input: usize, level: bool) {
+        assert!(input < 6, "input must be one of the input ports (0 to 5)");
 
-        assert!(self.is_beam_drawing());
+        if self.input_level(input) != level {
+            self.record_input_change();
+        }
 
-        let line = self.scanline - (3 + 37);
-        let pixel = self.scanline_cycle - 68;
+        let value = &mut self.tia.registers[INPT0 as usize + input];
+        match level {
+            true  => *value |= 0b1000_0000,
+            false => *value &= 0b0111_1111
+        }
+    }
 
-        (line as usize, pixel as usize)
+    /// Level of an input port.
+    ///
+    /// This function returns the level of the bit 7 of an input port (INPT0 to
+    /// INPT5) as seen by the game.
+    ///
+    pub fn input_level(&self, input: usize) -> bool {
+        assert!(input < 6, "input must be one of the input ports (0 to 5)");
+        self.tia.registers[INPT0 as usize + input] & 0b1000_0000 != 0
     }
 
-    pub fn update_timer(&mut self) {
+    /// Drive a pin of the port A externally.
+    ///
+    /// This function sets the level of one of the 8 pins of the port A (SWCHA)
+    /// as seen by the game. Bit 4 to 7 are the pins of the left controller and
+    /// bit 0 to 3 are the pins of the right controller. If the game configured
+    /// the pin as an output (SWACNT), the console drives it and this function
+    /// has no effect.
+    ///
+    pub fn drive_port_a_pin(&mut self, pin: usize, level: bool) {
+        assert!(pin < 8, "pin can't be higher than 7");
 
+        let mask = 1 << pin;
+        if self.pia.ports[(SWACNT - SWCHA) as usize] & mask != 0 {
+            return
+        }
 
-        // When the elapsed clocks variable reaches 0, we must decrement the
-        // timer value.
-        self.timer_elapsed_clocks -= 1;
-        if self.timer_elapsed_clocks == 0 {
+        if self.port_a_pin(pin) != level {
+            self.record_input_change();
+        }
 
-            // If the timer value is 0, it's underflowing and we must update the
-            // timer status (bit 6 and 7).
-            if self.timer_value == 0 {
+        let value = &mut self.pia.ports[0];
+        match level {
+            true  => *value |= mask,
+            false => *value &= !mask
+        }
+    }
 
-                // The timer value reached 0, the timer is now entering the
-                // high speed decrement mode.
-                self.timer_interval = 1;
+    /// Level of a pin of the port A.
+    ///
+    /// This function returns the level of one of the 8 pins of the port A
+    /// (SWCHA), regardless of whether it's driven by the console or
+    /// externally.
+    ///
+    pub fn port_a_pin(&self, pin: usize) -> bool {
+        assert!(pin < 8, "pin can't be higher than 7");
+        self.pia.ports[0] & (1 << pin) != 0
+    }
 
-                // Update the timer status.
-                self.timer_status |= 0b_1100_0000;
-            }
+    /// Levels of the pins of a controller port.
+    ///
+    /// This function returns the levels of the signal pins of a controller
+    /// port as seen by the game (see `PortPins`), regardless of what drives
+    /// them.
+    ///
+    pub fn port_pins(&self, port: Port) -> PortPins {
+        let (first_pin, first_pot, trigger) = match port {
+            Port::Left => (4, 0, 4),
+            Port::Right => (0, 2, 5)
+        };
+
+        PortPins {
+            data: [0, 1, 2, 3].map(|index| self.port_a_pin(first_pin + index)),
+            trigger: self.input_level(trigger),
+            pots: [0, 1].map(|index| self.input_level(first_pot + index))
+        }
+    }
+
+    /// Drive the pins of a controller port.
+    ///
+    /// This function sets the levels of the signal pins of a controller port
+    /// (see `PortPins`), bypassing the controllers; it's meant for projects
+    /// bridging real controllers, which call it every frame. The data pins the
+    /// game configured as outputs aren't changed (see `drive_port_a_pin()`),
+    /// and the charge of the pot inputs of the port is disabled so their
+    /// levels remain as they're set (see `set_input_charge_time()`).
+    ///
+    /// Note that a controller plugged in the port drives the same pins; it
+    /// must be unplugged first, otherwise the levels are overwritten whenever
+    /// it changes.
+    ///
+    pub fn set_port_pins(&mut self, port: Port, pins: PortPins) {
+        let (first_pin, first_pot, trigger) = match port {
+            Port::Left => (4, 0, 4),
+            Port::Right => (0, 2, 5)
+        };
+
+        for (index, level) in pins.data.iter().enumerate() {
+            self.drive_port_a_pin(first_pin + index, *level);
+        }
+        self.set_input_level(trigger, pins.trigger);
+
+        for (index, level) in pins.pots.iter().enumerate() {
+            self.input_charge_times[first_pot + index] = None;
+            self.set_input_level(first_pot + index, *level);
+        }
+    }
 
-            // Decrement the timer value.
-            self.timer_value = self.timer_value.wrapping_sub(1);
+    fn update_input_charges(&mut self) {
+        // When the bit 7 of VBLANK is set, the capacitors of the pot inputs are
+        // dumped to ground and the inputs read low. Otherwise, they're slowly
+        // charging and read high once the threshold voltage is reached.
+        let is_dumped = self.tia.registers[VBLANK as usize] & 0b1000_0000 != 0;
 
-            // Adjust the elapsed clocks according to the current timer
-            // interval.
-            self.timer_elapsed_clocks = self.timer_interval;
+        // Some adapters use the dump signal to select a controller.
+        if is_dumped != self.is_dumped {
+            self.is_dumped = is_dumped;
+            self.with_controllers(|controller, console| controller.dump_changed(console, is_dumped));
         }
 
+        for input in 0..4 {
+            if is_dumped {
+                self.input_charges[input] = 0;
+            }
+            else {
+                self.input_charges[input] = self.input_charges[input].saturating_add(1);
+            }
 
+            if let Some(charge_time) = self.input_charge_times[input] {
+                let level = !is_dumped && self.input_charges[input] >= charge_time;
+                self.set_input_level(input, level);
+            }
+        }
     }
+
     pub fn execute_cycle(&mut self) {
 
 
-        // Update the timer unless it's 'blocked'. It's a little hack that we
-        // are forced to introduce because it would be inconvenient to know in
-        // advance how many cycles an instruction would take. We must not update
-        // the timer during the cycles that an instruction modifying the timer
-        // register is taking, otherwise the timer would be decrement
-        // prematurely.
-        if !self.timer_block {
-            self.update_timer();
-        }
+        // Update the timer unless it's 'blocked'.
+        self.pia.step();
 
         // Check for change in the VSYNC bit and adjust scanline accordingly if
         // it was switched off.
-        let vsync_bit = *self.memory(VSYNC) & 0b_0000_0010 > 0;
-        if self.is_vsync && vsync_bit == false { // Check for vsync being switched off
-            self.scanline = 2;
+        let was_vsync = self.tia.is_vsync;
+        self.tia.update_vertical_sync();
+
+        if self.tia.is_vsync && !was_vsync && self.events.has_subscribers() {
+            self.publish(Event::VsyncStart { frame: self.frames_count + 1, scanline: self.tia.scanline });
         }
-        self.is_vsync = vsync_bit;
+
+        self.update_input_charges();
 
         self.execute_color_cycle();
         self.execute_color_cycle();
@@ -477,39 +1351,55 @@ impl Console {
         self.color_cycles_count += 3;
     }
     pub fn execute_color_cycle(&mut self) {
+        let collisions = match self.events.has_subscribers() {
+            true => Some(self.collision_latches()),
+            false => None
+        };
 
-        // // Draw the current pixel if the beam is on a drawable area.
-        // if self.is_beam_drawing() {
-        //     let (line, pixel) = self.beam_position();
-        //     println!("drawing at {}, {}", line, pixel);
-
-        //     self.framebuffer[line][pixel] = (125, 125, 125);
-        // }
-
-        self.scanline_cycle += 1;
-        // println!("scanline cycle is increased");
-        if self.scanline_cycle >= HORIZONTAL_CYCLES {
+        let (sample, is_frame_completed) = self.tia.step();
 
-            // TODO; Trigger WSYNc perhaps releasing CPU halt.
-            self.cpu_halt = false;
+        if let Some(collisions) = collisions {
+            for (register, (before, after)) in (CXM0P..=CXPPMM).zip(collisions.iter().zip(self.collision_latches().iter())) {
+                if after & !before != 0 {
+                    self.publish(Event::CollisionLatched { register, bits: after & !before });
+                }
+            }
+        }
 
-            // println!("scanline is increased");
-            self.scanline += 1;
+        if let Some(sample) = sample {
+            self.audio_buffer.push(sample);
+            self.audio_samples.push_back(sample);
 
-            if self.scanline >= 3 + 37 && self.scanline < 3 + 37 + 192 {
-                let line = self.scanline - (3 + 37);
-                self.framebuffer[line as usize] = create_scanline(self);
+            // Drop the oldest samples if the frontend doesn't keep up.
+            if self.audio_samples.len() > MAX_QUEUED_AUDIO_SAMPLES {
+                self.audio_samples.pop_front();
             }
+        }
 
-            if self.scanline >= VERTICAL_LINES {
-
-                // clear out framebuffer  for debugging purpose
-                self.framebuffer = [[(0, 0, 0); 160]; 192];
+        if is_frame_completed {
+
+            // The frame is completed; the TIA keeps it aside until the next
+            // one is completed, and so are the audio samples.
+            self.pending_audio_buffer = std::mem::take(&mut self.audio_buffer);
+            std::mem::swap(&mut self.bank_cycles, &mut self.pending_bank_cycles);
+            self.bank_cycles.fill(0);
+            self.frames_count += 1;
+            self.update_sync_roll();
+            self.check_scanline_count();
+            self.check_watches();
+            self.record_checksum();
+
+            if let Some(timing) = self.input_timing.get_mut().as_mut() {
+                let next_timing = InputTiming::new(self.frames_count + 1);
+                self.pending_input_timing = Some(std::mem::replace(timing, next_timing));
+            }
 
-                self.scanline = 0;
+            if !self.is_running_ahead {
+                self.update_controllers();
             }
+            self.update_switch_taps();
 
-            self.scanline_cycle = 0;
+            self.publish(Event::FrameCompleted { frame: self.frames_count });
         }
     }
 
@@ -517,13 +1407,19 @@ impl Console {
 
         self.elapsed_time += elapsed_time;
 
-        while self.elapsed_time >= CYCLE_DURATION {
-            self.elapsed_time -= CYCLE_DURATION;
+        let cycle_duration = self.cycle_duration();
+        while self.elapsed_time >= cycle_duration {
+            self.elapsed_time -= cycle_duration;
             self.remaining_cycles += 1;
         }
 
         while self.remaining_cycles > 0 {
-            if !self.cpu_halt {
+            if self.is_stopped_by_jam() {
+                self.remaining_cycles = 0;
+                break
+            }
+
+            if self.is_cpu_running() {
 
                 let mut elapsed_cycles = self.execute_instruction();
                 self.remaining_cycles -= elapsed_cycles as isize;
@@ -533,20 +1429,21 @@ impl Console {
                     elapsed_cycles -= 1;
                 }
 
-                self.timer_block = false;
+                self.pia.timer_block = false;
             }
             else {
                 while self.remaining_cycles > 0 {
                     self.execute_cycle();
                     self.remaining_cycles -= 1;
 
-                    if !self.cpu_halt {
+                    if self.is_cpu_running() {
                         break
                     }
                 }
             }
         }
 
+        self.sample_rates();
     }
 
     /// Advance the simulation in time.
@@ -561,9 +1458,12 @@ impl Console {
     ///
     /// After this function is called, the audio and video components are
     /// updated and can be used to display an eventual new TV frame or play the
-    /// sounds on your side.
+    /// sounds on your side. It returns a summary of what happened during the
+    /// update.
     ///
-    pub fn update(&mut self, elapsed_time: Duration) {
+    pub fn update(&mut self, elapsed_time: Duration) -> UpdateSummary {
+
+        let start = self.summary_start();
 
         // Update our own elapsed time tracker.
         self.elapsed_time += elapsed_time;
@@ -572,8 +1472,9 @@ impl Console {
         // by the standard library, and it would likely result in poorer
         // performance anyway as modern machines run significantly faster than
         // the Atari 2600  (and thus the elapsed time is very small).
-        while self.elapsed_time >= CYCLE_DURATION {
-            self.elapsed_time -= CYCLE_DURATION;
+        let cycle_duration = self.cycle_duration();
+        while self.elapsed_time >= cycle_duration {
+            self.elapsed_time -= cycle_duration;
             self.remaining_cycles += 1;
         }
 
@@ -586,12 +1487,27 @@ impl Console {
         // cycles.
         while self.remaining_cycles >= 10 {
 
-            if !self.cpu_halt {
+            // A fatal jam stops the simulation for good; the remaining cycles
+            // are dropped.
+            if self.is_stopped_by_jam() {
+                self.remaining_cycles = 0;
+                break
+            }
+
+            if self.is_cpu_running() {
                 // When the CPU is not halted by the TIA, we simply execute a
                 // CPU instruction. If the TIA is halting the CPU after the
                 // execution of the instruction, we let the next iteration
                 // process the remaining cycles.
 
+                // Stop right before the instruction if there is a breakpoint;
+                // the remaining cycles are dropped as the simulation is
+                // expected to be paused.
+                if self.is_at_breakpoint() || self.is_stopped_by_register_write() {
+                    self.remaining_cycles = 0;
+                    break
+                }
+
                 // Execute the next instruction (and update the iterator).
                 let mut elapsed_cycles = self.execute_instruction();
                 self.remaining_cycles -= elapsed_cycles as isize;
@@ -603,13 +1519,14 @@ impl Console {
                     elapsed_cycles -= 1;
                 }
 
-                self.timer_block = false;
+                self.pia.timer_block = false;
             }
             else {
                 // When the CPU is halted, we run only TIA cycles until the CPU
                 // is released. As soon as it's release, we let the next
                 // iteration continue the job (as it will immediately start
-                // resume executing instructions).
+                // resume executing instructions). A jammed CPU is never
+                // released.
 
                 // For each remaining cycles to simulate, execute 3 TIA cycles.
                 while self.remaining_cycles > 0 {
@@ -618,7 +1535,7 @@ impl Console {
 
                     // If the CPU is release, we stop here and let the next
                     // iteration execute the next instruction.
-                    if !self.cpu_halt {
+                    if self.is_cpu_running() {
                         break
                     }
                 }
@@ -628,273 +1545,1389 @@ impl Console {
         // If remaining cycles was less than 0, we'd be ahead of the simulation
         // and this is a logical error.
         assert!(self.remaining_cycles >= 0);
-    }
 
-    fn wait_for_leading_edge_of_horizontal_blank(&mut self) {
-        // TODO; To be implemented.
-        self.cpu_halt = true;
+        self.sample_rates();
+        self.make_summary(start)
     }
 
-    fn reset_horizontal_sync_counter(&mut self) {
-        // TODO; To be implemented.
-        // panic!("not implemented yet");
-
-// 10h - RESP0 <strobe> - Reset player 0
-// 11h - RESP1 <strobe> - Reset player 1
-// 12h - RESM0 <strobe> - Reset missile 0
-// 13h - RESM1 <strobe> - Reset missile 1
-// 14h - RESBL <strobe> - Reset ball
-// Writing any value to these addresses sets the associated objects horizontal
-// position equal to the current position of the cathode ray beam, if the write
-// takes place anywhere within horizontal blanking then the position is set to
-// the left edge of the screen (plus a few pixels towards right: 3 pixels for P0/P1, and only 2 pixels for M0/M1/BL).
-// Note: Because of opcode execution times, it is usually necessary to adjust
-//the resulting position to the desired value by subsequently using the Horizontal Motion function.
-    }
+    /// Last completed frame.
+    ///
+    /// This function returns the last frame that was completely drawn by the
+    /// console. Until the first frame is completed, it returns a black frame
+    /// numbered 0.
+    ///
+    pub fn frame(&self) -> Frame {
+        let mut frame = Frame {
+            number: self.frames_count,
+            pixels: *self.tia.pending_framebuffer,
+            orientation: Orientation::default()
+        };
 
-    fn reset_position(&mut self, position: &mut u32, is_player: bool) {
-        if self.is_horizontal_blank() {
-            // If the strobe register is triggered during horizontal blanking,
-            // the position will become at the very left of the screen edge plus
-            // 3 pixels for players, and 2 pixels for missiles and the ball.
-            *position = if is_player { 3 } else { 2 };
-        }
-        else {
-            *position = self.beam_position().1 as u32;
+        if self.is_sync_roll_enabled && self.sync_roll != 0 {
+            self.roll_picture(&mut frame);
         }
-    }
 
-    fn reset_player_0(&mut self) {
-        // self.reset_position(&mut self.players_position[0], true);
+        frame
     }
 
-    fn reset_player_1(&mut self) {
-        // self.reset_position(&mut self.players_position[1], true);
+    /// Check whether the vertical sync is unstable.
+    ///
+    /// A TV locks on the vertical sync produced by the game to position the
+    /// picture; it can't when the game doesn't produce any (or not every
+    /// frame), or when the sync isn't close to the number of scanlines of the
+    /// TV standard (within 10 scanlines), and the picture rolls. This function
+    /// tells whether it was the case of the last completed frame. It's usually
+    /// a bug of the game, or a game that was made for another TV standard.
+    ///
+    pub fn is_sync_unstable(&self) -> bool {
+        self.is_sync_unstable
     }
 
-    fn reset_missile_0(&mut self) {
-        // self.reset_position(&mut self.missiles_position[0], false);
+    /// Number of scanlines of the vertical sync.
+    ///
+    /// This function returns the number of scanlines between the last two
+    /// vertical syncs, if the last one ended during the last completed
+    /// frame.
+    ///
+    pub fn sync_lines(&self) -> Option<u32> {
+        self.tia.pending_vsync_lines
     }
 
-    fn reset_missile_1(&mut self) {
-        // self.reset_position(&mut self.missiles_position[1], false);
+    /// Show the roll of the picture.
+    ///
+    /// By default, frames are always drawn as if the TV was locked on the
+    /// vertical sync. When the roll is shown, `frame()` returns the picture as
+    /// a TV displays it when the sync is unstable; it rolls vertically, along
+    /// with the black band of the vertical blank, until the sync becomes
+    /// stable again. That includes the first frames after the console is
+    /// turned on, before the game starts producing a vertical sync, like a TV
+    /// warming up.
+    ///
+    pub fn set_sync_roll(&mut self, is_enabled: bool) {
+        self.is_sync_roll_enabled = is_enabled;
     }
 
-    fn reset_ball(&mut self) {
-        // self.reset_position(&mut self.ball_position, false);
+    /// Check whether the roll of the picture is shown.
+    pub fn is_sync_roll_enabled(&self) -> bool {
+        self.is_sync_roll_enabled
     }
 
-    fn apply_horizontal_motion(&mut self) {
-        // TODO; To be implemented.
-        // panic!("not implemented yet");
-    }
+    fn update_sync_roll(&mut self) {
+        let lines = self.tv_standard().scanlines() as i32;
+        let roll = match self.tia.pending_vsync_lines {
+            Some(vsync_lines) if (vsync_lines as i32 - lines).abs() <= SYNC_TOLERANCE => None,
+            Some(vsync_lines) => Some(vsync_lines as i32 - lines),
+            None => Some(FREE_RUNNING_ROLL)
+        };
 
-    fn clear_horizontal_motion_registers(&mut self) {
-        // TODO; To be implemented.
-        // panic!("not implemented yet");
+        self.is_sync_unstable = roll.is_some();
+        self.sync_roll = match roll {
+            Some(roll) => (self.sync_roll as i32 + roll).rem_euclid(lines) as u32,
+            None => 0
+        };
     }
 
-    fn clear_collision_latches(&mut self) {
-        // Reset all collision-related bits to 0.
-        *self.memory_mut(CXM0P)  = 0x0000_0000;
-        *self.memory_mut(CXM1P)  = 0x0000_0000;
-        *self.memory_mut(CXP0FB) = 0x0000_0000;
-        *self.memory_mut(CXP1FB) = 0x0000_0000;
-        *self.memory_mut(CXM0FB) = 0x0000_0000;
-        *self.memory_mut(CXM1FB) = 0x0000_0000;
-        *self.memory_mut(CXBLPF) = 0x0000_0000;
-        *self.memory_mut(CXPPMM) = 0x0000_0000;
-    }
+    fn check_scanline_count(&mut self) {
+        let lines = match self.tia.pending_vsync_lines {
+            Some(lines) => lines,
+            None => return
+        };
 
-    #[allow(mutable_transmutes)]
-    pub(crate) fn memory<'a>(&self, mut index: u16) -> &'a u8 {
-        // Cannot address more than 8192 bytes because bit 13, 14 and 15 are
-        // ignored on the MOS 6507 (bus lines aren't attached).
-        index &= 0b0001_1111_1111_1111;
+        if let Some(previous_lines) = self.last_sync_lines.filter(|previous_lines| *previous_lines != lines) {
+            let is_silent = self.last_scanline_warning
+                .is_some_and(|frame| self.frames_count - frame < SCANLINE_WARNING_INTERVAL);
+
+            if is_silent {
+                self.suppressed_scanline_warnings += 1;
+            } else {
+                self.scanline_warnings.push(ScanlineWarning {
+                    frame: self.frames_count,
+                    scanlines: lines,
+                    previous_scanlines: previous_lines,
+                    suppressed: std::mem::take(&mut self.suppressed_scanline_warnings)
+                });
+                self.last_scanline_warning = Some(self.frames_count);
+            }
+        }
 
-        let reference = match index {
-            0x_00..=0x_3D => &self.tia[index as usize],
-            0x_80..=0x_FF => &self.ram[(index - 0x_80) as usize],
+        self.last_sync_lines = Some(lines);
+    }
 
-            // The PIA has 10 relevant memory locations but all timer-related
-            // locations are mapped to local values. Last 4 aren't holding any
-            // values and thus are mapped to dummy.
-            0x_0280..=0x_0283 => &self.pia[(index - 0x_0280) as usize],
-            0x_0284 => &self.timer_value,
-            0x_0285 => {
-                // Note: Technically, callers of this method usually have a
-                // mutable reference of the console, and the signature of this
-                // method should be changed to use `&mut self`. That said, it's
-                // nicer this way for several reasons.
+    fn roll_picture(&self, frame: &mut Frame) {
+        // The picture wraps around along with the scanlines that aren't
+        // visible, which show as a black band.
+        let lines = self.tv_standard().scanlines();
+        for (line, pixels) in frame.pixels.iter_mut().enumerate() {
+            let source = (line as u32 + PICTURE_TOP + self.sync_roll) % lines;
+            *pixels = match source.checked_sub(PICTURE_TOP) {
+                Some(source) if source < Frame::HEIGHT as u32 => self.tia.pending_framebuffer[source as usize],
+                _ => [(0, 0, 0); 160]
+            };
+        }
+    }
 
-                unsafe {
-                    // Whenever the INSTAT register is read, its 6th bit is reset.
-                    let mut_self = std::mem::transmute::<&Console, &mut Console>(self);
-                    mut_self.timer_status &= 0b1011_1111;
+    /// Sample rate of the audio (in Hertz).
+    ///
+    /// The console produces two samples per scanline; it's about 31.4 kHz and
+    /// it depends on the TV standard. Frontends usually resample it to the
+    /// rate of the audio device.
+    ///
+    pub fn audio_sample_rate(&self) -> f64 {
+        self.tv_standard().color_clock_frequency() / COLOR_CYCLES_PER_SAMPLE
+    }
+
+    /// Set the volume of the audio.
+    ///
+    /// This function scales the samples the console produces by the given
+    /// volume, which is clamped between 0.0 (silence) and 1.0 (the default).
+    /// It's a setting of the output, it isn't saved in the states.
+    ///
+    pub fn set_audio_volume(&mut self, volume: f32) {
+        self.tia.audio_volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Volume of the audio.
+    pub fn audio_volume(&self) -> f32 {
+        self.tia.audio_volume
+    }
+
+    /// Mute or unmute an audio channel.
+    ///
+    /// This function silences one of the two audio channels of the TIA (0 or
+    /// 1); the channel keeps running, it's only left out of the mix. Games
+    /// often play the music on one channel and the sound effects on the
+    /// other, so it's useful to hear them apart. It's a setting of the
+    /// output, it isn't saved in the states.
+    ///
+    pub fn set_audio_channel_muted(&mut self, channel: usize, is_muted: bool) {
+        assert!(channel < 2, "the TIA has only two audio channels");
+        self.tia.muted_audio_channels[channel] = is_muted;
+    }
+
+    /// Check whether an audio channel is muted.
+    pub fn is_audio_channel_muted(&self, channel: usize) -> bool {
+        assert!(channel < 2, "the TIA has only two audio channels");
+        self.tia.muted_audio_channels[channel]
+    }
+
+    /// Take the audio samples produced so far.
+    ///
+    /// This function returns the samples produced since it was last called,
+    /// between 0.0 (silence) and 1.0. They're queued until taken, up to about
+    /// one second worth of samples; the oldest ones are dropped beyond.
+    ///
+    /// Restoring a state discards the samples that weren't taken yet, as the
+    /// audio is synthesized again from the restored state.
+    ///
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.audio_samples.drain(..).collect()
+    }
+
+    /// Number of audio samples waiting to be taken.
+    pub fn queued_audio_samples(&self) -> usize {
+        self.audio_samples.len()
+    }
+
+    /// Audio samples of the last completed frame.
+    #[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+    pub(crate) fn frame_audio(&self) -> &[f32] {
+        &self.pending_audio_buffer
+    }
+
+    /// CPU cycles executed in each bank during the last completed frame.
+    ///
+    /// This function returns the number of CPU cycles spent executing the
+    /// instructions of each bank of the cartridge (see `current_bank()`)
+    /// during the last completed frame, indexed by bank; the cycles of an
+    /// instruction count for the bank it was fetched from. The cycles the CPU
+    /// spends halted (by WSYNC) don't count. It's meant for the frontends to
+    /// show how the banks are used, as a heat map for instance.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// console.run_frame();
+    /// for (bank, cycles) in console.frame_bank_cycles().iter().enumerate() {
+    ///     println!("bank {}: {:.1}%", bank, *cycles as f64 * 100.0 / 19_912.0);
+    /// }
+    /// ```
+    ///
+    pub fn frame_bank_cycles(&self) -> &[u64] {
+        &self.pending_bank_cycles
+    }
+
+    /// Last audio samples waiting to be taken (mutable).
+    ///
+    /// This function is used to process the samples the console just produced
+    /// (for instance, to fade them in).
+    ///
+    #[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+    pub(crate) fn last_audio_samples_mut(&mut self, count: usize) -> impl Iterator<Item = &mut f32> {
+        let skipped = self.audio_samples.len().saturating_sub(count);
+        self.audio_samples.iter_mut().skip(skipped)
+    }
+
+    /// Queue audio samples to be played.
+    ///
+    /// This function is used to play something else than what the console
+    /// produces (for instance, while rewinding).
+    ///
+    #[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+    pub(crate) fn queue_audio_samples(&mut self, samples: &[f32]) {
+        self.audio_samples.extend(samples.iter().copied());
+        while self.audio_samples.len() > MAX_QUEUED_AUDIO_SAMPLES {
+            self.audio_samples.pop_front();
+        }
+    }
+
+    /// Advance the simulation by one frame.
+    ///
+    /// This function runs the simulation, as fast as possible and regardless
+    /// of the time, until the next frame is completed (use `frame()` to
+    /// retrieve it) or a breakpoint is hit, then returns a summary of what
+    /// happened. It's meant for batch processing, tests and tools that don't
+    /// need to run at the pace of the real console.
+    ///
+    pub fn run_frame(&mut self) -> UpdateSummary {
+        let start = self.summary_start();
+
+        let frames_count = self.frames_count;
+        while self.frames_count == frames_count {
+            if self.is_at_breakpoint() || self.is_stopped_by_jam() || self.is_stopped_by_register_write() {
+                break
+            }
+
+            self.step();
+        }
+
+        self.sample_rates();
+        self.make_summary(start)
+    }
+
+    /// Advance the simulation by several frames with the same action.
+    ///
+    /// This function applies the action to the joystick of the left port (see
+    /// `apply_action()`), runs the given number of frames and returns the
+    /// last one, or the maximum of the last two ones, like the learning
+    /// environments skip frames. The steps are deterministic; the action is
+    /// applied to every frame. Breakpoints are ignored, and it stops early if
+    /// the CPU jams and it's fatal.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Action, Cartridge, Console, FramePooling, Joystick, Port, Region, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # console.plug_controller(Port::Left, Box::new(Joystick::new()));
+    /// let frame = console.step_action(Action::Fire, 4, FramePooling::Max);
+    /// let observation = frame.grayscale(Region::FULL, 84, 84);
+    /// ```
+    ///
+    pub fn step_action(&mut self, action: Action, frames: u32, pooling: FramePooling) -> Frame {
+        assert!(frames > 0, "at least one frame must be run");
+        self.apply_action(action);
+
+        let mut previous_frame = None;
+        let mut frame = self.frame();
+        for _ in 0..frames {
+            match self.frames().next() {
+                Some(next_frame) => previous_frame = Some(std::mem::replace(&mut frame, next_frame)),
+                None => break
+            }
+        }
+
+        match (pooling, previous_frame) {
+            (FramePooling::Max, Some(previous_frame)) => frame.max(&previous_frame),
+            _ => frame
+        }
+    }
+
+    /// Run the next frame ahead of time.
+    ///
+    /// This function runs the simulation until the next frame is completed,
+    /// then restores the console as it was and returns that frame. Nothing
+    /// else it did is observable; the audio, the watched RAM locations, the
+    /// checksums, the trace, the input timing, the scanline warnings and the
+    /// next summary are left untouched, the events aren't published, the
+    /// controllers aren't updated (they keep driving the ports as they are)
+    /// and the breakpoints are ignored. It fails if the state of a controller
+    /// can't be restored, which doesn't happen with the controllers of the
+    /// crate. It's used by the run-ahead mode of the emulator.
+    ///
+    #[cfg_attr(not(feature = "frontend"), allow(dead_code))]
+    pub(crate) fn run_ahead(&mut self) -> Result<Frame, StateError> {
+        let state = self.save_state();
+
+        // The subscribers don't hear about the frame that is thrown away, and
+        // the controllers don't act on it (a Kid Vid would start a track).
+        let is_muted = self.events.is_muted();
+        self.events.set_muted(true);
+        self.is_running_ahead = true;
+
+        let audio_buffer = self.audio_buffer.clone();
+        let pending_audio_buffer = self.pending_audio_buffer.clone();
+        let bank_cycles = self.bank_cycles.clone();
+        let pending_bank_cycles = self.pending_bank_cycles.clone();
+        let audio_samples = std::mem::take(&mut self.audio_samples);
+        let watches = self.watches.clone();
+        let dummy = self.dummy;
+        let unknown_opcodes = self.unknown_opcodes.len();
+        #[cfg(feature = "debugger")]
+        let opcode_stats = self.opcode_stats.clone();
+        let ram_changes = self.ram_changes.len();
+        let unknown_register_writes = self.unknown_register_writes.len();
+        let mapper_faults = self.mapper_faults.get_mut().len();
+        #[cfg(feature = "debugger")]
+        let crash_trace = self.crash_trace.clone();
+        #[cfg(feature = "debugger")]
+        let crash_report = self.crash_report.take();
+        let reported_unknown_registers = self.reported_unknown_registers;
+        let checksums = self.checksums.as_ref().map(Vec::len);
+        #[cfg(feature = "debugger")]
+        let trace = self.trace.take();
+        let tia_log = self.tia_log.take();
+        let input_timing = self.input_timing.take();
+        let pending_input_timing = self.pending_input_timing.take();
+        let last_sync_lines = self.last_sync_lines;
+        let last_scanline_warning = self.last_scanline_warning;
+        let suppressed_scanline_warnings = self.suppressed_scanline_warnings;
+        let scanline_warnings = self.scanline_warnings.len();
+
+        let frames_count = self.frames_count;
+        while self.frames_count == frames_count {
+            self.step();
+        }
+        let frame = self.frame();
+
+        let result = self.load_state(&state);
+
+        self.audio_buffer = audio_buffer;
+        self.pending_audio_buffer = pending_audio_buffer;
+        self.bank_cycles = bank_cycles;
+        self.pending_bank_cycles = pending_bank_cycles;
+        self.audio_samples = audio_samples;
+        self.watches = watches;
+        self.dummy = dummy;
+        self.unknown_opcodes.truncate(unknown_opcodes);
+        self.ram_changes.truncate(ram_changes);
+        self.unknown_register_writes.truncate(unknown_register_writes);
+        self.mapper_faults.get_mut().truncate(mapper_faults);
+        self.reported_unknown_registers = reported_unknown_registers;
+        self.is_unknown_register_break = false;
+        if let (Some(checksums), Some(length)) = (self.checksums.as_mut(), checksums) {
+            checksums.truncate(length);
+        }
+        #[cfg(feature = "debugger")]
+        {
+            self.opcode_stats = opcode_stats;
+            self.crash_trace = crash_trace;
+            self.crash_report = crash_report;
+            self.trace = trace;
+        }
+        self.tia_log = tia_log;
+        *self.input_timing.get_mut() = input_timing;
+        self.pending_input_timing = pending_input_timing;
+        self.last_sync_lines = last_sync_lines;
+        self.last_scanline_warning = last_scanline_warning;
+        self.suppressed_scanline_warnings = suppressed_scanline_warnings;
+        self.scanline_warnings.truncate(scanline_warnings);
+        self.events.set_muted(is_muted);
+        self.is_running_ahead = false;
+
+        result.map(|_| frame)
+    }
+
+    /// Add a breakpoint.
+    ///
+    /// This function adds a breakpoint at the given address; the simulation
+    /// stops right before the instruction at that address is executed and it's
+    /// reported in the update summary. Calling the update functions again
+    /// resumes the simulation.
+    ///
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Remove a breakpoint.
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Remove all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Subscribe to the events of the emulation.
+    ///
+    /// This function adds a callback receiving the events of the emulation
+    /// (see `Event`) as they happen, and returns the handle to unsubscribe it.
+    /// The events are only produced while there are subscribers.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Event, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// console.subscribe(|event| match event {
+    ///     Event::BankSwitched { from, to } => println!("bank {} -> {}", from, to),
+    ///     _ => ()
+    /// });
+    /// ```
+    ///
+    pub fn subscribe<F: FnMut(&Event) + Send + 'static>(&mut self, callback: F) -> SubscriptionId {
+        self.events.subscribe(callback)
+    }
+
+    /// Unsubscribe from the events of the emulation.
+    ///
+    /// This function removes a callback added with `subscribe()` and returns
+    /// whether it was found.
+    ///
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.events.unsubscribe(id)
+    }
+
+    fn publish(&mut self, event: Event) {
+        if self.events.has_subscribers() {
+            self.events.publish(event);
+        }
+    }
+
+    // The collision latches, from CXM0P to CXPPMM.
+    fn collision_latches(&self) -> [u8; 8] {
+        let mut latches = [0; 8];
+        latches.copy_from_slice(&self.tia.registers[CXM0P as usize..=CXPPMM as usize]);
+
+        latches
+    }
+
+    /// Watch a RAM location.
+    ///
+    /// This function adds a RAM location (from 0x80 to 0xFF) to the watched
+    /// locations; their values are compared at the end of each frame and the
+    /// changes are reported in the update summary. It's much cheaper than
+    /// intercepting each write, but intermediate values are missed.
+    ///
+    pub fn watch_ram(&mut self, address: u16) {
+        assert!((0x_80..=0x_FF).contains(&address), "address must be in the RAM");
+
+        let index = (address - 0x_80) as usize;
+        if !self.watches.iter().any(|(watched, _)| *watched == index) {
+            self.watches.push((index, self.pia.ram[index]));
+        }
+    }
+
+    /// Stop watching a RAM location.
+    pub fn unwatch_ram(&mut self, address: u16) {
+        self.watches.retain(|(index, _)| *index + 0x_80 != address as usize);
+    }
+
+    /// Value of a RAM location.
+    ///
+    /// This function returns the value of a RAM location (from 0x80 to 0xFF)
+    /// without the side effects of a read by the CPU.
+    ///
+    pub fn ram(&self, address: u16) -> u8 {
+        assert!((0x_80..=0x_FF).contains(&address), "address must be in the RAM");
+        self.pia.ram[(address - 0x_80) as usize]
+    }
+
+    /// Write a register of the TIA or of the PIA.
+    ///
+    /// This function writes a register (or a RAM location) as the CPU would,
+    /// the address being decoded the same way (mirrors included), and with
+    /// the same side effects; the strobes are triggered at the current
+    /// position of the beam, the writes to TIM64T and its siblings start the
+    /// timer, the write is recorded in the log of the TIA writes, etc. It's
+    /// for the poke panels of the frontends, to experiment with the registers
+    /// while the emulation is paused; the addresses of the cartridge aren't
+    /// registers (see `poke_assembly()` to patch the ROM).
+    ///
+    #[cfg_attr(all(feature = "test-rom", feature = "frontend"), doc = "```")]
+    #[cfg_attr(not(all(feature = "test-rom", feature = "frontend")), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Emulator, TEST_ROM};
+    /// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+    /// emulator.pause();
+    /// emulator.console_mut().poke(0x_09, 0x_84); // COLUBK
+    /// ```
+    ///
+    pub fn poke(&mut self, address: u16, value: u8) {
+        assert!(decode_write(address).0 != Component::Cartridge, "address must be a register or in the RAM");
+        Bus::write(self, address, value);
+    }
+
+    /// Assemble a program into the memory.
+    ///
+    /// This function assembles a program (see `assemble()`) at the given
+    /// address, writes it to the RAM or to the ROM of the cartridge, and
+    /// returns its size. It's the poke command of a debugger; it patches the
+    /// code of the game or drops a routine in the RAM while it's running. It
+    /// panics if the program doesn't fit entirely in the RAM or in the ROM.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// console.poke_assembly(0x_F000, "lda #$0E\nsta $09 ; COLUBK\njmp $F000")?;
+    /// # Ok::<(), atari_2600::AssemblerError>(())
+    /// ```
+    ///
+    pub fn poke_assembly(&mut self, address: u16, source: &str) -> Result<usize, AssemblerError> {
+        let bytes = assemble_at(address, source)?;
+
+        let component = |offset: usize| decode_read(address.wrapping_add(offset as u16)).0;
+        let is_writable = (0..bytes.len()).all(|offset| matches!(
+            (component(0), component(offset)),
+            (Component::Ram, Component::Ram) | (Component::Cartridge, Component::Cartridge)
+        ));
+        assert!(is_writable, "program must fit in the RAM or in the ROM");
+
+        for (offset, byte) in bytes.iter().enumerate() {
+            match decode_read(address.wrapping_add(offset as u16)) {
+                (Component::Ram, index) => self.pia.ram[(index - 0x_80) as usize] = *byte,
+                (_, index) => if let Ok(rom_byte) = self.cartridge.rom_byte_mut(self.current_bank(), index) {
+                    *rom_byte = *byte;
                 }
+            }
+        }
+
+        Ok(bytes.len())
+    }
+
+    /// Enable the verification mode.
+    ///
+    /// In this mode, the console records a checksum of the inputs and the
+    /// state of every completed frame. Comparing the checksums of two runs
+    /// with the same cartridge and inputs (see `Replay::verify()`) tells
+    /// whether the simulation is deterministic, and if not, from which frame
+    /// it differs. Checksums recorded previously are discarded.
+    ///
+    pub fn enable_checksums(&mut self) {
+        self.checksums = Some(Vec::new());
+    }
+
+    /// Disable the verification mode.
+    ///
+    /// This function stops recording checksums and returns the ones that were
+    /// recorded.
+    ///
+    pub fn disable_checksums(&mut self) -> Vec<FrameChecksum> {
+        self.checksums.take().unwrap_or_default()
+    }
+
+    /// Checksums recorded in the verification mode.
+    pub fn checksums(&self) -> &[FrameChecksum] {
+        self.checksums.as_deref().unwrap_or(&[])
+    }
+
+    fn record_checksum(&mut self) {
+        if self.checksums.is_none() {
+            return
+        }
+
+        let inputs = [
+            self.pia.ports[0], self.pia.ports[1], self.pia.ports[2], self.pia.ports[3],
+            self.tia.registers[INPT0 as usize], self.tia.registers[INPT1 as usize], self.tia.registers[INPT2 as usize],
+            self.tia.registers[INPT3 as usize], self.tia.registers[INPT4 as usize], self.tia.registers[INPT5 as usize]
+        ];
+        let input_hash = fnv1a(FNV_OFFSET, &inputs);
+
+        let registers = [
+            self.cpu.accumulator, self.cpu.x_register, self.cpu.y_register, self.cpu.stack_pointer,
+            self.cpu.negative_flag as u8, self.cpu.overflow_flag as u8, self.cpu.break_flag as u8,
+            self.cpu.decimal_flag as u8, self.cpu.interrupt_flag as u8, self.cpu.zero_flag as u8,
+            self.cpu.carry_flag as u8, self.pia.timer_value, self.pia.timer_status
+        ];
+        let mut state_hash = fnv1a(FNV_OFFSET, &self.cpu.program_counter.to_le_bytes());
+        state_hash = fnv1a(state_hash, &registers);
+        state_hash = fnv1a(state_hash, &self.tia.registers);
+        state_hash = fnv1a(state_hash, &self.pia.ram);
+        state_hash = fnv1a(state_hash, &self.cycles_count.to_le_bytes());
+        for line in self.tia.pending_framebuffer.iter() {
+            for (red, green, blue) in line.iter() {
+                state_hash = fnv1a(state_hash, &[*red, *green, *blue]);
+            }
+        }
+
+        let checksums = self.checksums.as_mut().unwrap();
+        let previous_hash = checksums.last().map_or(FNV_OFFSET, |checksum| checksum.rolling_hash);
+
+        let mut rolling_hash = fnv1a(previous_hash, &input_hash.to_le_bytes());
+        rolling_hash = fnv1a(rolling_hash, &state_hash.to_le_bytes());
+
+        checksums.push(FrameChecksum {
+            frame: self.frames_count,
+            input_hash,
+            state_hash,
+            rolling_hash
+        });
+    }
+
+    fn check_watches(&mut self) {
+        for (index, value) in self.watches.iter_mut() {
+            let new_value = self.pia.ram[*index];
+            if new_value != *value {
+                self.ram_changes.push(RamChange {
+                    frame: self.frames_count,
+                    address: *index as u16 + 0x_80,
+                    old_value: *value,
+                    new_value
+                });
+                *value = new_value;
+            }
+        }
+    }
+
+    /// Enable the measurements of the input timing.
+    ///
+    /// This function makes the console record where in each frame the inputs
+    /// change and where the game reads them (see `InputTiming`). The
+    /// measurements of a frame are available once it's completed.
+    ///
+    pub fn enable_input_timing(&mut self) {
+        let timing = self.input_timing.get_mut();
+        if timing.is_none() {
+            *timing = Some(InputTiming::new(self.frames_count + 1));
+        }
+    }
+
+    /// Disable the measurements of the input timing.
+    pub fn disable_input_timing(&mut self) {
+        *self.input_timing.get_mut() = None;
+        self.pending_input_timing = None;
+    }
+
+    /// Input timing of the last completed frame.
+    ///
+    /// This function returns the measurements of the last completed frame, if
+    /// they're enabled and a frame was completed since.
+    ///
+    pub fn input_timing(&self) -> Option<&InputTiming> {
+        self.pending_input_timing.as_ref()
+    }
+
+    fn record_input_change(&mut self) {
+        let position = (self.tia.scanline, self.tia.scanline_cycle);
+        if let Some(timing) = self.input_timing.get_mut().as_mut() {
+            timing.changes.push(position);
+        }
+    }
+
+    fn record_input_read(&self, register: u16) {
+        let read = InputRead {
+            register,
+            scanline: self.tia.scanline,
+            cycle: self.tia.scanline_cycle
+        };
+
+        if let Some(timing) = self.input_timing.borrow_mut().as_mut() {
+            timing.reads.push(read);
+        }
+    }
+
+    /// Emulated time since the console was turned on.
+    ///
+    /// This function returns the time it took the real console to execute the
+    /// cycles executed so far, as a timecode (see `Timecode`).
+    ///
+    pub fn timecode(&self) -> Timecode {
+        let standard = self.tv_standard();
+        let seconds = self.cycles_count as f64 * 3.0 / standard.color_clock_frequency();
+
+        Timecode::new(seconds, standard.frame_rate())
+    }
+
+    fn is_at_breakpoint(&mut self) -> bool {
+        if !self.is_cpu_running() || self.breakpoints.is_empty() {
+            return false
+        }
+
+        // The breakpoint that stopped the simulation must not stop it again
+        // when it's resumed.
+        if self.is_resuming_breakpoint {
+            self.is_resuming_breakpoint = false;
+            return false
+        }
+
+        if self.breakpoints.contains(&self.cpu.program_counter) {
+            self.breakpoint_hit = Some(self.cpu.program_counter);
+            self.publish(Event::BreakpointHit { address: self.cpu.program_counter });
+            self.is_resuming_breakpoint = true;
+            return true
+        }
+
+        false
+    }
+
+    /// Performance statistics.
+    ///
+    /// This function returns the counters of the simulation (cycles,
+    /// instructions, frames and position of the beam) and the rates at which
+    /// they progressed during the last second of real time, measured by the
+    /// update functions.
+    ///
+    pub fn telemetry(&self) -> Telemetry {
+        let (cycles_per_second, instructions_per_second, frames_per_second) = self.rate_meter.rates;
+
+        Telemetry {
+            cycles: self.cycles_count as u64,
+            color_cycles: self.color_cycles_count as u64,
+            instructions: self.instructions_count as u64,
+            frames: self.frames_count,
+            scanline: self.tia.scanline,
+            scanline_cycle: self.tia.scanline_cycle,
+            emulated_time: Duration::from_nanos((self.cycles_count * self.cycle_duration().as_nanos()) as u64),
+            timecode: self.timecode(),
+            cycles_per_second,
+            instructions_per_second,
+            frames_per_second
+        }
+    }
+
+    /// Inspect the state of the TIA.
+    ///
+    /// This function returns the current placement of the moving objects
+    /// (their horizontal position counters, their widths and copies as
+    /// configured by NUSIZx and CTRLPF) for debuggers to draw their bounding
+    /// boxes over the frame.
+    ///
+    pub fn tia_state(&self) -> TiaState {
+        let player = |player, index: usize| ObjectState::new(
+            self.tia.players_position[index],
+            player_width(&self.tia, player),
+            player_copies(&self.tia, player)
+        );
+
+        // The missiles are repeated like the players but they aren't
+        // stretched when the player is.
+        let missile = |player, index: usize| ObjectState::new(
+            self.tia.missiles_position[index],
+            missile_width(&self.tia, player),
+            match player_width(&self.tia, player) {
+                8 => player_copies(&self.tia, player),
+                _ => &[0]
+            }
+        );
+
+        TiaState {
+            players: [player(Player::One, 0), player(Player::Two, 1)],
+            missiles: [missile(Player::One, 0), missile(Player::Two, 1)],
+            ball: ObjectState::new(self.tia.ball_position, ball_width(&self.tia), &[0])
+        }
+    }
+
+    /// Enable or disable the capture of the layers.
+    ///
+    /// When enabled, the TIA records the layers of each pixel it draws (which
+    /// of the playfield and the moving objects cover it, before the priorities
+    /// are applied) so a frontend can draw each layer and the collisions
+    /// separately, frame after frame. It's disabled by default as it slows
+    /// the emulation down; disabling it discards the captured layers.
+    ///
+    pub fn set_layer_capture(&mut self, is_enabled: bool) {
+        if is_enabled {
+            if self.tia.layers.is_none() {
+                self.tia.layers = Some(Box::new([[PixelLayers::default(); 160]; 192]));
+            }
+        }
+        else {
+            self.tia.layers = None;
+            self.tia.pending_layers = None;
+        }
+    }
+
+    /// Check whether the layers are captured.
+    pub fn is_capturing_layers(&self) -> bool {
+        self.tia.layers.is_some()
+    }
+
+    /// Layers of the last completed frame.
+    ///
+    /// This function returns the layers of the pixels of the last completed
+    /// frame, line by line (like the pixels of `frame()`), if they're
+    /// captured (see `set_layer_capture()`); the frame completed right after
+    /// the capture is enabled is only partly captured.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # const PLAYFIELD_COLOR: (u8, u8, u8) = (0, 0, 255);
+    /// # const COLLISION_COLOR: (u8, u8, u8) = (255, 0, 0);
+    /// # fn draw_pixel(_x: usize, _y: usize, _color: (u8, u8, u8)) {}
+    /// console.set_layer_capture(true);
+    /// console.run_frame();
+    /// console.run_frame();
+    ///
+    /// for (y, line) in console.frame_layers().unwrap().iter().enumerate() {
+    ///     for (x, layers) in line.iter().enumerate() {
+    ///         if layers.playfield {
+    ///             draw_pixel(x, y, PLAYFIELD_COLOR);
+    ///         }
+    ///         if layers.collisions().iter().any(|bits| *bits != 0) {
+    ///             draw_pixel(x, y, COLLISION_COLOR);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    pub fn frame_layers(&self) -> Option<&[[PixelLayers; 160]]> {
+        self.tia.pending_layers.as_deref().map(|layers| &layers[..])
+    }
+
+    /// Emulate the open bus of the TIA read registers.
+    ///
+    /// The TIA only drives the high bits of its read registers (bit 7 and 6 of
+    /// the collision latches, bit 7 of the input ports) and the other bits keep
+    /// whatever was left on the data bus, usually the address of the register
+    /// (the last byte of the instruction). By default, the undriven bits read
+    /// as 0; in strict mode, they read as the data bus, which some games depend
+    /// on when they AND or BIT these registers.
+    ///
+    pub fn set_open_bus(&mut self, is_open_bus: bool) {
+        self.is_open_bus = is_open_bus;
+    }
+
+    /// Check whether the open bus of the TIA read registers is emulated.
+    pub fn is_open_bus(&self) -> bool {
+        self.is_open_bus
+    }
+
+    /// Random number generator.
+    ///
+    /// This function returns the generator used by the stochastic features of
+    /// the console; it's seeded with 0 unless another seed is set.
+    ///
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    /// Random number generator (mutable).
+    ///
+    /// Scripts and tests can use it to generate random values (to fuzz the
+    /// inputs for instance) that are reproducible from the seed of the
+    /// console.
+    ///
+    pub fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Seed the random number generator.
+    ///
+    /// This function restarts the generator with another seed; the entire run
+    /// of the console is reproducible from it.
+    ///
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Fill the RAM with random values.
+    ///
+    /// The content of the RAM is undefined when the console is powered on;
+    /// it's zeroed by default, but it can be randomized (from the generator
+    /// of the console) to catch games that don't initialize it.
+    ///
+    pub fn randomize_ram(&mut self) {
+        self.rng.fill(&mut self.pia.ram);
+    }
+
+    /// Start tracing the executed instructions.
+    ///
+    /// This function writes a line to the writer for each instruction that
+    /// is about to be executed and passes the filter; the line contains the
+    /// frame, the position of the beam, the bank and the address, the bytes of
+    /// the instruction, and the registers and flags of the CPU. An ongoing
+    /// trace is replaced (and its errors are discarded).
+    ///
+    /// ```text
+    ///      1  40  68 0:F000 85 09      A:0E X:00 Y:00 SP:FF P:nv-bdIZc
+    /// ```
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn start_trace<W: Write + Send + 'static>(&mut self, writer: W, filter: TraceFilter) {
+        self.trace = Some(Tracer::new(Box::new(writer), filter));
+    }
+
+    /// Stop tracing the executed instructions.
+    ///
+    /// This function flushes the writer and returns the number of traced
+    /// instructions, or the first error that occurred while writing the
+    /// trace (the trace stops being written after an error). It returns 0 if
+    /// there is no ongoing trace.
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn stop_trace(&mut self) -> io::Result<u64> {
+        match self.trace.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(0)
+        }
+    }
+
+    /// Check whether the executed instructions are traced.
+    #[cfg(feature = "debugger")]
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Start logging the writes to the TIA.
+    ///
+    /// This function starts recording the writes to the registers of the TIA,
+    /// timed in color cycles, along with the current position of the beam
+    /// (see `TiaLog`). An ongoing log is discarded.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// console.start_tia_log();
+    /// console.run_frame();
+    /// let log = console.stop_tia_log().unwrap();
+    /// assert!(log.replay(console.tv_standard()).last().unwrap().pixels == console.frame().pixels);
+    /// ```
+    ///
+    pub fn start_tia_log(&mut self) {
+        let log = TiaLog::new(self.tia.scanline, self.tia.scanline_cycle);
+        self.tia_log = Some((self.color_cycles_count, log));
+    }
+
+    /// Stop logging the writes to the TIA.
+    ///
+    /// This function returns the writes logged since `start_tia_log()` was
+    /// called, or nothing if there is no ongoing log.
+    ///
+    pub fn stop_tia_log(&mut self) -> Option<TiaLog> {
+        self.tia_log.take().map(|(_, log)| log)
+    }
+
+    /// Keep the last instructions for the crash reports.
+    ///
+    /// This function sets the number of instructions executed last that are
+    /// included in the crash reports (see `CrashReport`); none by default,
+    /// since capturing them slows the simulation down.
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn set_crash_trace_length(&mut self, length: usize) {
+        self.crash_trace_length = length;
+        while self.crash_trace.len() > length {
+            self.crash_trace.pop_front();
+        }
+    }
+
+    /// Number of instructions kept for the crash reports.
+    #[cfg(feature = "debugger")]
+    pub fn crash_trace_length(&self) -> usize {
+        self.crash_trace_length
+    }
+
+    /// Take the report of the last crash.
+    ///
+    /// This function returns the report the console made when the CPU jammed
+    /// while jams are fatal (see `set_jam_fatal()`), if any; the update
+    /// summaries report the jam.
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn take_crash_report(&mut self) -> Option<CrashReport> {
+        self.crash_report.take()
+    }
+
+    /// Run a function catching the crashes of the emulation.
+    ///
+    /// This function runs the given function with the console and returns
+    /// its result, or a crash report if the emulator panicked meanwhile (an
+    /// internal assertion failed). The console is left as it was when it
+    /// panicked, which may be inconsistent; it's meant to be saved and
+    /// reported, not to keep running.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use std::path::Path;
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// if let Err(report) = console.catch_crash(|console| console.run_frame()) {
+    ///     report.write_to_directory(Path::new("crash"))?;
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn catch_crash<R, F: FnOnce(&mut Console) -> R>(&mut self, function: F) -> Result<R, Box<CrashReport>> {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(|| function(self))) {
+            Ok(result) => return Ok(result),
+            Err(payload) => payload
+        };
+
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => String::from("unknown error")
+        };
+
+        Err(Box::new(self.make_crash_report(CrashCause::Panic { message })))
+    }
+
+    #[cfg(feature = "debugger")]
+    fn make_crash_report(&self, cause: CrashCause) -> CrashReport {
+        CrashReport {
+            cause,
+            timecode: self.timecode(),
+            rom_md5: self.cartridge.md5(),
+            state: self.save_state(),
+            trace: self.crash_trace.iter().map(TraceEntry::to_string).collect(),
+            screenshot: self.frame()
+        }
+    }
+
+    fn read_tia_register(&self, index: u16) -> u8 {
+        let driven_bits = Tia::driven_bits(index);
+
+        let value = self.tia.read(index);
+        if self.is_open_bus {
+            value | (self.data_bus & !driven_bits)
+        }
+        else {
+            value
+        }
+    }
+
+    /// Set the clock.
+    ///
+    /// This function sets the source of real time used to measure the rates
+    /// of the telemetry; it's the clock of the system by default.
+    ///
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock + Send + Sync>) {
+        self.clock = clock;
+    }
 
-                &self.timer_status
-            },
-            0x_0294..=0x_0297 => &self.dummy[index as usize],
+    fn sample_rates(&mut self) {
+        let counters = (self.cycles_count, self.instructions_count, self.frames_count);
+        self.rate_meter.sample(self.clock.now(), counters);
+    }
 
-            // This portion of the memory is mapped to the ROM on the cartridge
-            // but it's varying from cartridge to cartridge.
-            0x_1000..=0x_1FFF => &self.cartridge.memory[(index - 0x_1000) as usize],
-
-            // Adressing an irrelevant memory location, just returning 0; it's
-            // legal and it doesn't matter.
-            //
-            // TODO; Perhaps log this message, and also it could be a mapped
-            // memory which is not supported yet by this emulator.
-            _ => &self.dummy[index as usize]
-            // _ => &self.dummy
-        };
+    fn summary_start(&self) -> (u128, u128, u64) {
+        (self.instructions_count, self.cycles_count, self.frames_count)
+    }
 
-        unsafe {
-            std::mem::transmute(reference)
+    fn make_summary(&mut self, start: (u128, u128, u64)) -> UpdateSummary {
+        let (instructions_count, cycles_count, frames_count) = start;
+
+        UpdateSummary {
+            instructions: (self.instructions_count - instructions_count) as u64,
+            cycles: (self.cycles_count - cycles_count) as u64,
+            frames: self.frames_count - frames_count,
+            unknown_opcodes: std::mem::take(&mut self.unknown_opcodes),
+            ram_changes: std::mem::take(&mut self.ram_changes),
+            breakpoint: self.breakpoint_hit.take(),
+            jam: self.cpu.jam_address,
+            scanline_warnings: std::mem::take(&mut self.scanline_warnings),
+            unknown_register_writes: std::mem::take(&mut self.unknown_register_writes),
+            mapper_faults: self.mapper_faults.take()
         }
     }
 
-    pub(crate) fn memory_mut<'a>(&mut self, mut index: u16) -> &'a mut u8 {
-
-        // Cannot address more than 8192 bytes because bit 13, 14 and 15 are
-        // ignored on the MOS 6507 (bus lines aren't attached).
-        index &= 0b0001_1111_1111_1111;
-
-        let reference = match index {
-            0x_00..=0x_3D => {
-                match index {
-                    0x_02 => self.wait_for_leading_edge_of_horizontal_blank(),
-                    0x_03 => self.reset_horizontal_sync_counter(),
-                    0x_10 => self.reset_player_0(),
-                    0x_11 => self.reset_player_1(),
-                    0x_12 => self.reset_missile_0(),
-                    0x_13 => self.reset_missile_1(),
-                    0x_14 => self.reset_ball(),
-                    0x_2A => self.apply_horizontal_motion(),
-                    0x_2B => self.clear_horizontal_motion_registers(),
-                    0x_2C => self.clear_collision_latches(),
-                    _ => ()
-                }
+    /// Iterate over the upcoming frames.
+    ///
+    /// This function returns an iterator that advances the simulation one
+    /// frame at a time and yields them (see `run_frame()`).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Frame, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # fn display_frame(_frame: Frame) {}
+    /// for frame in console.frames().take(60) {
+    ///     display_frame(frame);
+    /// }
+    /// ```
+    ///
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames::new(self)
+    }
 
-                &mut self.tia[index as usize]
-            },
-            0x_80..=0x_FF => &mut self.ram[(index - 0x_80) as usize],
-
-            // The PIA has 10 relevant memory locations but all timer-related
-            // locations are mapped to local values. Last 4 aren't holding any
-            // values and thus are mapped to dummy.
-            0x_0280..=0x_0283 => &mut self.pia[(index - 0x_0280) as usize],
-            0x_0284 => {
-                // I'm not sure if it's legal to write to this register
-                // directly. Usually it's done via one of TIM1T, TIM8T, TIM64T
-                // or T1024T registers. What would the side effect be ?
-                println!("fishy ROM warning; is it legal to write to INTIM register ?");
-
-                &mut self.timer_value
-            },
-            0x_0285 => {
-                // Whenever the INSTAT register is read, its 6th bit is reset.
-                self.timer_status &= 0b1011_1111;
+    #[cfg(test)]
+    pub(crate) fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
 
-                &mut self.timer_status
-            },
-            0x_0294..=0x_0297 => {
-                // Adjust the timer interval accordingly.
-                self.timer_interval = match index {
-                    0x_0294 => 1,
-                    0x_0295 => 8,
-                    0x_0296 => 64,
-                    0x_0297 => 1024,
-                    _ => panic!("foo")
-                };
+    /// The CPU of the console.
+    ///
+    /// This function returns the processor of the console, to inspect its
+    /// registers and its status flags.
+    ///
+    pub fn cpu(&self) -> &Cpu6507 {
+        &self.cpu
+    }
 
-                self.timer_block = true;
+    /// Address of the next instruction.
+    ///
+    /// This function returns the value of the program counter, which is the
+    /// address of the instruction the CPU is about to execute.
+    ///
+    pub fn program_counter(&self) -> u16 {
+        self.cpu.program_counter
+    }
 
-                // Whenever register TIM1T, TIM8T, TIM64T and T1024T are
-                // written, it resets the 7th bit of INSTAT register.
-                *self.memory_mut(INSTAT) &= 0b0111_1111;
+    /// Bank of the cartridge the CPU is executing.
+    ///
+    /// This function returns the bank of the cartridge currently selected by
+    /// the bank switching (the F8, F6 and F4 schemes are emulated); it's
+    /// always the first bank for the ROMs of 4K and less.
+    ///
+    pub fn current_bank(&self) -> usize {
+        self.cartridge.bank()
+    }
 
-                self.timer_elapsed_clocks = 1;
+    /// Address of the opcode that jammed the CPU.
+    ///
+    /// The undocumented KIL opcodes jam the CPU; it stops executing
+    /// instructions until the console is turned off, while the TIA and the
+    /// PIA keep running. This function returns the address of the KIL opcode
+    /// if the CPU is jammed.
+    ///
+    pub fn jam_address(&self) -> Option<u16> {
+        self.cpu.jam_address
+    }
 
-                // When those registers are written, it's actually updating the
-                // value of the INTIM register (which is mapped to our local
-                // value).
-                &mut self.timer_value
-            },
+    /// Number of times each opcode was executed.
+    ///
+    /// This function returns the opcodes counted since the console was
+    /// created or since `reset_opcode_stats()` was called (see
+    /// `OpcodeStats`); loading a state doesn't reset them.
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn opcode_stats(&self) -> &OpcodeStats {
+        &self.opcode_stats
+    }
 
-            // This portion of the memory is mapped to the ROM on the cartridge
-            // but it's varying from cartridge to cartridge.
-            0x_F000..=0x_FFFF => &mut self.cartridge.memory[(index - 0x_F000) as usize],
-            // 0x_1000..=0x_1FFF => &mut self.cartridge.memory[(index - 0x_1000) as usize],
-
-            // Adressing an irrelevant memory location, just returning 0; it's
-            // legal and it doesn't matter.
-            //
-            // TODO; Perhaps log this message, and also it could be a mapped
-            // memory which is not supported yet by this emulator.
-            _ => &mut self.dummy[index as usize]
-            // _ => &mut self.dummy
-        };
+    /// Reset the counts of the executed opcodes.
+    #[cfg(feature = "debugger")]
+    pub fn reset_opcode_stats(&mut self) {
+        self.opcode_stats = OpcodeStats::new();
+    }
 
-        unsafe {
-            std::mem::transmute(reference)
-        }
+    /// Check whether the CPU is jammed.
+    pub fn is_jammed(&self) -> bool {
+        self.cpu.jam_address.is_some()
     }
 
-    /// Value pointed by the pointer counter.
+    /// Treat a jam of the CPU as a fatal error.
     ///
-    /// This function returns the pointed value by the pointer counter (also
-    /// called the instruction pointer).
+    /// By default, the simulation keeps running when the CPU jams, like on
+    /// the hardware. When jams are fatal, the simulation stops as soon as the
+    /// CPU jams instead; the update functions don't advance it anymore and
+    /// the jam is reported in their summary.
     ///
-    #[inline]
-    pub(crate) fn pointed_value(&self) -> &u8 {
-        &self.memory(self.pointer_counter)
+    pub fn set_jam_fatal(&mut self, is_fatal: bool) {
+        self.is_jam_fatal = is_fatal;
     }
 
-    /// Brief description.
+    /// Check whether a jam of the CPU is treated as a fatal error.
+    pub fn is_jam_fatal(&self) -> bool {
+        self.is_jam_fatal
+    }
+
+    /// Change the policy for the writes to the unknown registers.
+    ///
+    /// This function changes how the writes to the locations of the TIA
+    /// without register are reported (see `UnknownRegisterPolicy`); the
+    /// locations reported already are forgotten. It's meant for developers to
+    /// find out when their game (or the emulator) touches a register that
+    /// doesn't exist.
     ///
-    /// This function does something that isn't documented yet.
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, UnknownRegisterPolicy, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// console.set_unknown_register_policy(UnknownRegisterPolicy::Break);
     ///
-    #[inline]
-    pub(crate) fn pointed_value_mut(&mut self) -> &mut u8 {
-        self.memory_mut(self.pointer_counter)
+    /// let summary = console.run_frame();
+    /// for write in &summary.unknown_register_writes {
+    ///     println!("{}", write);
+    /// }
+    /// ```
+    ///
+    pub fn set_unknown_register_policy(&mut self, policy: UnknownRegisterPolicy) {
+        self.unknown_register_policy = policy;
+        self.reported_unknown_registers = 0;
     }
 
-    /// Brief description.
+    /// Policy for the writes to the unknown registers.
+    pub fn unknown_register_policy(&self) -> UnknownRegisterPolicy {
+        self.unknown_register_policy
+    }
+
+    // Report an access to the cartridge the mapper can't serve, once per
+    // address until the next summary (a game running off the ROM hits the
+    // same addresses over and over).
+    fn report_mapper_fault(&self, fault: MapperFault) {
+        let mut faults = self.mapper_faults.borrow_mut();
+        if faults.iter().all(|other_fault| other_fault.address != fault.address) {
+            faults.push(fault);
+        }
+    }
+
+    fn report_unknown_register_write(&mut self, address: u16, value: u8) {
+        let bit = 1 << address;
+        let is_reported = match self.unknown_register_policy {
+            UnknownRegisterPolicy::Ignore => false,
+            UnknownRegisterPolicy::LogOnce => self.reported_unknown_registers & bit == 0,
+            UnknownRegisterPolicy::LogAlways | UnknownRegisterPolicy::Break => true
+        };
+
+        if is_reported {
+            // The CPU is taken out of the console while it executes the
+            // instruction, therefore its program counter is the address of
+            // the instruction.
+            self.unknown_register_writes.push(UnknownRegisterWrite {
+                address,
+                value,
+                program_counter: self.cpu.program_counter
+            });
+            self.reported_unknown_registers |= bit;
+            self.is_unknown_register_break = self.unknown_register_policy == UnknownRegisterPolicy::Break;
+        }
+    }
+
+    fn is_stopped_by_register_write(&mut self) -> bool {
+        std::mem::take(&mut self.is_unknown_register_break)
+    }
+
+    fn is_cpu_running(&self) -> bool {
+        !self.tia.cpu_halt && self.cpu.jam_address.is_none()
+    }
+
+    fn is_stopped_by_jam(&self) -> bool {
+        self.is_jam_fatal && self.cpu.jam_address.is_some()
+    }
+
+    /// Execute the next instruction.
     ///
-    /// This function does something that isn't documented yet.
+    /// This function executes the next instruction (if the CPU is halted by
+    /// the TIA, it runs the TIA until the CPU is released first) regardless of
+    /// the time and the breakpoints, and returns the number of elapsed cycles.
+    /// If the CPU is jammed, it runs a single cycle instead. It's meant for
+    /// debuggers stepping through the code.
     ///
-    #[inline]
-    pub(crate) fn advance_pointer(&mut self) -> u8 {
-        self.pointer_counter += 1;
-        *self.memory(self.pointer_counter)
+    pub fn step_instruction(&mut self) -> u32 {
+        let mut elapsed_cycles = 0;
+        while self.tia.cpu_halt {
+            elapsed_cycles += self.step();
+        }
+
+        elapsed_cycles + self.step()
     }
 
-    /// Brief description.
+    /// Advance the simulation by one step.
     ///
-    /// This function does something that isn't documented yet.
+    /// This function executes the next instruction, or a single cycle if the
+    /// CPU is halted by the TIA (or jammed), and returns the number of elapsed
+    /// cycles.
     ///
-    pub(crate) fn push_value(&mut self, value: u8) {
-        // Stack is only 128 bytes long (merged with the RAM), if it were to
-        // go below, it would touch the TIA mapped registers. This would likely
-        // be a bug in the ROM.
-        assert!(self.stack_pointer != 0x_79, "cannot push value; stack is full");
+    fn step(&mut self) -> u32 {
+        if self.is_cpu_running() {
+            let elapsed_cycles = self.execute_instruction();
+            for _ in 0..elapsed_cycles {
+                self.execute_cycle();
+            }
+
+            self.pia.timer_block = false;
 
-        *self.memory_mut(self.stack_pointer as u16) = value;
-        self.stack_pointer -= 1;
+            elapsed_cycles
+        }
+        else {
+            self.execute_cycle();
 
+            1
+        }
     }
 
-    /// Brief description.
-    ///
-    /// This function does something that isn't documented yet.
-    ///
-    pub(crate) fn pop_value(&mut self) -> u8 {
-        assert!(self.stack_pointer != 0x_FF, "cannot pop value; stack is empty");
+    pub(crate) fn memory(&self, address: u16) -> u8 {
+        // The emulator reads its own TIA write registers back at their
+        // address, therefore they aren't decoded as the read registers they
+        // are mirroring on the bus.
+        let (component, index) = match address {
+            0x_00..=0x_2C => (Component::Tia, address),
+            _ => decode_read(address)
+        };
+
+        if (index == SWCHA || (INPT0..=INPT5).contains(&index)) && self.input_timing.borrow().is_some() {
+            self.record_input_read(index);
+        }
+
+        match component {
+            Component::Tia => match index {
+                // The value read is composed on the fly because only some bits
+                // of the read registers are driven by the TIA.
+                0x_30..=0x_3D => self.read_tia_register(index),
+                _ => self.tia.registers[index as usize]
+            },
+            Component::Ram => self.pia.ram[(index - 0x_80) as usize],
+
+            // The PIA has 10 relevant memory locations; reading the timer
+            // registers through the bus also updates its status (see the
+            // `Bus` implementation), but not peeking them.
+            Component::Pia => self.pia.peek(index),
 
-        self.stack_pointer += 1;
-        *self.memory(self.stack_pointer as u16)
+            // This portion of the memory is mapped to the ROM on the cartridge
+            // but it's varying from cartridge to cartridge.
+            Component::Cartridge => match self.cartridge.rom_byte(self.current_bank(), address) {
+                Ok(byte) => *byte,
+                Err(fault) => {
+                    self.report_mapper_fault(fault);
+                    self.dummy[index as usize]
+                }
+            },
+
+            // Nothing drives the data bus; just returning 0, it's legal and it
+            // doesn't matter.
+            Component::OpenBus => self.dummy[index as usize]
+        }
+    }
+
+    pub(crate) fn memory_mut(&mut self, address: u16) -> &mut u8 {
+        let (component, index) = decode_write(address);
+
+        match component {
+            Component::Tia => match index {
+                // The read registers of the TIA can't be written, but they're
+                // read this way by read-modify instructions.
+                0x_30..=0x_3D => {
+                    self.read_latch = self.read_tia_register(index);
+                    &mut self.read_latch
+                },
+                0x_00..=0x_2C => self.tia.register_mut(index),
+
+                // There is no register at these locations.
+                _ => &mut self.dummy[index as usize]
+            },
+            Component::Ram => &mut self.pia.ram[(index - 0x_80) as usize],
+
+            // The PIA has 10 relevant memory locations; writing the timer
+            // registers starts the timer.
+            Component::Pia => match index {
+                0x_0280..=0x_0283 => &mut self.pia.ports[(index - 0x_0280) as usize],
+                0x_0294..=0x_029F => self.pia.start_timer(index),
+                _ => {
+                    // The value written to the edge detect control of PA7
+                    // doesn't matter.
+                    self.pia.set_edge_detect(index);
+                    &mut self.dummy[index as usize]
+                }
+            },
+
+            // The ROM of the cartridge can't be written, but it's read this way
+            // by some instructions.
+            Component::Cartridge => {
+                self.read_latch = match self.cartridge.rom_byte(self.current_bank(), address) {
+                    Ok(byte) => *byte,
+                    Err(fault) => {
+                        self.report_mapper_fault(fault);
+                        self.dummy[index as usize]
+                    }
+                };
+                &mut self.read_latch
+            },
+            Component::OpenBus => &mut self.dummy[index as usize]
+        }
     }
 
     /// Execute the next instruction.
@@ -902,72 +2935,56 @@ impl Console {
     /// Long description to be written.
     ///
     pub(crate) fn execute_instruction(&mut self) -> u32 {
-        let opcode = *self.pointed_value();
-        self.advance_pointer();
-
-        let cycles = match opcode {
-            0x_69 | 0x_65 | 0x_75 | 0x_6D | 0x_7D | 0x_79 | 0x_61 | 0x_71 => adc_instruction(self, opcode),
-            0x_29 | 0x_25 | 0x_35 | 0x_2D | 0x_3D | 0x_39 | 0x_21 | 0x_31 => and_instruction(self, opcode),
-            0x_0A | 0x_06 | 0x_16 | 0x_0E | 0x_1E => asl_instruction(self, opcode),
-            0x_90 => bcc_instruction(self, opcode),
-            0x_B0 => bcs_instruction(self, opcode),
-            0x_F0 => beq_instruction(self, opcode),
-            0x_24 | 0x_2C => bit_instruction(self, opcode),
-            0x_30 => bmi_instruction(self, opcode),
-            0x_D0 => bne_instruction(self, opcode),
-            0x_10 => bpl_instruction(self, opcode),
-            0x_00 => brk_instruction(self, opcode),
-            0x_50 => bvc_instruction(self, opcode),
-            0x_70 => bvs_instruction(self, opcode),
-            0x_18 => clc_instruction(self, opcode),
-            0x_D8 => cld_instruction(self, opcode),
-            0x_58 => cli_instruction(self, opcode),
-            0x_B8 => clv_instruction(self, opcode),
-            0x_C9 | 0x_C5 | 0x_D5 | 0x_CD | 0x_DD | 0x_D9 | 0x_C1 | 0x_D1 => cmp_instruction(self, opcode),
-            0x_E0 => cpx_instruction(self, opcode),
-            0x_C0 | 0x_C4 | 0x_CC => cpy_instruction(self, opcode),
-            0x_C6 | 0x_D6 | 0x_CE | 0x_DE => dec_instruction(self, opcode),
-            0x_CA => dex_instruction(self, opcode),
-            0x_88 => dey_instruction(self, opcode),
-            0x_49 | 0x_45 | 0x_55 | 0x_4D | 0x_5D | 0x_59 | 0x_41 | 0x_51 => eor_instruction(self, opcode),
-            0x_E6 | 0x_F6 | 0x_EE | 0x_FE => inc_instruction(self, opcode),
-            0x_E8 => inx_instruction(self, opcode),
-            0x_C8 => iny_instruction(self, opcode),
-            0x_4C | 0x_6C => jmp_instruction(self, opcode),
-            0x_20 => jsr_instruction(self, opcode),
-            0x_A9 | 0x_A5 | 0x_B5 | 0x_AD | 0x_BD | 0x_B9 | 0x_A1 | 0x_B1 => lda_instruction(self, opcode),
-            0x_A2 | 0x_A6 | 0x_B6 | 0x_AE | 0x_BE => ldx_instruction(self, opcode),
-            0x_A0 | 0x_A4 | 0x_B4 | 0x_AC | 0x_BC => ldy_instruction(self, opcode),
-            0x_4A | 0x_46 | 0x_56 | 0x_4E | 0x_5E => lsr_instruction(self, opcode),
-            0x_EA => nop_instruction(self, opcode),
-            0x_09 | 0x_05 | 0x_15 | 0x_0D | 0x_1D | 0x_19 | 0x_01 | 0x_11 => ora_instruction(self, opcode),
-            0x_48 => pha_instruction(self, opcode),
-            0x_08 => php_instruction(self, opcode),
-            0x_68 => pla_instruction(self, opcode),
-            0x_28 => plp_instruction(self, opcode),
-            0x_2A | 0x_26 | 0x_36 | 0x_2E | 0x_3E => rol_instruction(self, opcode),
-            0x_6A | 0x_66 | 0x_76 | 0x_6E | 0x_7E => ror_instruction(self, opcode),
-            0x_40 => rti_instruction(self, opcode),
-            0x_60 => rts_instruction(self, opcode),
-            0x_E9 | 0x_E5 | 0x_F5 | 0x_ED | 0x_FD | 0x_F9 | 0x_E1 | 0x_F1 => sbc_instruction(self, opcode),
-            0x_38 => sec_instruction(self, opcode),
-            0x_F8 => sed_instruction(self, opcode),
-            0x_78 => sei_instruction(self, opcode),
-            0x_85 | 0x_95 | 0x_8D | 0x_9D | 0x_99 | 0x_81 | 0x_91 => sta_instruction(self, opcode),
-            0x_86 | 0x_96 | 0x_8E => stx_instruction(self, opcode),
-            0x_84 | 0x_94 | 0x_8C => sty_instruction(self, opcode),
-            0x_AA => tax_instruction(self, opcode),
-            0x_A8 => tay_instruction(self, opcode),
-            0x_BA => tsx_instruction(self, opcode),
-            0x_8A => txa_instruction(self, opcode),
-            0x_9A => txs_instruction(self, opcode),
-            0x_98 => tya_instruction(self, opcode),
-            _ => {
-                println!("unknown instruction");
+        #[cfg(feature = "debugger")]
+        if let Some(mut tracer) = self.trace.take() {
+            tracer.trace(self);
+            self.trace = Some(tracer);
+        }
+
+        #[cfg(feature = "debugger")]
+        if self.crash_trace_length > 0 {
+            if self.crash_trace.len() == self.crash_trace_length {
+                self.crash_trace.pop_front();
+            }
+            self.crash_trace.push_back(TraceEntry::capture(self));
+        }
+
+        let bank = self.current_bank();
+        #[cfg(feature = "debugger")]
+        let was_jammed = self.is_jammed();
+
+        #[cfg(feature = "debugger")]
+        if !was_jammed {
+            let opcode = self.memory(self.cpu.program_counter);
+            self.opcode_stats.record(opcode);
+        }
+
+        // The CPU is taken out of the console while it executes the
+        // instruction, since the console is the bus it operates on.
+        let mut cpu = self.cpu;
+        let cycles = match cpu.step(self) {
+            Ok(cycles) => cycles,
+            Err(UnknownOpcode { address, opcode }) => {
+                self.unknown_opcodes.push((address, opcode));
+                self.publish(Event::UnknownOpcode { address, opcode });
                 0
-                // panic!("unknown instruction")
             }
         };
+        self.cpu = cpu;
+
+        #[cfg(feature = "debugger")]
+        if let Some(address) = self.cpu.jam_address.filter(|_| self.is_jam_fatal && !was_jammed) {
+            self.crash_report = Some(self.make_crash_report(CrashCause::Jam { address }));
+        }
+
+        if bank >= self.bank_cycles.len() {
+            self.bank_cycles.resize(bank + 1, 0);
+        }
+        self.bank_cycles[bank] += cycles as u64;
+
+        if self.events.has_subscribers() && self.current_bank() != bank {
+            self.publish(Event::BankSwitched { from: bank, to: self.current_bank() });
+        }
 
         // Increase instructions count (for debugging and analysis).
         self.instructions_count += 1;
@@ -1015,9 +3032,75 @@ impl Console {
     // }
 }
 
+impl Bus for Console {
+    fn read(&mut self, address: u16) -> u8 {
+        // The CPU reads the read registers of the TIA where the emulator reads
+        // its write registers back (see `memory()`), and reading the timer
+        // registers clears the flags of the timer, which peeking them doesn't.
+        match decode_read(address) {
+            (Component::Tia, index) => self.memory(index),
+            (Component::Pia, index) if !(SWCHA..=SWBCNT).contains(&index) => self.pia.read(index),
+            (Component::Cartridge, _) => {
+                self.cartridge.access(address);
+                self.memory(address)
+            },
+            _ => self.memory(address)
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match decode_write(address) {
+            (Component::Tia, index @ 0x_00..=0x_2C) => {
+                if let Some((start, log)) = self.tia_log.as_mut() {
+                    let cycle = (self.color_cycles_count - *start) as u64;
+                    log.writes.push(TiaWrite { cycle, address: index, value });
+                }
+                self.tia.write(index, value)
+            },
+            (Component::Ram, index) | (Component::Pia, index) => self.pia.write(index, value),
+            (Component::Tia, index) => {
+                self.report_unknown_register_write(index, value);
+                *self.memory_mut(address) = value
+            },
+            (Component::Cartridge, _) => {
+                self.cartridge.access(address);
+                *self.memory_mut(address) = value
+            },
+            _ => *self.memory_mut(address) = value
+        }
+    }
+
+    fn fetch(&mut self, address: u16) -> u8 {
+        // The byte that was fetched is left on the data bus.
+        if let (Component::Cartridge, _) = decode_read(address) {
+            self.cartridge.access(address);
+        }
+        self.data_bus = self.memory(address);
+        self.data_bus
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::joystick::Direction;
+    use crate::assembler::assemble;
+    use crate::rom_builder::RomBuilder;
+    use crate::analysis::BankSwitching;
+    use crate::kidvid::KidVid;
+    use crate::fixtures;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_kernel() {
+        // A standard kernel draws stable frames of 262 scanlines.
+        let mut console = Console::new(fixtures::rom("kernel_01.bin"));
+        for _ in 0..5 {
+            assert!(console.run_frame().scanline_warnings.is_empty());
+        }
+        assert_eq!(console.sync_lines(), Some(262));
+        assert!(!console.is_sync_unstable());
+    }
 
     #[test]
     fn test_subroutine() {
@@ -1025,19 +3108,20 @@ mod test {
 
         // Create a ROM to put the console into different states and check if
         // the states are correct.
-        let mut rom = vec![
-            0x_A9, 0x_00,        // Load accumulator with value 0
-            0x_20, 0x_42, 0x_F1, // Jump to subroutine at location 0x_4221
-            0x_A9, 0x_00,        // Load accumulator with value 0
-        ];
-        // TODO; Here I'm accounting for the beginning of the ROM but it should
-        // be handled in a better way. F000 F142
-        rom.resize(0x_0142 + 3 + 1, 0x_FF); // the +1 is because it needs to advance pointer at the end of execution of instruction
-        rom[0x_0142 + 0] = 0x_A9; // The subroutine loads accumulator with value 42...
-        rom[0x_0142 + 1] = 0x_42;
-        rom[0x_0142 + 2] = 0x_60; // ... then return to the caller
-
-        let cartridge = Cartridge::new(rom);
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                lda #0
+                jsr $F142
+                lda #0
+            ")
+            // The subroutine loads the accumulator with 0x42, then returns to
+            // the caller.
+            .code(0x_F142, "
+                lda #$42
+                rts
+            ")
+            .build()
+            .unwrap();
 
         // Create the console and advance the simulation slightly forward to
         // avoid being on the cycle edges.
@@ -1046,15 +3130,721 @@ mod test {
 
         // Execute the ROM step by step with checking at relevant places.
         console.update_accurate(CYCLE_DURATION * 2); // load accumulator with value 0
-        assert_eq!(console.accumulator, 0);
+        assert_eq!(console.cpu.accumulator, 0);
 
         console.update_accurate(CYCLE_DURATION * 6); // jump to subroutine
         console.update_accurate(CYCLE_DURATION * 2); // load accumulator with value 42
-        assert_eq!(console.accumulator, 0x_42);
+        assert_eq!(console.cpu.accumulator, 0x_42);
 
         console.update_accurate(CYCLE_DURATION * 6); // return to the caller
         console.update_accurate(CYCLE_DURATION * 2); // load accumulator with value 0
-        assert_eq!(console.accumulator, 0);
+        assert_eq!(console.cpu.accumulator, 0);
+    }
+
+    #[test]
+    fn test_tia_strobe_registers() {
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                lda #$FF
+                sta $2A ; HMOVE
+                sta $2C ; CXCLR
+                sta $12 ; RESM0
+                lda $2A
+                ldx $2C
+                ldy $12
+            ")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        for _ in 0..7 {
+            console.execute_instruction();
+        }
+
+        // The written value isn't kept, by the registers nor on the bus.
+        assert_eq!(console.memory(HMOVE), 0);
+        assert_eq!(console.memory(CXCLR), 0);
+        assert_eq!(console.memory(RESM0), 0);
+        assert_eq!(console.cpu.accumulator, 0);
+        assert_eq!(console.cpu.x_register, console.tia.read(INPT4));
+        assert_eq!(console.cpu.y_register, 0);
+
+        // And a read-modify-write instruction triggers them too.
+        console.tia.registers[CXM0P as usize] = 0b1100_0000;
+        console.cpu.program_counter = 0x_F100;
+        console.cartridge_mut().memory[0x_100..0x_102].copy_from_slice(&[0x_E6, 0x_2C]); // INC CXCLR
+        console.execute_instruction();
+        assert_eq!(console.tia.read(CXM0P), 0);
+        assert_eq!(console.memory(CXCLR), 0);
+    }
+
+    #[test]
+    fn test_tia_read_registers() {
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                lda $30
+                and $32
+                bit $36
+            ")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        console.tia.registers[CXM0P as usize] = 0b1111_1111;
+        console.tia.registers[CXP0FB as usize] = 0b1111_1111;
+        console.tia.registers[CXBLPF as usize] = 0b1111_1111;
+
+        // Only the driven bits are read.
+        console.execute_instruction();
+        assert_eq!(console.cpu.accumulator, 0b1100_0000);
+        console.execute_instruction();
+        assert_eq!(console.cpu.accumulator, 0b1100_0000);
+
+        // Writing them has no effect.
+        *console.memory_mut(CXM0P) = 0b0000_0000;
+        assert_eq!(console.memory(CXM0P), 0b1100_0000);
+
+        // In strict mode, the undriven bits read as the data bus; the address
+        // of the register.
+        console.cpu.program_counter = 0x_F000;
+        console.set_open_bus(true);
+        console.execute_instruction();
+        assert_eq!(console.cpu.accumulator, 0b1111_0000);
+        console.execute_instruction();
+        assert_eq!(console.cpu.accumulator, 0b1111_0000 & 0b1111_0010);
+
+        console.execute_instruction();
+        assert!(!console.cpu.zero_flag);
+        assert!(console.cpu.negative_flag);
+        assert!(!console.cpu.overflow_flag);
+
+        console.tia.clear_collision_latches();
+        console.set_open_bus(false);
+        assert_eq!(console.memory(CXM0P), 0b0000_0000);
+    }
+
+    #[test]
+    fn test_port() {
+        assert_eq!(Port::from(Player::One), Port::Left);
+        assert_eq!(Port::from(Player::Two), Port::Right);
+
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.plug_controller(Player::Two.into(), Box::new(Joystick::new()));
+        assert!(console.controller(Port::Left).is_none());
+        assert!(console.controller_mut::<Joystick>(Port::Right).is_some());
+    }
+
+    #[test]
+    fn test_tia_state() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        // Objects reset during horizontal blanking are placed at the left
+        // edge of the screen.
+        console.tia.scanline_cycle = 10;
+        *console.memory_mut(RESP1) = 0;
+        *console.memory_mut(RESM1) = 0;
+
+        // Otherwise, they are placed at the position of the beam.
+        console.tia.scanline_cycle = 68 + 40;
+        *console.memory_mut(RESP0) = 0;
+        console.tia.scanline_cycle = 68 + 150;
+        *console.memory_mut(RESM0) = 0;
+        console.tia.scanline_cycle = 68 + 100;
+        *console.memory_mut(RESBL) = 0;
+
+        // Three close copies of player 0 with missiles of 4 pixels, a quad
+        // sized player 1 and a ball of 8 pixels.
+        *console.memory_mut(NUSIZ0) = 0b0010_0011;
+        *console.memory_mut(NUSIZ1) = 0b0000_0111;
+        *console.memory_mut(CTRLPF) = 0b0011_0000;
+
+        let state = console.tia_state();
+        assert_eq!(state.players[0], ObjectState { position: 40, width: 8, copies: vec![40, 56, 72] });
+        assert_eq!(state.players[1], ObjectState { position: 3, width: 32, copies: vec![3] });
+        assert_eq!(state.missiles[0], ObjectState { position: 150, width: 4, copies: vec![150, 6, 22] });
+        assert_eq!(state.missiles[1], ObjectState { position: 2, width: 1, copies: vec![2] });
+        assert_eq!(state.ball, ObjectState { position: 100, width: 8, copies: vec![100] });
+    }
+
+    #[test]
+    fn test_frame_layers() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.run_frame();
+        assert!(!console.is_capturing_layers());
+        assert!(console.frame_layers().is_none());
+
+        // The last bit of PF2 covers the pixels 76 to 79 on the left side, and
+        // the playfield has priority over the other objects.
+        *console.memory_mut(PF2) = 0b1000_0000;
+        *console.memory_mut(CTRLPF) = 0b0000_0101;
+
+        console.set_layer_capture(true);
+        assert!(console.is_capturing_layers());
+        console.run_frame();
+        console.run_frame();
+
+        let layers = console.frame_layers().unwrap();
+        assert_eq!(layers.len(), 192);
+        assert!(layers.iter().all(|line| line[75] == PixelLayers::default()));
+        assert!(layers.iter().all(|line| line[76].playfield && line[79].playfield));
+        assert!(layers.iter().all(|line| line[80].playfield));
+
+        console.set_layer_capture(false);
+        assert!(console.frame_layers().is_none());
+    }
+
+    #[test]
+    fn test_input_ports() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        // The digital inputs read high until they're grounded.
+        assert!(console.input_level(4));
+        assert!(console.input_level(5));
+        console.set_input_level(4, false);
+        assert_eq!(console.memory(INPT4) & 0b1000_0000, 0);
+
+        // The pot input charges after the capacitor is no longer dumped.
+        console.set_input_charge_time(0, Some(10));
+        *console.memory_mut(VBLANK) = 0b1000_0000;
+        console.execute_cycle();
+        assert!(!console.input_level(0));
+
+        *console.memory_mut(VBLANK) = 0b0000_0000;
+        for _ in 0..9 {
+            console.execute_cycle();
+        }
+        assert!(!console.input_level(0));
+
+        console.execute_cycle();
+        assert!(console.input_level(0));
+        assert_eq!(console.memory(INPT0) & 0b1000_0000, 0b1000_0000);
+
+        // The pin of port A can be driven unless it's an output.
+        console.drive_port_a_pin(7, true);
+        assert_eq!(console.memory(SWCHA), 0b1000_0000);
+
+        *console.memory_mut(SWACNT) = 0b0100_0000;
+        console.drive_port_a_pin(6, true);
+        assert!(!console.port_a_pin(6));
+    }
+
+    #[test]
+    fn test_poke_assembly() {
+        let mut rom = assemble("start: jmp start").unwrap();
+        rom.resize(4096, 0x_EA);
+        let mut console = Console::new(Cartridge::new(rom));
+
+        // Replace the game loop with a routine in the RAM that counts the
+        // loops in the first byte of the RAM.
+        assert_eq!(console.poke_assembly(0x_F000, "jmp $0081").unwrap(), 3);
+        assert_eq!(console.poke_assembly(0x_0081, "loop: inc $80\njmp loop").unwrap(), 5);
+        assert_eq!(console.ram(0x_81), 0x_E6);
+
+        console.run_frame();
+        assert!(console.ram(0x_80) > 0);
+
+        assert_eq!(console.poke_assembly(0x_F000, "foo").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_sync_roll() {
+        // A gray background and a vertical sync every 3 + 256 + N scanlines.
+        let sync_rom = |lines: u8| {
+            let mut rom = assemble(&format!("
+                    lda #$0E
+                    sta $09  ; COLUBK
+                start:
+                    lda #2
+                    sta $00  ; VSYNC
+                    sta $02  ; WSYNC
+                    sta $02
+                    sta $02
+                    lda #0
+                    sta $00
+                    ldx #0
+                first_loop:
+                    sta $02
+                    dex
+                    bne first_loop
+                    ldx #{}
+                second_loop:
+                    sta $02
+                    dex
+                    bne second_loop
+                    jmp start
+            ", lines)).unwrap();
+            rom.resize(4096, 0x_EA);
+            rom
+        };
+
+        // The first frames roll until the vertical sync is measured.
+        let mut console = Console::new(Cartridge::new(sync_rom(3)));
+        console.set_sync_roll(true);
+        console.run_frame();
+        assert!(console.is_sync_unstable());
+        assert!(console.frame().pixels != *console.tia.pending_framebuffer);
+
+        for _ in 0..3 {
+            console.run_frame();
+        }
+        assert!(!console.is_sync_unstable());
+        assert_eq!(console.sync_lines(), Some(262));
+        assert!(console.frame().pixels == *console.tia.pending_framebuffer);
+
+        // A sync that is too far from the TV standard makes the picture roll
+        // by the difference, along with the vertical blank.
+        let mut console = Console::new(Cartridge::new(sync_rom(30)));
+        console.set_sync_roll(true);
+        for _ in 0..4 {
+            console.run_frame();
+        }
+        assert!(console.is_sync_unstable());
+        assert_eq!(console.sync_lines(), Some(289));
+
+        let roll = console.sync_roll;
+        console.run_frame();
+        assert_eq!(console.sync_roll, roll + 27);
+
+        let frame = console.frame();
+        let black_lines = frame.pixels.iter().filter(|line| line[0] == (0, 0, 0)).count();
+        assert!(black_lines > 0 && black_lines < Frame::HEIGHT);
+
+        // The roll isn't shown unless it's enabled.
+        console.set_sync_roll(false);
+        assert!(console.frame().pixels == *console.tia.pending_framebuffer);
+
+        // Without vertical sync, the TV runs freely.
+        let mut rom = assemble("lda #$0E\nsta $09\nloop: jmp loop").unwrap();
+        rom.resize(4096, 0x_EA);
+        let mut console = Console::new(Cartridge::new(rom));
+        console.set_sync_roll(true);
+        console.run_frame();
+        console.run_frame();
+        assert!(console.is_sync_unstable());
+        assert_eq!(console.sync_lines(), None);
+
+        let frame = console.frame();
+        let black_lines = frame.pixels.iter().filter(|line| line[0] == (0, 0, 0)).count();
+        assert_eq!(black_lines, 14);
+
+        // The measurement of the sync is restored with the states; a state
+        // taken once the sync is stable doesn't roll when it's loaded by a
+        // console which is still warming up, and the other way around.
+        let mut console = Console::new(Cartridge::new(sync_rom(3)));
+        console.set_sync_roll(true);
+        console.run_frame();
+        let warming_state = console.save_state();
+        for _ in 0..3 {
+            console.run_frame();
+        }
+        let stable_state = console.save_state();
+
+        let mut other_console = Console::new(Cartridge::new(sync_rom(3)));
+        other_console.set_sync_roll(true);
+        other_console.run_frame();
+        assert!(other_console.is_sync_unstable());
+
+        other_console.load_state(&stable_state).unwrap();
+        assert!(!other_console.is_sync_unstable());
+        assert_eq!(other_console.sync_lines(), Some(262));
+        assert!(other_console.frame().pixels == *other_console.tia.pending_framebuffer);
+
+        other_console.run_frame();
+        assert!(!other_console.is_sync_unstable());
+        assert!(other_console.frame().pixels == *other_console.tia.pending_framebuffer);
+
+        console.load_state(&warming_state).unwrap();
+        assert!(console.is_sync_unstable());
+        assert_eq!(console.sync_lines(), None);
+        assert!(console.frame().pixels != *console.tia.pending_framebuffer);
+    }
+
+    #[test]
+    fn test_run_ahead_controllers() {
+        // The game starts the tape of a Kid Vid right away.
+        let mut rom = assemble("
+                lda #1
+                sta $0281 ; SWACNT
+                sta $0280 ; SWCHA
+            loop:
+                jmp loop
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let tracks = Arc::new(Mutex::new(Vec::new()));
+        let tracks_clone = tracks.clone();
+        let mut kidvid = KidVid::new();
+        kidvid.insert_tape(1);
+        kidvid.set_playback_callback(move |tape, track| {
+            tracks_clone.lock().unwrap().push((tape, track));
+            600
+        });
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.plug_controller(Port::Right, Box::new(kidvid));
+
+        // The track isn't played by the frame that is run ahead, only by the
+        // frame that is actually run.
+        console.run_ahead().unwrap();
+        assert!(tracks.lock().unwrap().is_empty());
+
+        console.run_frame();
+        console.run_ahead().unwrap();
+        console.run_frame();
+        assert_eq!(*tracks.lock().unwrap(), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_run_ahead_sync() {
+        // A vertical sync every 262 and 289 scanlines in turn, which rolls
+        // the picture and raises scanline warnings.
+        let mut rom = assemble("
+                lda #$0E
+                sta $09  ; COLUBK
+            start:
+                lda #2
+                sta $00  ; VSYNC
+                sta $02  ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #0
+            first_loop:
+                sta $02
+                dex
+                bne first_loop
+                lda $80
+                eor #1
+                sta $80
+                ldx #3
+                cmp #0
+                beq second_loop
+                ldx #30
+            second_loop:
+                sta $02
+                dex
+                bne second_loop
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        // Running ahead doesn't change the roll nor the warnings of the
+        // frames that are actually run.
+        let mut console = Console::new(Cartridge::new(rom.clone()));
+        let mut other_console = Console::new(Cartridge::new(rom));
+        console.set_sync_roll(true);
+        other_console.set_sync_roll(true);
+
+        let mut warnings = 0;
+        for _ in 0..70 {
+            let summary = console.run_frame();
+            console.run_ahead().unwrap();
+            let other_summary = other_console.run_frame();
+
+            assert_eq!(summary.scanline_warnings, other_summary.scanline_warnings);
+            assert_eq!(console.sync_lines(), other_console.sync_lines());
+            assert_eq!(console.sync_roll, other_console.sync_roll);
+            assert!(console.frame().pixels == other_console.frame().pixels);
+            warnings += summary.scanline_warnings.len();
+        }
+        assert_eq!(warnings, 2);
+        assert!(console.is_sync_unstable());
+    }
+
+    #[test]
+    fn test_tv_standard() {
+        // A grey background, a pure tone, and a loop which never syncs.
+        let mut rom = assemble("
+                lda #$0E
+                sta $09 ; COLUBK
+                lda #4
+                sta $15 ; AUDC0
+                lda #15
+                sta $19 ; AUDV0
+            end:
+                jmp end
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert_eq!(console.tv_standard(), TvStandard::Ntsc);
+        console.run_frame();
+        console.take_audio_samples();
+
+        // The state carries on with the new line count and sample rate.
+        let ram = console.pia.ram;
+        let frames = console.frames_count;
+        console.set_tv_standard(TvStandard::Pal);
+        assert_eq!(console.pia.ram, ram);
+        assert!((console.audio_sample_rate() - 31_113.1).abs() < 1.0);
+
+        console.run_frame();
+        assert_eq!(console.frames_count, frames + 1);
+        assert_eq!(console.take_audio_samples().len(), 2 * 312);
+        assert_eq!(console.cycle_duration(), Duration::from_secs_f64(3.0 / 3_546_894.0));
+
+        // The colors follow the TV standard.
+        console.set_tv_standard(TvStandard::Secam);
+        console.run_frame();
+        assert_eq!(console.frame().pixels[100][0], (0x_FF, 0x_FF, 0x_FF));
+
+        console.set_tv_standard(TvStandard::Ntsc);
+        console.run_frame();
+        assert_eq!(console.frame().pixels[100][0], (0x_EC, 0x_EC, 0x_EC));
+        assert_eq!(console.take_audio_samples().len(), 2 * 312 + 2 * 262);
+        assert_eq!(console.cycle_duration(), CYCLE_DURATION);
+    }
+
+    #[test]
+    fn test_frame_bank_cycles() {
+        let mut rom = assemble("loop: jmp loop").unwrap();
+        rom.resize(8192, 0x_EA);
+        assert_eq!(Cartridge::new(rom.clone()).bank_count(), 2);
+        assert_eq!(Cartridge::new(vec![0; 2048]).bank_count(), 1);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert_eq!(console.frame_bank_cycles(), &[0, 0]);
+
+        // Without reading a hotspot, the cycles of a frame are all spent in
+        // the first bank.
+        console.run_frame();
+        console.run_frame();
+        let cycles = console.frame_bank_cycles()[0];
+        assert!((19_900..19_920).contains(&cycles), "{}", cycles);
+        assert_eq!(console.frame_bank_cycles()[1], 0);
+
+        let state = console.save_state();
+        console.load_state(&state).unwrap();
+        assert_eq!(console.frame_bank_cycles(), &[0, 0]);
+
+        // Once the second bank is selected, the cycles are spent there.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "lda $FFF9")
+            .bank_switching(BankSwitching::F8)
+            .bank(1)
+            .code(0x_F003, "loop: jmp loop")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        console.run_frame();
+        console.run_frame();
+        assert_eq!(console.current_bank(), 1);
+        assert_eq!(console.frame_bank_cycles()[0], 0);
+        assert!((19_900..19_920).contains(&console.frame_bank_cycles()[1]));
+    }
+
+    #[test]
+    fn test_bank_switching() {
+        // Each bank reads a hotspot which selects the next one; the last
+        // bank reads the first hotspot.
+        let schemes: [(BankSwitching, u16, usize); 3] = [(BankSwitching::F8, 0x_FFF8, 2), (BankSwitching::F6, 0x_FFF6, 4), (BankSwitching::F4, 0x_FFF4, 8)];
+        for (bank_switching, first_hotspot, count) in schemes {
+            let mut builder = RomBuilder::new().bank_switching(bank_switching);
+            for bank in 0..count {
+                let hotspot = first_hotspot + ((bank + 1) % count) as u16;
+                builder = builder
+                    .bank(bank)
+                    .code(0x_F000, &format!("ldx #{}\nlda ${:04X}\njmp $F000", bank, hotspot));
+            }
+
+            let mut console = Console::new(builder.build().unwrap());
+            for index in 0..2 * count {
+                assert_eq!(console.current_bank(), index % count);
+                for _ in 0..3 {
+                    console.execute_instruction();
+                }
+                assert_eq!(console.cpu.x_register as usize, index % count);
+            }
+        }
+
+        // Both reading and writing a hotspot selects a bank, and the other
+        // addresses don't.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "sta $1FF9")
+            .bank_switching(BankSwitching::F8)
+            .bank(1)
+            .code(0x_F003, "lda $1FF7\nlda $1FF0\nlda $1FF8")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        console.execute_instruction();
+        assert_eq!(console.current_bank(), 1);
+        console.execute_instruction();
+        console.execute_instruction();
+        assert_eq!(console.current_bank(), 1);
+        console.execute_instruction();
+        assert_eq!(console.current_bank(), 0);
+
+        // The ROMs without bank switching stay on their only bank.
+        let cartridge = RomBuilder::new().code(0x_F000, "lda $FFF9").build().unwrap();
+        let mut console = Console::new(cartridge);
+        console.execute_instruction();
+        assert_eq!(console.current_bank(), 0);
+    }
+
+    #[test]
+    fn test_bank_switching_state() {
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "lda $FFF9")
+            .bank_switching(BankSwitching::F8)
+            .bank(1)
+            .code(0x_F003, "loop: jmp loop")
+            .build()
+            .unwrap();
+
+        // The selected bank is saved and restored.
+        let mut console = Console::new(cartridge);
+        let state = console.save_state();
+        console.execute_instruction();
+        assert_eq!(console.current_bank(), 1);
+
+        let other_state = console.save_state();
+        console.load_state(&state).unwrap();
+        assert_eq!(console.current_bank(), 0);
+        assert!(state.diff(&other_state).other);
+
+        let bytes = other_state.to_bytes();
+        console.load_state(&SaveState::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(console.current_bank(), 1);
+
+        // A bank that doesn't exist in the cartridge is rejected.
+        let mut console = Console::new(Cartridge::new(vec![0x_EA; 4096]));
+        match console.load_state(&other_state) {
+            Err(error) => assert_eq!(error.to_string(), "bank is out of range"),
+            Ok(_) => panic!("the bank shouldn't be restored")
+        }
+        assert_eq!(console.current_bank(), 0);
+    }
+
+    #[test]
+    fn test_mapper_fault() {
+        // An empty ROM reads as the open bus, and its faults are reported once
+        // per address and per summary.
+        let mut console = Console::new(Cartridge::new(vec![]));
+        let start = console.summary_start();
+        for _ in 0..2 {
+            console.cpu.program_counter = 0x_F000;
+            for _ in 0..10 {
+                console.execute_instruction();
+            }
+        }
+
+        // The open bus reads as BRK, which reads its padding byte and goes
+        // through the IRQ vector.
+        let summary = console.make_summary(start);
+        let addresses = summary.mapper_faults.iter().map(|fault| fault.address).collect::<Vec<_>>();
+        assert_eq!(addresses, [0x_F000, 0x_F001, 0x_FFFE, 0x_FFFF]);
+        assert_eq!(summary.mapper_faults[0].rom_size, 0);
+        assert!(console.make_summary(start).mapper_faults.is_empty());
+
+        console.poke_assembly(0x_F000, "nop").unwrap();
+        assert_eq!(console.memory(0x_F000), 0);
+
+        // Now that BRK goes through the IRQ vector, the NOPs must loop back
+        // rather than wrapping to the zero page.
+        let mut rom = vec![0x_EA; 2048];
+        rom[2045..].copy_from_slice(&[0x_4C, 0x_00, 0x_F0]); // JMP $F000
+        let mut console = Console::new(Cartridge::new(rom));
+        assert!(console.run_frame().mapper_faults.is_empty());
+    }
+
+    #[test]
+    fn test_poke() {
+        let mut rom = vec![0x_EA; 4096];
+        rom[4093..].copy_from_slice(&[0x_4C, 0x_00, 0x_F0]); // JMP $F000
+        let mut console = Console::new(Cartridge::new(rom));
+        console.start_tia_log();
+
+        // The mirrors are decoded as they are for the CPU.
+        console.poke(0x_49, 0x_84); // COLUBK
+        console.poke(0x_0180, 0x_42);
+        assert_eq!(console.save_state().tia[0x_09], 0x_84);
+        assert_eq!(console.ram(0x_80), 0x_42);
+
+        // Writing a timer register starts the timer.
+        console.poke(0x_0296, 5); // TIM64T
+        assert_eq!(console.pia.timer_value, 5);
+        assert_eq!(console.pia.timer_interval, 64);
+
+        // And the writes to the TIA are logged.
+        let log = console.stop_tia_log().unwrap();
+        assert_eq!(log.writes.len(), 1);
+        assert_eq!((log.writes[0].address, log.writes[0].value), (0x_09, 0x_84));
+
+        console.run_frame();
+        assert_ne!(console.frame().pixels[100][80], (0, 0, 0));
+    }
+
+    #[test]
+    fn test_audio() {
+        // A pure tone at full volume on the left channel.
+        let mut rom = assemble("
+                lda #4
+                sta $15 ; AUDC0
+                lda #15
+                sta $19 ; AUDV0
+            end:
+                jmp end
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert!((console.audio_sample_rate() - 31_399.5).abs() < 1.0);
+
+        // Two samples per scanline.
+        console.run_frame();
+        let samples = console.take_audio_samples();
+        assert_eq!(samples.len(), 2 * 262);
+        assert_eq!(console.frame_audio(), &samples[..]);
+        assert_eq!(console.queued_audio_samples(), 0);
+        assert!(samples[100..].iter().all(|sample| *sample == 0.0 || *sample == 0.5));
+        assert!(samples[100..].contains(&0.5));
+
+        // The audio is synthesized again from a restored state, and the queued
+        // samples are discarded.
+        let state = console.save_state();
+        console.run_frame();
+        let samples = console.take_audio_samples();
+
+        console.run_frame();
+        console.load_state(&state).unwrap();
+        assert_eq!(console.queued_audio_samples(), 0);
+        console.run_frame();
+        assert_eq!(console.take_audio_samples(), samples);
+
+        // The volume scales the samples and a muted channel is left out of
+        // the mix.
+        console.set_audio_volume(0.5);
+        assert_eq!(console.audio_volume(), 0.5);
+        console.run_frame();
+        let samples = console.take_audio_samples();
+        assert!(samples[100..].iter().all(|sample| *sample == 0.0 || *sample == 0.25));
+        assert!(samples[100..].contains(&0.25));
+
+        console.set_audio_volume(2.0);
+        assert_eq!(console.audio_volume(), 1.0);
+
+        console.set_audio_channel_muted(0, true);
+        assert!(console.is_audio_channel_muted(0));
+        assert!(!console.is_audio_channel_muted(1));
+        console.run_frame();
+        assert!(console.take_audio_samples().iter().all(|sample| *sample == 0.0));
+
+        // The settings aren't part of the state.
+        console.load_state(&state).unwrap();
+        assert!(console.is_audio_channel_muted(0));
     }
 
     #[test]
@@ -1063,96 +3853,259 @@ mod test {
 
         // Create a ROM to put the console into different states and check if
         // the states are correct.
-        let cartridge = Cartridge::new(vec![
-            0x_A9, 0x_05,        // Load accumulator with value 5
-            0x_8D, 0x_95, 0x_02, // Write to register TIM8T with the accumulator value
-            // Do 2 times 8 'do nothing' cycles.
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            // During this 8 cycles, read the INSTAT register (don't be confused with EA and AE)
-            0x_EA, 0x_EA, 0x_AE, 0x_85, 0x_02, 0x_EA, 0x_EA, 0x_EA,
-            // Do 2 times 8 'do nothing' cycles.
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            // Do 2 times 'do nothing' cycles to finsih the testing.
-            0x_EA, 0x_EA,
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-            0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA, 0x_EA,
-        ]);
+        // Write 5 to TIM8T, then do nothing for 2 times 8 cycles, then read
+        // INSTAT during the next 8 cycles; the rest of the ROM does nothing.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                lda #5
+                sta $0295 ; TIM8T
+            ")
+            .code(0x_F017, "ldx $0285 ; INSTAT")
+            .build()
+            .unwrap();
 
         // Create the console and advance the simulation slightly forward to
         // avoid being on the cycle edges.
         let mut console = Console::new(cartridge);
         console.update_accurate(CYCLE_DURATION / 10); // slightly advance the simulation
 
-        assert_eq!(console.timer_value, 0);
-        assert_eq!(console.timer_status & 0b_0100_0000 != 0, false);
-        assert_eq!(console.timer_status & 0b_1000_0000 != 0, false);
-        assert_eq!(console.timer_interval, 1);
+        assert_eq!(console.pia.timer_value, 0);
+        assert!(console.pia.timer_status & 0b_0100_0000 == 0);
+        assert!(console.pia.timer_status & 0b_1000_0000 == 0);
+        assert_eq!(console.pia.timer_interval, 1);
 
         // Advance the simulation by 2 cycles. At this time, the accumulator is
         // loaded with value 5.
         console.update_accurate(CYCLE_DURATION * 2);
-        assert_eq!(console.accumulator, 5);
+        assert_eq!(console.cpu.accumulator, 5);
 
         // Advance the simulation by 4 cycles. At this time, the register TIM8T
         // has been written with the value of the accumulator (which is 5). The
         // register INTIM is updated and the register INSTAT 7th bit is reset.
-        console.timer_status |= 0b_1000_000;
+        console.pia.timer_status |= 0b_1000_0000;
         console.update_accurate(CYCLE_DURATION * 4);
-        assert_eq!(console.timer_value, 5);
-        assert_eq!(console.timer_status & 0b_1000_0000 != 0, false);
+        assert_eq!(console.pia.timer_value, 5);
+        assert!(console.pia.timer_status & 0b_1000_0000 == 0);
 
         // The timer is immediately decremented after the first cycle.
         console.update_accurate(CYCLE_DURATION);
-        assert_eq!(console.timer_value, 4);
+        assert_eq!(console.pia.timer_value, 4);
 
         // Then after that, it's taking 8 cycles for the next decrement.
         console.update_accurate(CYCLE_DURATION * 8);
-        assert_eq!(console.timer_value, 3);
+        assert_eq!(console.pia.timer_value, 3);
 
         // During the next 8 cycles, the INSTAT register is read which should
         // reset the 6th bit of INSTAT register.
         console.update_accurate(CYCLE_DURATION * 2);
 
-        console.timer_status |= 0b_0100_000;
+        console.pia.timer_status |= 0b_0010_0000;
         console.update_accurate(CYCLE_DURATION * 3);
-        assert_eq!(console.timer_status & 0b_0100_0000 != 0, false);
+        assert!(console.pia.timer_status & 0b_0100_0000 == 0);
 
         console.update_accurate(CYCLE_DURATION * 3);
-        assert_eq!(console.timer_value, 2);
+        assert_eq!(console.pia.timer_value, 2);
 
         // Run another 2 times more 8 cycles for the timer value to finally
         // reach 0.
         console.update_accurate(CYCLE_DURATION * 16);
-        assert_eq!(console.timer_value, 0);
+        assert_eq!(console.pia.timer_value, 0);
+        assert_eq!(console.cycles_count, 40);
+
+        // Then the timer stays at 0 for 8 cycles, underflows to 255 and is
+        // decremented every cycle. The simulation only stops between two
+        // instructions and the NOPs which follow take 2 cycles, therefore the
+        // timer is observed every 2 cycles; the first time, it was already
+        // decremented once after the underflow. Only the 7th bit of INSTAT is
+        // set; the 6th bit is the edge detection of PA7.
+        console.pia.timer_status &= 0b_0011_1111; // reset 6th and 7th bit
+        console.update_accurate(CYCLE_DURATION * 8);
+        assert_eq!(console.cycles_count, 48);
+        assert_eq!(console.pia.timer_value, 0x_FE);
+        assert!(console.pia.timer_status & 0b_0100_0000 == 0);
+        assert!(console.pia.timer_status & 0b_1000_0000 != 0);
 
-        console.update_accurate(CYCLE_DURATION);
-        console.update_accurate(CYCLE_DURATION);
-        console.update_accurate(CYCLE_DURATION);
-        console.update_accurate(CYCLE_DURATION);
-        console.update_accurate(CYCLE_DURATION);
-        console.update_accurate(CYCLE_DURATION);
-        // console.update_accurate(CYCLE_DURATION);
+        console.update_accurate(CYCLE_DURATION * 2);
+        assert_eq!(console.cycles_count, 50);
+        assert_eq!(console.pia.timer_value, 0x_FC);
+    }
 
-        // Then it's high speed decrement, timer values underflows and become
-        // 255.
-        console.timer_status &= 0b_0011_1111; // reset 6th and 7th bit
-        console.update_accurate(CYCLE_DURATION);
-        assert_eq!(console.timer_value, 0x_FF);
-        assert_eq!(console.timer_status & 0b_0100_0000 != 0, true);
-        assert_eq!(console.timer_status & 0b_1000_0000 != 0, true);
+    #[test]
+    fn test_jam() {
+        // INC $80 and KIL; the CPU jams on its second instruction.
+        let mut rom = vec![0x_E6, 0x_80, 0x_02];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom.clone()));
+        assert!(!console.is_jammed());
+
+        // The CPU stops but the rest of the console keeps running.
+        let summary = console.run_frame();
+        assert_eq!(summary.jam, Some(0x_F002));
+        assert_eq!(summary.unknown_opcodes, vec![]);
+        assert_eq!(console.jam_address(), Some(0x_F002));
+        assert_eq!(console.program_counter(), 0x_F002);
+        assert_eq!(console.memory(0x_80), 1);
+
+        let summary = console.run_frame();
+        assert_eq!(summary.frames, 1);
+        assert_eq!(summary.instructions, 0);
+        assert_eq!(summary.jam, Some(0x_F002));
+        assert_eq!(console.step_instruction(), 1);
+
+        // The jammed CPU is saved with the state.
+        let state = console.save_state();
+        let mut other_console = Console::new(Cartridge::new(rom.clone()));
+        other_console.load_state(&state).unwrap();
+        assert_eq!(other_console.jam_address(), Some(0x_F002));
+
+        // When jams are fatal, the simulation stops as soon as the CPU jams.
+        let mut console = Console::new(Cartridge::new(rom));
+        console.set_jam_fatal(true);
+        assert!(console.is_jam_fatal());
+
+        let summary = console.run_frame();
+        assert_eq!(summary.frames, 0);
+        assert_eq!(summary.jam, Some(0x_F002));
+
+        let summary = console.update(Duration::from_millis(100));
+        assert_eq!(summary.cycles, 0);
+        assert_eq!(summary.jam, Some(0x_F002));
+    }
 
-        console.update_accurate(CYCLE_DURATION);
-        assert_eq!(console.timer_value, 0x_FE);
+    #[test]
+    fn test_tap_switches() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom.clone()));
+        console.release_reset_button();
+        console.release_select_button();
+        assert_eq!(console.memory(SWCHB) & 0b0000_0011, 0b0000_0011);
+
+        // The switches stay pressed for the given number of frames.
+        console.tap_reset(2);
+        console.tap_select(1);
+        assert_eq!(console.memory(SWCHB) & 0b0000_0011, 0b0000_0000);
+
+        console.run_frame();
+        assert_eq!(console.memory(SWCHB) & 0b0000_0011, 0b0000_0010);
+
+        // The pending tap is saved with the state.
+        let state = console.save_state();
+        let mut other_console = Console::new(Cartridge::new(rom));
+        other_console.load_state(&state).unwrap();
+        other_console.run_frame();
+        assert_eq!(other_console.memory(SWCHB) & 0b0000_0011, 0b0000_0011);
+
+        console.run_frame();
+        assert_eq!(console.memory(SWCHB) & 0b0000_0011, 0b0000_0011);
+
+        // Pressing the switch cancels the tap.
+        console.tap_reset(1);
+        console.press_reset_button();
+        console.run_frame();
+        assert_eq!(console.memory(SWCHB) & 0b0000_0001, 0b0000_0000);
+    }
 
-        console.update_accurate(CYCLE_DURATION);
-        assert_eq!(console.timer_value, 0x_FD);
+    #[test]
+    fn test_apply_action() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+        console.plug_controller(Port::Left, Box::new(Joystick::new()));
+
+        console.apply_action(Action::DownRightFire);
+        assert_eq!(console.memory(SWCHA), 0b0101_0000);
+        assert_eq!(console.memory(INPT4), 0b0000_0000);
+
+        let joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        assert!(joystick.is_direction_pressed(Direction::Down));
+        assert!(!joystick.is_direction_pressed(Direction::Up));
+        drop(joystick);
+
+        console.apply_action(Action::Noop);
+        assert_eq!(console.memory(SWCHA), 0b1111_0000);
+        assert_eq!(console.memory(INPT4), 0b1000_0000);
+    }
+
+    #[test]
+    fn test_step_action() {
+        // A ROM that keeps changing the color of the background.
+        let mut rom = vec![
+            0x_E8,               // Increment X register
+            0x_86, 0x_09,        // Write COLUBK with X register
+            0x_4C, 0x_00, 0x_F0  // Jump to location 0x_F000
+        ];
+        rom.resize(4096, 0x_EA);
+
+        let create_console = || {
+            let mut console = Console::new(Cartridge::new(rom.clone()));
+            console.plug_controller(Port::Left, Box::new(Joystick::new()));
+            console
+        };
+
+        let mut console = create_console();
+        let frames: Vec<Frame> = console.frames().take(4).collect();
+
+        // The last frame, or the maximum of the last two ones.
+        let mut console = create_console();
+        let frame = console.step_action(Action::Fire, 4, FramePooling::Last);
+        assert_eq!(frame.number, 4);
+        assert!(frame.pixels == frames[3].pixels);
+        assert_eq!(console.memory(INPT4), 0b0000_0000);
+
+        let mut console = create_console();
+        let frame = console.step_action(Action::Fire, 4, FramePooling::Max);
+        assert_eq!(frame.number, 4);
+        assert!(frame.pixels == frames[3].max(&frames[2]).pixels);
+        assert!(frame.pixels != frames[3].pixels);
+    }
+
+    #[test]
+    fn test_concurrent_consoles() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Console>();
+        assert_send::<Cartridge>();
+        assert_send::<Frame>();
+        assert_send::<SaveState>();
+        assert_send::<FrameChecksum>();
+
+        // A ROM that keeps changing the color of the background, and counts
+        // in the RAM.
+        let mut rom = vec![
+            0x_E8,               // Increment X register
+            0x_86, 0x_09,        // Write COLUBK with X register
+            0x_E6, 0x_80,        // Increment location 0x_80
+            0x_4C, 0x_00, 0x_F0  // Jump to location 0x_F000
+        ];
+        rom.resize(4096, 0x_EA);
+
+        let create = |rom: Vec<u8>, seed: u64| {
+            let mut console = Box::new(Console::new(Cartridge::new(rom)));
+            console.plug_controller(Port::Left, Box::new(crate::joystick::Joystick::new()));
+            console.subscribe(|_| ());
+            console.set_seed(seed);
+            console.randomize_ram();
+            console.enable_checksums();
 
-        // console.update_accurate(CYCLE_DURATION);
-        // assert_eq!(console.timer_value, 255);
+            console
+        };
+        let run = |mut console: Box<Console>| {
+            console.frames().take(5).for_each(drop);
+            console.disable_checksums()
+        };
 
-        // TODO; This unit test is not completed.
+        // The consoles created on one thread and run on others don't interfere
+        // with each other; they produce the same frames as they do one at a
+        // time.
+        let threads: Vec<_> = (0..8).map(|index| {
+            let console = create(rom.clone(), index % 2);
+            std::thread::spawn(move || run(console))
+        }).collect();
+
+        let checksums: Vec<_> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        for (index, checksums) in checksums.iter().enumerate() {
+            assert_eq!(checksums, &run(create(rom.clone(), index as u64 % 2)));
+        }
+        assert_ne!(checksums[0], checksums[1]);
     }
-}
\ No newline at end of file
+}
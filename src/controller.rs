@@ -6,13 +6,116 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, September 2020
 
+use std::any::Any;
+use std::ops::{Deref, DerefMut};
+
 use crate::Console;
+use crate::Port;
+use crate::state::{StateWriter, StateError};
 
 /// Brief description.
 ///
 /// Long description.
 ///
-pub trait Controller {
-    fn plugged(&mut self, console: *mut Console);
+pub trait Controller: Any + Send {
+    fn plugged(&mut self, port: Port);
     fn unplugged(&mut self);
-}
\ No newline at end of file
+
+    /// Write the controller to its port.
+    ///
+    /// This function is called by the console whenever the controller may
+    /// have changed (when it's plugged, when it's released by
+    /// `Console::controller_mut()` and when its state is loaded), giving it
+    /// a chance to drive the pins of the port it's plugged in. It does
+    /// nothing by default.
+    ///
+    fn apply(&mut self, _console: &mut Console) {
+    }
+
+    /// Update the controller.
+    ///
+    /// This function is called by the console every time a frame is completed,
+    /// giving the controller a chance to react to what the game did with the
+    /// port (peripherals driven by the game like the Kid Vid need it). It does
+    /// nothing by default.
+    ///
+    fn update(&mut self, _console: &mut Console) {
+    }
+
+    /// Notify the controller the dump signal changed.
+    ///
+    /// This function is called by the console whenever the game changes the
+    /// bit 7 of VBLANK, which dumps the pot inputs to ground. Adapters like the
+    /// QuadTari use it as a selection signal. It does nothing by default.
+    ///
+    fn dump_changed(&mut self, _console: &mut Console, _is_dumped: bool) {
+    }
+
+    /// Save the internal state of the controller.
+    ///
+    /// This function returns the state of the controller that isn't visible
+    /// from the ports (buttons, position, phase, etc.) so it's part of the save
+    /// states and the replays; otherwise, they would desync.
+    ///
+    fn save_state(&self) -> StateWriter;
+
+    /// Restore the internal state of the controller.
+    ///
+    /// This function restores a state previously returned by `save_state()`;
+    /// the console writes it to the port afterward (see `apply()`).
+    ///
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError>;
+}
+
+/// A controller borrowed from the console.
+///
+/// It's returned by `Console::controller_mut()` and dereferences to the
+/// controller. The controller is written to its port when it's dropped (see
+/// `Controller::apply()`), so the game sees the changes from then on.
+///
+pub struct ControllerMut<'a, T: Controller> {
+    console: &'a mut Console,
+    port: Port,
+    controller: Option<Box<T>>
+}
+
+impl<'a, T: Controller> ControllerMut<'a, T> {
+    pub(crate) fn new(console: &'a mut Console, port: Port) -> Option<ControllerMut<'a, T>> {
+        let slot = console.controller_slot(port);
+        if !slot.as_deref().is_some_and(|controller| (controller as &dyn Any).is::<T>()) {
+            return None;
+        }
+
+        let controller: Box<dyn Any> = slot.take()?;
+        let controller = controller.downcast::<T>().ok();
+
+        Some(ControllerMut {
+            console,
+            port,
+            controller
+        })
+    }
+}
+
+impl<T: Controller> Deref for ControllerMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.controller.as_ref().unwrap()
+    }
+}
+
+impl<T: Controller> DerefMut for ControllerMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.controller.as_mut().unwrap()
+    }
+}
+
+impl<T: Controller> Drop for ControllerMut<'_, T> {
+    fn drop(&mut self) {
+        if let Some(mut controller) = self.controller.take() {
+            controller.apply(self.console);
+            *self.console.controller_slot(self.port) = Some(controller);
+        }
+    }
+}
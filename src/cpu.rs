@@ -0,0 +1,347 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::instruction::*;
+
+/// An opcode the processor doesn't know.
+///
+/// It's returned by `Cpu6507::step()` along with the address it was fetched
+/// from; the processor skips it.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UnknownOpcode {
+    pub address: u16,
+    pub opcode: u8
+}
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:#04X} at {:#06X}", self.opcode, self.address)
+    }
+}
+
+impl std::error::Error for UnknownOpcode {}
+
+/// The MOS 6507 processor.
+///
+/// This structure holds the state of the processor; its registers and its
+/// status flags. It doesn't know anything about the machine it's part of; the
+/// memory is reached through a bus (see `Bus`), which is given to each step.
+/// It can run against a flat memory as well as against the console, which
+/// makes it testable on its own, and reusable by other machines built around
+/// a 6502 family processor.
+///
+/// ```
+/// # use atari_2600::{Bus, Cpu6507};
+/// # struct Memory([u8; 65536]);
+/// # impl Bus for Memory {
+/// #     fn read(&mut self, address: u16) -> u8 { self.0[address as usize] }
+/// #     fn write(&mut self, address: u16, value: u8) { self.0[address as usize] = value; }
+/// # }
+/// # let mut memory = Memory([0x_EA; 65536]);
+/// let mut cpu = Cpu6507::new();
+/// cpu.reset(&mut memory);
+///
+/// loop {
+///     let cycles = cpu.step(&mut memory)?;
+/// #   break;
+/// }
+/// # Ok::<(), atari_2600::UnknownOpcode>(())
+/// ```
+///
+/// The instructions execute at once; the bus sees their reads and writes in
+/// order, but not the cycles they happen on.
+///
+/// The break flag isn't a flag of the processor, it's kept for the sake of
+/// the debugger only; see the instruction module.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cpu6507 {
+    pub program_counter: u16,
+    pub accumulator: u8,
+    pub x_register: u8,
+    pub y_register: u8,
+
+    pub negative_flag: bool,
+    pub overflow_flag: bool,
+    pub break_flag: bool,
+    pub decimal_flag: bool,
+    pub interrupt_flag: bool,
+    pub zero_flag: bool,
+    pub carry_flag: bool,
+
+    pub stack_pointer: u8,
+
+    // The address of the KIL opcode that jammed the processor, if any.
+    pub jam_address: Option<u16>
+}
+
+impl Cpu6507 {
+    /// Create a processor.
+    ///
+    /// This function creates a processor in the state the console leaves it
+    /// at power-on; the program counter is at the beginning of the cartridge,
+    /// the stack is empty and all the flags are set.
+    ///
+    pub fn new() -> Cpu6507 {
+        Cpu6507 {
+            program_counter: 0x_F000, // Until the reset vector is read by reset().
+            accumulator: 0,
+            x_register: 0,
+            y_register: 0,
+
+            negative_flag: true,
+            overflow_flag: true,
+            break_flag: true,
+            decimal_flag: true,
+            interrupt_flag: true,
+            zero_flag: true,
+            carry_flag: true,
+
+            stack_pointer: 0x_FF,
+
+            jam_address: None
+        }
+    }
+
+    /// Reset the processor.
+    ///
+    /// This function does what the processor does when its reset line is
+    /// released; it loads the program counter from the reset vector (0xFFFC),
+    /// sets the interrupt flag and unjams the processor. The other registers
+    /// are left unchanged, as on the hardware.
+    ///
+    pub fn reset<B: Bus>(&mut self, bus: &mut B) {
+        let ll = bus.read(0x_FFFC);
+        let hh = bus.read(0x_FFFD);
+
+        self.program_counter = u16::from_le_bytes([ll, hh]);
+        self.interrupt_flag = true;
+        self.jam_address = None;
+    }
+
+    /// Check whether the processor is jammed.
+    pub fn is_jammed(&self) -> bool {
+        self.jam_address.is_some()
+    }
+
+    /// Execute the next instruction.
+    ///
+    /// This function fetches the instruction pointed by the program counter,
+    /// executes it and returns the number of cycles it took. If the opcode
+    /// isn't known, it's skipped and returned as an error; it doesn't take any
+    /// cycle. A jammed processor doesn't execute anything and the function
+    /// returns 1 cycle, until it's reset.
+    ///
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> Result<u32, UnknownOpcode> {
+        if self.is_jammed() {
+            return Ok(1)
+        }
+
+        let address = self.program_counter;
+        let opcode = self.fetch(bus);
+
+        let cycles = match opcode {
+            0x_69 | 0x_65 | 0x_75 | 0x_6D | 0x_7D | 0x_79 | 0x_61 | 0x_71 => adc_instruction(self, bus, opcode),
+            0x_29 | 0x_25 | 0x_35 | 0x_2D | 0x_3D | 0x_39 | 0x_21 | 0x_31 => and_instruction(self, bus, opcode),
+            0x_0A | 0x_06 | 0x_16 | 0x_0E | 0x_1E => asl_instruction(self, bus, opcode),
+            0x_90 => bcc_instruction(self, bus, opcode),
+            0x_B0 => bcs_instruction(self, bus, opcode),
+            0x_F0 => beq_instruction(self, bus, opcode),
+            0x_24 | 0x_2C => bit_instruction(self, bus, opcode),
+            0x_30 => bmi_instruction(self, bus, opcode),
+            0x_D0 => bne_instruction(self, bus, opcode),
+            0x_10 => bpl_instruction(self, bus, opcode),
+            0x_00 => brk_instruction(self, bus, opcode),
+            0x_50 => bvc_instruction(self, bus, opcode),
+            0x_70 => bvs_instruction(self, bus, opcode),
+            0x_18 => clc_instruction(self, bus, opcode),
+            0x_D8 => cld_instruction(self, bus, opcode),
+            0x_58 => cli_instruction(self, bus, opcode),
+            0x_B8 => clv_instruction(self, bus, opcode),
+            0x_C9 | 0x_C5 | 0x_D5 | 0x_CD | 0x_DD | 0x_D9 | 0x_C1 | 0x_D1 => cmp_instruction(self, bus, opcode),
+            0x_E0 | 0x_E4 | 0x_EC => cpx_instruction(self, bus, opcode),
+            0x_C0 | 0x_C4 | 0x_CC => cpy_instruction(self, bus, opcode),
+            0x_C6 | 0x_D6 | 0x_CE | 0x_DE => dec_instruction(self, bus, opcode),
+            0x_CA => dex_instruction(self, bus, opcode),
+            0x_88 => dey_instruction(self, bus, opcode),
+            0x_49 | 0x_45 | 0x_55 | 0x_4D | 0x_5D | 0x_59 | 0x_41 | 0x_51 => eor_instruction(self, bus, opcode),
+            0x_E6 | 0x_F6 | 0x_EE | 0x_FE => inc_instruction(self, bus, opcode),
+            0x_E8 => inx_instruction(self, bus, opcode),
+            0x_C8 => iny_instruction(self, bus, opcode),
+            0x_4C | 0x_6C => jmp_instruction(self, bus, opcode),
+            0x_20 => jsr_instruction(self, bus, opcode),
+            0x_A9 | 0x_A5 | 0x_B5 | 0x_AD | 0x_BD | 0x_B9 | 0x_A1 | 0x_B1 => lda_instruction(self, bus, opcode),
+            0x_A2 | 0x_A6 | 0x_B6 | 0x_AE | 0x_BE => ldx_instruction(self, bus, opcode),
+            0x_A0 | 0x_A4 | 0x_B4 | 0x_AC | 0x_BC => ldy_instruction(self, bus, opcode),
+            0x_4A | 0x_46 | 0x_56 | 0x_4E | 0x_5E => lsr_instruction(self, bus, opcode),
+            0x_EA => nop_instruction(self, bus, opcode),
+            0x_09 | 0x_05 | 0x_15 | 0x_0D | 0x_1D | 0x_19 | 0x_01 | 0x_11 => ora_instruction(self, bus, opcode),
+            0x_48 => pha_instruction(self, bus, opcode),
+            0x_08 => php_instruction(self, bus, opcode),
+            0x_68 => pla_instruction(self, bus, opcode),
+            0x_28 => plp_instruction(self, bus, opcode),
+            0x_2A | 0x_26 | 0x_36 | 0x_2E | 0x_3E => rol_instruction(self, bus, opcode),
+            0x_6A | 0x_66 | 0x_76 | 0x_6E | 0x_7E => ror_instruction(self, bus, opcode),
+            0x_40 => rti_instruction(self, bus, opcode),
+            0x_60 => rts_instruction(self, bus, opcode),
+            0x_E9 | 0x_E5 | 0x_F5 | 0x_ED | 0x_FD | 0x_F9 | 0x_E1 | 0x_F1 => sbc_instruction(self, bus, opcode),
+            0x_38 => sec_instruction(self, bus, opcode),
+            0x_F8 => sed_instruction(self, bus, opcode),
+            0x_78 => sei_instruction(self, bus, opcode),
+            0x_85 | 0x_95 | 0x_8D | 0x_9D | 0x_99 | 0x_81 | 0x_91 => sta_instruction(self, bus, opcode),
+            0x_86 | 0x_96 | 0x_8E => stx_instruction(self, bus, opcode),
+            0x_84 | 0x_94 | 0x_8C => sty_instruction(self, bus, opcode),
+            0x_AA => tax_instruction(self, bus, opcode),
+            0x_A8 => tay_instruction(self, bus, opcode),
+            0x_BA => tsx_instruction(self, bus, opcode),
+            0x_8A => txa_instruction(self, bus, opcode),
+            0x_9A => txs_instruction(self, bus, opcode),
+            0x_98 => tya_instruction(self, bus, opcode),
+            0x_02 | 0x_12 | 0x_22 | 0x_32 | 0x_42 | 0x_52 | 0x_62 | 0x_72 | 0x_92 | 0x_B2 | 0x_D2 | 0x_F2 => kil_instruction(self, bus, opcode),
+            _ => return Err(UnknownOpcode { address, opcode })
+        };
+
+        Ok(cycles)
+    }
+
+    /// Fetch the byte pointed by the program counter.
+    ///
+    /// This function reads the next byte of the instruction stream (an opcode
+    /// or its operands) and advances the program counter past it.
+    ///
+    pub fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.fetch(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        value
+    }
+
+    /// Push a value on the stack.
+    ///
+    /// The stack is located in page 1 and grows downward; on the console, page
+    /// 1 is a mirror of page 0 and the stack ends up in the RAM.
+    ///
+    pub fn push<B: Bus>(&mut self, bus: &mut B, value: u8) {
+        bus.write(0x_0100 | self.stack_pointer as u16, value);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+
+    /// Pull a value from the stack.
+    pub fn pull<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        bus.read(0x_0100 | self.stack_pointer as u16)
+    }
+}
+
+impl Default for Cpu6507 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Memory([u8; 65536]);
+
+    impl Bus for Memory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.0[address as usize]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.0[address as usize] = value;
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut memory = Memory([0; 65536]);
+
+        let program = [
+            0x_A2, 0x_03,        // LDX #3
+            0x_20, 0x_00, 0x_30, // JSR $3000
+            0x_CA,               // DEX
+            0x_D0, 0x_FA,        // BNE $2002
+            0x_03,               // (unknown)
+            0x_02                // KIL
+        ];
+        memory.0[0x_2000..0x_2000 + program.len()].copy_from_slice(&program);
+        memory.0[0x_3000..0x_3003].copy_from_slice(&[0x_E6, 0x_10, 0x_60]); // INC $10, RTS
+        memory.0[0x_FFFC..0x_FFFE].copy_from_slice(&[0x_00, 0x_20]);
+
+        let mut cpu = Cpu6507::new();
+        cpu.reset(&mut memory);
+        assert_eq!(cpu.program_counter, 0x_2000);
+
+        let mut cycles = 0;
+        let error = loop {
+            match cpu.step(&mut memory) {
+                Ok(count) => cycles += count,
+                Err(error) => break error
+            }
+        };
+
+        // The subroutine was called three times; the stack is in page 1.
+        assert_eq!(memory.0[0x_10], 3);
+        assert_eq!(cpu.x_register, 0);
+        assert_eq!(cpu.stack_pointer, 0x_FF);
+        assert_eq!(memory.0[0x_01FE..0x_0200], [0x_04, 0x_20]);
+        assert_eq!(cycles, 2 + 3 * (6 + 5 + 6 + 2) + 2 * 3 + 2);
+
+        // The unknown opcode is skipped.
+        assert_eq!(error, UnknownOpcode { address: 0x_2008, opcode: 0x_03 });
+        assert_eq!(cpu.program_counter, 0x_2009);
+
+        // The CPU jams and stays jammed until it's reset.
+        assert_eq!(cpu.step(&mut memory), Ok(2));
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.step(&mut memory), Ok(1));
+        assert_eq!(cpu.program_counter, 0x_2009);
+
+        cpu.reset(&mut memory);
+        assert!(!cpu.is_jammed());
+        assert_eq!(cpu.program_counter, 0x_2000);
+    }
+
+    #[test]
+    fn test_step_cpx() {
+        let mut memory = Memory([0; 65536]);
+
+        let program = [
+            0x_A2, 0x_40,        // LDX #$40
+            0x_E0, 0x_40,        // CPX #$40
+            0x_E4, 0x_10,        // CPX $10
+            0x_EC, 0x_00, 0x_30  // CPX $3000
+        ];
+        memory.0[0x_2000..0x_2000 + program.len()].copy_from_slice(&program);
+        memory.0[0x_10] = 0x_41;
+        memory.0[0x_3000] = 0x_3F;
+        memory.0[0x_FFFC..0x_FFFE].copy_from_slice(&[0x_00, 0x_20]);
+
+        let mut cpu = Cpu6507::new();
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory).unwrap();
+
+        // The immediate, zero page and absolute variants are all dispatched.
+        assert_eq!(cpu.step(&mut memory), Ok(2));
+        assert!(cpu.zero_flag && cpu.carry_flag);
+
+        assert_eq!(cpu.step(&mut memory), Ok(3));
+        assert!(!cpu.zero_flag && !cpu.carry_flag && cpu.negative_flag);
+
+        assert_eq!(cpu.step(&mut memory), Ok(4));
+        assert!(!cpu.zero_flag && cpu.carry_flag && !cpu.negative_flag);
+        assert_eq!(cpu.program_counter, 0x_2009);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::frame::Frame;
+use crate::state::SaveState;
+use crate::telemetry::Timecode;
+
+/// The cause of a crash of the emulation.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CrashCause {
+    /// The CPU jammed on the KIL opcode at the given address while jams are
+    /// fatal (see `Console::set_jam_fatal()`).
+    Jam { address: u16 },
+    /// The emulator panicked (an internal assertion failed); it's the message
+    /// of the panic.
+    Panic { message: String }
+}
+
+impl fmt::Display for CrashCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrashCause::Jam { address } => write!(f, "the CPU jammed at ${:04X}", address),
+            CrashCause::Panic { message } => write!(f, "the emulator panicked: {}", message)
+        }
+    }
+}
+
+/// The diagnostics of a crash of the emulation.
+///
+/// This structure bundles what's needed to investigate a crash; the state of
+/// the console when it crashed, the last instructions it executed (if they're
+/// kept, see `Console::set_crash_trace_length()`), the last completed frame,
+/// and the MD5 digest of the ROM. The console makes one when the CPU jams and
+/// jams are fatal, and when a panic is caught by `Console::catch_crash()`.
+/// Frontends attach it to the bug reports, usually by writing it to a
+/// directory.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::path::Path;
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// console.set_jam_fatal(true);
+/// console.set_crash_trace_length(1000);
+///
+/// let report = match console.catch_crash(|console| console.run_frame()) {
+///     Ok(_) => console.take_crash_report(),
+///     Err(report) => Some(*report)
+/// };
+/// if let Some(report) = report {
+///     report.write_to_directory(Path::new("crash"))?;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+#[derive(Clone)]
+pub struct CrashReport {
+    /// What crashed the emulation.
+    pub cause: CrashCause,
+    /// When it crashed, in emulated time.
+    pub timecode: Timecode,
+    /// MD5 digest of the ROM.
+    pub rom_md5: String,
+    /// State of the console when it crashed.
+    pub state: SaveState,
+    /// Last instructions executed, oldest first, formatted as the lines of a
+    /// trace (see `Console::start_trace()`).
+    pub trace: Vec<String>,
+    /// Last completed frame.
+    pub screenshot: Frame
+}
+
+impl CrashReport {
+    /// Write the report to a directory.
+    ///
+    /// This function creates the directory if needed and writes the report
+    /// in it; a summary (`report.txt`), the state (`state.bin`), the last
+    /// instructions (`trace.txt`) and the last frame (`screenshot.png`).
+    ///
+    pub fn write_to_directory(&self, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+
+        fs::write(directory.join("report.txt"), self.to_string())?;
+        fs::write(directory.join("state.bin"), self.state.to_bytes())?;
+        fs::write(directory.join("trace.txt"), self.trace.iter().map(|line| format!("{}\n", line)).collect::<String>())?;
+        fs::write(directory.join("screenshot.png"), self.screenshot.to_png())?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Crash: {}", self.cause)?;
+        writeln!(f, "Time: {} (frame {})", self.timecode, self.screenshot.number)?;
+        writeln!(f, "ROM: {}", self.rom_md5)?;
+        write!(f, "Trace: {} instructions", self.trace.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_crash_report() {
+        // A few instructions, then a KIL opcode.
+        let mut rom = assemble("
+                lda #1
+                ldx #2
+                ldy #3
+                .byte $02
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+        let md5 = Cartridge::new(rom.clone()).md5();
+
+        let mut console = Console::new(Cartridge::new(rom.clone()));
+        console.set_crash_trace_length(2);
+
+        // Jams aren't crashes unless they're fatal.
+        console.run_frame();
+        assert!(console.take_crash_report().is_none());
+
+        let mut console = Console::new(Cartridge::new(rom.clone()));
+        console.set_jam_fatal(true);
+        console.set_crash_trace_length(2);
+        let summary = console.run_frame();
+        assert_eq!(summary.jam, Some(0x_F006));
+
+        let report = console.take_crash_report().unwrap();
+        assert!(console.take_crash_report().is_none());
+        assert_eq!(report.cause, CrashCause::Jam { address: 0x_F006 });
+        assert_eq!(report.rom_md5, md5);
+        assert_eq!(report.trace.len(), 2);
+        assert!(report.trace[0].contains("0:F004 A0 03"));
+        assert!(report.trace[1].contains("0:F006 02"));
+        assert!(report.to_string().starts_with("Crash: the CPU jammed at $F006\n"));
+
+        // The state is the one of the jammed console.
+        let mut other_console = Console::new(Cartridge::new(rom));
+        other_console.load_state(&report.state).unwrap();
+        assert_eq!(other_console.cpu.y_register, 3);
+
+        // A panic is caught and turned into a report.
+        let result = console.catch_crash(|_| panic!("bad assertion"));
+        let report = result.err().unwrap();
+        assert_eq!(report.cause, CrashCause::Panic { message: String::from("bad assertion") });
+        assert_eq!(console.catch_crash(|console| console.cpu.y_register).ok(), Some(3));
+
+        let directory = std::env::temp_dir().join(format!("crash-report-{}", std::process::id()));
+        report.write_to_directory(&directory).unwrap();
+        for file in ["report.txt", "state.bin", "trace.txt", "screenshot.png"] {
+            assert!(directory.join(file).exists());
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::fs;
+
+use crate::cartridge::Cartridge;
+use crate::console::ConsoleVariant;
+use crate::controller::Controller;
+use crate::joystick::Joystick;
+use crate::booster_grip::BoosterGrip;
+use crate::paddle::{Paddle, PaddleCalibration, SCANLINE_CYCLES};
+use crate::trackball::{Trackball, TrackballMode};
+use crate::kidvid::KidVid;
+
+// The games known without a database file. The original cartridges of Missile
+// Command and Centipede read a joystick (which the CX-22 Trak-Ball emulates);
+// their trackball hacks are recognized when they're listed in a database file.
+// The paddle games read the whole default range of the paddles.
+const BUILTIN_ENTRIES: &str = "\
+3a2e2d0c6892aa14544083dfb7762782|Missile Command|Atari|joystick|joystick
+91c2098e88a6b13f977af8c003e0bca5|Centipede|Atari|joystick|joystick
+f34f08e5eb96e500e851a80be3277a56|Breakout|Atari|paddles|joystick
+cbe5a166550a8129a5e6d374901dffad|Warlords|Atari|paddles|paddles
+5428cdfada281c569c74c7308c7f2c26|Kaboom!|Activision|paddles|joystick
+";
+
+/// The kinds of controller a game expects.
+///
+/// This enumeration identifies the controllers (and their variant) the ROM
+/// database can recommend for a game. It's also used to override the
+/// recommendation when building a console.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ControllerKind {
+    Joystick,
+    BoosterGrip,
+    Paddles,
+    Trackball(TrackballMode),
+    KidVid
+}
+
+impl ControllerKind {
+    /// Create a controller of this kind.
+    pub fn create(&self) -> Box<dyn Controller> {
+        match self {
+            ControllerKind::Joystick => Box::new(Joystick::new()),
+            ControllerKind::BoosterGrip => Box::new(BoosterGrip::new()),
+            ControllerKind::Paddles => Box::new(Paddle::new()),
+            ControllerKind::Trackball(mode) => Box::new(Trackball::new(*mode)),
+            ControllerKind::KidVid => Box::new(KidVid::new())
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ControllerKind> {
+        match name {
+            "joystick" => Some(ControllerKind::Joystick),
+            "booster-grip" => Some(ControllerKind::BoosterGrip),
+            "paddles" => Some(ControllerKind::Paddles),
+            "trackball" => Some(ControllerKind::Trackball(TrackballMode::Trackball)),
+            "amiga-mouse" => Some(ControllerKind::Trackball(TrackballMode::AmigaMouse)),
+            "atari-mouse" => Some(ControllerKind::Trackball(TrackballMode::AtariMouse)),
+            "kidvid" => Some(ControllerKind::KidVid),
+            _ => None
+        }
+    }
+}
+
+/// An entry of the ROM database.
+///
+/// It describes a game identified by the MD5 digest of its ROM, which
+/// controllers it expects in the left and right ports, the variant of the
+/// console it must run on if it's known to be incompatible with the others,
+/// and the calibration of its paddles if the default one doesn't suit it.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct RomEntry {
+    pub md5: String,
+    pub name: String,
+    pub manufacturer: String,
+    pub left_controller: ControllerKind,
+    pub right_controller: ControllerKind,
+    pub variant: Option<ConsoleVariant>,
+    pub paddle_calibration: Option<PaddleCalibration>
+}
+
+/// An error that occurred while parsing the ROM database.
+#[derive(Debug)]
+pub struct DatabaseError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// The ROM database.
+///
+/// The ROM database identifies games from the MD5 digest of their ROM and
+/// tells how to set up the console to play them. It's loaded from a text file
+/// where each line describes a game with the following fields separated by
+/// the `|` character; the MD5 digest, the name, the manufacturer, and the
+/// controllers of the left and right ports (`joystick`, `booster-grip`,
+/// `paddles`, `trackball`, `amiga-mouse`, `atari-mouse` or `kidvid`), and
+/// optionally, the variant of the console (`original`, `sears` or `junior`,
+/// or empty) and the scanlines the game reads the paddles on (the first and
+/// the last one, see `PaddleCalibration::from_scanlines()`). Empty lines and
+/// lines starting with `#` are ignored.
+///
+/// ```text
+/// # md5|name|manufacturer|left|right[|variant[|paddles]]
+/// 0123456789abcdef0123456789abcdef|Some Game|Some Company|trackball|joystick
+/// 0123456789abcdef0123456789abcdef|Other Game|Some Company|joystick|joystick|original
+/// 0123456789abcdef0123456789abcdef|Third Game|Some Company|paddles|joystick||8-150
+/// ```
+///
+pub struct Database {
+    entries: HashMap<String, RomEntry>
+}
+
+impl Database {
+    /// Create an empty database.
+    pub fn new() -> Database {
+        Database {
+            entries: HashMap::new()
+        }
+    }
+
+    /// Create the built-in database.
+    ///
+    /// This function returns a database of common games, mostly the ones that
+    /// don't play with joysticks; a database file loaded afterward (see
+    /// `extend()`) completes or overrides it.
+    ///
+    pub fn builtin() -> Database {
+        Database::parse(BUILTIN_ENTRIES).expect("the built-in database is valid")
+    }
+
+    /// Parse a database.
+    ///
+    /// This function parses the content of a database file (see the format
+    /// above) and returns the database.
+    ///
+    pub fn parse(text: &str) -> Result<Database, DatabaseError> {
+        let mut database = Database::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let error = |message: &str| DatabaseError {
+                line: index + 1,
+                message: message.to_string()
+            };
+
+            let fields: Vec<&str> = line.split('|').map(|field| field.trim()).collect();
+            if fields.len() < 5 || fields.len() > 7 {
+                return Err(error("expected 5 to 7 fields"));
+            }
+
+            let left_controller = ControllerKind::from_name(fields[3])
+                .ok_or_else(|| error("unknown left controller"))?;
+            let right_controller = ControllerKind::from_name(fields[4])
+                .ok_or_else(|| error("unknown right controller"))?;
+            let variant = match fields.get(5) {
+                Some(name) if !name.is_empty() => Some(ConsoleVariant::from_name(name).ok_or_else(|| error("unknown console variant"))?),
+                _ => None
+            };
+            let paddle_calibration = match fields.get(6) {
+                Some(range) => Some(parse_scanlines(range).ok_or_else(|| error("invalid paddle scanlines"))?),
+                None => None
+            };
+
+            database.insert(RomEntry {
+                md5: fields[0].to_lowercase(),
+                name: fields[1].to_string(),
+                manufacturer: fields[2].to_string(),
+                left_controller,
+                right_controller,
+                variant,
+                paddle_calibration
+            });
+        }
+
+        Ok(database)
+    }
+
+    /// Load a database from a file.
+    ///
+    /// This function reads and parses a database file; parsing errors are
+    /// reported as invalid data.
+    ///
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Database> {
+        let text = fs::read_to_string(path)?;
+        Database::parse(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Insert an entry.
+    ///
+    /// This function adds a game to the database, replacing the previous
+    /// entry with the same MD5 digest.
+    ///
+    pub fn insert(&mut self, entry: RomEntry) {
+        self.entries.insert(entry.md5.clone(), entry);
+    }
+
+    /// Insert the entries of another database.
+    ///
+    /// This function adds the games of the other database, replacing the
+    /// entries with the same MD5 digest.
+    ///
+    pub fn extend(&mut self, other: Database) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Look up a game.
+    ///
+    /// This function returns the entry of the game contained in the cartridge,
+    /// if it's known by the database.
+    ///
+    pub fn lookup(&self, cartridge: &Cartridge) -> Option<&RomEntry> {
+        self.lookup_md5(&cartridge.md5())
+    }
+
+    /// Look up a game by the MD5 digest of its ROM (in lowercase
+    /// hexadecimal).
+    pub fn lookup_md5(&self, md5: &str) -> Option<&RomEntry> {
+        self.entries.get(md5)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the database is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Parse a range of scanlines ("first-last") into a paddle calibration.
+fn parse_scanlines(range: &str) -> Option<PaddleCalibration> {
+    let (first, last) = range.split_once('-')?;
+    let (first, last): (u32, u32) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+
+    // The charge times must fit, so they don't saturate silently.
+    let (min_charge_time, max_charge_time) = (first.checked_mul(SCANLINE_CYCLES)?, last.checked_mul(SCANLINE_CYCLES)?);
+
+    if first < last { Some(PaddleCalibration::new(min_charge_time, max_charge_time)) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_database() {
+        let cartridge = Cartridge::new(vec![0x_4C, 0x_00, 0x_F0]);
+        let other_cartridge = Cartridge::new(vec![0x_EA]);
+
+        let text = format!(
+            "# A comment.\n\n{}|Test Game|Nobody|amiga-mouse|joystick\n{}|Other Game|Nobody|paddles|paddles|junior\n",
+            cartridge.md5().to_uppercase(),
+            other_cartridge.md5()
+        );
+        let database = Database::parse(&text).unwrap();
+        assert_eq!(database.len(), 2);
+
+        let entry = database.lookup(&cartridge).unwrap();
+        assert_eq!(entry.name, "Test Game");
+        assert_eq!(entry.left_controller, ControllerKind::Trackball(TrackballMode::AmigaMouse));
+        assert_eq!(entry.right_controller, ControllerKind::Joystick);
+        assert_eq!(entry.variant, None);
+
+        let entry = database.lookup(&other_cartridge).unwrap();
+        assert_eq!(entry.left_controller, ControllerKind::Paddles);
+        assert_eq!(entry.right_controller, ControllerKind::Paddles);
+        assert_eq!(entry.variant, Some(ConsoleVariant::Junior));
+
+        assert!(database.lookup(&Cartridge::new(vec![])).is_none());
+
+        let error = Database::parse("foo|bar").err().unwrap();
+        assert_eq!(error.line, 1);
+
+        let error = Database::parse("\nfoo|bar|baz|mouse|joystick").err().unwrap();
+        assert_eq!(error.line, 2);
+
+        let error = Database::parse("foo|bar|baz|joystick|joystick|heavy-sixer").err().unwrap();
+        assert_eq!(error.message, "unknown console variant");
+
+        // The scanlines the paddles are read on, with or without a variant.
+        let database = Database::parse("foo|bar|baz|paddles|joystick||8-150\nqux|bar|baz|paddles|paddles|sears|0-20").unwrap();
+        let entry = database.lookup_md5("foo").unwrap();
+        assert_eq!(entry.variant, None);
+        assert_eq!(entry.paddle_calibration, Some(PaddleCalibration::from_scanlines(8, 150)));
+        assert_eq!(database.lookup_md5("qux").unwrap().variant, Some(ConsoleVariant::Sears));
+        assert_eq!(database.lookup_md5("qux").unwrap().paddle_calibration, Some(PaddleCalibration::new(0, 1520)));
+
+        for range in ["150-8", "8", "a-b", "", "8-60000000"] {
+            let error = Database::parse(&format!("foo|bar|baz|paddles|joystick||{}", range)).err().unwrap();
+            assert_eq!(error.message, "invalid paddle scanlines");
+        }
+        assert!(Database::parse("foo|bar|baz|paddles|joystick||8-150|").is_err());
+    }
+
+    #[test]
+    fn test_builtin_database() {
+        let mut database = Database::builtin();
+        assert_eq!(database.lookup_md5("3a2e2d0c6892aa14544083dfb7762782").unwrap().name, "Missile Command");
+        assert_eq!(database.lookup_md5("91c2098e88a6b13f977af8c003e0bca5").unwrap().name, "Centipede");
+
+        let entry = database.lookup_md5("cbe5a166550a8129a5e6d374901dffad").unwrap();
+        assert_eq!(entry.left_controller, ControllerKind::Paddles);
+        assert_eq!(entry.right_controller, ControllerKind::Paddles);
+
+        // A database file overrides the built-in entries.
+        let count = database.len();
+        database.extend(Database::parse("3a2e2d0c6892aa14544083dfb7762782|Missile Command|Atari|trackball|joystick").unwrap());
+        assert_eq!(database.len(), count);
+        assert_eq!(database.lookup_md5("3a2e2d0c6892aa14544083dfb7762782").unwrap().left_controller,
+            ControllerKind::Trackball(TrackballMode::Trackball));
+    }
+}
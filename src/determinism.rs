@@ -0,0 +1,121 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::state::StateError;
+
+/// The checksum of a frame.
+///
+/// When the verification mode of the console is enabled, a checksum is
+/// recorded for every completed frame. It's made of the hash of the inputs
+/// (the ports and the switches), the hash of the state (the CPU, the memory
+/// and the frame itself) and a rolling hash of all the frames so far, which
+/// changes as soon as any earlier frame differs.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameChecksum {
+    pub frame: u64,
+    pub input_hash: u64,
+    pub state_hash: u64,
+    pub rolling_hash: u64
+}
+
+/// An error reported by the verification of a replay.
+#[derive(Debug)]
+pub enum DeterminismError {
+    /// The replay couldn't be played.
+    State(StateError),
+    /// The frames differ, starting from the given one; the checksum is
+    /// missing if one of the runs has fewer frames.
+    Mismatch {
+        frame: u64,
+        expected: Option<FrameChecksum>,
+        actual: Option<FrameChecksum>
+    }
+}
+
+impl fmt::Display for DeterminismError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeterminismError::State(error) => write!(f, "{}", error),
+            DeterminismError::Mismatch { frame, expected, actual } => {
+                let state_hash = |checksum: &Option<FrameChecksum>| match checksum {
+                    Some(checksum) => format!("{:016x}", checksum.state_hash),
+                    None => String::from("none")
+                };
+
+                write!(
+                    f, "frame {} differs (expected state {}, got {})",
+                    frame, state_hash(expected), state_hash(actual)
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeterminismError {}
+
+impl From<StateError> for DeterminismError {
+    fn from(error: StateError) -> Self {
+        DeterminismError::State(error)
+    }
+}
+
+/// Compare the checksums of two runs.
+///
+/// This function returns the first frame that differs between two runs,
+/// comparing the inputs and the state of each frame.
+///
+pub fn compare_checksums(expected: &[FrameChecksum], actual: &[FrameChecksum]) -> Result<(), DeterminismError> {
+    for index in 0..expected.len().max(actual.len()) {
+        let (expected, actual) = (expected.get(index).copied(), actual.get(index).copied());
+        if expected != actual {
+            let frame = expected.or(actual).map(|checksum| checksum.frame).unwrap();
+            return Err(DeterminismError::Mismatch { frame, expected, actual });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare_checksums() {
+        let checksum = |frame, state_hash| FrameChecksum {
+            frame,
+            input_hash: 0,
+            state_hash,
+            rolling_hash: state_hash
+        };
+
+        let expected = vec![checksum(1, 10), checksum(2, 20), checksum(3, 30)];
+        assert!(compare_checksums(&expected, &expected).is_ok());
+
+        let actual = vec![checksum(1, 10), checksum(2, 21), checksum(3, 31)];
+        match compare_checksums(&expected, &actual) {
+            Err(DeterminismError::Mismatch { frame, expected, actual }) => {
+                assert_eq!(frame, 2);
+                assert_eq!(expected.unwrap().state_hash, 20);
+                assert_eq!(actual.unwrap().state_hash, 21);
+            },
+            _ => panic!("frames should differ")
+        }
+
+        match compare_checksums(&expected, &expected[..1]) {
+            Err(DeterminismError::Mismatch { frame, actual, .. }) => {
+                assert_eq!(frame, 2);
+                assert_eq!(actual, None);
+            },
+            _ => panic!("frames should differ")
+        }
+    }
+}
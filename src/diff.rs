@@ -0,0 +1,178 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+use crate::builder::ConsoleBuilder;
+use crate::console::Console;
+use crate::replay::Replay;
+use crate::state::StateError;
+
+/// An error reported while comparing two runs.
+#[derive(Debug)]
+pub enum DiffError {
+    /// A replay couldn't be played.
+    State(StateError),
+    /// The screenshots couldn't be saved.
+    Io(io::Error)
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffError::State(error) => write!(f, "{}", error),
+            DiffError::Io(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+impl From<StateError> for DiffError {
+    fn from(error: StateError) -> Self {
+        DiffError::State(error)
+    }
+}
+
+impl From<io::Error> for DiffError {
+    fn from(error: io::Error) -> Self {
+        DiffError::Io(error)
+    }
+}
+
+/// Path of the screenshot of a frame of a run.
+///
+/// This function returns where `compare_runs()` saves the screenshot of the
+/// given frame (starting from 0) of the first run (`a`) or the second run
+/// (`b`).
+///
+pub fn screenshot_path(directory: &Path, frame: usize, run: char) -> PathBuf {
+    directory.join(format!("frame-{:05}-{}.png", frame, run))
+}
+
+/// Compare the output of two runs of a game.
+///
+/// This function plays two replays of a game side by side, each on its own
+/// console (created with joysticks plugged, see `ConsoleBuilder`), for the
+/// given number of frames. It returns the frames (starting from 0) whose
+/// pixels differ and saves a screenshot of both runs for each of them in the
+/// given directory (see `screenshot_path()`). If a replay is shorter than the
+/// number of frames, its last inputs are held.
+///
+/// It's useful to find where two versions of the emulator start to behave
+/// differently (same replay, different builds) or where a replay goes out of
+/// sync (different replays, same build).
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::path::Path;
+/// # use atari_2600::{compare_runs, Cartridge, ConsoleBuilder, Replay, TEST_ROM};
+/// # let rom = TEST_ROM.to_vec();
+/// # let console = ConsoleBuilder::new(Cartridge::new(rom.clone())).build();
+/// # let (replay, other_replay) = (Replay::new(&console), Replay::new(&console));
+/// let frames = compare_runs(&rom, &replay, &other_replay, 60, Path::new("diff"))?;
+/// if let Some(frame) = frames.first() {
+///     println!("runs diverge from frame {}", frame);
+/// }
+/// # Ok::<(), atari_2600::DiffError>(())
+/// ```
+///
+pub fn compare_runs(rom: &[u8], inputs_a: &Replay, inputs_b: &Replay, frames: usize, directory: &Path) -> Result<Vec<usize>, DiffError> {
+    let mut console_a = ConsoleBuilder::new(Cartridge::new(rom.to_vec())).build();
+    let mut console_b = ConsoleBuilder::new(Cartridge::new(rom.to_vec())).build();
+
+    inputs_a.rewind(&mut console_a)?;
+    inputs_b.rewind(&mut console_b)?;
+
+    let mut divergent_frames = Vec::new();
+    for index in 0..frames {
+        run_replay_frame(inputs_a, index, &mut console_a)?;
+        run_replay_frame(inputs_b, index, &mut console_b)?;
+
+        let (frame_a, frame_b) = (console_a.frame(), console_b.frame());
+        if frame_a.pixels != frame_b.pixels {
+            fs::create_dir_all(directory)?;
+            fs::write(screenshot_path(directory, index, 'a'), frame_a.to_png())?;
+            fs::write(screenshot_path(directory, index, 'b'), frame_b.to_png())?;
+
+            divergent_frames.push(index);
+        }
+    }
+
+    Ok(divergent_frames)
+}
+
+fn run_replay_frame(replay: &Replay, index: usize, console: &mut Console) -> Result<(), StateError> {
+    if index < replay.len() {
+        replay.apply_frame(index, console)?;
+    }
+
+    console.run_frame();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::console::Port;
+    use crate::assembler::assemble;
+    use crate::joystick::{Joystick, Direction};
+
+    #[test]
+    fn test_compare_runs() {
+        // Paint the background with the value of the port A.
+        let mut rom = assemble("
+            start:
+                lda $0280 ; SWCHA
+                sta $09   ; COLUBK
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let record = |pressed_from: usize| {
+            let mut console = ConsoleBuilder::new(Cartridge::new(rom.clone())).build();
+            let mut replay = Replay::new(&console);
+            for index in 0..6 {
+                if index == pressed_from {
+                    console.controller_mut::<Joystick>(Port::Left).unwrap().press_direction(Direction::Right);
+                }
+
+                replay.record_frame(&console);
+                console.run_frame();
+            }
+
+            replay
+        };
+
+        let directory = std::env::temp_dir().join(format!("atari-2600-diff-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        // The same inputs produce the same frames.
+        let replay = record(usize::MAX);
+        assert_eq!(compare_runs(&rom, &replay, &replay, 6, &directory).unwrap(), vec![]);
+        assert!(!directory.exists());
+
+        // The frames differ as soon as the joystick is pushed, and they keep
+        // differing as the last inputs are held.
+        let other_replay = record(3);
+        let frames = compare_runs(&rom, &replay, &other_replay, 8, &directory).unwrap();
+        assert_eq!(frames, vec![3, 4, 5, 6, 7]);
+
+        let png_a = fs::read(screenshot_path(&directory, 3, 'a')).unwrap();
+        let png_b = fs::read(screenshot_path(&directory, 3, 'b')).unwrap();
+        assert_eq!(png_a[1..4], *b"PNG");
+        assert_ne!(png_a, png_b);
+        assert!(!screenshot_path(&directory, 2, 'a').exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
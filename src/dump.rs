@@ -0,0 +1,293 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::location::{self, WSYNC, RSYNC, RESP0, RESP1, RESM0, RESM1, RESBL, HMOVE, HMCLR, CXCLR};
+use crate::location::{SWCHA, SWACNT, SWCHB, SWBCNT};
+use crate::state::SaveState;
+
+// The registers of the TIA that are strobes; they hold nothing, so they're
+// left out of the dumps.
+const STROBES: [u16; 10] = [WSYNC, RSYNC, RESP0, RESP1, RESM0, RESM1, RESBL, HMOVE, HMCLR, CXCLR];
+
+/// A human-readable dump of the state of a console.
+///
+/// This structure is the text form of a save state, made for the bug reports
+/// about the accuracy of the emulation; it's split in sections (the CPU, the
+/// RAM, the TIA, the PIA and the beam) of labeled lines, with the same values
+/// the debugger of Stella shows, so a maintainer can compare both side by
+/// side. It's created with `SaveState::dump()`, it's formatted with
+/// `Display`, a pasted dump is read back with `parse()`, and two dumps are
+/// compared line by line with `diff()`.
+///
+/// ```text
+/// [CPU]
+/// PC F004
+/// A 01
+/// P 24 nv-bdIzc
+/// [RAM]
+/// 80 01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+/// [TIA]
+/// VSYNC 00
+/// ```
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::fs;
+/// # use atari_2600::{Cartridge, Console, StateDump, TEST_ROM};
+/// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let expected = StateDump::parse(&fs::read_to_string("stella.txt")?);
+/// for difference in expected.diff(&console.save_state().dump()) {
+///     println!("{}", difference);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct StateDump {
+    sections: Vec<(String, Vec<(String, String)>)>
+}
+
+impl StateDump {
+    /// Create the dump of a save state.
+    pub fn new(state: &SaveState) -> StateDump {
+        let mut dump = StateDump::default();
+
+        let flags = ['N', 'V', 'B', 'D', 'I', 'Z', 'C'].iter()
+            .zip(state.flags.iter())
+            .map(|(name, &set)| if set { *name } else { name.to_ascii_lowercase() })
+            .collect::<Vec<_>>();
+        let status = state.flags.iter()
+            .zip([0x_80, 0x_40, 0x_10, 0x_08, 0x_04, 0x_02, 0x_01].iter())
+            .filter(|(&set, _)| set)
+            .fold(0x_20, |status, (_, bit)| status | bit);
+
+        dump.push_section("CPU");
+        dump.push_line("PC", format!("{:04X}", state.pointer_counter));
+        dump.push_line("A", format!("{:02X}", state.accumulator));
+        dump.push_line("X", format!("{:02X}", state.x_register));
+        dump.push_line("Y", format!("{:02X}", state.y_register));
+        dump.push_line("SP", format!("{:02X}", state.stack_pointer));
+        dump.push_line("P", format!("{:02X} {}{}-{}", status, flags[0], flags[1], flags[2..].iter().collect::<String>()));
+        dump.push_line("BUS", format!("{:02X}", state.data_bus));
+        dump.push_line("JAM", match state.jam_address {
+            Some(address) => format!("{:04X}", address),
+            None => String::from("-")
+        });
+
+        dump.push_section("RAM");
+        for (row, bytes) in state.ram.chunks(16).enumerate() {
+            let line = bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+            dump.push_line(&format!("{:02X}", 0x_80 + row * 16), line);
+        }
+
+        dump.push_section("TIA");
+        for (address, value) in state.tia.iter().enumerate() {
+            let address = address as u16;
+            if STROBES.contains(&address) {
+                continue;
+            }
+            if let Some(label) = location::label(address) {
+                dump.push_line(label, format!("{:02X}", value));
+            }
+        }
+
+        dump.push_section("PIA");
+        for (address, value) in [SWCHA, SWACNT, SWCHB, SWBCNT].iter().zip(state.pia.iter()) {
+            dump.push_line(location::label(*address).unwrap(), format!("{:02X}", value));
+        }
+        dump.push_line("INTIM", format!("{:02X}", state.timer_value));
+        dump.push_line("INSTAT", format!("{:02X}", state.timer_status));
+        dump.push_line("TIMINT", state.timer_interval.to_string());
+        dump.push_line("TIMCLK", state.timer_elapsed_clocks.to_string());
+
+        dump.push_section("BEAM");
+        dump.push_line("FRAME", state.frames_count.to_string());
+        dump.push_line("SCANLINE", state.scanline.to_string());
+        dump.push_line("CYCLE", state.scanline_cycle.to_string());
+        dump.push_line("CYCLES", state.cycles_count.to_string());
+
+        dump
+    }
+
+    /// Read a dump from its text form.
+    ///
+    /// This function reads the lines of a dump as they're formatted by
+    /// `Display`; a line between brackets starts a section, and the other
+    /// lines are a label followed by a value. Blank lines and the lines before
+    /// the first section are ignored, so a dump can be pasted with the text
+    /// around it.
+    ///
+    pub fn parse(text: &str) -> StateDump {
+        let mut dump = StateDump::default();
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with('[') && line.ends_with(']') {
+                dump.push_section(&line[1..line.len() - 1]);
+            }
+            else if !dump.sections.is_empty() {
+                let (label, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                dump.push_line(label, value.trim().to_string());
+            }
+        }
+
+        dump
+    }
+
+    /// Value of a line of the dump.
+    pub fn value(&self, section: &str, label: &str) -> Option<&str> {
+        self.sections.iter()
+            .filter(|(name, _)| name == section)
+            .flat_map(|(_, lines)| lines.iter())
+            .find(|(name, _)| name == label)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Compare with another dump.
+    ///
+    /// This function returns the lines that differ between this dump and
+    /// another one, in the order of this dump (followed by the lines that are
+    /// only in the other dump). It's empty if both dumps are identical.
+    ///
+    pub fn diff(&self, other: &StateDump) -> Vec<DumpDifference> {
+        let mut differences = Vec::new();
+
+        for (section, lines) in &self.sections {
+            for (label, value) in lines {
+                let other_value = other.value(section, label);
+                if other_value != Some(value.as_str()) {
+                    differences.push(DumpDifference {
+                        section: section.clone(),
+                        label: label.clone(),
+                        left: Some(value.clone()),
+                        right: other_value.map(String::from)
+                    });
+                }
+            }
+        }
+
+        for (section, lines) in &other.sections {
+            for (label, value) in lines {
+                if self.value(section, label).is_none() {
+                    differences.push(DumpDifference {
+                        section: section.clone(),
+                        label: label.clone(),
+                        left: None,
+                        right: Some(value.clone())
+                    });
+                }
+            }
+        }
+
+        differences
+    }
+
+    fn push_section(&mut self, name: &str) {
+        self.sections.push((name.to_string(), Vec::new()));
+    }
+
+    fn push_line(&mut self, label: &str, value: String) {
+        self.sections.last_mut().unwrap().1.push((label.to_string(), value));
+    }
+}
+
+impl fmt::Display for StateDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (section, lines) in &self.sections {
+            writeln!(f, "[{}]", section)?;
+            for (label, value) in lines {
+                writeln!(f, "{} {}", label, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A line that differs between two dumps.
+///
+/// This structure is returned by `StateDump::diff()`; a value is `None` when
+/// the line is missing from its dump.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct DumpDifference {
+    pub section: String,
+    pub label: String,
+    pub left: Option<String>,
+    pub right: Option<String>
+}
+
+impl fmt::Display for DumpDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("(missing)"));
+        write!(f, "[{}] {}: {} -> {}", self.section, self.label, value(&self.left), value(&self.right))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_state_dump() {
+        let mut rom = assemble("
+                cld
+                clc
+                clv
+                lda #$42
+                sta $81
+                sta $09   ; COLUBK
+                ldx #$FF
+                txs
+            loop:
+                inc $80
+                jmp loop
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        for _ in 0..8 {
+            console.execute_instruction();
+        }
+
+        let dump = console.save_state().dump();
+        assert_eq!(dump.value("CPU", "A"), Some("42"));
+        assert_eq!(dump.value("CPU", "SP"), Some("FF"));
+        assert_eq!(dump.value("CPU", "P").map(|status| &status[3..]), Some("Nv-BdIzc"));
+        assert_eq!(dump.value("RAM", "80"), Some("00 42 00 00 00 00 00 00 00 00 00 00 00 00 00 00"));
+        assert_eq!(dump.value("TIA", "COLUBK"), Some("42"));
+        assert_eq!(dump.value("TIA", "WSYNC"), None);
+        assert!(dump.value("PIA", "INTIM").is_some());
+
+        // The text form reads back to the same dump.
+        let text = dump.to_string();
+        assert!(text.starts_with("[CPU]\nPC "));
+        assert_eq!(StateDump::parse(&format!("Dump:\n\n{}\n", text)), dump);
+        assert!(dump.diff(&StateDump::parse(&text)).is_empty());
+
+        // After a few iterations, the counter and the registers differ.
+        for _ in 0..4 {
+            console.execute_instruction();
+        }
+        let other_dump = console.save_state().dump();
+        let differences = dump.diff(&other_dump);
+        assert!(differences.iter().any(|difference| difference.section == "RAM" && difference.label == "80"));
+        assert!(differences.iter().all(|difference| difference.section != "TIA"));
+
+        let mut edited = text.replace("COLUBK 42", "COLUBK 44");
+        edited.push_str("[EXTRA]\nFOO 1\n");
+        let differences = dump.diff(&StateDump::parse(&edited));
+        assert_eq!(differences.len(), 2);
+        assert_eq!(differences[0].to_string(), "[TIA] COLUBK: 42 -> 44");
+        assert_eq!(differences[1].to_string(), "[EXTRA] FOO: (missing) -> 1");
+    }
+}
@@ -6,9 +6,1143 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, September 2020
 
+use std::io;
+use std::fs;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cartridge::Cartridge;
+use crate::console::{Console, Port, TvType, Difficulty};
+use crate::location::SWCHB;
+use crate::clock::{Clock, SystemClock};
+use crate::summary::UpdateSummary;
+use crate::frame::{Frame, Orientation};
+use crate::texture::{Texture, PixelFormat};
+use crate::state::{SaveState, StateError};
+use crate::menu::{PauseMenu, MenuInput, MenuAction, STATE_SLOTS};
+use crate::audio::{GainEnvelope, GainRamp};
+use crate::rewind::RewindHistory;
+use crate::sink::{VideoSink, AudioSink};
+
+// How often the watched ROM file is checked for changes.
+const ROM_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// The size of the thumbnails of the states saved in the slots; it's the
+// aspect ratio of the frame with pixels twice as wide as they're high.
+const SLOT_THUMBNAIL_WIDTH: usize = 80;
+const SLOT_THUMBNAIL_HEIGHT: usize = 48;
+
+/// What to do with the console when the ROM is reloaded.
+///
+/// See `Emulator::watch_rom()`.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReloadMode {
+    /// Restart the console as if it was just powered on.
+    Restart,
+    /// Restart the console but keep the content of the RAM.
+    PreserveRam
+}
+
+/// What to play while the emulator rewinds.
+///
+/// See `Emulator::rewind()`.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RewindAudio {
+    /// Play the audio of the rewound frames backward.
+    Reverse,
+    /// Play silence for the duration of the rewound frames.
+    Mute
+}
+
+// The function receiving the frames as textures, and their format.
+struct FrameCallback {
+    format: PixelFormat,
+    callback: Box<dyn FnMut(&Texture)>
+}
+
+struct RomWatch {
+    path: PathBuf,
+    mode: ReloadMode,
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>
+}
+
 /// A ready-to-use emulator of the Atari 2600 gaming console.
 ///
-/// Long description.
+/// The emulator runs a console in real time; each tick advances the
+/// simulation by the time elapsed since the previous tick, read from a clock.
+/// The clock of the system is used by default but another one can be injected
+/// to simulate time in tests or to use the time base of the host.
+///
+/// The emulator can be paused; the simulation stops and a pause menu is drawn
+/// over the frame, which the frontend navigates with the menu inputs. It
+/// offers to reset the console, to save and load states in slots, to switch
+/// the difficulties and the TV type, and to quit.
+///
+/// To reduce the input latency, the emulator can run ahead; it displays the
+/// frame being drawn as soon as the previous one is completed.
+///
+/// The emulator can record a history of the last frames and rewind through
+/// them; the audio is rewound along with the video.
+///
+/// The audio fades out when the emulator is paused and fades in when it
+/// resumes, so the speakers don't pop (see `set_gain_envelope()`).
+///
+/// For homebrew development, the emulator can also watch the ROM file and
+/// reload it as soon as it's rebuilt.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, Frame, TEST_ROM};
+/// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn display_frame(_frame: Frame) {}
+/// let mut emulator = Emulator::new(console);
+/// loop {
+///     let summary = emulator.tick();
+///     if summary.has_new_frame() {
+///         display_frame(emulator.console().frame());
+///     }
+/// #   break;
+/// }
+/// ```
 ///
 pub struct Emulator {
-}
\ No newline at end of file
+    console: Console,
+    clock: Arc<dyn Clock + Send + Sync>,
+    last_time: Option<Instant>,
+    initial_state: SaveState,
+    slots: [Option<SaveState>; STATE_SLOTS],
+    menu: Option<PauseMenu>,
+    rom_watch: Option<RomWatch>,
+    rewind_history: RewindHistory,
+    rewind_audio: RewindAudio,
+    is_running_ahead: bool,
+    run_ahead_frame: Option<Frame>,
+    orientation: Orientation,
+    frame_callback: Option<FrameCallback>,
+    video_sink: Option<Box<dyn VideoSink>>,
+    audio_sink: Option<Box<dyn AudioSink>>,
+    gain_envelope: GainEnvelope,
+    fade_in: Option<GainRamp>,
+    last_audio_sample: f32
+}
+
+impl Emulator {
+    /// Create an emulator.
+    ///
+    /// This function creates an emulator running the given console with the
+    /// clock of the system.
+    ///
+    pub fn new(console: Console) -> Emulator {
+        let mut emulator = Emulator {
+            initial_state: console.save_state(),
+            console,
+            clock: Arc::new(SystemClock),
+            last_time: None,
+            slots: Default::default(),
+            menu: None,
+            rom_watch: None,
+            rewind_history: RewindHistory::new(),
+            rewind_audio: RewindAudio::Reverse,
+            is_running_ahead: false,
+            run_ahead_frame: None,
+            orientation: Orientation::default(),
+            frame_callback: None,
+            video_sink: None,
+            audio_sink: None,
+            gain_envelope: GainEnvelope::default(),
+            fade_in: None,
+            last_audio_sample: 0.0
+        };
+        emulator.console.set_clock(emulator.clock.clone());
+
+        emulator
+    }
+
+    /// Use another clock.
+    ///
+    /// This function sets the clock the emulator (and the console) reads the
+    /// time from.
+    ///
+    pub fn with_clock(mut self, clock: Arc<dyn Clock + Send + Sync>) -> Emulator {
+        self.console.set_clock(clock.clone());
+        self.clock = clock;
+        self.last_time = None;
+        self
+    }
+
+    /// Emulated console.
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+
+    /// Emulated console (mutable).
+    pub fn console_mut(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
+    /// Advance the simulation.
+    ///
+    /// This function advances the simulation by the time elapsed since the
+    /// previous tick (nothing happens on the first tick) and returns a summary
+    /// of what happened. Nothing happens either while the emulator is paused.
+    ///
+    pub fn tick(&mut self) -> UpdateSummary {
+        let now = self.clock.now();
+        let elapsed_time = match self.last_time {
+            Some(last_time) => now.saturating_duration_since(last_time),
+            None => Default::default()
+        };
+        self.last_time = Some(now);
+
+        // A ROM that can't be read is likely being rebuilt; it's checked again
+        // later.
+        let is_check_due = self.rom_watch.as_ref().is_some_and(|watch| {
+            watch.last_check.is_none_or(|last_check| now.saturating_duration_since(last_check) >= ROM_CHECK_INTERVAL)
+        });
+
+        if is_check_due {
+            let _ = self.check_rom();
+            if let Some(watch) = self.rom_watch.as_mut() {
+                watch.last_check = Some(now);
+            }
+        }
+
+        if self.is_paused() {
+            return UpdateSummary::default();
+        }
+
+        let queued_samples = self.console.queued_audio_samples();
+        let summary = self.console.update(elapsed_time);
+        self.process_new_audio(queued_samples);
+
+        if summary.has_new_frame() && self.rewind_history.capacity() > 0 {
+            self.record_rewind_frame();
+        }
+
+        if summary.has_new_frame() && self.is_running_ahead {
+            // The console restores a state it has just saved, with the same
+            // controllers; failing means it's left a frame ahead.
+            let frame = self.console.run_ahead().expect("the state saved before running ahead can't be restored");
+            self.run_ahead_frame = Some(frame);
+        }
+
+        if summary.has_new_frame() && self.frame_callback.is_some() {
+            let frame = self.frame();
+            if let Some(frame_callback) = self.frame_callback.as_mut() {
+                (frame_callback.callback)(&Texture::from_frame(&frame, frame_callback.format));
+            }
+        }
+
+        if summary.has_new_frame() && self.video_sink.is_some() {
+            let frame = self.frame();
+            if let Some(video_sink) = self.video_sink.as_mut() {
+                video_sink.frame(&frame);
+            }
+        }
+        self.push_audio();
+
+        summary
+    }
+
+    // Fade in the samples produced since the given number of samples were
+    // queued, if the emulator just resumed, and remember the last one to fade
+    // it out when the emulator is paused.
+    fn process_new_audio(&mut self, queued_samples: usize) {
+        let count = self.console.queued_audio_samples().saturating_sub(queued_samples);
+
+        if let Some(ramp) = self.fade_in.as_mut() {
+            for sample in self.console.last_audio_samples_mut(count) {
+                *sample *= ramp.next_gain();
+            }
+
+            if ramp.is_finished() {
+                self.fade_in = None;
+            }
+        }
+
+        if let Some(sample) = self.console.last_audio_samples_mut(count).last() {
+            self.last_audio_sample = *sample;
+        }
+    }
+
+    // Push the queued samples into the audio sink, if any.
+    fn push_audio(&mut self) {
+        if let Some(audio_sink) = self.audio_sink.as_mut() {
+            audio_sink.samples(&self.console.take_audio_samples());
+        }
+    }
+
+    /// Set the gain envelope of the audio.
+    ///
+    /// This function sets how long the audio fades out when the emulator is
+    /// paused and fades in when it resumes; by default, it's 5 milliseconds
+    /// each way.
+    ///
+    pub fn set_gain_envelope(&mut self, envelope: GainEnvelope) {
+        self.gain_envelope = envelope;
+    }
+
+    /// Gain envelope of the audio.
+    pub fn gain_envelope(&self) -> GainEnvelope {
+        self.gain_envelope
+    }
+
+    /// Enable or disable the run-ahead mode.
+    ///
+    /// In run-ahead mode, each time a frame is completed, the emulator saves
+    /// the state of the console, runs it with the current inputs until the
+    /// next frame is completed, displays that frame and restores the state.
+    /// The player sees the effect of an input about one frame sooner, at the
+    /// cost of emulating twice as many frames. The emulation itself isn't
+    /// affected; it's deterministic and the state is fully restored.
+    ///
+    /// Games that read the inputs only once per frame benefit the most from
+    /// it.
+    ///
+    pub fn set_run_ahead(&mut self, is_running_ahead: bool) {
+        self.is_running_ahead = is_running_ahead;
+        self.run_ahead_frame = None;
+    }
+
+    /// Check whether the run-ahead mode is enabled.
+    pub fn is_running_ahead(&self) -> bool {
+        self.is_running_ahead
+    }
+
+    /// Enable rewinding.
+    ///
+    /// This function makes the emulator record the state of the console at
+    /// the end of each frame, along with the audio of the frame, and keep the
+    /// given number of the most recent ones; it's the number of frames that
+    /// can be rewound. The audio played while rewinding is either the audio of
+    /// the rewound frames played backward, or silence.
+    ///
+    /// Most states are stored as the difference with the previous one, so
+    /// minutes of history take a few megabytes; the oldest frames are dropped
+    /// anyway beyond a memory budget (see `set_rewind_memory_budget()`).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Emulator, RewindAudio, TEST_ROM};
+    /// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+    /// // Keep 10 seconds of history.
+    /// emulator.enable_rewind(600, RewindAudio::Reverse);
+    /// ```
+    ///
+    pub fn enable_rewind(&mut self, frames: usize, audio: RewindAudio) {
+        self.rewind_history.set_capacity(frames);
+        self.rewind_audio = audio;
+    }
+
+    /// Disable rewinding.
+    ///
+    /// This function stops recording the frames and drops the history.
+    ///
+    pub fn disable_rewind(&mut self) {
+        self.rewind_history.set_capacity(0);
+        self.rewind_history.clear();
+    }
+
+    /// Number of frames that can be rewound.
+    pub fn rewind_frames(&self) -> usize {
+        self.rewind_history.len().saturating_sub(1)
+    }
+
+    /// Set the memory budget of the rewind history.
+    ///
+    /// This function sets how much memory (in bytes) the history may take;
+    /// the oldest frames are dropped beyond it, even if the history holds
+    /// less frames than requested. It's 16 MiB by default.
+    ///
+    pub fn set_rewind_memory_budget(&mut self, bytes: usize) {
+        self.rewind_history.set_memory_budget(bytes);
+    }
+
+    /// Memory budget of the rewind history (in bytes).
+    pub fn rewind_memory_budget(&self) -> usize {
+        self.rewind_history.memory_budget()
+    }
+
+    /// Memory taken by the rewind history (in bytes).
+    pub fn rewind_memory_usage(&self) -> usize {
+        self.rewind_history.memory_usage()
+    }
+
+    /// Set the interval between the keyframes of the rewind history.
+    ///
+    /// This function sets how often (in frames) a full state is stored in the
+    /// history; the states in between are stored as the difference with the
+    /// previous one and rewinding replays up to that many differences. A
+    /// full state is also stored whenever the difference isn't worth it. It's
+    /// 60 frames by default.
+    ///
+    pub fn set_rewind_keyframe_interval(&mut self, frames: usize) {
+        self.rewind_history.set_keyframe_interval(frames);
+    }
+
+    /// Interval between the keyframes of the rewind history (in frames).
+    pub fn rewind_keyframe_interval(&self) -> usize {
+        self.rewind_history.keyframe_interval()
+    }
+
+    fn record_rewind_frame(&mut self) {
+        self.rewind_history.push(&self.console.save_state(), self.console.frame_audio());
+    }
+
+    /// Rewind the console.
+    ///
+    /// This function restores the console as it was the given number of
+    /// frames ago (or as far as the history goes) and returns the number of
+    /// frames that were rewound. The audio that wasn't played yet is dropped
+    /// and replaced with the audio of the rewound frames (played backward or
+    /// muted), so the frontend keeps playing audio that matches the frames it
+    /// displays; the audio is then synthesized again from the restored state.
+    ///
+    /// It fails like loading a state if the plugged controllers changed since
+    /// the frames were recorded.
+    ///
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, StateError> {
+        let frames = frames.min(self.rewind_frames());
+        if frames == 0 {
+            return Ok(0);
+        }
+
+        let mut audio = Vec::new();
+        for _ in 0..frames {
+            let frame_audio = self.rewind_history.pop_back().unwrap();
+            match self.rewind_audio {
+                RewindAudio::Reverse => audio.extend(frame_audio.iter().rev()),
+                RewindAudio::Mute => audio.resize(audio.len() + frame_audio.len(), 0.0)
+            }
+        }
+
+        let state = self.rewind_history.last_state().unwrap()?;
+        self.console.load_state(&state)?;
+        self.run_ahead_frame = None;
+        self.console.queue_audio_samples(&audio);
+        self.push_audio();
+
+        Ok(frames)
+    }
+
+    /// Watch the ROM file.
+    ///
+    /// This function makes the emulator check the ROM file for changes a few
+    /// times per second (when it ticks) and reload it when it's modified; it's
+    /// meant for a fast edit-build-run loop during homebrew development. The
+    /// console is restarted with the new ROM (keeping its RAM if requested);
+    /// the controllers, the switches and the state slots are kept.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Emulator, ReloadMode, TEST_ROM};
+    /// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+    /// emulator.watch_rom("game.bin", ReloadMode::Restart);
+    /// ```
+    ///
+    pub fn watch_rom<P: AsRef<Path>>(&mut self, path: P, mode: ReloadMode) {
+        let path = path.as_ref().to_path_buf();
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+
+        self.rom_watch = Some(RomWatch {
+            path,
+            mode,
+            modified,
+            last_check: None
+        });
+    }
+
+    /// Stop watching the ROM file.
+    pub fn unwatch_rom(&mut self) {
+        self.rom_watch = None;
+    }
+
+    /// Check the watched ROM file for changes.
+    ///
+    /// This function checks the watched ROM file right away (instead of
+    /// waiting for the next periodic check) and reloads it if it was modified.
+    /// It returns whether the ROM was reloaded.
+    ///
+    pub fn check_rom(&mut self) -> io::Result<bool> {
+        let watch = match self.rom_watch.as_mut() {
+            Some(watch) => watch,
+            None => return Ok(false)
+        };
+
+        let modified = fs::metadata(&watch.path)?.modified()?;
+        if watch.modified == Some(modified) {
+            return Ok(false);
+        }
+
+        let cartridge = Cartridge::from_file(&watch.path)?;
+        watch.modified = Some(modified);
+
+        // The file may be touched without being changed.
+        if cartridge.memory == self.console.cartridge().memory {
+            return Ok(false);
+        }
+
+        let mode = watch.mode;
+        self.reload(cartridge, mode);
+
+        Ok(true)
+    }
+
+    fn reload(&mut self, cartridge: Cartridge, mode: ReloadMode) {
+        let mut console = Console::new(cartridge);
+        console.set_clock(self.clock.clone());
+
+        for port in [Port::Left, Port::Right] {
+            if let Some(controller) = self.console.unplug_controller(port) {
+                console.plug_controller(port, controller);
+            }
+        }
+
+        *console.memory_mut(SWCHB) = self.console.memory(SWCHB);
+        self.initial_state = console.save_state();
+
+        if mode == ReloadMode::PreserveRam {
+            for address in 0x_80..=0x_FF {
+                *console.memory_mut(address) = self.console.memory(address);
+            }
+        }
+
+        self.console = console;
+
+        // The history belongs to the previous ROM.
+        self.rewind_history.clear();
+        self.run_ahead_frame = None;
+    }
+
+    /// Set the orientation of the display.
+    ///
+    /// This function sets how the frames are rotated and flipped when they're
+    /// presented, for displays that are mounted rotated; the frames returned
+    /// by the emulator carry it.
+    ///
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    /// Orientation of the display.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Receive the frames as textures.
+    ///
+    /// This function sets a function which is called with each frame to
+    /// display (see `frame()`) as soon as it's completed, converted to a
+    /// texture of the given format. It's meant for GUI applications embedding
+    /// the emulator in a widget, which upload the frames to a texture of their
+    /// toolkit.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Emulator, PixelFormat, TEST_ROM};
+    /// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+    /// # struct Widget;
+    /// # impl Widget { fn upload(&mut self, _width: usize, _height: usize, _stride: usize, _pixels: &[u8]) {} }
+    /// # let mut widget = Widget;
+    /// emulator.set_frame_callback(PixelFormat::Rgba8, move |texture| {
+    ///     widget.upload(texture.width, texture.height, texture.stride, &texture.pixels);
+    /// });
+    /// ```
+    ///
+    pub fn set_frame_callback<F: FnMut(&Texture) + 'static>(&mut self, format: PixelFormat, callback: F) {
+        self.frame_callback = Some(FrameCallback {
+            format,
+            callback: Box::new(callback)
+        });
+    }
+
+    /// Stop receiving the frames as textures.
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback = None;
+    }
+
+    /// Push the frames into a sink.
+    ///
+    /// This function sets the sink the frames to display (see `frame()`) are
+    /// pushed into as soon as they're completed, replacing the previous one
+    /// (see `VideoSink`).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```no_run")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use std::fs::File;
+    /// # use atari_2600::{Cartridge, Console, Emulator, Y4mSink, TEST_ROM};
+    /// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+    /// emulator.set_video_sink(Y4mSink::new(File::create("video.y4m")?, emulator.console().tv_standard().frame_rate()));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    pub fn set_video_sink<S: VideoSink + 'static>(&mut self, sink: S) {
+        self.video_sink = Some(Box::new(sink));
+    }
+
+    /// Stop pushing the frames into a sink.
+    pub fn clear_video_sink(&mut self) {
+        self.video_sink = None;
+    }
+
+    /// Push the audio into a sink.
+    ///
+    /// This function sets the sink the audio samples are pushed into as soon
+    /// as they're produced, replacing the previous one (see `AudioSink`);
+    /// the samples no longer queue in the console, including the fades of
+    /// the pause and the audio of the rewinds.
+    ///
+    pub fn set_audio_sink<S: AudioSink + 'static>(&mut self, sink: S) {
+        self.audio_sink = Some(Box::new(sink));
+    }
+
+    /// Stop pushing the audio into a sink.
+    pub fn clear_audio_sink(&mut self) {
+        self.audio_sink = None;
+    }
+
+    /// Frame to display.
+    ///
+    /// This function returns the last completed frame of the console (or the
+    /// next one in run-ahead mode), with the pause menu drawn over it if the
+    /// emulator is paused, and with the orientation of the display.
+    ///
+    pub fn frame(&self) -> Frame {
+        let mut frame = match &self.run_ahead_frame {
+            Some(frame) => frame.clone(),
+            None => self.console.frame()
+        };
+        if let Some(menu) = &self.menu {
+            let mut used_slots = [false; STATE_SLOTS];
+            for (used, slot) in used_slots.iter_mut().zip(self.slots.iter()) {
+                *used = slot.is_some();
+            }
+
+            menu.render(&mut frame, &self.console, &used_slots);
+        }
+        frame.orientation = self.orientation;
+
+        frame
+    }
+
+    /// Pause the emulator.
+    ///
+    /// This function stops the simulation and opens the pause menu. The audio
+    /// is faded out; samples going from the last one produced to silence are
+    /// queued (see `set_gain_envelope()`).
+    ///
+    pub fn pause(&mut self) {
+        if self.menu.is_some() {
+            return;
+        }
+
+        self.menu = Some(PauseMenu::new());
+        self.fade_in = None;
+
+        let mut ramp = GainRamp::new(1.0, 0.0, self.gain_envelope.fade_out, self.console.audio_sample_rate());
+        let samples = (0..ramp.len())
+            .map(|_| self.last_audio_sample * ramp.next_gain())
+            .collect::<Vec<_>>();
+
+        self.console.queue_audio_samples(&samples);
+        self.last_audio_sample = 0.0;
+        self.push_audio();
+    }
+
+    /// Resume the emulator.
+    ///
+    /// This function closes the pause menu and resumes the simulation. The
+    /// audio produced next is faded in (see `set_gain_envelope()`).
+    ///
+    pub fn resume(&mut self) {
+        if self.menu.take().is_some() {
+            let ramp = GainRamp::new(0.0, 1.0, self.gain_envelope.fade_in, self.console.audio_sample_rate());
+            self.fade_in = Some(ramp).filter(|ramp| !ramp.is_finished());
+        }
+    }
+
+    /// Check whether the emulator is paused.
+    pub fn is_paused(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    /// State saved in a slot, if any.
+    ///
+    /// The states saved from the pause menu have a thumbnail of 80x48 pixels
+    /// (see `SaveState::thumbnail()`) which frontends can show next to the
+    /// slots.
+    ///
+    pub fn slot(&self, slot: usize) -> Option<&SaveState> {
+        self.slots.get(slot).and_then(|state| state.as_ref())
+    }
+
+    /// Fill or clear a slot.
+    ///
+    /// This function replaces the state of a slot, for frontends that keep
+    /// the slots between runs (see `Session`). The slot must be lower than
+    /// `STATE_SLOTS`.
+    ///
+    pub fn set_slot(&mut self, slot: usize, state: Option<SaveState>) {
+        self.slots[slot] = state;
+    }
+
+    /// Pause menu, if the emulator is paused.
+    pub fn menu(&self) -> Option<&PauseMenu> {
+        self.menu.as_ref()
+    }
+
+    /// Navigate the pause menu.
+    ///
+    /// This function passes an input to the pause menu (it does nothing if
+    /// the emulator isn't paused) and carries out the selected action. The
+    /// actions that leave the menu resume the emulation (resume, reset, save
+    /// and load), the others keep it open; quitting is left to the frontend.
+    /// It returns the action that was carried out, if any; loading an empty
+    /// slot does nothing.
+    ///
+    /// Resetting restores the state of the console when the emulator was
+    /// created, so it fails like loading a state if the plugged controllers
+    /// changed since.
+    ///
+    pub fn handle_menu_input(&mut self, input: MenuInput) -> Result<Option<MenuAction>, StateError> {
+        let action = match self.menu.as_mut().and_then(|menu| menu.handle_input(input)) {
+            Some(action) => action,
+            None => return Ok(None)
+        };
+
+        // The frame that was run ahead is outdated once the console is reset
+        // or a state is loaded.
+        if let MenuAction::Reset | MenuAction::LoadState(_) = action {
+            self.run_ahead_frame = None;
+        }
+
+        match action {
+            MenuAction::Resume => self.resume(),
+            MenuAction::Reset => {
+                self.console.load_state(&self.initial_state)?;
+                self.resume();
+            },
+            MenuAction::SaveState(slot) => {
+                let state = self.console.save_state().with_thumbnail(SLOT_THUMBNAIL_WIDTH, SLOT_THUMBNAIL_HEIGHT);
+                self.slots[slot] = Some(state);
+                self.resume();
+            },
+            MenuAction::LoadState(slot) => {
+                match &self.slots[slot] {
+                    Some(state) => self.console.load_state(state)?,
+                    None => return Ok(None)
+                }
+                self.resume();
+            },
+            MenuAction::ToggleDifficulty(player) => {
+                let difficulty = match self.console.difficulty_switch(player) {
+                    Difficulty::Amateur => Difficulty::Pro,
+                    Difficulty::Pro => Difficulty::Amateur
+                };
+                self.console.set_difficulty_switch(player, difficulty);
+            },
+            MenuAction::ToggleTvType => {
+                let tv_type = match self.console.tv_type_switch() {
+                    TvType::Color => TvType::Mono,
+                    TvType::Mono => TvType::Color
+                };
+                self.console.set_tv_type_switch(tv_type);
+            },
+            MenuAction::Quit => ()
+        }
+
+        Ok(Some(action))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::cartridge::Cartridge;
+    use crate::clock::ManualClock;
+    use crate::console::Player;
+    use crate::joystick::Joystick;
+    use crate::frame::{Rotation, Flip};
+    use crate::assembler::assemble;
+    use crate::event::Event;
+    use crate::fixtures;
+
+    #[test]
+    fn test_emulator() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(rom)))
+            .with_clock(clock.clone());
+
+        assert_eq!(emulator.tick().cycles, 0);
+
+        // The simulation only advances when the clock does.
+        clock.advance(Duration::from_millis(10));
+        let summary = emulator.tick();
+        assert!(summary.cycles > 11_900 && summary.cycles < 12_000);
+        assert_eq!(emulator.tick().cycles, 0);
+
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        assert_eq!(emulator.console().telemetry().frames, 1);
+
+        // The rates of the telemetry are measured with the same clock.
+        for _ in 0..100 {
+            clock.advance(Duration::from_millis(10));
+            emulator.tick();
+        }
+
+        let telemetry = emulator.console().telemetry();
+        assert!((telemetry.frames_per_second - 59.92).abs() < 1.0);
+
+        // The frames carry the orientation of the display, not the console.
+        let orientation = Orientation { rotation: Rotation::Quarter, flip: Flip::None };
+        emulator.set_orientation(orientation);
+        assert_eq!(emulator.orientation(), orientation);
+        assert_eq!(emulator.frame().orientation, orientation);
+        assert_eq!(emulator.console().frame().orientation, Orientation::default());
+
+        // The callback receives each completed frame as a texture.
+        let textures = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let received_textures = textures.clone();
+        emulator.set_frame_callback(PixelFormat::Bgra8, move |texture| {
+            received_textures.borrow_mut().push((texture.stride, texture.pixels.len()));
+        });
+
+        for _ in 0..10 {
+            clock.advance(Duration::from_millis(10));
+            emulator.tick();
+        }
+        assert!(textures.borrow().len() >= 5);
+        assert!(textures.borrow().iter().all(|&texture| texture == (640, 640 * 192)));
+
+        emulator.clear_frame_callback();
+        textures.borrow_mut().clear();
+        clock.advance(Duration::from_millis(100));
+        emulator.tick();
+        assert!(textures.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_rewind() {
+        // INC $80, STA AUDV0 (with the counter as volume), LDA $80 and JMP
+        // $F000; the volume changes with each iteration.
+        let mut rom = vec![0x_E6, 0x_80, 0x_85, 0x_19, 0x_A5, 0x_80, 0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(rom)))
+            .with_clock(clock.clone());
+        emulator.enable_rewind(3, RewindAudio::Reverse);
+        emulator.tick();
+
+        // Nothing can be rewound until two frames are recorded.
+        assert_eq!(emulator.rewind(1).unwrap(), 0);
+
+        let mut values = Vec::new();
+        let mut audio = Vec::new();
+        while values.len() < 5 {
+            clock.advance(Duration::from_millis(1));
+            if emulator.tick().has_new_frame() {
+                values.push(emulator.console().memory(0x_80));
+                audio.push(emulator.console().frame_audio().to_vec());
+            }
+        }
+
+        // Only the last 3 frames are kept.
+        assert_eq!(emulator.rewind_frames(), 2);
+        emulator.console_mut().take_audio_samples();
+
+        // The audio of the rewound frame is played backward.
+        assert_eq!(emulator.rewind(1).unwrap(), 1);
+        assert_eq!(emulator.console().memory(0x_80), values[3]);
+        let samples = emulator.console_mut().take_audio_samples();
+        assert_eq!(samples, audio[4].iter().rev().copied().collect::<Vec<_>>());
+
+        // The history doesn't go further than the capacity.
+        emulator.enable_rewind(3, RewindAudio::Mute);
+        assert_eq!(emulator.rewind(10).unwrap(), 1);
+        assert_eq!(emulator.console().memory(0x_80), values[2]);
+        let samples = emulator.console_mut().take_audio_samples();
+        assert_eq!(samples.len(), audio[3].len());
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+        assert_eq!(emulator.rewind_frames(), 0);
+
+        // The emulation resumes from the rewound frame.
+        clock.advance(Duration::from_millis(20));
+        emulator.tick();
+        assert_eq!(emulator.rewind_frames(), 1);
+
+        // Most states are stored as deltas, within the memory budget.
+        assert_eq!(emulator.rewind_memory_budget(), 16 * 1024 * 1024);
+        assert_eq!(emulator.rewind_keyframe_interval(), 60);
+        assert!(emulator.rewind_memory_usage() > 0);
+        emulator.set_rewind_memory_budget(0);
+        assert_eq!(emulator.rewind_frames(), 0);
+
+        emulator.disable_rewind();
+        assert_eq!(emulator.rewind_frames(), 0);
+        assert_eq!(emulator.rewind_memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_run_ahead() {
+        // INC $80, LDA $80, STA COLUBK, STA AUDV0 and JMP $F000; the color of
+        // the background and the volume change with each iteration.
+        let mut rom = vec![0x_E6, 0x_80, 0x_A5, 0x_80, 0x_85, 0x_09, 0x_85, 0x_19, 0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(rom)))
+            .with_clock(clock.clone());
+        emulator.console_mut().watch_ram(0x_80);
+        emulator.set_run_ahead(true);
+        assert!(emulator.is_running_ahead());
+        emulator.tick();
+
+        // The frame is ahead of the console by one frame.
+        let mut summary = UpdateSummary::default();
+        while !summary.has_new_frame() {
+            clock.advance(Duration::from_millis(1));
+            summary = emulator.tick();
+        }
+        let frame = emulator.frame();
+        assert_eq!(emulator.console().telemetry().frames, 1);
+        assert_eq!(frame.number, 2);
+
+        // Nothing else than the frame is observable; the audio and the RAM
+        // changes of the frame that was run ahead aren't reported.
+        assert!(summary.ram_changes.iter().all(|change| change.frame == 1));
+        assert!(emulator.console_mut().update(Duration::default()).ram_changes.is_empty());
+        assert!(emulator.console().queued_audio_samples() < 2 * 262 + 100);
+
+        // The frame that was run ahead is the one the console draws next.
+        emulator.console_mut().run_frame();
+        let next_frame = emulator.console().frame();
+        assert_eq!(next_frame.number, frame.number);
+        assert!(next_frame.pixels == frame.pixels);
+
+        emulator.set_run_ahead(false);
+        assert_eq!(emulator.frame().number, 2);
+    }
+
+    #[test]
+    fn test_run_ahead_events() {
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(fixtures::counter_rom(0x_80))))
+            .with_clock(clock.clone());
+        emulator.set_run_ahead(true);
+
+        let frames = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let frames_clone = frames.clone();
+        emulator.console_mut().subscribe(move |event| {
+            if let Event::FrameCompleted { frame } = event {
+                frames_clone.lock().unwrap().push(*frame);
+            }
+        });
+
+        // The frames that are run ahead aren't published; each frame is
+        // completed once.
+        emulator.tick();
+        while emulator.console().telemetry().frames < 3 {
+            clock.advance(Duration::from_millis(1));
+            emulator.tick();
+        }
+        assert_eq!(*frames.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(emulator.frame().number, 4);
+    }
+
+    #[test]
+    fn test_watch_rom() {
+        let first_rom = fixtures::counter_rom(0x_80);
+        let second_rom = fixtures::counter_rom(0x_81);
+
+        let path = std::env::temp_dir().join(format!("atari-2600-watch-{}.bin", std::process::id()));
+        fs::write(&path, &first_rom).unwrap();
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&path).unwrap()))
+            .with_clock(clock.clone());
+        emulator.console_mut().plug_controller(Port::Left, Box::new(Joystick::new()));
+        emulator.watch_rom(&path, ReloadMode::PreserveRam);
+        emulator.tick();
+
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        let value = emulator.console().memory(0x_80);
+        assert_ne!(value, 0);
+
+        // Nothing happens until the file changes.
+        assert!(!emulator.check_rom().unwrap());
+
+        // The RAM is kept and the controllers are plugged into the new console.
+        fs::write(&path, &second_rom).unwrap();
+        assert!(emulator.check_rom().unwrap());
+        assert_eq!(emulator.console().cartridge().memory, second_rom);
+        assert_eq!(emulator.console().memory(0x_80), value);
+        assert!(emulator.console_mut().controller_mut::<Joystick>(Port::Left).is_some());
+
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        assert_eq!(emulator.console().memory(0x_80), value);
+        assert_ne!(emulator.console().memory(0x_81), 0);
+
+        // The console is restarted cleanly; the file is checked when the
+        // emulator ticks.
+        emulator.watch_rom(&path, ReloadMode::Restart);
+        fs::write(&path, &first_rom).unwrap();
+        emulator.tick();
+        assert_eq!(emulator.console().cartridge().memory, first_rom);
+        assert_eq!(emulator.console().memory(0x_80), 0);
+        assert_eq!(emulator.console().memory(0x_81), 0);
+
+        fs::remove_file(&path).unwrap();
+        emulator.unwatch_rom();
+        assert!(!emulator.check_rom().unwrap());
+    }
+
+    #[test]
+    fn test_pause_menu() {
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(fixtures::counter_rom(0x_80))))
+            .with_clock(clock.clone());
+        emulator.tick();
+
+        // The menu does nothing until the emulator is paused.
+        assert_eq!(emulator.handle_menu_input(MenuInput::Select).unwrap(), None);
+
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        let value = emulator.console().memory(0x_80);
+
+        // The simulation stops while the emulator is paused.
+        emulator.pause();
+        assert!(emulator.is_paused());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(emulator.tick().cycles, 0);
+        assert_eq!(emulator.console().memory(0x_80), value);
+
+        // Save the state in the slot 2; it resumes the emulation.
+        let input = |emulator: &mut Emulator, input| emulator.handle_menu_input(input).unwrap();
+        input(&mut emulator, MenuInput::Down);
+        input(&mut emulator, MenuInput::Down);
+        input(&mut emulator, MenuInput::Right);
+        input(&mut emulator, MenuInput::Right);
+        assert_eq!(input(&mut emulator, MenuInput::Select), Some(MenuAction::SaveState(2)));
+        assert!(!emulator.is_paused());
+        assert!(emulator.slot(1).is_none());
+        let thumbnail = emulator.slot(2).unwrap().thumbnail().unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (80, 48));
+
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        assert_ne!(emulator.console().memory(0x_80), value);
+
+        // Loading an empty slot does nothing, loading the slot 2 restores the
+        // state.
+        emulator.pause();
+        for _ in 0..3 {
+            input(&mut emulator, MenuInput::Down);
+        }
+        assert_eq!(input(&mut emulator, MenuInput::Select), None);
+        input(&mut emulator, MenuInput::Right);
+        input(&mut emulator, MenuInput::Right);
+        assert_eq!(input(&mut emulator, MenuInput::Select), Some(MenuAction::LoadState(2)));
+        assert_eq!(emulator.console().memory(0x_80), value);
+
+        // The switches are toggled without leaving the menu.
+        emulator.console_mut().set_tv_type_switch(TvType::Color);
+        emulator.console_mut().set_difficulty_switch(Player::Two, Difficulty::Amateur);
+
+        emulator.pause();
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Select);
+        assert_eq!(emulator.console().tv_type_switch(), TvType::Mono);
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Select);
+        assert_eq!(emulator.console().difficulty_switch(Player::Two), Difficulty::Pro);
+        input(&mut emulator, MenuInput::Select);
+        assert_eq!(emulator.console().difficulty_switch(Player::Two), Difficulty::Amateur);
+        assert!(emulator.is_paused());
+
+        // Resetting restores the initial state.
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Up);
+        input(&mut emulator, MenuInput::Up);
+        assert_eq!(input(&mut emulator, MenuInput::Select), Some(MenuAction::Reset));
+        assert_eq!(emulator.console().memory(0x_80), 0);
+        assert!(!emulator.is_paused());
+
+        // Quitting is left to the frontend.
+        emulator.pause();
+        input(&mut emulator, MenuInput::Up);
+        assert_eq!(input(&mut emulator, MenuInput::Select), Some(MenuAction::Quit));
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    fn test_gain_envelope() {
+        // A constant output at full volume on the left channel.
+        let mut rom = assemble("
+                lda #0
+                sta $15 ; AUDC0
+                lda #15
+                sta $19 ; AUDV0
+            end:
+                jmp end
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(rom)))
+            .with_clock(clock.clone());
+        assert_eq!(emulator.gain_envelope(), GainEnvelope::default());
+        emulator.tick();
+
+        clock.advance(Duration::from_millis(20));
+        emulator.tick();
+        let samples = emulator.console_mut().take_audio_samples();
+        assert_eq!(*samples.last().unwrap(), 0.5);
+
+        // The audio fades out from the last sample to silence when the
+        // emulator is paused.
+        emulator.pause();
+        let samples = emulator.console_mut().take_audio_samples();
+        assert_eq!(samples.len(), 157);
+        assert!(samples.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(samples[0] < 0.5);
+        assert_eq!(*samples.last().unwrap(), 0.0);
+
+        emulator.pause();
+        assert_eq!(emulator.console().queued_audio_samples(), 0);
+
+        // It fades in when the emulator resumes.
+        emulator.resume();
+        clock.advance(Duration::from_millis(20));
+        emulator.tick();
+        let samples = emulator.console_mut().take_audio_samples();
+        assert!(samples[..157].windows(2).all(|pair| pair[1] > pair[0]));
+        assert!(samples[0] < 0.01);
+        assert!(samples[157..].iter().all(|sample| *sample == 0.5));
+
+        // No fade at all.
+        emulator.set_gain_envelope(GainEnvelope::none());
+        emulator.pause();
+        assert_eq!(emulator.console().queued_audio_samples(), 0);
+
+        emulator.resume();
+        clock.advance(Duration::from_millis(10));
+        emulator.tick();
+        let samples = emulator.console_mut().take_audio_samples();
+        assert!(samples.iter().all(|sample| *sample == 0.5));
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+/// An event of the emulation.
+///
+/// The events are published by the console as they happen, in the middle of
+/// an update, to the subscribers of its event bus (see `Console::subscribe()`).
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// A frame was completed (see `Console::frame()`); it's the number of the
+    /// frame.
+    FrameCompleted { frame: u64 },
+    /// The game turned the vertical sync on, at the given scanline of the
+    /// frame being drawn.
+    VsyncStart { frame: u64, scanline: u32 },
+    /// The cartridge switched from a bank to another.
+    BankSwitched { from: usize, to: usize },
+    /// The simulation stopped right before the instruction at the address of
+    /// a breakpoint.
+    BreakpointHit { address: u16 },
+    /// The CPU fetched an opcode it doesn't know.
+    UnknownOpcode { address: u16, opcode: u8 },
+    /// Collision bits were latched; the address of the collision register
+    /// (from CXM0P to CXPPMM) and its bits that were set.
+    CollisionLatched { register: u16, bits: u8 }
+}
+
+/// A handle of a subscription to an event bus.
+///
+/// It's returned when subscribing, and used to unsubscribe.
+///
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SubscriptionId(u64);
+
+// A callback receiving the events.
+type Subscriber = Box<dyn FnMut(&Event) + Send>;
+
+/// A bus dispatching the events of the emulation to subscribers.
+///
+/// Subscribers are callbacks receiving every event, in the order they were
+/// published; they filter the ones they're interested in. Frontends, scripts
+/// and loggers subscribe independently of each other, and the console
+/// doesn't know about them.
+///
+/// The events are only produced when there are subscribers; some of them are
+/// detected by comparing the state of the console before and after each
+/// cycle, which isn't free.
+///
+pub struct EventBus {
+    subscribers: Vec<(SubscriptionId, Subscriber)>,
+    next_id: u64,
+    is_muted: bool
+}
+
+impl EventBus {
+    /// Create an event bus without subscribers.
+    pub fn new() -> EventBus {
+        EventBus {
+            subscribers: Vec::new(),
+            next_id: 0,
+            is_muted: false
+        }
+    }
+
+    /// Add a subscriber.
+    pub fn subscribe<F: FnMut(&Event) + Send + 'static>(&mut self, callback: F) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+
+        self.subscribers.push((id, Box::new(callback)));
+        id
+    }
+
+    /// Remove a subscriber.
+    ///
+    /// This function returns whether the subscriber was found.
+    ///
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.subscribers.len();
+        self.subscribers.retain(|(subscriber_id, _)| *subscriber_id != id);
+
+        self.subscribers.len() != count
+    }
+
+    /// Check whether there are subscribers.
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Mute the bus.
+    ///
+    /// This function stops the events from being sent to the subscribers,
+    /// until the bus is unmuted; the events published in the meantime are
+    /// dropped. It's used while the console runs a frame which is thrown away
+    /// afterwards.
+    ///
+    pub fn set_muted(&mut self, is_muted: bool) {
+        self.is_muted = is_muted;
+    }
+
+    /// Check whether the bus is muted.
+    pub fn is_muted(&self) -> bool {
+        self.is_muted
+    }
+
+    /// Send an event to all subscribers.
+    ///
+    /// The event is dropped if the bus is muted.
+    ///
+    pub fn publish(&mut self, event: Event) {
+        if self.is_muted {
+            return
+        }
+
+        for (_, callback) in self.subscribers.iter_mut() {
+            callback(&event);
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::rom_builder::RomBuilder;
+    use crate::analysis::BankSwitching;
+
+    #[test]
+    fn test_event_bus() {
+        let mut bus = EventBus::new();
+        assert!(!bus.has_subscribers());
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let id = bus.subscribe(move |event| events_clone.lock().unwrap().push(*event));
+        assert!(bus.has_subscribers());
+
+        bus.publish(Event::BreakpointHit { address: 0x_F000 });
+        bus.set_muted(true);
+        assert!(bus.is_muted());
+        bus.publish(Event::BreakpointHit { address: 0x_F001 });
+        bus.set_muted(false);
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.publish(Event::BreakpointHit { address: 0x_F002 });
+
+        assert_eq!(*events.lock().unwrap(), vec![Event::BreakpointHit { address: 0x_F000 }]);
+    }
+
+    #[test]
+    fn test_console_events() {
+        // A vertical sync every frame, and an unknown opcode.
+        let mut rom = assemble("
+            start:
+                lda #2
+                sta $00  ; VSYNC
+                sta $02  ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #0
+            first_loop:
+                sta $02
+                dex
+                bne first_loop
+                ldx #10
+            second_loop:
+                sta $02
+                dex
+                bne second_loop
+                .byte $03
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        console.subscribe(move |event| events_clone.lock().unwrap().push(*event));
+
+        console.add_breakpoint(0x_F002);
+        console.run_frame();
+        assert_eq!(*events.lock().unwrap(), vec![Event::BreakpointHit { address: 0x_F002 }]);
+        console.remove_breakpoint(0x_F002);
+
+        events.lock().unwrap().clear();
+        for _ in 0..3 {
+            console.run_frame();
+        }
+
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(event, Event::VsyncStart { .. })));
+        assert!(events.iter().any(|event| matches!(event, Event::UnknownOpcode { opcode: 0x_03, .. })));
+        assert_eq!(
+            events.iter().filter(|event| matches!(event, Event::FrameCompleted { .. })).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_bank_switched_event() {
+        // The first bank selects the second one, which selects the first one
+        // back.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "lda $FFF9")
+            .code(0x_F006, "loop: jmp loop")
+            .bank_switching(BankSwitching::F8)
+            .bank(1)
+            .code(0x_F003, "lda $FFF8")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        console.subscribe(move |event| {
+            if let Event::BankSwitched { .. } = event {
+                events_clone.lock().unwrap().push(*event);
+            }
+        });
+
+        console.run_frame();
+        assert_eq!(*events.lock().unwrap(), vec![
+            Event::BankSwitched { from: 0, to: 1 },
+            Event::BankSwitched { from: 1, to: 0 }
+        ]);
+    }
+}
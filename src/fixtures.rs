@@ -0,0 +1,124 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+use crate::rom_builder::RomBuilder;
+
+/// The environment variable naming the directory of the ROM files of the
+/// tests.
+pub(crate) const FIXTURES_VARIABLE: &str = "ATARI_2600_FIXTURES";
+
+/// A ROM of the tests.
+///
+/// This function loads a ROM from the directory named by the environment
+/// variable `ATARI_2600_FIXTURES` if it's set and it has the ROM, so the
+/// tests run against the real ROMs on the machines that have them; otherwise
+/// it builds the synthetic ROM of the same name (with `RomBuilder`), so the
+/// full test suite runs on any machine. It panics if the name isn't the one
+/// of a synthetic ROM.
+///
+pub(crate) fn rom(name: &str) -> Cartridge {
+    rom_from(env::var_os(FIXTURES_VARIABLE).map(PathBuf::from).as_deref(), name)
+}
+
+fn rom_from(directory: Option<&Path>, name: &str) -> Cartridge {
+    if let Some(path) = directory.map(|directory| directory.join(name)).filter(|path| path.is_file()) {
+        return Cartridge::from_file(&path)
+            .unwrap_or_else(|error| panic!("can't load the fixture {}: {}", path.display(), error));
+    }
+
+    synthetic_rom(name).unwrap_or_else(|| panic!("there is no synthetic ROM named {}", name))
+}
+
+/// A ROM counting in the RAM.
+///
+/// This function returns a 4 KB ROM which increments the given location of
+/// the RAM and jumps back (INC and JMP $F000), the rest filled with NOPs; it's
+/// what the tests run when they only need the console to do something.
+///
+pub(crate) fn counter_rom(location: u8) -> Vec<u8> {
+    let mut rom = vec![
+        0x_E6, location,     // INC location
+        0x_4C, 0x_00, 0x_F0  // JMP $F000
+    ];
+    rom.resize(4096, 0x_EA);
+
+    rom
+}
+
+// The synthetic ROMs, built from their source.
+fn synthetic_rom(name: &str) -> Option<Cartridge> {
+    let source = match name {
+        // A standard NTSC kernel; 3 lines of vertical sync, 37 lines of
+        // vertical blank, 192 visible lines with a background changing color
+        // on each line, and 30 lines of overscan.
+        "kernel_01.bin" => "
+            frame:
+                lda #2
+                sta $01   ; VBLANK
+                sta $00   ; VSYNC
+                sta $02   ; WSYNC
+                sta $02   ; WSYNC
+                sta $02   ; WSYNC
+                lda #0
+                sta $00   ; VSYNC
+                ldx #37
+            vblank:
+                sta $02   ; WSYNC
+                dex
+                bne vblank
+                lda #0
+                sta $01   ; VBLANK
+                ldx #192
+            picture:
+                stx $09   ; COLUBK
+                sta $02   ; WSYNC
+                dex
+                bne picture
+                lda #2
+                sta $01   ; VBLANK
+                ldx #30
+            overscan:
+                sta $02   ; WSYNC
+                dex
+                bne overscan
+                jmp frame
+        ",
+        _ => return None
+    };
+
+    Some(RomBuilder::new().code(0x_F000, source).build().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_fixtures() {
+        let synthetic = rom_from(None, "kernel_01.bin");
+        assert_eq!(synthetic.bank_count(), 1);
+
+        // The ROM files of the directory take precedence over the synthetic
+        // ROMs, which fill in for the missing ones.
+        let directory = env::temp_dir().join(format!("atari-2600-fixtures-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("kernel_01.bin"), vec![0x_EA; 2048]).unwrap();
+
+        assert_ne!(rom_from(Some(&directory), "kernel_01.bin").md5(), synthetic.md5());
+        fs::remove_file(directory.join("kernel_01.bin")).unwrap();
+        assert_eq!(rom_from(Some(&directory), "kernel_01.bin").md5(), synthetic.md5());
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(std::panic::catch_unwind(|| rom_from(None, "missing.bin")).is_err());
+    }
+}
@@ -0,0 +1,462 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::console::Console;
+use crate::png::encode_png;
+
+// The characters of the text representations, from the darkest to the
+// brightest.
+const ASCII_RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+const BLOCK_RAMP: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+
+// The number of columns of the text representation of the debug output.
+const DEBUG_TEXT_COLUMNS: usize = 80;
+
+/// A rotation of the frame, clockwise.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters
+}
+
+/// A flip of the frame.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flip {
+    None,
+    /// The left and the right are swapped.
+    Horizontal,
+    /// The top and the bottom are swapped.
+    Vertical
+}
+
+/// How a frame is presented.
+///
+/// The frame is flipped first, then rotated. It's meant for vertical arcade
+/// cabinets and handhelds whose display is mounted rotated; the pixels of the
+/// frame aren't transformed, the transform is applied when the frame is
+/// presented (see `Scaler`).
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub flip: Flip
+}
+
+impl Default for Orientation {
+    fn default() -> Orientation {
+        Orientation {
+            rotation: Rotation::None,
+            flip: Flip::None
+        }
+    }
+}
+
+impl Orientation {
+    /// Check whether the width and the height are swapped.
+    pub fn is_sideways(&self) -> bool {
+        matches!(self.rotation, Rotation::Quarter | Rotation::ThreeQuarters)
+    }
+
+    /// Map a position of the presented frame to a position of the frame.
+    ///
+    /// This function takes a position of the presented frame and returns the
+    /// position of the frame it shows; the positions are relative to the size
+    /// of the frames, between 0.0 and 1.0.
+    ///
+    pub fn unapply(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (y, 1.0 - x),
+            Rotation::Half => (1.0 - x, 1.0 - y),
+            Rotation::ThreeQuarters => (1.0 - y, x)
+        };
+
+        match self.flip {
+            Flip::None => (x, y),
+            Flip::Horizontal => (1.0 - x, y),
+            Flip::Vertical => (x, 1.0 - y)
+        }
+    }
+}
+
+/// How the frames skipped by a step are combined.
+///
+/// Several games flicker their objects, drawing them every other frame; the
+/// learning environments take the maximum of the last two frames of each step
+/// to see all of them.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FramePooling {
+    /// The last frame of the step.
+    Last,
+    /// The maximum of each color component of the last two frames.
+    Max
+}
+
+/// A rectangular region of a frame (in pixels).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Region {
+    /// The whole frame.
+    pub const FULL: Region = Region { x: 0, y: 0, width: Frame::WIDTH, height: Frame::HEIGHT };
+}
+
+/// The characters of the text representation of a frame.
+///
+/// See `Frame::to_text()`.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextCharset {
+    /// 10 levels of brightness drawn with ASCII characters (` .:-=+*#%@`).
+    Ascii,
+    /// 5 levels of brightness drawn with the Unicode shade blocks (` ░▒▓█`).
+    Blocks
+}
+
+/// A TV frame produced by the console.
+///
+/// A frame is a snapshot of the video output of the console, taken when the
+/// electron beam has finished drawing the visible area and goes back to the
+/// top of the screen. It's made of 192 lines of 160 pixels each and every
+/// pixel is a RGB color.
+///
+/// Frames are numbered from 1, in the order they were completed since the
+/// console was turned on. They also carry how they're presented; the frames of
+/// the console are upright, the emulator applies its orientation to them.
+///
+#[derive(Clone)]
+pub struct Frame {
+    pub number: u64,
+    pub pixels: [[(u8, u8, u8); 160]; 192],
+    pub orientation: Orientation
+}
+
+impl Frame {
+    /// Width of a frame (in pixels).
+    pub const WIDTH: usize = 160;
+
+    /// Height of a frame (in pixels).
+    pub const HEIGHT: usize = 192;
+
+    /// Color of a given pixel.
+    ///
+    /// This function returns the RGB color of the pixel located at column `x`
+    /// and line `y` of the frame.
+    ///
+    pub fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        self.pixels[y][x]
+    }
+
+    /// PNG image of the frame.
+    ///
+    /// This function returns the frame encoded as an (uncompressed) PNG
+    /// image, regardless of its orientation.
+    ///
+    pub fn to_png(&self) -> Vec<u8> {
+        let pixels: Vec<(u8, u8, u8)> = self.pixels.iter().flatten().copied().collect();
+        encode_png(Frame::WIDTH, Frame::HEIGHT, &pixels)
+    }
+
+    /// Text representation of the frame.
+    ///
+    /// This function returns a coarse picture of the frame made of characters
+    /// whose density follows the brightness of the pixels (see
+    /// `grayscale()`), the given number of columns wide, one line of text per
+    /// line. As the pixels are twice as wide as they're high and characters
+    /// are about twice as high as they're wide, there are about 3 lines for
+    /// 10 columns; 80 columns make 24 lines.
+    ///
+    /// It's meant for terminals, screen readers and logs; a test that fails
+    /// shows recognizable screen content without opening an image (the
+    /// frames are printed this way when they're debugged).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TextCharset, TEST_ROM};
+    /// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// println!("{}", console.frame().to_text(80, TextCharset::Blocks));
+    /// ```
+    ///
+    pub fn to_text(&self, columns: usize, charset: TextCharset) -> String {
+        let ramp: &[char] = match charset {
+            TextCharset::Ascii => &ASCII_RAMP,
+            TextCharset::Blocks => &BLOCK_RAMP
+        };
+
+        let columns = columns.max(1);
+        let lines = (columns * 3 / 10).max(1);
+
+        let pixels = self.grayscale(Region::FULL, columns, lines);
+        let mut text = String::with_capacity(lines * (columns + 1));
+        for line in pixels.chunks(columns) {
+            for luminance in line {
+                text.push(ramp[*luminance as usize * ramp.len() / 256]);
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Maximum of two frames.
+    ///
+    /// This function returns the frame whose color components are the
+    /// maximum of the ones of the two frames; the number and the orientation
+    /// are the ones of this frame.
+    ///
+    pub fn max(&self, other: &Frame) -> Frame {
+        let mut frame = self.clone();
+        for (line, other_line) in frame.pixels.iter_mut().zip(other.pixels.iter()) {
+            for (pixel, other_pixel) in line.iter_mut().zip(other_line.iter()) {
+                pixel.0 = pixel.0.max(other_pixel.0);
+                pixel.1 = pixel.1.max(other_pixel.1);
+                pixel.2 = pixel.2.max(other_pixel.2);
+            }
+        }
+
+        frame
+    }
+
+    /// Grayscale version of a region of the frame.
+    ///
+    /// This function crops the frame to a region and resizes it to the given
+    /// size, and returns the luminance of its pixels, line by line, one byte
+    /// per pixel. It's the preprocessing agents usually apply to the frames
+    /// before they're observed (84x84 luminance, for instance).
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, Region, TEST_ROM};
+    /// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// let observation = console.frame().grayscale(Region::FULL, 84, 84);
+    /// assert_eq!(observation.len(), 84 * 84);
+    /// ```
+    ///
+    pub fn grayscale(&self, region: Region, width: usize, height: usize) -> Vec<u8> {
+        let mut pixels = vec![0; width * height];
+        self.grayscale_into(region, width, height, &mut pixels);
+
+        pixels
+    }
+
+    /// Grayscale version of a region of the frame, in a buffer.
+    ///
+    /// This function is the same as `grayscale()` but it writes the pixels
+    /// to a buffer of `width * height` bytes, which avoids allocating one for
+    /// each frame.
+    ///
+    /// Each pixel of the result is the average luminance of the pixels of the
+    /// region it covers when it's downsampled (like the "area" interpolation
+    /// of the image libraries), or the luminance of the nearest pixel when
+    /// it's upsampled. The luminance is computed in fixed point on whole lines
+    /// of the region, which the compiler vectorizes.
+    ///
+    pub fn grayscale_into(&self, region: Region, width: usize, height: usize, pixels: &mut [u8]) {
+        assert!(region.width > 0 && region.height > 0, "region can't be empty");
+        assert!(region.x + region.width <= Frame::WIDTH && region.y + region.height <= Frame::HEIGHT, "region must be inside the frame");
+        assert!(width > 0 && height > 0, "size can't be null");
+        assert_eq!(pixels.len(), width * height, "buffer must be of {} bytes", width * height);
+
+        let columns = spans(region.width, width);
+        let lines = spans(region.height, height);
+
+        let mut luminances = vec![0; region.width];
+        let mut sums = vec![0; width];
+
+        for (output, (top, bottom)) in pixels.chunks_exact_mut(width).zip(lines) {
+            sums.iter_mut().for_each(|sum| *sum = 0);
+
+            for line in &self.pixels[region.y + top..region.y + bottom] {
+                luminance_line(&line[region.x..region.x + region.width], &mut luminances);
+
+                for (sum, &(left, right)) in sums.iter_mut().zip(columns.iter()) {
+                    *sum += luminances[left..right].iter().sum::<u32>();
+                }
+            }
+
+            // The luminances are scaled by 256; round the averages.
+            for (pixel, (sum, (left, right))) in output.iter_mut().zip(sums.iter().zip(columns.iter())) {
+                let area = ((right - left) * (bottom - top)) as u32;
+                *pixel = ((sum + area * 128) / (area * 256)) as u8;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Frame {} ({:?})", self.number, self.orientation)?;
+        write!(f, "{}", self.to_text(DEBUG_TEXT_COLUMNS, TextCharset::Ascii))
+    }
+}
+
+// The luminance of each pixel of a line (ITU-R BT.601), scaled by 256.
+fn luminance_line(line: &[(u8, u8, u8)], luminances: &mut [u32]) {
+    for (luminance, &(red, green, blue)) in luminances.iter_mut().zip(line) {
+        *luminance = 77 * red as u32 + 150 * green as u32 + 29 * blue as u32;
+    }
+}
+
+// The source pixels covered by each of the output pixels, when a length is
+// resized to another; each output pixel covers at least one source pixel.
+pub(crate) fn spans(source: usize, output: usize) -> Vec<(usize, usize)> {
+    (0..output).map(|index| {
+        let start = index * source / output;
+        let end = ((index + 1) * source / output).max(start + 1);
+
+        (start, end)
+    }).collect()
+}
+
+/// An iterator over the frames of a console.
+///
+/// This iterator advances the simulation of the console one frame at a time,
+/// as fast as possible (time isn't taken into account), and yields each of the
+/// completed frames. It's infinite (unless a jam of the CPU is fatal) and is
+/// meant to be used with the iterator adapters of the standard library.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Frame, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn save_frame(_frame: Frame) {}
+/// for frame in console.frames().take(60) {
+///     save_frame(frame);
+/// }
+/// ```
+///
+/// It's created by the `Console::frames()` function.
+///
+pub struct Frames<'a> {
+    console: &'a mut Console
+}
+
+impl<'a> Frames<'a> {
+    pub(crate) fn new(console: &'a mut Console) -> Frames<'a> {
+        Frames { console }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        // Breakpoints may interrupt the simulation before the frame is
+        // completed, and a fatal jam of the CPU stops it for good.
+        while !self.console.run_frame().has_new_frame() {
+            if self.console.is_jammed() && self.console.is_jam_fatal() {
+                return None
+            }
+        }
+
+        Some(self.console.frame())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_frames() {
+        // A ROM that loops forever, giving the TIA time to complete frames.
+        let mut rom = vec![
+            0x_4C, 0x_00, 0x_F0 // Jump to location 0x_F000
+        ];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        let numbers: Vec<u64> = console.frames().take(3).map(|frame| frame.number).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+
+        // The iterator doesn't hold the console; it can be resumed later.
+        let frame = console.frames().next().unwrap();
+        assert_eq!(frame.number, 4);
+        assert_eq!(console.frame().number, 4);
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let mut frame = Frame {
+            number: 1,
+            pixels: [[(0, 0, 0); 160]; 192],
+            orientation: Orientation::default()
+        };
+
+        // White, and a pure red, green and blue pixel.
+        frame.pixels[0][0] = (255, 255, 255);
+        frame.pixels[0][1] = (255, 0, 0);
+        frame.pixels[0][2] = (0, 255, 0);
+        frame.pixels[0][3] = (0, 0, 255);
+
+        let region = Region { x: 0, y: 0, width: 4, height: 1 };
+        assert_eq!(frame.grayscale(region, 4, 1), vec![255, 77, 149, 29]);
+
+        // Downsampling averages the pixels, upsampling repeats them.
+        assert_eq!(frame.grayscale(region, 2, 1), vec![166, 89]);
+        assert_eq!(frame.grayscale(region, 8, 2), vec![
+            255, 255, 77, 77, 149, 149, 29, 29,
+            255, 255, 77, 77, 149, 149, 29, 29
+        ]);
+
+        // The region is cropped out of the frame.
+        frame.pixels[100][50] = (200, 200, 200);
+        let region = Region { x: 50, y: 100, width: 2, height: 2 };
+        assert_eq!(frame.grayscale(region, 1, 1), vec![50]);
+        assert_eq!(frame.grayscale(region, 2, 2), vec![200, 0, 0, 0]);
+
+        // The usual observation of the agents; the pixels are averaged with
+        // the line below them.
+        let mut pixels = vec![0xFF; 84 * 84];
+        frame.grayscale_into(Region::FULL, 84, 84, &mut pixels);
+        assert_eq!(pixels[0], 128);
+        assert_eq!(pixels[1], 57);
+        assert_eq!(pixels.iter().filter(|&&pixel| pixel != 0).count(), 4);
+    }
+
+    #[test]
+    fn test_to_text() {
+        let mut frame = Frame {
+            number: 1,
+            pixels: [[(0, 0, 0); 160]; 192],
+            orientation: Orientation::default()
+        };
+
+        // A white square in the top left corner, and a gray band at the
+        // bottom.
+        for line in frame.pixels[..64].iter_mut() {
+            line[..16].iter_mut().for_each(|pixel| *pixel = (255, 255, 255));
+        }
+        frame.pixels[128..].iter_mut().for_each(|line| *line = [(128, 128, 128); 160]);
+
+        let text = frame.to_text(10, TextCharset::Ascii);
+        assert_eq!(text, "@         \n          \n++++++++++\n");
+
+        let text = frame.to_text(10, TextCharset::Blocks);
+        assert_eq!(text.lines().next().unwrap(), "\u{2588}         ");
+
+        // 80 columns make 24 lines.
+        let text = frame.to_text(80, TextCharset::Ascii);
+        assert_eq!(text.lines().count(), 24);
+        assert!(text.lines().all(|line| line.len() == 80));
+        assert!(format!("{:?}", frame).starts_with("Frame 1 ("));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::frame::Frame;
+use crate::overlay::{draw_text, fill_rectangle};
+
+// The number of scanlines of a frame, to scale the positions to the height of
+// the frame in the overlay.
+const SCANLINES: usize = 262;
+
+/// A read of an input register by the game.
+///
+/// The register is the address that was read (SWCHA, or INPT0 to INPT5) and
+/// the position is the position of the beam when it was read.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InputRead {
+    pub register: u16,
+    pub scanline: u32,
+    pub cycle: u32
+}
+
+/// The timing of the inputs during a frame.
+///
+/// This structure tells where in the frame the inputs were changed (by the
+/// frontend or by the controllers) and where the game read them. An input
+/// that changes after the game read it for the last time in a frame is only
+/// seen in the next frame; frontend authors can use these measurements to
+/// poll the host inputs right before the game reads them, and lower the input
+/// latency.
+///
+/// The measurements are enabled with `Console::enable_input_timing()`.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # console.enable_input_timing();
+/// # console.run_frame();
+/// # console.run_frame();
+/// # let mut frame = console.frame();
+/// if let Some(timing) = console.input_timing() {
+///     println!("the game reads the inputs at scanline {:?}", timing.first_read().map(|read| read.scanline));
+///     timing.render(&mut frame);
+/// }
+/// ```
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct InputTiming {
+    /// Number of the frame.
+    pub frame: u64,
+    /// Positions of the beam (scanline and cycle) when the inputs changed.
+    pub changes: Vec<(u32, u32)>,
+    /// Reads of the input registers, in order.
+    pub reads: Vec<InputRead>
+}
+
+impl InputTiming {
+    pub(crate) fn new(frame: u64) -> InputTiming {
+        InputTiming {
+            frame,
+            changes: Vec::new(),
+            reads: Vec::new()
+        }
+    }
+
+    /// First read of the inputs in the frame.
+    pub fn first_read(&self) -> Option<&InputRead> {
+        self.reads.first()
+    }
+
+    /// Last read of the inputs in the frame.
+    pub fn last_read(&self) -> Option<&InputRead> {
+        self.reads.last()
+    }
+
+    /// Number of scanlines between the last change of the inputs and their
+    /// first read that followed.
+    ///
+    /// This function returns how long the inputs waited before the game read
+    /// them; it's `None` if they didn't change or if they weren't read after
+    /// changing in the frame.
+    ///
+    pub fn read_delay(&self) -> Option<u32> {
+        let (scanline, cycle) = *self.changes.last()?;
+        let read = self.reads.iter().find(|read| (read.scanline, read.cycle) >= (scanline, cycle))?;
+
+        Some(read.scanline - scanline)
+    }
+
+    /// Draw the timing over a frame.
+    ///
+    /// This function draws the position of the changes (in red) and of the
+    /// reads (in green) as ticks on the right edge of the frame, scaled from
+    /// the scanlines of the whole frame, and the scanline of the first read at
+    /// the bottom.
+    ///
+    pub fn render(&self, frame: &mut Frame) {
+        let red = (255, 64, 64);
+        let green = (64, 255, 64);
+
+        let y = |scanline: u32| (scanline as usize * Frame::HEIGHT / SCANLINES).min(Frame::HEIGHT - 1);
+
+        fill_rectangle(frame, Frame::WIDTH - 12, 0, 12, Frame::HEIGHT, (0, 0, 0));
+        for (scanline, _) in self.changes.iter() {
+            fill_rectangle(frame, Frame::WIDTH - 12, y(*scanline), 5, 1, red);
+        }
+        for read in self.reads.iter() {
+            fill_rectangle(frame, Frame::WIDTH - 6, y(read.scanline), 5, 1, green);
+        }
+
+        if let Some(read) = self.first_read() {
+            draw_text(frame, 8, Frame::HEIGHT - 8, &format!("READ: {}", read.scanline), green);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::assembler::assemble;
+    use crate::location::{SWCHA, INPT4};
+
+    #[test]
+    fn test_input_timing() {
+        // STA WSYNC 100 times (with X as counter), LDA SWCHA, LDA INPT4, then
+        // loop forever.
+        let mut rom = assemble("
+                ldx #100
+            wait:
+                sta $02
+                dex
+                bne wait
+                lda $0280
+                lda $3C
+            end:
+                jmp end
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert!(console.input_timing().is_none());
+
+        console.enable_input_timing();
+        console.drive_port_a_pin(4, !console.port_a_pin(4));
+        console.run_frame();
+
+        let timing = console.input_timing().unwrap();
+        assert_eq!(timing.frame, 1);
+        assert_eq!(timing.changes, vec![(0, 0)]);
+        assert_eq!(timing.reads.len(), 2);
+        assert_eq!(timing.reads[0].register, SWCHA);
+        assert_eq!(timing.reads[0].scanline, 100);
+        assert_eq!(timing.last_read().unwrap().register, INPT4);
+        assert_eq!(timing.read_delay(), Some(100));
+
+        // The reads are drawn on the right edge, scaled to the frame.
+        let mut frame = console.frame();
+        timing.render(&mut frame);
+        assert_eq!(frame.pixel(Frame::WIDTH - 4, 73), (64, 255, 64));
+        assert_eq!(frame.pixel(Frame::WIDTH - 10, 0), (255, 64, 64));
+        assert_eq!(frame.pixel(Frame::WIDTH - 4, 0), (0, 0, 0));
+
+        // Nothing is read in the next frame.
+        console.run_frame();
+        let timing = console.input_timing().unwrap();
+        assert_eq!(timing.frame, 2);
+        assert!(timing.reads.is_empty());
+        assert_eq!(timing.read_delay(), None);
+
+        console.disable_input_timing();
+        assert!(console.input_timing().is_none());
+    }
+}
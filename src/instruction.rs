@@ -30,12 +30,56 @@
 //! - SBC, SEC, SED, SEI, STA, STX, STY
 //! - TAX, TAY, TSX, TXA, TXS, TYA
 //!
-//! TODO; Mark instructions that were excluded.
+//! The undocumented KIL opcodes (also called JAM) are implemented as well since
+//! they have a drastic effect; they jam the CPU.
 //!
-//! Note that they're tightly coupled with the **Console** struct. In fact,
-//! they were put outside just to increase readability.
+//! The other undocumented opcodes aren't implemented; the processor reports
+//! them as unknown instead of executing them (see `UnknownOpcode`).
 //!
-use super::console::Console;
+//! **Interrupts**
+//!
+//! Unlike the 6502, the 6507 has no IRQ and NMI pins; no external interrupt
+//! can ever happen and BRK is the only instruction going through the
+//! interrupt vector (0xFFFE). The interrupt flag only masks the IRQ line, so
+//! it has no effect on the console, but it's still set and cleared by the
+//! instructions and saved with the status on the stack. What's intentionally
+//! absent is everything about the lines; the NMI vector isn't used, and the
+//! one-instruction delay before a change of the interrupt flag by CLI, SEI and
+//! PLP masks the line isn't simulated, as there's no line to poll.
+//!
+//! The break flag isn't a flag of the processor; it only exists in the copies
+//! of the status pushed on the stack, where it tells BRK apart from an
+//! interrupt. It's always set when pushed by PHP and BRK, and so is bit 5,
+//! which isn't connected to anything. Pulling the status with PLP or RTI
+//! leaves it unchanged, and restores all the other flags (including the
+//! interrupt flag) right away.
+//!
+//! Each instruction takes the processor and the bus it's connected to (see
+//! `Cpu6507` and `Bus`), along with the opcode, which was already fetched.
+//! They don't depend on the console; they can be reused by any machine built
+//! around a 6502 family processor, as long as it implements the bus.
+//!
+//! ```
+//! # use atari_2600::{Bus, Cpu6507};
+//! # use atari_2600::instruction::lda_instruction;
+//! # struct Memory([u8; 65536]);
+//! # impl Bus for Memory {
+//! #     fn read(&mut self, address: u16) -> u8 { self.0[address as usize] }
+//! #     fn write(&mut self, address: u16, value: u8) { self.0[address as usize] = value; }
+//! # }
+//! # let mut bus = Memory([0x_A9; 65536]);
+//! # let mut cpu = Cpu6507::new();
+//! # cpu.reset(&mut bus);
+//! let opcode = cpu.fetch(&mut bus);
+//! let cycles = match opcode {
+//!     0x_A9 | 0x_A5 | 0x_B5 | 0x_AD | 0x_BD | 0x_B9 | 0x_A1 | 0x_B1 => lda_instruction(&mut cpu, &mut bus, opcode),
+//!     // ...
+//! #   _ => unreachable!()
+//! };
+//! ```
+//!
+use crate::cpu::Cpu6507;
+use crate::bus::Bus;
 use super::addressing_mode::*;
 
 /// Increment a byte value by one.
@@ -67,10 +111,78 @@ fn update_zero_and_negative_flags(value: &u8, zero_flag: &mut bool, negative_fla
     *negative_flag = *value > 127;
 }
 
-/// Brief description.
+/// Compose the status byte.
+///
+/// This function composes the status byte pushed on the stack from the
+/// status flags. Bit 5 is always set, and the break flag (bit 4) is set when
+/// the status is pushed by an instruction (PHP or BRK) rather than by an
+/// interrupt.
+///
+fn status_byte(cpu: &Cpu6507, is_instruction: bool) -> u8 {
+    let mut status_flag = 0b0010_0000u8;
+    if cpu.negative_flag  { status_flag |= 0b1000_0000 };
+    if cpu.overflow_flag  { status_flag |= 0b0100_0000 };
+    if is_instruction     { status_flag |= 0b0001_0000 };
+    if cpu.decimal_flag   { status_flag |= 0b0000_1000 };
+    if cpu.interrupt_flag { status_flag |= 0b0000_0100 };
+    if cpu.zero_flag      { status_flag |= 0b0000_0010 };
+    if cpu.carry_flag     { status_flag |= 0b0000_0001 };
+
+    status_flag
+}
+
+/// Restore the status flags.
 ///
-/// Long description.
+/// This function restores the status flags from a status byte pulled from the
+/// stack. The break flag (bit 4) and bit 5 aren't flags of the processor and
+/// are ignored.
 ///
+fn restore_status(cpu: &mut Cpu6507, status_flag: u8) {
+    cpu.negative_flag  = status_flag & 0b1000_0000 > 0;
+    cpu.overflow_flag  = status_flag & 0b0100_0000 > 0;
+    cpu.decimal_flag   = status_flag & 0b0000_1000 > 0;
+    cpu.interrupt_flag = status_flag & 0b0000_0100 > 0;
+    cpu.zero_flag      = status_flag & 0b0000_0010 > 0;
+    cpu.carry_flag     = status_flag & 0b0000_0001 > 0;
+}
+
+/// The operand of a read-modify-write instruction.
+enum ModifiedOperand {
+    Accumulator,
+    Memory(u16)
+}
+
+/// Modify the operand of a read-modify-write instruction.
+///
+/// This function modifies the operand (the accumulator or a memory location)
+/// with the given function, and returns its new value. Like the processor, it
+/// writes a memory location twice; the unmodified value first (the dummy
+/// write), then the modified value. Both writes trigger the registers of the
+/// TIA strobed by writing, like WSYNC and HMOVE.
+///
+fn modify_operand<B: Bus, F>(cpu: &mut Cpu6507, bus: &mut B, operand: ModifiedOperand, modify: F) -> u8
+    where F: FnOnce(&mut Cpu6507, u8) -> u8
+{
+    match operand {
+        ModifiedOperand::Accumulator => {
+            let value = modify(cpu, cpu.accumulator);
+            cpu.accumulator = value;
+
+            value
+        },
+        ModifiedOperand::Memory(index) => {
+            let value = bus.read(index);
+            bus.write(index, value);
+
+            let value = modify(cpu, value);
+            bus.write(index, value);
+
+            value
+        }
+    }
+}
+
+/// Copy a byte from a register to another.
 fn transfer_byte(source: &mut u8, destination: &mut u8) {
     *destination = *source;
 }
@@ -103,37 +215,111 @@ fn shift_right(value: &mut u8, bit_in: bool, bit_out: &mut bool) {
     }
 }
 
+/// Add a value to the accumulator.
+///
+/// This function adds the value and the carry flag to the accumulator and
+/// updates the flags as ADC does. In decimal mode, the operands are read as
+/// two BCD digits and the result is adjusted after each digit; the zero flag
+/// is still computed from the binary sum and the negative and overflow flags
+/// from the sum before the high digit is adjusted, as on the NMOS 6502.
+///
+fn add_with_carry(cpu: &mut Cpu6507, value: u8) {
+    let accumulator = cpu.accumulator;
+    let carry = cpu.carry_flag as u16;
+
+    let binary = accumulator as u16 + value as u16 + carry;
+    if !cpu.decimal_flag {
+        cpu.accumulator = binary as u8;
+        cpu.carry_flag = binary > 0x_FF;
+        cpu.overflow_flag = (!(accumulator ^ value) & (accumulator ^ cpu.accumulator)) & 0x_80 > 0;
+        update_zero_and_negative_flags(&cpu.accumulator, &mut cpu.zero_flag, &mut cpu.negative_flag);
+        return;
+    }
+
+    let mut low = (accumulator & 0x_0F) as u16 + (value & 0x_0F) as u16 + carry;
+    if low > 9 {
+        low += 6;
+    }
+    let mut high = (accumulator >> 4) as u16 + (value >> 4) as u16 + (low > 0x_0F) as u16;
+
+    let intermediate = ((high << 4) | (low & 0x_0F)) as u8;
+    cpu.zero_flag = binary as u8 == 0;
+    cpu.negative_flag = intermediate & 0x_80 > 0;
+    cpu.overflow_flag = (!(accumulator ^ value) & (accumulator ^ intermediate)) & 0x_80 > 0;
+
+    if high > 9 {
+        high += 6;
+    }
+    cpu.carry_flag = high > 0x_0F;
+    cpu.accumulator = ((high << 4) | (low & 0x_0F)) as u8;
+}
+
+/// Subtract a value from the accumulator.
+///
+/// This function subtracts the value and the complement of the carry flag
+/// (the borrow) from the accumulator and updates the flags as SBC does. In
+/// decimal mode, the result is adjusted after each BCD digit, but all the
+/// flags are computed from the binary difference, as on the NMOS 6502.
+///
+fn subtract_with_borrow(cpu: &mut Cpu6507, value: u8) {
+    let accumulator = cpu.accumulator;
+    let borrow = !cpu.carry_flag as i16;
+
+    let binary = accumulator as i16 - value as i16 - borrow;
+    cpu.carry_flag = binary >= 0;
+    cpu.overflow_flag = ((accumulator ^ value) & (accumulator ^ binary as u8)) & 0x_80 > 0;
+    update_zero_and_negative_flags(&(binary as u8), &mut cpu.zero_flag, &mut cpu.negative_flag);
+
+    if !cpu.decimal_flag {
+        cpu.accumulator = binary as u8;
+        return;
+    }
+
+    let mut low = (accumulator & 0x_0F) as i16 - (value & 0x_0F) as i16 - borrow;
+    let mut high = (accumulator >> 4) as i16 - (value >> 4) as i16;
+    if low < 0 {
+        low -= 6;
+        high -= 1;
+    }
+    if high < 0 {
+        high -= 6;
+    }
+    cpu.accumulator = (((high << 4) | (low & 0x_0F)) & 0x_FF) as u8;
+}
+
 /// The ADC instruction.
 ///
 /// This instruction makes an addition with the accumulator, the operand and
 /// the value of the carry flag (0 or 1), and store it in the accumulator. If
 /// an overflow occurred, the carry flag is set to 1, otherwise it's set to 0.
-/// It also updates the zero and negative flags according to the final value.
+/// The overflow flag is set when the signed result doesn't fit in a byte (two
+/// operands of the same sign giving a result of the other sign). It also
+/// updates the zero and negative flags according to the final value.
 ///
-/// TODO; The documentation says the overflow flag is updated, but I'm unable
-/// to understand in which context.
+/// When the decimal flag is set, the operands are treated as BCD numbers (two
+/// decimal digits), which is what games use for their score counters.
 ///
-pub fn adc_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn adc_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
     let (index, cycles) = match opcode {
-        0x_69 => (immediate(console), 2),
-        0x_65 => (zero_page(console), 3),
-        0x_75 => (zero_page_x(console), 4),
-        0x_6D => (absolute(console), 4),
+        0x_69 => (immediate(cpu, bus), 2),
+        0x_65 => (zero_page(cpu, bus), 3),
+        0x_75 => (zero_page_x(cpu, bus), 4),
+        0x_6D => (absolute(cpu, bus), 4),
         0x_7D => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_79 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_61 => (indexed_indirect(console), 6),
+        0x_61 => (indexed_indirect(cpu, bus), 6),
         0x_71 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -141,29 +327,8 @@ pub fn adc_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {:#X} not associated to ADC instruction", opcode)
     };
 
-    let value = *console.memory_mut(index);
-
-    // The operation is A + M + 1, and thus, it can overflow during either of
-    // the two additions. We make sure to intercept if it's overflowing in both
-    // addition and update the cary flag accordingly.
-    let (new_value, has_overflowed_a) = console.accumulator.overflowing_add(value);
-    let (new_value, has_overflowed_b) = if console.carry_flag {
-        new_value.overflowing_add(1)
-    } else {
-        (new_value, false)
-    };
-
-    console.accumulator = new_value;
-    console.carry_flag = has_overflowed_a || has_overflowed_b;
-
-    update_zero_and_negative_flags(
-        &console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
-    );
-
-    // TODO; This flag is documented as potentially modified, but in which context ?
-    // console.overflow_flag = true;
+    let value = bus.read(index);
+    add_with_carry(cpu, value);
 
     cycles
 }
@@ -174,29 +339,29 @@ pub fn adc_instruction(console: &mut Console, opcode: u8) -> u32 {
 /// accumulator, then stores the result in the accumulator. It also updates the
 /// zero and negative flags according to the resulting value.
 ///
-pub fn and_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn and_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_29 => (immediate(console), 2),
-        0x_25 => (zero_page(console), 3),
-        0x_35 => (zero_page_x(console), 4),
+        0x_29 => (immediate(cpu, bus), 2),
+        0x_25 => (zero_page(cpu, bus), 3),
+        0x_35 => (zero_page_x(cpu, bus), 4),
 
-        0x_2D => (absolute(console), 4),
+        0x_2D => (absolute(cpu, bus), 4),
         0x_3D => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_39 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_21 => (indexed_indirect(console), 6),
+        0x_21 => (indexed_indirect(cpu, bus), 6),
         0x_31 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -204,13 +369,13 @@ pub fn and_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {:#X} not associated to AND instruction", opcode)
     };
 
-    let value = console.memory_mut(index);
-    console.accumulator = *value & console.accumulator;
+    let value = bus.read(index);
+    cpu.accumulator &= value;
 
     update_zero_and_negative_flags(
-        &console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -218,33 +383,32 @@ pub fn and_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The ASL instruction.
 ///
-/// Long description.
+/// This instruction shifts the bits of the accumulator or of a value in
+/// memory to the left; bit 0 is cleared and bit 7 goes to the carry flag. It
+/// also updates the zero and negative flags according to the resulting value.
 ///
-/// Unlike the ROL instruction, it doesn't shift the value with a the carry flag.
+/// Unlike the ROL instruction, it doesn't shift the value with the carry flag.
 ///
-pub fn asl_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn asl_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, cycles) = match opcode {
-        0x_0A => (&mut console.accumulator, 2),
-        _ => {
-            let (index, cycles) = match opcode {
-                0x_06 => (zero_page(console),    5),
-                0x_16 => (zero_page_x(console),  6),
-                0x_0E => (absolute(console),     6),
-                0x_1E => (absolute_x(console).0, 7),
-                _ => panic!("opcode {:#X} not associated to ASL instruction", opcode)
-            };
-
-            (console.memory_mut(index), cycles)
-        }
+        0x_0A => (ModifiedOperand::Accumulator, 2),
+        0x_06 => (ModifiedOperand::Memory(zero_page(cpu, bus)), 5),
+        0x_16 => (ModifiedOperand::Memory(zero_page_x(cpu, bus)), 6),
+        0x_0E => (ModifiedOperand::Memory(absolute(cpu, bus)), 6),
+        0x_1E => (ModifiedOperand::Memory(absolute_x(cpu, bus).0), 7),
+        _ => panic!("opcode {:#X} not associated to ASL instruction", opcode)
     };
 
-    shift_left(operand, false, &mut console.carry_flag);
+    let value = modify_operand(cpu, bus, operand, |cpu, mut value| {
+        shift_left(&mut value, false, &mut cpu.carry_flag);
+        value
+    });
 
     update_zero_and_negative_flags(
-        operand,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -252,29 +416,32 @@ pub fn asl_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BCC instruction.
 ///
-/// Long description.
+/// This instruction branches if the carry flag is cleared; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bcc_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bcc_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_90 => (relative(console), 2),
+        0x_90 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BCC instruction", opcode)
     };
 
-    if console.carry_flag == false {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if !cpu.carry_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occuring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -286,29 +453,32 @@ pub fn bcc_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BCS instruction.
 ///
-/// Long description.
+/// This instruction branches if the carry flag is set; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bcs_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bcs_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_B0 => (relative(console), 2),
+        0x_B0 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BCS instruction", opcode)
     };
 
-    if console.carry_flag == true {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if cpu.carry_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occuring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -320,29 +490,32 @@ pub fn bcs_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BEQ instruction.
 ///
-/// Long description.
+/// This instruction branches if the zero flag is set; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn beq_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn beq_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_F0 => (relative(console), 2),
+        0x_F0 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BEQ instruction", opcode)
     };
 
-    if console.zero_flag == true {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if cpu.zero_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occuring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -354,54 +527,60 @@ pub fn beq_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BIT instruction.
 ///
-/// Long description.
+/// This instruction tests the bits of a value in memory without changing
+/// the accumulator; the zero flag is set if the bitwise AND of the value and
+/// the accumulator is 0, and bit 7 and bit 6 of the value are copied to the
+/// negative and overflow flags.
 ///
-pub fn bit_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bit_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_24 => (zero_page(console), 3),
-        0x_2C => (absolute(console), 4),
+        0x_24 => (zero_page(cpu, bus), 3),
+        0x_2C => (absolute(cpu, bus), 4),
         _ => panic!("opcode {:#X} not associated to BIT instruction", opcode)
     };
 
-    let operand = console.memory_mut(index);
+    let operand = &bus.read(index);
 
     let bit_7 = *operand & 0b1000_0000 > 0;
     let bit_6 = *operand & 0b0100_0000 > 0;
 
-    console.negative_flag = bit_7;
-    console.overflow_flag = bit_6;
+    cpu.negative_flag = bit_7;
+    cpu.overflow_flag = bit_6;
 
-    console.zero_flag = console.accumulator & *operand == 0;
+    cpu.zero_flag = cpu.accumulator & *operand == 0;
 
     cycles
 }
 
 /// The BMI instruction.
 ///
-/// Long description.
+/// This instruction branches if the negative flag is set; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bmi_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bmi_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_30 => (relative(console), 2),
+        0x_30 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BMI instruction", opcode)
     };
 
-    if console.negative_flag == true {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if cpu.negative_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occurring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -413,29 +592,32 @@ pub fn bmi_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BNE instruction.
 ///
-/// Long description.
+/// This instruction branches if the zero flag is cleared; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bne_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bne_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_D0 => (relative(console), 2),
+        0x_D0 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BNE instruction", opcode)
     };
 
-    if console.zero_flag == false {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if !cpu.zero_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occurring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -447,29 +629,32 @@ pub fn bne_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BPL instruction.
 ///
-/// Long description.
+/// This instruction branches if the negative flag is cleared; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bpl_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bpl_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_10 => (relative(console), 2),
+        0x_10 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BPL instruction", opcode)
     };
 
-    if console.negative_flag == false {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if !cpu.negative_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occurring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -481,39 +666,60 @@ pub fn bpl_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BRK instruction.
 ///
-/// Long description.
+/// This instruction triggers a software interrupt; it pushes the program
+/// counter (the address of the BRK plus 2, as the byte following it is
+/// skipped) and the status flags with the break flag set, sets the interrupt
+/// flag, then jumps through the IRQ vector (0x_FFFE). The 6507 of the console
+/// has no IRQ line, so BRK is the only way to go through this vector; the
+/// handler returns with RTI.
 ///
-pub fn brk_instruction(_console: &mut Console, _opcode: u8) -> u32 {
-    // TODO; To be implemented.
+pub fn brk_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
+
+    assert_eq!(opcode, 0x_00, "opcode {:#X} not associated to BRK instruction", opcode);
+
+    // The byte following BRK is read and ignored (homebrews use it as the
+    // argument of their trap).
+    cpu.fetch(bus);
 
-    0
+    let [ll, hh] = cpu.program_counter.to_le_bytes();
+    cpu.push(bus, hh);
+    cpu.push(bus, ll);
+    cpu.push(bus, status_byte(cpu, true));
+    cpu.interrupt_flag = true;
+
+    cpu.program_counter = u16::from_le_bytes([bus.read(0x_FFFE), bus.read(0x_FFFF)]);
+
+    7
 }
 
 /// The BVC instruction.
 ///
-/// Long description.
+/// This instruction branches if the overflow flag is cleared; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bvc_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bvc_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_50 => (relative(console), 2),
+        0x_50 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BVC instruction", opcode)
     };
 
-    if console.overflow_flag == false {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if !cpu.overflow_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occurring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -525,29 +731,32 @@ pub fn bvc_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The BVS instruction.
 ///
-/// Long description.
+/// This instruction branches if the overflow flag is set; the signed offset
+/// following the opcode is added to the program counter (which points to the
+/// next instruction). It takes an extra cycle when the branch is taken, and
+/// another one when it lands on another page.
 ///
-pub fn bvs_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn bvs_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, mut cycles) = match opcode {
-        0x_70 => (relative(console), 2),
+        0x_70 => (relative(cpu, bus), 2),
         _ => panic!("opcode {:#X} not associated to BVS instruction", opcode)
     };
 
-    if console.overflow_flag == true {
-        let page = console.pointer_counter.to_be_bytes()[0];
+    if cpu.overflow_flag {
+        let page = cpu.program_counter.to_be_bytes()[0];
 
-        if operand > 0 {
-            console.pointer_counter = console.pointer_counter.wrapping_add(operand as u16);
+        if operand >= 0 {
+            cpu.program_counter = cpu.program_counter.wrapping_add(operand as u16);
         }
         else {
             let value = !(operand as u8) + 1;
-            console.pointer_counter = console.pointer_counter.wrapping_sub(value as u16);
+            cpu.program_counter = cpu.program_counter.wrapping_sub(value as u16);
         }
 
         // Branch is occuring, increment the cycle count by one if on the same
         // page, by two if on a different page.
-        if console.pointer_counter.to_be_bytes()[0] == page {
+        if cpu.program_counter.to_be_bytes()[0] == page {
             cycles += 1;
         } else {
             cycles += 2;
@@ -559,75 +768,81 @@ pub fn bvs_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The CLC instruction.
 ///
-/// This instruction does something.
+/// This instruction clears the carry flag.
 ///
-pub fn clc_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn clc_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_18, "opcode {:#X} not associated to CLC instruction", opcode);
-    console.carry_flag = false;
+    cpu.carry_flag = false;
 
     2
 }
 
 /// The CLD instruction.
 ///
-/// This instruction does something.
+/// This instruction clears the decimal flag; ADC and SBC go back to binary
+/// arithmetic.
 ///
-pub fn cld_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn cld_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_D8, "opcode {:#X} not associated to CLD instruction", opcode);
-    console.decimal_flag = false;
+    cpu.decimal_flag = false;
 
     2
 }
 
 /// The CLI instruction.
 ///
-/// This instruction does something.
+/// This instruction clears the interrupt flag. The 6507 has no IRQ line, so
+/// it has no effect besides the flag itself.
 ///
-pub fn cli_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn cli_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_58, "opcode {:#X} not associated to CLI instruction", opcode);
-    console.interrupt_flag = false;
+    cpu.interrupt_flag = false;
 
     2
 }
 
 /// The CLV instruction.
 ///
-/// This instruction does something.
+/// This instruction clears the overflow flag.
 ///
-pub fn clv_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn clv_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_B8, "opcode {:#X} not associated to CLV instruction", opcode);
-    console.overflow_flag = false;
+    cpu.overflow_flag = false;
 
     2
 }
 
 /// The CMP instruction.
 ///
-/// This instruction does something.
+/// This instruction compares the accumulator with the operand; it subtracts
+/// the operand from the accumulator without storing the result. The carry
+/// flag is set if the accumulator is greater than or equal to the operand, and
+/// the zero and negative flags are updated according to the result of the
+/// subtraction.
 ///
-pub fn cmp_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn cmp_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_C9 => (immediate(console), 2),
-        0x_C5 => (zero_page(console), 3),
-        0x_D5 => (zero_page_x(console), 4),
+        0x_C9 => (immediate(cpu, bus), 2),
+        0x_C5 => (zero_page(cpu, bus), 3),
+        0x_D5 => (zero_page_x(cpu, bus), 4),
 
-        0x_CD => (absolute(console), 4),
+        0x_CD => (absolute(cpu, bus), 4),
         0x_DD => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_D9 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_C1 => (indexed_indirect(console), 6),
+        0x_C1 => (indexed_indirect(cpu, bus), 6),
         0x_D1 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -636,14 +851,14 @@ pub fn cmp_instruction(console: &mut Console, opcode: u8) -> u32 {
     };
 
     // Update the carry flag according to A >= M.
-    let value = console.memory(index);
-    console.carry_flag = if console.accumulator >= *value { true } else { false };
+    let value = &bus.read(index);
+    cpu.carry_flag = cpu.accumulator >= *value;
 
     // Update the zero and negative flag according to X - M.
     update_zero_and_negative_flags(
-        &console.accumulator.wrapping_sub(*value),
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator.wrapping_sub(*value),
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -651,25 +866,26 @@ pub fn cmp_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The CPX instruction.
 ///
-/// This instruction does something.
+/// This instruction compares the X register with the operand, like CMP does
+/// with the accumulator.
 ///
-pub fn cpx_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn cpx_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
     let (index, cycles) = match opcode {
-        0x_E0 => (immediate(console), 2),
-        0x_E4 => (zero_page(console), 3),
-        0x_EC => (absolute(console), 4),
+        0x_E0 => (immediate(cpu, bus), 2),
+        0x_E4 => (zero_page(cpu, bus), 3),
+        0x_EC => (absolute(cpu, bus), 4),
         _ => panic!("opcode {:#X} not associated to CPX instruction", opcode)
     };
 
     // Update the carry flag according to X >= M.
-    let value = console.memory(index);
-    console.carry_flag = if console.x_register >= *value { true } else { false };
+    let value = &bus.read(index);
+    cpu.carry_flag = cpu.x_register >= *value;
 
     // Update the zero and negative flag according to X - M.
     update_zero_and_negative_flags(
-        &console.x_register.wrapping_sub(*value),
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register.wrapping_sub(*value),
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -677,25 +893,26 @@ pub fn cpx_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The CPY instruction.
 ///
-/// This instruction does something.
+/// This instruction compares the Y register with the operand, like CMP does
+/// with the accumulator.
 ///
-pub fn cpy_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn cpy_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
     let (index, cycles) = match opcode {
-        0x_C0 => (immediate(console), 2),
-        0x_C4 => (zero_page(console), 3),
-        0x_CC => (absolute(console), 4),
+        0x_C0 => (immediate(cpu, bus), 2),
+        0x_C4 => (zero_page(cpu, bus), 3),
+        0x_CC => (absolute(cpu, bus), 4),
         _ => panic!("opcode {:#X} not associated to CPY instruction", opcode)
     };
 
     // Update the carry flag according to Y >= M.
-    let value = console.memory(index);
-    console.carry_flag = if console.y_register >= *value { true } else { false };
+    let value = &bus.read(index);
+    cpu.carry_flag = cpu.y_register >= *value;
 
     // Update the zero and negative flag according to Y - M.
     update_zero_and_negative_flags(
-        &console.y_register.wrapping_sub(*value),
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.y_register.wrapping_sub(*value),
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -703,24 +920,27 @@ pub fn cpy_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The DEC instruction.
 ///
-/// This instruction does something.
+/// This instruction decrements a value in memory by one (wrapping around
+/// 0). It also updates the zero and negative flags according to the resulting
+/// value.
 ///
-pub fn dec_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn dec_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
     let (index, cycles) = match opcode {
-        0x_C6 => (zero_page(console), 5),
-        0x_D6 => (zero_page_x(console), 6),
-        0x_CE => (absolute(console), 6),
-        0x_DE => (absolute_x(console).0, 7),
+        0x_C6 => (zero_page(cpu, bus), 5),
+        0x_D6 => (zero_page_x(cpu, bus), 6),
+        0x_CE => (absolute(cpu, bus), 6),
+        0x_DE => (absolute_x(cpu, bus).0, 7),
         _ => panic!("opcode {} not associated to DEC instruction", opcode)
     };
 
-    let value = console.memory_mut(index);
-
-    decrement_byte(value);
+    let value = modify_operand(cpu, bus, ModifiedOperand::Memory(index), |_, mut value| {
+        decrement_byte(&mut value);
+        value
+    });
     update_zero_and_negative_flags(
-        value,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -731,14 +951,14 @@ pub fn dec_instruction(console: &mut Console, opcode: u8) -> u32 {
 /// This instruction decrements the X register by one. It also updates the zero
 /// and negative flags.
 ///
-pub fn dex_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn dex_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_CA, "opcode {:#X} not associated to DEX instruction", opcode);
 
-    decrement_byte(&mut console.x_register);
+    decrement_byte(&mut cpu.x_register);
     update_zero_and_negative_flags(
-        &mut console.x_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -749,14 +969,14 @@ pub fn dex_instruction(console: &mut Console, opcode: u8) -> u32 {
 /// This instruction decrements the Y register by one. It also updates the zero
 /// and negative flags.
 ///
-pub fn dey_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn dey_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_88, "opcode {:#X} not associated to DEY instruction", opcode);
 
-    decrement_byte(&mut console.y_register);
+    decrement_byte(&mut cpu.y_register);
     update_zero_and_negative_flags(
-        &mut console.y_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.y_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -764,31 +984,33 @@ pub fn dey_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The EOR instruction.
 ///
-/// Long description.
+/// This instruction performs a bitwise exclusive OR operation with the
+/// operand and the accumulator, then stores the result in the accumulator. It
+/// also updates the zero and negative flags according to the resulting value.
 ///
-pub fn eor_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn eor_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_49 => (immediate(console), 2),
-        0x_45 => (zero_page(console), 3),
-        0x_55 => (zero_page_x(console), 4),
+        0x_49 => (immediate(cpu, bus), 2),
+        0x_45 => (zero_page(cpu, bus), 3),
+        0x_55 => (zero_page_x(cpu, bus), 4),
 
-        0x_4D => (absolute(console), 4),
+        0x_4D => (absolute(cpu, bus), 4),
         0x_5D => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_59 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_41 => (indexed_indirect(console), 6),
+        0x_41 => (indexed_indirect(cpu, bus), 6),
         0x_51 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -796,13 +1018,13 @@ pub fn eor_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {:#X} not associated to EOR instruction", opcode)
     };
 
-    let value = console.memory(index);
-    console.accumulator ^= *value;
+    let value = &bus.read(index);
+    cpu.accumulator ^= *value;
 
     update_zero_and_negative_flags(
-        &console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -810,24 +1032,27 @@ pub fn eor_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The INC instruction.
 ///
-/// This instruction does something.
+/// This instruction increments a value in memory by one (wrapping around
+/// 0xFF). It also updates the zero and negative flags according to the resulting
+/// value.
 ///
-pub fn inc_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn inc_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
     let (index, cycles) = match opcode {
-        0x_E6 => (zero_page(console), 5),
-        0x_F6 => (zero_page_x(console), 6),
-        0x_EE => (absolute(console), 6),
-        0x_FE => (absolute_x(console).0, 7),
+        0x_E6 => (zero_page(cpu, bus), 5),
+        0x_F6 => (zero_page_x(cpu, bus), 6),
+        0x_EE => (absolute(cpu, bus), 6),
+        0x_FE => (absolute_x(cpu, bus).0, 7),
         _ => panic!("opcode {} not associated to INC instruction", opcode)
     };
 
-    let value = console.memory_mut(index);
-
-    increment_byte(value);
+    let value = modify_operand(cpu, bus, ModifiedOperand::Memory(index), |_, mut value| {
+        increment_byte(&mut value);
+        value
+    });
     update_zero_and_negative_flags(
-        value,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -838,14 +1063,14 @@ pub fn inc_instruction(console: &mut Console, opcode: u8) -> u32 {
 /// This instruction increments the X register by one. It also updates the zero
 /// and negative flags.
 ///
-pub fn inx_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn inx_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_E8, "opcode {:#X} not associated to INX instruction", opcode);
 
-    increment_byte(&mut console.x_register);
+    increment_byte(&mut cpu.x_register);
     update_zero_and_negative_flags(
-        &mut console.x_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -856,14 +1081,14 @@ pub fn inx_instruction(console: &mut Console, opcode: u8) -> u32 {
 /// This instruction increments the Y register by one. It also updates the zero
 /// and negative flags.
 ///
-pub fn iny_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn iny_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_C8, "opcode {:#X} not associated to INY instruction", opcode);
 
-    increment_byte(&mut console.y_register);
+    increment_byte(&mut cpu.y_register);
     update_zero_and_negative_flags(
-        &mut console.y_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.y_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -871,47 +1096,50 @@ pub fn iny_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The JMP instruction.
 ///
-/// Long description.
+/// This instruction jumps to the address following the opcode (absolute
+/// mode), or to the address stored at this address (indirect mode).
 ///
-pub fn jmp_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn jmp_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (pointer_counter, cycles) = match opcode {
-        0x_4C => (absolute(console), 3),
+        0x_4C => (absolute(cpu, bus), 3),
         0x_6C => {
             // Note that advancing the pointer here is irrelevant as the pointer
             // counter is modified later.
-            let indirect_index = absolute(console);
+            let indirect_index = absolute(cpu, bus);
 
-            let ll = *console.memory(indirect_index);
-            let hh = *console.memory(indirect_index + 1);
+            let ll = bus.read(indirect_index);
+            let hh = bus.read(indirect_index + 1);
 
             (u16::from_le_bytes([ll, hh]), 5)
         },
         _ => panic!("opcode {} not associated to JMP instruction", opcode)
     };
 
-    console.pointer_counter = pointer_counter;
+    cpu.program_counter = pointer_counter;
 
     cycles
 }
 
 /// The JSR instruction.
 ///
-/// Long description.
+/// This instruction calls a subroutine; it pushes the address of its own
+/// last byte (the address of the next instruction minus one) on the stack, then
+/// jumps to the address following the opcode. The subroutine returns with RTS.
 ///
-pub fn jsr_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn jsr_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_20, "opcode {:#X} not associated to JSR instruction", opcode);
 
-    let pointer_counter = absolute(console);
+    let pointer_counter = absolute(cpu, bus);
 
-    // let [ll, hh] = console.pointer_counter.to_le_bytes();
-    let [ll, hh] = (console.pointer_counter - 1).to_le_bytes(); // that doesn't
+    // let [ll, hh] = cpu.program_counter.to_le_bytes();
+    let [ll, hh] = (cpu.program_counter - 1).to_le_bytes(); // that doesn't
     // seem right, but the online emulator seems to do that way
-    console.push_value(hh);
-    console.push_value(ll);
+    cpu.push(bus, hh);
+    cpu.push(bus, ll);
 
-    console.pointer_counter = pointer_counter;
+    cpu.program_counter = pointer_counter;
 
     6
 }
@@ -919,31 +1147,32 @@ pub fn jsr_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The LDA instruction.
 ///
-/// Long description.
+/// This instruction loads the operand in the accumulator. It also updates the
+/// zero and negative flags according to the loaded value.
 ///
-pub fn lda_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn lda_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_A9 => (immediate(console), 2),
-        0x_A5 => (zero_page(console), 3),
-        0x_B5 => (zero_page_x(console), 4),
+        0x_A9 => (immediate(cpu, bus), 2),
+        0x_A5 => (zero_page(cpu, bus), 3),
+        0x_B5 => (zero_page_x(cpu, bus), 4),
 
-        0x_AD => (absolute(console), 4),
+        0x_AD => (absolute(cpu, bus), 4),
         0x_BD => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_B9 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_A1 => (indexed_indirect(console), 6),
+        0x_A1 => (indexed_indirect(cpu, bus), 6),
         0x_B1 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -951,13 +1180,13 @@ pub fn lda_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {:#X} not associated to LDA instruction", opcode)
     };
 
-    let value = console.memory(index);
-    console.accumulator = *value;
+    let value = &bus.read(index);
+    cpu.accumulator = *value;
 
     update_zero_and_negative_flags(
-        &console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -965,17 +1194,18 @@ pub fn lda_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The LDX instruction.
 ///
-/// Long description.
+/// This instruction loads the operand in the X register. It also updates the
+/// zero and negative flags according to the loaded value.
 ///
-pub fn ldx_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn ldx_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_A2 => (immediate(console), 2),
-        0x_A6 => (zero_page(console),3),
-        0x_B6 => (zero_page_y(console), 4),
-        0x_AE => (absolute(console), 4),
+        0x_A2 => (immediate(cpu, bus), 2),
+        0x_A6 => (zero_page(cpu, bus),3),
+        0x_B6 => (zero_page_y(cpu, bus), 4),
+        0x_AE => (absolute(cpu, bus), 4),
         0x_BE => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
@@ -983,11 +1213,11 @@ pub fn ldx_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {} not associated to LDX instruction", opcode)
     };
 
-    console.x_register = *console.memory(index);
+    cpu.x_register = bus.read(index);
     update_zero_and_negative_flags(
-        &console.x_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -995,17 +1225,18 @@ pub fn ldx_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The LDY instruction.
 ///
-/// Long description.
+/// This instruction loads the operand in the Y register. It also updates the
+/// zero and negative flags according to the loaded value.
 ///
-pub fn ldy_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn ldy_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_A0 => (immediate(console), 2),
-        0x_A4 => (zero_page(console),3),
-        0x_B4 => (zero_page_x(console), 4),
-        0x_AC => (absolute(console), 4),
+        0x_A0 => (immediate(cpu, bus), 2),
+        0x_A4 => (zero_page(cpu, bus),3),
+        0x_B4 => (zero_page_x(cpu, bus), 4),
+        0x_AC => (absolute(cpu, bus), 4),
         0x_BC => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
@@ -1013,11 +1244,11 @@ pub fn ldy_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {} not associated to LDY instruction", opcode)
     };
 
-    console.y_register = *console.memory(index);
+    cpu.y_register = bus.read(index);
     update_zero_and_negative_flags(
-        &console.y_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.y_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -1025,33 +1256,33 @@ pub fn ldy_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The LSR instruction.
 ///
-/// Long description.
+/// This instruction shifts the bits of the accumulator or of a value in
+/// memory to the right; bit 7 is cleared and bit 0 goes to the carry flag. It
+/// also updates the zero and negative flags according to the resulting value
+/// (the negative flag is always cleared).
 ///
-pub fn lsr_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn lsr_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, cycles) = match opcode {
-        0x_4A => (&mut console.accumulator, 2),
-        _ => {
-            let (index, cycles) = match opcode {
-                0x_46 => (zero_page(console),    5),
-                0x_56 => (zero_page_x(console),  6),
-                0x_4E => (absolute(console),     6),
-                0x_5E => (absolute_x(console).0, 7),
-                _ => panic!("opcode {:#X} not associated to LSR instruction", opcode)
-            };
-
-            (console.memory_mut(index), cycles)
-        }
+        0x_4A => (ModifiedOperand::Accumulator, 2),
+        0x_46 => (ModifiedOperand::Memory(zero_page(cpu, bus)), 5),
+        0x_56 => (ModifiedOperand::Memory(zero_page_x(cpu, bus)), 6),
+        0x_4E => (ModifiedOperand::Memory(absolute(cpu, bus)), 6),
+        0x_5E => (ModifiedOperand::Memory(absolute_x(cpu, bus).0), 7),
+        _ => panic!("opcode {:#X} not associated to LSR instruction", opcode)
     };
 
-    shift_right(operand, false, &mut console.carry_flag);
+    let value = modify_operand(cpu, bus, operand, |cpu, mut value| {
+        shift_right(&mut value, false, &mut cpu.carry_flag);
+        value
+    });
 
     // Note that while the zero flag must always be set to 0, this function will
     // always update it correctly since the entering bit was 0.
     update_zero_and_negative_flags(
-        operand,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -1059,9 +1290,9 @@ pub fn lsr_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The NOP instruction.
 ///
-/// Long description.
+/// This instruction does nothing; it only takes 2 cycles.
 ///
-pub fn nop_instruction(_console: &mut Console, opcode: u8) -> u32 {
+pub fn nop_instruction<B: Bus>(_cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_EA, "opcode {:#X} not associated to ORA instruction", opcode);
 
@@ -1072,31 +1303,33 @@ pub fn nop_instruction(_console: &mut Console, opcode: u8) -> u32 {
 
 /// The ORA instruction.
 ///
-/// Long description.
+/// This instruction performs a bitwise OR operation with the operand and
+/// the accumulator, then stores the result in the accumulator. It also updates
+/// the zero and negative flags according to the resulting value.
 ///
-pub fn ora_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn ora_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_09 => (immediate(console), 2),
-        0x_05 => (zero_page(console), 3),
-        0x_15 => (zero_page_x(console), 4),
+        0x_09 => (immediate(cpu, bus), 2),
+        0x_05 => (zero_page(cpu, bus), 3),
+        0x_15 => (zero_page_x(cpu, bus), 4),
 
-        0x_0D => (absolute(console), 4),
+        0x_0D => (absolute(cpu, bus), 4),
         0x_1D => {
-            match absolute_x(console) {
+            match absolute_x(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
         0x_19 => {
-            match absolute_y(console) {
+            match absolute_y(cpu, bus) {
                 (index, false) => (index, 4),
                 (index, true) => (index, 5)
             }
         },
-        0x_01 => (indexed_indirect(console), 6),
+        0x_01 => (indexed_indirect(cpu, bus), 6),
         0x_11 => {
-            match indirect_indexed(console) {
+            match indirect_indexed(cpu, bus) {
                 (index, false) => (index, 5),
                 (index, true) => (index, 6)
             }
@@ -1104,13 +1337,13 @@ pub fn ora_instruction(console: &mut Console, opcode: u8) -> u32 {
         _ => panic!("opcode {:#X} not associated to ORA instruction", opcode)
     };
 
-    let value = console.memory(index);
-    console.accumulator |= *value;
+    let value = &bus.read(index);
+    cpu.accumulator |= *value;
 
     update_zero_and_negative_flags(
-        &console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -1118,96 +1351,84 @@ pub fn ora_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The PHA instruction.
 ///
-/// Long description.
+/// This instruction pushes the accumulator on the stack.
 ///
-pub fn pha_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn pha_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_48, "opcode {:#X} not associated to PHA instruction", opcode);
-    console.push_value(console.accumulator);
+    cpu.push(bus, cpu.accumulator);
 
     3
 }
 
 /// The PHP instruction.
 ///
-/// Long description.
+/// This instruction pushes the status flags on the stack, with the break
+/// flag and bit 5 set.
 ///
-pub fn php_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn php_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_08, "opcode {:#X} not associated to PHP instruction", opcode);
-
-    let mut status_flag = 0b0000_0000u8;
-    if console.negative_flag  { status_flag |= 0b1000_0000 };
-    if console.overflow_flag  { status_flag |= 0b0100_0000 };
-    if console.break_flag     { status_flag |= 0b0001_0000 };
-    if console.decimal_flag   { status_flag |= 0b0000_1000 };
-    if console.interrupt_flag { status_flag |= 0b0000_0100 };
-    if console.zero_flag      { status_flag |= 0b0000_0010 };
-    if console.carry_flag     { status_flag |= 0b0000_0001 };
-
-    console.push_value(status_flag);
+    cpu.push(bus, status_byte(cpu, true));
 
     3
 }
 
 /// The PLA instruction.
 ///
-/// Long description.
+/// This instruction pulls a byte from the stack and loads it in the
+/// accumulator.
 ///
-pub fn pla_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn pla_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_68, "opcode {:#X} not associated to PLA instruction", opcode);
-    console.accumulator = console.pop_value();
+    cpu.accumulator = cpu.pull(bus);
 
     4
 }
 
 /// The PLP instruction.
 ///
-/// Long description.
+/// This instruction pulls the status flags from the stack; the break flag of
+/// the processor is left unchanged.
 ///
-pub fn plp_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn plp_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_28, "opcode {:#X} not associated to PLP instruction", opcode);
 
-    let status_flag = console.pop_value();
-    console.negative_flag  = status_flag & 0b1000_0000 > 0;
-    console.overflow_flag  = status_flag & 0b0100_0000 > 0;
-    console.break_flag     = status_flag & 0b0001_0000 > 0;
-    console.decimal_flag   = status_flag & 0b0000_1000 > 0;
-    console.interrupt_flag = status_flag & 0b0000_0100 > 0;
-    console.zero_flag      = status_flag & 0b0000_0010 > 0;
-    console.carry_flag     = status_flag & 0b0000_0001 > 0;
+    let status_flag = cpu.pull(bus);
+    restore_status(cpu, status_flag);
 
     4
 }
 
 /// The ROL instruction.
 ///
-/// Long description.
+/// This instruction rotates the bits of the accumulator or of a value in
+/// memory to the left through the carry flag; the carry flag goes to bit 0 and
+/// bit 7 goes to the carry flag. It also updates the zero and negative flags
+/// according to the resulting value.
 ///
-pub fn rol_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn rol_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, cycles) = match opcode {
-        0x_2A => (&mut console.accumulator, 2),
-        _ => {
-            let (index, cycles) = match opcode {
-                0x_26 => (zero_page(console), 5),
-                0x_36 => (zero_page_x(console), 6),
-                0x_2E => (absolute(console), 6),
-                0x_3E => (absolute_x(console).0, 7),
-                _ => panic!("opcode {:#X} not associated to ROL instruction", opcode)
-            };
-
-            (console.memory_mut(index), cycles)
-        }
+        0x_2A => (ModifiedOperand::Accumulator, 2),
+        0x_26 => (ModifiedOperand::Memory(zero_page(cpu, bus)), 5),
+        0x_36 => (ModifiedOperand::Memory(zero_page_x(cpu, bus)), 6),
+        0x_2E => (ModifiedOperand::Memory(absolute(cpu, bus)), 6),
+        0x_3E => (ModifiedOperand::Memory(absolute_x(cpu, bus).0), 7),
+        _ => panic!("opcode {:#X} not associated to ROL instruction", opcode)
     };
 
-    shift_left(operand, console.carry_flag, &mut console.carry_flag);
+    let value = modify_operand(cpu, bus, operand, |cpu, mut value| {
+        shift_left(&mut value, cpu.carry_flag, &mut cpu.carry_flag);
+        value
+    });
+
     update_zero_and_negative_flags(
-        operand,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -1215,30 +1436,31 @@ pub fn rol_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The ROR instruction.
 ///
-/// Long description.
+/// This instruction rotates the bits of the accumulator or of a value in
+/// memory to the right through the carry flag; the carry flag goes to bit 7 and
+/// bit 0 goes to the carry flag. It also updates the zero and negative flags
+/// according to the resulting value.
 ///
-pub fn ror_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn ror_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (operand, cycles) = match opcode {
-        0x_6A => (&mut console.accumulator, 2),
-        _ => {
-            let (index, cycles) = match opcode {
-                0x_66 => (zero_page(console), 5),
-                0x_76 => (zero_page_x(console), 6),
-                0x_6E => (absolute(console), 6),
-                0x_7E => (absolute_x(console).0, 7),
-                _ => panic!("opcode {:#X} not associated to ROR instruction", opcode)
-            };
-
-            (console.memory_mut(index), cycles)
-        }
+        0x_6A => (ModifiedOperand::Accumulator, 2),
+        0x_66 => (ModifiedOperand::Memory(zero_page(cpu, bus)), 5),
+        0x_76 => (ModifiedOperand::Memory(zero_page_x(cpu, bus)), 6),
+        0x_6E => (ModifiedOperand::Memory(absolute(cpu, bus)), 6),
+        0x_7E => (ModifiedOperand::Memory(absolute_x(cpu, bus).0), 7),
+        _ => panic!("opcode {:#X} not associated to ROR instruction", opcode)
     };
 
-    shift_right(operand, console.carry_flag, &mut console.carry_flag);
+    let value = modify_operand(cpu, bus, operand, |cpu, mut value| {
+        shift_right(&mut value, cpu.carry_flag, &mut cpu.carry_flag);
+        value
+    });
+
     update_zero_and_negative_flags(
-        operand,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &value,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     cycles
@@ -1246,150 +1468,215 @@ pub fn ror_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The RTI instruction.
 ///
-/// Long description.
+/// This instruction returns from an interrupt handler; it pulls the status
+/// flags, then the pointer counter from the stack. Unlike RTS, the pulled
+/// address is the address of the next instruction, not the one before it.
 ///
-pub fn rti_instruction(_console: &mut Console, _opcode: u8) -> u32 {
+pub fn rti_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
+
+    assert_eq!(opcode, 0x_40, "opcode {:#X} not associated to RTI instruction", opcode);
 
-    // TODO; Not implemented yet.
+    let status_flag = cpu.pull(bus);
+    restore_status(cpu, status_flag);
 
-    0
+    let ll = cpu.pull(bus);
+    let hh = cpu.pull(bus);
+    cpu.program_counter = u16::from_le_bytes([ll, hh]);
+
+    6
 }
 
 /// The RTS instruction.
 ///
-/// Long description.
+/// This instruction returns from a subroutine; it pulls the address pushed
+/// by JSR from the stack and jumps to the instruction following it.
 ///
-pub fn rts_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn rts_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_60, "opcode {:#X} not associated to RTS instruction", opcode);
 
-    let ll = console.pop_value();
-    let hh = console.pop_value();
-    console.pointer_counter = u16::from_le_bytes([ll, hh]) + 1;
+    let ll = cpu.pull(bus);
+    let hh = cpu.pull(bus);
+    cpu.program_counter = u16::from_le_bytes([ll, hh]) + 1;
 
     6
 }
 
 /// The SBC instruction.
 ///
-/// Long description.
+/// This instruction subtracts the operand and the complement of the carry
+/// flag (the borrow) from the accumulator, and store it in the accumulator.
+/// The carry flag is cleared if a borrow occurred, otherwise it's set to 1.
+/// The overflow, zero and negative flags are updated as for ADC.
 ///
-pub fn sbc_instruction(_console: &mut Console, _opcode: u8) -> u32 {
+/// When the decimal flag is set, the operands are treated as BCD numbers (two
+/// decimal digits).
+///
+pub fn sbc_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
+    let (index, cycles) = match opcode {
+        0x_E9 => (immediate(cpu, bus), 2),
+        0x_E5 => (zero_page(cpu, bus), 3),
+        0x_F5 => (zero_page_x(cpu, bus), 4),
+        0x_ED => (absolute(cpu, bus), 4),
+        0x_FD => {
+            match absolute_x(cpu, bus) {
+                (index, false) => (index, 4),
+                (index, true) => (index, 5)
+            }
+        },
+        0x_F9 => {
+            match absolute_y(cpu, bus) {
+                (index, false) => (index, 4),
+                (index, true) => (index, 5)
+            }
+        },
+        0x_E1 => (indexed_indirect(cpu, bus), 6),
+        0x_F1 => {
+            match indirect_indexed(cpu, bus) {
+                (index, false) => (index, 5),
+                (index, true) => (index, 6)
+            }
+        },
+        _ => panic!("opcode {:#X} not associated to SBC instruction", opcode)
+    };
+
+    let value = bus.read(index);
+    subtract_with_borrow(cpu, value);
 
-    // TODO; Not implemented yet.
-    0
+    cycles
 }
 
 /// The SEC instruction.
 ///
-/// Long description.
+/// This instruction sets the carry flag.
 ///
-pub fn sec_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn sec_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_38, "opcode {:#X} not associated to SEC instruction", opcode);
-    console.carry_flag = true;
+    cpu.carry_flag = true;
 
     2
 }
 
 /// The SED instruction.
 ///
-/// Long description.
+/// This instruction sets the decimal flag; ADC and SBC then treat their
+/// operands as BCD numbers.
 ///
-pub fn sed_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn sed_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_F8, "opcode {:#X} not associated to SED instruction", opcode);
-    console.decimal_flag = true;
+    cpu.decimal_flag = true;
 
     2
 }
 
 /// The SEI instruction.
 ///
-/// Long description.
+/// This instruction sets the interrupt flag. The 6507 has no IRQ line, so it
+/// has no effect besides the flag itself.
 ///
-pub fn sei_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn sei_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
 
     assert_eq!(opcode, 0x_78, "opcode {:#X} not associated to SEI instruction", opcode);
-    console.interrupt_flag = true;
+    cpu.interrupt_flag = true;
 
     2
 }
 
 /// The STA instruction.
 ///
-/// Long description.
+/// This instruction stores the accumulator in memory. It doesn't change the
+/// flags.
 ///
-pub fn sta_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn sta_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_85 => (zero_page(console), 3),
-        0x_95 => (zero_page_x(console), 4),
-        0x_8D => (absolute(console), 4),
-        0x_9D => (absolute_x(console).0, 5),
-        0x_99 => (absolute_y(console).0, 5),
-        0x_81 => (indexed_indirect(console), 6),
-        0x_91 => (indirect_indexed(console).0, 6),
+        0x_85 => (zero_page(cpu, bus), 3),
+        0x_95 => (zero_page_x(cpu, bus), 4),
+        0x_8D => (absolute(cpu, bus), 4),
+        0x_9D => (absolute_x(cpu, bus).0, 5),
+        0x_99 => (absolute_y(cpu, bus).0, 5),
+        0x_81 => (indexed_indirect(cpu, bus), 6),
+        0x_91 => (indirect_indexed(cpu, bus).0, 6),
         _ => panic!("opcode {:#X} not associated to STA instruction", opcode)
     };
 
-    *console.memory_mut(index) = console.accumulator;
+    bus.write(index, cpu.accumulator);
 
     cycles
 }
 
 /// The STX instruction.
 ///
-/// This instruction does something.
+/// This instruction stores the X register in memory. It doesn't change the
+/// flags.
 ///
-/// STX	....	store X
-///
-pub fn stx_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn stx_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_86 => (zero_page(console), 3),
-        0x_96 => (zero_page_y(console), 4),
-        0x_8E => (absolute(console), 4),
+        0x_86 => (zero_page(cpu, bus), 3),
+        0x_96 => (zero_page_y(cpu, bus), 4),
+        0x_8E => (absolute(cpu, bus), 4),
         _ => panic!("opcode {:#X} not associated to STX instruction", opcode)
     };
 
-    *console.memory_mut(index) = console.x_register;
+    bus.write(index, cpu.x_register);
 
     cycles
 }
 
 /// The STY instruction.
 ///
-/// This instruction does something.
-///
-/// STY	....	store Y
+/// This instruction stores the Y register in memory. It doesn't change the
+/// flags.
 ///
-pub fn sty_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn sty_instruction<B: Bus>(cpu: &mut Cpu6507, bus: &mut B, opcode: u8) -> u32 {
 
     let (index, cycles) = match opcode {
-        0x_84 => (zero_page(console), 3),
-        0x_94 => (zero_page_x(console), 4),
-        0x_8C => (absolute(console), 4),
+        0x_84 => (zero_page(cpu, bus), 3),
+        0x_94 => (zero_page_x(cpu, bus), 4),
+        0x_8C => (absolute(cpu, bus), 4),
         _ => panic!("opcode {:#X} not associated to STY instruction", opcode)
     };
 
-    *console.memory_mut(index) = console.y_register;
+    bus.write(index, cpu.y_register);
 
     cycles
 }
 
+/// The KIL instruction.
+///
+/// This undocumented instruction jams the CPU; it stops fetching instructions
+/// and stays stuck on the opcode until the console is turned off. The rest of
+/// the console keeps running.
+///
+pub fn kil_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
+
+    match opcode {
+        0x_02 | 0x_12 | 0x_22 | 0x_32 | 0x_42 | 0x_52 | 0x_62 | 0x_72 | 0x_92 | 0x_B2 | 0x_D2 | 0x_F2 => (),
+        _ => panic!("opcode {:#X} not associated to KIL instruction", opcode)
+    }
+
+    cpu.program_counter = cpu.program_counter.wrapping_sub(1);
+    cpu.jam_address = Some(cpu.program_counter);
+
+    2
+}
+
 /// The TAX instruction.
 ///
-/// This instruction does something.
+/// This instruction copies the accumulator to the X register. It also updates
+/// the zero and negative flags according to the copied value.
 ///
-pub fn tax_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn tax_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_AA, "opcode {:#X} not associated to TAX instruction", opcode);
 
-    transfer_byte(&mut console.accumulator, &mut console.x_register);
+    transfer_byte(&mut cpu.accumulator, &mut cpu.x_register);
     update_zero_and_negative_flags(
-        &mut console.x_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -1397,19 +1684,17 @@ pub fn tax_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The TAY instruction.
 ///
-/// This instruction does something.
+/// This instruction copies the accumulator to the Y register. It also updates
+/// the zero and negative flags according to the copied value.
 ///
-/// TAY	....	transfer accumulator to Y
-///
-///
-pub fn tay_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn tay_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_A8, "opcode {:#X} not associated to TAY instruction", opcode);
 
-    transfer_byte(&mut console.accumulator, &mut console.y_register);
+    transfer_byte(&mut cpu.accumulator, &mut cpu.y_register);
     update_zero_and_negative_flags(
-        &mut console.y_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.y_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -1417,16 +1702,17 @@ pub fn tay_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The TSX instruction.
 ///
-/// This instruction does something.
+/// This instruction copies the stack pointer to the X register. It also
+/// updates the zero and negative flags according to the copied value.
 ///
-pub fn tsx_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn tsx_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_BA, "opcode {:#X} not associated to TSX instruction", opcode);
 
-    transfer_byte(&mut console.stack_pointer, &mut console.x_register);
+    transfer_byte(&mut cpu.stack_pointer, &mut cpu.x_register);
     update_zero_and_negative_flags(
-        &mut console.x_register,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.x_register,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -1434,18 +1720,17 @@ pub fn tsx_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The TXA instruction.
 ///
-/// This instruction does something.
-///
-/// TXA	....	transfer X to accumulator
+/// This instruction copies the X register to the accumulator. It also updates
+/// the zero and negative flags according to the copied value.
 ///
-pub fn txa_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn txa_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_8A, "opcode {:#X} not associated to TXA instruction", opcode);
 
-    transfer_byte(&mut console.x_register, &mut console.accumulator);
+    transfer_byte(&mut cpu.x_register, &mut cpu.accumulator);
     update_zero_and_negative_flags(
-        &mut console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -1453,29 +1738,29 @@ pub fn txa_instruction(console: &mut Console, opcode: u8) -> u32 {
 
 /// The TXS instruction.
 ///
-/// This instruction does something.
+/// This instruction copies the X register to the stack pointer. Unlike the
+/// other transfers, it doesn't change the flags.
 ///
-pub fn txs_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn txs_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_9A, "opcode {:#X} not associated to TXS instruction", opcode);
-    transfer_byte(&mut console.x_register, &mut console.stack_pointer);
+    transfer_byte(&mut cpu.x_register, &mut cpu.stack_pointer);
 
     2
 }
 
 /// The TYA instruction.
 ///
-/// This instruction does something.
+/// This instruction copies the Y register to the accumulator. It also updates
+/// the zero and negative flags according to the copied value.
 ///
-/// TYA	....	transfer Y to accumulator
-///
-pub fn tya_instruction(console: &mut Console, opcode: u8) -> u32 {
+pub fn tya_instruction<B: Bus>(cpu: &mut Cpu6507, _bus: &mut B, opcode: u8) -> u32 {
     assert_eq!(opcode, 0x_98, "opcode {:#X} not associated to TYA instruction", opcode);
 
-    transfer_byte(&mut console.y_register, &mut console.accumulator);
+    transfer_byte(&mut cpu.y_register, &mut cpu.accumulator);
     update_zero_and_negative_flags(
-        &mut console.accumulator,
-        &mut console.zero_flag,
-        &mut console.negative_flag,
+        &cpu.accumulator,
+        &mut cpu.zero_flag,
+        &mut cpu.negative_flag,
     );
 
     2
@@ -1484,29 +1769,50 @@ pub fn tya_instruction(console: &mut Console, opcode: u8) -> u32 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::console::Console;
     use crate::cartridge::Cartridge;
+    use crate::location::{INTIM, TIM64T};
+    use crate::rom_builder::RomBuilder;
+
+    // The instructions are placed in the cartridge (which starts at 0x1000)
+    // since it's the only place code is fetched from.
+    const CODE: u16 = 0x_1000;
 
     fn setup_instruction(console: &mut Console, bytes: Vec<u8>) {
-        // setup_instruction_x(console, bytes, 0x_200);
         setup_instruction_x(console, bytes, 0x_00);
     }
 
     fn setup_instruction_x(console: &mut Console, bytes: Vec<u8>, index: u16) {
-        // todo; replace this code with more idiomatic Rust
-        let mut i: u16 = 0;
-        for byte in bytes.iter() {
-            *console.memory_mut(index + i) = *byte;
-            i += 1;
-        };
+        let memory = &mut console.cartridge_mut().memory;
+        memory.resize(4096, 0x_00);
+        memory[index as usize..index as usize + bytes.len()].copy_from_slice(&bytes);
 
-        console.pointer_counter = index;
+        console.cpu.program_counter = CODE + index;
     }
 
-    fn execute_instruction(console: &mut Console, instruction: fn(&mut Console, u8) -> u32) -> u32 {
-        let opcode = *console.pointed_value();
-        console.advance_pointer();
+    type Instruction = fn(&mut Cpu6507, &mut Console, u8) -> u32;
+
+    fn execute_instruction(console: &mut Console, instruction: Instruction) -> u32 {
+        let mut cpu = console.cpu;
+        let opcode = cpu.fetch(console);
+        let cycles = instruction(&mut cpu, console, opcode);
+        console.cpu = cpu;
 
-        instruction(console, opcode)
+        cycles
+    }
+
+    fn push_value(console: &mut Console, value: u8) {
+        let mut cpu = console.cpu;
+        cpu.push(console, value);
+        console.cpu = cpu;
+    }
+
+    fn pop_value(console: &mut Console) -> u8 {
+        let mut cpu = console.cpu;
+        let value = cpu.pull(console);
+        console.cpu = cpu;
+
+        value
     }
 
     #[test]
@@ -1518,21 +1824,22 @@ mod test {
     fn test_adc_instruction() {
 
         let mut console = Console::new(Cartridge::new(vec![]));
+        console.cpu.decimal_flag = false;
 
         {
             setup_instruction(&mut console, vec![0x_69, 0x_86]);
 
-            console.accumulator = 0x_43;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 0x_43;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_CA);
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.accumulator, 0x_CA);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -1541,118 +1848,118 @@ mod test {
             setup_instruction(&mut console, vec![0x_65, 0x_E5]);
             *console.memory_mut(0x_E5) = 0x_D1;
 
-            console.accumulator = 0x_79;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.accumulator = 0x_79;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_4B);
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_4B);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 3);
         }
 
         {
             setup_instruction(&mut console, vec![0x_75, 0x_86]);
-            console.x_register = 0x_39;
+            console.cpu.x_register = 0x_39;
             *console.memory_mut(0x_BF) = 0x_D1;
 
-            console.accumulator = 0x_43;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 0x_43;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_15);
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_15);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 4);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_6D, 0x_A6, 0x_03]);
-            *console.memory_mut(0x_03A6) = 0x_DB;
+            setup_instruction(&mut console, vec![0x_6D, 0x_A6, 0x_01]);
+            *console.memory_mut(0x_01A6) = 0x_DB;
 
-            console.accumulator = 0x_37;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.accumulator = 0x_37;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_13);
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_13);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 4);
         }
 
         {
             setup_instruction(&mut console, vec![0x_7D, 0x_DB, 0x_04]);
-            console.x_register = 0x_A6;
+            console.cpu.x_register = 0x_A6;
             *console.memory_mut(0x_0581) = 0x_41;
 
-            console.accumulator = 0x_50;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 0x_50;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_92);
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.accumulator, 0x_92);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 4 + 1);
         }
 
         {
             setup_instruction(&mut console, vec![0x_79, 0x_DB, 0x_04]);
-            console.y_register = 0x_A6;
+            console.cpu.y_register = 0x_A6;
             *console.memory_mut(0x_0581) = 0x_41;
 
-            console.accumulator = 0x_50;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 0x_50;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_92);
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.accumulator, 0x_92);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 5);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_61, 0x_60]);
-            console.x_register = 0x_B9;
-            *console.memory_mut(0x_19) = 0x_79;
-            *console.memory_mut(0x_1A) = 0x_02;
-            *console.memory_mut(0x_0279) = 0x_E5;
+            setup_instruction(&mut console, vec![0x_61, 0x_E0]);
+            console.cpu.x_register = 0x_B9;
+            *console.memory_mut(0x_99) = 0x_F9;
+            *console.memory_mut(0x_9A) = 0x_01;
+            *console.memory_mut(0x_01F9) = 0x_E5;
 
-            console.accumulator = 0x_50;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.accumulator = 0x_50;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_36);
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_36);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 6);
         }
@@ -1662,62 +1969,105 @@ mod test {
     fn test_adc_instruction_indirect_indexed() {
 
         let mut console = Console::new(Cartridge::new(vec![]));
+        console.cpu.decimal_flag = false;
 
         {
-            setup_instruction(&mut console, vec![0x_71, 0x_42]);
-            console.y_register = 0x_B7;
-            *console.memory_mut(0x_42)     = 0x_24;
-            *console.memory_mut(0x_42 + 1) = 0x_11;
+            setup_instruction(&mut console, vec![0x_71, 0x_C2]);
+            console.cpu.y_register = 0x_B7;
+            *console.memory_mut(0x_C2)     = 0x_24;
+            *console.memory_mut(0x_C2 + 1) = 0x_11;
 
-            console.carry_flag = false;
-            console.accumulator = 0x_00;
-            *console.memory_mut(0x_11DB) = 0x_FF;
+            console.cpu.carry_flag = false;
+            console.cpu.accumulator = 0x_00;
+            console.cartridge_mut().memory[0x_01DB] = 0x_FF;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_FF);
+            assert_eq!(console.cpu.accumulator, 0x_FF);
 
             assert_eq!(cycles, 5);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_71, 0x_42]);
-            console.y_register = 0x_87;
-            *console.memory_mut(0x_42)     = 0x_A3;
-            *console.memory_mut(0x_42 + 1) = 0x_11;
+            setup_instruction(&mut console, vec![0x_71, 0x_C2]);
+            console.cpu.y_register = 0x_87;
+            *console.memory_mut(0x_C2)     = 0x_A3;
+            *console.memory_mut(0x_C2 + 1) = 0x_11;
 
-            console.carry_flag = false;
-            console.accumulator = 0x_00;
-            *console.memory_mut(0x_122A) = 0x_FF;
+            console.cpu.carry_flag = false;
+            console.cpu.accumulator = 0x_00;
+            console.cartridge_mut().memory[0x_022A] = 0x_FF;
 
             let cycles = execute_instruction(&mut console, adc_instruction);
 
-            assert_eq!(console.accumulator, 0x_FF);
+            assert_eq!(console.cpu.accumulator, 0x_FF);
 
             assert_eq!(cycles, 6);
         }
     }
 
     #[test]
-    fn test_and_instruction() {
+    fn test_adc_instruction_decimal() {
 
-        // TODO; To be implemented, but frankly, the instruction and if the
-        // other unit tests are passing, that instruction is high likely to be
-        // correct. See ADC instruction.
+        let mut console = Console::new(Cartridge::new(vec![]));
+        console.cpu.decimal_flag = true;
+
+        // The accumulator, the operand and the carry flag, then the result
+        // and the carry, zero, negative and overflow flags. The zero flag is
+        // computed from the binary sum, and the negative and overflow flags
+        // before the high digit is adjusted.
+        let cases = [
+            (0x_12, 0x_34, false, 0x_46, false, false, false, false),
+            (0x_09, 0x_01, false, 0x_10, false, false, false, false),
+            (0x_58, 0x_46, true,  0x_05, true,  false, true,  true),
+            (0x_99, 0x_01, false, 0x_00, true,  false, true,  false),
+            (0x_79, 0x_00, true,  0x_80, false, false, true,  true),
+            (0x_50, 0x_50, false, 0x_00, true,  false, true,  true),
+            (0x_81, 0x_92, false, 0x_73, true,  false, false, true),
+        ];
+
+        for &(accumulator, operand, carry, result, carry_flag, zero_flag, negative_flag, overflow_flag) in cases.iter() {
+            setup_instruction(&mut console, vec![0x_69, operand]);
+            console.cpu.accumulator = accumulator;
+            console.cpu.carry_flag = carry;
+
+            let cycles = execute_instruction(&mut console, adc_instruction);
+
+            assert_eq!(console.cpu.accumulator, result, "{:#04X} + {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.carry_flag, carry_flag, "{:#04X} + {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.zero_flag, zero_flag, "{:#04X} + {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.negative_flag, negative_flag, "{:#04X} + {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.overflow_flag, overflow_flag, "{:#04X} + {:#04X}", accumulator, operand);
+            assert_eq!(cycles, 2);
+        }
+
+        // A score counter; adding 1 to 99 wraps to 00 with the carry, and
+        // the carry goes to the next byte.
+        setup_instruction(&mut console, vec![0x_18, 0x_A5, 0x_80, 0x_69, 0x_01, 0x_85, 0x_80, 0x_A5, 0x_81, 0x_69, 0x_00, 0x_85, 0x_81]);
+        *console.memory_mut(0x_80) = 0x_99;
+        *console.memory_mut(0x_81) = 0x_12;
+        for _ in 0..7 {
+            console.execute_instruction();
+        }
+        assert_eq!((console.ram(0x_80), console.ram(0x_81)), (0x_00, 0x_13));
+    }
+
+    #[test]
+    fn test_and_instruction() {
         let mut console = Console::new(Cartridge::new(vec![]));
 
         {
             setup_instruction(&mut console, vec![0x_29, 0x_42]);
 
-            console.accumulator = 0x_F0;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.accumulator = 0x_F0;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, and_instruction);
 
-            assert_eq!(console.accumulator, 0x_40);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_40);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -1731,17 +2081,17 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_0A]);
 
-            console.accumulator = 0x_42;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 0x_42;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, asl_instruction);
 
-            assert_eq!(console.accumulator, 0x_84);
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.accumulator, 0x_84);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -1750,16 +2100,16 @@ mod test {
             setup_instruction(&mut console, vec![0x_06, 127]);
 
             *console.memory_mut(127) = 0x_42;
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, asl_instruction);
 
-            assert_eq!(*console.memory(127), 0x_84);
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.memory(127), 0x_84);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 5);
         }
@@ -1772,45 +2122,45 @@ mod test {
 
         // Check if it's not branching on C == 1.
         setup_instruction_x(&mut console, vec![0x_90, 0x_42], 0);
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, bcc_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_90, 0x_42], 0);
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, bcc_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x90, 0x_F0], 0x_42);
 
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, bcc_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x90, 0x_6F], 0x_AE);
 
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, bcc_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x90, 0x_80], 0x_05);
 
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, bcc_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -1821,45 +2171,45 @@ mod test {
 
         // Check if it's not branching on C == 0.
         setup_instruction_x(&mut console, vec![0xB0, 0x_42], 0);
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, bcs_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0xB0, 0x_42], 0);
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, bcs_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0xB0, 0x_F0], 0x_42);
 
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, bcs_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0xB0, 0x_6F], 0x_AE);
 
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, bcs_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0xB0, 0x_80], 0x_05);
 
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, bcs_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -1870,45 +2220,45 @@ mod test {
 
         // Check if it's not branching on Z == 0.
         setup_instruction_x(&mut console, vec![0x_F0, 0x_42], 0);
-        console.zero_flag = false;
+        console.cpu.zero_flag = false;
         let cycles = execute_instruction(&mut console, beq_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_F0, 0x_42], 0);
-        console.zero_flag = true;
+        console.cpu.zero_flag = true;
         let cycles = execute_instruction(&mut console, beq_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_F0, 0x_F0], 0x_42);
 
-        console.zero_flag = true;
+        console.cpu.zero_flag = true;
         let cycles = execute_instruction(&mut console, beq_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_F0, 0x_6F], 0x_AE);
 
-        console.zero_flag = true;
+        console.cpu.zero_flag = true;
         let cycles = execute_instruction(&mut console, beq_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_F0, 0x_80], 0x_05);
 
-        console.zero_flag = true;
+        console.cpu.zero_flag = true;
         let cycles = execute_instruction(&mut console, beq_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -1917,41 +2267,41 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
 
         {
-            setup_instruction(&mut console, vec![0x_24, 0x_42]);
+            setup_instruction(&mut console, vec![0x_24, 0x_82]);
 
-            *console.memory_mut(0x_42) = 0x_40;
-            console.negative_flag = true;
-            console.overflow_flag = false;
+            *console.memory_mut(0x_82) = 0x_40;
+            console.cpu.negative_flag = true;
+            console.cpu.overflow_flag = false;
 
-            console.accumulator = 0x_00;
-            console.zero_flag = false;
+            console.cpu.accumulator = 0x_00;
+            console.cpu.zero_flag = false;
 
             let cycles = execute_instruction(&mut console, bit_instruction);
 
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.overflow_flag, true);
+            assert!(!console.cpu.negative_flag);
+            assert!(console.cpu.overflow_flag);
 
-            assert_eq!(console.zero_flag, true);
+            assert!(console.cpu.zero_flag);
 
             assert_eq!(cycles, 3);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_24, 0x_42]);
+            setup_instruction(&mut console, vec![0x_24, 0x_82]);
 
-            *console.memory_mut(0x_42) = 0x_80;
-            console.negative_flag = false;
-            console.overflow_flag = true;
+            *console.memory_mut(0x_82) = 0x_80;
+            console.cpu.negative_flag = false;
+            console.cpu.overflow_flag = true;
 
-            console.accumulator = 0x_80;
-            console.zero_flag = true;
+            console.cpu.accumulator = 0x_80;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, bit_instruction);
 
-            assert_eq!(console.negative_flag, true);
-            assert_eq!(console.overflow_flag, false);
+            assert!(console.cpu.negative_flag);
+            assert!(!console.cpu.overflow_flag);
 
-            assert_eq!(console.zero_flag, false);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 3);
         }
@@ -1965,45 +2315,45 @@ mod test {
 
         // Check if it's not branching on N == 0.
         setup_instruction_x(&mut console, vec![0x30, 0x_42], 0);
-        console.negative_flag = false;
+        console.cpu.negative_flag = false;
         let cycles = execute_instruction(&mut console, bmi_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x30, 0x_42], 0);
-        console.negative_flag = true;
+        console.cpu.negative_flag = true;
         let cycles = execute_instruction(&mut console, bmi_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x30, 0x_F0], 0x_42);
 
-        console.negative_flag = true;
+        console.cpu.negative_flag = true;
         let cycles = execute_instruction(&mut console, bmi_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x30, 0x_6F], 0x_AE);
 
-        console.negative_flag = true;
+        console.cpu.negative_flag = true;
         let cycles = execute_instruction(&mut console, bmi_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x30, 0x_80], 0x_05);
 
-        console.negative_flag = true;
+        console.cpu.negative_flag = true;
         let cycles = execute_instruction(&mut console, bmi_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -2014,46 +2364,56 @@ mod test {
 
         // Check if it's not branching on Z == 1.
         setup_instruction_x(&mut console, vec![0x_D0, 0x_42], 0);
-        console.zero_flag = true;
+        console.cpu.zero_flag = true;
         let cycles = execute_instruction(&mut console, bne_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_D0, 0x_42], 0);
-        console.zero_flag = false;
+        console.cpu.zero_flag = false;
         let cycles = execute_instruction(&mut console, bne_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_D0, 0x_F0], 0x_42);
 
-        console.zero_flag = false;
+        console.cpu.zero_flag = false;
         let cycles = execute_instruction(&mut console, bne_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_D0, 0x_6F], 0x_AE);
 
-        console.zero_flag = false;
+        console.cpu.zero_flag = false;
         let cycles = execute_instruction(&mut console, bne_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_D0, 0x_80], 0x_05);
 
-        console.zero_flag = false;
+        console.cpu.zero_flag = false;
         let cycles = execute_instruction(&mut console, bne_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
+
+        // Check branching with a null operand; it's taken to the next
+        // instruction (and it takes a cycle more).
+        setup_instruction_x(&mut console, vec![0x_D0, 0x_00], 0);
+
+        console.cpu.zero_flag = false;
+        let cycles = execute_instruction(&mut console, bne_instruction);
+
+        assert_eq!(console.cpu.program_counter, CODE + 2);
+        assert_eq!(cycles, 3);
     }
 
     #[test]
@@ -2063,51 +2423,108 @@ mod test {
 
         // Check if it's not branching on N == 1.
         setup_instruction_x(&mut console, vec![0x10, 0x_42], 0);
-        console.negative_flag = true;
+        console.cpu.negative_flag = true;
         let cycles = execute_instruction(&mut console, bpl_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x10, 0x_42], 0);
-        console.negative_flag = false;
+        console.cpu.negative_flag = false;
         let cycles = execute_instruction(&mut console, bpl_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x10, 0x_F0], 0x_42);
 
-        console.negative_flag = false;
+        console.cpu.negative_flag = false;
         let cycles = execute_instruction(&mut console, bpl_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x10, 0x_6F], 0x_AE);
 
-        console.negative_flag = false;
+        console.cpu.negative_flag = false;
         let cycles = execute_instruction(&mut console, bpl_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x10, 0x_80], 0x_05);
 
-        console.negative_flag = false;
+        console.cpu.negative_flag = false;
         let cycles = execute_instruction(&mut console, bpl_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
     #[test]
     fn test_brk_instruction() {
-        // To be implemented.
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        setup_instruction_x(&mut console, vec![0x_00, 0x_42], 0x_10);
+        console.cartridge_mut().memory[0x_0FFE] = 0x_34;
+        console.cartridge_mut().memory[0x_0FFF] = 0x_F2;
+        console.cpu.negative_flag  = true;
+        console.cpu.overflow_flag  = false;
+        console.cpu.decimal_flag   = false;
+        console.cpu.interrupt_flag = false;
+        console.cpu.zero_flag      = false;
+        console.cpu.carry_flag     = true;
+
+        // The address of the BRK plus 2 and the status flags (with the break
+        // flag) are pushed, and the handler is called with interrupts
+        // disabled.
+        let cycles = execute_instruction(&mut console, brk_instruction);
+        assert_eq!(console.cpu.program_counter, 0x_F234);
+        assert!(console.cpu.interrupt_flag);
+        assert_eq!(console.cpu.stack_pointer, 0x_FC);
+        assert_eq!(cycles, 7);
+
+        assert_eq!(pop_value(&mut console), 0b1011_0001);
+        assert_eq!(pop_value(&mut console), (CODE + 0x_12).to_le_bytes()[0]);
+        assert_eq!(pop_value(&mut console), (CODE + 0x_12).to_le_bytes()[1]);
+
+        // A homebrew using BRK as a trap; the handler counts the traps and
+        // returns after the byte following the BRK.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                ldx #$FF
+                txs
+                cli
+            loop:
+                brk
+                nop
+                inc $81
+                jmp loop
+            ")
+            .code(0x_F800, "
+                inc $80
+                rti
+            ")
+            .irq_vector(0x_F800)
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        for _ in 0..21 {
+            console.execute_instruction();
+        }
+
+        // 4 traps of 5 instructions (BRK, INC, RTI, INC and JMP), after the 3
+        // first instructions.
+        assert_eq!(console.memory(0x_80), 4);
+        assert_eq!(console.memory(0x_81), 3);
+        assert_eq!(console.cpu.stack_pointer, 0x_FF);
+        assert!(!console.cpu.interrupt_flag);
     }
 
     #[test]
@@ -2117,45 +2534,45 @@ mod test {
 
         // Check if it's not branching on V == 1.
         setup_instruction_x(&mut console, vec![0x_50, 0x_42], 0);
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, bvc_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_50, 0x_42], 0);
-        console.overflow_flag = false;
+        console.cpu.overflow_flag = false;
         let cycles = execute_instruction(&mut console, bvc_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_50, 0x_F0], 0x_42);
 
-        console.overflow_flag = false;
+        console.cpu.overflow_flag = false;
         let cycles = execute_instruction(&mut console, bvc_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_50, 0x_6F], 0x_AE);
 
-        console.overflow_flag = false;
+        console.cpu.overflow_flag = false;
         let cycles = execute_instruction(&mut console, bvc_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_50, 0x_80], 0x_05);
 
-        console.overflow_flag = false;
+        console.cpu.overflow_flag = false;
         let cycles = execute_instruction(&mut console, bvc_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -2166,45 +2583,45 @@ mod test {
 
         // Check if it's not branching on V == 0.
         setup_instruction_x(&mut console, vec![0x_70, 0x_42], 0);
-        console.overflow_flag = false;
+        console.cpu.overflow_flag = false;
         let cycles = execute_instruction(&mut console, bvs_instruction);
 
-        assert_eq!(console.pointer_counter, 2);
+        assert_eq!(console.cpu.program_counter, CODE + 2);
         assert_eq!(cycles, 2);
 
         // Check branching with positive operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_70, 0x_42], 0);
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, bvs_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_42);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_42);
         assert_eq!(cycles, 3);
 
         // Check branching with negative operand, without crossing page.
         setup_instruction_x(&mut console, vec![0x_70, 0x_F0], 0x_42);
 
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, bvs_instruction);
 
-        assert_eq!(console.pointer_counter, 2 + 0x_32);
+        assert_eq!(console.cpu.program_counter, CODE + 2 + 0x_32);
         assert_eq!(cycles, 3);
 
         // Check branching with positive operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_70, 0x_6F], 0x_AE);
 
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, bvs_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_11F);
+        assert_eq!(console.cpu.program_counter, CODE + 0x_11F);
         assert_eq!(cycles, 4);
 
         // Check branching with negative operand, with crossing page.
         setup_instruction_x(&mut console, vec![0x_70, 0x_80], 0x_05);
 
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, bvs_instruction);
 
-        assert_eq!(console.pointer_counter, 0x_FF87);
+        assert_eq!(console.cpu.program_counter, CODE - 0x_79);
         assert_eq!(cycles, 4);
     }
 
@@ -2214,9 +2631,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_18]);
 
-        console.carry_flag = true;
+        console.cpu.carry_flag = true;
         let cycles = execute_instruction(&mut console, clc_instruction);
-        assert_eq!(console.carry_flag, false);
+        assert!(!console.cpu.carry_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2227,9 +2644,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_D8]);
 
-        console.decimal_flag = true;
+        console.cpu.decimal_flag = true;
         let cycles = execute_instruction(&mut console, cld_instruction);
-        assert_eq!(console.decimal_flag, false);
+        assert!(!console.cpu.decimal_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2240,9 +2657,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_58]);
 
-        console.interrupt_flag = true;
+        console.cpu.interrupt_flag = true;
         let cycles = execute_instruction(&mut console, cli_instruction);
-        assert_eq!(console.interrupt_flag, false);
+        assert!(!console.cpu.interrupt_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2253,9 +2670,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_B8]);
 
-        console.overflow_flag = true;
+        console.cpu.overflow_flag = true;
         let cycles = execute_instruction(&mut console, clv_instruction);
-        assert_eq!(console.overflow_flag, false);
+        assert!(!console.cpu.overflow_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2270,51 +2687,51 @@ mod test {
 
         {
             setup_instruction(&mut console, vec![0x_C9, 0x_41]);
-            console.accumulator = 0x_42;
+            console.cpu.accumulator = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cmp_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_C9, 0x_42]);
-            console.accumulator = 0x_42;
+            console.cpu.accumulator = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = false;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = false;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cmp_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, true);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_C9, 0x_43]);
-            console.accumulator = 0x_42;
+            console.cpu.accumulator = 0x_42;
 
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, cmp_instruction);
 
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2329,51 +2746,51 @@ mod test {
 
         {
             setup_instruction(&mut console, vec![0x_E0, 0x_41]);
-            console.x_register = 0x_42;
+            console.cpu.x_register = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cpx_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_E0, 0x_42]);
-            console.x_register = 0x_42;
+            console.cpu.x_register = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = false;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = false;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cpx_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, true);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_E0, 0x_43]);
-            console.x_register = 0x_42;
+            console.cpu.x_register = 0x_42;
 
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, cpx_instruction);
 
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2388,51 +2805,51 @@ mod test {
 
         {
             setup_instruction(&mut console, vec![0x_C0, 0x_41]);
-            console.y_register = 0x_42;
+            console.cpu.y_register = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cpy_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_C0, 0x_42]);
-            console.y_register = 0x_42;
+            console.cpu.y_register = 0x_42;
 
-            console.carry_flag = false;
-            console.zero_flag = false;
-            console.negative_flag = true;
+            console.cpu.carry_flag = false;
+            console.cpu.zero_flag = false;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, cpy_instruction);
 
-            assert_eq!(console.carry_flag, true);
-            assert_eq!(console.zero_flag, true);
-            assert_eq!(console.negative_flag, false);
+            assert!(console.cpu.carry_flag);
+            assert!(console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
             setup_instruction(&mut console, vec![0x_C0, 0x_43]);
-            console.y_register = 0x_42;
+            console.cpu.y_register = 0x_42;
 
-            console.carry_flag = true;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.carry_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, cpy_instruction);
 
-            assert_eq!(console.carry_flag, false);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert!(!console.cpu.carry_flag);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2444,67 +2861,67 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
 
         {
-            setup_instruction(&mut console, vec![0x_C6, 0x_42]);
+            setup_instruction(&mut console, vec![0x_C6, 0x_C2]);
 
-            *console.memory_mut(0x_42) = 128;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            *console.memory_mut(0x_C2) = 128;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, dec_instruction);
 
-            assert_eq!(*console.memory(0x_42), 127);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_C2), 127);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 5);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_D6, 0x_41]);
-            console.x_register = 0x_01;
+            setup_instruction(&mut console, vec![0x_D6, 0x_C1]);
+            console.cpu.x_register = 0x_01;
 
-            *console.memory_mut(0x_42) = 128;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            *console.memory_mut(0x_C2) = 128;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, dec_instruction);
 
-            assert_eq!(*console.memory(0x_42), 127);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_C2), 127);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 6);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_CE, 0x_42, 3]);
+            setup_instruction(&mut console, vec![0x_CE, 0x_C2, 1]);
 
-            *console.memory_mut(3 * 256 + 0x_42) = 128;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            *console.memory_mut(0x_01C2) = 128;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, dec_instruction);
 
-            assert_eq!(*console.memory(3 * 256 + 0x_42), 127);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_01C2), 127);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 6);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_DE, 0x_41, 3]);
-            console.x_register = 0x_01;
+            setup_instruction(&mut console, vec![0x_DE, 0x_C1, 1]);
+            console.cpu.x_register = 0x_01;
 
-            *console.memory_mut(3 * 256 + 0x_42) = 128;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            *console.memory_mut(0x_01C2) = 128;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, dec_instruction);
 
-            assert_eq!(*console.memory(3 * 256 + 0x_42), 127);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_01C2), 127);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 7);
         }
@@ -2517,15 +2934,15 @@ mod test {
 
         setup_instruction(&mut console, vec![0x_CA]);
 
-        console.x_register = 128;
-        console.negative_flag = true;
-        console.zero_flag = true;
+        console.cpu.x_register = 128;
+        console.cpu.negative_flag = true;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, dex_instruction);
 
-        assert_eq!(console.x_register, 127);
-        assert_eq!(console.negative_flag, false);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.x_register, 127);
+        assert!(!console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2536,15 +2953,15 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_88]);
 
-        console.y_register = 128;
-        console.negative_flag = true;
-        console.zero_flag = true;
+        console.cpu.y_register = 128;
+        console.cpu.negative_flag = true;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, dey_instruction);
 
-        assert_eq!(console.y_register, 127);
-        assert_eq!(console.negative_flag, false);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.y_register, 127);
+        assert!(!console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2558,15 +2975,15 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_49, 0x_55]);
 
-        console.accumulator = 0x_33;
-        console.zero_flag = true;
-        console.negative_flag = true;
+        console.cpu.accumulator = 0x_33;
+        console.cpu.zero_flag = true;
+        console.cpu.negative_flag = true;
 
         let cycles = execute_instruction(&mut console, eor_instruction);
 
-        assert_eq!(console.accumulator, 0x_66);
-        assert_eq!(console.zero_flag, false);
-        assert_eq!(console.negative_flag, false);
+        assert_eq!(console.cpu.accumulator, 0x_66);
+        assert!(!console.cpu.zero_flag);
+        assert!(!console.cpu.negative_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2577,67 +2994,67 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
 
         {
-            setup_instruction(&mut console, vec![0x_E6, 0x_42]);
+            setup_instruction(&mut console, vec![0x_E6, 0x_C2]);
 
-            *console.memory_mut(0x_42) = 127;
-            console.negative_flag = false;
-            console.zero_flag = true;
+            *console.memory_mut(0x_C2) = 127;
+            console.cpu.negative_flag = false;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, inc_instruction);
 
-            assert_eq!(*console.memory(0x_42), 128);
-            assert_eq!(console.negative_flag, true);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_C2), 128);
+            assert!(console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 5);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_F6, 0x_41]);
-            console.x_register = 0x_01;
+            setup_instruction(&mut console, vec![0x_F6, 0x_C1]);
+            console.cpu.x_register = 0x_01;
 
-            *console.memory_mut(0x_42) = 127;
-            console.negative_flag = false;
-            console.zero_flag = true;
+            *console.memory_mut(0x_C2) = 127;
+            console.cpu.negative_flag = false;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, inc_instruction);
 
-            assert_eq!(*console.memory(0x_42), 128);
-            assert_eq!(console.negative_flag, true);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_C2), 128);
+            assert!(console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 6);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_EE, 0x_42, 3]);
+            setup_instruction(&mut console, vec![0x_EE, 0x_C2, 1]);
 
-            *console.memory_mut(3 * 256 + 0x_42) = 127;
-            console.negative_flag = false;
-            console.zero_flag = true;
+            *console.memory_mut(0x_01C2) = 127;
+            console.cpu.negative_flag = false;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, inc_instruction);
 
-            assert_eq!(*console.memory(3 * 256 + 0x_42), 128);
-            assert_eq!(console.negative_flag, true);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_01C2), 128);
+            assert!(console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 6);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_FE, 0x_41, 3]);
-            console.x_register = 0x_01;
+            setup_instruction(&mut console, vec![0x_FE, 0x_C1, 1]);
+            console.cpu.x_register = 0x_01;
 
-            *console.memory_mut(3 * 256 + 0x_42) = 127;
-            console.negative_flag = false;
-            console.zero_flag = true;
+            *console.memory_mut(0x_01C2) = 127;
+            console.cpu.negative_flag = false;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, inc_instruction);
 
-            assert_eq!(*console.memory(3 * 256 + 0x_42), 128);
-            assert_eq!(console.negative_flag, true);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.memory(0x_01C2), 128);
+            assert!(console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 7);
         }
@@ -2650,15 +3067,15 @@ mod test {
 
         setup_instruction(&mut console, vec![0x_E8]);
 
-        console.x_register = 127;
-        console.negative_flag = false;
-        console.zero_flag = true;
+        console.cpu.x_register = 127;
+        console.cpu.negative_flag = false;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, inx_instruction);
 
-        assert_eq!(console.x_register, 128);
-        assert_eq!(console.negative_flag, true);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.x_register, 128);
+        assert!(console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2669,15 +3086,15 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_C8]);
 
-        console.y_register = 127;
-        console.negative_flag = false;
-        console.zero_flag = true;
+        console.cpu.y_register = 127;
+        console.cpu.negative_flag = false;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, iny_instruction);
 
-        assert_eq!(console.y_register, 128);
-        assert_eq!(console.negative_flag, true);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.y_register, 128);
+        assert!(console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2691,18 +3108,18 @@ mod test {
             setup_instruction(&mut console, vec![0x_4C, 0x_42, 0x_31]);
             let cycles = execute_instruction(&mut console, jmp_instruction);
 
-            assert_eq!(console.pointer_counter, 0x_3142);
+            assert_eq!(console.cpu.program_counter, 0x_3142);
             assert_eq!(cycles, 3);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_6C, 0x_11, 0x_22]);
-            *console.memory_mut(0x_2211)     = 0x_42;
-            *console.memory_mut(0x_2211 + 1) = 0x_31;
+            setup_instruction(&mut console, vec![0x_6C, 0x_11, 0x_12]);
+            console.cartridge_mut().memory[0x_0211] = 0x_42;
+            console.cartridge_mut().memory[0x_0212] = 0x_31;
 
             let cycles = execute_instruction(&mut console, jmp_instruction);
 
-            assert_eq!(console.pointer_counter, 0x_3142);
+            assert_eq!(console.cpu.program_counter, 0x_3142);
             assert_eq!(cycles, 5);
         }
     }
@@ -2712,15 +3129,15 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
 
         setup_instruction(&mut console, vec![0x_20, 0x_42, 0x_31]);
-        let pointer_counter = console.pointer_counter;
+        let pointer_counter = console.cpu.program_counter;
 
         let cycles = execute_instruction(&mut console, jsr_instruction);
 
-        let ll = console.pop_value();
-        let hh = console.pop_value();
+        let ll = pop_value(&mut console);
+        let hh = pop_value(&mut console);
         assert_eq!(u16::from_le_bytes([ll, hh]), pointer_counter + 2);
 
-        assert_eq!(console.pointer_counter, 0x_3142);
+        assert_eq!(console.cpu.program_counter, 0x_3142);
 
         assert_eq!(cycles, 6);
     }
@@ -2736,15 +3153,15 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_A9, 128]);
 
-            console.accumulator = 127;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.accumulator = 127;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, lda_instruction);
 
-            assert_eq!(console.accumulator, 128);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.accumulator, 128);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2761,15 +3178,15 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_A2, 128]);
 
-            console.x_register = 127;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.x_register = 127;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, ldx_instruction);
 
-            assert_eq!(console.x_register, 128);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.x_register, 128);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2786,15 +3203,15 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_A0, 128]);
 
-            console.y_register = 127;
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.y_register = 127;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, ldy_instruction);
 
-            assert_eq!(console.y_register, 128);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert_eq!(console.cpu.y_register, 128);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2811,39 +3228,39 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_4A]);
 
-            console.carry_flag = true;
-            console.accumulator = 0x_AA;
+            console.cpu.carry_flag = true;
+            console.cpu.accumulator = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, lsr_instruction);
 
-            console.carry_flag = true;
-            assert_eq!(console.accumulator, 0x_55);
+            console.cpu.carry_flag = true;
+            assert_eq!(console.cpu.accumulator, 0x_55);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_46, 0x_42]);
+            setup_instruction(&mut console, vec![0x_46, 0x_C2]);
 
-            console.carry_flag = true;
-            *console.memory_mut(0x_42) = 0x_AA;
+            console.cpu.carry_flag = true;
+            *console.memory_mut(0x_C2) = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, lsr_instruction);
 
-            console.carry_flag = true;
-            assert_eq!(*console.memory(0x_42), 0x_55);
+            console.cpu.carry_flag = true;
+            assert_eq!(console.memory(0x_C2), 0x_55);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 5);
         }
@@ -2854,35 +3271,35 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_EA]);
 
-        let pointer_counter = console.pointer_counter;
+        let pointer_counter = console.cpu.program_counter;
 
-        console.accumulator = 0x_4B;
-        console.x_register = 0x_E1;
-        console.y_register = 0x_CD;
+        console.cpu.accumulator = 0x_4B;
+        console.cpu.x_register = 0x_E1;
+        console.cpu.y_register = 0x_CD;
 
-        console.negative_flag = true;
-        console.overflow_flag = false;
-        console.break_flag = true;
-        console.decimal_flag = false;
-        console.interrupt_flag = true;
-        console.zero_flag = false;
-        console.carry_flag = true;
+        console.cpu.negative_flag = true;
+        console.cpu.overflow_flag = false;
+        console.cpu.break_flag = true;
+        console.cpu.decimal_flag = false;
+        console.cpu.interrupt_flag = true;
+        console.cpu.zero_flag = false;
+        console.cpu.carry_flag = true;
 
         let cycles = execute_instruction(&mut console, nop_instruction);
 
-        assert_eq!(console.pointer_counter, pointer_counter + 1);
+        assert_eq!(console.cpu.program_counter, pointer_counter + 1);
 
-        assert_eq!(console.accumulator, 0x_4B);
-        assert_eq!(console.x_register, 0x_E1);
-        assert_eq!(console.y_register, 0x_CD);
+        assert_eq!(console.cpu.accumulator, 0x_4B);
+        assert_eq!(console.cpu.x_register, 0x_E1);
+        assert_eq!(console.cpu.y_register, 0x_CD);
 
-        assert_eq!(console.negative_flag, true);
-        assert_eq!(console.overflow_flag, false);
-        assert_eq!(console.break_flag, true);
-        assert_eq!(console.decimal_flag, false);
-        assert_eq!(console.interrupt_flag, true);
-        assert_eq!(console.zero_flag, false);
-        assert_eq!(console.carry_flag, true);
+        assert!(console.cpu.negative_flag);
+        assert!(!console.cpu.overflow_flag);
+        assert!(console.cpu.break_flag);
+        assert!(!console.cpu.decimal_flag);
+        assert!(console.cpu.interrupt_flag);
+        assert!(!console.cpu.zero_flag);
+        assert!(console.cpu.carry_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -2898,15 +3315,15 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_09, 0x_55]);
 
-            console.accumulator = 0x_33;
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.accumulator = 0x_33;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, ora_instruction);
 
-            assert_eq!(console.accumulator, 0x_77);
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert_eq!(console.cpu.accumulator, 0x_77);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -2918,13 +3335,13 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_48]);
 
-        console.accumulator = 0x_42;
+        console.cpu.accumulator = 0x_42;
         *console.memory_mut(0x_FF) = 0x_00;
 
         let cycles = execute_instruction(&mut console, pha_instruction);
 
-        assert_eq!(console.accumulator, 0x_42);
-        assert_eq!(*console.memory(0x_FF), 0x_42);
+        assert_eq!(console.cpu.accumulator, 0x_42);
+        assert_eq!(console.memory(0x_FF), 0x_42);
 
         assert_eq!(cycles, 3);
     }
@@ -2935,20 +3352,31 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_08]);
 
-        console.negative_flag  = true;
-        console.overflow_flag  = false;
-        console.break_flag     = false;
-        console.decimal_flag   = true;
-        console.interrupt_flag = false;
-        console.zero_flag      = true;
-        console.carry_flag     = false;
+        console.cpu.negative_flag  = true;
+        console.cpu.overflow_flag  = false;
+        console.cpu.break_flag     = false;
+        console.cpu.decimal_flag   = true;
+        console.cpu.interrupt_flag = false;
+        console.cpu.zero_flag      = true;
+        console.cpu.carry_flag     = false;
         *console.memory_mut(0x_FF) = 0x_00;
 
         let cycles = execute_instruction(&mut console, php_instruction);
 
-        assert_eq!(*console.memory(0x_FF), 0b1000_1010);
+        // The break flag and bit 5 are always pushed set.
+        assert_eq!(console.memory(0x_FF), 0b1011_1010);
 
         assert_eq!(cycles, 3);
+
+        // Pulling the status back restores the flags.
+        setup_instruction(&mut console, vec![0x_28]);
+        console.cpu.negative_flag = false;
+        console.cpu.zero_flag = false;
+        execute_instruction(&mut console, plp_instruction);
+        assert!(console.cpu.negative_flag);
+        assert!(console.cpu.zero_flag);
+        assert!(console.cpu.decimal_flag);
+        assert!(!console.cpu.break_flag);
     }
 
     #[test]
@@ -2957,11 +3385,11 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_68]);
 
-        console.push_value(0x_42);
-        console.accumulator = 0x_00;
+        push_value(&mut console, 0x_42);
+        console.cpu.accumulator = 0x_00;
 
         let cycles = execute_instruction(&mut console, pla_instruction);
-        assert_eq!(console.accumulator, 0x_42);
+        assert_eq!(console.cpu.accumulator, 0x_42);
 
         assert_eq!(cycles, 4);
     }
@@ -2972,25 +3400,36 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_28]);
 
-        console.push_value(0b1000_1010);
-        console.negative_flag  = false;
-        console.overflow_flag  = true;
-        console.break_flag     = true;
-        console.decimal_flag   = false;
-        console.interrupt_flag = true;
-        console.zero_flag      = false;
-        console.carry_flag     = true;
+        push_value(&mut console, 0b1000_1010);
+        console.cpu.negative_flag  = false;
+        console.cpu.overflow_flag  = true;
+        console.cpu.break_flag     = true;
+        console.cpu.decimal_flag   = false;
+        console.cpu.interrupt_flag = true;
+        console.cpu.zero_flag      = false;
+        console.cpu.carry_flag     = true;
 
         let cycles = execute_instruction(&mut console, plp_instruction);
-        assert_eq!(console.negative_flag, true);
-        assert_eq!(console.overflow_flag, false);
-        assert_eq!(console.break_flag, false);
-        assert_eq!(console.decimal_flag, true);
-        assert_eq!(console.interrupt_flag, false);
-        assert_eq!(console.zero_flag, true);
-        assert_eq!(console.carry_flag, false);
+        assert!(console.cpu.negative_flag);
+        assert!(!console.cpu.overflow_flag);
+        assert!(console.cpu.break_flag);
+        assert!(console.cpu.decimal_flag);
+        assert!(!console.cpu.interrupt_flag);
+        assert!(console.cpu.zero_flag);
+        assert!(!console.cpu.carry_flag);
 
         assert_eq!(cycles, 4);
+
+        // The interrupt flag is restored when it was set, and the break flag
+        // is left unchanged.
+        setup_instruction(&mut console, vec![0x_28]);
+        push_value(&mut console, 0b0000_0100);
+        console.cpu.interrupt_flag = false;
+        console.cpu.break_flag = false;
+
+        execute_instruction(&mut console, plp_instruction);
+        assert!(console.cpu.interrupt_flag);
+        assert!(!console.cpu.break_flag);
     }
 
     #[test]
@@ -3004,39 +3443,39 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_2A]);
 
-            console.carry_flag = false;
-            console.accumulator = 0x_AA;
+            console.cpu.carry_flag = false;
+            console.cpu.accumulator = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, rol_instruction);
 
-            console.carry_flag = true;
-            assert_eq!(console.accumulator, 0x_54);
+            console.cpu.carry_flag = true;
+            assert_eq!(console.cpu.accumulator, 0x_54);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_26, 0x_42]);
+            setup_instruction(&mut console, vec![0x_26, 0x_C2]);
 
-            console.carry_flag = false;
-            *console.memory_mut(0x_42) = 0x_AA;
+            console.cpu.carry_flag = false;
+            *console.memory_mut(0x_C2) = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = true;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = true;
 
             let cycles = execute_instruction(&mut console, rol_instruction);
 
-            console.carry_flag = true;
-            assert_eq!(*console.memory(0x_42), 0x_54);
+            console.cpu.carry_flag = true;
+            assert_eq!(console.memory(0x_C2), 0x_54);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, false);
+            assert!(!console.cpu.zero_flag);
+            assert!(!console.cpu.negative_flag);
 
             assert_eq!(cycles, 5);
         }
@@ -3053,47 +3492,125 @@ mod test {
         {
             setup_instruction(&mut console, vec![0x_6A]);
 
-            console.carry_flag = true;
-            console.accumulator = 0x_AA;
+            console.cpu.carry_flag = true;
+            console.cpu.accumulator = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, ror_instruction);
 
-            console.carry_flag = false;
-            assert_eq!(console.accumulator, 0x_D5);
+            console.cpu.carry_flag = false;
+            assert_eq!(console.cpu.accumulator, 0x_D5);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 2);
         }
 
         {
-            setup_instruction(&mut console, vec![0x_66, 0x_42]);
+            setup_instruction(&mut console, vec![0x_66, 0x_C2]);
 
-            console.carry_flag = true;
-            *console.memory_mut(0x_42) = 0x_AA;
+            console.cpu.carry_flag = true;
+            *console.memory_mut(0x_C2) = 0x_AA;
 
-            console.zero_flag = true;
-            console.negative_flag = false;
+            console.cpu.zero_flag = true;
+            console.cpu.negative_flag = false;
 
             let cycles = execute_instruction(&mut console, ror_instruction);
 
-            console.carry_flag = false;
-            assert_eq!(*console.memory(0x_42), 0x_D5);
+            console.cpu.carry_flag = false;
+            assert_eq!(console.memory(0x_C2), 0x_D5);
 
-            assert_eq!(console.zero_flag, false);
-            assert_eq!(console.negative_flag, true);
+            assert!(!console.cpu.zero_flag);
+            assert!(console.cpu.negative_flag);
 
             assert_eq!(cycles, 5);
         }
     }
 
+    #[test]
+    fn test_kil_instruction() {
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        for &opcode in [0x_02, 0x_12, 0x_22, 0x_32, 0x_42, 0x_52, 0x_62, 0x_72, 0x_92, 0x_B2, 0x_D2, 0x_F2].iter() {
+            setup_instruction_x(&mut console, vec![opcode], 0x_42);
+            console.cpu.jam_address = None;
+
+            // The CPU stays stuck on the opcode.
+            execute_instruction(&mut console, kil_instruction);
+            assert_eq!(console.cpu.program_counter, CODE + 0x_42);
+            assert_eq!(console.cpu.jam_address, Some(CODE + 0x_42));
+        }
+    }
+
+    #[test]
+    fn test_read_modify_write() {
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        // The accumulator and the memory are modified the same way.
+        for &(accumulator_opcode, memory_opcode, instruction) in [
+            (0x_0A, 0x_06, asl_instruction as Instruction),
+            (0x_4A, 0x_46, lsr_instruction),
+            (0x_2A, 0x_26, rol_instruction),
+            (0x_6A, 0x_66, ror_instruction)
+        ].iter() {
+            for &value in [0x_00, 0x_01, 0x_80, 0x_C3].iter() {
+                setup_instruction(&mut console, vec![accumulator_opcode]);
+                console.cpu.accumulator = value;
+                console.cpu.carry_flag = true;
+                execute_instruction(&mut console, instruction);
+                let expected = (console.cpu.accumulator, console.cpu.carry_flag, console.cpu.zero_flag, console.cpu.negative_flag);
+
+                setup_instruction(&mut console, vec![memory_opcode, 0x_C2]);
+                *console.memory_mut(0x_C2) = value;
+                console.cpu.carry_flag = true;
+                execute_instruction(&mut console, instruction);
+                assert_eq!((console.memory(0x_C2), console.cpu.carry_flag, console.cpu.zero_flag, console.cpu.negative_flag), expected);
+            }
+        }
+
+        // The value is read from the location at the address and written to
+        // the one at the address; INTIM is read and TIM1T is written back.
+        setup_instruction(&mut console, vec![0x_EE, 0x_94, 0x_02]);
+        *console.memory_mut(TIM64T) = 0x_10;
+        execute_instruction(&mut console, inc_instruction);
+        assert_eq!(console.memory(INTIM), 0x_11);
+    }
+
     #[test]
     fn test_rti_instruction() {
-        // To be implemetend.
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        // The interrupt flag is restored, whether it was set or not, along
+        // with the pointer counter.
+        for &interrupt_flag in [false, true].iter() {
+            setup_instruction(&mut console, vec![0x_40]);
+
+            push_value(&mut console, 0x_F1);
+            push_value(&mut console, 0x_23);
+            push_value(&mut console, 0b1100_0001 | (interrupt_flag as u8) << 2);
+            console.cpu.interrupt_flag = !interrupt_flag;
+            console.cpu.negative_flag  = false;
+            console.cpu.overflow_flag  = false;
+            console.cpu.break_flag     = true;
+            console.cpu.carry_flag     = false;
+
+            let cycles = execute_instruction(&mut console, rti_instruction);
+            assert_eq!(console.cpu.program_counter, 0x_F123);
+            assert_eq!(console.cpu.interrupt_flag, interrupt_flag);
+            assert!(console.cpu.negative_flag);
+            assert!(console.cpu.overflow_flag);
+            assert!(console.cpu.break_flag);
+            assert!(console.cpu.carry_flag);
+            assert_eq!(console.cpu.stack_pointer, 0x_FF);
+
+            assert_eq!(cycles, 6);
+        }
     }
 
     #[test]
@@ -3110,7 +3627,80 @@ mod test {
 
     #[test]
     fn test_sbc_instruction() {
-        // To be implemetend.
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+        console.cpu.decimal_flag = false;
+
+        // The carry flag is the complement of the borrow.
+        let cases = [
+            (0x_50, 0x_F0, true,  0x_60, false, false, false, false),
+            (0x_50, 0x_B0, true,  0x_A0, false, false, true,  true),
+            (0x_D0, 0x_70, true,  0x_60, true,  false, false, true),
+            (0x_05, 0x_05, false, 0x_FF, false, false, true,  false),
+            (0x_05, 0x_05, true,  0x_00, true,  true,  false, false),
+        ];
+
+        for &(accumulator, operand, carry, result, carry_flag, zero_flag, negative_flag, overflow_flag) in cases.iter() {
+            setup_instruction(&mut console, vec![0x_E9, operand]);
+            console.cpu.accumulator = accumulator;
+            console.cpu.carry_flag = carry;
+
+            let cycles = execute_instruction(&mut console, sbc_instruction);
+
+            assert_eq!(console.cpu.accumulator, result, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.carry_flag, carry_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.zero_flag, zero_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.negative_flag, negative_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.overflow_flag, overflow_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(cycles, 2);
+        }
+
+        {
+            setup_instruction(&mut console, vec![0x_FD, 0x_DB, 0x_04]);
+            console.cpu.x_register = 0x_A6;
+            *console.memory_mut(0x_0581) = 0x_41;
+
+            console.cpu.accumulator = 0x_50;
+            console.cpu.carry_flag = true;
+
+            let cycles = execute_instruction(&mut console, sbc_instruction);
+
+            assert_eq!(console.cpu.accumulator, 0x_0F);
+            assert!(console.cpu.carry_flag);
+
+            assert_eq!(cycles, 4 + 1);
+        }
+    }
+
+    #[test]
+    fn test_sbc_instruction_decimal() {
+
+        let mut console = Console::new(Cartridge::new(vec![]));
+        console.cpu.decimal_flag = true;
+
+        // All the flags are computed from the binary difference.
+        let cases = [
+            (0x_46, 0x_12, true,  0x_34, true,  false, false, false),
+            (0x_40, 0x_13, true,  0x_27, true,  false, false, false),
+            (0x_32, 0x_02, false, 0x_29, true,  false, false, false),
+            (0x_00, 0x_01, true,  0x_99, false, false, true,  false),
+            (0x_12, 0x_21, true,  0x_91, false, false, true,  false),
+            (0x_10, 0x_10, true,  0x_00, true,  true,  false, false),
+        ];
+
+        for &(accumulator, operand, carry, result, carry_flag, zero_flag, negative_flag, overflow_flag) in cases.iter() {
+            setup_instruction(&mut console, vec![0x_E9, operand]);
+            console.cpu.accumulator = accumulator;
+            console.cpu.carry_flag = carry;
+
+            execute_instruction(&mut console, sbc_instruction);
+
+            assert_eq!(console.cpu.accumulator, result, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.carry_flag, carry_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.zero_flag, zero_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.negative_flag, negative_flag, "{:#04X} - {:#04X}", accumulator, operand);
+            assert_eq!(console.cpu.overflow_flag, overflow_flag, "{:#04X} - {:#04X}", accumulator, operand);
+        }
     }
 
     #[test]
@@ -3119,9 +3709,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_38]);
 
-        console.carry_flag = false;
+        console.cpu.carry_flag = false;
         let cycles = execute_instruction(&mut console, sec_instruction);
-        assert_eq!(console.carry_flag, true);
+        assert!(console.cpu.carry_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -3132,9 +3722,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_F8]);
 
-        console.decimal_flag = false;
+        console.cpu.decimal_flag = false;
         let cycles = execute_instruction(&mut console, sed_instruction);
-        assert_eq!(console.decimal_flag, true);
+        assert!(console.cpu.decimal_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -3145,9 +3735,9 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_78]);
 
-        console.interrupt_flag = false;
+        console.cpu.interrupt_flag = false;
         let cycles = execute_instruction(&mut console, sei_instruction);
-        assert_eq!(console.interrupt_flag, true);
+        assert!(console.cpu.interrupt_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -3160,10 +3750,10 @@ mod test {
         setup_instruction(&mut console, vec![0x_85, 127]);
 
         *console.memory_mut(127) = 0;
-        console.accumulator = 0x_42;
+        console.cpu.accumulator = 0x_42;
 
         let cycles = execute_instruction(&mut console, sta_instruction);
-        assert_eq!(*console.memory(127), 0x_42);
+        assert_eq!(console.memory(127), 0x_42);
 
         assert_eq!(cycles, 3);
     }
@@ -3177,10 +3767,10 @@ mod test {
             setup_instruction(&mut console, vec![0x_86, 127]);
 
             *console.memory_mut(127) = 0;
-            console.x_register = 0x_42;
+            console.cpu.x_register = 0x_42;
 
             let cycles = execute_instruction(&mut console, stx_instruction);
-            assert_eq!(*console.memory(127), 0x_42);
+            assert_eq!(console.memory(127), 0x_42);
 
             assert_eq!(cycles, 3);
         }
@@ -3189,11 +3779,11 @@ mod test {
             setup_instruction(&mut console, vec![0x_96, 127]);
 
             *console.memory_mut(128) = 0;
-            console.x_register = 0x_42;
-            console.y_register = 1;
+            console.cpu.x_register = 0x_42;
+            console.cpu.y_register = 1;
 
             let cycles = execute_instruction(&mut console, stx_instruction);
-            assert_eq!(*console.memory(128), 0x_42);
+            assert_eq!(console.memory(128), 0x_42);
 
             assert_eq!(cycles, 4);
         }
@@ -3202,10 +3792,10 @@ mod test {
             setup_instruction(&mut console, vec![0x_8E, 0x_7F, 0x_03]);
 
             *console.memory_mut(0x_037F) = 0;
-            console.x_register = 0x_42;
+            console.cpu.x_register = 0x_42;
 
             let cycles = execute_instruction(&mut console, stx_instruction);
-            assert_eq!(*console.memory(0x_037F), 0x_42);
+            assert_eq!(console.memory(0x_037F), 0x_42);
 
             assert_eq!(cycles, 4);
         }
@@ -3220,10 +3810,10 @@ mod test {
             setup_instruction(&mut console, vec![0x_84, 127]);
 
             *console.memory_mut(127) = 0;
-            console.y_register = 0x_42;
+            console.cpu.y_register = 0x_42;
 
             let cycles = execute_instruction(&mut console, sty_instruction);
-            assert_eq!(*console.memory(127), 0x_42);
+            assert_eq!(console.memory(127), 0x_42);
 
             assert_eq!(cycles, 3);
         }
@@ -3232,11 +3822,11 @@ mod test {
             setup_instruction(&mut console, vec![0x_94, 127]);
 
             *console.memory_mut(128) = 0;
-            console.x_register = 1;
-            console.y_register = 0x_42;
+            console.cpu.x_register = 1;
+            console.cpu.y_register = 0x_42;
 
             let cycles = execute_instruction(&mut console, sty_instruction);
-            assert_eq!(*console.memory(128), 0x_42);
+            assert_eq!(console.memory(128), 0x_42);
 
             assert_eq!(cycles, 4);
         }
@@ -3245,10 +3835,10 @@ mod test {
             setup_instruction(&mut console, vec![0x_8C, 0x_7F, 0x_03]);
 
             *console.memory_mut(0x_037F) = 0;
-            console.y_register = 0x_42;
+            console.cpu.y_register = 0x_42;
 
             let cycles = execute_instruction(&mut console, sty_instruction);
-            assert_eq!(*console.memory(0x_037F), 0x_42);
+            assert_eq!(console.memory(0x_037F), 0x_42);
 
             assert_eq!(cycles, 4);
         }
@@ -3260,17 +3850,17 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_AA]);
 
-        console.accumulator = 42;
-        console.x_register = 0;
-        console.negative_flag = true;
-        console.zero_flag = true;
+        console.cpu.accumulator = 42;
+        console.cpu.x_register = 0;
+        console.cpu.negative_flag = true;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, tax_instruction);
 
-        assert_eq!(console.accumulator, 42);
-        assert_eq!(console.x_register, 42);
-        assert_eq!(console.negative_flag, false);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.accumulator, 42);
+        assert_eq!(console.cpu.x_register, 42);
+        assert!(!console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -3281,17 +3871,17 @@ mod test {
         let mut console = Console::new(Cartridge::new(vec![]));
         setup_instruction(&mut console, vec![0x_A8]);
 
-        console.accumulator = 42;
-        console.y_register = 0;
-        console.negative_flag = true;
-        console.zero_flag = true;
+        console.cpu.accumulator = 42;
+        console.cpu.y_register = 0;
+        console.cpu.negative_flag = true;
+        console.cpu.zero_flag = true;
 
         let cycles = execute_instruction(&mut console, tay_instruction);
 
-        assert_eq!(console.accumulator, 42);
-        assert_eq!(console.y_register, 42);
-        assert_eq!(console.negative_flag, false);
-        assert_eq!(console.zero_flag, false);
+        assert_eq!(console.cpu.accumulator, 42);
+        assert_eq!(console.cpu.y_register, 42);
+        assert!(!console.cpu.negative_flag);
+        assert!(!console.cpu.zero_flag);
 
         assert_eq!(cycles, 2);
     }
@@ -3302,17 +3892,17 @@ mod test {
             let mut console = Console::new(Cartridge::new(vec![]));
             setup_instruction(&mut console, vec![0x_BA]);
 
-            console.x_register = 0;
-            console.stack_pointer = 42;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            console.cpu.x_register = 0;
+            console.cpu.stack_pointer = 42;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, tsx_instruction);
 
-            assert_eq!(console.x_register, 42);
-            assert_eq!(console.stack_pointer, 42);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.cpu.x_register, 42);
+            assert_eq!(console.cpu.stack_pointer, 42);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -3323,17 +3913,17 @@ mod test {
             let mut console = Console::new(Cartridge::new(vec![]));
             setup_instruction(&mut console, vec![0x_8A]);
 
-            console.accumulator = 0;
-            console.x_register = 42;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            console.cpu.accumulator = 0;
+            console.cpu.x_register = 42;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, txa_instruction);
 
-            assert_eq!(console.accumulator, 42);
-            assert_eq!(console.x_register, 42);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.cpu.accumulator, 42);
+            assert_eq!(console.cpu.x_register, 42);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 2);
         }
@@ -3344,13 +3934,13 @@ mod test {
             let mut console = Console::new(Cartridge::new(vec![]));
             setup_instruction(&mut console, vec![0x_9A]);
 
-            console.x_register = 42;
-            console.stack_pointer = 0;
+            console.cpu.x_register = 42;
+            console.cpu.stack_pointer = 0;
 
             let cycles = execute_instruction(&mut console, txs_instruction);
 
-            assert_eq!(console.x_register, 42);
-            assert_eq!(console.stack_pointer, 42);
+            assert_eq!(console.cpu.x_register, 42);
+            assert_eq!(console.cpu.stack_pointer, 42);
 
             assert_eq!(cycles, 2);
         }
@@ -3361,17 +3951,17 @@ mod test {
             let mut console = Console::new(Cartridge::new(vec![]));
             setup_instruction(&mut console, vec![0x_98]);
 
-            console.accumulator = 0;
-            console.y_register = 42;
-            console.negative_flag = true;
-            console.zero_flag = true;
+            console.cpu.accumulator = 0;
+            console.cpu.y_register = 42;
+            console.cpu.negative_flag = true;
+            console.cpu.zero_flag = true;
 
             let cycles = execute_instruction(&mut console, tya_instruction);
 
-            assert_eq!(console.accumulator, 42);
-            assert_eq!(console.y_register, 42);
-            assert_eq!(console.negative_flag, false);
-            assert_eq!(console.zero_flag, false);
+            assert_eq!(console.cpu.accumulator, 42);
+            assert_eq!(console.cpu.y_register, 42);
+            assert!(!console.cpu.negative_flag);
+            assert!(!console.cpu.zero_flag);
 
             assert_eq!(cycles, 2);
         }
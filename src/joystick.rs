@@ -7,33 +7,222 @@
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, November 2020
 
 use crate::Console;
+use crate::Port;
 use crate::Controller;
+use crate::action::Action;
+use crate::state::{StateWriter, StateError};
 
-/// Brief description.
+/// The directions of a joystick.
 ///
-/// Long description.
+/// A joystick has 4 switches, one for each direction. Diagonals are obtained by
+/// pressing two of them at the same time.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Up, Down, Left, Right
+}
+
+/// The standard joystick controller.
+///
+/// The joystick has a stick with 4 directions and a single button. Directions
+/// are read by the game from the pins of the port A (SWCHA) and the button is
+/// read from the input port INPT4 (left controller) or INPT5 (right
+/// controller). All of them are low when they're pressed.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Direction, Joystick, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut joystick = Joystick::new();
+/// joystick.press_direction(Direction::Left);
+/// joystick.press_button();
+///
+/// console.plug_controller(Port::Left, Box::new(joystick));
+/// ```
 ///
 pub struct Joystick {
-    console: Option<*mut Console>
+    port: Port,
+    directions: [bool; 4],
+    button: bool
 }
 
 impl Joystick {
+    /// Create a joystick.
+    ///
+    /// The joystick is created with its stick centered and its button
+    /// released.
+    ///
+    pub fn new() -> Joystick {
+        Joystick {
+            port: Port::Left,
+            directions: [false; 4],
+            button: false
+        }
+    }
+
+    /// Press a direction.
+    ///
+    /// This function pushes the stick in the given direction.
+    ///
+    pub fn press_direction(&mut self, direction: Direction) {
+        self.directions[direction as usize] = true;
+    }
+
+    /// Release a direction.
+    ///
+    /// This function releases the stick from the given direction.
+    ///
+    pub fn release_direction(&mut self, direction: Direction) {
+        self.directions[direction as usize] = false;
+    }
+
+    /// Check whether a direction is pressed.
+    ///
+    /// This function returns whether the stick is pushed in the given
+    /// direction.
+    ///
+    pub fn is_direction_pressed(&self, direction: Direction) -> bool {
+        self.directions[direction as usize]
+    }
+
+    /// Press the button.
+    ///
+    /// This function presses the button of the joystick.
+    ///
+    pub fn press_button(&mut self) {
+        self.button = true;
+    }
+
+    /// Release the button.
+    ///
+    /// This function releases the button of the joystick.
+    ///
+    pub fn release_button(&mut self) {
+        self.button = false;
+    }
+
+    /// Check whether the button is pressed.
+    ///
+    /// This function returns whether the button of the joystick is pressed.
+    ///
+    pub fn is_button_pressed(&self) -> bool {
+        self.button
+    }
+
+    /// Perform an action.
+    ///
+    /// This function pushes the stick in the directions of the action and
+    /// presses the button if it's a fire action; the other directions and
+    /// the button are released.
+    ///
+    pub fn perform_action(&mut self, action: Action) {
+        self.directions = [false; 4];
+        for direction in action.directions() {
+            self.directions[*direction as usize] = true;
+        }
+        self.button = action.is_fire();
+    }
+
+    /// Slot the joystick is plugged in.
+    pub(crate) fn port(&self) -> Port {
+        self.port
+    }
+}
+
+impl Default for Joystick {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Controller for Joystick {
-    fn plugged(&mut self, console: *mut Console) {
-        self.console = Some(console);
+    fn plugged(&mut self, port: Port) {
+        self.port = port;
     }
 
     fn unplugged(&mut self) {
-        self.console = None;
+    }
+
+    fn apply(&mut self, console: &mut Console) {
+        // The left controller uses the 4 most significant pins of port A and
+        // the right controller uses the 4 less significant ones.
+        let (first_pin, input) = match self.port {
+            Port::Left => (4, 4),
+            Port::Right => (0, 5)
+        };
+
+        console.drive_port_a_pin(first_pin,     !self.directions[Direction::Up as usize]);
+        console.drive_port_a_pin(first_pin + 1, !self.directions[Direction::Down as usize]);
+        console.drive_port_a_pin(first_pin + 2, !self.directions[Direction::Left as usize]);
+        console.drive_port_a_pin(first_pin + 3, !self.directions[Direction::Right as usize]);
+
+        console.set_input_level(input, !self.button);
+    }
+
+    fn save_state(&self) -> StateWriter {
+        let mut state = StateWriter::new();
+        for direction in self.directions {
+            state.write_bool(direction);
+        }
+        state.write_bool(self.button);
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        for direction in self.directions.iter_mut() {
+            *direction = reader.read_bool()?;
+        }
+        self.button = reader.read_bool()?;
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::location::{SWCHA, INPT4, INPT5};
 
     #[test]
     fn test_joystick() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        console.plug_controller(Port::Left, Box::new(Joystick::new()));
+        assert_eq!(console.memory(SWCHA), 0b1111_0000);
+        assert_eq!(console.memory(INPT4), 0b1000_0000);
+
+        let mut joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        joystick.press_direction(Direction::Up);
+        joystick.press_direction(Direction::Right);
+        joystick.press_button();
+        drop(joystick);
+        assert_eq!(console.memory(SWCHA), 0b0110_0000);
+        assert_eq!(console.memory(INPT4), 0b0000_0000);
+
+        let mut joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        joystick.release_direction(Direction::Up);
+        joystick.release_button();
+        drop(joystick);
+        assert_eq!(console.memory(SWCHA), 0b0111_0000);
+        assert_eq!(console.memory(INPT4), 0b1000_0000);
+
+        let mut joystick = Joystick::new();
+        joystick.press_direction(Direction::Down);
+        joystick.press_button();
+        console.plug_controller(Port::Right, Box::new(joystick));
+        assert_eq!(console.memory(SWCHA), 0b0111_1101);
+        assert_eq!(console.memory(INPT5), 0b0000_0000);
+
+        // An action replaces the state of the joystick entirely.
+        console.controller_mut::<Joystick>(Port::Right).unwrap().perform_action(Action::UpLeftFire);
+        assert_eq!(console.memory(SWCHA), 0b0111_1010);
+        assert_eq!(console.memory(INPT5), 0b0000_0000);
+
+        console.controller_mut::<Joystick>(Port::Right).unwrap().perform_action(Action::Noop);
+        assert_eq!(console.memory(SWCHA), 0b0111_1111);
+        assert_eq!(console.memory(INPT5), 0b1000_0000);
     }
 }
@@ -0,0 +1,285 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::Console;
+use crate::Port;
+use crate::Controller;
+use crate::state::{StateWriter, StateError};
+
+// Number of frames a track lasts when no playback callback tells otherwise.
+const DEFAULT_TRACK_FRAMES: u32 = 60 * 10;
+
+/// The Kid Vid Voice Module.
+///
+/// The Kid Vid is a cassette player plugged into the right port of the
+/// console. The game starts the tape by raising the pin 1 (bit 0 of SWCHA),
+/// then the module plays the next audio track of the cassette and tells the
+/// game it's busy through the pin 4 (bit 3 of SWCHA) until the track is over.
+/// When it's plugged into the left port, the same pins of that port are used
+/// instead (bit 4 and 7 of SWCHA).
+///
+/// The audio itself isn't emulated; instead, a playback callback is called
+/// every time a track starts so the frontend can play the matching recording.
+/// The callback returns the length of the track (in frames) which is used to
+/// signal the end of the track to the game.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, KidVid, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn play_recording(_tape: u8, _track: u8) -> u32 { 600 }
+/// let mut kidvid = KidVid::new();
+/// kidvid.insert_tape(1);
+/// kidvid.set_playback_callback(|tape, track| play_recording(tape, track));
+///
+/// console.plug_controller(Port::Right, Box::new(kidvid));
+/// ```
+///
+pub struct KidVid {
+    port: Port,
+    tape: Option<u8>,
+    track: u8,
+    is_tape_started: bool,
+    remaining_frames: u32,
+    playback_callback: Option<Box<dyn FnMut(u8, u8) -> u32 + Send>>
+}
+
+impl KidVid {
+    /// Create a Kid Vid Voice Module.
+    ///
+    /// The module is created without a cassette inserted.
+    ///
+    pub fn new() -> KidVid {
+        KidVid {
+            port: Port::Right,
+            tape: None,
+            track: 0,
+            is_tape_started: false,
+            remaining_frames: 0,
+            playback_callback: None
+        }
+    }
+
+    /// Insert a cassette.
+    ///
+    /// This function inserts the tape 1, 2 or 3 of the game cassettes; it's
+    /// rewound to the first track.
+    ///
+    pub fn insert_tape(&mut self, tape: u8) {
+        assert!((1..=3).contains(&tape), "tape must be 1, 2 or 3");
+
+        self.tape = Some(tape);
+        self.track = 0;
+        self.remaining_frames = 0;
+    }
+
+    /// Eject the cassette.
+    ///
+    /// This function ejects the cassette, stopping the playback if it was
+    /// playing.
+    ///
+    pub fn eject_tape(&mut self) {
+        self.tape = None;
+        self.track = 0;
+        self.remaining_frames = 0;
+    }
+
+    /// Inserted cassette.
+    ///
+    /// This function returns the inserted tape, if any.
+    ///
+    pub fn tape(&self) -> Option<u8> {
+        self.tape
+    }
+
+    /// Set the playback callback.
+    ///
+    /// This function sets the callback that is called with the tape and the
+    /// track number (starting from 1) every time the game starts a track. It
+    /// must return the length of the track in frames.
+    ///
+    pub fn set_playback_callback<F: FnMut(u8, u8) -> u32 + Send + 'static>(&mut self, callback: F) {
+        self.playback_callback = Some(Box::new(callback));
+    }
+
+    /// Check whether a track is playing.
+    ///
+    /// This function returns whether a track is being played.
+    ///
+    pub fn is_playing(&self) -> bool {
+        self.remaining_frames > 0
+    }
+}
+
+impl Default for KidVid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controller for KidVid {
+    fn plugged(&mut self, port: Port) {
+        self.port = port;
+    }
+
+    fn unplugged(&mut self) {
+    }
+
+    fn update(&mut self, console: &mut Console) {
+        if self.remaining_frames > 0 {
+            self.remaining_frames -= 1;
+        }
+
+        // The pins of the left port are the upper nibble of SWCHA.
+        let first_pin = match self.port {
+            Port::Left => 4,
+            Port::Right => 0
+        };
+
+        // The tape starts on the rising edge of the pin 1.
+        let is_tape_started = console.port_a_pin(first_pin);
+        if is_tape_started && !self.is_tape_started && !self.is_playing() {
+            if let Some(tape) = self.tape {
+                // The cassettes have far fewer tracks; games that keep on
+                // starting the tape replay the last one.
+                self.track = self.track.saturating_add(1);
+                self.remaining_frames = match &mut self.playback_callback {
+                    Some(callback) => callback(tape, self.track),
+                    None => DEFAULT_TRACK_FRAMES
+                };
+            }
+        }
+        self.is_tape_started = is_tape_started;
+
+        // Tell the game whether the module is busy playing a track.
+        console.drive_port_a_pin(first_pin + 3, self.is_playing());
+    }
+
+    fn save_state(&self) -> StateWriter {
+        // The tape is saved as 0 when there is no cassette.
+        let mut state = StateWriter::new();
+        state.write_u8(self.tape.unwrap_or(0));
+        state.write_u8(self.track);
+        state.write_bool(self.is_tape_started);
+        state.write_u32(self.remaining_frames);
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        self.tape = match reader.read_u8()? {
+            0 => None,
+            tape @ 1..=3 => Some(tape),
+            _ => return Err(StateError::new("tape is out of range"))
+        };
+        self.track = reader.read_u8()?;
+        self.is_tape_started = reader.read_bool()?;
+        self.remaining_frames = reader.read_u32()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::cartridge::Cartridge;
+    use crate::location::{SWCHA, SWACNT};
+
+    #[test]
+    fn test_kidvid() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        let tracks = Arc::new(Mutex::new(Vec::new()));
+        let tracks_copy = tracks.clone();
+
+        let mut kidvid = KidVid::new();
+        kidvid.insert_tape(2);
+        kidvid.set_playback_callback(move |tape, track| {
+            tracks_copy.lock().unwrap().push((tape, track));
+            2
+        });
+
+        // The game drives the pin 1 and reads the pin 4.
+        *console.memory_mut(SWACNT) = 0b0000_0001;
+
+        kidvid.update(&mut console);
+        assert!(!kidvid.is_playing());
+
+        *console.memory_mut(SWCHA) |= 0b0000_0001;
+        kidvid.update(&mut console);
+        assert!(kidvid.is_playing());
+        assert!(console.port_a_pin(3));
+        assert_eq!(*tracks.lock().unwrap(), vec![(2, 1)]);
+
+        // Holding the pin doesn't restart the track.
+        kidvid.update(&mut console);
+        kidvid.update(&mut console);
+        assert!(!kidvid.is_playing());
+        assert!(!console.port_a_pin(3));
+
+        *console.memory_mut(SWCHA) &= 0b1111_1110;
+        kidvid.update(&mut console);
+        *console.memory_mut(SWCHA) |= 0b0000_0001;
+        kidvid.update(&mut console);
+        assert_eq!(*tracks.lock().unwrap(), vec![(2, 1), (2, 2)]);
+
+        // The track number doesn't wrap around.
+        kidvid.track = u8::MAX;
+        kidvid.update(&mut console);
+        kidvid.update(&mut console);
+        *console.memory_mut(SWCHA) &= 0b1111_1110;
+        kidvid.update(&mut console);
+        *console.memory_mut(SWCHA) |= 0b0000_0001;
+        kidvid.update(&mut console);
+        assert_eq!(tracks.lock().unwrap().last(), Some(&(2, u8::MAX)));
+    }
+
+    #[test]
+    fn test_kidvid_state() {
+        let mut kidvid = KidVid::new();
+        kidvid.insert_tape(3);
+
+        let mut other_kidvid = KidVid::new();
+        other_kidvid.load_state(&kidvid.save_state()).unwrap();
+        assert_eq!(other_kidvid.tape(), Some(3));
+
+        // Only the tapes 1, 2 and 3 exist.
+        let mut state = StateWriter::new();
+        state.write_u8(7);
+        state.write_u8(0);
+        state.write_bool(false);
+        state.write_u32(0);
+        assert!(other_kidvid.load_state(&state).is_err());
+    }
+
+    #[test]
+    fn test_kidvid_left_port() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        let mut kidvid = KidVid::new();
+        kidvid.insert_tape(1);
+        kidvid.plugged(Port::Left);
+
+        // The pins of the left port are used, and those of the right port
+        // are left alone.
+        *console.memory_mut(SWACNT) = 0b0001_0001;
+
+        *console.memory_mut(SWCHA) |= 0b0000_0001;
+        kidvid.update(&mut console);
+        assert!(!kidvid.is_playing());
+
+        *console.memory_mut(SWCHA) |= 0b0001_0000;
+        kidvid.update(&mut console);
+        assert!(kidvid.is_playing());
+        assert!(console.port_a_pin(7));
+        assert!(!console.port_a_pin(3));
+    }
+}
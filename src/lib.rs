@@ -23,79 +23,261 @@
 //!
 //! # Get started
 //!
-//! Naming
-//!
 //! It was hard to dissect the different components (CPU, RAM, PIA, TIA, etc.)
 //! without making the architecture too heavy and unnecesiryly complicated, so
 //! instead, it just revolves around the `Console` structure which is the very
 //! main component.
 //!
-//! ```
-//! let cartridge = Cartridge::open("breakout.bin");
-//! let console = Console::new(cartridge);
+#![cfg_attr(feature = "test-rom", doc = "```")]
+#![cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+//! use std::time::Duration;
+//! use atari_2600::prelude::*;
+//! use atari_2600::TEST_ROM;
+//! # fn display_frame(_frame: &Frame) {}
+//! # fn play_samples(_samples: &[f32]) {}
+//! # let elapsed_time = Duration::from_millis(16);
+//!
+//! let cartridge = Cartridge::new(TEST_ROM.to_vec());
+//! let mut console = Console::new(cartridge);
+//! console.plug_controller(Port::Left, Box::new(Joystick::new()));
 //!
 //! console.update(elapsed_time);
 //!
-//! display_frame(console.video.output);
-//! play_samples(console.audio.output);
+//! display_frame(&console.frame());
+//! play_samples(&console.take_audio_samples());
 //!
-//! console.controllers[0].press_button();
+//! console.controller_mut::<Joystick>(Port::Left).unwrap().press_button();
 //! ```
 //!
-//! It represents a virtual gaming console with .
-//! Cartridge can't be removed during the simulation. Two controllers are always
-//! plugged in, and TV set is NTSC and plugged too.
+//! It represents a virtual gaming console with its cartridge, its TV set and
+//! its two controller ports. The cartridge can't be removed during the
+//! simulation; to change it, another console is created. The controllers are
+//! plugged and unplugged at any time (see `Console::plug_controller()`), and
+//! the TV set is NTSC unless told otherwise (see `Console::set_tv_standard()`).
 //!
-//! ```
-//! ```
+//! Normally cartridges are created from the ROM binary file (see
+//! `Cartridge::from_file()`), but they can also be created from the bytes of a
+//! ROM (see `Cartridge::new()`), like the test ROM embedded in the crate (see
+//! `TEST_ROM`) the example above runs. Each call to `Console::update()`
+//! advances the emulation by the elapsed time; the last completed frame is then
+//! returned by `Console::frame()` and the audio produced in the meantime by
+//! `Console::take_audio_samples()`. The `Emulator` structure wraps a console
+//! with the rest of what a frontend needs (pause, rewind, save slots, etc.).
+//!
+//! # Cargo features
 //!
-//! Normally cartridges are created from the ROM binary file.
+//! The console and its controllers are always compiled; the rest is split in
+//! features, so the programs embedding the emulator (bots, test harnesses,
+//! etc.) leave out what they don't use. The crate has no dependencies.
 //!
-//! represents the hardware of the
-//! console itself. See it as
+//! - `frontend` (default): the `Emulator` and what a frontend builds on (the
+//!   pause menu, the ROM browser, the pacers, the video and audio sinks, etc.).
+//!   It's required by the executable.
+//! - `debugger` (default): the listings, the dumps of the save states, the
+//!   timer probe, the traces of the executed instructions, the crash reports
+//!   and the statistics of the opcodes.
+//! - `test-rom` (default): the test ROM (see `TEST_ROM`), which the examples of
+//!   the documentation run (they're ignored without it).
+//! - `tui`: a frontend drawing the frames in the terminal.
+//! - `remote`: a server to control the emulator over HTTP.
+//!
+//! The analysis of the ROMs isn't optional, since the console builder and the
+//! ROM information rely on it. There are no features for an audio backend, a
+//! scripting language, serialization with serde or WebAssembly; they would
+//! bring dependencies, and the frontends provide them instead (the samples
+//! and the save states are plain data).
+//!
+//! ```toml
+//! [dependencies]
+//! atari-2600 = { version = "0.1", default-features = false }
+//! ```
 //!
 //! # More information
 //!
 //! For specifications and more information about the gaming console, look at
 //! the following.
 //!
-//! - https://problemkaputt.de/2k6specs.htm
-//! - foo
-//! - bar
-//!
-//! Useful documents were also added directly to the source repository.
+//! - <https://problemkaputt.de/2k6specs.htm>, the hardware of the console
+//! - <https://www.alienbill.com/2600/101/docs/stella.html>, the Stella
+//!   Programmer's Guide
+//! - <https://www.masswerk.at/6502/6502_instruction_set.html>, the
+//!   instruction set of the processor
 //!
+
+// The console and its components are plain safe Rust; the controllers are
+// handed the console they drive instead of keeping a pointer to it.
+#![forbid(unsafe_code)]
+
 pub(crate) mod location;
 pub mod addressing_mode;
 pub mod instruction;
 pub(crate) mod color;
+pub(crate) mod palette;
 pub(crate) mod playfield;
 pub(crate) mod sprite;
 pub(crate) mod missile;
 pub(crate) mod ball;
 pub(crate) mod utils;
+pub mod overlay;
+pub mod prelude;
 
 mod cartridge;
+mod patch;
 mod controller;
 mod joystick;
+mod action;
+mod booster_grip;
 mod paddle;
 mod keypad;
 mod steering;
 mod lightgun;
 mod trackball;
+mod kidvid;
+mod quadtari;
+mod port_pins;
+mod frame;
+mod scaler;
+mod texture;
+#[cfg(feature = "frontend")]
+mod sink;
+mod summary;
+mod telemetry;
+mod watch;
+#[cfg(feature = "frontend")]
+mod pacer;
+#[cfg(feature = "frontend")]
+mod av_sync;
+mod state;
+mod replay;
+mod determinism;
+mod png;
+mod diff;
+mod clock;
+mod tia_state;
+mod tia_log;
+#[cfg(feature = "debugger")]
+mod listing;
+#[cfg(feature = "debugger")]
+mod trace;
+#[cfg(feature = "debugger")]
+mod crash;
+#[cfg(feature = "debugger")]
+mod opcode_stats;
+#[cfg(feature = "debugger")]
+mod dump;
+mod input_timing;
+mod rng;
+mod event;
+mod bus;
+mod cpu;
+mod assembler;
+mod pia;
+mod tia;
+#[cfg(feature = "debugger")]
+mod timer_probe;
+mod analysis;
+mod rom_info;
 mod video;
 mod audio;
 mod console;
+mod database;
+mod reward;
+mod builder;
+mod rom_builder;
+#[cfg(feature = "test-rom")]
+mod test_rom;
+#[cfg(test)]
+mod fixtures;
+#[cfg(feature = "frontend")]
+mod menu;
+#[cfg(feature = "frontend")]
+mod browser;
+#[cfg(feature = "frontend")]
+mod rewind;
+#[cfg(feature = "frontend")]
 mod emulator;
+#[cfg(feature = "frontend")]
+mod session;
+#[cfg(feature = "frontend")]
+mod pipeline;
+#[cfg(feature = "tui")]
+mod terminal;
+#[cfg(feature = "remote")]
+mod remote;
 
-pub use cartridge::Cartridge;
-pub use controller::Controller;
-pub use joystick::Joystick;
-pub use paddle::Paddle;
+pub use cartridge::{Cartridge, MapperFault};
+pub use patch::{Patch, PatchFormat, PatchError};
+pub use controller::{Controller, ControllerMut};
+pub use joystick::{Joystick, Direction};
+pub use action::Action;
+pub use booster_grip::BoosterGrip;
+pub use paddle::{Paddle, PaddleCalibration};
 pub use keypad::Keypad;
 pub use steering::Steering;
 pub use lightgun::Lightgun;
-pub use trackball::Trackball;
-pub use console::{TvType, Player, Difficulty};
+pub use trackball::{Trackball, TrackballMode};
+pub use kidvid::KidVid;
+pub use quadtari::QuadTari;
+pub use port_pins::PortPins;
+pub use console::{TvType, TvStandard, Player, Port, Difficulty, ConsoleVariant, TiaRevision, UnknownRegisterPolicy};
 pub use console::Console;
-pub use emulator::Emulator;
\ No newline at end of file
+pub use builder::ConsoleBuilder;
+pub use rom_builder::RomBuilder;
+#[cfg(feature = "test-rom")]
+pub use test_rom::TEST_ROM;
+pub use database::{Database, DatabaseError, RomEntry, ControllerKind};
+pub use reward::{RewardExtractor, RewardTracker, RewardDatabase, RamRewards, ScoreLocation, ScoreEncoding, LivesLocation};
+pub use frame::{Frame, Frames, FramePooling, Orientation, Rotation, Flip, Region, TextCharset};
+pub use scaler::{Scaler, ScaleFilter, Viewport};
+pub use texture::{Texture, PixelFormat, write_frame};
+#[cfg(feature = "frontend")]
+pub use sink::{VideoSink, AudioSink, NullSink, WindowSink, PngSink, Y4mSink};
+pub use summary::{UpdateSummary, ScanlineWarning, UnknownRegisterWrite};
+pub use telemetry::{Telemetry, Timecode};
+pub use watch::RamChange;
+#[cfg(feature = "frontend")]
+pub use pacer::{RealTimePacer, Pacing};
+#[cfg(feature = "frontend")]
+pub use av_sync::AvSyncMonitor;
+pub use state::{SaveState, StateDiff, Thumbnail, StateWriter, StateReader, StateVersion, StateError};
+pub use replay::Replay;
+pub use determinism::{FrameChecksum, DeterminismError, compare_checksums};
+pub use diff::{DiffError, compare_runs, screenshot_path};
+pub use clock::{Clock, SystemClock, ManualClock};
+pub use tia_state::{TiaState, ObjectState, PixelLayers};
+pub use tia_log::{TiaLog, TiaWrite, TiaLogError};
+#[cfg(feature = "debugger")]
+pub use listing::{Listing, SourceLine};
+#[cfg(feature = "debugger")]
+pub use trace::{TraceFilter, Register, Flag};
+#[cfg(feature = "debugger")]
+pub use crash::{CrashReport, CrashCause};
+#[cfg(feature = "debugger")]
+pub use opcode_stats::{OpcodeStats, OpcodeCoverage};
+#[cfg(feature = "debugger")]
+pub use dump::{StateDump, DumpDifference};
+pub use input_timing::{InputTiming, InputRead};
+pub use rng::Rng;
+pub use event::{Event, EventBus, SubscriptionId};
+pub use bus::Bus;
+pub use cpu::{Cpu6507, UnknownOpcode};
+pub use assembler::{assemble, assemble_at, AssemblerError};
+#[cfg(feature = "debugger")]
+pub use timer_probe::{TimerProbe, TimerOutput};
+pub use analysis::{RomAnalysis, BankAnalysis, BankSwitching, analyze_rom};
+pub use rom_info::{RomInfo, rom_info};
+#[cfg(feature = "frontend")]
+pub use emulator::{Emulator, ReloadMode, RewindAudio};
+#[cfg(feature = "frontend")]
+pub use session::{Session, SessionError, RecentRom, GameConfig, RECENT_ROMS};
+#[cfg(feature = "frontend")]
+pub use menu::{PauseMenu, MenuInput, MenuAction, STATE_SLOTS};
+#[cfg(feature = "frontend")]
+pub use browser::{RomBrowser, BrowserEntry, ROM_EXTENSIONS};
+#[cfg(feature = "frontend")]
+pub use pipeline::{Pipeline, FrameQueue, AudioQueue};
+#[cfg(feature = "tui")]
+pub use terminal::{Terminal, TerminalKey, parse_keys, render_frame, run_in_terminal};
+#[cfg(feature = "remote")]
+pub use remote::{RemoteServer, RemoteResponse, handle_request};
+pub use audio::GainEnvelope;
\ No newline at end of file
@@ -0,0 +1,346 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::io;
+use std::fs;
+use std::path::Path;
+use std::collections::HashMap;
+
+use crate::console::Console;
+
+// The maximum number of instructions executed to reach the next source line
+// before giving up (code that isn't in the listing, an endless loop, etc.);
+// it's about a frame worth of instructions.
+const MAX_STEP_INSTRUCTIONS: usize = 20_000;
+
+/// A line of assembly source.
+///
+/// The line is identified by its file and its line number in the file (as
+/// reported by DASM), and it's located by its bank and its address. The text
+/// is the source line as it appears in the listing.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: u32,
+    pub bank: usize,
+    pub address: u16,
+    pub text: String
+}
+
+/// A DASM listing.
+///
+/// The listing (the .lst file generated by DASM with the `-l` option) maps the
+/// bytes of the ROM back to the lines of the assembly source; it lets a
+/// debugger display and step through the original source rather than the raw
+/// disassembly. The symbols (the .sym file generated with the `-s` option) can
+/// be added to it to look up labels.
+///
+/// The bank of a line is deduced from the origin of its segment when the code
+/// is relocated (`ORG $1000` followed by `RORG $F000` is the second bank),
+/// which is how bank-switched games are usually assembled.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Listing, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let listing = Listing::from_file("game.lst")?;
+/// while let Some(line) = listing.step(&mut console) {
+///     println!("{}:{} {}", line.file, line.line, line.text);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+#[derive(Clone, Default, Debug)]
+pub struct Listing {
+    lines: Vec<SourceLine>,
+    addresses: HashMap<(usize, u16), usize>,
+    symbols: HashMap<String, u16>
+}
+
+fn parse_value(text: &str) -> Option<u32> {
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix('$') {
+        u32::from_str_radix(hex, 16).ok()
+    }
+    else if let Some(binary) = text.strip_prefix('%') {
+        u32::from_str_radix(binary, 2).ok()
+    }
+    else {
+        text.parse().ok()
+    }
+}
+
+fn is_byte(token: &str) -> bool {
+    token.len() == 2 && token.chars().all(|character| character.is_ascii_hexdigit())
+}
+
+impl Listing {
+    /// Parse a listing.
+    ///
+    /// This function parses the content of a DASM listing file; the lines it
+    /// doesn't understand are ignored.
+    ///
+    pub fn parse(text: &str) -> Listing {
+        let mut listing = Listing::default();
+
+        let mut file = String::new();
+        let mut origin = 0;
+        let mut is_relocated = false;
+
+        for line in text.lines() {
+            // The header of each included file, or the end of an included file.
+            //
+            // ------- FILE game.asm LEVEL 1 PASS 2
+            if let Some(header) = line.strip_prefix("------- FILE ") {
+                file = header.split_whitespace().next().unwrap_or_default().to_string();
+                continue;
+            }
+
+            // Each line starts with the line number in its file, followed by
+            // the address (prefixed with 'U' in uninitialized segments), the
+            // bytes that were generated, then the source line.
+            //
+            //      4  f000		       a9 00		      lda	#0
+            let line = line.trim_start();
+            let digits = line.find(|character: char| !character.is_ascii_digit()).unwrap_or(line.len());
+            let line_number = match line[..digits].parse() {
+                Ok(line_number) => line_number,
+                Err(_) => continue
+            };
+
+            let rest = line[digits..].trim_start_matches(' ');
+            if rest.starts_with('U') {
+                continue;
+            }
+
+            let address = match rest.get(..4).map(|address| u16::from_str_radix(address, 16)) {
+                Some(Ok(address)) => address,
+                _ => continue
+            };
+
+            // The value of some lines isn't known ('????').
+            let rest = rest[4..].trim_start_matches(" ????");
+
+            // The bytes are in the first field that isn't empty, and the
+            // source line follows.
+            let mut fields = rest.split('\t').skip_while(|field| field.trim().is_empty()).peekable();
+            let bytes = match fields.peek() {
+                Some(field) if field.split_whitespace().all(is_byte) => {
+                    let count = field.split_whitespace().count();
+                    fields.next();
+                    count
+                },
+                _ => 0
+            };
+            let text = fields.collect::<Vec<_>>().join("\t").trim().to_string();
+
+            // Keep track of the origin to deduce the bank.
+            let mut words = text.split_whitespace();
+            let (first, second) = (words.next().unwrap_or_default(), words.next().unwrap_or_default());
+            for (directive, value) in [(first, second), (second, words.next().unwrap_or_default())] {
+                match directive.trim_start_matches('.').to_ascii_lowercase().as_str() {
+                    "org" => {
+                        origin = parse_value(value).unwrap_or(origin);
+                        is_relocated = false;
+                    },
+                    "rorg" => is_relocated = true,
+                    _ => continue
+                }
+                break;
+            }
+
+            let bank = match is_relocated {
+                true  => (origin / 4096) as usize,
+                false => 0
+            };
+
+            let source_line = SourceLine {
+                file: file.clone(),
+                line: line_number,
+                bank,
+                address,
+                text
+            };
+
+            // Only the lines that generated bytes are located; a label shares
+            // the address of the instruction that follows it.
+            if bytes > 0 {
+                listing.addresses.entry((bank, address & 0x_1FFF)).or_insert(listing.lines.len());
+            }
+            listing.lines.push(source_line);
+        }
+
+        listing
+    }
+
+    /// Load a listing from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Listing> {
+        Ok(Listing::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Add symbols to the listing.
+    ///
+    /// This function parses the content of a DASM symbol file and adds its
+    /// symbols to the listing; the lines it doesn't understand are ignored.
+    ///
+    /// ```text
+    /// --- Symbol List (sorted by symbol)
+    /// Reset                    f000              (R )
+    /// --- End of Symbol List.
+    /// ```
+    ///
+    pub fn parse_symbols(&mut self, text: &str) {
+        for line in text.lines().filter(|line| !line.starts_with("---")) {
+            let mut fields = line.split_whitespace();
+            if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+                if let Ok(value) = u16::from_str_radix(value, 16) {
+                    self.symbols.insert(name.to_string(), value);
+                }
+            }
+        }
+    }
+
+    /// Add symbols to the listing from a file.
+    pub fn load_symbols<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.parse_symbols(&fs::read_to_string(path)?);
+        Ok(())
+    }
+
+    /// Lines of the listing.
+    pub fn lines(&self) -> &[SourceLine] {
+        &self.lines
+    }
+
+    /// Source line of an address.
+    ///
+    /// This function returns the source line that generated the instruction
+    /// (or the data) at the given address of the given bank, if any. The
+    /// mirrors of the cartridge are taken into account.
+    ///
+    pub fn lookup(&self, bank: usize, address: u16) -> Option<&SourceLine> {
+        self.addresses
+            .get(&(bank, address & 0x_1FFF))
+            .map(|index| &self.lines[*index])
+    }
+
+    /// Value of a symbol.
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Source line the console is at.
+    ///
+    /// This function returns the source line of the instruction the console is
+    /// about to execute, if it's in the listing.
+    ///
+    pub fn current_line(&self, console: &Console) -> Option<&SourceLine> {
+        self.lookup(console.current_bank(), console.program_counter())
+    }
+
+    /// Step to the next source line.
+    ///
+    /// This function executes instructions until the console reaches the
+    /// beginning of another source line of the listing (instructions whose
+    /// source isn't in the listing are stepped over), and returns that line.
+    /// It gives up after about a frame worth of instructions and returns
+    /// `None`.
+    ///
+    pub fn step(&self, console: &mut Console) -> Option<&SourceLine> {
+        let start = self.current_line(console).map(|line| (line.file.clone(), line.line));
+
+        for _ in 0..MAX_STEP_INSTRUCTIONS {
+            console.step_instruction();
+
+            if let Some(line) = self.current_line(console) {
+                if start.as_ref() != Some(&(line.file.clone(), line.line)) {
+                    return Some(line);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::fixtures;
+
+    const LISTING: &str = "\
+------- FILE game.asm LEVEL 1 PASS 2
+      1  0000 ????\t\t\t\t      processor\t6502
+      2  0080\t\t\t\t      seg.u\tvars
+      3  U0080\t\t\t\t      org\t$80
+      4  U0080\t\t   00\t    counter    ds\t1
+      5  0000 ????\t\t\t\t      seg\tcode
+      6  0000\t\t\t\t      org\t$0000
+      7  f000\t\t\t\t      rorg\t$F000
+      8  f000\t\t\t    Reset
+      9  f000\t\t       e6 80\t    Loop       inc\tcounter
+     10  f002\t\t       4c 00 f0\t      jmp\tLoop
+     11  f005\t\t\t\t      org\t$1000
+     12  f000\t\t\t\t      rorg\t$F000
+     13  f000\t\t       ea\t\t      nop
+------- FILE game.asm LEVEL 1 PASS 2
+";
+
+    #[test]
+    fn test_listing() {
+        let listing = Listing::parse(LISTING);
+        assert_eq!(listing.lines().len(), 11);
+
+        let line = listing.lookup(0, 0x_F000).unwrap();
+        assert_eq!(line.file, "game.asm");
+        assert_eq!(line.line, 9);
+        assert_eq!(line.text, "Loop       inc\tcounter");
+
+        // The mirrors of the cartridge are the same address.
+        assert_eq!(listing.lookup(0, 0x_1002).unwrap().line, 10);
+        assert!(listing.lookup(0, 0x_F001).is_none());
+
+        // The second bank is relocated at the same address.
+        assert_eq!(listing.lookup(1, 0x_F000).unwrap().line, 13);
+        assert_eq!(listing.lookup(1, 0x_F000).unwrap().text, "nop");
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut listing = Listing::parse(LISTING);
+        listing.parse_symbols("\
+--- Symbol List (sorted by symbol)
+Loop                     f000              (R )
+counter                  0080              (R )
+--- End of Symbol List.
+");
+
+        assert_eq!(listing.symbol("Loop"), Some(0x_F000));
+        assert_eq!(listing.symbol("counter"), Some(0x_0080));
+        assert_eq!(listing.symbol("Reset"), None);
+    }
+
+    #[test]
+    fn test_step() {
+        let listing = Listing::parse(LISTING);
+        let mut console = Console::new(Cartridge::new(fixtures::counter_rom(0x_80)));
+        assert_eq!(listing.current_line(&console).unwrap().line, 9);
+
+        assert_eq!(listing.step(&mut console).unwrap().line, 10);
+        assert_eq!(console.memory(0x_80), 1);
+        assert_eq!(listing.step(&mut console).unwrap().line, 9);
+        assert_eq!(listing.step(&mut console).unwrap().line, 10);
+        assert_eq!(console.memory(0x_80), 2);
+
+        // There is nothing to step to if the code isn't in the listing.
+        let listing = Listing::parse("");
+        assert!(listing.step(&mut console).is_none());
+    }
+}
@@ -84,3 +84,81 @@ pub(crate) const TIM1T  : u16 = 0x_0294; //  11111111  set 1 clock interval (838
 pub(crate) const TIM8T  : u16 = 0x_0295; //  11111111  set 8 clock interval (6.7 usec/interval)
 pub(crate) const TIM64T : u16 = 0x_0296; //  11111111  set 64 clock interval (53.6 usec/interval)
 pub(crate) const T1024T : u16 = 0x_0297; //  11111111  set 1024 clock interval (858.2 usec/interval)
+
+// The labels of the locations, for the debugging output.
+const LABELS: [(u16, &str); 69] = [
+    (VSYNC, "VSYNC"),
+    (VBLANK, "VBLANK"),
+    (WSYNC, "WSYNC"),
+    (RSYNC, "RSYNC"),
+    (NUSIZ0, "NUSIZ0"),
+    (NUSIZ1, "NUSIZ1"),
+    (COLUP0, "COLUP0"),
+    (COLUP1, "COLUP1"),
+    (COLUPF, "COLUPF"),
+    (COLUBK, "COLUBK"),
+    (CTRLPF, "CTRLPF"),
+    (REFP0, "REFP0"),
+    (REFP1, "REFP1"),
+    (PF0, "PF0"),
+    (PF1, "PF1"),
+    (PF2, "PF2"),
+    (RESP0, "RESP0"),
+    (RESP1, "RESP1"),
+    (RESM0, "RESM0"),
+    (RESM1, "RESM1"),
+    (RESBL, "RESBL"),
+    (AUDC0, "AUDC0"),
+    (AUDC1, "AUDC1"),
+    (AUDF0, "AUDF0"),
+    (AUDF1, "AUDF1"),
+    (AUDV0, "AUDV0"),
+    (AUDV1, "AUDV1"),
+    (GRP0, "GRP0"),
+    (GRP1, "GRP1"),
+    (ENAM0, "ENAM0"),
+    (ENAM1, "ENAM1"),
+    (ENABL, "ENABL"),
+    (HMP0, "HMP0"),
+    (HMP1, "HMP1"),
+    (HMM0, "HMM0"),
+    (HMM1, "HMM1"),
+    (HMBL, "HMBL"),
+    (VDELP0, "VDELP0"),
+    (VDELP1, "VDELP1"),
+    (VDELBL, "VDELBL"),
+    (RESMP0, "RESMP0"),
+    (RESMP1, "RESMP1"),
+    (HMOVE, "HMOVE"),
+    (HMCLR, "HMCLR"),
+    (CXCLR, "CXCLR"),
+    (CXM0P, "CXM0P"),
+    (CXM1P, "CXM1P"),
+    (CXP0FB, "CXP0FB"),
+    (CXP1FB, "CXP1FB"),
+    (CXM0FB, "CXM0FB"),
+    (CXM1FB, "CXM1FB"),
+    (CXBLPF, "CXBLPF"),
+    (CXPPMM, "CXPPMM"),
+    (INPT0, "INPT0"),
+    (INPT1, "INPT1"),
+    (INPT2, "INPT2"),
+    (INPT3, "INPT3"),
+    (INPT4, "INPT4"),
+    (INPT5, "INPT5"),
+    (SWCHA, "SWCHA"),
+    (SWACNT, "SWACNT"),
+    (SWCHB, "SWCHB"),
+    (SWBCNT, "SWBCNT"),
+    (INTIM, "INTIM"),
+    (INSTAT, "INSTAT"),
+    (TIM1T, "TIM1T"),
+    (TIM8T, "TIM8T"),
+    (TIM64T, "TIM64T"),
+    (T1024T, "T1024T")
+];
+
+/// Label of a location (by its canonical address), if it has one.
+pub(crate) fn label(address: u16) -> Option<&'static str> {
+    LABELS.iter().find(|(location, _)| *location == address).map(|(_, label)| *label)
+}
@@ -1,12 +1,144 @@
-
 // Copyright (c) 2020 - Jonathan De Wachter
 //
-// This source file is part of Atari 2600 Emulator which is released under the 
-// MIT license. Please refer to the LICENSE file that can be found at the root 
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
 // of the project directory.
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, January 2020
 
+use std::env;
+use std::process;
+
+use atari_2600::{Database, RomBrowser, rom_info};
+#[cfg(feature = "tui")]
+use atari_2600::{Cartridge, Console, Emulator, Joystick, Port, Session, run_in_terminal};
+
+const USAGE: &str = "usage: atari-2600 (--info <rom> | --browse <directory> | --tui <rom> [--session <directory> [--resume]]) [--database <file>]";
+
 fn main() {
-    println!("Hello, world!");
-}
\ No newline at end of file
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let mut rom = None;
+    let mut directory = None;
+    let mut database_file = None;
+    let mut terminal_rom = None;
+    let mut session = None;
+    let mut is_resuming = false;
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--info" => rom = arguments.next(),
+            "--browse" => directory = arguments.next(),
+            "--database" => database_file = arguments.next(),
+            "--tui" => terminal_rom = arguments.next(),
+            "--session" => session = arguments.next(),
+            "--resume" => is_resuming = true,
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    // The games of the database file complete the built-in ones.
+    let mut database = Database::builtin();
+    match database_file.map(Database::from_file) {
+        Some(Ok(other_database)) => database.extend(other_database),
+        Some(Err(error)) => {
+            eprintln!("can't read the database: {}", error);
+            process::exit(1);
+        },
+        None => ()
+    }
+
+    match (rom, directory, terminal_rom) {
+        (Some(rom), None, None) => print_info(rom, &database),
+        (None, Some(directory), None) => print_roms(directory, &database),
+        (None, None, Some(rom)) => run_rom_in_terminal(rom, session, is_resuming),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn print_info(rom: &str, database: &Database) {
+    let info = match rom_info(rom) {
+        Ok(info) => info,
+        Err(error) => {
+            eprintln!("can't read {}: {}", rom, error);
+            process::exit(1);
+        }
+    };
+
+    println!("{}", info.with_database(database));
+}
+
+// List the ROMs of a directory as the ROM browser does, the last picked one
+// marked.
+fn print_roms(directory: &str, database: &Database) {
+    let browser = match RomBrowser::open(directory, Some(database)) {
+        Ok(browser) => browser,
+        Err(error) => {
+            eprintln!("can't read {}: {}", directory, error);
+            process::exit(1);
+        }
+    };
+
+    for (index, entry) in browser.entries().iter().enumerate() {
+        let marker = if index == browser.selected() { '>' } else { ' ' };
+        println!("{} {} ({})", marker, entry.name, entry.path.display());
+    }
+}
+
+// Run a ROM in the terminal (see `run_in_terminal()`); with a session, the
+// settings and the slots of the game are restored and saved when the user
+// quits (see `Session`), and the state it was left in is restored if resuming.
+#[cfg(feature = "tui")]
+fn run_rom_in_terminal(rom: &str, session: Option<&String>, is_resuming: bool) {
+    let cartridge = match Cartridge::from_file(rom) {
+        Ok(cartridge) => cartridge,
+        Err(error) => {
+            eprintln!("can't read {}: {}", rom, error);
+            process::exit(1);
+        }
+    };
+
+    let mut console = Console::new(cartridge);
+    console.plug_controller(Port::Left, Box::new(Joystick::new()));
+
+    let mut emulator = Emulator::new(console);
+
+    let session = session.map(|directory| {
+        let mut session = match Session::open(directory) {
+            Ok(session) => session,
+            Err(error) => {
+                eprintln!("can't open the session {}: {}", directory, error);
+                process::exit(1);
+            }
+        };
+
+        if let Err(error) = session.launch(rom, &mut emulator, is_resuming) {
+            eprintln!("can't restore the session {}: {}", directory, error);
+            process::exit(1);
+        }
+
+        session
+    });
+
+    if let Err(error) = run_in_terminal(&mut emulator) {
+        eprintln!("can't run in the terminal: {}", error);
+        process::exit(1);
+    }
+
+    if let Some(Err(error)) = session.map(|session| session.save(&emulator)) {
+        eprintln!("can't save the session: {}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_rom_in_terminal(_rom: &str, _session: Option<&String>, _is_resuming: bool) {
+    eprintln!("the emulator was built without the terminal frontend (the tui feature)");
+    process::exit(1);
+}
@@ -0,0 +1,262 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::console::{Console, Player, TvType, Difficulty};
+use crate::frame::Frame;
+use crate::overlay::{draw_text, dim_frame, LINE_HEIGHT};
+
+/// Number of state slots of the emulator.
+pub const STATE_SLOTS: usize = 10;
+
+/// The inputs of the pause menu.
+///
+/// The frontend maps its own inputs (keyboard, gamepad, etc.) to these ones to
+/// navigate the menu.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back
+}
+
+/// The actions of the pause menu.
+///
+/// Most actions are carried out by the emulator itself; quitting is left to
+/// the frontend.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuAction {
+    Resume,
+    Reset,
+    SaveState(usize),
+    LoadState(usize),
+    ToggleDifficulty(Player),
+    ToggleTvType,
+    Quit
+}
+
+// The items of the menu, in display order.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum MenuItem {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    LeftDifficulty,
+    RightDifficulty,
+    TvType,
+    Quit
+}
+
+const ITEMS: [MenuItem; 8] = [
+    MenuItem::Resume,
+    MenuItem::Reset,
+    MenuItem::SaveState,
+    MenuItem::LoadState,
+    MenuItem::LeftDifficulty,
+    MenuItem::RightDifficulty,
+    MenuItem::TvType,
+    MenuItem::Quit
+];
+
+/// The pause menu of the emulator.
+///
+/// The menu lists the actions available while the emulator is paused; resume,
+/// reset, save or load a state slot (left and right change the slot), switch
+/// the difficulties or the TV type, and quit. It's navigated with the menu
+/// inputs, which select an action when it's activated.
+///
+#[derive(Clone, Debug)]
+pub struct PauseMenu {
+    selected: usize,
+    slot: usize
+}
+
+impl PauseMenu {
+    /// Create a pause menu.
+    ///
+    /// The menu is created with its first item (resume) selected, and the
+    /// first state slot.
+    ///
+    pub fn new() -> PauseMenu {
+        PauseMenu {
+            selected: 0,
+            slot: 0
+        }
+    }
+
+    /// Index of the selected item.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selected state slot.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Navigate the menu.
+    ///
+    /// This function moves the selection, changes the state slot or activates
+    /// the selected item, depending on the input, and returns the action to
+    /// carry out, if any. Going back resumes the emulation.
+    ///
+    pub fn handle_input(&mut self, input: MenuInput) -> Option<MenuAction> {
+        let item = ITEMS[self.selected];
+
+        match input {
+            MenuInput::Up => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                None
+            },
+            MenuInput::Down => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+                None
+            },
+            MenuInput::Left | MenuInput::Right => match item {
+                MenuItem::SaveState | MenuItem::LoadState => {
+                    self.slot = match input {
+                        MenuInput::Left => (self.slot + STATE_SLOTS - 1) % STATE_SLOTS,
+                        _ => (self.slot + 1) % STATE_SLOTS
+                    };
+                    None
+                },
+                MenuItem::LeftDifficulty | MenuItem::RightDifficulty | MenuItem::TvType => self.action(item),
+                _ => None
+            },
+            MenuInput::Select => self.action(item),
+            MenuInput::Back => Some(MenuAction::Resume)
+        }
+    }
+
+    fn action(&self, item: MenuItem) -> Option<MenuAction> {
+        let action = match item {
+            MenuItem::Resume => MenuAction::Resume,
+            MenuItem::Reset => MenuAction::Reset,
+            MenuItem::SaveState => MenuAction::SaveState(self.slot),
+            MenuItem::LoadState => MenuAction::LoadState(self.slot),
+            MenuItem::LeftDifficulty => MenuAction::ToggleDifficulty(Player::One),
+            MenuItem::RightDifficulty => MenuAction::ToggleDifficulty(Player::Two),
+            MenuItem::TvType => MenuAction::ToggleTvType,
+            MenuItem::Quit => MenuAction::Quit
+        };
+
+        Some(action)
+    }
+
+    /// Draw the menu over a frame.
+    ///
+    /// This function dims the frame and draws the menu over it; the values of
+    /// the switches are read from the console, and the used state slots tell
+    /// which slots can be loaded.
+    ///
+    pub fn render(&self, frame: &mut Frame, console: &Console, used_slots: &[bool; STATE_SLOTS]) {
+        let white = (255, 255, 255);
+        let gray = (160, 160, 160);
+
+        let difficulty = |player| match console.difficulty_switch(player) {
+            Difficulty::Amateur => "AMATEUR",
+            Difficulty::Pro => "PRO"
+        };
+
+        let slot = match used_slots[self.slot] {
+            true  => format!("{} (USED)", self.slot),
+            false => format!("{} (EMPTY)", self.slot)
+        };
+
+        dim_frame(frame);
+        draw_text(frame, 8, 8, "PAUSED", white);
+
+        for (index, item) in ITEMS.iter().enumerate() {
+            let text = match item {
+                MenuItem::Resume => String::from("RESUME"),
+                MenuItem::Reset => String::from("RESET"),
+                MenuItem::SaveState => format!("SAVE STATE < {} >", slot),
+                MenuItem::LoadState => format!("LOAD STATE < {} >", slot),
+                MenuItem::LeftDifficulty => format!("LEFT DIFFICULTY: {}", difficulty(Player::One)),
+                MenuItem::RightDifficulty => format!("RIGHT DIFFICULTY: {}", difficulty(Player::Two)),
+                MenuItem::TvType => match console.tv_type_switch() {
+                    TvType::Color => String::from("TV TYPE: COLOR"),
+                    TvType::Mono => String::from("TV TYPE: B/W")
+                },
+                MenuItem::Quit => String::from("QUIT")
+            };
+
+            let y = 20 + index * (LINE_HEIGHT + 2);
+            match index == self.selected {
+                true  => draw_text(frame, 8, y, &format!("> {}", text), white),
+                false => draw_text(frame, 8, y, &format!("  {}", text), gray)
+            }
+        }
+    }
+}
+
+impl Default for PauseMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    #[test]
+    fn test_pause_menu() {
+        let mut menu = PauseMenu::new();
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::Resume));
+        assert_eq!(menu.handle_input(MenuInput::Back), Some(MenuAction::Resume));
+
+        // The selection wraps around.
+        assert_eq!(menu.handle_input(MenuInput::Up), None);
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::Quit));
+        menu.handle_input(MenuInput::Down);
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::Reset));
+
+        // The state slot is changed with left and right, and it wraps around.
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.handle_input(MenuInput::Left), None);
+        assert_eq!(menu.slot(), 9);
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::SaveState(9)));
+        menu.handle_input(MenuInput::Right);
+        menu.handle_input(MenuInput::Right);
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::LoadState(1)));
+
+        // The switches are toggled with left and right too.
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.handle_input(MenuInput::Right), Some(MenuAction::ToggleDifficulty(Player::One)));
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.handle_input(MenuInput::Left), Some(MenuAction::ToggleDifficulty(Player::Two)));
+        menu.handle_input(MenuInput::Down);
+        assert_eq!(menu.selected(), 6);
+        assert_eq!(menu.handle_input(MenuInput::Select), Some(MenuAction::ToggleTvType));
+    }
+
+    #[test]
+    fn test_render_pause_menu() {
+        let console = Console::new(Cartridge::new(vec![]));
+        let mut frame = console.frame();
+        frame.pixels = [[(200, 200, 200); 160]; 192];
+
+        let menu = PauseMenu::new();
+        menu.render(&mut frame, &console, &[false; STATE_SLOTS]);
+
+        // The frame is dimmed and the selected item is drawn in white; the
+        // arrow starts at column 8 of the first item.
+        assert_eq!(frame.pixel(0, 0), (50, 50, 50));
+        assert_eq!(frame.pixel(8, 20), (255, 255, 255));
+        assert_eq!(frame.pixel(8, 28), (50, 50, 50));
+    }
+}
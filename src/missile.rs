@@ -11,25 +11,56 @@
 //!
 //! This module defines something that is to be described.
 //!
-use crate::location::{ENAM0, ENAM1};
-use crate::console::Console;
+use crate::location::{ENAM0, ENAM1, NUSIZ0, NUSIZ1, RESMP0, RESMP1};
+use crate::console::Player;
+use crate::tia::Tia;
 
-fn _is_missile0_enabled(console: &Console) -> bool {
+pub(crate) fn is_missile_enabled(tia: &Tia, player: Player) -> bool {
     //   1D      ENAM0   ......1.  graphics (enable) missile 0
-    *console.memory(ENAM0) & 0b0000_00010 > 0
-}
-
-fn _is_missile1_enabled(console: &Console) -> bool {
     //   1E      ENAM1   ......1.  graphics (enable) missile 1
-    *console.memory(ENAM1) & 0b0000_00010 > 0
+    //   28      RESMP0  ......1.  reset missile 0 to player 0
+    //   29      RESMP1  ......1.  reset missile 1 to player 1
+    //
+    // A missile locked to its player is hidden, whatever ENAMx is.
+    let (enable, lock) = match player {
+        Player::One => (tia.register(ENAM0), tia.register(RESMP0)),
+        Player::Two => (tia.register(ENAM1), tia.register(RESMP1))
+    };
+
+    enable & 0b0000_0010 != 0 && lock & 0b0000_0010 == 0
 }
 
+pub(crate) fn missile_width(tia: &Tia, player: Player) -> u32 {
+    //   04      NUSIZ0  ..111111  number-size player-missile 0
+    //   05      NUSIZ1  ..111111  number-size player-missile 1
+    //
+    // Bit 4 and 5 select the width of the missile; 1, 2, 4 or 8 pixels.
+    let value = match player {
+        Player::One => tia.register(NUSIZ0),
+        Player::Two => tia.register(NUSIZ1)
+    };
+
+    1 << ((value & 0b0011_0000) >> 4)
+}
 
 #[cfg(test)]
 mod test {
+    use super::*;
 
     #[test]
-    fn test_is_missile_enabled() {
-        // assert_eq!(is_missile0_enabled(console: &Console))
+    fn test_missile() {
+        let mut tia = Tia::new();
+        assert!(!is_missile_enabled(&tia, Player::One));
+
+        tia.write(ENAM0, 0b0000_0010);
+        tia.write(NUSIZ1, 0b0011_0000);
+        assert!(is_missile_enabled(&tia, Player::One));
+        assert!(!is_missile_enabled(&tia, Player::Two));
+        assert_eq!(missile_width(&tia, Player::One), 1);
+        assert_eq!(missile_width(&tia, Player::Two), 8);
+
+        // Locked to its player, the missile is hidden.
+        tia.write(RESMP0, 0b0000_0010);
+        assert!(!is_missile_enabled(&tia, Player::One));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,240 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::assembler::mnemonic;
+use crate::bus::Bus;
+use crate::cpu::Cpu6507;
+
+// The number of official opcodes of the 6502; the 105 others are the
+// unofficial ones.
+const OFFICIAL_OPCODES: usize = 151;
+
+/// The number of times each opcode was executed.
+///
+/// The console counts the opcodes it executes (see
+/// `Console::opcode_stats()`), the ones the CPU doesn't emulate included;
+/// running a corpus of ROMs tells which instructions are worth implementing
+/// next, and which ones the test ROMs don't exercise yet (see `coverage()`).
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// for _ in 0..60 {
+///     console.run_frame();
+/// }
+/// println!("{}", console.opcode_stats().coverage());
+/// ```
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpcodeStats {
+    counts: [u64; 256]
+}
+
+impl OpcodeStats {
+    /// Create empty statistics.
+    pub fn new() -> OpcodeStats {
+        OpcodeStats {
+            counts: [0; 256]
+        }
+    }
+
+    /// Number of times an opcode was executed.
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.counts[opcode as usize]
+    }
+
+    /// Number of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Opcodes that were executed, with their counts, most executed first.
+    pub fn executed(&self) -> Vec<(u8, u64)> {
+        let mut opcodes = (0..=255).map(|opcode| (opcode, self.count(opcode)))
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        opcodes
+    }
+
+    /// Coverage of the opcodes.
+    ///
+    /// This function returns which opcodes were executed among the official
+    /// and the unofficial ones, and which of them the CPU doesn't emulate.
+    ///
+    pub fn coverage(&self) -> OpcodeCoverage {
+        let executed = |opcode: &u8| self.count(*opcode) > 0;
+
+        let (official, unofficial): (Vec<u8>, Vec<u8>) = (0..=255).partition(|opcode| is_official(*opcode));
+
+        OpcodeCoverage {
+            official: official.iter().copied().filter(executed).collect(),
+            unofficial: unofficial.iter().copied().filter(executed).collect(),
+            missing: official.iter().copied().filter(|opcode| !executed(opcode)).collect(),
+            unimplemented: self.executed().into_iter().filter(|(opcode, _)| !is_implemented(*opcode)).collect()
+        }
+    }
+
+    pub(crate) fn record(&mut self, opcode: u8) {
+        self.counts[opcode as usize] += 1;
+    }
+}
+
+impl Default for OpcodeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The coverage of the opcodes by a run.
+///
+/// This structure is returned by `OpcodeStats::coverage()`; its text form is
+/// a short report.
+///
+/// ```text
+/// Official opcodes: 42/151 executed
+/// Unofficial opcodes: 1/105 executed
+/// Unimplemented opcodes executed: $E4 CPX (12 times), $A7 (3 times)
+/// ```
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpcodeCoverage {
+    /// Official opcodes that were executed.
+    pub official: Vec<u8>,
+    /// Unofficial opcodes that were executed.
+    pub unofficial: Vec<u8>,
+    /// Official opcodes that weren't executed.
+    pub missing: Vec<u8>,
+    /// Opcodes the CPU doesn't emulate that were executed, with their counts,
+    /// most executed first.
+    pub unimplemented: Vec<(u8, u64)>
+}
+
+impl fmt::Display for OpcodeCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Official opcodes: {}/{} executed", self.official.len(), OFFICIAL_OPCODES)?;
+        writeln!(f, "Unofficial opcodes: {}/{} executed", self.unofficial.len(), 256 - OFFICIAL_OPCODES)?;
+
+        let unimplemented = self.unimplemented.iter()
+            .map(|(opcode, count)| match mnemonic(*opcode) {
+                Some(name) => format!("${:02X} {} ({} times)", opcode, name, count),
+                None => format!("${:02X} ({} times)", opcode, count)
+            })
+            .collect::<Vec<_>>();
+
+        match unimplemented.is_empty() {
+            true => write!(f, "Unimplemented opcodes executed: none"),
+            false => write!(f, "Unimplemented opcodes executed: {}", unimplemented.join(", "))
+        }
+    }
+}
+
+/// Check whether an opcode is an official one.
+pub(crate) fn is_official(opcode: u8) -> bool {
+    mnemonic(opcode).is_some()
+}
+
+// A bus with nothing attached, to find out which opcodes the CPU knows; the
+// operands are all 1 (a branch of 0 bytes isn't handled by the branches).
+struct ProbeBus {
+    opcode: u8
+}
+
+impl Bus for ProbeBus {
+    fn read(&mut self, _address: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _address: u16, _value: u8) {}
+
+    fn fetch(&mut self, address: u16) -> u8 {
+        match address {
+            0x_F000 => self.opcode,
+            _ => 1
+        }
+    }
+}
+
+/// Check whether the CPU emulates an opcode.
+///
+/// This function executes the opcode once on a bus with nothing attached;
+/// the opcodes the CPU emulates are the ones it doesn't report as unknown.
+///
+pub(crate) fn is_implemented(opcode: u8) -> bool {
+    static IMPLEMENTED: OnceLock<[bool; 256]> = OnceLock::new();
+
+    let implemented = IMPLEMENTED.get_or_init(|| {
+        let mut implemented = [false; 256];
+        for (opcode, is_implemented) in implemented.iter_mut().enumerate() {
+            let mut cpu = Cpu6507::new();
+            cpu.program_counter = 0x_F000;
+            *is_implemented = cpu.step(&mut ProbeBus { opcode: opcode as u8 }).is_ok();
+        }
+
+        implemented
+    });
+
+    implemented[opcode as usize]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_opcode_stats() {
+        assert_eq!((0..=255).filter(|opcode| is_official(*opcode)).count(), OFFICIAL_OPCODES);
+        assert!(is_implemented(0x_A9));
+        assert!(is_implemented(0x_02)); // KIL
+        assert!(!is_implemented(0x_A7)); // LAX
+
+        // A loop of 4 instructions, one of which (an unofficial NOP) isn't
+        // emulated.
+        let mut rom = assemble("
+            loop:
+                lda #1
+                ldx #2
+                .byte $1A
+                jmp loop
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        for _ in 0..40 {
+            console.execute_instruction();
+        }
+
+        let stats = console.opcode_stats();
+        assert_eq!(stats.total(), 40);
+        assert_eq!(stats.count(0x_A9), 10);
+        assert_eq!(stats.count(0x_1A), 10);
+        assert_eq!(stats.count(0x_EA), 0);
+        assert_eq!(stats.executed().len(), 4);
+
+        let coverage = stats.coverage();
+        assert_eq!(coverage.official, vec![0x_4C, 0x_A2, 0x_A9]);
+        assert_eq!(coverage.unofficial, vec![0x_1A]);
+        assert_eq!(coverage.missing.len(), OFFICIAL_OPCODES - 3);
+        assert_eq!(coverage.unimplemented, vec![(0x_1A, 10)]);
+        assert_eq!(coverage.to_string(), "\
+            Official opcodes: 3/151 executed\n\
+            Unofficial opcodes: 1/105 executed\n\
+            Unimplemented opcodes executed: $1A (10 times)");
+
+        console.reset_opcode_stats();
+        assert_eq!(console.opcode_stats().total(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+//! Drawing helpers for overlays.
+//!
+//! This module contains helper functions to draw text and rectangles over a
+//! frame, for the emulator menus and for the frontends (debugging information,
+//! notifications, etc.). The text is drawn with a tiny built-in font whose
+//! glyphs are 3 pixels wide and 5 pixels high, so 40 characters fit on a line
+//! of the frame.
+//!
+use crate::frame::Frame;
+
+/// Width of a character, including the spacing (in pixels).
+pub const CHARACTER_WIDTH: usize = 4;
+
+/// Height of a line of text, including the spacing (in pixels).
+pub const LINE_HEIGHT: usize = 6;
+
+// The glyphs of the font; each row is 3 bits wide and the most significant bit
+// is the left pixel.
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        ' ' => [0b000; 5],
+        _   => [0b111; 5]
+    }
+}
+
+/// Width of a text (in pixels).
+pub fn text_width(text: &str) -> usize {
+    text.chars().count() * CHARACTER_WIDTH
+}
+
+/// Draw a text over a frame.
+///
+/// This function draws a line of text with its top-left corner at column `x`
+/// and line `y` of the frame. Letters are drawn in uppercase and characters
+/// the font doesn't have are drawn as blocks; the text is clipped to the
+/// frame.
+///
+pub fn draw_text(frame: &mut Frame, x: usize, y: usize, text: &str, color: (u8, u8, u8)) {
+    for (index, character) in text.chars().enumerate() {
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                let (x, y) = (x + index * CHARACTER_WIDTH + column, y + row);
+                if x < Frame::WIDTH && y < Frame::HEIGHT {
+                    frame.pixels[y][x] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Fill a rectangle of a frame.
+///
+/// This function fills the rectangle with its top-left corner at column `x`
+/// and line `y` of the frame with a color; the rectangle is clipped to the
+/// frame.
+///
+pub fn fill_rectangle(frame: &mut Frame, x: usize, y: usize, width: usize, height: usize, color: (u8, u8, u8)) {
+    for line in frame.pixels.iter_mut().skip(y).take(height) {
+        for pixel in line.iter_mut().skip(x).take(width) {
+            *pixel = color;
+        }
+    }
+}
+
+/// Dim a frame.
+///
+/// This function darkens all the pixels of a frame, which makes the text
+/// drawn over it readable regardless of the content of the frame.
+///
+pub fn dim_frame(frame: &mut Frame) {
+    for line in frame.pixels.iter_mut() {
+        for pixel in line.iter_mut() {
+            *pixel = (pixel.0 / 4, pixel.1 / 4, pixel.2 / 4);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_frame() -> Frame {
+        Frame {
+            number: 0,
+            pixels: [[(0, 0, 0); 160]; 192],
+            orientation: Default::default()
+        }
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut frame = create_frame();
+        let white = (255, 255, 255);
+
+        assert_eq!(text_width("Hi!"), 12);
+
+        // The 'H' is drawn from column 10, the 'i' from column 14.
+        draw_text(&mut frame, 10, 20, "Hi", white);
+        assert_eq!(frame.pixel(10, 20), white);
+        assert_eq!(frame.pixel(11, 20), (0, 0, 0));
+        assert_eq!(frame.pixel(11, 22), white);
+        assert_eq!(frame.pixel(12, 24), white);
+        assert_eq!(frame.pixel(13, 20), (0, 0, 0));
+        assert_eq!(frame.pixel(14, 20), white);
+        assert_eq!(frame.pixel(15, 21), white);
+
+        // The text is clipped to the frame.
+        draw_text(&mut frame, 158, 190, "XX", white);
+        assert_eq!(frame.pixel(158, 190), white);
+        assert_eq!(frame.pixel(159, 191), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_fill_rectangle() {
+        let mut frame = create_frame();
+        frame.pixels[0][0] = (200, 100, 40);
+
+        dim_frame(&mut frame);
+        assert_eq!(frame.pixel(0, 0), (50, 25, 10));
+
+        fill_rectangle(&mut frame, 150, 5, 20, 2, (1, 2, 3));
+        assert_eq!(frame.pixel(149, 5), (0, 0, 0));
+        assert_eq!(frame.pixel(150, 5), (1, 2, 3));
+        assert_eq!(frame.pixel(159, 6), (1, 2, 3));
+        assert_eq!(frame.pixel(159, 7), (0, 0, 0));
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::time::Duration;
+
+use crate::console::TvStandard;
+
+// Maximum number of frames to run in one iteration to catch up when the host
+// falls behind (instead of spiraling).
+const MAX_FRAMES: u32 = 4;
+
+// Maximum relative difference between the refresh rate of the display and the
+// frame rate of the console for them to be locked together (59.94 Hertz on a
+// 60 Hertz display for instance).
+const LOCK_TOLERANCE: f64 = 0.01;
+
+/// What the frontend must do in one iteration of its loop.
+///
+/// The frontend runs the given number of frames (it may be none), then
+/// sleeps for the given duration (it may be zero).
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pacing {
+    pub frames: u32,
+    pub sleep: Duration
+}
+
+enum Strategy {
+    Video { refresh_period: f64, is_locked: bool, accumulated_time: f64 },
+    Audio { sample_rate: f64, target_samples: f64 }
+}
+
+/// A helper to run the simulation at the pace of the real console.
+///
+/// Frontends usually run a loop that is synchronized with either the display
+/// (the loop is blocked until the next vertical refresh) or the audio device
+/// (the loop keeps the audio queue filled). This structure tells the frontend
+/// how many frames to run, and how long to sleep, at each iteration of its
+/// loop so the simulation runs at the pace of the real console.
+///
+/// With the video as master, if the refresh rate of the display is close to
+/// the frame rate of the console (60 Hertz versus 59.94 Hertz), exactly one
+/// frame is run per refresh and the simulation runs slightly too fast; the
+/// audio must then be resampled with the ratio returned by `audio_ratio()`.
+/// Otherwise, frames are run (or skipped) to match the real time.
+///
+/// With the audio as master, frames are run until the audio queue reaches the
+/// requested latency, and the frontend sleeps while the queue is full enough.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Frame, RealTimePacer, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn present_frame(_frame: Frame) {}
+/// let mut pacer = RealTimePacer::video_master(console.tv_standard(), 60.0);
+///
+/// loop {
+///     let pacing = pacer.pace(0);
+///     for _ in 0..pacing.frames {
+///         console.run_frame();
+///     }
+///
+///     present_frame(console.frame()); // blocks until the next refresh
+/// #   break;
+/// }
+/// ```
+///
+pub struct RealTimePacer {
+    frame_rate: f64,
+    strategy: Strategy
+}
+
+impl RealTimePacer {
+    /// Create a pacer synchronized with the display.
+    ///
+    /// This function creates a pacer for a loop that is blocked until the
+    /// next vertical refresh of a display with the given refresh rate (in
+    /// Hertz).
+    ///
+    pub fn video_master(standard: TvStandard, refresh_rate: f64) -> RealTimePacer {
+        let frame_rate = standard.frame_rate();
+        let is_locked = ((refresh_rate - frame_rate) / frame_rate).abs() < LOCK_TOLERANCE;
+
+        RealTimePacer {
+            frame_rate,
+            strategy: Strategy::Video {
+                refresh_period: 1.0 / refresh_rate,
+                is_locked,
+                accumulated_time: 0.0
+            }
+        }
+    }
+
+    /// Create a pacer synchronized with the audio device.
+    ///
+    /// This function creates a pacer for a loop that keeps the queue of an
+    /// audio device with the given sample rate (in Hertz) filled with the
+    /// given latency.
+    ///
+    pub fn audio_master(standard: TvStandard, sample_rate: u32, latency: Duration) -> RealTimePacer {
+        let sample_rate = sample_rate as f64;
+
+        RealTimePacer {
+            frame_rate: standard.frame_rate(),
+            strategy: Strategy::Audio {
+                sample_rate,
+                target_samples: latency.as_secs_f64() * sample_rate
+            }
+        }
+    }
+
+    /// Frame rate of the console (in Hertz).
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    /// Duration of a frame of the console.
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.frame_rate)
+    }
+
+    /// Resampling ratio of the audio.
+    ///
+    /// This function returns the ratio by which the audio produced by the
+    /// simulation must be resampled because it runs faster (greater than 1.0)
+    /// or slower than the real console. It's only different from 1.0 when the
+    /// video is master and locked to the display.
+    ///
+    pub fn audio_ratio(&self) -> f64 {
+        match self.strategy {
+            Strategy::Video { refresh_period, is_locked: true, .. } => {
+                1.0 / (refresh_period * self.frame_rate)
+            },
+            _ => 1.0
+        }
+    }
+
+    /// Pace an iteration of the loop.
+    ///
+    /// This function must be called at each iteration of the loop of the
+    /// frontend with the number of samples waiting in the audio queue (it's
+    /// ignored when the video is master) and tells what to do.
+    ///
+    pub fn pace(&mut self, queued_samples: usize) -> Pacing {
+        let frame_duration = 1.0 / self.frame_rate;
+
+        match &mut self.strategy {
+            Strategy::Video { is_locked: true, .. } => {
+                Pacing { frames: 1, sleep: Duration::from_secs(0) }
+            },
+            Strategy::Video { refresh_period, accumulated_time, .. } => {
+                *accumulated_time += *refresh_period;
+
+                let frames = (*accumulated_time / frame_duration).floor() as u32;
+                *accumulated_time -= frames as f64 * frame_duration;
+
+                // Drop the time that can't be caught up.
+                if frames > MAX_FRAMES {
+                    *accumulated_time = 0.0;
+                }
+
+                Pacing { frames: frames.min(MAX_FRAMES), sleep: Duration::from_secs(0) }
+            },
+            Strategy::Audio { sample_rate, target_samples } => {
+                let queued_samples = queued_samples as f64;
+                let samples_per_frame = *sample_rate / self.frame_rate;
+
+                if queued_samples < *target_samples {
+                    let frames = ((*target_samples - queued_samples) / samples_per_frame).ceil() as u32;
+                    Pacing { frames: frames.min(MAX_FRAMES), sleep: Duration::from_secs(0) }
+                }
+                else {
+                    // Sleep until the queue drains to the target latency.
+                    let sleep = (queued_samples - *target_samples) / *sample_rate;
+                    Pacing { frames: 0, sleep: Duration::from_secs_f64(sleep) }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_video_master() {
+        // A 60 Hertz display is locked to the NTSC frame rate.
+        let mut pacer = RealTimePacer::video_master(TvStandard::Ntsc, 60.0);
+        assert!((pacer.frame_rate() - 59.92).abs() < 0.01);
+        assert!((pacer.audio_ratio() - 1.0013).abs() < 0.0001);
+
+        for _ in 0..100 {
+            assert_eq!(pacer.pace(0), Pacing { frames: 1, sleep: Duration::from_secs(0) });
+        }
+
+        // A 50 Hertz console on a 60 Hertz display skips a refresh every 6.
+        let mut pacer = RealTimePacer::video_master(TvStandard::Pal, 60.0);
+        assert_eq!(pacer.audio_ratio(), 1.0);
+
+        let frames: u32 = (0..600).map(|_| pacer.pace(0).frames).sum();
+        assert!((498..=499).contains(&frames));
+
+        // A 120 Hertz display runs a frame about every other refresh.
+        let mut pacer = RealTimePacer::video_master(TvStandard::Ntsc, 120.0);
+        let frames: Vec<u32> = (0..120).map(|_| pacer.pace(0).frames).collect();
+        assert!(frames.iter().all(|frames| *frames <= 1));
+        assert_eq!(frames.iter().sum::<u32>(), 59);
+    }
+
+    #[test]
+    fn test_audio_master() {
+        // 44100 Hertz with 50 milliseconds of latency is 2205 samples; a frame
+        // produces about 736 samples.
+        let mut pacer = RealTimePacer::audio_master(TvStandard::Ntsc, 44100, Duration::from_millis(50));
+
+        assert_eq!(pacer.pace(0).frames, 3);
+        assert_eq!(pacer.pace(2000).frames, 1);
+
+        let pacing = pacer.pace(2205 + 441);
+        assert_eq!(pacing.frames, 0);
+        assert_eq!(pacing.sleep, Duration::from_millis(10));
+
+        // Frames are capped when catching up.
+        let mut pacer = RealTimePacer::audio_master(TvStandard::Ntsc, 44100, Duration::from_millis(500));
+        assert_eq!(pacer.pace(0).frames, MAX_FRAMES);
+    }
+}
@@ -6,34 +6,363 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, November 2020
 
+
 use crate::Console;
+use crate::Port;
 use crate::Controller;
+use crate::state::{StateWriter, StateError};
+
+// The charge time of the capacitor of a pot input when a paddle is turned
+// fully counter-clockwise (highest resistance), in CPU cycles; about 200
+// scanlines, which covers the range games read.
+const MAX_CHARGE_TIME: u32 = 200 * 76;
+
+// The number of CPU cycles of a scanline.
+pub(crate) const SCANLINE_CYCLES: u32 = 76;
 
-/// Brief description.
+/// The calibration of the paddles.
+///
+/// A paddle is read by the time the capacitor of its pot input takes to
+/// charge, which the games measure by counting the scanlines until the input
+/// goes high. This structure maps the positions of the paddles to their
+/// charge time in CPU cycles; from `max_charge_time` when turned fully
+/// counter-clockwise, to `min_charge_time` when turned fully clockwise.
+///
+/// The games don't count the same number of scanlines, so the range of a real
+/// paddle is wider than what most of them read, and a good part of its course
+/// is lost. A game is calibrated with the range of scanlines it reads (see
+/// `from_scanlines()`), which the ROM database can tell, and the frontend can
+/// fine-tune the sensitivity on top of it.
 ///
-/// Long description.
+/// ```
+/// # use atari_2600::{Paddle, PaddleCalibration};
+/// # let mut paddles = Paddle::new();
+/// let calibration = PaddleCalibration::from_scanlines(4, 150).with_sensitivity(1.25);
+/// paddles.set_calibration(calibration);
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PaddleCalibration {
+    pub min_charge_time: u32,
+    pub max_charge_time: u32
+}
+
+impl PaddleCalibration {
+    /// Create a calibration from a range of charge times.
+    ///
+    /// This function creates a calibration with the given minimum and maximum
+    /// charge times, in CPU cycles; they're swapped if they're not in order.
+    ///
+    pub fn new(min_charge_time: u32, max_charge_time: u32) -> PaddleCalibration {
+        PaddleCalibration {
+            min_charge_time: min_charge_time.min(max_charge_time),
+            max_charge_time: min_charge_time.max(max_charge_time)
+        }
+    }
+
+    /// Create a calibration from a range of scanlines.
+    ///
+    /// This function creates a calibration whose charge times go from the
+    /// first to the last scanline a game reads the paddles on, counted from
+    /// the moment the capacitors stop being dumped; the charge times saturate
+    /// if the scanlines are out of range.
+    ///
+    pub fn from_scanlines(first: u32, last: u32) -> PaddleCalibration {
+        PaddleCalibration::new(first.saturating_mul(SCANLINE_CYCLES), last.saturating_mul(SCANLINE_CYCLES))
+    }
+
+    /// Change the sensitivity of the calibration.
+    ///
+    /// This function returns the calibration with its range scaled around its
+    /// center; a sensitivity above 1.0 narrows it so the paddles need to be
+    /// turned less, and a sensitivity below 1.0 widens it (the charge times
+    /// can't go below 0).
+    ///
+    pub fn with_sensitivity(&self, sensitivity: f32) -> PaddleCalibration {
+        assert!(sensitivity > 0.0, "sensitivity must be positive");
+
+        let center = (self.min_charge_time as f32 + self.max_charge_time as f32) / 2.0;
+        let half_range = (self.max_charge_time - self.min_charge_time) as f32 / 2.0 / sensitivity;
+
+        PaddleCalibration::new(
+            (center - half_range).max(0.0).round() as u32,
+            (center + half_range).round() as u32
+        )
+    }
+
+    /// Compute the charge time of a position.
+    ///
+    /// This function returns the number of CPU cycles it takes for the pot
+    /// input of a paddle at the given position (from 0.0 to 1.0, clamped) to
+    /// go high.
+    ///
+    pub fn charge_time(&self, position: f32) -> u32 {
+        let range = (self.max_charge_time - self.min_charge_time) as f32;
+        self.min_charge_time + ((1.0 - position.clamp(0.0, 1.0)) * range) as u32
+    }
+}
+
+impl Default for PaddleCalibration {
+    /// The default calibration, which covers the 200 scanlines most games
+    /// read the paddles on at most.
+    fn default() -> Self {
+        PaddleCalibration::new(0, MAX_CHARGE_TIME)
+    }
+}
+
+/// A pair of paddle controllers.
+///
+/// Paddles come in pairs plugged into a single port, so a pair serves two
+/// players and up to four players can play with a pair on each port (as in
+/// Warlords). The position of each paddle determines how fast the capacitor of
+/// its pot input charges; INPT0 and INPT1 for the left port, and INPT2 and
+/// INPT3 for the right port. The buttons are read from the pins of the port A
+/// (SWCHA); bit 7 and 6 for the left port, and bit 3 and 2 for the right port.
+///
+/// Paddles are denoted 0 and 1 within a pair; the players of the left pair are
+/// usually players 1 and 2, and the players of the right pair are players 3
+/// and 4.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Paddle, Port, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut paddles = Paddle::new();
+/// paddles.set_position(1, 0.25);
+/// paddles.press_button(1);
+///
+/// console.plug_controller(Port::Left, Box::new(paddles));
+/// ```
+///
+/// The positions are converted to charge times with the calibration of the
+/// pair (see `PaddleCalibration`); it's a setting of the paddles, so it isn't
+/// part of their state.
 ///
 pub struct Paddle {
-    console: Option<*mut Console>
+    port: Port,
+    positions: [f32; 2],
+    buttons: [bool; 2],
+    calibration: PaddleCalibration
 }
 
 impl Paddle {
+    /// Create a pair of paddles.
+    ///
+    /// The paddles are created centered with their buttons released.
+    ///
+    pub fn new() -> Paddle {
+        Paddle {
+            port: Port::Left,
+            positions: [0.5; 2],
+            buttons: [false; 2],
+            calibration: PaddleCalibration::default()
+        }
+    }
+
+    /// Change the calibration of the paddles.
+    pub fn set_calibration(&mut self, calibration: PaddleCalibration) {
+        self.calibration = calibration;
+    }
+
+    /// Calibration of the paddles.
+    pub fn calibration(&self) -> PaddleCalibration {
+        self.calibration
+    }
+
+    /// Charge time of a paddle.
+    ///
+    /// This function returns the number of CPU cycles it takes for the pot
+    /// input of a paddle to go high at its current position, once the
+    /// capacitor stops being dumped.
+    ///
+    pub fn charge_time(&self, paddle: usize) -> u32 {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+        self.calibration.charge_time(self.positions[paddle])
+    }
+
+    /// Turn a paddle.
+    ///
+    /// This function sets the position of a paddle, from 0.0 (turned fully
+    /// counter-clockwise) to 1.0 (turned fully clockwise); the position is
+    /// clamped to this range.
+    ///
+    pub fn set_position(&mut self, paddle: usize, position: f32) {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+
+        self.positions[paddle] = position.clamp(0.0, 1.0);
+    }
+
+    /// Position of a paddle.
+    pub fn position(&self, paddle: usize) -> f32 {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+        self.positions[paddle]
+    }
+
+    /// Press the button of a paddle.
+    pub fn press_button(&mut self, paddle: usize) {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+
+        self.buttons[paddle] = true;
+    }
+
+    /// Release the button of a paddle.
+    pub fn release_button(&mut self, paddle: usize) {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+
+        self.buttons[paddle] = false;
+    }
+
+    /// Check whether the button of a paddle is pressed.
+    pub fn is_button_pressed(&self, paddle: usize) -> bool {
+        assert!(paddle < 2, "paddle must be 0 or 1");
+        self.buttons[paddle]
+    }
+}
+
+impl Default for Paddle {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Controller for Paddle {
-    fn plugged(&mut self, console: *mut Console) {
-        self.console = Some(console);
+    fn plugged(&mut self, port: Port) {
+        self.port = port;
     }
 
     fn unplugged(&mut self) {
-        self.console = None;
+    }
+
+    fn apply(&mut self, console: &mut Console) {
+        let (first_input, first_pin) = match self.port {
+            Port::Left => (0, 7),
+            Port::Right => (2, 3)
+        };
+
+        // The resistance of a paddle decreases as it's turned clockwise, and
+        // so does the time it takes to charge the capacitor. The buttons
+        // ground their pin when they're pressed.
+        for paddle in 0..2 {
+            console.set_input_charge_time(first_input + paddle, Some(self.charge_time(paddle)));
+            console.drive_port_a_pin(first_pin - paddle, !self.buttons[paddle]);
+        }
+    }
+
+    fn save_state(&self) -> StateWriter {
+        let mut state = StateWriter::new();
+        for paddle in 0..2 {
+            state.write_u32(self.positions[paddle].to_bits());
+            state.write_bool(self.buttons[paddle]);
+        }
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        for paddle in 0..2 {
+            self.positions[paddle] = f32::from_bits(reader.read_u32()?);
+            self.buttons[paddle] = reader.read_bool()?;
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::location::{SWCHA, VBLANK};
 
     #[test]
     fn test_paddle() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        console.plug_controller(Port::Left, Box::new(Paddle::new()));
+        console.plug_controller(Port::Right, Box::new(Paddle::new()));
+
+        // Each of the four paddles has its own pot input.
+        let mut left_paddles = console.controller_mut::<Paddle>(Port::Left).unwrap();
+        left_paddles.set_position(0, 1.0);
+        left_paddles.set_position(1, 0.0);
+        drop(left_paddles);
+
+        let mut right_paddles = console.controller_mut::<Paddle>(Port::Right).unwrap();
+        right_paddles.set_position(0, 0.75);
+        right_paddles.set_position(1, 2.0);
+        assert_eq!(right_paddles.position(1), 1.0);
+        drop(right_paddles);
+
+        assert_eq!(console.input_charge_time(0), Some(0));
+        assert_eq!(console.input_charge_time(1), Some(MAX_CHARGE_TIME));
+        assert_eq!(console.input_charge_time(2), Some(MAX_CHARGE_TIME / 4));
+        assert_eq!(console.input_charge_time(3), Some(0));
+
+        // Each of the four paddles has its own button.
+        assert_eq!(console.memory(SWCHA) & 0b1100_1100, 0b1100_1100);
+        console.controller_mut::<Paddle>(Port::Left).unwrap().press_button(1);
+        console.controller_mut::<Paddle>(Port::Right).unwrap().press_button(0);
+        assert_eq!(console.memory(SWCHA) & 0b1100_1100, 0b1000_0100);
+        console.controller_mut::<Paddle>(Port::Left).unwrap().release_button(1);
+        assert_eq!(console.memory(SWCHA) & 0b1100_1100, 0b1100_0100);
+
+        // The capacitors charge once they're no longer dumped to ground.
+        *console.memory_mut(VBLANK) = 0b1000_0000;
+        console.execute_cycle();
+        *console.memory_mut(VBLANK) = 0b0000_0000;
+        for _ in 0..MAX_CHARGE_TIME / 4 {
+            console.execute_cycle();
+        }
+
+        assert!(console.input_level(0));
+        assert!(!console.input_level(1));
+        assert!(console.input_level(2));
+        assert!(console.input_level(3));
+    }
+
+    #[test]
+    fn test_paddle_calibration() {
+        let calibration = PaddleCalibration::default();
+        assert_eq!(calibration.charge_time(1.0), 0);
+        assert_eq!(calibration.charge_time(0.0), MAX_CHARGE_TIME);
+
+        // A game reading the paddles from the scanline 10 to the scanline 110.
+        let calibration = PaddleCalibration::from_scanlines(110, 10);
+        assert_eq!(calibration, PaddleCalibration::new(760, 8360));
+        assert_eq!(calibration.charge_time(1.0), 760);
+        assert_eq!(calibration.charge_time(0.5), 4560);
+        assert_eq!(calibration.charge_time(-1.0), 8360);
+
+        // The sensitivity scales the range around its center.
+        assert_eq!(calibration.with_sensitivity(2.0), PaddleCalibration::new(2660, 6460));
+        assert_eq!(calibration.with_sensitivity(0.5), PaddleCalibration::new(0, 12160));
+        assert_eq!(calibration.with_sensitivity(1.0), calibration);
+
+        // The paddles apply their calibration to the pot inputs.
+        let mut console = Console::new(Cartridge::new(vec![]));
+        let mut paddles = Paddle::new();
+        paddles.set_position(0, 0.5);
+        paddles.set_calibration(calibration);
+        assert_eq!(paddles.calibration(), calibration);
+        assert_eq!(paddles.charge_time(0), 4560);
+
+        console.plug_controller(Port::Right, Box::new(paddles));
+        assert_eq!(console.input_charge_time(2), Some(4560));
+        assert_eq!(console.input_charge_time(3), Some(4560));
+    }
+
+    #[test]
+    fn test_paddle_state() {
+        let mut paddles = Paddle::new();
+        paddles.set_position(0, 0.3);
+        paddles.press_button(1);
+
+        let mut other_paddles = Paddle::new();
+        other_paddles.load_state(&paddles.save_state()).unwrap();
+        assert_eq!(other_paddles.position(0), 0.3);
+        assert_eq!(other_paddles.position(1), 0.5);
+        assert!(!other_paddles.is_button_pressed(0));
+        assert!(other_paddles.is_button_pressed(1));
     }
 }
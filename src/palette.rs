@@ -0,0 +1,178 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+//! Resolution of the colors of the objects.
+//!
+//! The TIA has four color registers shared by the six objects; each missile
+//! is drawn with the color of its player (COLUP0 and COLUP1) and the ball with
+//! the color of the playfield (COLUPF). This module is the one place where
+//! the registers are turned into RGB colors and where this sharing is
+//! enforced; the renderer never reads the color registers itself.
+//!
+//! The colors depend on the TV standard; the NTSC colors are the reference
+//! ones, the PAL colors are approximated with the closest NTSC hues, and the
+//! SECAM colors only depend on the luminance.
+//!
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+use crate::console::TvStandard;
+use crate::color::{to_rgb, color_and_luminance};
+use crate::location::{COLUP0, COLUP1, COLUPF, COLUBK};
+
+/// RGB color of a value of a color register.
+///
+/// The colors are computed once (see `to_rgb()`) and looked up afterward; the
+/// bit 0 of the value is unused.
+///
+pub(crate) fn ntsc_rgb(value: u8) -> (u8, u8, u8) {
+    static PALETTE: OnceLock<[(u8, u8, u8); 128]> = OnceLock::new();
+
+    let palette = PALETTE.get_or_init(|| {
+        let mut palette = [(0, 0, 0); 128];
+        for (index, color) in palette.iter_mut().enumerate() {
+            *color = to_rgb(color_and_luminance((index as u8) << 1));
+        }
+
+        palette
+    });
+
+    palette[(value >> 1) as usize]
+}
+
+// The NTSC hue closest to each PAL hue; the first two and the last two PAL
+// hues are shades of gray.
+const PAL_HUES: [u8; 16] = [
+    0x_0, 0x_0, 0x_1, 0x_D, 0x_2, 0x_C, 0x_4, 0x_B,
+    0x_5, 0x_A, 0x_6, 0x_9, 0x_7, 0x_8, 0x_0, 0x_0
+];
+
+// The eight colors of SECAM, one per luminance.
+const SECAM_COLORS: [(u8, u8, u8); 8] = [
+    (0x_00, 0x_00, 0x_00), // black
+    (0x_21, 0x_21, 0x_FF), // blue
+    (0x_F0, 0x_3C, 0x_79), // red
+    (0x_FF, 0x_50, 0x_FF), // magenta
+    (0x_7F, 0x_FF, 0x_00), // green
+    (0x_7F, 0x_FF, 0x_FF), // cyan
+    (0x_FF, 0x_FF, 0x_3F), // yellow
+    (0x_FF, 0x_FF, 0x_FF)  // white
+];
+
+/// RGB color of a value of a color register for a given TV standard.
+pub(crate) fn standard_rgb(standard: TvStandard, value: u8) -> (u8, u8, u8) {
+    match standard {
+        TvStandard::Ntsc => ntsc_rgb(value),
+        TvStandard::Pal => ntsc_rgb(PAL_HUES[(value >> 4) as usize] << 4 | (value & 0b0000_1110)),
+        TvStandard::Secam => SECAM_COLORS[((value & 0b0000_1110) >> 1) as usize]
+    }
+}
+
+/// The colors of the objects.
+///
+/// There are only four of them; the missiles are drawn with the color of
+/// their player, and the ball with the color of the playfield (see
+/// `PixelColor`).
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct ObjectColors {
+    pub(crate) player0: (u8, u8, u8),
+    pub(crate) player1: (u8, u8, u8),
+    pub(crate) playfield: (u8, u8, u8),
+    pub(crate) background: (u8, u8, u8)
+}
+
+impl ObjectColors {
+    /// Resolve the colors from the color registers (COLUP0, COLUP1, COLUPF and
+    /// COLUBK, in that order) for a given TV standard.
+    pub(crate) fn new(standard: TvStandard, registers: [u8; 4]) -> ObjectColors {
+        ObjectColors {
+            player0: standard_rgb(standard, registers[0]),
+            player1: standard_rgb(standard, registers[1]),
+            playfield: standard_rgb(standard, registers[2]),
+            background: standard_rgb(standard, registers[3])
+        }
+    }
+}
+
+/// A cache of the colors of the objects.
+///
+/// The colors are resolved again only when the color registers change, which
+/// the games usually do once per scanline at most; the pixels of a scanline
+/// are then drawn without converting any color. Since the cache is keyed by
+/// the values of the registers (and the TV standard), the changes in the
+/// middle of a scanline are honored.
+///
+#[derive(Default)]
+pub(crate) struct ColorCache {
+    entry: Cell<Option<(TvStandard, [u8; 4], ObjectColors)>>
+}
+
+impl ColorCache {
+    /// Colors of the objects for the given TV standard and color registers
+    /// (COLUP0, COLUP1, COLUPF and COLUBK, in that order).
+    pub(crate) fn colors(&self, standard: TvStandard, registers: [u8; 4]) -> ObjectColors {
+        match self.entry.get() {
+            Some((cached_standard, cached_registers, colors))
+                if cached_standard == standard && cached_registers == registers => colors,
+            _ => {
+                let colors = ObjectColors::new(standard, registers);
+                self.entry.set(Some((standard, registers, colors)));
+
+                colors
+            }
+        }
+    }
+}
+
+/// Addresses of the color registers, in the order the colors are resolved.
+pub(crate) const COLOR_REGISTERS: [u16; 4] = [COLUP0, COLUP1, COLUPF, COLUBK];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ntsc_rgb() {
+        for value in 0..=255_u8 {
+            assert_eq!(ntsc_rgb(value), to_rgb(color_and_luminance(value)));
+        }
+        assert_eq!(ntsc_rgb(0x_0E), (0x_EC, 0x_EC, 0x_EC));
+        assert_eq!(ntsc_rgb(0x_0F), ntsc_rgb(0x_0E));
+    }
+
+    #[test]
+    fn test_object_colors() {
+        let colors = ObjectColors::new(TvStandard::Ntsc, [0x_1E, 0x_44, 0x_86, 0x_00]);
+        assert_eq!(colors.player0, ntsc_rgb(0x_1E));
+        assert_eq!(colors.player1, ntsc_rgb(0x_44));
+        assert_eq!(colors.playfield, ntsc_rgb(0x_86));
+        assert_eq!(colors.background, (0, 0, 0));
+
+        // The cache follows the changes of the registers.
+        let cache = ColorCache::default();
+        assert_eq!(cache.colors(TvStandard::Ntsc, [0x_1E, 0x_44, 0x_86, 0x_00]), colors);
+        assert_eq!(cache.colors(TvStandard::Ntsc, [0x_1E, 0x_44, 0x_86, 0x_00]), colors);
+        assert_eq!(cache.colors(TvStandard::Ntsc, [0x_1E, 0x_44, 0x_86, 0x_0E]).background, (0x_EC, 0x_EC, 0x_EC));
+
+        // And the changes of the TV standard.
+        assert_eq!(cache.colors(TvStandard::Secam, [0x_1E, 0x_44, 0x_86, 0x_0E]).background, (0x_FF, 0x_FF, 0x_FF));
+    }
+
+    #[test]
+    fn test_standard_rgb() {
+        // The grays are the same, except with SECAM.
+        assert_eq!(standard_rgb(TvStandard::Pal, 0x_0A), ntsc_rgb(0x_0A));
+        assert_eq!(standard_rgb(TvStandard::Pal, 0x_EA), ntsc_rgb(0x_0A));
+        assert_eq!(standard_rgb(TvStandard::Secam, 0x_00), (0x_00, 0x_00, 0x_00));
+
+        // The hues differ.
+        assert_eq!(standard_rgb(TvStandard::Pal, 0x_44), ntsc_rgb(0x_24));
+        assert_eq!(standard_rgb(TvStandard::Secam, 0x_44), standard_rgb(TvStandard::Secam, 0x_A4));
+    }
+}
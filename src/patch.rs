@@ -0,0 +1,447 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cartridge::MAX_ROM_SIZE;
+use crate::utils::crc32;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+/// An error reported while reading or applying a patch.
+#[derive(Debug)]
+pub struct PatchError {
+    pub message: String
+}
+
+impl PatchError {
+    fn new(message: &str) -> PatchError {
+        PatchError {
+            message: message.to_string()
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<PatchError> for io::Error {
+    fn from(error: PatchError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// The format of a patch.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PatchFormat {
+    /// International Patching System; records of bytes to write at given
+    /// offsets.
+    Ips,
+    /// Beat patches; copies from the original and the patched ROM, with
+    /// checksums of both.
+    Bps
+}
+
+/// A patch of a ROM.
+///
+/// Patches are how ROM hacks and translations are distributed; they hold the
+/// differences between the original ROM and the modified one. Both the IPS
+/// and the BPS formats are supported and detected from the content of the
+/// patch. BPS patches carry the checksum of the ROM they apply to, which is
+/// verified.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Patch, TEST_ROM};
+/// # let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// let patch = Patch::from_file("translation.bps")?;
+/// let memory = patch.apply(&cartridge.memory)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// See `Cartridge::from_file_with_patch()` to patch a ROM as it's loaded.
+///
+pub struct Patch {
+    format: PatchFormat,
+    bytes: Vec<u8>
+}
+
+impl Patch {
+    /// Read a patch.
+    ///
+    /// This function detects the format of the patch from its header; it
+    /// fails if it's neither an IPS nor a BPS patch.
+    ///
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Patch, PatchError> {
+        let format = if bytes.starts_with(IPS_MAGIC) {
+            PatchFormat::Ips
+        } else if bytes.starts_with(BPS_MAGIC) {
+            PatchFormat::Bps
+        } else {
+            return Err(PatchError::new("unknown patch format"));
+        };
+
+        Ok(Patch { format, bytes })
+    }
+
+    /// Read a patch from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Patch> {
+        Ok(Patch::from_bytes(fs::read(path)?)?)
+    }
+
+    /// Format of the patch.
+    pub fn format(&self) -> PatchFormat {
+        self.format
+    }
+
+    /// Apply the patch.
+    ///
+    /// This function returns the patched version of the given ROM. It fails
+    /// if the patch is truncated or corrupted, or if it's a BPS patch made
+    /// for another ROM.
+    ///
+    pub fn apply(&self, rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+        match self.format {
+            PatchFormat::Ips => apply_ips(&self.bytes, rom),
+            PatchFormat::Bps => apply_bps(&self.bytes, rom)
+        }
+    }
+}
+
+struct PatchReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> PatchReader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], PatchError> {
+        let bytes = self.position.checked_add(count)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| PatchError::new("patch is truncated"))?;
+        self.position += count;
+
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, PatchError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_be(&mut self, count: usize) -> Result<usize, PatchError> {
+        Ok(self.read_bytes(count)?.iter().fold(0, |value, byte| (value << 8) | *byte as usize))
+    }
+
+    fn read_number(&mut self) -> Result<usize, PatchError> {
+        // Numbers of BPS patches are variable-length; 7 bits per byte, the
+        // last byte having its highest bit set.
+        let (mut value, mut shift) = (0_usize, 1_usize);
+        loop {
+            let byte = self.read_u8()?;
+            value = (byte as usize & 0x_7F).checked_mul(shift)
+                .and_then(|bits| value.checked_add(bits))
+                .ok_or_else(|| PatchError::new("number is too large"))?;
+            if byte & 0x_80 != 0 {
+                return Ok(value);
+            }
+
+            shift = shift.checked_shl(7).filter(|shift| *shift != 0)
+                .ok_or_else(|| PatchError::new("number is too large"))?;
+            value = value.checked_add(shift)
+                .ok_or_else(|| PatchError::new("number is too large"))?;
+        }
+    }
+}
+
+fn apply_ips(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = PatchReader { bytes: patch, position: IPS_MAGIC.len() };
+    let mut memory = rom.to_vec();
+
+    loop {
+        if reader.read_bytes(3)? == IPS_EOF {
+            break
+        }
+
+        reader.position -= 3;
+        let offset = reader.read_be(3)?;
+        let size = reader.read_be(2)?;
+
+        // Records with no size are run-length encoded.
+        let (size, bytes) = if size == 0 {
+            let size = reader.read_be(2)?;
+            (size, vec![reader.read_u8()?; size])
+        } else {
+            (size, reader.read_bytes(size)?.to_vec())
+        };
+
+        // The patched ROM is no larger than the largest cartridges; it's not
+        // extended before that's checked.
+        if offset + size > MAX_ROM_SIZE {
+            return Err(PatchError::new("patched ROM is too large"));
+        }
+
+        if memory.len() < offset + size {
+            memory.resize(offset + size, 0);
+        }
+        memory[offset..offset + size].copy_from_slice(&bytes);
+    }
+
+    // Some patches truncate the ROM after the end marker.
+    if reader.position + 3 <= patch.len() {
+        memory.truncate(reader.read_be(3)?);
+    }
+
+    Ok(memory)
+}
+
+fn apply_bps(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < BPS_MAGIC.len() + 12 {
+        return Err(PatchError::new("patch is truncated"));
+    }
+
+    let footer = patch.len() - 12;
+    let checksum = |index: usize| {
+        let bytes = &patch[footer + index * 4..footer + index * 4 + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+
+    if crc32(&patch[..footer + 8]) != checksum(2) {
+        return Err(PatchError::new("patch is corrupted"));
+    }
+    if crc32(rom) != checksum(0) {
+        return Err(PatchError::new("patch is made for another ROM"));
+    }
+
+    let mut reader = PatchReader { bytes: &patch[..footer], position: BPS_MAGIC.len() };
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_number()?;
+    let metadata_size = reader.read_number()?;
+    reader.read_bytes(metadata_size)?;
+
+    if source_size != rom.len() {
+        return Err(PatchError::new("patch is made for another ROM"));
+    }
+
+    // The patched ROM is no larger than the largest cartridges; it's not
+    // allocated before that's checked.
+    if target_size > MAX_ROM_SIZE {
+        return Err(PatchError::new("patched ROM is too large"));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0_usize, 0_usize);
+
+    let relative_offset = |reader: &mut PatchReader, offset: usize| -> Result<usize, PatchError> {
+        let number = reader.read_number()?;
+        let delta = number >> 1;
+        let offset = if number & 1 != 0 { offset.checked_sub(delta) } else { offset.checked_add(delta) };
+
+        offset.ok_or_else(|| PatchError::new("patch is corrupted"))
+    };
+
+    while reader.position < footer {
+        let action = reader.read_number()?;
+        let length = (action >> 2) + 1;
+
+        if target.len() + length > target_size {
+            return Err(PatchError::new("patch is corrupted"));
+        }
+
+        match action & 0b11 {
+            // Source read; copy the bytes at the same offset in the original.
+            0 => {
+                let start = target.len();
+                let bytes = rom.get(start..start + length)
+                    .ok_or_else(|| PatchError::new("patch is corrupted"))?;
+                target.extend_from_slice(bytes);
+            },
+            // Target read; copy the bytes of the patch.
+            1 => target.extend_from_slice(reader.read_bytes(length)?),
+            // Source copy; copy bytes from anywhere in the original.
+            2 => {
+                source_offset = relative_offset(&mut reader, source_offset)?;
+                let bytes = source_offset.checked_add(length)
+                    .and_then(|end| rom.get(source_offset..end))
+                    .ok_or_else(|| PatchError::new("patch is corrupted"))?;
+                target.extend_from_slice(bytes);
+                source_offset += length;
+            },
+            // Target copy; copy bytes already written, one at a time as the
+            // ranges may overlap.
+            _ => {
+                target_offset = relative_offset(&mut reader, target_offset)?;
+                for _ in 0..length {
+                    let byte = *target.get(target_offset)
+                        .ok_or_else(|| PatchError::new("patch is corrupted"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != checksum(1) {
+        return Err(PatchError::new("patched ROM doesn't match its checksum"));
+    }
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    fn bps_patch(rom: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend_from_slice(&[0x_80 | rom.len() as u8, 0x_80 | target.len() as u8, 0x_80]);
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32(rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_ips_patch() {
+        let rom = vec![0x_00, 0x_01, 0x_02, 0x_03, 0x_04, 0x_05];
+
+        // Write 2 bytes at offset 1, then 3 bytes of 0xFF at offset 6 (which
+        // extends the ROM).
+        let mut bytes = b"PATCH".to_vec();
+        bytes.extend_from_slice(&[0x_00, 0x_00, 0x_01, 0x_00, 0x_02, 0x_AA, 0x_BB]);
+        bytes.extend_from_slice(&[0x_00, 0x_00, 0x_06, 0x_00, 0x_00, 0x_00, 0x_03, 0x_FF]);
+        bytes.extend_from_slice(b"EOF");
+
+        let patch = Patch::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(patch.format(), PatchFormat::Ips);
+        assert_eq!(
+            patch.apply(&rom).unwrap(),
+            vec![0x_00, 0x_AA, 0x_BB, 0x_03, 0x_04, 0x_05, 0x_FF, 0x_FF, 0x_FF]
+        );
+
+        // The ROM can be truncated after the end marker.
+        let mut truncated = bytes.clone();
+        truncated.extend_from_slice(&[0x_00, 0x_00, 0x_04]);
+        assert_eq!(Patch::from_bytes(truncated).unwrap().apply(&rom).unwrap(), vec![0x_00, 0x_AA, 0x_BB, 0x_03]);
+
+        // Cartridges can be patched as they're loaded.
+        let directory = std::env::temp_dir();
+        let rom_path = directory.join(format!("atari-2600-patch-{}.bin", std::process::id()));
+        let patch_path = directory.join(format!("atari-2600-patch-{}.ips", std::process::id()));
+        fs::write(&rom_path, &rom).unwrap();
+        fs::write(&patch_path, &bytes).unwrap();
+
+        let cartridge = Cartridge::from_file_with_patch(&rom_path, &patch_path).unwrap();
+        assert_eq!(cartridge.memory.len(), 9);
+        assert_eq!(cartridge.memory[1], 0x_AA);
+
+        fs::write(&patch_path, b"NOTAPATCH").unwrap();
+        match Cartridge::from_file_with_patch(&rom_path, &patch_path) {
+            Err(error) => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("patch should be invalid")
+        }
+
+        fs::remove_file(&rom_path).unwrap();
+        fs::remove_file(&patch_path).unwrap();
+
+        // A record can't write beyond the largest cartridges.
+        let mut large = b"PATCH".to_vec();
+        large.extend_from_slice(&[0x_FF, 0x_FF, 0x_FF, 0x_00, 0x_01, 0x_AA]);
+        large.extend_from_slice(b"EOF");
+        assert_eq!(Patch::from_bytes(large).unwrap().apply(&rom).err().unwrap().message, "patched ROM is too large");
+
+        // Without the end marker, the patch is truncated.
+        bytes.truncate(bytes.len() - 3);
+        assert!(Patch::from_bytes(bytes).unwrap().apply(&rom).is_err());
+        assert!(Patch::from_bytes(b"NOTAPATCH".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_bps_patch() {
+        let rom = b"ABCDEFGH".to_vec();
+        let target = b"ABCxyGHxyGHxCD".to_vec();
+
+        let actions = [
+            // Source read of 3 bytes ("ABC").
+            0x_80 | (2 << 2),
+            // Target read of 2 bytes ("xy").
+            0x_80 | (1 << 2 | 1), b'x', b'y',
+            // Source copy of 2 bytes from offset 6 ("GH").
+            0x_80 | (1 << 2 | 2), 0x_80 | (6 << 1),
+            // Target copy of 5 bytes from offset 3 ("xyGHx", overlapping).
+            0x_80 | (4 << 2 | 3), 0x_80 | (3 << 1),
+            // Source copy of 2 bytes from offset 2 ("CD"); 6 bytes backward.
+            0x_80 | (1 << 2 | 2), 0x_80 | (6 << 1 | 1)
+        ];
+
+        let bytes = bps_patch(&rom, &target, &actions);
+        let patch = Patch::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(patch.format(), PatchFormat::Bps);
+        assert_eq!(patch.apply(&rom).unwrap(), target);
+
+        // The patch only applies to the original ROM.
+        let error = patch.apply(b"ABCDEFGX").unwrap_err();
+        assert_eq!(error.message, "patch is made for another ROM");
+
+        // Corrupted patches are detected.
+        let mut corrupted = bytes;
+        corrupted[8] ^= 0x_01;
+        let error = Patch::from_bytes(corrupted).unwrap().apply(&rom).unwrap_err();
+        assert_eq!(error.message, "patch is corrupted");
+
+        // Patches declaring a patched ROM larger than the largest cartridges
+        // are rejected before anything is allocated.
+        let mut large = BPS_MAGIC.to_vec();
+        large.extend_from_slice(&[0x_80 | rom.len() as u8]);
+        large.extend_from_slice(&[0x_00; 8]);
+        large.extend_from_slice(&[0x_80, 0x_80]);
+        large.extend_from_slice(&crc32(&rom).to_le_bytes());
+        large.extend_from_slice(&crc32(&target).to_le_bytes());
+        let checksum = crc32(&large);
+        large.extend_from_slice(&checksum.to_le_bytes());
+
+        let error = Patch::from_bytes(large).unwrap().apply(&rom).unwrap_err();
+        assert_eq!(error.message, "patched ROM is too large");
+
+        // Source copies out of the original ROM are corrupted.
+        let actions = [0x_80 | (1 << 2 | 2), 0x_80 | (60 << 1)];
+        let error = Patch::from_bytes(bps_patch(&rom, &target, &actions)).unwrap().apply(&rom).unwrap_err();
+        assert_eq!(error.message, "patch is corrupted");
+
+        // Sizes and numbers that overflow are rejected.
+        let raw_patch = |numbers: &[u8]| {
+            let mut patch = BPS_MAGIC.to_vec();
+            patch.extend_from_slice(numbers);
+            patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+            patch.extend_from_slice(&crc32(&target).to_le_bytes());
+            let checksum = crc32(&patch);
+            patch.extend_from_slice(&checksum.to_le_bytes());
+            Patch::from_bytes(patch).unwrap()
+        };
+
+        let mut numbers = vec![0x_80 | rom.len() as u8, 0x_80 | target.len() as u8];
+        numbers.extend_from_slice(&[0x_7F, 0x_7E, 0x_7E, 0x_7E, 0x_7E, 0x_7E, 0x_7E, 0x_7E, 0x_7E, 0x_80]);
+        let error = raw_patch(&numbers).apply(&rom).unwrap_err();
+        assert_eq!(error.message, "patch is truncated");
+
+        let error = raw_patch(&[0x_7F; 9]).apply(&rom).unwrap_err();
+        assert_eq!(error.message, "number is too large");
+    }
+}
@@ -0,0 +1,436 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::location::{SWCHA, SWBCNT, INTIM, INSTAT, TIM1T, T1024T};
+
+// The flags of the interrupt register (INSTAT); the timer underflowed, and an
+// active edge was detected on PA7.
+const TIMER_FLAG: u8 = 0b1000_0000;
+const EDGE_FLAG: u8  = 0b0100_0000;
+
+// The address line that enables the timer interrupt when the timer is read
+// or written.
+const INTERRUPT_LINE: u16 = 0x_0008;
+
+// The edge detect control of PA7 (written at 0x_0284 to 0x_0287); A0 selects
+// the active edge and A1 enables the interrupt.
+const EDGE_DETECT: u16 = 0x_0284;
+const POSITIVE_EDGE_LINE: u16 = 0x_0001;
+const EDGE_INTERRUPT_LINE: u16 = 0x_0002;
+
+/// The PIA chip (MOS 6532 RIOT).
+///
+/// The PIA holds the RAM of the console (128 bytes), the two I/O ports (the
+/// port A is wired to the controllers and the port B to the switches of the
+/// console) and the timer. It's addressed with the canonical addresses of the
+/// location module; the decoding of the bus is left to the console.
+///
+/// The timer is decremented once per interval (1, 8, 64 or 1024 cycles) and
+/// the PIA must be stepped once per CPU cycle.
+///
+/// The RAM is selected when A9 (the RS line) is low, which the console
+/// decodes; the PIA itself only looks at A0 to A4 of its registers. A3 is
+/// kept in the addresses of the timer (0x_0284 and 0x_0285 when reading,
+/// 0x_0294 to 0x_0297 when writing) as it enables the timer interrupt when
+/// set, and disables it when clear. Writing 0x_0284 to 0x_0287 sets the edge
+/// detect control of PA7 instead; A0 selects the positive edge, and A1
+/// enables the interrupt. The timer flag (bit 7 of INSTAT) is cleared when the
+/// timer is read or written, and the edge flag (bit 6) when INSTAT is read.
+///
+/// The following is intentionally left unimplemented.
+///
+/// - The IRQ line isn't connected on the console (the 6507 doesn't have one);
+///   the enabled interrupts are only reflected by `irq()`, in the unit tests.
+/// - The pins of the ports and their output registers are the same; the
+///   data direction registers (SWACNT and SWBCNT) don't mask what's read.
+/// - The timer flag is cleared even if the timer is read on the very cycle it
+///   underflows.
+///
+pub(crate) struct Pia {
+    pub(crate) ram: [u8; 128],  // from 0x_80 to 0x_FF
+    pub(crate) ports: [u8; 4],  // SWCHA, SWACNT, SWCHB and SWBCNT
+
+    pub(crate) timer_value: u8,
+    pub(crate) timer_status: u8, // only bit 7 and 6 are relevant
+    pub(crate) timer_interval: u32,
+    pub(crate) timer_elapsed_clocks: u32,
+
+    // Whether the timer must not be clocked; it's a little hack that we are
+    // forced to introduce because it would be inconvenient to know in advance
+    // how many cycles an instruction would take. We must not update the timer
+    // during the cycles that an instruction modifying the timer register is
+    // taking, otherwise the timer would be decremented prematurely.
+    pub(crate) timer_block: bool,
+
+    // Whether the timer and the edge detect interrupts are enabled, the
+    // active edge of PA7, and the level of PA7 when it was last sampled.
+    pub(crate) timer_interrupt: bool,
+    pub(crate) edge_interrupt: bool,
+    pub(crate) positive_edge: bool,
+    pub(crate) pa7_level: bool
+}
+
+impl Pia {
+    pub(crate) fn new() -> Pia {
+        Pia {
+            ram: [0; 128],
+            ports: [0; 4],
+
+            timer_value: 0,
+            timer_status: 0,
+            timer_interval: 1,
+            timer_elapsed_clocks: 1,
+            timer_block: true,
+
+            timer_interrupt: false,
+            edge_interrupt: false,
+            positive_edge: false,
+            pa7_level: false
+        }
+    }
+
+    /// Advance the PIA by one CPU cycle.
+    pub(crate) fn step(&mut self) {
+        // Detect the active edge on PA7, whether it's driven by a controller
+        // or by the console.
+        let pa7_level = self.ports[0] & 0b1000_0000 != 0;
+        if pa7_level != self.pa7_level && pa7_level == self.positive_edge {
+            self.timer_status |= EDGE_FLAG;
+        }
+        self.pa7_level = pa7_level;
+
+        if !self.timer_block {
+            clock_timer(
+                &mut self.timer_value,
+                &mut self.timer_status,
+                &mut self.timer_interval,
+                &mut self.timer_elapsed_clocks
+            );
+        }
+    }
+
+    /// Read a location of the PIA.
+    ///
+    /// This function reads the RAM, a port, or the timer (INTIM or INSTAT,
+    /// with or without A3); reading INTIM clears the timer flag and reading
+    /// INSTAT clears the edge flag.
+    ///
+    pub(crate) fn read(&mut self, address: u16) -> u8 {
+        match address {
+            0x_80..=0x_FF => self.ram[(address - 0x_80) as usize],
+            SWCHA..=SWBCNT => self.ports[(address - SWCHA) as usize],
+            _ if address & !INTERRUPT_LINE == INTIM => {
+                let (value, _) = self.read_timer();
+
+                self.timer_interrupt = address & INTERRUPT_LINE != 0;
+                self.timer_status &= !TIMER_FLAG;
+                value
+            },
+            _ if address & !INTERRUPT_LINE == INSTAT => {
+                let (_, status) = self.read_timer();
+
+                // Whenever the INSTAT register is read, the edge flag is
+                // reset.
+                self.timer_status &= !EDGE_FLAG;
+                status
+            },
+            _ => 0
+        }
+    }
+
+    /// Peek a location of the PIA.
+    ///
+    /// This function returns what `read()` would return, without clearing
+    /// the flags of the timer; it's how the emulator inspects the PIA.
+    ///
+    pub(crate) fn peek(&self, address: u16) -> u8 {
+        match address {
+            0x_80..=0x_FF => self.ram[(address - 0x_80) as usize],
+            SWCHA..=SWBCNT => self.ports[(address - SWCHA) as usize],
+            _ if address & !INTERRUPT_LINE == INTIM => self.read_timer().0,
+            _ if address & !INTERRUPT_LINE == INSTAT => self.read_timer().1,
+            _ => 0
+        }
+    }
+
+    /// Write a location of the PIA.
+    ///
+    /// This function writes the RAM, a port, starts the timer (TIM1T to
+    /// T1024T, with or without A3) with the given value, or sets the edge
+    /// detect control of PA7.
+    ///
+    pub(crate) fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x_80..=0x_FF => self.ram[(address - 0x_80) as usize] = value,
+            SWCHA..=SWBCNT => self.ports[(address - SWCHA) as usize] = value,
+            _ if (TIM1T..=T1024T).contains(&(address & !INTERRUPT_LINE)) => *self.start_timer(address) = value,
+            _ if address & !0x_0003 == EDGE_DETECT => self.set_edge_detect(address),
+            _ => ()
+        }
+    }
+
+    /// Set the edge detect control of PA7.
+    ///
+    /// This function selects the active edge of PA7 and enables its interrupt
+    /// according to the address which is written (0x_0284 to 0x_0287); the
+    /// value written doesn't matter.
+    ///
+    pub(crate) fn set_edge_detect(&mut self, address: u16) {
+        self.positive_edge = address & POSITIVE_EDGE_LINE != 0;
+        self.edge_interrupt = address & EDGE_INTERRUPT_LINE != 0;
+    }
+
+    /// Whether the IRQ line is asserted.
+    ///
+    /// This function returns whether a flag of INSTAT is set while its
+    /// interrupt is enabled; the line isn't connected on the console, so
+    /// it's only checked by the unit tests.
+    ///
+    #[cfg(test)]
+    pub(crate) fn irq(&self) -> bool {
+        (self.timer_interrupt && self.timer_status & TIMER_FLAG != 0) ||
+        (self.edge_interrupt && self.timer_status & EDGE_FLAG != 0)
+    }
+
+    /// The interrupt control of the PIA.
+    ///
+    /// This function packs the enabled interrupts, the active edge and the
+    /// level of PA7 into a byte (from bit 0 to 3), for the save states.
+    ///
+    pub(crate) fn control(&self) -> u8 {
+        self.timer_interrupt as u8 |
+        (self.edge_interrupt as u8) << 1 |
+        (self.positive_edge as u8) << 2 |
+        (self.pa7_level as u8) << 3
+    }
+
+    /// Restore the interrupt control of the PIA.
+    pub(crate) fn set_control(&mut self, control: u8) {
+        self.timer_interrupt = control & 0b0001 != 0;
+        self.edge_interrupt = control & 0b0010 != 0;
+        self.positive_edge = control & 0b0100 != 0;
+        self.pa7_level = control & 0b1000 != 0;
+    }
+
+    /// Start the timer.
+    ///
+    /// This function selects the interval of the timer according to the
+    /// register which is written (TIM1T to T1024T), enables its interrupt if
+    /// A3 is set, and returns the value of the timer to be written.
+    ///
+    pub(crate) fn start_timer(&mut self, address: u16) -> &mut u8 {
+        // Adjust the timer interval accordingly.
+        self.timer_interval = match address & !INTERRUPT_LINE {
+            0x_0294 => 1,
+            0x_0295 => 8,
+            0x_0296 => 64,
+            _ => 1024
+        };
+
+        self.timer_block = true;
+        self.timer_interrupt = address & INTERRUPT_LINE != 0;
+
+        // Whenever register TIM1T, TIM8T, TIM64T and T1024T are written, it
+        // resets the timer flag of INSTAT register.
+        self.timer_status &= !TIMER_FLAG;
+
+        self.timer_elapsed_clocks = 1;
+
+        // When those registers are written, it's actually updating the value
+        // of the INTIM register.
+        &mut self.timer_value
+    }
+
+    // The timer as it's read by an instruction.
+    //
+    // Instructions are executed at once when they start, but the timer is read
+    // on their last cycle, which is the 4th one because it's always addressed
+    // absolutely (it's out of the zero page). The value and the status of the
+    // timer are returned as they will be at that cycle.
+    pub(crate) fn read_timer(&self) -> (u8, u8) {
+        let mut value = self.timer_value;
+        let mut status = self.timer_status;
+        let mut interval = self.timer_interval;
+        let mut elapsed_clocks = self.timer_elapsed_clocks;
+
+        if !self.timer_block {
+            for _ in 0..4 {
+                clock_timer(&mut value, &mut status, &mut interval, &mut elapsed_clocks);
+            }
+        }
+
+        (value, status)
+    }
+}
+
+// Advance the timer of the PIA by one cycle.
+fn clock_timer(value: &mut u8, status: &mut u8, interval: &mut u32, elapsed_clocks: &mut u32) {
+
+    // When the elapsed clocks variable reaches 0, we must decrement the timer
+    // value.
+    *elapsed_clocks -= 1;
+    if *elapsed_clocks == 0 {
+
+        // If the timer value is 0, it's underflowing and we must update the
+        // timer status (bit 7).
+        if *value == 0 {
+
+            // The timer value reached 0, the timer is now entering the high
+            // speed decrement mode.
+            *interval = 1;
+
+            // Update the timer status.
+            *status |= TIMER_FLAG;
+        }
+
+        // Decrement the timer value.
+        *value = value.wrapping_sub(1);
+
+        // Adjust the elapsed clocks according to the current timer interval.
+        *elapsed_clocks = *interval;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::location::{SWCHB, TIM8T, TIM64T};
+
+    #[test]
+    fn test_ram() {
+        let mut pia = Pia::new();
+
+        // Each of the 128 bytes is distinct, and the ports aren't affected.
+        for address in 0x_80..=0x_FF {
+            pia.write(address, address as u8 ^ 0x_A5);
+        }
+        for address in 0x_80..=0x_FF {
+            assert_eq!(pia.read(address), address as u8 ^ 0x_A5);
+        }
+        assert_eq!(pia.ports, [0; 4]);
+
+        // Locations that aren't registers are ignored.
+        pia.write(0x_0298, 0x_42);
+        assert_eq!(pia.read(0x_0298), 0);
+        assert_eq!(pia.ram[0], 0x_80 ^ 0x_A5);
+    }
+
+    #[test]
+    fn test_pia() {
+        let mut pia = Pia::new();
+
+        // The ports are read as they were written.
+        pia.write(SWCHB, 0b0000_1011);
+        assert_eq!(pia.read(SWCHB), 0b0000_1011);
+
+        // The timer isn't clocked during the instruction which starts it.
+        pia.write(TIM8T, 2);
+        pia.step();
+        assert_eq!(pia.timer_value, 2);
+        assert_eq!(pia.timer_interval, 8);
+
+        // Once running, it's decremented every 8 cycles.
+        pia.timer_block = false;
+        pia.step();
+        assert_eq!(pia.timer_value, 1);
+        for _ in 0..8 {
+            pia.step();
+        }
+        assert_eq!(pia.timer_value, 0);
+
+        // It underflows and the interval drops to 1; reading INSTAT doesn't
+        // clear the timer flag, but reading INTIM does.
+        for _ in 0..8 {
+            pia.step();
+        }
+        assert_eq!(pia.timer_value, 0x_FF);
+        assert_eq!(pia.timer_interval, 1);
+        assert_eq!(pia.read(INSTAT), TIMER_FLAG);
+        assert_eq!(pia.read(INSTAT), TIMER_FLAG);
+
+        // Peeking the timer doesn't clear the flag either.
+        pia.timer_block = true;
+        assert_eq!(pia.peek(INTIM), 0x_FF);
+        assert_eq!(pia.peek(INSTAT), TIMER_FLAG);
+        assert_eq!(pia.read(INTIM), 0x_FF);
+        assert_eq!(pia.read(INSTAT), 0);
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        let mut pia = Pia::new();
+
+        // Writing the timer with A3 set enables the interrupt, which is raised
+        // when the timer underflows.
+        pia.write(TIM64T | INTERRUPT_LINE, 0);
+        assert!(pia.timer_interrupt);
+        assert_eq!(pia.timer_interval, 64);
+        assert!(!pia.irq());
+
+        pia.timer_block = false;
+        pia.step();
+        assert!(pia.irq());
+
+        // Reading the timer with A3 clear disables it (and clears the flag);
+        // reading it with A3 set enables it again.
+        pia.timer_status |= TIMER_FLAG;
+        pia.read(INTIM);
+        assert!(!pia.timer_interrupt);
+        assert!(!pia.irq());
+
+        pia.read(INTIM | INTERRUPT_LINE);
+        assert!(pia.timer_interrupt);
+
+        // Writing the timer with A3 clear disables it.
+        pia.write(TIM64T, 10);
+        assert!(!pia.timer_interrupt);
+        assert_eq!(pia.timer_interval, 64);
+        assert_eq!(pia.timer_value, 10);
+    }
+
+    #[test]
+    fn test_edge_detect() {
+        let mut pia = Pia::new();
+        pia.step();
+
+        // The negative edge is detected by default; a positive edge doesn't
+        // set the flag.
+        pia.ports[0] = 0b1000_0000;
+        pia.step();
+        assert_eq!(pia.timer_status & EDGE_FLAG, 0);
+
+        pia.ports[0] = 0b0000_0000;
+        pia.step();
+        assert_eq!(pia.timer_status & EDGE_FLAG, EDGE_FLAG);
+        assert!(!pia.irq());
+
+        // Reading INSTAT returns the flag and clears it.
+        assert_eq!(pia.read(INSTAT), EDGE_FLAG);
+        assert_eq!(pia.read(INSTAT), 0);
+
+        // Select the positive edge and enable the interrupt; the other pins
+        // don't matter.
+        pia.write(EDGE_DETECT | POSITIVE_EDGE_LINE | EDGE_INTERRUPT_LINE, 0);
+        assert!(pia.positive_edge);
+        assert!(pia.edge_interrupt);
+
+        pia.ports[0] = 0b0111_1111;
+        pia.step();
+        assert!(!pia.irq());
+
+        pia.ports[0] = 0b1000_0000;
+        pia.step();
+        assert!(pia.irq());
+
+        // The control survives the save states.
+        let mut other_pia = Pia::new();
+        other_pia.set_control(pia.control());
+        assert!(other_pia.positive_edge && other_pia.edge_interrupt && other_pia.pa7_level);
+        assert!(!other_pia.timer_interrupt);
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::thread::{self, JoinHandle};
+use std::sync::mpsc::{self, Sender, Receiver, SyncSender, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::emulator::Emulator;
+use crate::frame::Frame;
+
+// How long the emulation thread waits for commands between two ticks.
+const TICK_INTERVAL: Duration = Duration::from_millis(1);
+
+// Number of frames (and audio buffers) queued until the consumer takes them;
+// beyond, the new ones are dropped.
+const FRAME_QUEUE_CAPACITY: usize = 3;
+const AUDIO_QUEUE_CAPACITY: usize = 64;
+
+type Command = Box<dyn FnOnce(&mut Emulator) + Send>;
+
+/// The queue of the frames produced by the emulation thread.
+///
+/// It's consumed by the render thread; see `Pipeline::spawn()`.
+///
+pub struct FrameQueue {
+    frames: Receiver<Frame>
+}
+
+impl FrameQueue {
+    /// Wait for the next frame.
+    ///
+    /// This function blocks until the emulation thread produces a frame, and
+    /// returns it. It returns nothing once the emulation thread is stopped.
+    ///
+    pub fn wait(&self) -> Option<Frame> {
+        self.frames.recv().ok()
+    }
+
+    /// Take the most recent frame.
+    ///
+    /// This function returns the most recent of the queued frames, dropping
+    /// the older ones, or nothing if no frame was produced since the last
+    /// call; it doesn't block.
+    ///
+    pub fn latest(&self) -> Option<Frame> {
+        self.frames.try_iter().last()
+    }
+}
+
+/// The queue of the audio samples produced by the emulation thread.
+///
+/// It's consumed by the audio thread; see `Pipeline::spawn()`.
+///
+pub struct AudioQueue {
+    samples: Receiver<Vec<f32>>,
+    sample_rate: f64
+}
+
+impl AudioQueue {
+    /// Sample rate of the audio (in Hertz).
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Take the audio samples produced so far.
+    ///
+    /// This function returns the samples produced since it was last called,
+    /// in order; it doesn't block.
+    ///
+    pub fn take_samples(&self) -> Vec<f32> {
+        self.samples.try_iter().flatten().collect()
+    }
+}
+
+/// An emulator running on its own thread.
+///
+/// Presenting a frame usually blocks until the next vertical refresh of the
+/// display, and so does filling the audio device once its buffer is full.
+/// When the emulator runs on the same thread, it can't run while it's blocked,
+/// and the frames are paced irregularly. The pipeline runs the emulator on a
+/// thread of its own instead; it ticks continuously and pushes the frames and
+/// the audio samples to two queues, which are consumed by a render thread and
+/// an audio thread. The queues are the bounded channels of the standard
+/// library, which don't lock; when a consumer doesn't keep up, the new items
+/// are dropped instead of stalling the emulation.
+///
+/// The emulator isn't meant to be shared between threads; it's created on the
+/// emulation thread by the given function and it's only reached from the
+/// other threads through commands.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::thread;
+/// # use atari_2600::{Cartridge, Console, Emulator, Frame, Pipeline, TEST_ROM};
+/// # let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// # fn present_frame(_frame: Frame) {}
+/// # fn play_samples(_samples: Vec<f32>, _sample_rate: f64) {}
+/// let (pipeline, frames, audio) = Pipeline::spawn(move || Emulator::new(Console::new(cartridge)));
+///
+/// thread::spawn(move || {
+///     while let Some(frame) = frames.wait() {
+///         present_frame(frame); // blocks until the next refresh
+///     }
+/// });
+///
+/// thread::spawn(move || loop {
+///     play_samples(audio.take_samples(), audio.sample_rate());
+/// });
+///
+/// pipeline.send(|emulator| emulator.console_mut().press_reset_button());
+/// ```
+///
+/// The emulation thread is stopped when the pipeline is dropped.
+///
+pub struct Pipeline {
+    commands: Option<Sender<Command>>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl Pipeline {
+    /// Spawn the emulation thread.
+    ///
+    /// This function spawns the thread, creates the emulator on it with the
+    /// given function, and returns the pipeline along with the queues of the
+    /// frames and the audio samples, to be moved to the render and audio
+    /// threads. The frames are drawn by the emulator (the pause menu is drawn
+    /// over them).
+    ///
+    pub fn spawn<F>(create_emulator: F) -> (Pipeline, FrameQueue, AudioQueue)
+    where
+        F: FnOnce() -> Emulator + Send + 'static
+    {
+        let (commands, command_receiver) = mpsc::channel::<Command>();
+        let (frame_sender, frames) = mpsc::sync_channel(FRAME_QUEUE_CAPACITY);
+        let (audio_sender, samples) = mpsc::sync_channel(AUDIO_QUEUE_CAPACITY);
+        let (sample_rate_sender, sample_rate) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let mut emulator = create_emulator();
+            let _ = sample_rate_sender.send(emulator.console().audio_sample_rate());
+
+            run_emulation(&mut emulator, command_receiver, frame_sender, audio_sender);
+        });
+
+        let pipeline = Pipeline {
+            commands: Some(commands),
+            thread: Some(thread)
+        };
+
+        let audio = AudioQueue {
+            samples,
+            sample_rate: sample_rate.recv().unwrap_or_default()
+        };
+
+        (pipeline, FrameQueue { frames }, audio)
+    }
+
+    /// Send a command to the emulator.
+    ///
+    /// This function queues the given function, which is run with the
+    /// emulator on the emulation thread before its next tick; it doesn't wait
+    /// for it. It's how the inputs are sent to the emulator.
+    ///
+    pub fn send<F>(&self, command: F)
+    where
+        F: FnOnce(&mut Emulator) + Send + 'static
+    {
+        if let Some(commands) = &self.commands {
+            let _ = commands.send(Box::new(command));
+        }
+    }
+
+    /// Run a function with the emulator and wait for its result.
+    ///
+    /// This function is like `send()` but it waits for the function to be run
+    /// on the emulation thread and returns its result. It returns nothing if
+    /// the emulation thread is stopped.
+    ///
+    pub fn call<F, R>(&self, function: F) -> Option<R>
+    where
+        F: FnOnce(&mut Emulator) -> R + Send + 'static,
+        R: Send + 'static
+    {
+        let (result_sender, result) = mpsc::channel();
+        self.send(move |emulator| {
+            let _ = result_sender.send(function(emulator));
+        });
+
+        result.recv().ok()
+    }
+
+    /// Check whether the emulation thread is running.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Stop the emulation thread.
+    ///
+    /// This function stops the emulation thread and waits for it to finish;
+    /// the queues then report that the emulation is stopped once drained.
+    ///
+    pub fn stop(&mut self) {
+        self.commands = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_emulation(emulator: &mut Emulator, commands: Receiver<Command>, frames: SyncSender<Frame>, audio: SyncSender<Vec<f32>>) {
+    loop {
+        // Waiting for the commands is what paces the loop; the emulator
+        // advances by the time elapsed anyway.
+        let has_commands = match commands.recv_timeout(TICK_INTERVAL) {
+            Ok(command) => {
+                command(emulator);
+                for command in commands.try_iter() {
+                    command(emulator);
+                }
+                true
+            },
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => return
+        };
+
+        let summary = emulator.tick();
+
+        let samples = emulator.console_mut().take_audio_samples();
+        if !samples.is_empty() {
+            let _ = audio.try_send(samples);
+        }
+
+        // While paused, the frame only changes when the menu is navigated. A
+        // consumer lagging behind only drops frames.
+        if summary.has_new_frame() || (has_commands && emulator.is_paused()) {
+            let _ = frames.try_send(emulator.frame());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_pipeline() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let (mut pipeline, frames, audio) = Pipeline::spawn(move || {
+            Emulator::new(Console::new(Cartridge::new(rom)))
+        });
+        assert!(pipeline.is_running());
+        assert!((audio.sample_rate() - 31_400.0).abs() < 100.0);
+
+        // The frames are produced in order, by the emulation thread.
+        let first_frame = frames.wait().unwrap();
+        let second_frame = frames.wait().unwrap();
+        assert!(second_frame.number > first_frame.number);
+
+        assert!(!audio.take_samples().is_empty());
+
+        // The commands are run on the emulation thread, in order.
+        pipeline.send(|emulator| emulator.pause());
+        assert_eq!(pipeline.call(|emulator| emulator.is_paused()), Some(true));
+
+        pipeline.stop();
+        assert!(!pipeline.is_running());
+        assert_eq!(pipeline.call(|emulator| emulator.is_paused()), None);
+
+        while frames.latest().is_some() {}
+        assert!(frames.wait().is_none());
+    }
+}
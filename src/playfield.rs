@@ -14,52 +14,41 @@
 //! TODO; Write description of this module.
 //!
 use crate::location::{PF0, PF1, PF2, CTRLPF};
-use crate::console::Console;
+use crate::tia::Tia;
 use crate::utils::byte_to_boolean_array;
 
-pub(crate) fn playfield_mirror_mode(console: &Console) -> bool {
-    *console.memory(CTRLPF) & 0b000_0001 != 0
+pub(crate) fn playfield_mirror_mode(tia: &Tia) -> bool {
+    tia.register(CTRLPF) & 0b000_0001 != 0
 }
 
-pub(crate) fn playfield_priority(console: &Console) -> bool {
-    *console.memory(CTRLPF) & 0b0000_0100 != 0
+pub(crate) fn playfield_priority(tia: &Tia) -> bool {
+    tia.register(CTRLPF) & 0b0000_0100 != 0
 }
 
-pub(crate) fn playfield_color(console: &Console) -> (u8, u8, u8) {
-    crate::color::playfield_color(console)
+pub(crate) fn playfield_score_mode(tia: &Tia) -> bool {
+    tia.register(CTRLPF) & 0b0000_0010 != 0
 }
 
-pub(crate) fn playfield_left_color(console: &Console) -> (u8, u8, u8) {
-    crate::color::player0_color(console)
-}
-
-pub(crate) fn playfield_right_color(console: &Console) -> (u8, u8, u8) {
-    crate::color::player1_color(console)
-}
-
-pub(crate) fn playfield_score_mode(console: &Console) -> bool {
-    *console.memory(CTRLPF) & 0b0000_0010 != 0
-}
-
-pub(crate) fn playfield_bits(console: &Console) -> [bool; 20] {
-    let pf0_bits = byte_to_boolean_array(*console.memory(PF0));
-    let pf1_bits = byte_to_boolean_array(*console.memory(PF1));
-    let pf2_bits = byte_to_boolean_array(*console.memory(PF2));
+pub(crate) fn playfield_bits(tia: &Tia) -> [bool; 20] {
+    let pf0_bits = byte_to_boolean_array(tia.register(PF0));
+    let pf1_bits = byte_to_boolean_array(tia.register(PF1));
+    let pf2_bits = byte_to_boolean_array(tia.register(PF2));
 
+    // PF0 and PF2 are drawn from their low bit, but PF1 from its high bit.
     [
         pf0_bits[4],
         pf0_bits[5],
         pf0_bits[6],
         pf0_bits[7],
 
-        pf1_bits[0],
-        pf1_bits[1],
-        pf1_bits[2],
-        pf1_bits[3],
-        pf1_bits[4],
-        pf1_bits[5],
-        pf1_bits[6],
         pf1_bits[7],
+        pf1_bits[6],
+        pf1_bits[5],
+        pf1_bits[4],
+        pf1_bits[3],
+        pf1_bits[2],
+        pf1_bits[1],
+        pf1_bits[0],
 
         pf2_bits[0],
         pf2_bits[1],
@@ -74,8 +63,91 @@ pub(crate) fn playfield_bits(console: &Console) -> [bool; 20] {
 
 #[cfg(test)]
 mod test {
+    use crate::console::Console;
+    use crate::rom_builder::RomBuilder;
+
+    // Render a frame of a kernel setting the playfield registers, and return
+    // one of its lines as 40 characters (one per bit of the playfield); '.'
+    // is the background, 'P' the playfield color, and '0' and '1' the colors
+    // of the players. It panics if the 4 pixels of a bit don't have the same
+    // color.
+    fn render_line(ctrlpf: u8, playfield: [u8; 3]) -> String {
+        let source = format!("
+                lda #$1E
+                sta $06   ; COLUP0
+                lda #$44
+                sta $07   ; COLUP1
+                lda #$86
+                sta $08   ; COLUPF
+                lda #$00
+                sta $09   ; COLUBK
+                lda #${:02X}
+                sta $0A   ; CTRLPF
+                lda #${:02X}
+                sta $0D   ; PF0
+                lda #${:02X}
+                sta $0E   ; PF1
+                lda #${:02X}
+                sta $0F   ; PF2
+            loop:
+                jmp loop
+        ", ctrlpf, playfield[0], playfield[1], playfield[2]);
+
+        let mut console = Console::new(RomBuilder::new().code(0x_F000, &source).build().unwrap());
+        console.run_frame();
+        console.run_frame();
+
+        let colors = console.tia.object_colors();
+        let line = console.frame().pixels[100];
+
+        line.chunks(4).enumerate().map(|(index, pixels)| {
+            assert!(pixels.iter().all(|pixel| *pixel == pixels[0]),
+                "the pixels {} to {} differ with CTRLPF={:08b}", index * 4, index * 4 + 3, ctrlpf);
+
+            match pixels[0] {
+                color if color == colors.background => '.',
+                color if color == colors.playfield => 'P',
+                color if color == colors.player0 => '0',
+                color if color == colors.player1 => '1',
+                color => panic!("unexpected color {:?}", color)
+            }
+        }).collect()
+    }
 
     #[test]
-    fn test_playfield() {
+    fn test_playfield_seam() {
+        // The bits 4 and 5 of PF0, the bits 7 and 6 of PF1 (drawn from its
+        // high bit) and the bits 0 and 7 of PF2; the pattern isn't
+        // symmetric, so the reflected right half can't be mistaken for the
+        // repeated one, and the last bit of the left half meets the first bit
+        // of the right half at the seam (pixels 79 and 80).
+        let playfield = [0b0011_0000, 0b1100_0000, 0b1000_0001];
+
+        // The golden outputs, for every combination of the reflection (bit
+        // 0), score mode (bit 1) and priority (bit 2) bits of CTRLPF.
+        let goldens = [
+            (0b000, "PP..PP......P......PPP..PP......P......P"),
+            (0b001, "PP..PP......P......PP......P......PP..PP"),
+            (0b010, "00..00......0......011..11......1......1"),
+            (0b011, "00..00......0......01......1......11..11"),
+            (0b100, "PP..PP......P......PPP..PP......P......P"),
+            (0b101, "PP..PP......P......PP......P......PP..PP"),
+            (0b110, "PP..PP......P......PPP..PP......P......P"),
+            (0b111, "PP..PP......P......PP......P......PP..PP")
+        ];
+
+        for (ctrlpf, golden) in goldens {
+            assert_eq!(render_line(ctrlpf, playfield), golden, "CTRLPF={:03b}", ctrlpf);
+        }
+
+        // The ball size bits don't affect the playfield.
+        assert_eq!(render_line(0b0011_0001, playfield), goldens[1].1);
+
+        // A single bit on each side of the seam.
+        assert_eq!(render_line(0b000, [0b0001_0000, 0, 0b1000_0000]), "P..................PP..................P");
+        assert_eq!(render_line(0b001, [0b0001_0000, 0, 0b1000_0000]), "P..................PP..................P");
+        assert_eq!(render_line(0b001, [0, 0, 0b1000_0000]), "...................PP...................");
+        assert_eq!(render_line(0b000, [0, 0, 0b1000_0000]), "...................P...................P");
+        assert_eq!(render_line(0b011, [0, 0, 0b1000_0000]), "...................01...................");
     }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::utils::crc32;
+
+const SIGNATURE: [u8; 8] = [0x_89, b'P', b'N', b'G', 0x_0D, 0x_0A, 0x_1A, 0x_0A];
+
+// Maximum length of a stored (uncompressed) deflate block.
+const MAX_STORED_BLOCK: usize = 0x_FFFF;
+
+/// Encode RGB pixels to a PNG image.
+///
+/// This function returns a PNG image (8 bits per component, no transparency)
+/// of the given pixels, listed line by line. The image data isn't compressed
+/// (it uses stored deflate blocks) which keeps the encoder small; the images
+/// are meant for inspection and tests, not for storage.
+///
+pub(crate) fn encode_png(width: usize, height: usize, pixels: &[(u8, u8, u8)]) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "number of pixels doesn't match the size");
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // RGB, 8 bits per component.
+
+    // Each line starts with its filter type (none).
+    let mut data = Vec::with_capacity(height * (1 + width * 3));
+    for line in pixels.chunks(width.max(1)).take(height) {
+        data.push(0);
+        for &(red, green, blue) in line {
+            data.extend_from_slice(&[red, green, blue]);
+        }
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&data));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x_78, 0x_01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x_01, 0x_00, 0x_00, 0x_FF, 0x_FF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_png() {
+        assert_eq!(adler32(b"Wikipedia"), 0x_11E60398);
+
+        let pixels = vec![(0x_FF, 0x_00, 0x_00), (0x_00, 0x_FF, 0x_00), (0x_00, 0x_00, 0x_FF)];
+        let png = encode_png(3, 1, &pixels);
+
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(png[12..16], *b"IHDR");
+        assert_eq!(png[16..20], 3_u32.to_be_bytes());
+        assert_eq!(png[20..24], 1_u32.to_be_bytes());
+        assert_eq!(png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0x_AE, 0x_42, 0x_60, 0x_82]);
+
+        // The image data is a single stored block holding the filter type of
+        // the line followed by its pixels.
+        let idat = &png[33..];
+        assert_eq!(idat[4..8], *b"IDAT");
+        assert_eq!(idat[8..13], [0x_78, 0x_01, 0x_01, 10, 0]);
+        assert_eq!(idat[15..25], [0, 0x_FF, 0, 0, 0, 0x_FF, 0, 0, 0, 0x_FF]);
+
+        // Large images are split into several blocks.
+        let pixels = vec![(0x_12, 0x_34, 0x_56); 160 * 192];
+        let data_length = 192 * (1 + 160 * 3);
+        let png = encode_png(160, 192, &pixels);
+        let idat_length = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(idat_length, 2 + data_length + 5 * 2 + 4);
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+/// The levels of the signal pins of a controller port.
+///
+/// A controller port has 9 pins; 2 of them are the power and the ground, and
+/// the 7 others carry the signals the game reads; 4 data pins (pins 1 to 4)
+/// on the port A of the PIA, the trigger (pin 6) on an input port of the TIA,
+/// and 2 pot inputs (pins 9 and 5) on the pot inputs of the TIA. A level is
+/// high when it's `true`; the switches of the controllers ground the pins
+/// when they're pressed.
+///
+/// It's the lowest level of the controllers; it's what the console reads and
+/// writes with `port_pins()` and `set_port_pins()`, bypassing the
+/// `Controller` implementations. Projects bridging a real controller (over a
+/// serial link for instance) forward its pins every frame.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Port, PortPins, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # struct Serial;
+/// # impl Serial { fn read_pins(&mut self) -> std::io::Result<[bool; 9]> { Ok([true; 9]) } }
+/// # let mut serial = Serial;
+/// loop {
+///     let levels = serial.read_pins()?;
+///     console.set_port_pins(Port::Left, PortPins {
+///         data: [levels[0], levels[1], levels[2], levels[3]],
+///         trigger: levels[5],
+///         pots: [levels[8], levels[4]]
+///     });
+///     console.run_frame();
+/// #   break;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PortPins {
+    /// Pins 1 to 4; up, down, left and right with a joystick.
+    pub data: [bool; 4],
+    /// Pin 6; the button of a joystick.
+    pub trigger: bool,
+    /// Pins 9 and 5; the pot inputs of a pair of paddles.
+    pub pots: [bool; 2]
+}
+
+impl Default for PortPins {
+    /// The levels of a port where nothing is plugged; the data pins and the
+    /// trigger are pulled up, and the pot inputs never charge.
+    fn default() -> Self {
+        PortPins {
+            data: [true; 4],
+            trigger: true,
+            pots: [false; 2]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::{Console, Port};
+
+    #[test]
+    fn test_port_pins() {
+        // Read the port A, the trigger of the right port and a pot input of
+        // the left port, over and over.
+        let mut rom = assemble("
+            loop:
+                lda $0280 ; SWCHA
+                sta $80
+                lda $3D   ; INPT5
+                sta $81
+                lda $39   ; INPT1
+                sta $82
+                jmp loop
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        let left_pins = PortPins {
+            data: [false, true, true, false],
+            trigger: true,
+            pots: [false, true]
+        };
+        let right_pins = PortPins {
+            data: [true, false, true, true],
+            trigger: false,
+            pots: [false, false]
+        };
+        console.set_port_pins(Port::Left, left_pins);
+        console.set_port_pins(Port::Right, right_pins);
+        assert_eq!(console.port_pins(Port::Left), left_pins);
+        assert_eq!(console.port_pins(Port::Right), right_pins);
+
+        // The game reads the levels, and they remain as they were set.
+        console.run_frame();
+        assert_eq!(console.pia.ram[0], 0b0110_1101);
+        assert_eq!(console.pia.ram[1] & 0b1000_0000, 0);
+        assert_eq!(console.pia.ram[2] & 0b1000_0000, 0b1000_0000);
+        assert_eq!(console.port_pins(Port::Left), left_pins);
+
+        console.set_port_pins(Port::Left, PortPins::default());
+        assert_eq!(console.port_pins(Port::Left), PortPins::default());
+    }
+}
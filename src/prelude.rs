@@ -0,0 +1,33 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+//! The most used items of the crate.
+//!
+//! This module re-exports what most programs embedding the emulator need; the
+//! console, its cartridge, its controllers, its frames and the types of its
+//! inputs. Its content only grows between the minor versions, so it can be
+//! glob imported without breaking on an update.
+//!
+//! ```
+//! use atari_2600::prelude::*;
+//! ```
+//!
+
+pub use crate::cartridge::Cartridge;
+pub use crate::console::{Console, TvType, TvStandard, Player, Port, Difficulty};
+pub use crate::builder::ConsoleBuilder;
+pub use crate::controller::Controller;
+pub use crate::joystick::{Joystick, Direction};
+pub use crate::paddle::{Paddle, PaddleCalibration};
+pub use crate::keypad::Keypad;
+pub use crate::steering::Steering;
+pub use crate::booster_grip::BoosterGrip;
+pub use crate::action::Action;
+pub use crate::frame::Frame;
+pub use crate::summary::UpdateSummary;
+pub use crate::state::SaveState;
@@ -0,0 +1,266 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::any::Any;
+
+use crate::Console;
+use crate::Port;
+use crate::Controller;
+use crate::state::{StateWriter, StateError};
+use crate::location::VBLANK;
+
+/// The QuadTari adapter.
+///
+/// The QuadTari is an adapter used by modern homebrew games to plug two
+/// controllers in a single port, and thus, up to four controllers in the
+/// console. The game selects which of the two controllers is connected to the
+/// port with the bit 7 of VBLANK; the first controller when it's cleared and
+/// the second controller when it's set.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Joystick, Port, QuadTari, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut quadtari = QuadTari::new();
+/// quadtari.plug_controller(0, Box::new(Joystick::new()));
+/// quadtari.plug_controller(1, Box::new(Joystick::new()));
+///
+/// console.plug_controller(Port::Left, Box::new(quadtari));
+/// ```
+///
+pub struct QuadTari {
+    port: Port,
+    controllers: [Option<Box<dyn Controller>>; 2],
+    selected: usize
+}
+
+impl QuadTari {
+    /// Create a QuadTari adapter.
+    ///
+    /// The adapter is created without controllers plugged in.
+    ///
+    pub fn new() -> QuadTari {
+        QuadTari {
+            port: Port::Left,
+            controllers: [None, None],
+            selected: 0
+        }
+    }
+
+    /// Plug a controller in the adapter.
+    ///
+    /// This function plugs a controller in the first (0) or the second (1)
+    /// slot of the adapter.
+    ///
+    pub fn plug_controller(&mut self, index: usize, controller: Box<dyn Controller>) {
+        assert!(index < 2, "index must be 0 or 1");
+
+        self.controllers[index] = Some(controller);
+        if index == self.selected {
+            self.connect();
+        }
+    }
+
+    /// Unplug a controller from the adapter.
+    ///
+    /// This function unplugs the controller from the first (0) or the second
+    /// (1) slot of the adapter and returns it, if any.
+    ///
+    pub fn unplug_controller(&mut self, index: usize) -> Option<Box<dyn Controller>> {
+        assert!(index < 2, "index must be 0 or 1");
+
+        self.controllers[index].take().map(|mut controller| {
+            controller.unplugged();
+            controller
+        })
+    }
+
+    /// Plugged controller (mutable).
+    ///
+    /// This function returns the controller plugged in the first (0) or the
+    /// second (1) slot of the adapter if it's of the requested type.
+    ///
+    pub fn controller_mut<T: Controller>(&mut self, index: usize) -> Option<&mut T> {
+        assert!(index < 2, "index must be 0 or 1");
+
+        let controller: &mut dyn Controller = self.controllers[index].as_deref_mut()?;
+        let controller: &mut dyn Any = controller;
+        controller.downcast_mut::<T>()
+    }
+
+    /// Index of the controller connected to the port.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    fn select(&mut self, index: usize) {
+        if index == self.selected {
+            return
+        }
+
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.unplugged();
+        }
+
+        self.selected = index;
+        self.connect();
+    }
+
+    fn connect(&mut self) {
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.plugged(self.port);
+        }
+    }
+}
+
+impl Default for QuadTari {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controller for QuadTari {
+    fn plugged(&mut self, port: Port) {
+        self.port = port;
+        self.connect();
+    }
+
+    fn unplugged(&mut self) {
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.unplugged();
+        }
+    }
+
+    fn apply(&mut self, console: &mut Console) {
+        // Only the selected controller drives the port.
+        self.select((console.memory(VBLANK) & 0b1000_0000 != 0) as usize);
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.apply(console);
+        }
+    }
+
+    fn update(&mut self, console: &mut Console) {
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.update(console);
+        }
+    }
+
+    fn dump_changed(&mut self, console: &mut Console, is_dumped: bool) {
+        self.select(is_dumped as usize);
+        if let Some(controller) = self.controllers[self.selected].as_mut() {
+            controller.apply(console);
+        }
+    }
+
+    fn save_state(&self) -> StateWriter {
+        let mut state = StateWriter::new();
+        state.write_u8(self.selected as u8);
+        for controller in self.controllers.iter() {
+            state.write_bool(controller.is_some());
+            if let Some(controller) = controller {
+                state.write_state(&controller.save_state());
+            }
+        }
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        let selected = reader.read_u8()? as usize;
+        if selected > 1 {
+            return Err(StateError::new("invalid QuadTari selection"));
+        }
+
+        for controller in self.controllers.iter_mut() {
+            match (controller, reader.read_bool()?) {
+                (Some(controller), true) => controller.load_state(&reader.read_state()?)?,
+                (None, false) => {},
+                _ => return Err(StateError::new("plugged controllers don't match the state"))
+            }
+        }
+
+        self.select(selected);
+        self.connect();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::joystick::{Joystick, Direction};
+
+    #[test]
+    fn test_quadtari() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        let mut first = Joystick::new();
+        first.press_direction(Direction::Up);
+
+        let mut second = Joystick::new();
+        second.press_direction(Direction::Down);
+        second.press_button();
+
+        let mut quadtari = QuadTari::new();
+        quadtari.plug_controller(0, Box::new(first));
+        quadtari.plug_controller(1, Box::new(second));
+        console.plug_controller(Port::Left, Box::new(quadtari));
+
+        // The first controller is selected when the dump bit is cleared.
+        console.execute_cycle();
+        assert!(!console.port_a_pin(4));
+        assert!(console.port_a_pin(5));
+        assert!(console.input_level(4));
+
+        // The second controller is selected when the dump bit is set.
+        *console.memory_mut(VBLANK) = 0b1000_0000;
+        console.execute_cycle();
+        assert!(console.port_a_pin(4));
+        assert!(!console.port_a_pin(5));
+        assert!(!console.input_level(4));
+
+        // Only the selected controller drives the port.
+        *console.memory_mut(VBLANK) = 0b0000_0000;
+        console.execute_cycle();
+
+        let mut quadtari = console.controller_mut::<QuadTari>(Port::Left).unwrap();
+        assert_eq!(quadtari.selected(), 0);
+        quadtari.controller_mut::<Joystick>(1).unwrap().press_direction(Direction::Left);
+        drop(quadtari);
+        assert!(console.port_a_pin(6));
+
+        let mut quadtari = console.controller_mut::<QuadTari>(Port::Left).unwrap();
+        quadtari.controller_mut::<Joystick>(0).unwrap().press_button();
+        drop(quadtari);
+        assert!(!console.input_level(4));
+    }
+
+    #[test]
+    fn test_quadtari_state() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        let mut quadtari = QuadTari::new();
+        quadtari.plug_controller(0, Box::new(Joystick::new()));
+        quadtari.plug_controller(1, Box::new(Joystick::new()));
+        console.plug_controller(Port::Left, Box::new(quadtari));
+
+        let mut quadtari = console.controller_mut::<QuadTari>(Port::Left).unwrap();
+        quadtari.controller_mut::<Joystick>(1).unwrap().press_button();
+        let state = quadtari.save_state();
+
+        // The states of both controllers are restored.
+        quadtari.controller_mut::<Joystick>(1).unwrap().release_button();
+        quadtari.load_state(&state).unwrap();
+        assert!(quadtari.controller_mut::<Joystick>(1).unwrap().is_button_pressed());
+
+        quadtari.unplug_controller(1);
+        assert!(quadtari.load_state(&state).is_err());
+    }
+}
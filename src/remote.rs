@@ -0,0 +1,538 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::action::Action;
+use crate::console::Port;
+use crate::emulator::Emulator;
+use crate::joystick::Joystick;
+
+// How long a connection may take to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The largest request accepted (headers and body).
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+// How long the reset and select switches are held (in frames).
+const SWITCH_TAP_FRAMES: u32 = 5;
+
+// The most instructions and frames run by a single request; the server runs
+// them before answering, and meanwhile the emulator is blocked.
+const MAX_STEP_COUNT: u32 = 100_000;
+const MAX_FRAME_COUNT: u32 = 600;
+
+/// A response of the remote control server.
+///
+/// The body is JSON, except for the screenshots which are PNG images.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct RemoteResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>
+}
+
+impl RemoteResponse {
+    fn json(status: u16, body: String) -> RemoteResponse {
+        RemoteResponse {
+            status,
+            content_type: "application/json",
+            body: body.into_bytes()
+        }
+    }
+
+    fn error(status: u16, message: &str) -> RemoteResponse {
+        let message = message.replace('\\', "\\\\").replace('"', "\\\"");
+        RemoteResponse::json(status, format!("{{\"error\":\"{}\"}}", message))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error"
+        }
+    }
+}
+
+/// An HTTP server to control the emulator remotely.
+///
+/// The server exposes the debugging facilities of the emulator to external
+/// tools (IDE plugins, CI scripts, etc.); they pause and step it, read its
+/// memory, take screenshots and drive the joystick of the left port with
+/// plain HTTP requests. The responses are JSON objects.
+///
+/// | Request                           | Effect                                       |
+/// |-----------------------------------|----------------------------------------------|
+/// | `GET /state`                      | Whether it's paused, the frame, etc.         |
+/// | `GET /registers`                  | Registers and flags of the CPU               |
+/// | `GET /memory?address=&length=`    | Bytes of the RAM (from 0x80 to 0xFF)         |
+/// | `GET /screenshot`                 | Current frame (PNG image)                    |
+/// | `POST /pause`, `POST /resume`     | Pause or resume the emulator                 |
+/// | `POST /step?count=`               | Execute instructions (up to 100000)          |
+/// | `POST /frame?count=`              | Run frames (up to 600)                       |
+/// | `POST /input?action=`             | Apply an action (its index or its name)      |
+/// | `POST /reset`, `POST /select`     | Tap the reset or the select switch           |
+/// | `POST /assemble?address=`         | Assemble the body (see `poke_assembly()`)    |
+/// | `POST /breakpoint?address=`       | Add a breakpoint                             |
+/// | `DELETE /breakpoint?address=`     | Remove a breakpoint                          |
+/// | `DELETE /breakpoints`             | Remove all breakpoints                       |
+///
+/// The numbers are decimal or hexadecimal (prefixed with `0x`); stepping and
+/// running frames work even if the emulator is paused. As the
+/// emulator isn't shared between threads, the server doesn't run in the
+/// background; it's polled by the loop running the emulator, which handles the
+/// pending requests in between two ticks. There are no pushed events; the
+/// clients poll the state.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, RemoteServer, TEST_ROM};
+/// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// let mut server = RemoteServer::bind("127.0.0.1:6502")?;
+/// loop {
+///     server.poll(&mut emulator)?;
+///     emulator.tick();
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// The server isn't authenticated; it should only be bound to the loopback
+/// interface.
+///
+pub struct RemoteServer {
+    listener: TcpListener
+}
+
+impl RemoteServer {
+    /// Bind the server to an address.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<RemoteServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(RemoteServer { listener })
+    }
+
+    /// Address the server is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Handle the pending requests.
+    ///
+    /// This function handles the requests of the pending connections (one per
+    /// connection, which is closed afterwards) and returns their number. It
+    /// doesn't wait for new connections; it fails if the server can't accept
+    /// connections anymore, the errors of the connections themselves are
+    /// ignored.
+    ///
+    pub fn poll(&mut self, emulator: &mut Emulator) -> io::Result<usize> {
+        let mut count = 0;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = serve_connection(stream, emulator);
+                    count += 1;
+                },
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(count),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+}
+
+fn serve_connection(mut stream: TcpStream, emulator: &mut Emulator) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+
+    let response = match read_request(&mut stream)? {
+        Ok((method, target, body)) => handle_request(emulator, &method, &target, &body),
+        Err(response) => response
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, response.reason(), response.content_type, response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+// Read some bytes of a request, failing if the deadline has passed.
+fn read_before(stream: &mut TcpStream, buffer: &mut [u8], deadline: Instant) -> io::Result<usize> {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    if timeout.is_zero() {
+        return Err(io::ErrorKind::TimedOut.into());
+    }
+
+    stream.set_read_timeout(Some(timeout))?;
+    stream.read(buffer)
+}
+
+// The method, the target and the body of a request.
+type Request = (String, String, Vec<u8>);
+
+// Read the method, the target and the body of a request.
+//
+// The whole request must be received within the timeout, no matter how slowly
+// its bytes trickle in. A request that is too large or whose body length can't
+// be parsed is answered with the returned error response.
+fn read_request(stream: &mut TcpStream) -> io::Result<Result<Request, RemoteResponse>> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut bytes = Vec::new();
+    let mut buffer = [0; 1024];
+
+    let header_end = loop {
+        if let Some(position) = bytes.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        if bytes.len() > MAX_REQUEST_SIZE {
+            return Ok(Err(RemoteResponse::error(413, "the request is too large")));
+        }
+
+        match read_before(stream, &mut buffer, deadline)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            count => bytes.extend_from_slice(&buffer[..count])
+        }
+    };
+
+    let header = String::from_utf8_lossy(&bytes[..header_end]).into_owned();
+    let mut lines = header.split("\r\n");
+
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+
+    // Only a missing Content-Length header means there is no body.
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.trim().parse::<usize>());
+
+    let content_length = match content_length {
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Ok(Err(RemoteResponse::error(400, "invalid Content-Length header"))),
+        None => 0
+    };
+
+    match content_length.checked_add(header_end + 4) {
+        Some(size) if size <= MAX_REQUEST_SIZE => {},
+        _ => return Ok(Err(RemoteResponse::error(413, "the request is too large")))
+    }
+
+    let mut body = bytes.split_off(header_end + 4);
+    while body.len() < content_length {
+        match read_before(stream, &mut buffer, deadline)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            count => body.extend_from_slice(&buffer[..count])
+        }
+    }
+    body.truncate(content_length);
+
+    Ok(Ok((method, target, body)))
+}
+
+/// Handle a request of the remote control server.
+///
+/// This function does what the server does with a request (see
+/// `RemoteServer`), minus the HTTP transport; it lets other transports (a
+/// pipe, a WebSocket of another server, etc.) reuse the same commands.
+///
+pub fn handle_request(emulator: &mut Emulator, method: &str, target: &str, body: &[u8]) -> RemoteResponse {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let parameter = |name: &str| {
+        query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    };
+    let number = |name: &str, default: Option<u32>| -> Result<u32, RemoteResponse> {
+        match parameter(name) {
+            Some(value) => parse_number(value)
+                .ok_or_else(|| RemoteResponse::error(400, &format!("invalid {} parameter", name))),
+            None => default.ok_or_else(|| RemoteResponse::error(400, &format!("missing {} parameter", name)))
+        }
+    };
+
+    let address = |name: &str| -> Result<u16, RemoteResponse> {
+        let address = number(name, None)?;
+        u16::try_from(address).map_err(|_| RemoteResponse::error(400, &format!("invalid {} parameter", name)))
+    };
+    let count = |maximum: u32| -> Result<u32, RemoteResponse> {
+        match number("count", Some(1))? {
+            count if count > maximum => Err(RemoteResponse::error(400, &format!("the count must be at most {}", maximum))),
+            count => Ok(count)
+        }
+    };
+
+    let result = match (method, path) {
+        ("GET", "/state") => Ok(state_json(emulator)),
+        ("GET", "/registers") => Ok(registers_json(emulator)),
+        ("GET", "/memory") => (|| {
+            let address = address("address")?;
+            let length = number("length", Some(1))?;
+            let end = match u32::from(address).checked_add(length) {
+                Some(end) if address >= 0x_80 && end <= 0x_100 => end as u16,
+                _ => return Err(RemoteResponse::error(400, "the memory must be in the RAM (from 0x80 to 0xFF)"))
+            };
+
+            let bytes = (address..end)
+                .map(|address| emulator.console().ram(address).to_string())
+                .collect::<Vec<_>>();
+            Ok(format!("{{\"address\":{},\"bytes\":[{}]}}", address, bytes.join(",")))
+        })(),
+        ("GET", "/screenshot") => {
+            return RemoteResponse {
+                status: 200,
+                content_type: "image/png",
+                body: emulator.frame().to_png()
+            };
+        },
+        ("POST", "/pause") => {
+            emulator.pause();
+            Ok(state_json(emulator))
+        },
+        ("POST", "/resume") => {
+            emulator.resume();
+            Ok(state_json(emulator))
+        },
+        ("POST", "/step") => count(MAX_STEP_COUNT).map(|count| {
+            let cycles: u64 = (0..count).map(|_| u64::from(emulator.console_mut().step_instruction())).sum();
+            format!("{{\"cycles\":{},\"registers\":{}}}", cycles, registers_json(emulator))
+        }),
+        ("POST", "/frame") => count(MAX_FRAME_COUNT).map(|count| {
+            for _ in 0..count {
+                emulator.console_mut().run_frame();
+            }
+            state_json(emulator)
+        }),
+        ("POST", "/input") => (|| {
+            let value = parameter("action").ok_or_else(|| RemoteResponse::error(400, "missing action parameter"))?;
+            let action = parse_action(value).ok_or_else(|| RemoteResponse::error(400, "invalid action parameter"))?;
+
+            let mut joystick = emulator.console_mut().controller_mut::<Joystick>(Port::Left)
+                .ok_or_else(|| RemoteResponse::error(409, "no joystick is plugged in the left port"))?;
+            joystick.perform_action(action);
+
+            Ok(format!("{{\"action\":{}}}", action.index()))
+        })(),
+        ("POST", "/reset") => {
+            emulator.console_mut().tap_reset(SWITCH_TAP_FRAMES);
+            Ok("{}".to_string())
+        },
+        ("POST", "/select") => {
+            emulator.console_mut().tap_select(SWITCH_TAP_FRAMES);
+            Ok("{}".to_string())
+        },
+        ("POST", "/assemble") => (|| {
+            let address = address("address")?;
+            let source = String::from_utf8_lossy(body);
+            let size = emulator.console_mut().poke_assembly(address, &source)
+                .map_err(|error| RemoteResponse::error(400, &error.to_string()))?;
+
+            Ok(format!("{{\"size\":{}}}", size))
+        })(),
+        ("POST", "/breakpoint") => address("address").map(|address| {
+            emulator.console_mut().add_breakpoint(address);
+            "{}".to_string()
+        }),
+        ("DELETE", "/breakpoint") => address("address").map(|address| {
+            emulator.console_mut().remove_breakpoint(address);
+            "{}".to_string()
+        }),
+        ("DELETE", "/breakpoints") => {
+            emulator.console_mut().clear_breakpoints();
+            Ok("{}".to_string())
+        },
+        (_, "/state") | (_, "/registers") | (_, "/memory") | (_, "/screenshot") |
+        (_, "/pause") | (_, "/resume") | (_, "/step") | (_, "/frame") | (_, "/input") |
+        (_, "/reset") | (_, "/select") | (_, "/assemble") | (_, "/breakpoint") | (_, "/breakpoints") => {
+            Err(RemoteResponse::error(405, "method not allowed"))
+        },
+        _ => Err(RemoteResponse::error(404, "not found"))
+    };
+
+    match result {
+        Ok(body) => RemoteResponse::json(200, body),
+        Err(response) => response
+    }
+}
+
+fn state_json(emulator: &Emulator) -> String {
+    let console = emulator.console();
+    format!(
+        "{{\"paused\":{},\"frame\":{},\"program_counter\":{},\"bank\":{},\"jammed\":{}}}",
+        emulator.is_paused(),
+        emulator.frame().number,
+        console.program_counter(),
+        console.current_bank(),
+        console.is_jammed()
+    )
+}
+
+fn registers_json(emulator: &Emulator) -> String {
+    let cpu = emulator.console().cpu();
+    format!(
+        "{{\"program_counter\":{},\"accumulator\":{},\"x\":{},\"y\":{},\"stack_pointer\":{},\
+        \"negative\":{},\"overflow\":{},\"break\":{},\"decimal\":{},\"interrupt\":{},\"zero\":{},\"carry\":{}}}",
+        cpu.program_counter, cpu.accumulator, cpu.x_register, cpu.y_register, cpu.stack_pointer,
+        cpu.negative_flag, cpu.overflow_flag, cpu.break_flag, cpu.decimal_flag,
+        cpu.interrupt_flag, cpu.zero_flag, cpu.carry_flag
+    )
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hexadecimal) => u32::from_str_radix(hexadecimal, 16).ok(),
+        None => value.parse().ok()
+    }
+}
+
+// An action is given by its index or its name (case insensitive).
+fn parse_action(value: &str) -> Option<Action> {
+    match value.parse::<usize>() {
+        Ok(index) => Action::from_index(index),
+        Err(_) => Action::ALL.iter().copied().find(|action| format!("{:?}", action).eq_ignore_ascii_case(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::fixtures;
+
+    fn emulator() -> Emulator {
+        let mut rom = fixtures::counter_rom(0x_80);
+        rom[0x_FFC] = 0x_00;
+        rom[0x_FFD] = 0x_F0;
+
+        Emulator::new(Console::new(Cartridge::new(rom)))
+    }
+
+    fn body(response: &RemoteResponse) -> &str {
+        std::str::from_utf8(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_handle_request() {
+        let mut emulator = emulator();
+
+        let response = handle_request(&mut emulator, "POST", "/pause", b"");
+        assert_eq!(response.status, 200);
+        assert!(body(&response).starts_with("{\"paused\":true,"));
+
+        // INC $80 takes 5 cycles.
+        let response = handle_request(&mut emulator, "POST", "/step", b"");
+        assert!(body(&response).starts_with("{\"cycles\":5,\"registers\":{\"program_counter\":61442,"));
+
+        let response = handle_request(&mut emulator, "GET", "/memory?address=0x80&length=2", b"");
+        assert_eq!(body(&response), "{\"address\":128,\"bytes\":[1,0]}");
+        assert_eq!(handle_request(&mut emulator, "GET", "/memory?address=0xFF&length=2", b"").status, 400);
+        assert_eq!(handle_request(&mut emulator, "GET", "/memory?address=0x80&length=0xFFFFFFFF", b"").status, 400);
+        assert_eq!(handle_request(&mut emulator, "GET", "/memory?address=0x10080", b"").status, 400);
+
+        assert_eq!(handle_request(&mut emulator, "POST", "/step?count=100001", b"").status, 400);
+        assert_eq!(handle_request(&mut emulator, "POST", "/frame?count=601", b"").status, 400);
+
+        assert_eq!(handle_request(&mut emulator, "POST", "/input?action=fire", b"").status, 409);
+        emulator.console_mut().plug_controller(Port::Left, Box::new(Joystick::new()));
+
+        let response = handle_request(&mut emulator, "POST", "/input?action=upfire", b"");
+        assert_eq!(body(&response), "{\"action\":10}");
+        assert_eq!(handle_request(&mut emulator, "POST", "/input?action=18", b"").status, 400);
+
+        let response = handle_request(&mut emulator, "GET", "/screenshot", b"");
+        assert_eq!(response.content_type, "image/png");
+        assert_eq!(response.body, emulator.frame().to_png());
+
+        assert_eq!(handle_request(&mut emulator, "POST", "/breakpoint?address=0xF002", b"").status, 200);
+        assert_eq!(handle_request(&mut emulator, "POST", "/breakpoint", b"").status, 400);
+        assert_eq!(handle_request(&mut emulator, "POST", "/breakpoint?address=0x1F002", b"").status, 400);
+        assert_eq!(handle_request(&mut emulator, "GET", "/breakpoint?address=0xF002", b"").status, 405);
+        assert_eq!(handle_request(&mut emulator, "GET", "/nothing", b"").status, 404);
+
+        let response = handle_request(&mut emulator, "POST", "/assemble?address=0x80", b"brk");
+        assert_eq!(body(&response), "{\"size\":1}");
+    }
+
+    #[test]
+    fn test_remote_server() {
+        let mut emulator = emulator();
+        let mut server = RemoteServer::bind("127.0.0.1:0").unwrap();
+        assert_eq!(server.poll(&mut emulator).unwrap(), 0);
+
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream.write_all(b"POST /pause HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n").unwrap();
+
+        while server.poll(&mut emulator).unwrap() == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(emulator.is_paused());
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"paused\":true,\"frame\":0,\"program_counter\":61440,\"bank\":0,\"jammed\":false}"));
+
+        // A request declaring a body larger than the address space is too
+        // large, not a crash.
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream.write_all(b"POST /pause HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n").unwrap();
+
+        while server.poll(&mut emulator).unwrap() == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        // A body length that can't be parsed is a bad request.
+        for length in ["abc", "-1"] {
+            let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+            write!(stream, "POST /pause HTTP/1.1\r\nContent-Length: {}\r\n\r\n", length).unwrap();
+
+            while server.poll(&mut emulator).unwrap() == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+        }
+
+        // A request sent a byte at a time can't hold the emulator past the
+        // timeout.
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        let sender = std::thread::spawn(move || {
+            for byte in b"GET /state HTTP/1.1\r\n".iter().cycle().take(20) {
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        });
+
+        let start = Instant::now();
+        while server.poll(&mut emulator).unwrap() == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(start.elapsed() < REQUEST_TIMEOUT + Duration::from_millis(500));
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::console::Console;
+use crate::location::SWCHB;
+use crate::state::{SaveState, StateWriter, StateReader, StateVersion, StateError};
+use crate::state::{REPLAY_MAGIC, write_controllers, read_controllers};
+use crate::determinism::{FrameChecksum, DeterminismError, compare_checksums};
+
+// The inputs of a frame; the state of the controllers and of the switches of
+// the console (SWCHB).
+#[derive(Clone)]
+struct ReplayFrame {
+    controllers: [Option<StateWriter>; 2],
+    switches: u8
+}
+
+/// A replay of a game session.
+///
+/// A replay starts from a save state of the console and records the inputs
+/// of each frame; the internal state of the controllers (which includes the
+/// buttons being pressed but also the position of an analog controller for
+/// instance) and the switches of the console. Because the simulation is
+/// deterministic, applying the same inputs from the same state reproduces the
+/// session exactly.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Replay, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut replay = Replay::new(&console);
+/// loop {
+///     // Update the controllers...
+///     replay.record_frame(&console);
+///     console.run_frame();
+/// #   break;
+/// }
+///
+/// replay.play(&mut console)?;
+/// # Ok::<(), atari_2600::StateError>(())
+/// ```
+///
+/// Like save states, replays are serialized with `to_bytes()` and replays
+/// written by older versions of the crate remain loadable with
+/// `from_bytes()`.
+///
+#[derive(Clone)]
+pub struct Replay {
+    initial_state: SaveState,
+    frames: Vec<ReplayFrame>
+}
+
+impl Replay {
+    /// Start recording a replay.
+    ///
+    /// This function creates a replay starting from the current state of the
+    /// console.
+    ///
+    pub fn new(console: &Console) -> Replay {
+        Replay {
+            initial_state: console.save_state(),
+            frames: Vec::new()
+        }
+    }
+
+    /// Record the inputs of a frame.
+    ///
+    /// This function must be called right before each frame is run to record
+    /// the inputs applied during that frame.
+    ///
+    pub fn record_frame(&mut self, console: &Console) {
+        self.frames.push(ReplayFrame {
+            controllers: console.controller_states(),
+            switches: console.memory(SWCHB)
+        });
+    }
+
+    /// State of the console when the replay starts.
+    pub fn initial_state(&self) -> &SaveState {
+        &self.initial_state
+    }
+
+    /// Number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check whether no frames were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Rewind the console to the beginning of the replay.
+    ///
+    /// This function restores the state of the console when the replay
+    /// started. The plugged controllers must be the same as when it was
+    /// recorded.
+    ///
+    pub fn rewind(&self, console: &mut Console) -> Result<(), StateError> {
+        console.load_state(&self.initial_state)
+    }
+
+    /// Apply the inputs of a frame.
+    ///
+    /// This function restores the inputs recorded for the given frame
+    /// (starting from 0); the frame must be run afterward.
+    ///
+    pub fn apply_frame(&self, index: usize, console: &mut Console) -> Result<(), StateError> {
+        let frame = &self.frames[index];
+
+        *console.memory_mut(SWCHB) = frame.switches;
+        console.load_controller_states(&frame.controllers)
+    }
+
+    /// Play the replay.
+    ///
+    /// This function rewinds the console to the beginning of the replay, then
+    /// runs all the recorded frames with their inputs.
+    ///
+    pub fn play(&self, console: &mut Console) -> Result<(), StateError> {
+        self.rewind(console)?;
+
+        for index in 0..self.frames.len() {
+            self.apply_frame(index, console)?;
+            console.run_frame();
+        }
+
+        Ok(())
+    }
+
+    /// Play the replay and checksum its frames.
+    ///
+    /// This function plays the replay in the verification mode and returns the
+    /// checksums of the frames.
+    ///
+    pub fn checksums(&self, console: &mut Console) -> Result<Vec<FrameChecksum>, StateError> {
+        self.rewind(console)?;
+        console.enable_checksums();
+
+        let result = self.play(console);
+        let checksums = console.disable_checksums();
+
+        result.map(|_| checksums)
+    }
+
+    /// Serialize the replay.
+    ///
+    /// This function returns the replay serialized in the current version of
+    /// the format (see `StateVersion`).
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        StateVersion::write(&mut writer, REPLAY_MAGIC);
+        self.initial_state.write(&mut writer);
+
+        writer.write_u32(self.frames.len() as u32);
+        for frame in self.frames.iter() {
+            write_controllers(&mut writer, &frame.controllers);
+            writer.write_u8(frame.switches);
+        }
+
+        writer.as_bytes().to_vec()
+    }
+
+    /// Deserialize a replay.
+    ///
+    /// This function reads a replay serialized by `to_bytes()`, possibly by
+    /// an older version of the crate, and migrates it to the current version
+    /// of the format.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, StateError> {
+        let mut reader = StateReader::new(bytes);
+        let version = StateVersion::read(&mut reader, REPLAY_MAGIC)?;
+        let initial_state = SaveState::read(&mut reader, version)?;
+
+        let count = reader.read_u32()?;
+        let mut frames = Vec::new();
+        for _ in 0..count {
+            frames.push(ReplayFrame {
+                controllers: read_controllers(&mut reader)?,
+                switches: reader.read_u8()?
+            });
+        }
+
+        if !reader.is_empty() {
+            return Err(StateError::new("replay has trailing bytes"));
+        }
+
+        Ok(Replay {
+            initial_state,
+            frames
+        })
+    }
+
+    /// Verify the replay is deterministic.
+    ///
+    /// This function plays the replay in the verification mode and compares
+    /// the checksums of its frames with the expected ones (from an earlier run,
+    /// another version of the emulator or another machine). It reports the
+    /// first frame that differs.
+    ///
+    pub fn verify(&self, console: &mut Console, expected: &[FrameChecksum]) -> Result<(), DeterminismError> {
+        let checksums = self.checksums(console)?;
+        compare_checksums(expected, &checksums)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::assembler::assemble;
+    use crate::console::Port;
+    use crate::trackball::{Trackball, TrackballMode};
+
+    #[test]
+    fn test_replay() {
+        // Accumulate the value of the port A in the RAM.
+        let mut rom = assemble("
+            start:
+                lda $80
+                clc
+                adc $0280 ; SWCHA
+                sta $80
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.plug_controller(Port::Left, Box::new(Trackball::new(TrackballMode::AmigaMouse)));
+
+        let mut replay = Replay::new(&console);
+        for index in 0..10 {
+            // The position of the mouse is internal to the controller.
+            console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(index % 3, 1);
+
+            replay.record_frame(&console);
+            console.run_frame();
+        }
+        assert_eq!(replay.len(), 10);
+
+        let ram = console.save_state().ram;
+        let frame = console.frame();
+
+        // Moving the mouse afterward doesn't matter; the replay restores its
+        // state.
+        console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(1, 1);
+
+        replay.play(&mut console).unwrap();
+        assert_eq!(console.save_state().ram, ram);
+        assert_eq!(console.frame().number, frame.number);
+        assert!(console.frame().pixels == frame.pixels);
+
+        // The replay is deterministic.
+        let checksums = replay.checksums(&mut console).unwrap();
+        assert_eq!(checksums.len(), 10);
+        assert_eq!(checksums[9].frame, 10);
+        assert!(replay.verify(&mut console, &checksums).is_ok());
+
+        // The replay is the same once serialized.
+        let other_replay = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(other_replay.len(), 10);
+        assert!(other_replay.verify(&mut console, &checksums).is_ok());
+        assert!(Replay::from_bytes(&replay.initial_state.to_bytes()).is_err());
+
+        // Frames differ if the inputs differ.
+        let mut other_replay = Replay::new(&console);
+        other_replay.frames = replay.frames.clone();
+        other_replay.initial_state = replay.initial_state.clone();
+        other_replay.frames[4] = other_replay.frames[3].clone();
+
+        match other_replay.verify(&mut console, &checksums) {
+            Err(DeterminismError::Mismatch { frame, expected, actual }) => {
+                assert_eq!(frame, 5);
+                assert_ne!(expected.unwrap().rolling_hash, actual.unwrap().rolling_hash);
+            },
+            _ => panic!("frames should differ")
+        }
+
+        // The replay can't be played with different controllers.
+        console.unplug_controller(Port::Left);
+        assert!(replay.play(&mut console).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::fs;
+
+use crate::cartridge::Cartridge;
+use crate::console::Console;
+use crate::database::DatabaseError;
+
+/// An extractor of the score and the lives of a game.
+///
+/// The console doesn't know anything about the games; this trait is
+/// implemented for each game to derive its score and lives from its RAM. It's
+/// what the reinforcement learning environments and the test harnesses use
+/// to compute the rewards (see `RewardTracker`).
+///
+pub trait RewardExtractor {
+    /// Score of the player.
+    fn score(&self, console: &Console) -> i64;
+
+    /// Lives left, if the game has lives.
+    fn lives(&self, console: &Console) -> Option<u32> {
+        let _ = console;
+        None
+    }
+}
+
+/// How a score is stored in the RAM.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScoreEncoding {
+    /// Two decimal digits per byte (one per nibble), like most games do.
+    Bcd,
+    /// A binary number.
+    Binary
+}
+
+/// A score stored in the RAM.
+///
+/// The score is made of the bytes at the given RAM locations, the most
+/// significant one first.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScoreLocation {
+    pub addresses: Vec<u16>,
+    pub encoding: ScoreEncoding
+}
+
+/// A number of lives stored in the RAM.
+///
+/// The lives are the bits of the mask of the byte at the given RAM location
+/// (shifted to the right), plus an offset for the games that count the lives
+/// in reserve.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LivesLocation {
+    pub address: u16,
+    pub mask: u8,
+    pub offset: i32
+}
+
+/// The rewards of a game derived from known RAM locations.
+///
+/// This is the extractor of the games described by the reward database; the
+/// score and the lives are read from the RAM as they're laid out by the game.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct RamRewards {
+    pub md5: String,
+    pub name: String,
+    pub score: ScoreLocation,
+    pub lives: Option<LivesLocation>
+}
+
+impl RewardExtractor for RamRewards {
+    fn score(&self, console: &Console) -> i64 {
+        self.score.addresses.iter().fold(0, |score, address| {
+            let value = console.ram(*address) as i64;
+            match self.score.encoding {
+                ScoreEncoding::Bcd => score * 100 + (value >> 4) * 10 + (value & 0x_0F),
+                ScoreEncoding::Binary => score * 256 + value
+            }
+        })
+    }
+
+    fn lives(&self, console: &Console) -> Option<u32> {
+        self.lives.map(|lives| {
+            let value = (console.ram(lives.address) & lives.mask) >> lives.mask.trailing_zeros();
+            (value as i32 + lives.offset).max(0) as u32
+        })
+    }
+}
+
+/// The reward database.
+///
+/// The reward database identifies games from the MD5 digest of their ROM and
+/// tells where their score and lives are in the RAM. It's loaded from a text
+/// file where each line describes a game with the following fields separated
+/// by the `|` character; the MD5 digest, the name, the score, and optionally,
+/// the lives. The score is its encoding (`bcd` or `binary`) followed by the
+/// RAM locations of its bytes, the most significant one first. The lives are
+/// a RAM location, optionally followed by a mask (`&`) and an offset (`+` or
+/// `-`). Empty lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// # md5|name|score[|lives]
+/// 0123456789abcdef0123456789abcdef|Some Game|bcd 0xCC 0xCD|0xB9
+/// 0123456789abcdef0123456789abcdef|Other Game|binary 0x90|0x9F&0x0F+1
+/// ```
+///
+pub struct RewardDatabase {
+    entries: HashMap<String, RamRewards>
+}
+
+impl RewardDatabase {
+    /// Create an empty database.
+    pub fn new() -> RewardDatabase {
+        RewardDatabase {
+            entries: HashMap::new()
+        }
+    }
+
+    /// Parse a database.
+    ///
+    /// This function parses the content of a reward database file (see the
+    /// format above) and returns the database.
+    ///
+    pub fn parse(text: &str) -> Result<RewardDatabase, DatabaseError> {
+        let mut database = RewardDatabase::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let error = |message: &str| DatabaseError {
+                line: index + 1,
+                message: message.to_string()
+            };
+
+            let fields: Vec<&str> = line.split('|').map(|field| field.trim()).collect();
+            if fields.len() != 3 && fields.len() != 4 {
+                return Err(error("expected 3 or 4 fields"));
+            }
+
+            let score = parse_score(fields[2]).ok_or_else(|| error("invalid score"))?;
+            let lives = match fields.get(3) {
+                Some(field) => Some(parse_lives(field).ok_or_else(|| error("invalid lives"))?),
+                None => None
+            };
+
+            database.insert(RamRewards {
+                md5: fields[0].to_lowercase(),
+                name: fields[1].to_string(),
+                score,
+                lives
+            });
+        }
+
+        Ok(database)
+    }
+
+    /// Load a database from a file.
+    ///
+    /// This function reads and parses a reward database file; parsing errors
+    /// are reported as invalid data.
+    ///
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<RewardDatabase> {
+        let text = fs::read_to_string(path)?;
+        RewardDatabase::parse(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Insert an entry.
+    ///
+    /// This function adds a game to the database, replacing the previous
+    /// entry with the same MD5 digest.
+    ///
+    pub fn insert(&mut self, entry: RamRewards) {
+        self.entries.insert(entry.md5.clone(), entry);
+    }
+
+    /// Look up a game.
+    ///
+    /// This function returns the rewards of the game contained in the
+    /// cartridge, if it's known by the database.
+    ///
+    pub fn lookup(&self, cartridge: &Cartridge) -> Option<&RamRewards> {
+        self.entries.get(&cartridge.md5())
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the database is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for RewardDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tracker of the rewards of a game.
+///
+/// This structure turns the score of a game into rewards; the reward of a
+/// step is how much the score changed since the previous step.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Action, Cartridge, Console, Joystick, Port, RamRewards, RewardDatabase, RewardTracker, ScoreEncoding, ScoreLocation, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # console.plug_controller(Port::Left, Box::new(Joystick::new()));
+/// # let mut database = RewardDatabase::new();
+/// # database.insert(RamRewards {
+/// #     md5: console.cartridge().md5(),
+/// #     name: "Rainbow".to_string(),
+/// #     score: ScoreLocation { addresses: vec![0x_80], encoding: ScoreEncoding::Binary },
+/// #     lives: None
+/// # });
+/// # let action = Action::Fire;
+/// let rewards = database.lookup(console.cartridge()).unwrap().clone();
+/// let mut tracker = RewardTracker::new(Box::new(rewards), &console);
+///
+/// console.apply_action(action);
+/// console.run_frame();
+/// let reward = tracker.reward(&console);
+/// ```
+///
+pub struct RewardTracker {
+    extractor: Box<dyn RewardExtractor>,
+    score: i64
+}
+
+impl RewardTracker {
+    /// Create a tracker.
+    ///
+    /// This function creates a tracker that computes the rewards from the
+    /// current score of the game.
+    ///
+    pub fn new(extractor: Box<dyn RewardExtractor>, console: &Console) -> RewardTracker {
+        let score = extractor.score(console);
+        RewardTracker { extractor, score }
+    }
+
+    /// Reward since the previous step.
+    ///
+    /// This function returns how much the score changed since it was last
+    /// called (or since the tracker was created).
+    ///
+    pub fn reward(&mut self, console: &Console) -> i64 {
+        let score = self.extractor.score(console);
+        let reward = score - self.score;
+        self.score = score;
+
+        reward
+    }
+
+    /// Lives left, if the game has lives.
+    pub fn lives(&self, console: &Console) -> Option<u32> {
+        self.extractor.lives(console)
+    }
+
+    /// Check whether the game is over.
+    ///
+    /// This function returns whether the player has no lives left; it's never
+    /// over for the games without lives.
+    ///
+    pub fn is_game_over(&self, console: &Console) -> bool {
+        self.lives(console) == Some(0)
+    }
+}
+
+fn parse_address(text: &str) -> Option<u16> {
+    let address = parse_byte(text)? as u16;
+    if address >= 0x_80 { Some(address) } else { None }
+}
+
+fn parse_byte(text: &str) -> Option<u8> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u8::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+fn parse_score(text: &str) -> Option<ScoreLocation> {
+    let mut words = text.split_whitespace();
+    let encoding = match words.next()? {
+        "bcd" => ScoreEncoding::Bcd,
+        "binary" => ScoreEncoding::Binary,
+        _ => return None
+    };
+
+    let addresses = words.map(parse_address).collect::<Option<Vec<u16>>>()?;
+    if addresses.is_empty() {
+        return None
+    }
+
+    Some(ScoreLocation { addresses, encoding })
+}
+
+fn parse_lives(text: &str) -> Option<LivesLocation> {
+    let (text, offset) = match text.find(['+', '-']) {
+        Some(index) => (&text[..index], text[index..].parse().ok()?),
+        None => (text, 0)
+    };
+    let (address, mask) = match text.split_once('&') {
+        Some((address, mask)) => (address, parse_byte(mask)?),
+        None => (text, 0b1111_1111)
+    };
+
+    if mask == 0 {
+        return None
+    }
+
+    Some(LivesLocation { address: parse_address(address)?, mask, offset })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rewards() {
+        let cartridge = Cartridge::new(vec![0x_4C, 0x_00, 0x_F0]);
+        let text = format!(
+            "# A comment.\n\n{}|Test Game|bcd 0x80 0x81|0x82&0xF0-1\nfoo|Other Game|binary 0x90",
+            cartridge.md5().to_uppercase()
+        );
+        let database = RewardDatabase::parse(&text).unwrap();
+        assert_eq!(database.len(), 2);
+
+        let rewards = database.lookup(&cartridge).unwrap().clone();
+        assert_eq!(rewards.name, "Test Game");
+        assert_eq!(rewards.score, ScoreLocation { addresses: vec![0x_80, 0x_81], encoding: ScoreEncoding::Bcd });
+        assert_eq!(rewards.lives, Some(LivesLocation { address: 0x_82, mask: 0x_F0, offset: -1 }));
+
+        let mut console = Console::new(cartridge);
+        *console.memory_mut(0x_80) = 0x_12;
+        *console.memory_mut(0x_81) = 0x_34;
+        *console.memory_mut(0x_82) = 0x_3F;
+        assert_eq!(rewards.score(&console), 1234);
+        assert_eq!(rewards.lives(&console), Some(2));
+
+        // The rewards are the changes of the score.
+        let mut tracker = RewardTracker::new(Box::new(rewards), &console);
+        assert_eq!(tracker.reward(&console), 0);
+
+        *console.memory_mut(0x_81) = 0x_84;
+        assert_eq!(tracker.reward(&console), 50);
+        assert_eq!(tracker.reward(&console), 0);
+
+        *console.memory_mut(0x_82) = 0x_1F;
+        assert!(tracker.is_game_over(&console));
+
+        // Binary scores, and games without lives.
+        let rewards = database.entries.get("foo").unwrap();
+        *console.memory_mut(0x_90) = 0x_FF;
+        assert_eq!(rewards.score(&console), 255);
+        assert_eq!(rewards.lives(&console), None);
+
+        let error = RewardDatabase::parse("foo|bar").err().unwrap();
+        assert_eq!(error.message, "expected 3 or 4 fields");
+
+        let error = RewardDatabase::parse("foo|bar|decimal 0x80").err().unwrap();
+        assert_eq!(error.message, "invalid score");
+
+        let error = RewardDatabase::parse("\nfoo|bar|bcd 0x80|0x10").err().unwrap();
+        assert_eq!((error.line, error.message.as_str()), (2, "invalid lives"));
+    }
+}
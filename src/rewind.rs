@@ -0,0 +1,367 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::collections::VecDeque;
+
+use crate::state::{SaveState, StateError};
+
+/// Default number of frames between two keyframes.
+pub(crate) const DEFAULT_KEYFRAME_INTERVAL: usize = 60;
+
+/// Default memory budget of the history (in bytes).
+pub(crate) const DEFAULT_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+// A state is either stored in full (a keyframe), or as the difference with
+// the state of the previous frame (XOR'ed and run-length encoded).
+enum Snapshot {
+    Keyframe(Vec<u8>),
+    Delta(Vec<u8>)
+}
+
+struct HistoryFrame {
+    snapshot: Snapshot,
+    audio: Vec<f32>
+}
+
+impl HistoryFrame {
+    fn size(&self) -> usize {
+        let snapshot = match &self.snapshot {
+            Snapshot::Keyframe(bytes) | Snapshot::Delta(bytes) => bytes.len()
+        };
+
+        snapshot + self.audio.len() * std::mem::size_of::<f32>()
+    }
+}
+
+/// A history of the states of the console, frame by frame.
+///
+/// The states are stored serialized and, as consecutive states barely differ,
+/// most of them are stored as the difference with the previous one; the bytes
+/// of both states are XOR'ed and the runs of zeroes are skipped. A full state
+/// (a keyframe) is stored at regular intervals, and whenever the difference
+/// isn't worth it, so restoring a state never replays too many differences.
+///
+/// The history keeps a number of frames but drops the oldest ones beyond a
+/// memory budget; the oldest frame is always a keyframe.
+///
+pub(crate) struct RewindHistory {
+    frames: VecDeque<HistoryFrame>,
+    capacity: usize,
+    memory_budget: usize,
+    keyframe_interval: usize,
+    frames_since_keyframe: usize,
+    memory_usage: usize,
+
+    // The serialized state of the most recent frame.
+    last_state: Vec<u8>
+}
+
+impl RewindHistory {
+    pub(crate) fn new() -> RewindHistory {
+        RewindHistory {
+            frames: VecDeque::new(),
+            capacity: 0,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            keyframe_interval: DEFAULT_KEYFRAME_INTERVAL,
+            frames_since_keyframe: 0,
+            memory_usage: 0,
+            last_state: Vec::new()
+        }
+    }
+
+    /// Number of frames in the history.
+    pub(crate) fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Number of frames the history keeps (0 disables it).
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    pub(crate) fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    pub(crate) fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = bytes;
+        self.shrink();
+    }
+
+    pub(crate) fn keyframe_interval(&self) -> usize {
+        self.keyframe_interval
+    }
+
+    pub(crate) fn set_keyframe_interval(&mut self, frames: usize) {
+        self.keyframe_interval = frames.max(1);
+    }
+
+    /// Memory taken by the frames (in bytes).
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.frames.clear();
+        self.frames_since_keyframe = 0;
+        self.memory_usage = 0;
+        self.last_state.clear();
+    }
+
+    /// Record a frame.
+    pub(crate) fn push(&mut self, state: &SaveState, audio: &[f32]) {
+        let bytes = state.to_bytes();
+
+        // States of different sizes (a controller was plugged, etc.) can't be
+        // XOR'ed, and a delta larger than half the state isn't worth it.
+        let delta = match self.frames.is_empty() || self.frames_since_keyframe + 1 >= self.keyframe_interval {
+            true => None,
+            false => xor_rle_encode(&self.last_state, &bytes).filter(|delta| delta.len() < bytes.len() / 2)
+        };
+
+        let snapshot = match delta {
+            Some(delta) => {
+                self.frames_since_keyframe += 1;
+                Snapshot::Delta(delta)
+            },
+            None => {
+                self.frames_since_keyframe = 0;
+                Snapshot::Keyframe(bytes.clone())
+            }
+        };
+
+        let frame = HistoryFrame {
+            snapshot,
+            audio: audio.to_vec()
+        };
+
+        self.memory_usage += frame.size();
+        self.frames.push_back(frame);
+        self.last_state = bytes;
+
+        self.shrink();
+    }
+
+    /// Remove the most recent frame.
+    ///
+    /// This function returns the audio of the removed frame.
+    ///
+    pub(crate) fn pop_back(&mut self) -> Option<Vec<f32>> {
+        let frame = self.frames.pop_back()?;
+        self.memory_usage -= frame.size();
+
+        // Restore the state of the new most recent frame from its keyframe.
+        let keyframe = self.frames.iter().rposition(|frame| matches!(frame.snapshot, Snapshot::Keyframe(_)));
+        self.last_state.clear();
+        self.frames_since_keyframe = 0;
+
+        if let Some(keyframe) = keyframe {
+            for frame in self.frames.range(keyframe..) {
+                match &frame.snapshot {
+                    Snapshot::Keyframe(bytes) => self.last_state = bytes.clone(),
+                    Snapshot::Delta(delta) => xor_rle_decode(&mut self.last_state, delta)
+                }
+            }
+
+            self.frames_since_keyframe = self.frames.len() - 1 - keyframe;
+        }
+
+        Some(frame.audio)
+    }
+
+    /// State of the most recent frame.
+    pub(crate) fn last_state(&self) -> Option<Result<SaveState, StateError>> {
+        match self.frames.is_empty() {
+            true => None,
+            false => Some(SaveState::from_bytes(&self.last_state))
+        }
+    }
+
+    // Drop the oldest frames beyond the capacity and the memory budget; the
+    // frame following a dropped one becomes a keyframe.
+    fn shrink(&mut self) {
+        while self.frames.len() > self.capacity || (self.memory_usage > self.memory_budget && self.frames.len() > 1) {
+            let frame = self.frames.pop_front().unwrap();
+            self.memory_usage -= frame.size();
+
+            let mut bytes = match frame.snapshot {
+                Snapshot::Keyframe(bytes) => bytes,
+                Snapshot::Delta(_) => unreachable!("the oldest frame is always a keyframe")
+            };
+
+            if let Some(next_frame) = self.frames.front_mut() {
+                if let Snapshot::Delta(delta) = &next_frame.snapshot {
+                    self.memory_usage -= next_frame.size();
+                    xor_rle_decode(&mut bytes, delta);
+                    next_frame.snapshot = Snapshot::Keyframe(bytes);
+                    self.memory_usage += next_frame.size();
+                }
+            }
+        }
+
+        if self.frames.is_empty() {
+            self.clear();
+        }
+    }
+}
+
+/// Encode the difference between two buffers of the same size.
+///
+/// This function XOR's the buffers and encodes the result as a sequence of
+/// runs; each of them is the number of unchanged bytes (skipped), the number
+/// of changed bytes and the changed bytes (XOR'ed). The counts are written as
+/// LEB128 numbers. It returns nothing if the sizes differ.
+///
+pub(crate) fn xor_rle_encode(previous: &[u8], current: &[u8]) -> Option<Vec<u8>> {
+    if previous.len() != current.len() {
+        return None;
+    }
+
+    let mut delta = Vec::new();
+    let mut index = 0;
+    while index < current.len() {
+        let start = index;
+        while index < current.len() && previous[index] == current[index] {
+            index += 1;
+        }
+        let skipped = index - start;
+
+        let start = index;
+        while index < current.len() && previous[index] != current[index] {
+            index += 1;
+        }
+
+        write_leb128(&mut delta, skipped);
+        write_leb128(&mut delta, index - start);
+        delta.extend(previous[start..index].iter().zip(current[start..index].iter()).map(|(a, b)| a ^ b));
+    }
+
+    Some(delta)
+}
+
+/// Apply a difference encoded by `xor_rle_encode()`.
+pub(crate) fn xor_rle_decode(bytes: &mut [u8], delta: &[u8]) {
+    let (mut index, mut position) = (0, 0);
+    while position < delta.len() {
+        index += read_leb128(delta, &mut position);
+        let changed = read_leb128(delta, &mut position);
+
+        for (byte, xor) in bytes[index..index + changed].iter_mut().zip(delta[position..position + changed].iter()) {
+            *byte ^= xor;
+        }
+
+        index += changed;
+        position += changed;
+    }
+}
+
+fn write_leb128(bytes: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x_7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x_80);
+    }
+}
+
+fn read_leb128(bytes: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+
+        value |= ((byte & 0x_7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x_80 == 0 {
+            return value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::fixtures;
+
+    #[test]
+    fn test_xor_rle() {
+        let previous = vec![0_u8; 300];
+        let mut current = previous.clone();
+        current[0] = 0x_01;
+        current[200] = 0x_FF;
+        current[201] = 0x_0F;
+
+        let delta = xor_rle_encode(&previous, &current).unwrap();
+        assert_eq!(delta, vec![0, 1, 0x_01, 199, 1, 2, 0x_FF, 0x_0F, 98, 0]);
+
+        let mut bytes = previous.clone();
+        xor_rle_decode(&mut bytes, &delta);
+        assert_eq!(bytes, current);
+
+        assert_eq!(xor_rle_encode(&previous, &current[1..]), None);
+        assert_eq!(xor_rle_encode(&previous, &previous).unwrap(), vec![0xAC, 0x_02, 0]);
+    }
+
+    #[test]
+    fn test_rewind_history() {
+        let mut console = Console::new(Cartridge::new(fixtures::counter_rom(0x_80)));
+        let mut history = RewindHistory::new();
+        history.set_capacity(10);
+        history.set_keyframe_interval(4);
+
+        let mut values = Vec::new();
+        for _ in 0..12 {
+            console.run_frame();
+            history.push(&console.save_state(), console.frame_audio());
+            values.push(console.memory(0x_80));
+        }
+
+        // The oldest frames are dropped; the frames 4 and 8 are keyframes, and
+        // the frame 2 became one when the frames before were dropped. The
+        // deltas are much smaller than the keyframes.
+        assert_eq!(history.len(), 10);
+        let keyframes = history.frames.iter()
+            .map(|frame| matches!(frame.snapshot, Snapshot::Keyframe(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(keyframes, vec![true, false, true, false, false, false, true, false, false, false]);
+        assert!(history.memory_usage() < 4 * console.save_state().to_bytes().len());
+
+        // The states are restored from the keyframes and the deltas.
+        for index in (2..12).rev() {
+            let state = history.last_state().unwrap().unwrap();
+            console.load_state(&state).unwrap();
+            assert_eq!(console.memory(0x_80), values[index]);
+            assert!(history.pop_back().is_some());
+        }
+        assert!(history.last_state().is_none());
+        assert_eq!(history.memory_usage(), 0);
+
+        // The memory budget drops the oldest frames too, but keeps at least
+        // one.
+        for _ in 0..5 {
+            console.run_frame();
+            history.push(&console.save_state(), console.frame_audio());
+        }
+        history.set_memory_budget(0);
+        assert_eq!(history.len(), 1);
+        console.load_state(&history.last_state().unwrap().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+/// A deterministic random number generator.
+///
+/// The console owns one of these and uses it for all its stochastic features
+/// (for instance, the random content of the RAM at power-on); scripts and
+/// tests can use it too (to fuzz the inputs for instance). Since it's seeded,
+/// an entire run is reproducible from a single seed, and its state is part of
+/// the save states.
+///
+/// It's a SplitMix64 generator; it's fast and good enough for emulation, but
+/// it's not meant for anything security-related.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, ConsoleBuilder, Joystick, TEST_ROM};
+/// # let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// # let mut joystick = Joystick::new();
+/// let mut console = ConsoleBuilder::new(cartridge)
+///     .seed(42)
+///     .random_ram()
+///     .build();
+///
+/// if console.rng_mut().next_bool() {
+///     joystick.press_button();
+/// }
+/// ```
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rng {
+    seed: u64,
+    state: u64
+}
+
+impl Rng {
+    /// Create a generator with a seed.
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            seed,
+            state: seed
+        }
+    }
+
+    /// Seed the generator was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Generate a 64-bit integer.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x_9E37_79B9_7F4A_7C15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0x_BF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x_94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Generate a 32-bit integer.
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Generate a byte.
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    /// Generate a boolean.
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 != 0
+    }
+
+    /// Generate an integer lower than a bound.
+    ///
+    /// The bound must not be 0.
+    ///
+    pub fn below(&mut self, bound: u32) -> u32 {
+        assert!(bound > 0, "bound must not be 0");
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+
+    /// Fill a buffer with random bytes.
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for byte in bytes.iter_mut() {
+            *byte = self.next_u8();
+        }
+    }
+
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub(crate) fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rng() {
+        // The first values of SplitMix64 with a seed of 0.
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0x_E220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x_6E78_9E6A_A1B9_65F4);
+
+        // The same seed generates the same values.
+        let mut first = Rng::new(42);
+        let mut second = Rng::new(42);
+        let mut bytes = [0; 16];
+        first.fill(&mut bytes);
+        let other_bytes = (0..16).map(|_| second.next_u8()).collect::<Vec<_>>();
+        assert_eq!(&bytes[..], &other_bytes[..]);
+        assert_eq!(first.seed(), 42);
+
+        // The values are within the bound.
+        assert!((0..1000).all(|_| first.below(6) < 6));
+        assert_eq!(first.below(1), 0);
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::cartridge::Cartridge;
+use crate::analysis::BankSwitching;
+use crate::assembler::{assemble_at, AssemblerError, DEFAULT_ORIGIN};
+
+const BANK_SIZE: usize = 4096;
+
+// The content placed in a bank; code is assembled when the ROM is built.
+enum Chunk {
+    Code(u16, String),
+    Data(u16, Vec<u8>)
+}
+
+/// A builder of test cartridges.
+///
+/// This structure builds the ROM of a cartridge from pieces of code (see
+/// `assemble()`) and data placed at given addresses, which makes the programs
+/// of unit tests readable. The ROM is made of banks of 4k filled with NOP
+/// instructions (as many as the bank switching scheme requires), and each of
+/// them ends with the reset and IRQ vectors; both point to 0xF000 unless
+/// they're set otherwise.
+///
+/// Code and data go to the first bank until another one is selected; only
+/// the lowest 12 bits of their address matter.
+///
+/// ```
+/// # use atari_2600::{BankSwitching, RomBuilder};
+/// let cartridge = RomBuilder::new()
+///     .code(0x_F000, "
+///         lda #$0E
+///         sta $09   ; COLUBK
+///         lda $FFF9 ; Switch to the second bank.
+///     ")
+///     .bank_switching(BankSwitching::F8)
+///     .bank(1)
+///     .data(0x_F800, &[0x_00, 0x_FF])
+///     .build()?;
+/// # Ok::<(), atari_2600::AssemblerError>(())
+/// ```
+///
+pub struct RomBuilder {
+    bank_switching: BankSwitching,
+    reset_vector: u16,
+    irq_vector: Option<u16>,
+    bank: usize,
+    chunks: Vec<(usize, Chunk)>
+}
+
+impl RomBuilder {
+    /// Create a builder.
+    ///
+    /// This function creates a builder of a 4k cartridge with no bank
+    /// switching.
+    ///
+    pub fn new() -> RomBuilder {
+        RomBuilder {
+            bank_switching: BankSwitching::Standard,
+            reset_vector: DEFAULT_ORIGIN,
+            irq_vector: None,
+            bank: 0,
+            chunks: Vec::new()
+        }
+    }
+
+    /// Choose the bank switching scheme.
+    ///
+    /// This function sets the scheme, which decides the number of banks of
+    /// the ROM (its size). Unknown schemes are built as a single bank.
+    ///
+    pub fn bank_switching(mut self, bank_switching: BankSwitching) -> RomBuilder {
+        self.bank_switching = bank_switching;
+        self
+    }
+
+    /// Set the reset vector of all the banks.
+    pub fn reset_vector(mut self, address: u16) -> RomBuilder {
+        self.reset_vector = address;
+        self
+    }
+
+    /// Set the IRQ vector (which BRK goes through) of all the banks.
+    pub fn irq_vector(mut self, address: u16) -> RomBuilder {
+        self.irq_vector = Some(address);
+        self
+    }
+
+    /// Select the bank the following code and data go to.
+    pub fn bank(mut self, index: usize) -> RomBuilder {
+        self.bank = index;
+        self
+    }
+
+    /// Place code at an address.
+    ///
+    /// This function places a program, which is assembled at the given
+    /// address, in the selected bank.
+    ///
+    pub fn code(mut self, address: u16, source: &str) -> RomBuilder {
+        self.chunks.push((self.bank, Chunk::Code(address, source.to_string())));
+        self
+    }
+
+    /// Place data at an address.
+    pub fn data(mut self, address: u16, bytes: &[u8]) -> RomBuilder {
+        self.chunks.push((self.bank, Chunk::Data(address, bytes.to_vec())));
+        self
+    }
+
+    /// Build the cartridge.
+    ///
+    /// This function assembles the code and returns the cartridge. It fails
+    /// if the code can't be assembled; it panics if a bank doesn't exist or
+    /// if a chunk goes beyond the end of its bank.
+    ///
+    pub fn build(self) -> Result<Cartridge, AssemblerError> {
+        let count = bank_count(self.bank_switching);
+        let mut rom = vec![0x_EA; count * BANK_SIZE];
+
+        // Write the vectors first so code and data can override them.
+        let irq_vector = self.irq_vector.unwrap_or(self.reset_vector);
+        for bank in rom.chunks_mut(BANK_SIZE) {
+            bank[0x_FFC..0x_FFE].copy_from_slice(&self.reset_vector.to_le_bytes());
+            bank[0x_FFE..].copy_from_slice(&irq_vector.to_le_bytes());
+        }
+
+        for (bank, chunk) in self.chunks {
+            assert!(bank < count, "bank {} doesn't exist", bank);
+
+            let (address, bytes) = match chunk {
+                Chunk::Code(address, source) => (address, assemble_at(address, &source)?),
+                Chunk::Data(address, bytes) => (address, bytes)
+            };
+
+            let start = bank * BANK_SIZE + (address as usize & 0x_0FFF);
+            assert!(start + bytes.len() <= (bank + 1) * BANK_SIZE, "chunk at {:04X} goes beyond its bank", address);
+
+            rom[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+
+        Ok(Cartridge::new(rom))
+    }
+}
+
+impl Default for RomBuilder {
+    fn default() -> Self {
+        RomBuilder::new()
+    }
+}
+
+fn bank_count(bank_switching: BankSwitching) -> usize {
+    match bank_switching {
+        BankSwitching::Standard | BankSwitching::Unknown => 1,
+        BankSwitching::F8 => 2,
+        BankSwitching::FA => 3,
+        BankSwitching::F6 => 4,
+        BankSwitching::F4 => 8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyze_rom;
+
+    #[test]
+    fn test_rom_builder() {
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "start: lda $FFF9\njmp start")
+            .data(0x_F100, &[0x_01, 0x_02])
+            .bank_switching(BankSwitching::F8)
+            .bank(1)
+            .code(0x_D800, "loop: jmp loop")
+            .reset_vector(0x_F000)
+            .irq_vector(0x_F800)
+            .build()
+            .unwrap();
+
+        assert_eq!(cartridge.memory.len(), 8192);
+        assert_eq!(cartridge.memory[..6], [0x_AD, 0x_F9, 0x_FF, 0x_4C, 0x_00, 0x_F0]);
+        assert_eq!(cartridge.memory[0x_0100..0x_0102], [0x_01, 0x_02]);
+        assert_eq!(cartridge.memory[0x_1800..0x_1803], [0x_4C, 0x_00, 0x_D8]);
+        assert_eq!(cartridge.memory[0x_0FFC..0x_1000], [0x_00, 0x_F0, 0x_00, 0x_F8]);
+        assert_eq!(cartridge.memory[0x_1FFC..], [0x_00, 0x_F0, 0x_00, 0x_F8]);
+        assert_eq!(cartridge.memory[0x_0006], 0x_EA);
+
+        let analysis = analyze_rom(&cartridge);
+        assert_eq!(analysis.bank_switching, BankSwitching::F8);
+        assert_eq!(analysis.hotspots, vec![0x_1FF9]);
+
+        // Code is assembled when the cartridge is built.
+        match RomBuilder::new().code(0x_F000, "nop\nfoo").build() {
+            Err(error) => assert_eq!(error.line, 2),
+            Ok(_) => panic!("code shouldn't assemble")
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::cartridge::Cartridge;
+use crate::analysis::{BankSwitching, analyze_rom};
+use crate::database::{Database, RomEntry, ControllerKind};
+use crate::utils::{crc32, sha1, to_hex_string};
+
+/// The information about a ROM file.
+///
+/// This structure identifies a ROM (its size and the digests used by the ROM
+/// database and the ROM catalogues), tells how it's mapped (its bank switching
+/// scheme) and which controllers it likely expects. It's created with
+/// `rom_info()` and is meant for tools cataloguing ROM collections, and for
+/// the `--info` flag of the emulator.
+///
+/// The controllers are the ones of the database entry of the game, if it's
+/// known (see `with_database()`), otherwise they're guessed from the code (see
+/// `RomAnalysis::controller()`), if possible.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct RomInfo {
+    pub size: usize,
+    pub md5: String,
+    pub sha1: String,
+    pub crc32: u32,
+    pub bank_switching: BankSwitching,
+    pub entry: Option<RomEntry>,
+    pub controllers: [Option<ControllerKind>; 2]
+}
+
+impl RomInfo {
+    /// Create the information about a cartridge.
+    pub fn new(cartridge: &Cartridge) -> RomInfo {
+        let analysis = analyze_rom(cartridge);
+        let controller = analysis.controller();
+
+        RomInfo {
+            size: analysis.size,
+            md5: analysis.md5,
+            sha1: to_hex_string(&sha1(&cartridge.memory)),
+            crc32: crc32(&cartridge.memory),
+            bank_switching: analysis.bank_switching,
+            entry: None,
+            controllers: [controller, controller]
+        }
+    }
+
+    /// Look up the game in a database.
+    ///
+    /// This function adds the database entry of the game, if it's known, and
+    /// takes the controllers from it.
+    ///
+    pub fn with_database(mut self, database: &Database) -> RomInfo {
+        let entry = database.lookup_md5(&self.md5).cloned();
+
+        if let Some(entry) = &entry {
+            self.controllers = [Some(entry.left_controller), Some(entry.right_controller)];
+        }
+        self.entry = entry;
+
+        self
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let controller = |controller: Option<ControllerKind>| match controller {
+            Some(controller) => format!("{:?}", controller),
+            None => "unknown".to_string()
+        };
+
+        writeln!(f, "Size: {} bytes", self.size)?;
+        writeln!(f, "MD5: {}", self.md5)?;
+        writeln!(f, "SHA-1: {}", self.sha1)?;
+        writeln!(f, "CRC-32: {:08x}", self.crc32)?;
+        writeln!(f, "Bank switching: {:?}", self.bank_switching)?;
+        if let Some(entry) = &self.entry {
+            writeln!(f, "Name: {}", entry.name)?;
+            writeln!(f, "Manufacturer: {}", entry.manufacturer)?;
+            if let Some(variant) = entry.variant {
+                writeln!(f, "Console: {:?}", variant)?;
+            }
+        }
+        writeln!(f, "Left controller: {}", controller(self.controllers[0]))?;
+        write!(f, "Right controller: {}", controller(self.controllers[1]))
+    }
+}
+
+/// Read the information about a ROM file.
+///
+/// This function reads a ROM file and returns its information (see
+/// `RomInfo`); it fails if the file can't be read.
+///
+/// ```no_run
+/// # use std::fs;
+/// # use atari_2600::{rom_info, Database};
+/// # let database = Database::builtin();
+/// for path in fs::read_dir("roms")? {
+///     let info = rom_info(path?.path())?.with_database(&database);
+///     println!("{} {}", info.sha1, info.entry.map_or("?".to_string(), |entry| entry.name));
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+pub fn rom_info<P: AsRef<Path>>(path: P) -> io::Result<RomInfo> {
+    Ok(RomInfo::new(&Cartridge::from_file(path)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::trackball::TrackballMode;
+    use crate::rom_builder::RomBuilder;
+
+    #[test]
+    fn test_rom_info() {
+        let rom = RomBuilder::new()
+            .bank_switching(BankSwitching::F8)
+            .code(0x_F000, "
+                start:
+                    lda $0280 ; SWCHA
+                    jmp start
+            ")
+            .build()
+            .unwrap()
+            .memory;
+
+        let path = std::env::temp_dir().join(format!("atari-2600-rom-info-{}.bin", std::process::id()));
+        std::fs::write(&path, &rom).unwrap();
+        let info = rom_info(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(info.size, 8192);
+        assert_eq!(info.md5, Cartridge::new(rom.clone()).md5());
+        assert_eq!(info.sha1, to_hex_string(&sha1(&rom)));
+        assert_eq!(info.crc32, crc32(&rom));
+        assert_eq!(info.bank_switching, BankSwitching::F8);
+        assert_eq!(info.entry, None);
+
+        // The code reads the port A; it's likely played with joysticks.
+        assert_eq!(info.controllers, [Some(ControllerKind::Joystick); 2]);
+
+        // The database knows better.
+        let mut database = Database::new();
+        database.insert(RomEntry {
+            md5: info.md5.clone(),
+            name: "Some Game".to_string(),
+            manufacturer: "Some Company".to_string(),
+            left_controller: ControllerKind::Trackball(TrackballMode::AtariMouse),
+            right_controller: ControllerKind::Joystick,
+            variant: None,
+            paddle_calibration: None
+        });
+
+        let info = info.with_database(&database);
+        assert_eq!(info.entry.as_ref().unwrap().name, "Some Game");
+        assert_eq!(info.controllers[0], Some(ControllerKind::Trackball(TrackballMode::AtariMouse)));
+        assert!(info.to_string().contains("Name: Some Game\n"));
+
+        assert!(rom_info("this-rom-doesnt-exist.bin").is_err());
+    }
+}
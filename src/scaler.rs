@@ -0,0 +1,337 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::frame::{Frame, Orientation};
+
+// The pixels of the console are twice as wide as they're high on a TV set;
+// the 160 pixels of a line span about the same width as 320 square pixels.
+const PIXEL_ASPECT_RATIO: usize = 2;
+
+/// The filter used to scale a frame.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScaleFilter {
+    /// Each pixel of the frame is a sharp block.
+    Nearest,
+    /// The pixels are interpolated; the frame looks blurry.
+    Bilinear,
+    /// The pixels are sharp blocks whose edges are interpolated, which hides
+    /// the uneven blocks of non-integer scales without blurring the frame.
+    SharpBilinear
+}
+
+/// The area of the output where the frame is drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+/// A scaler of frames to the size of the display.
+///
+/// This structure scales the frames of the console to the size of a window or
+/// a screen, keeping the pixels twice as wide as they're high like on a TV
+/// set, and centering the frame with black borders around it. The scale is
+/// either restricted to integer factors (all pixels have the same size), or
+/// the frame fills as much of the output as possible.
+///
+/// The frame is presented with its orientation; when it's rotated sideways,
+/// the frame is as high as it was wide.
+///
+/// The scaled frame can be made to look like it's displayed on a CRT TV set;
+/// the scanlines darken the edges of the lines, and the shadow mask lets the
+/// red, green and blue components through alternating columns.
+///
+/// The scaling is computed on the CPU, one output pixel at a time, the same
+/// way a fragment shader would on the GPU.
+///
+#[cfg_attr(all(feature = "test-rom", feature = "frontend"), doc = "```")]
+#[cfg_attr(not(all(feature = "test-rom", feature = "frontend")), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, ScaleFilter, Scaler, TEST_ROM};
+/// # let emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// # struct Window;
+/// # impl Window { fn width(&self) -> usize { 640 } fn height(&self) -> usize { 480 } }
+/// # let window = Window;
+/// # fn present_pixels(_pixels: &[u8]) {}
+/// let scaler = Scaler { filter: ScaleFilter::SharpBilinear, scanlines: 0.3, ..Scaler::default() };
+///
+/// let pixels = scaler.scale(&emulator.frame(), window.width(), window.height());
+/// present_pixels(&pixels); // RGBA, 4 bytes per pixel
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scaler {
+    pub filter: ScaleFilter,
+    pub integer_scaling: bool,
+    /// Intensity of the scanlines, between 0.0 (none) and 1.0.
+    pub scanlines: f32,
+    /// Intensity of the shadow mask, between 0.0 (none) and 1.0.
+    pub shadow_mask: f32
+}
+
+impl Default for Scaler {
+    fn default() -> Scaler {
+        Scaler {
+            filter: ScaleFilter::Nearest,
+            integer_scaling: true,
+            scanlines: 0.0,
+            shadow_mask: 0.0
+        }
+    }
+}
+
+impl Scaler {
+    /// Compute the area where the frame is drawn.
+    ///
+    /// This function returns the area of an output of the given size where a
+    /// frame with the given orientation is drawn. With integer scaling, if the
+    /// output is too small to fit the frame at its original size, the frame is
+    /// scaled down instead.
+    ///
+    pub fn viewport(&self, orientation: Orientation, width: usize, height: usize) -> Viewport {
+        let (frame_width, frame_height) = match orientation.is_sideways() {
+            false => (Frame::WIDTH * PIXEL_ASPECT_RATIO, Frame::HEIGHT),
+            true => (Frame::HEIGHT, Frame::WIDTH * PIXEL_ASPECT_RATIO)
+        };
+
+        let factor = (width / frame_width).min(height / frame_height);
+        let (viewport_width, viewport_height) = if self.integer_scaling && factor > 0 {
+            (frame_width * factor, frame_height * factor)
+        }
+        else {
+            let factor = (width as f64 / frame_width as f64).min(height as f64 / frame_height as f64);
+            ((frame_width as f64 * factor) as usize, (frame_height as f64 * factor) as usize)
+        };
+
+        Viewport {
+            x: (width - viewport_width) / 2,
+            y: (height - viewport_height) / 2,
+            width: viewport_width,
+            height: viewport_height
+        }
+    }
+
+    /// Scale a frame.
+    ///
+    /// This function scales a frame to an output of the given size and
+    /// returns its pixels, line by line, with 4 bytes per pixel (red, green,
+    /// blue and alpha); the area around the viewport is black.
+    ///
+    pub fn scale(&self, frame: &Frame, width: usize, height: usize) -> Vec<u8> {
+        let mut pixels = vec![0; width * height * 4];
+        for pixel in pixels.chunks_mut(4) {
+            pixel[3] = 0x_FF;
+        }
+
+        let orientation = frame.orientation;
+        let viewport = self.viewport(orientation, width, height);
+        if viewport.width == 0 || viewport.height == 0 {
+            return pixels;
+        }
+
+        // The number of output pixels per pixel of the frame.
+        let (scale_x, scale_y) = match orientation.is_sideways() {
+            false => (viewport.width as f32 / Frame::WIDTH as f32, viewport.height as f32 / Frame::HEIGHT as f32),
+            true => (viewport.height as f32 / Frame::WIDTH as f32, viewport.width as f32 / Frame::HEIGHT as f32)
+        };
+
+        for y in 0..viewport.height {
+            for x in 0..viewport.width {
+                // The position in the frame, in pixels of the frame.
+                let (texel_x, texel_y) = orientation.unapply(
+                    (x as f32 + 0.5) / viewport.width as f32,
+                    (y as f32 + 0.5) / viewport.height as f32
+                );
+                let (texel_x, texel_y) = (texel_x * Frame::WIDTH as f32, texel_y * Frame::HEIGHT as f32);
+
+                let scanline = 1.0 - self.scanlines * (2.0 * texel_y.fract() - 1.0).powi(2);
+
+                let color = match self.filter {
+                    ScaleFilter::Nearest => {
+                        let (red, green, blue) = frame.pixel((texel_x as usize).min(Frame::WIDTH - 1), (texel_y as usize).min(Frame::HEIGHT - 1));
+                        [red as f32, green as f32, blue as f32]
+                    },
+                    ScaleFilter::Bilinear => sample_bilinear(frame, texel_x, texel_y),
+                    ScaleFilter::SharpBilinear => {
+                        sample_bilinear(frame, sharpen(texel_x, scale_x), sharpen(texel_y, scale_y))
+                    }
+                };
+
+                let offset = ((viewport.y + y) * width + viewport.x + x) * 4;
+                for (component, value) in color.iter().enumerate() {
+                    let mask = if (viewport.x + x) % 3 == component { 1.0 } else { 1.0 - self.shadow_mask };
+                    pixels[offset + component] = (value * scanline * mask).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+// Interpolate the 4 pixels around a position of the frame; the centers of the
+// pixels are at the half-integer positions.
+fn sample_bilinear(frame: &Frame, x: f32, y: f32) -> [f32; 3] {
+    let clamp = |position: f32, size: usize| (position.max(0.0) as usize).min(size - 1);
+
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (clamp(x.floor(), Frame::WIDTH), clamp(y.floor(), Frame::HEIGHT));
+    let (x1, y1) = (clamp(x.floor() + 1.0, Frame::WIDTH), clamp(y.floor() + 1.0, Frame::HEIGHT));
+    let (weight_x, weight_y) = (x - x.floor(), y - y.floor());
+
+    let mut color = [0.0; 3];
+    for (pixel_x, pixel_y, weight) in [
+        (x0, y0, (1.0 - weight_x) * (1.0 - weight_y)),
+        (x1, y0, weight_x * (1.0 - weight_y)),
+        (x0, y1, (1.0 - weight_x) * weight_y),
+        (x1, y1, weight_x * weight_y)
+    ] {
+        let (red, green, blue) = frame.pixel(pixel_x, pixel_y);
+        color[0] += red as f32 * weight;
+        color[1] += green as f32 * weight;
+        color[2] += blue as f32 * weight;
+    }
+
+    color
+}
+
+// Move a position of the frame toward the center of its pixel, so only the
+// output pixels on the edges of a pixel (less than one output pixel away) are
+// interpolated with the neighbor pixel.
+fn sharpen(position: f32, scale: f32) -> f32 {
+    let range = (0.5 - 0.5 / scale).max(0.0);
+    let distance = position.fract() - 0.5;
+
+    position.floor() + (distance - distance.clamp(-range, range)) * scale + 0.5
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Rotation, Flip};
+
+    fn checkerboard() -> Frame {
+        let mut frame = Frame { number: 1, pixels: [[(0, 0, 0); 160]; 192], orientation: Orientation::default() };
+        for (y, line) in frame.pixels.iter_mut().enumerate() {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                if (x + y) % 2 == 0 {
+                    *pixel = (200, 100, 50);
+                }
+            }
+        }
+
+        frame
+    }
+
+    fn pixel(pixels: &[u8], width: usize, x: usize, y: usize) -> (u8, u8, u8) {
+        let offset = (y * width + x) * 4;
+        (pixels[offset], pixels[offset + 1], pixels[offset + 2])
+    }
+
+    #[test]
+    fn test_viewport() {
+        let scaler = Scaler::default();
+
+        // The frame is centered, with pixels twice as wide as high.
+        assert_eq!(scaler.viewport(Orientation::default(), 320, 192), Viewport { x: 0, y: 0, width: 320, height: 192 });
+        assert_eq!(scaler.viewport(Orientation::default(), 1920, 1080), Viewport { x: 160, y: 60, width: 1600, height: 960 });
+        assert_eq!(scaler.viewport(Orientation::default(), 1000, 1000), Viewport { x: 20, y: 212, width: 960, height: 576 });
+
+        // The frame is scaled down when it doesn't fit.
+        assert_eq!(scaler.viewport(Orientation::default(), 160, 192), Viewport { x: 0, y: 48, width: 160, height: 96 });
+
+        let scaler = Scaler { integer_scaling: false, ..Scaler::default() };
+        assert_eq!(scaler.viewport(Orientation::default(), 1000, 1000), Viewport { x: 0, y: 200, width: 1000, height: 600 });
+    }
+
+    #[test]
+    fn test_scale() {
+        let frame = checkerboard();
+
+        // Each pixel is a block of 4 by 2 output pixels.
+        let pixels = Scaler::default().scale(&frame, 640, 384);
+        assert_eq!(pixels.len(), 640 * 384 * 4);
+        assert_eq!(pixel(&pixels, 640, 0, 0), (200, 100, 50));
+        assert_eq!(pixel(&pixels, 640, 3, 1), (200, 100, 50));
+        assert_eq!(pixel(&pixels, 640, 4, 1), (0, 0, 0));
+        assert_eq!(pixel(&pixels, 640, 4, 2), (200, 100, 50));
+        assert!(pixels.chunks(4).all(|pixel| pixel[3] == 0x_FF));
+
+        // The borders are black.
+        let pixels = Scaler::default().scale(&frame, 640, 400);
+        assert_eq!(pixel(&pixels, 640, 0, 7), (0, 0, 0));
+        assert_eq!(pixel(&pixels, 640, 0, 8), (200, 100, 50));
+
+        // Bilinear filtering blends the pixels; sharp bilinear filtering only
+        // blends them at their edges.
+        let scaler = Scaler { filter: ScaleFilter::Bilinear, ..Scaler::default() };
+        let pixels = scaler.scale(&frame, 640, 384);
+        assert_eq!(pixel(&pixels, 640, 3, 0), (125, 63, 31));
+
+        let scaler = Scaler { filter: ScaleFilter::SharpBilinear, ..Scaler::default() };
+        let pixels = scaler.scale(&frame, 640, 384);
+        assert_eq!(pixel(&pixels, 640, 1, 0), (200, 100, 50));
+        assert_eq!(pixel(&pixels, 640, 2, 0), (200, 100, 50));
+        assert_eq!(pixel(&pixels, 640, 5, 0), (0, 0, 0));
+
+        // The scanlines darken the edges of the lines, and the shadow mask the
+        // components of the other columns.
+        let scaler = Scaler { scanlines: 0.5, ..Scaler::default() };
+        let pixels = scaler.scale(&frame, 1280, 768);
+        assert_eq!(pixel(&pixels, 1280, 0, 0), (144, 72, 36));
+        assert_eq!(pixel(&pixels, 1280, 0, 1), (194, 97, 48));
+
+        let scaler = Scaler { shadow_mask: 0.5, ..Scaler::default() };
+        let pixels = scaler.scale(&frame, 640, 384);
+        assert_eq!(pixel(&pixels, 640, 0, 0), (200, 50, 25));
+        assert_eq!(pixel(&pixels, 640, 1, 0), (100, 100, 25));
+        assert_eq!(pixel(&pixels, 640, 2, 0), (100, 50, 50));
+    }
+
+    #[test]
+    fn test_orientation() {
+        let mut frame = Frame { number: 1, pixels: [[(0, 0, 0); 160]; 192], orientation: Orientation::default() };
+        frame.pixels[0][0] = (255, 0, 0);
+        frame.pixels[0][159] = (0, 255, 0);
+
+        // Rotated sideways, the frame is as high as it was wide.
+        frame.orientation.rotation = Rotation::Quarter;
+        let scaler = Scaler::default();
+        assert_eq!(scaler.viewport(frame.orientation, 192, 320), Viewport { x: 0, y: 0, width: 192, height: 320 });
+
+        // The top-left pixel ends up at the top-right corner, and each pixel
+        // is a block of 1 by 2 output pixels.
+        let pixels = scaler.scale(&frame, 192, 320);
+        assert_eq!(pixel(&pixels, 192, 191, 0), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 192, 191, 1), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 192, 191, 2), (0, 0, 0));
+        assert_eq!(pixel(&pixels, 192, 191, 319), (0, 255, 0));
+
+        frame.orientation.rotation = Rotation::ThreeQuarters;
+        let pixels = scaler.scale(&frame, 192, 320);
+        assert_eq!(pixel(&pixels, 192, 0, 319), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 192, 0, 0), (0, 255, 0));
+
+        frame.orientation.rotation = Rotation::Half;
+        let pixels = scaler.scale(&frame, 320, 192);
+        assert_eq!(pixel(&pixels, 320, 319, 191), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 320, 0, 191), (0, 255, 0));
+
+        // The frame is flipped before it's rotated.
+        frame.orientation = Orientation { rotation: Rotation::None, flip: Flip::Horizontal };
+        let pixels = scaler.scale(&frame, 320, 192);
+        assert_eq!(pixel(&pixels, 320, 319, 0), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 320, 0, 0), (0, 255, 0));
+
+        frame.orientation = Orientation { rotation: Rotation::Quarter, flip: Flip::Vertical };
+        let pixels = scaler.scale(&frame, 192, 320);
+        assert_eq!(pixel(&pixels, 192, 0, 0), (255, 0, 0));
+        assert_eq!(pixel(&pixels, 192, 0, 319), (0, 255, 0));
+    }
+}
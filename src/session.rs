@@ -0,0 +1,439 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::console::{Player, TvType, Difficulty};
+use crate::frame::{Orientation, Rotation, Flip};
+use crate::state::{SaveState, StateError};
+use crate::menu::STATE_SLOTS;
+use crate::emulator::Emulator;
+
+/// Number of ROMs remembered by a session.
+pub const RECENT_ROMS: usize = 10;
+
+// The file of the session directory listing the recently played ROMs (one
+// 'md5|path' line per ROM, the most recent first), and the files of the
+// directory of a game.
+const RECENT_ROMS_FILE: &str = "recent";
+const CONFIG_FILE: &str = "config";
+const LAST_STATE_FILE: &str = "last.state";
+
+/// An error reported while launching a game.
+#[derive(Debug)]
+pub enum SessionError {
+    /// A file of the session couldn't be read or written.
+    Io(io::Error),
+    /// A saved state couldn't be loaded.
+    State(StateError)
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(error) => write!(f, "{}", error),
+            SessionError::State(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> Self {
+        SessionError::Io(error)
+    }
+}
+
+impl From<StateError> for SessionError {
+    fn from(error: StateError) -> Self {
+        SessionError::State(error)
+    }
+}
+
+/// A recently played ROM.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RecentRom {
+    pub md5: String,
+    pub path: PathBuf
+}
+
+/// The settings of the emulator remembered for a game.
+///
+/// It's stored as 'key=value' lines; the unknown keys and values are ignored
+/// and the missing ones are left to their default value.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GameConfig {
+    pub tv_type: TvType,
+    pub difficulties: [Difficulty; 2],
+    pub orientation: Orientation,
+    pub is_running_ahead: bool
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            tv_type: TvType::Color,
+            difficulties: [Difficulty::Amateur, Difficulty::Amateur],
+            orientation: Orientation::default(),
+            is_running_ahead: false
+        }
+    }
+}
+
+impl GameConfig {
+    /// Read the settings of an emulator.
+    pub fn from_emulator(emulator: &Emulator) -> GameConfig {
+        let console = emulator.console();
+        GameConfig {
+            tv_type: console.tv_type_switch(),
+            difficulties: [console.difficulty_switch(Player::One), console.difficulty_switch(Player::Two)],
+            orientation: emulator.orientation(),
+            is_running_ahead: emulator.is_running_ahead()
+        }
+    }
+
+    /// Change the settings of an emulator.
+    pub fn apply(&self, emulator: &mut Emulator) {
+        let console = emulator.console_mut();
+        console.set_tv_type_switch(self.tv_type);
+        console.set_difficulty_switch(Player::One, self.difficulties[0]);
+        console.set_difficulty_switch(Player::Two, self.difficulties[1]);
+
+        emulator.set_orientation(self.orientation);
+        emulator.set_run_ahead(self.is_running_ahead);
+    }
+
+    /// Parse the settings.
+    ///
+    /// This function reads the 'key=value' lines written by the `Display`
+    /// implementation.
+    ///
+    pub fn parse(text: &str) -> GameConfig {
+        let mut config = GameConfig::default();
+
+        for line in text.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue
+            };
+
+            match (key, value) {
+                ("tv-type", "color") => config.tv_type = TvType::Color,
+                ("tv-type", "mono") => config.tv_type = TvType::Mono,
+                ("left-difficulty", value) => config.difficulties[0] = parse_difficulty(value).unwrap_or(config.difficulties[0]),
+                ("right-difficulty", value) => config.difficulties[1] = parse_difficulty(value).unwrap_or(config.difficulties[1]),
+                ("rotation", "none") => config.orientation.rotation = Rotation::None,
+                ("rotation", "quarter") => config.orientation.rotation = Rotation::Quarter,
+                ("rotation", "half") => config.orientation.rotation = Rotation::Half,
+                ("rotation", "three-quarters") => config.orientation.rotation = Rotation::ThreeQuarters,
+                ("flip", "none") => config.orientation.flip = Flip::None,
+                ("flip", "horizontal") => config.orientation.flip = Flip::Horizontal,
+                ("flip", "vertical") => config.orientation.flip = Flip::Vertical,
+                ("run-ahead", value) => config.is_running_ahead = value == "true",
+                _ => ()
+            }
+        }
+
+        config
+    }
+}
+
+impl fmt::Display for GameConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tv_type = match self.tv_type {
+            TvType::Color => "color",
+            TvType::Mono => "mono"
+        };
+        let rotation = match self.orientation.rotation {
+            Rotation::None => "none",
+            Rotation::Quarter => "quarter",
+            Rotation::Half => "half",
+            Rotation::ThreeQuarters => "three-quarters"
+        };
+        let flip = match self.orientation.flip {
+            Flip::None => "none",
+            Flip::Horizontal => "horizontal",
+            Flip::Vertical => "vertical"
+        };
+
+        writeln!(f, "tv-type={}", tv_type)?;
+        writeln!(f, "left-difficulty={}", difficulty_name(self.difficulties[0]))?;
+        writeln!(f, "right-difficulty={}", difficulty_name(self.difficulties[1]))?;
+        writeln!(f, "rotation={}", rotation)?;
+        writeln!(f, "flip={}", flip)?;
+        writeln!(f, "run-ahead={}", self.is_running_ahead)
+    }
+}
+
+fn difficulty_name(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Amateur => "amateur",
+        Difficulty::Pro => "pro"
+    }
+}
+
+fn parse_difficulty(name: &str) -> Option<Difficulty> {
+    match name {
+        "amateur" => Some(Difficulty::Amateur),
+        "pro" => Some(Difficulty::Pro),
+        _ => None
+    }
+}
+
+/// The games played with the emulator, kept between runs.
+///
+/// A session is a directory remembering the recently played ROMs and, for
+/// each game, the settings of the emulator, the states of the slots and the
+/// state the game was left in. The games are identified by the MD5 digest of
+/// their ROM (see `Cartridge::md5()`), so they're found again if the files
+/// are moved or renamed.
+///
+/// ```no_run
+/// # use atari_2600::{Cartridge, Console, Emulator, Session};
+/// # fn run(_emulator: &mut Emulator) {}
+/// let mut session = Session::open("sessions")?;
+/// let path = session.recent_roms()[0].path.clone();
+///
+/// let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&path)?));
+/// session.launch(&path, &mut emulator, true)?;
+/// run(&mut emulator);
+/// session.save(&emulator)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct Session {
+    directory: PathBuf,
+    recent_roms: Vec<RecentRom>
+}
+
+impl Session {
+    /// Open a session.
+    ///
+    /// This function opens the session stored in a directory, which is
+    /// created if it doesn't exist. The malformed lines of the list of the
+    /// recently played ROMs are ignored.
+    ///
+    pub fn open<P: AsRef<Path>>(directory: P) -> io::Result<Session> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+
+        let recent_roms = match fs::read_to_string(directory.join(RECENT_ROMS_FILE)) {
+            Ok(text) => {
+                text.lines()
+                    .filter_map(|line| line.split_once('|'))
+                    .map(|(md5, path)| RecentRom { md5: md5.to_string(), path: PathBuf::from(path) })
+                    .take(RECENT_ROMS)
+                    .collect()
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error)
+        };
+
+        Ok(Session {
+            directory,
+            recent_roms
+        })
+    }
+
+    /// Directory of the session.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Recently played ROMs, the most recent first.
+    pub fn recent_roms(&self) -> &[RecentRom] {
+        &self.recent_roms
+    }
+
+    /// Settings remembered for a game, if any.
+    pub fn config(&self, md5: &str) -> Option<GameConfig> {
+        let text = fs::read_to_string(self.game_directory(md5).join(CONFIG_FILE)).ok()?;
+        Some(GameConfig::parse(&text))
+    }
+
+    /// Launch a game.
+    ///
+    /// This function records the ROM of the emulator (loaded from the given
+    /// path) as the most recently played one, then restores the settings
+    /// and the slots saved for the game, if any. If requested, it also
+    /// restores the state the game was left in.
+    ///
+    /// It fails if the files of the session can't be read or written, or if
+    /// a saved state can't be loaded, which happens when the plugged
+    /// controllers changed since it was saved (see `Console::load_state()`).
+    ///
+    pub fn launch<P: AsRef<Path>>(&mut self, path: P, emulator: &mut Emulator, restore_last_state: bool) -> Result<(), SessionError> {
+        let md5 = emulator.console().cartridge().md5();
+        let path = fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+
+        self.recent_roms.retain(|rom| rom.md5 != md5);
+        self.recent_roms.insert(0, RecentRom { md5: md5.clone(), path });
+        self.recent_roms.truncate(RECENT_ROMS);
+
+        let text: String = self.recent_roms.iter()
+            .map(|rom| format!("{}|{}\n", rom.md5, rom.path.display()))
+            .collect();
+        fs::write(self.directory.join(RECENT_ROMS_FILE), text)?;
+
+        if let Some(config) = self.config(&md5) {
+            config.apply(emulator);
+        }
+
+        let directory = self.game_directory(&md5);
+        for slot in 0..STATE_SLOTS {
+            let state = match fs::read(directory.join(slot_file(slot))) {
+                Ok(bytes) => Some(SaveState::from_bytes(&bytes)?),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(error.into())
+            };
+            emulator.set_slot(slot, state);
+        }
+
+        if restore_last_state {
+            match fs::read(directory.join(LAST_STATE_FILE)) {
+                Ok(bytes) => emulator.console_mut().load_state(&SaveState::from_bytes(&bytes)?)?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Save a game.
+    ///
+    /// This function saves the settings of the emulator, the states of its
+    /// slots and the state of its console for the game it runs, so they're
+    /// restored the next time the game is launched. It's meant to be called
+    /// when the emulator quits; the empty slots are cleared.
+    ///
+    pub fn save(&self, emulator: &Emulator) -> io::Result<()> {
+        let directory = self.game_directory(&emulator.console().cartridge().md5());
+        fs::create_dir_all(&directory)?;
+
+        fs::write(directory.join(CONFIG_FILE), GameConfig::from_emulator(emulator).to_string())?;
+
+        for slot in 0..STATE_SLOTS {
+            let path = directory.join(slot_file(slot));
+            match emulator.slot(slot) {
+                Some(state) => fs::write(path, state.to_bytes())?,
+                None => match fs::remove_file(path) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => ()
+                }
+            }
+        }
+
+        fs::write(directory.join(LAST_STATE_FILE), emulator.console().save_state().to_bytes())
+    }
+
+    fn game_directory(&self, md5: &str) -> PathBuf {
+        self.directory.join(md5)
+    }
+}
+
+fn slot_file(slot: usize) -> String {
+    format!("slot-{}.state", slot)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_game_config() {
+        let config = GameConfig {
+            tv_type: TvType::Mono,
+            difficulties: [Difficulty::Pro, Difficulty::Amateur],
+            orientation: Orientation { rotation: Rotation::ThreeQuarters, flip: Flip::Vertical },
+            is_running_ahead: true
+        };
+        assert_eq!(GameConfig::parse(&config.to_string()), config);
+
+        // The unknown keys and values are ignored.
+        let config = GameConfig::parse("tv-type=mono\nrotation=sideways\nvolume=3\n\nflip=horizontal\n");
+        assert_eq!(config.tv_type, TvType::Mono);
+        assert_eq!(config.orientation, Orientation { rotation: Rotation::None, flip: Flip::Horizontal });
+        assert_eq!(config.difficulties, [Difficulty::Amateur, Difficulty::Amateur]);
+    }
+
+    #[test]
+    fn test_session() {
+        let directory = std::env::temp_dir().join(format!("atari-2600-session-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        let mut rom = vec![0x_EA; 4096];
+        rom[4093..].copy_from_slice(&[0x_4C, 0x_00, 0x_F0]); // JMP $F000
+        let roms = [directory.join("nops.bin"), directory.join("zeros.bin"), directory.join("copy.bin")];
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&roms[0], &rom).unwrap();
+        fs::write(&roms[1], vec![0; 4096]).unwrap();
+        fs::write(&roms[2], &rom).unwrap();
+
+        // A first run; the settings, a slot and the last state are saved.
+        let mut session = Session::open(directory.join("session")).unwrap();
+        assert!(session.recent_roms().is_empty());
+
+        let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&roms[0]).unwrap()));
+        session.launch(&roms[0], &mut emulator, true).unwrap();
+        assert!(session.config(&emulator.console().cartridge().md5()).is_none());
+
+        emulator.console_mut().set_tv_type_switch(TvType::Mono);
+        emulator.console_mut().run_frame();
+        let slot = emulator.console().save_state();
+        emulator.set_slot(0, Some(slot.clone()));
+
+        emulator.console_mut().run_frame();
+        session.save(&emulator).unwrap();
+        let last_frame = emulator.console().save_state().frame();
+
+        // The ROMs are listed by their digest, the most recent first, and the
+        // same game found at another path replaces its entry.
+        let mut other = Emulator::new(Console::new(Cartridge::from_file(&roms[1]).unwrap()));
+        session.launch(&roms[1], &mut other, false).unwrap();
+
+        let mut session = Session::open(directory.join("session")).unwrap();
+        let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&roms[2]).unwrap()));
+        session.launch(&roms[2], &mut emulator, false).unwrap();
+
+        let paths = session.recent_roms().iter().map(|rom| rom.path.file_name().unwrap()).collect::<Vec<_>>();
+        assert_eq!(paths, ["copy.bin", "zeros.bin"]);
+        assert_eq!(session.recent_roms()[1].md5, Cartridge::new(vec![0; 4096]).md5());
+
+        // The settings and the slots are restored, and the last state only
+        // when requested.
+        assert_eq!(emulator.console().tv_type_switch(), TvType::Mono);
+        assert_eq!(emulator.slot(0).unwrap().to_bytes(), slot.to_bytes());
+        assert!(emulator.slot(1).is_none());
+        assert_eq!(emulator.console().save_state().frame(), 0);
+
+        let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&roms[2]).unwrap()));
+        session.launch(&roms[2], &mut emulator, true).unwrap();
+        assert_eq!(emulator.console().save_state().frame(), last_frame);
+
+        // The list is bounded.
+        for index in 0..RECENT_ROMS + 2 {
+            let path = directory.join(format!("{}.bin", index));
+            fs::write(&path, vec![index as u8; 2048]).unwrap();
+            let mut emulator = Emulator::new(Console::new(Cartridge::from_file(&path).unwrap()));
+            session.launch(&path, &mut emulator, false).unwrap();
+        }
+        assert_eq!(session.recent_roms().len(), RECENT_ROMS);
+        assert_eq!(Session::open(directory.join("session")).unwrap().recent_roms(), session.recent_roms());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
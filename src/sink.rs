@@ -0,0 +1,349 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::frame::Frame;
+use crate::texture::{Texture, PixelFormat};
+
+/// A destination of the frames.
+///
+/// The emulator pushes each frame to display into its video sink as soon as
+/// it's completed (see `Emulator::set_video_sink()`), so an integration
+/// composes the sinks it needs instead of polling the emulator for new
+/// frames. The crate comes with sinks presenting the frames in a window
+/// (`WindowSink`), writing them as PNG images (`PngSink`) or as a Y4M video
+/// (`Y4mSink`), and discarding them (`NullSink`); custom backends implement
+/// this trait.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, Frame, VideoSink, TEST_ROM};
+/// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// struct Encoder { /* ... */ }
+/// # impl Encoder {
+/// #     fn new() -> Encoder { Encoder {} }
+/// #     fn encode<T>(&mut self, _pixels: &T) {}
+/// # }
+///
+/// impl VideoSink for Encoder {
+///     fn frame(&mut self, frame: &Frame) {
+///         self.encode(&frame.pixels);
+///     }
+/// }
+///
+/// emulator.set_video_sink(Encoder::new());
+/// ```
+///
+pub trait VideoSink {
+    /// Receive a frame.
+    fn frame(&mut self, frame: &Frame);
+}
+
+/// A destination of the audio.
+///
+/// The emulator pushes the audio samples into its audio sink as soon as
+/// they're produced (see `Emulator::set_audio_sink()`); they're the samples
+/// `Console::take_audio_samples()` would return, at the rate of
+/// `Console::audio_sample_rate()`.
+///
+pub trait AudioSink {
+    /// Receive audio samples.
+    fn samples(&mut self, samples: &[f32]);
+}
+
+/// A sink discarding the frames and the audio.
+///
+/// It's for running the emulator headless (benchmarks, bots, etc.) with the
+/// same setup as the frontends.
+///
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NullSink;
+
+impl VideoSink for NullSink {
+    fn frame(&mut self, _frame: &Frame) {}
+}
+
+impl AudioSink for NullSink {
+    fn samples(&mut self, _samples: &[f32]) {}
+}
+
+/// A sink presenting the frames in a window.
+///
+/// The crate doesn't depend on a windowing toolkit; this sink converts the
+/// frames to textures of the format of the window (see `Texture`) and hands
+/// them to a function which uploads and presents them.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, PixelFormat, WindowSink, TEST_ROM};
+/// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// # struct Window;
+/// # impl Window { fn present(&mut self, _width: usize, _height: usize, _stride: usize, _pixels: &[u8]) {} }
+/// # let mut window = Window;
+/// emulator.set_video_sink(WindowSink::new(PixelFormat::Bgra8, move |texture| {
+///     window.present(texture.width, texture.height, texture.stride, &texture.pixels);
+/// }));
+/// ```
+///
+pub struct WindowSink<F: FnMut(&Texture)> {
+    format: PixelFormat,
+    present: F
+}
+
+impl<F: FnMut(&Texture)> WindowSink<F> {
+    /// Create a window sink.
+    pub fn new(format: PixelFormat, present: F) -> WindowSink<F> {
+        WindowSink {
+            format,
+            present
+        }
+    }
+}
+
+impl<F: FnMut(&Texture)> VideoSink for WindowSink<F> {
+    fn frame(&mut self, frame: &Frame) {
+        (self.present)(&Texture::from_frame(frame, self.format));
+    }
+}
+
+/// A sink writing the frames as PNG images.
+///
+/// This sink writes each frame to a PNG image of a directory (which must
+/// exist), named after the number of the frame (`frame-000042.png`). It stops
+/// at the first error, which is kept until it's taken with `take_error()`.
+///
+pub struct PngSink {
+    directory: PathBuf,
+    error: Option<io::Error>
+}
+
+impl PngSink {
+    /// Create a PNG sink writing to the given directory.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> PngSink {
+        PngSink {
+            directory: directory.into(),
+            error: None
+        }
+    }
+
+    /// Take the error that stopped the sink, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl VideoSink for PngSink {
+    fn frame(&mut self, frame: &Frame) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let path = self.directory.join(format!("frame-{:06}.png", frame.number));
+        self.error = fs::write(path, frame.to_png()).err();
+    }
+}
+
+/// A sink writing the frames as a Y4M video.
+///
+/// This sink writes the frames to an uncompressed YUV4MPEG2 stream (in the
+/// 4:4:4 color space of the BT.601 standard, with pixels twice as wide as
+/// they're high) which video encoders read from a file or a pipe, to record
+/// videos without a dependency on an encoder.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use std::process::{Command, Stdio};
+/// # use atari_2600::{Cartridge, Console, Emulator, Y4mSink, TEST_ROM};
+/// # let mut emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// let encoder = Command::new("ffmpeg")
+///     .args(&["-i", "-", "video.mp4"])
+///     .stdin(Stdio::piped())
+///     .spawn()?;
+/// emulator.set_video_sink(Y4mSink::new(encoder.stdin.unwrap(), emulator.console().tv_standard().frame_rate()));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// It stops at the first error, which is kept until it's taken with
+/// `take_error()`.
+///
+pub struct Y4mSink<W: Write> {
+    writer: W,
+    frame_rate: f64,
+    has_header: bool,
+    error: Option<io::Error>
+}
+
+impl<W: Write> Y4mSink<W> {
+    /// Create a Y4M sink writing to the given writer, at the given frame
+    /// rate.
+    pub fn new(writer: W, frame_rate: f64) -> Y4mSink<W> {
+        Y4mSink {
+            writer,
+            frame_rate,
+            has_header: false,
+            error: None
+        }
+    }
+
+    /// Take the error that stopped the sink, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Writer of the sink.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        if !self.has_header {
+            let frame_rate = (self.frame_rate * 1000.0).round() as u64;
+            writeln!(self.writer, "YUV4MPEG2 W{} H{} F{}:1000 Ip A2:1 C444", Frame::WIDTH, Frame::HEIGHT, frame_rate)?;
+            self.has_header = true;
+        }
+
+        let pixels = frame.pixels.iter().flatten().map(|&pixel| to_ycbcr(pixel)).collect::<Vec<_>>();
+
+        let mut bytes = Vec::with_capacity(6 + pixels.len() * 3);
+        bytes.extend_from_slice(b"FRAME\n");
+        bytes.extend(pixels.iter().map(|(y, _, _)| y));
+        bytes.extend(pixels.iter().map(|(_, cb, _)| cb));
+        bytes.extend(pixels.iter().map(|(_, _, cr)| cr));
+
+        self.writer.write_all(&bytes)
+    }
+}
+
+impl<W: Write> VideoSink for Y4mSink<W> {
+    fn frame(&mut self, frame: &Frame) {
+        if self.error.is_none() {
+            self.error = self.write_frame(frame).err();
+        }
+    }
+}
+
+// Convert a color to the (studio range) YCbCr components of BT.601.
+fn to_ycbcr((red, green, blue): (u8, u8, u8)) -> (u8, u8, u8) {
+    let (red, green, blue) = (red as f32, green as f32, blue as f32);
+
+    let y = 16.0 + (65.738 * red + 129.057 * green + 25.064 * blue) / 256.0;
+    let cb = 128.0 + (-37.945 * red - 74.494 * green + 112.439 * blue) / 256.0;
+    let cr = 128.0 + (112.439 * red - 94.154 * green - 18.285 * blue) / 256.0;
+
+    (y.round() as u8, cb.round() as u8, cr.round() as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::cartridge::Cartridge;
+    use crate::clock::ManualClock;
+    use crate::console::Console;
+    use crate::emulator::Emulator;
+
+    // A sink recording what it receives.
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        frames: Rc<RefCell<Vec<u64>>>,
+        samples: Rc<RefCell<Vec<f32>>>
+    }
+
+    impl VideoSink for RecordingSink {
+        fn frame(&mut self, frame: &Frame) {
+            self.frames.borrow_mut().push(frame.number);
+        }
+    }
+
+    impl AudioSink for RecordingSink {
+        fn samples(&mut self, samples: &[f32]) {
+            self.samples.borrow_mut().extend_from_slice(samples);
+        }
+    }
+
+    #[test]
+    fn test_sinks() {
+        let mut frame = Frame { number: 42, pixels: [[(0, 0, 0); 160]; 192], orientation: Default::default() };
+        frame.pixels[0][0] = (0x_FF, 0x_FF, 0x_FF);
+
+        // The Y4M stream has a header, then a frame marker and 3 planes per
+        // frame.
+        let mut sink = Y4mSink::new(Vec::new(), 59.92);
+        sink.frame(&frame);
+        sink.frame(&frame);
+        assert!(sink.take_error().is_none());
+
+        let bytes = sink.into_inner();
+        let header = b"YUV4MPEG2 W160 H192 F59920:1000 Ip A2:1 C444\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 2 * (6 + 160 * 192 * 3));
+        assert_eq!(&bytes[header.len()..header.len() + 6], b"FRAME\n");
+        assert_eq!(bytes[header.len() + 6], 235);
+        assert_eq!(bytes[header.len() + 7], 16);
+        assert_eq!(bytes[header.len() + 6 + 160 * 192], 128);
+
+        let directory = std::env::temp_dir().join(format!("png-sink-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut sink = PngSink::new(&directory);
+        sink.frame(&frame);
+        assert!(sink.take_error().is_none());
+        assert_eq!(fs::read(directory.join("frame-000042.png")).unwrap(), frame.to_png());
+        fs::remove_dir_all(&directory).unwrap();
+
+        // The first error is kept, and the sink stops until it's taken.
+        sink.frame(&frame);
+        sink.frame(&frame);
+        assert!(sink.take_error().is_some());
+        assert!(sink.take_error().is_none());
+
+        let mut textures = Vec::new();
+        WindowSink::new(PixelFormat::Rgb8, |texture: &Texture| textures.push(texture.pixels[0..3].to_vec())).frame(&frame);
+        assert_eq!(textures, vec![vec![0x_FF, 0x_FF, 0x_FF]]);
+
+        // The emulator pushes its frames and its audio into the sinks.
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut emulator = Emulator::new(Console::new(Cartridge::new(rom)))
+            .with_clock(clock.clone());
+
+        let sink = RecordingSink::default();
+        emulator.set_video_sink(sink.clone());
+        emulator.set_audio_sink(sink.clone());
+
+        emulator.tick();
+        for _ in 0..10 {
+            clock.advance(Duration::from_millis(10));
+            emulator.tick();
+        }
+        assert_eq!(*sink.frames.borrow(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(emulator.console().queued_audio_samples(), 0);
+
+        let samples = sink.samples.borrow().len();
+        assert!(samples > 0);
+
+        // The fade out of the pause goes to the sink too.
+        emulator.pause();
+        assert!(sink.samples.borrow().len() > samples);
+
+        emulator.clear_video_sink();
+        emulator.clear_audio_sink();
+        emulator.resume();
+        clock.advance(Duration::from_millis(20));
+        emulator.tick();
+        assert_eq!(sink.frames.borrow().len(), 5);
+        assert!(emulator.console().queued_audio_samples() > 0);
+    }
+}
@@ -10,29 +10,369 @@
 //!
 //! This module defines something that is to be described.
 //!
-use crate::location::{GRP0, GRP1, REFP0, REFP1};
-use crate::console::Console;
+use crate::location::{GRP0, GRP1, REFP0, REFP1, VDELP0, VDELP1, NUSIZ0, NUSIZ1};
+use crate::tia::Tia;
 use crate::console::Player;
 use crate::utils::byte_to_boolean_array;
 
-pub(crate) fn _player_bits(console: &Console, player: Player) -> [bool; 8] {
+pub(crate) fn is_player_delayed(tia: &Tia, player: Player) -> bool {
+    //   25      VDELP0  .......1  vertical delay player 0
+    //   26      VDELP1  .......1  vertical delay player 1
     match player {
-        Player::One => byte_to_boolean_array(*console.memory(GRP0)),
-        Player::Two => byte_to_boolean_array(*console.memory(GRP1))
+        Player::One => tia.register(VDELP0) & 0b0000_0001 != 0,
+        Player::Two => tia.register(VDELP1) & 0b0000_0001 != 0
     }
 }
 
-pub(crate) fn _is_player_mirrored(console: &Console, player: Player) -> bool {
+pub(crate) fn player_graphics(tia: &Tia, player: Player) -> u8 {
+    // The TIA holds two copies of the graphics of each player; the new one is
+    // what was last written to GRPx, and the old one is displayed instead when
+    // vertical delay is enabled.
+    let index = match player {
+        Player::One => 0,
+        Player::Two => 1
+    };
+
+    if is_player_delayed(tia, player) {
+        tia.old_players_graphics[index]
+    }
+    else {
+        match player {
+            Player::One => tia.register(GRP0),
+            Player::Two => tia.register(GRP1)
+        }
+    }
+}
+
+pub(crate) fn player_copies(tia: &Tia, player: Player) -> &'static [u32] {
+    //   04      NUSIZ0  ..111111  number-size player-missile 0
+    //   05      NUSIZ1  ..111111  number-size player-missile 1
+    //
+    // The 3 lower bits select the number of copies of the player (and of its
+    // missile) and their spacing; the offsets are in pixels from the position
+    // of the object.
+    let value = match player {
+        Player::One => tia.register(NUSIZ0),
+        Player::Two => tia.register(NUSIZ1)
+    };
+
+    match value & 0b0000_0111 {
+        1 => &[0, 16],
+        2 => &[0, 32],
+        3 => &[0, 16, 32],
+        4 => &[0, 64],
+        6 => &[0, 32, 64],
+        _ => &[0]
+    }
+}
+
+pub(crate) fn player_width(tia: &Tia, player: Player) -> u32 {
+    // The player is stretched to double size (5) or quad size (7), otherwise
+    // each copy is 8 pixels wide.
+    let value = match player {
+        Player::One => tia.register(NUSIZ0),
+        Player::Two => tia.register(NUSIZ1)
+    };
+
+    match value & 0b0000_0111 {
+        5 => 16,
+        7 => 32,
+        _ => 8
+    }
+}
+
+pub(crate) fn player_bits(tia: &Tia, player: Player) -> [bool; 8] {
+    byte_to_boolean_array(player_graphics(tia, player))
+}
+
+pub(crate) fn is_player_mirrored(tia: &Tia, player: Player) -> bool {
     match player {
-        Player::One => *console.memory(REFP0) & 0b000_1000 != 0,
-        Player::Two => *console.memory(REFP1) & 0b000_1000 != 0
+        Player::One => tia.register(REFP0) & 0b000_1000 != 0,
+        Player::Two => tia.register(REFP1) & 0b000_1000 != 0
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::rom_builder::RomBuilder;
+    use crate::console::Console;
+    use crate::location::{COLUP0, RESP0};
+    use crate::tia::HORIZONTAL_CYCLES;
+
+    #[test]
+    fn test_player_graphics() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        *console.memory_mut(GRP0) = 0b1010_1010;
+        console.tia.old_players_graphics[0] = 0b0101_0101;
+
+        // The new copy is displayed unless vertical delay is enabled.
+        assert!(!is_player_delayed(&console.tia, Player::One));
+        assert_eq!(player_graphics(&console.tia, Player::One), 0b1010_1010);
+
+        *console.memory_mut(VDELP0) = 0b0000_0001;
+        assert!(is_player_delayed(&console.tia, Player::One));
+        assert_eq!(player_graphics(&console.tia, Player::One), 0b0101_0101);
+        assert_eq!(player_graphics(&console.tia, Player::Two), 0b0000_0000);
+    }
+
+    #[test]
+    fn test_player_graphics_latches() {
+        // The core of the six-digit score routine; the six digits are loaded
+        // (here, from immediate values) and written to GRP0 and GRP1 in turn
+        // with vertical delay enabled on both players, then the last four
+        // writes happen during the scanline, in a timely manner.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                GRP0 = $1B
+                GRP1 = $1C
+
+                lda #$D0
+                sta GRP0
+                lda #$D1
+                sta GRP1
+                lda #$D2
+                sta GRP0
+                lda #$D3
+                ldx #$D4
+                ldy #$D5
+                sta GRP1
+                stx GRP0
+                sty GRP1
+                sta GRP0
+            ")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        *console.memory_mut(VDELP0) = 0b0000_0001;
+        *console.memory_mut(VDELP1) = 0b0000_0001;
+
+        for _ in 0..9 {
+            console.execute_instruction();
+        }
+
+        // The first two digits are displayed before the scanline starts.
+        assert_eq!(player_graphics(&console.tia, Player::One), 0x_D0);
+        assert_eq!(player_graphics(&console.tia, Player::Two), 0x_D1);
+
+        // Then each write reveals the next digit of the other player.
+        let digits = [(0x_D2, 0x_D1), (0x_D2, 0x_D3), (0x_D4, 0x_D3), (0x_D4, 0x_D5)];
+        for (first_player, second_player) in digits.iter() {
+            console.execute_instruction();
+            assert_eq!(player_graphics(&console.tia, Player::One), *first_player);
+            assert_eq!(player_graphics(&console.tia, Player::Two), *second_player);
+        }
+
+        // Without vertical delay, the new copies are displayed.
+        *console.memory_mut(VDELP0) = 0b0000_0000;
+        *console.memory_mut(VDELP1) = 0b0000_0000;
+        assert_eq!(player_graphics(&console.tia, Player::One), 0x_D3);
+        assert_eq!(player_graphics(&console.tia, Player::Two), 0x_D5);
+    }
+
+    #[test]
+    fn test_player_copies() {
+        // Draw a scanline with the first player at the given position, its
+        // graphics and NUSIZ0, mirrored or not, resetting it on the given color
+        // cycle, and return the pixels it covers.
+        fn draw(position: u32, graphics: u8, size: u8, mirrored: bool, reset: Option<u32>) -> Vec<usize> {
+            let mut tia = Tia::new();
+            tia.scanline = 100;
+            tia.write(COLUP0, 0x_1E);
+            tia.write(GRP0, graphics);
+            tia.write(NUSIZ0, size);
+            tia.write(REFP0, if mirrored { 0b0000_1000 } else { 0 });
+            tia.players_position[0] = position;
+
+            for cycle in 0..HORIZONTAL_CYCLES {
+                if Some(cycle) == reset {
+                    tia.write(RESP0, 0);
+                }
+                tia.step();
+            }
+
+            let background = tia.object_colors().background;
+            (0..160).filter(|pixel| tia.framebuffer[60][*pixel] != background).collect()
+        }
+
+        // The graphics are drawn from their high bit, or from their low bit
+        // when the player is mirrored.
+        assert_eq!(draw(10, 0b1100_0001, 0, false, None), [10, 11, 17]);
+        assert_eq!(draw(10, 0b1100_0001, 0, true, None), [10, 16, 17]);
+        assert_eq!(draw(10, 0b1100_0000, 5, true, None), [22, 23, 24, 25]);
+
+        // The copies, and the stretched players.
+        assert_eq!(draw(10, 0b1000_0000, 1, false, None), [10, 26]);
+        assert_eq!(draw(10, 0b1000_0000, 3, false, None), [10, 26, 42]);
+        assert_eq!(draw(10, 0b1000_0000, 6, false, None), [10, 42, 74]);
+        assert_eq!(draw(10, 0b1000_0001, 5, false, None), [10, 11, 24, 25]);
+        assert_eq!(draw(10, 0b1000_0000, 7, false, None), [10, 11, 12, 13]);
+        assert_eq!(draw(150, 0b1000_0000, 1, false, None), [6, 150]);
+
+        // Reset in the visible area, the player misses its first copy on
+        // this scanline, but not the other ones (it's drawn at its previous
+        // position up to the reset); reset in the horizontal blank, it has all
+        // of them.
+        assert_eq!(draw(10, 0b1000_0000, 3, false, Some(68 + 40)), [10, 26, 56, 72]);
+        assert_eq!(draw(10, 0b1000_0000, 0, false, Some(68 + 40)), [10]);
+        assert_eq!(draw(10, 0b1000_0000, 3, false, Some(20)), [3, 19, 35]);
+    }
 
     #[test]
-    fn test_player() {
+    fn test_six_sprites_kernel() {
+        // The 48-pixel bitmap kernel (six-digit scores, titles, etc.); both
+        // players have three close copies and vertical delay, the second
+        // player 8 pixels to the right of the first one, and the graphics of
+        // the last four copies are written while the first ones are drawn.
+        let cartridge = RomBuilder::new()
+            .code(0x_F000, "
+                NUSIZ0 = $04
+                NUSIZ1 = $05
+                COLUP0 = $06
+                COLUP1 = $07
+                RESP0 = $10
+                RESP1 = $11
+                GRP0 = $1B
+                GRP1 = $1C
+                HMP1 = $21
+                VDELP0 = $25
+                VDELP1 = $26
+                HMOVE = $2A
+
+                lda #3
+                sta NUSIZ0
+                sta NUSIZ1
+                lda #$1E
+                sta COLUP0
+                lda #$44
+                sta COLUP1
+                lda #$10
+                sta HMP1
+            frame:
+                lda #2
+                sta $01   ; VBLANK
+                sta $00   ; VSYNC
+                sta $02   ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #38
+            vblank:
+                sta $02
+                dex
+                bne vblank
+
+                ; The first visible scanline; the players are reset on the
+                ; CPU cycles 39 and 42 (the pixels 49 and 58) with all their
+                ; bits set.
+                lda #0
+                sta $01
+                lda #$FF
+                sta GRP0
+                sta GRP1
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                sta RESP0
+                sta RESP1
+                sta $02
+
+                ; The second player is moved 1 pixel to the left, 8 pixels
+                ; to the right of the first one.
+                sta HMOVE
+                lda #0
+                sta GRP0
+                sta GRP1
+                lda #1
+                sta VDELP0
+                sta VDELP1
+                lda #8
+                sta $80
+                sta $02
+                bne kernel
+
+                ; The kernel, which starts on the CPU cycle 3 of each
+                ; scanline; the last four writes happen on the CPU cycles 43,
+                ; 46, 49 and 52.
+            kernel:
+                lda #$80
+                sta GRP0
+                lda #$C0
+                sta GRP1
+                lda #$E0
+                sta GRP0
+                lda #$F0
+                ldx #$F8
+                ldy #$FC
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                nop
+                bit $80
+                sta GRP1
+                stx GRP0
+                sty GRP1
+                sta GRP0
+                dec $80
+                sta $02
+                bne kernel
+
+                lda #0
+                sta GRP0
+                sta GRP1
+                sta GRP0
+                sta VDELP0
+                sta VDELP1
+                ldx #211
+            overscan:
+                sta $02
+                dex
+                bne overscan
+                jmp frame
+            ")
+            .build()
+            .unwrap();
+
+        let mut console = Console::new(cartridge);
+        console.run_frame();
+        console.run_frame();
+        assert_eq!(console.sync_lines(), Some(262));
+
+        // The pixels 49 to 99 of a line; '0' and '1' are the colors of the
+        // players.
+        let colors = console.tia.object_colors();
+        let frame = console.frame();
+        let line = |line: usize| frame.pixels[line][49..100].iter().map(|pixel| match *pixel {
+            color if color == colors.player0 => '0',
+            color if color == colors.player1 => '1',
+            _ => '.'
+        }).collect::<String>();
+
+        // On the scanline the players are reset, their first copies are
+        // missing (the second player is drawn at its previous position up to
+        // its reset).
+        assert_eq!(line(0), "........1.......00000000.111111100000000.11111111..");
+
+        // The six copies draw the six digits side by side.
+        for index in 2..10 {
+            assert_eq!(line(index), "0.......11......000.....1111....00000...111111.....");
+        }
+        assert_eq!(line(10), ".".repeat(51));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1247 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::audio::AudioChannel;
+use crate::rng::Rng;
+use crate::frame::{Frame, Orientation};
+use crate::scaler::{Scaler, ScaleFilter};
+use crate::console::TvStandard;
+use crate::tia::{Motion, HORIZONTAL_CYCLES};
+#[cfg(feature = "debugger")]
+use crate::dump::StateDump;
+
+// The first bytes of a serialized save state and of a serialized replay.
+pub(crate) const STATE_MAGIC: &[u8; 4] = b"A26S";
+pub(crate) const REPLAY_MAGIC: &[u8; 4] = b"A26R";
+
+/// An error that occurred while restoring a state.
+#[derive(Debug)]
+pub struct StateError {
+    pub message: String
+}
+
+impl StateError {
+    pub(crate) fn new(message: &str) -> StateError {
+        StateError {
+            message: message.to_string()
+        }
+    }
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// A writer of serialized states.
+///
+/// This structure is a buffer the states are written to, and read back from
+/// (see `StateReader`); the save states and the replays are serialized with
+/// it. Controllers also have an internal state that isn't visible from the
+/// ports (the position of a paddle, the phase of the gray code of a driving
+/// controller, etc.) and that must be saved along with the console for save
+/// states and replays to be faithful; they write it the same way.
+///
+/// ```
+/// # use atari_2600::{StateError, StateWriter};
+/// # struct Controller { button: bool, position: i32 }
+/// # impl Controller {
+/// # fn save_and_restore(&mut self) -> Result<(), StateError> {
+/// let mut state = StateWriter::new();
+/// state.write_bool(self.button);
+/// state.write_i32(self.position);
+///
+/// let mut reader = state.reader();
+/// self.button = reader.read_bool()?;
+/// self.position = reader.read_i32()?;
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct StateWriter {
+    bytes: Vec<u8>
+}
+
+impl StateWriter {
+    /// Create an empty state.
+    pub fn new() -> StateWriter {
+        StateWriter {
+            bytes: Vec::new()
+        }
+    }
+
+    /// Create a state from its serialized form.
+    pub fn from_bytes(bytes: Vec<u8>) -> StateWriter {
+        StateWriter {
+            bytes
+        }
+    }
+
+    /// Serialized form of the state.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Write a boolean.
+    pub fn write_bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    /// Write an unsigned 8-bit integer.
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    /// Write an unsigned 16-bit integer.
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write an unsigned 32-bit integer.
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write an unsigned 64-bit integer.
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write a signed 32-bit integer.
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write bytes.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Write the state of another controller.
+    ///
+    /// This function is meant for adapters which must save the state of the
+    /// controllers plugged into them.
+    ///
+    pub fn write_state(&mut self, state: &StateWriter) {
+        self.write_u32(state.bytes.len() as u32);
+        self.bytes.extend_from_slice(&state.bytes);
+    }
+
+    /// Create a reader of the state.
+    pub fn reader(&self) -> StateReader<'_> {
+        StateReader {
+            bytes: &self.bytes
+        }
+    }
+}
+
+/// A reader of a serialized state.
+///
+/// Values must be read in the same order they were written (see
+/// `StateWriter`); reading past the end of the state is an error.
+///
+pub struct StateReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> StateReader<'a> {
+        StateReader {
+            bytes
+        }
+    }
+
+    /// Read bytes.
+    pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < count {
+            return Err(StateError::new("state is truncated"));
+        }
+
+        let (bytes, remaining) = self.bytes.split_at(count);
+        self.bytes = remaining;
+
+        Ok(bytes)
+    }
+
+    /// Read a boolean.
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    /// Read an unsigned 8-bit integer.
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Read an unsigned 16-bit integer.
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Read an unsigned 32-bit integer.
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read an unsigned 64-bit integer.
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        let bytes = self.read_bytes(8)?;
+        let mut array = [0; 8];
+        array.copy_from_slice(bytes);
+
+        Ok(u64::from_le_bytes(array))
+    }
+
+    /// Read a signed 32-bit integer.
+    pub fn read_i32(&mut self) -> Result<i32, StateError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read the state of another controller.
+    pub fn read_state(&mut self) -> Result<StateWriter, StateError> {
+        let length = self.read_u32()? as usize;
+        Ok(StateWriter::from_bytes(self.read_bytes(length)?.to_vec()))
+    }
+
+    /// Check whether everything was read.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// The version of the format of the serialized save states and replays.
+///
+/// The minor version is incremented when fields are appended to the format,
+/// or dropped from it; states of an older minor version are migrated when
+/// they're loaded (the missing fields get a default value, and the dropped
+/// ones are skipped). The major version is incremented when the format
+/// changes in a way that can't be migrated; states of another major version
+/// can't be loaded.
+///
+/// - 1.0; the console and the internal state of the plugged controllers.
+/// - 1.1; the audio channels and the random number generator.
+/// - 1.2; the address of the opcode that jammed the CPU.
+/// - 1.3; the remaining frames of the tapped reset and select switches.
+/// - 1.4; the interrupt control of the PIA (the enabled interrupts and the
+///   edge detection of PA7).
+/// - 1.5; the thumbnail, if any.
+/// - 1.6; the horizontal motion in progress (see HMOVE).
+/// - 1.7; the hidden first copies of the players (see RESP0 and RESP1).
+/// - 1.8; what was written to the unmapped locations is dropped.
+/// - 1.9; the bank selected by the bank switching.
+/// - 1.10; the measurement of the vertical sync and the roll of the picture.
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct StateVersion {
+    pub major: u16,
+    pub minor: u16
+}
+
+impl StateVersion {
+    /// The version of the states written by this version of the crate.
+    pub const CURRENT: StateVersion = StateVersion { major: 1, minor: 10 };
+
+    pub(crate) fn write(writer: &mut StateWriter, magic: &[u8; 4]) {
+        writer.write_bytes(magic);
+        writer.write_u16(StateVersion::CURRENT.major);
+        writer.write_u16(StateVersion::CURRENT.minor);
+    }
+
+    pub(crate) fn read(reader: &mut StateReader, magic: &[u8; 4]) -> Result<StateVersion, StateError> {
+        if reader.read_bytes(4).ok() != Some(&magic[..]) {
+            return Err(StateError::new("not a state of this emulator"));
+        }
+
+        let version = StateVersion {
+            major: reader.read_u16()?,
+            minor: reader.read_u16()?
+        };
+
+        if version.major != StateVersion::CURRENT.major || version > StateVersion::CURRENT {
+            return Err(StateError::new(&format!("state version {} isn't supported", version)));
+        }
+
+        Ok(version)
+    }
+}
+
+impl fmt::Display for StateVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A downscaled picture of the frame of a save state.
+///
+/// The pixels are listed line by line, with 4 bytes per pixel (red, green,
+/// blue and alpha), like the output of `Scaler`.
+///
+/// See `SaveState::with_thumbnail()`.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>
+}
+
+/// A save state of the console.
+///
+/// This structure is a snapshot of the entire state of the console (the CPU,
+/// the memory, the TIA, the PIA, the beam, etc.) including the internal state
+/// of the plugged controllers. It's created with `Console::save_state()` and
+/// restored with `Console::load_state()`. The cartridge isn't part of it; a
+/// state must be restored on a console with the same cartridge.
+///
+/// It's serialized with `to_bytes()`, in the format described by
+/// `StateVersion`, and states written by older versions of the crate remain
+/// loadable with `from_bytes()`.
+///
+/// It's plain data; it's `Send`, `Sync` and `Clone`, so it can be handed to
+/// another thread (for netplay, rewind or run-ahead) without being
+/// serialized, and two states can be compared with `diff()`.
+///
+#[derive(Clone)]
+pub struct SaveState {
+    pub(crate) pointer_counter: u16,
+    pub(crate) accumulator: u8,
+    pub(crate) x_register: u8,
+    pub(crate) y_register: u8,
+    pub(crate) flags: [bool; 7],
+    pub(crate) stack_pointer: u8,
+
+    pub(crate) tia: [u8; 62],
+    pub(crate) ram: [u8; 128],
+    pub(crate) pia: [u8; 4],
+
+    pub(crate) timer_value: u8,
+    pub(crate) timer_status: u8,
+    pub(crate) timer_interval: u32,
+    pub(crate) timer_elapsed_clocks: u32,
+
+    pub(crate) cycles_count: u128,
+    pub(crate) color_cycles_count: u128,
+    pub(crate) instructions_count: u128,
+    pub(crate) frames_count: u64,
+
+    pub(crate) old_players_graphics: [u8; 2],
+    pub(crate) old_ball_enable: u8,
+    pub(crate) players_position: [u32; 2],
+    pub(crate) missiles_position: [u32; 2],
+    pub(crate) ball_position: u32,
+
+    pub(crate) scanline: u32,
+    pub(crate) scanline_cycle: u32,
+    pub(crate) is_vsync: bool,
+    pub(crate) cpu_halt: bool,
+    pub(crate) data_bus: u8,
+
+    pub(crate) input_charge_times: [Option<u32>; 4],
+    pub(crate) input_charges: [u32; 4],
+    pub(crate) is_dumped: bool,
+
+    pub(crate) framebuffer: Box<[[(u8, u8, u8); 160]; 192]>,
+    pub(crate) pending_framebuffer: Box<[[(u8, u8, u8); 160]; 192]>,
+
+    pub(crate) audio_channels: [AudioChannel; 2],
+    pub(crate) rng_state: u64,
+    pub(crate) jam_address: Option<u16>,
+    pub(crate) switch_taps: [u32; 2],
+    pub(crate) pia_control: u8,
+    pub(crate) motion: Option<Motion>,
+    pub(crate) is_hmove_blank: bool,
+    pub(crate) hidden_first_copies: [u32; 2],
+    pub(crate) bank: usize,
+    pub(crate) lines_since_vsync: Option<u32>,
+    pub(crate) vsync_lines: Option<u32>,
+    pub(crate) pending_vsync_lines: Option<u32>,
+    pub(crate) sync_roll: u32,
+    pub(crate) is_sync_unstable: bool,
+
+    pub(crate) elapsed_time: Duration,
+    pub(crate) remaining_cycles: isize,
+    pub(crate) timer_block: bool,
+
+    pub(crate) controllers: [Option<StateWriter>; 2],
+
+    pub(crate) thumbnail: Option<Thumbnail>
+}
+
+impl SaveState {
+    /// Number of frames completed when the state was saved.
+    pub fn frame(&self) -> u64 {
+        self.frames_count
+    }
+
+    /// Add a thumbnail to the state.
+    ///
+    /// This function downscales the last completed frame of the state to the
+    /// given size (see `Scaler`; the frame keeps its aspect ratio and is
+    /// centered with black borders) and keeps it in the state, so a frontend
+    /// can show a preview of the state in its load-state menu. It's saved
+    /// along with the state; it takes 4 bytes per pixel.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```no_run")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use std::fs;
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// let state = console.save_state().with_thumbnail(80, 48);
+    /// fs::write("slot-1.state", state.to_bytes())?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    pub fn with_thumbnail(mut self, width: usize, height: usize) -> SaveState {
+        let frame = Frame {
+            number: self.frames_count,
+            pixels: *self.pending_framebuffer,
+            orientation: Orientation::default()
+        };
+
+        let scaler = Scaler {
+            filter: ScaleFilter::Bilinear,
+            integer_scaling: false,
+            ..Scaler::default()
+        };
+
+        self.thumbnail = Some(Thumbnail {
+            width,
+            height,
+            pixels: scaler.scale(&frame, width, height)
+        });
+
+        self
+    }
+
+    /// Thumbnail of the state, if any.
+    pub fn thumbnail(&self) -> Option<&Thumbnail> {
+        self.thumbnail.as_ref()
+    }
+
+    /// State of the left (player one) and right (player two) controllers.
+    pub fn controllers(&self) -> &[Option<StateWriter>; 2] {
+        &self.controllers
+    }
+
+    /// Compare with another state.
+    ///
+    /// This function returns what differs between this state and another one
+    /// (see `StateDiff`); comparing two states is cheap compared to
+    /// serializing them. It's useful to find where two consoles that should
+    /// run in lockstep (netplay, replays, etc.) went out of sync.
+    ///
+    #[cfg_attr(feature = "test-rom", doc = "```")]
+    #[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+    /// # use atari_2600::{Cartridge, Console, TEST_ROM};
+    /// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+    /// # let local_state = console.save_state();
+    /// # console.run_frame();
+    /// # let remote_state = console.save_state();
+    /// let diff = local_state.diff(&remote_state);
+    /// if !diff.is_empty() {
+    ///     println!("desync at frame {}, RAM: {:02X?}", local_state.frame(), diff.ram);
+    /// }
+    /// ```
+    ///
+    pub fn diff(&self, other: &SaveState) -> StateDiff {
+        let differing = |a: &[u8], b: &[u8], base: u16| {
+            a.iter().zip(b.iter())
+                .enumerate()
+                .filter(|(_, (a, b))| a != b)
+                .map(|(index, _)| base + index as u16)
+                .collect::<Vec<_>>()
+        };
+
+        StateDiff {
+            cpu: (self.pointer_counter, self.accumulator, self.x_register, self.y_register, self.flags, self.stack_pointer, self.jam_address, self.data_bus) !=
+                 (other.pointer_counter, other.accumulator, other.x_register, other.y_register, other.flags, other.stack_pointer, other.jam_address, other.data_bus),
+            ram: differing(&self.ram, &other.ram, 0x_80),
+            tia: differing(&self.tia, &other.tia, 0x_00),
+            tia_internals: (self.old_players_graphics, self.old_ball_enable, self.players_position, self.missiles_position, self.ball_position, self.is_vsync, self.cpu_halt, self.motion, self.is_hmove_blank, self.hidden_first_copies) !=
+                           (other.old_players_graphics, other.old_ball_enable, other.players_position, other.missiles_position, other.ball_position, other.is_vsync, other.cpu_halt, other.motion, other.is_hmove_blank, other.hidden_first_copies) ||
+                           self.audio_channels != other.audio_channels ||
+                           (self.input_charge_times, self.input_charges, self.is_dumped) != (other.input_charge_times, other.input_charges, other.is_dumped),
+            pia: (self.pia, self.timer_value, self.timer_status, self.timer_interval, self.timer_elapsed_clocks, self.timer_block, self.pia_control) !=
+                 (other.pia, other.timer_value, other.timer_status, other.timer_interval, other.timer_elapsed_clocks, other.timer_block, other.pia_control),
+            beam: (self.scanline, self.scanline_cycle) != (other.scanline, other.scanline_cycle) ||
+                  (self.lines_since_vsync, self.vsync_lines, self.pending_vsync_lines, self.sync_roll, self.is_sync_unstable) !=
+                  (other.lines_since_vsync, other.vsync_lines, other.pending_vsync_lines, other.sync_roll, other.is_sync_unstable),
+            frames: self.framebuffer != other.framebuffer || self.pending_framebuffer != other.pending_framebuffer,
+            controllers: self.controllers != other.controllers,
+            other: (self.cycles_count, self.color_cycles_count, self.instructions_count, self.frames_count) !=
+                   (other.cycles_count, other.color_cycles_count, other.instructions_count, other.frames_count) ||
+                   (self.rng_state, self.switch_taps, self.elapsed_time, self.remaining_cycles, self.bank) != (other.rng_state, other.switch_taps, other.elapsed_time, other.remaining_cycles, other.bank)
+        }
+    }
+
+    /// Dump the state in a human-readable form.
+    ///
+    /// This function returns the registers, the RAM, the registers of the
+    /// TIA and the timer as labeled lines of text (see `StateDump`), to be
+    /// attached to the bug reports and compared with another dump.
+    ///
+    #[cfg(feature = "debugger")]
+    pub fn dump(&self) -> StateDump {
+        StateDump::new(self)
+    }
+
+    /// Serialize the state.
+    ///
+    /// This function returns the state serialized in the current version of
+    /// the format.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        StateVersion::write(&mut writer, STATE_MAGIC);
+        self.write(&mut writer);
+
+        writer.bytes
+    }
+
+    /// Deserialize a state.
+    ///
+    /// This function reads a state serialized by `to_bytes()`, possibly by an
+    /// older version of the crate, and migrates it to the current version of
+    /// the format.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, StateError> {
+        let mut reader = StateReader::new(bytes);
+        let version = StateVersion::read(&mut reader, STATE_MAGIC)?;
+        let state = SaveState::read(&mut reader, version)?;
+
+        if !reader.is_empty() {
+            return Err(StateError::new("state has trailing bytes"));
+        }
+
+        Ok(state)
+    }
+
+    pub(crate) fn write(&self, writer: &mut StateWriter) {
+        // Version 1.0.
+        writer.write_u16(self.pointer_counter);
+        writer.write_u8(self.accumulator);
+        writer.write_u8(self.x_register);
+        writer.write_u8(self.y_register);
+        for flag in self.flags.iter() {
+            writer.write_bool(*flag);
+        }
+        writer.write_u8(self.stack_pointer);
+
+        writer.write_bytes(&self.tia);
+        writer.write_bytes(&self.ram);
+        writer.write_bytes(&self.pia);
+
+        writer.write_u8(self.timer_value);
+        writer.write_u8(self.timer_status);
+        writer.write_u32(self.timer_interval);
+        writer.write_u32(self.timer_elapsed_clocks);
+
+        for count in [self.cycles_count, self.color_cycles_count, self.instructions_count].iter() {
+            writer.write_u64(*count as u64);
+            writer.write_u64((*count >> 64) as u64);
+        }
+        writer.write_u64(self.frames_count);
+
+        writer.write_bytes(&self.old_players_graphics);
+        writer.write_u8(self.old_ball_enable);
+        for position in self.players_position.iter().chain(self.missiles_position.iter()) {
+            writer.write_u32(*position);
+        }
+        writer.write_u32(self.ball_position);
+
+        writer.write_u32(self.scanline);
+        writer.write_u32(self.scanline_cycle);
+        writer.write_bool(self.is_vsync);
+        writer.write_bool(self.cpu_halt);
+        writer.write_u8(self.data_bus);
+
+        for time in self.input_charge_times.iter() {
+            writer.write_bool(time.is_some());
+            writer.write_u32(time.unwrap_or(0));
+        }
+        for charge in self.input_charges.iter() {
+            writer.write_u32(*charge);
+        }
+        writer.write_bool(self.is_dumped);
+
+        for framebuffer in [&self.framebuffer, &self.pending_framebuffer].iter() {
+            for (red, green, blue) in framebuffer.iter().flatten() {
+                writer.write_bytes(&[*red, *green, *blue]);
+            }
+        }
+
+        writer.write_u64(self.elapsed_time.as_secs());
+        writer.write_u32(self.elapsed_time.subsec_nanos());
+        writer.write_u64(self.remaining_cycles as i64 as u64);
+        writer.write_bool(self.timer_block);
+
+        write_controllers(writer, &self.controllers);
+
+        // Version 1.1.
+        for channel in self.audio_channels.iter() {
+            channel.write_state(writer);
+        }
+        writer.write_u64(self.rng_state);
+
+        // Version 1.2.
+        writer.write_bool(self.jam_address.is_some());
+        writer.write_u16(self.jam_address.unwrap_or_default());
+
+        // Version 1.3.
+        writer.write_u32(self.switch_taps[0]);
+        writer.write_u32(self.switch_taps[1]);
+
+        // Version 1.4.
+        writer.write_u8(self.pia_control);
+
+        // Version 1.5.
+        writer.write_bool(self.thumbnail.is_some());
+        if let Some(thumbnail) = &self.thumbnail {
+            writer.write_u32(thumbnail.width as u32);
+            writer.write_u32(thumbnail.height as u32);
+            writer.write_bytes(&thumbnail.pixels);
+        }
+
+        // Version 1.6.
+        let motion = self.motion.unwrap_or_default();
+        writer.write_bool(self.motion.is_some());
+        writer.write_u32(motion.delay);
+        writer.write_u8(motion.counter);
+        for latch in motion.latches {
+            writer.write_bool(latch);
+        }
+        writer.write_bytes(&motion.values);
+        writer.write_bool(self.is_hmove_blank);
+
+        // Version 1.7.
+        writer.write_u32(self.hidden_first_copies[0]);
+        writer.write_u32(self.hidden_first_copies[1]);
+
+        // Version 1.9.
+        writer.write_u32(self.bank as u32);
+
+        // Version 1.10.
+        for lines in [self.lines_since_vsync, self.vsync_lines, self.pending_vsync_lines] {
+            writer.write_bool(lines.is_some());
+            writer.write_u32(lines.unwrap_or(0));
+        }
+        writer.write_u32(self.sync_roll);
+        writer.write_bool(self.is_sync_unstable);
+    }
+
+    pub(crate) fn read(reader: &mut StateReader, version: StateVersion) -> Result<SaveState, StateError> {
+        let pointer_counter = reader.read_u16()?;
+        let accumulator = reader.read_u8()?;
+        let x_register = reader.read_u8()?;
+        let y_register = reader.read_u8()?;
+        let mut flags = [false; 7];
+        for flag in flags.iter_mut() {
+            *flag = reader.read_bool()?;
+        }
+        let stack_pointer = reader.read_u8()?;
+
+        let mut tia = [0; 62];
+        tia.copy_from_slice(reader.read_bytes(62)?);
+        let mut ram = [0; 128];
+        ram.copy_from_slice(reader.read_bytes(128)?);
+        let mut pia = [0; 4];
+        pia.copy_from_slice(reader.read_bytes(4)?);
+
+        // Version 1.8 dropped what was written to the unmapped locations; it
+        // isn't part of the state of the console.
+        if version.minor < 8 {
+            reader.read_bytes(8192)?;
+        }
+
+        let timer_value = reader.read_u8()?;
+        let timer_status = reader.read_u8()?;
+        let timer_interval = reader.read_u32()?;
+        let timer_elapsed_clocks = reader.read_u32()?;
+
+        // The timer is decremented once per interval, and the clocks elapsed
+        // until the next decrement are within it.
+        if ![1, 8, 64, 1024].contains(&timer_interval) {
+            return Err(StateError::new("timer interval is invalid"));
+        }
+        if timer_elapsed_clocks == 0 || timer_elapsed_clocks > timer_interval {
+            return Err(StateError::new("timer elapsed clocks are out of range"));
+        }
+
+        let mut counts = [0_u128; 3];
+        for count in counts.iter_mut() {
+            *count = reader.read_u64()? as u128 | (reader.read_u64()? as u128) << 64;
+        }
+        let frames_count = reader.read_u64()?;
+
+        let mut old_players_graphics = [0; 2];
+        old_players_graphics.copy_from_slice(reader.read_bytes(2)?);
+        let old_ball_enable = reader.read_u8()?;
+        let players_position = [reader.read_u32()?, reader.read_u32()?];
+        let missiles_position = [reader.read_u32()?, reader.read_u32()?];
+        let ball_position = reader.read_u32()?;
+
+        // The objects are within the 160 pixels of a scanline.
+        let mut positions = players_position.iter().chain(missiles_position.iter()).chain(Some(&ball_position));
+        if positions.any(|position| *position >= 160) {
+            return Err(StateError::new("object position is out of range"));
+        }
+
+        // The beam is within a frame of the TV standard with the most
+        // scanlines, since the standard isn't part of the state.
+        let scanline = reader.read_u32()?;
+        let scanline_cycle = reader.read_u32()?;
+        if scanline >= TvStandard::Pal.scanlines() || scanline_cycle >= HORIZONTAL_CYCLES {
+            return Err(StateError::new("beam position is out of range"));
+        }
+        let is_vsync = reader.read_bool()?;
+        let cpu_halt = reader.read_bool()?;
+        let data_bus = reader.read_u8()?;
+
+        let mut input_charge_times = [None; 4];
+        for time in input_charge_times.iter_mut() {
+            let is_some = reader.read_bool()?;
+            let cycles = reader.read_u32()?;
+            *time = if is_some { Some(cycles) } else { None };
+        }
+        let mut input_charges = [0; 4];
+        for charge in input_charges.iter_mut() {
+            *charge = reader.read_u32()?;
+        }
+        let is_dumped = reader.read_bool()?;
+
+        let mut framebuffers = [Box::new([[(0, 0, 0); 160]; 192]), Box::new([[(0, 0, 0); 160]; 192])];
+        for framebuffer in framebuffers.iter_mut() {
+            for pixel in framebuffer.iter_mut().flatten() {
+                let bytes = reader.read_bytes(3)?;
+                *pixel = (bytes[0], bytes[1], bytes[2]);
+            }
+        }
+        let [framebuffer, pending_framebuffer] = framebuffers;
+
+        let (seconds, nanoseconds) = (reader.read_u64()?, reader.read_u32()?);
+        if nanoseconds >= 1_000_000_000 {
+            return Err(StateError::new("elapsed time is invalid"));
+        }
+        let elapsed_time = Duration::new(seconds, nanoseconds);
+        let remaining_cycles = reader.read_u64()? as i64 as isize;
+        let timer_block = reader.read_bool()?;
+
+        let controllers = read_controllers(reader)?;
+
+        // Version 1.1 added the audio channels and the random number
+        // generator; older states restart them from their initial state.
+        let mut audio_channels = [AudioChannel::default(); 2];
+        let mut rng_state = Rng::default().state();
+        if version.minor >= 1 {
+            for channel in audio_channels.iter_mut() {
+                *channel = AudioChannel::read_state(reader)?;
+            }
+            rng_state = reader.read_u64()?;
+        }
+
+        // Version 1.2 added the jammed CPU; the CPU of older states isn't.
+        let mut jam_address = None;
+        if version.minor >= 2 {
+            let is_jammed = reader.read_bool()?;
+            let address = reader.read_u16()?;
+            jam_address = if is_jammed { Some(address) } else { None };
+        }
+
+        // Version 1.3 added the tapped switches; the switches of older states
+        // aren't tapped (they stay as they are).
+        let mut switch_taps = [0; 2];
+        if version.minor >= 3 {
+            switch_taps = [reader.read_u32()?, reader.read_u32()?];
+        }
+
+        // Version 1.4 added the interrupt control of the PIA; the interrupts
+        // of older states are disabled.
+        let mut pia_control = 0;
+        if version.minor >= 4 {
+            pia_control = reader.read_u8()?;
+        }
+
+        // Version 1.5 added the thumbnail; older states don't have one.
+        let mut thumbnail = None;
+        if version.minor >= 5 && reader.read_bool()? {
+            let width = reader.read_u32()? as usize;
+            let height = reader.read_u32()? as usize;
+            let length = width.checked_mul(height).and_then(|length| length.checked_mul(4))
+                .ok_or_else(|| StateError::new("thumbnail is too large"))?;
+
+            thumbnail = Some(Thumbnail {
+                width,
+                height,
+                pixels: reader.read_bytes(length)?.to_vec()
+            });
+        }
+
+        // Version 1.6 added the horizontal motion; the objects of older
+        // states aren't being moved.
+        let mut motion = None;
+        let mut is_hmove_blank = false;
+        if version.minor >= 6 {
+            let is_moving = reader.read_bool()?;
+            let delay = reader.read_u32()?;
+            let counter = reader.read_u8()?;
+            if counter >= 16 {
+                return Err(StateError::new("motion counter is out of range"));
+            }
+
+            let mut latches = [false; 5];
+            for latch in latches.iter_mut() {
+                *latch = reader.read_bool()?;
+            }
+            let mut values = [0; 5];
+            values.copy_from_slice(reader.read_bytes(5)?);
+            motion = if is_moving { Some(Motion { delay, counter, latches, values }) } else { None };
+            is_hmove_blank = reader.read_bool()?;
+        }
+
+        // Version 1.7 added the hidden first copies of the players; the
+        // players of older states have all their copies.
+        let mut hidden_first_copies = [0; 2];
+        if version.minor >= 7 {
+            hidden_first_copies = [reader.read_u32()?, reader.read_u32()?];
+        }
+
+        // Version 1.9 added the selected bank; older states were saved when
+        // the bank switching wasn't emulated, with the first bank selected.
+        let mut bank = 0;
+        if version.minor >= 9 {
+            bank = reader.read_u32()? as usize;
+        }
+
+        // Version 1.10 added the measurement of the vertical sync; it starts
+        // over with the states of older versions, as when the console is
+        // turned on.
+        let mut sync_lines = [None; 3];
+        let mut sync_roll = 0;
+        let mut is_sync_unstable = false;
+        if version.minor >= 10 {
+            for lines in sync_lines.iter_mut() {
+                let is_some = reader.read_bool()?;
+                let value = reader.read_u32()?;
+                *lines = if is_some { Some(value) } else { None };
+            }
+            sync_roll = reader.read_u32()?;
+            is_sync_unstable = reader.read_bool()?;
+        }
+        let [lines_since_vsync, vsync_lines, pending_vsync_lines] = sync_lines;
+
+        Ok(SaveState {
+            pointer_counter,
+            accumulator,
+            x_register,
+            y_register,
+            flags,
+            stack_pointer,
+
+            tia,
+            ram,
+            pia,
+
+            timer_value,
+            timer_status,
+            timer_interval,
+            timer_elapsed_clocks,
+
+            cycles_count: counts[0],
+            color_cycles_count: counts[1],
+            instructions_count: counts[2],
+            frames_count,
+
+            old_players_graphics,
+            old_ball_enable,
+            players_position,
+            missiles_position,
+            ball_position,
+
+            scanline,
+            scanline_cycle,
+            is_vsync,
+            cpu_halt,
+            data_bus,
+
+            input_charge_times,
+            input_charges,
+            is_dumped,
+
+            framebuffer,
+            pending_framebuffer,
+
+            audio_channels,
+            rng_state,
+            jam_address,
+            switch_taps,
+            pia_control,
+            motion,
+            is_hmove_blank,
+            hidden_first_copies,
+            bank,
+            lines_since_vsync,
+            vsync_lines,
+            pending_vsync_lines,
+            sync_roll,
+            is_sync_unstable,
+
+            elapsed_time,
+            remaining_cycles,
+            timer_block,
+
+            controllers,
+
+            thumbnail
+        })
+    }
+}
+
+pub(crate) fn write_controllers(writer: &mut StateWriter, controllers: &[Option<StateWriter>; 2]) {
+    for controller in controllers.iter() {
+        writer.write_bool(controller.is_some());
+        if let Some(state) = controller {
+            writer.write_state(state);
+        }
+    }
+}
+
+pub(crate) fn read_controllers(reader: &mut StateReader) -> Result<[Option<StateWriter>; 2], StateError> {
+    let mut controllers = [None, None];
+    for controller in controllers.iter_mut() {
+        if reader.read_bool()? {
+            *controller = Some(reader.read_state()?);
+        }
+    }
+
+    Ok(controllers)
+}
+
+/// The differences between two save states.
+///
+/// This structure is returned by `SaveState::diff()`; it lists the bytes of
+/// the RAM and the registers of the TIA that differ, and tells which other
+/// parts of the console differ.
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct StateDiff {
+    /// The registers of the CPU (including the program counter) and the data
+    /// bus.
+    pub cpu: bool,
+    /// The addresses of the bytes of the RAM (0x80 to 0xFF).
+    pub ram: Vec<u16>,
+    /// The addresses of the registers of the TIA (0x00 to 0x3D).
+    pub tia: Vec<u16>,
+    /// The internal state of the TIA (the positions of the objects, the
+    /// latches, the audio channels, the input ports, etc.)
+    pub tia_internals: bool,
+    /// The ports and the timer of the PIA.
+    pub pia: bool,
+    /// The position of the beam and the measurement of the vertical sync.
+    pub beam: bool,
+    /// The frame being drawn and the last completed frame.
+    pub frames: bool,
+    /// The state of the controllers.
+    pub controllers: bool,
+    /// Everything else (the counters, the random number generator, the
+    /// timing, etc.)
+    pub other: bool
+}
+
+impl StateDiff {
+    /// Check whether the states are identical.
+    pub fn is_empty(&self) -> bool {
+        *self == StateDiff::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::{Console, Port};
+    use crate::joystick::{Joystick, Direction};
+    use crate::location::{SWCHA, INPT4};
+    use crate::fixtures;
+
+    #[test]
+    fn test_save_state() {
+        let mut console = Console::new(Cartridge::new(fixtures::counter_rom(0x_80)));
+        console.plug_controller(Port::Left, Box::new(Joystick::new()));
+
+        let mut joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        joystick.press_direction(Direction::Up);
+        joystick.press_button();
+        drop(joystick);
+
+        console.run_frame();
+        let state = console.save_state();
+        assert_eq!(state.frame(), 1);
+        assert_eq!(state.controllers()[0], Some(StateWriter::from_bytes(vec![1, 0, 0, 0, 1])));
+        assert_eq!(state.controllers()[1], None);
+
+        console.run_frame();
+        let value = console.memory(0x_80);
+
+        let mut joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        joystick.release_direction(Direction::Up);
+        joystick.release_button();
+        drop(joystick);
+
+        // Both the console and the joystick are restored.
+        console.load_state(&state).unwrap();
+        let joystick = console.controller_mut::<Joystick>(Port::Left).unwrap();
+        assert!(joystick.is_direction_pressed(Direction::Up));
+        assert!(joystick.is_button_pressed());
+        drop(joystick);
+        assert_eq!(console.memory(SWCHA), 0b1110_0000);
+        assert_eq!(console.memory(INPT4), 0b0000_0000);
+
+        console.run_frame();
+        assert_eq!(console.save_state().frame(), 2);
+        assert_eq!(console.memory(0x_80), value);
+    }
+
+    #[test]
+    fn test_state_diff() {
+        fn assert_thread_safe<T: Send + Sync + Clone>() {}
+        assert_thread_safe::<SaveState>();
+
+        let mut console = Console::new(Cartridge::new(fixtures::counter_rom(0x_80)));
+        console.run_frame();
+        let state = console.save_state();
+
+        // A state can be sent to another thread.
+        let other_state = std::thread::spawn({
+            let state = state.clone();
+            move || state
+        }).join().unwrap();
+        assert!(state.diff(&other_state).is_empty());
+
+        // Only the RAM differs when a byte is changed.
+        *console.memory_mut(0x_81) = 0x_42;
+        let diff = state.diff(&console.save_state());
+        assert_eq!(diff, StateDiff { ram: vec![0x_81], ..StateDiff::default() });
+
+        // The counters differ after another frame; the frames are the same
+        // as nothing is drawn.
+        console.run_frame();
+        let diff = state.diff(&console.save_state());
+        assert_eq!(diff.ram, vec![0x_80, 0x_81]);
+        assert!(diff.other);
+        assert!(!diff.frames && !diff.controllers);
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        // Paint the background in white.
+        let mut rom = vec![0x_A9, 0x_0E, 0x_85, 0x_09, 0x_4C, 0x_04, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.run_frame();
+        console.run_frame();
+
+        let state = console.save_state();
+        assert!(state.thumbnail().is_none());
+
+        // The frame is downscaled, with black borders above and below.
+        let state = state.with_thumbnail(80, 64);
+        let thumbnail = state.thumbnail().unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (80, 64));
+        assert_eq!(thumbnail.pixels.len(), 80 * 64 * 4);
+        assert_eq!(thumbnail.pixels[..4], [0, 0, 0, 0x_FF]);
+        let (red, green, blue) = console.frame().pixel(0, 0);
+        assert_eq!(thumbnail.pixels[32 * 80 * 4..32 * 80 * 4 + 4], [red, green, blue, 0x_FF]);
+
+        // It's saved along with the state.
+        let bytes = state.to_bytes();
+        let restored_state = SaveState::from_bytes(&bytes).unwrap();
+        assert_eq!(restored_state.thumbnail(), Some(thumbnail));
+        assert!(SaveState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_serialized_state() {
+        let mut console = Console::new(Cartridge::new(fixtures::counter_rom(0x_80)));
+        console.plug_controller(Port::Left, Box::new(Joystick::new()));
+        console.set_seed(42);
+        console.run_frame();
+
+        let state = console.save_state();
+        let bytes = state.to_bytes();
+        assert_eq!(&bytes[..8], &[b'A', b'2', b'6', b'S', 1, 0, 10, 0]);
+
+        // The state is restored entirely.
+        let other_state = SaveState::from_bytes(&bytes).unwrap();
+        assert_eq!(other_state.to_bytes(), bytes);
+
+        console.run_frame();
+        let value = console.memory(0x_80);
+        console.load_state(&other_state).unwrap();
+        console.run_frame();
+        assert_eq!(console.memory(0x_80), value);
+
+        // A state of version 1.9 doesn't have the measurement of the vertical
+        // sync; it starts over.
+        let mut old_bytes = bytes.clone();
+        old_bytes[6] = 9;
+        old_bytes.truncate(old_bytes.len() - 3 * 5 - 4 - 1);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!((old_state.lines_since_vsync, old_state.vsync_lines, old_state.pending_vsync_lines), (None, None, None));
+        assert_eq!((old_state.sync_roll, old_state.is_sync_unstable), (0, false));
+        assert_eq!(old_state.bank, state.bank);
+
+        // A state of version 1.8 doesn't have the selected bank.
+        let mut old_bytes = bytes.clone();
+        old_bytes[6] = 8;
+        old_bytes.truncate(old_bytes.len() - 3 * 5 - 4 - 1 - 4);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!(old_state.bank, 0);
+        assert_eq!(old_state.pending_vsync_lines, None);
+
+        // The states before version 1.8 have what was written to the unmapped
+        // locations after the I/O ports; it's skipped.
+        let versioned_bytes = |minor: u8| {
+            let mut old_bytes = bytes.clone();
+            old_bytes[6] = minor;
+            old_bytes.truncate(old_bytes.len() - 3 * 5 - 4 - 1 - 4);
+            old_bytes.splice(8 + 13 + 194..8 + 13 + 194, [0xFF; 8192]);
+            old_bytes
+        };
+
+        let mut new_state = SaveState::from_bytes(&bytes).unwrap();
+        (new_state.lines_since_vsync, new_state.vsync_lines, new_state.pending_vsync_lines) = (None, None, None);
+        (new_state.sync_roll, new_state.is_sync_unstable) = (0, false);
+
+        let old_state = SaveState::from_bytes(&versioned_bytes(7)).unwrap();
+        assert_eq!(old_state.to_bytes(), new_state.to_bytes());
+
+        // A state of version 1.0 doesn't have the audio channels and the
+        // random number generator; they're migrated to their initial state.
+        let mut old_bytes = versioned_bytes(0);
+        old_bytes.truncate(old_bytes.len() - 2 * 7 - 8 - 3 - 8 - 1 - 1 - 17 - 8);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!(old_state.audio_channels, [AudioChannel::default(); 2]);
+        assert_eq!(old_state.rng_state, Rng::default().state());
+        assert_eq!(old_state.ram, state.ram);
+        assert_eq!(old_state.controllers, state.controllers);
+
+        // A state of version 1.1 doesn't have the jammed CPU.
+        let mut old_bytes = versioned_bytes(1);
+        old_bytes.truncate(old_bytes.len() - 3 - 8 - 1 - 1 - 17 - 8);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!(old_state.jam_address, None);
+        assert_eq!(old_state.rng_state, state.rng_state);
+
+        // A state of version 1.5 doesn't have the horizontal motion.
+        let mut old_bytes = versioned_bytes(5);
+        old_bytes.truncate(old_bytes.len() - 17 - 8);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!((old_state.motion, old_state.is_hmove_blank), (None, false));
+        assert_eq!(old_state.pia_control, state.pia_control);
+
+        // A state of version 1.6 doesn't have the hidden first copies.
+        let mut old_bytes = versioned_bytes(6);
+        old_bytes.truncate(old_bytes.len() - 8);
+
+        let old_state = SaveState::from_bytes(&old_bytes).unwrap();
+        assert_eq!(old_state.hidden_first_copies, [0; 2]);
+        assert_eq!(old_state.motion, state.motion);
+
+        // States of newer or other major versions aren't loadable, nor are
+        // corrupted states.
+        let mut new_bytes = bytes.clone();
+        new_bytes[6] = 11;
+        assert_eq!(SaveState::from_bytes(&new_bytes).err().unwrap().message, "state version 1.11 isn't supported");
+
+        let mut other_bytes = bytes.clone();
+        other_bytes[4] = 2;
+        assert!(SaveState::from_bytes(&other_bytes).is_err());
+
+        assert!(SaveState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(SaveState::from_bytes(&bytes[4..]).is_err());
+
+        // Neither are states with values out of their range.
+        let mut invalid_state = state.clone();
+        invalid_state.elapsed_time = Duration::new(0x_0123_4567_89AB_CDEF, 999_999_999);
+        let mut invalid_bytes = invalid_state.to_bytes();
+        let position = invalid_bytes.windows(8).position(|bytes| bytes == 0x_0123_4567_89AB_CDEF_u64.to_le_bytes()).unwrap();
+        invalid_bytes[position + 8..position + 12].copy_from_slice(&1_000_000_000_u32.to_le_bytes());
+        assert_eq!(SaveState::from_bytes(&invalid_bytes).err().unwrap().message, "elapsed time is invalid");
+
+        let mut invalid_state = state.clone();
+        invalid_state.scanline = 312;
+        assert_eq!(SaveState::from_bytes(&invalid_state.to_bytes()).err().unwrap().message, "beam position is out of range");
+
+        let mut invalid_state = state.clone();
+        invalid_state.scanline_cycle = 228;
+        assert!(SaveState::from_bytes(&invalid_state.to_bytes()).is_err());
+
+        let mut invalid_state = state.clone();
+        invalid_state.missiles_position[1] = 160;
+        assert_eq!(SaveState::from_bytes(&invalid_state.to_bytes()).err().unwrap().message, "object position is out of range");
+
+        let mut invalid_state = state.clone();
+        invalid_state.timer_interval = 0;
+        assert_eq!(SaveState::from_bytes(&invalid_state.to_bytes()).err().unwrap().message, "timer interval is invalid");
+
+        let mut invalid_state = state.clone();
+        invalid_state.timer_interval = 16;
+        assert!(SaveState::from_bytes(&invalid_state.to_bytes()).is_err());
+
+        let mut invalid_state = state.clone();
+        invalid_state.timer_elapsed_clocks = 0;
+        assert_eq!(SaveState::from_bytes(&invalid_state.to_bytes()).err().unwrap().message, "timer elapsed clocks are out of range");
+
+        let mut invalid_state = state.clone();
+        invalid_state.timer_interval = 8;
+        invalid_state.timer_elapsed_clocks = 9;
+        assert!(SaveState::from_bytes(&invalid_state.to_bytes()).is_err());
+
+        let mut invalid_state = state.clone();
+        invalid_state.motion = Some(Motion { delay: 0, counter: 16, latches: [false; 5], values: [0; 5] });
+        assert_eq!(SaveState::from_bytes(&invalid_state.to_bytes()).err().unwrap().message, "motion counter is out of range");
+    }
+
+    #[test]
+    fn test_state_writer() {
+        let mut nested = StateWriter::new();
+        nested.write_u8(42);
+
+        let mut state = StateWriter::new();
+        state.write_bool(true);
+        state.write_u32(0x_DEAD_BEEF);
+        state.write_i32(-3);
+        state.write_state(&nested);
+
+        let state = StateWriter::from_bytes(state.as_bytes().to_vec());
+        let mut reader = state.reader();
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_u32().unwrap(), 0x_DEAD_BEEF);
+        assert_eq!(reader.read_i32().unwrap(), -3);
+        assert_eq!(reader.read_state().unwrap(), nested);
+
+        assert!(reader.read_u8().is_err());
+    }
+}
@@ -6,27 +6,37 @@
 //
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, November 2020
 
-use crate::Console;
+use crate::Port;
 use crate::Controller;
+use crate::state::{StateWriter, StateError};
 
 /// Brief description.
 ///
 /// Long description.
 ///
 pub struct Steering {
-    console: Option<*mut Console>
+    port: Option<Port>
 }
 
 impl Steering {
 }
 
 impl Controller for Steering {
-    fn plugged(&mut self, console: *mut Console) {
-        self.console = Some(console);
+    fn plugged(&mut self, port: Port) {
+        self.port = Some(port);
     }
 
     fn unplugged(&mut self) {
-        self.console = None;
+        self.port = None;
+    }
+
+    fn save_state(&self) -> StateWriter {
+        // Not implemented yet, thus no internal state.
+        StateWriter::new()
+    }
+
+    fn load_state(&mut self, _state: &StateWriter) -> Result<(), StateError> {
+        Ok(())
     }
 }
 
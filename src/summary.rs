@@ -0,0 +1,274 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::watch::RamChange;
+use crate::cartridge::MapperFault;
+
+/// A change of the number of scanlines per frame.
+///
+/// Games must produce the same number of scanlines every frame (262 with
+/// NTSC), otherwise TVs struggle to lock on the vertical sync and the picture
+/// jumps or rolls; the mistake goes unnoticed on most emulators, which is why
+/// the console reports it. Warnings are rate-limited to one per second (60
+/// frames), and the changes in between are only counted. Frontends usually
+/// forward them to their logger.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # let summary = console.run_frame();
+/// for warning in &summary.scanline_warnings {
+///     eprintln!("{}", warning);
+/// }
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScanlineWarning {
+    /// Number of the frame whose scanline count changed.
+    pub frame: u64,
+    /// Number of scanlines of the frame (between the last two vertical
+    /// syncs).
+    pub scanlines: u32,
+    /// Number of scanlines of the previous frame.
+    pub previous_scanlines: u32,
+    /// Number of changes that weren't reported since the previous warning.
+    pub suppressed: u32
+}
+
+impl fmt::Display for ScanlineWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "frame {} has {} scanlines instead of {}",
+            self.frame, self.scanlines, self.previous_scanlines
+        )?;
+
+        if self.suppressed > 0 {
+            write!(f, " ({} more changes since the last warning)", self.suppressed)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A write to a location of the TIA without register.
+///
+/// The TIA has no write register from 0x_2D to 0x_3F; what's written there
+/// is lost, which is either a mistake of the game or a register the emulator
+/// doesn't know about. The writes are reported according to the policy of
+/// the console (see `UnknownRegisterPolicy`).
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UnknownRegisterWrite {
+    /// Address of the location (from 0x_2D to 0x_3F, mirrors excluded).
+    pub address: u16,
+    /// Value that was written.
+    pub value: u8,
+    /// Address of the instruction that wrote it.
+    pub program_counter: u16
+}
+
+impl fmt::Display for UnknownRegisterWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "${:02X} written to the unknown register ${:02X} by the instruction at ${:04X}",
+            self.value, self.address, self.program_counter
+        )
+    }
+}
+
+/// A summary of what happened during an update of the console.
+///
+/// This structure is returned by the functions advancing the simulation so the
+/// frontend can drive its user interface (or state machine) without polling
+/// the console separately.
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct UpdateSummary {
+    /// Number of instructions executed.
+    pub instructions: u64,
+    /// Number of CPU cycles executed.
+    pub cycles: u64,
+    /// Number of frames completed.
+    pub frames: u64,
+    /// Unknown opcodes that were encountered, with their address.
+    pub unknown_opcodes: Vec<(u16, u8)>,
+    /// Changes of the watched RAM locations, frame by frame.
+    pub ram_changes: Vec<RamChange>,
+    /// Address of the breakpoint that was hit, if any. The update stops right
+    /// before executing the instruction at that address.
+    pub breakpoint: Option<u16>,
+    /// Address of the KIL opcode that jammed the CPU, if it's jammed. A
+    /// jammed CPU doesn't execute any instruction anymore but the rest of the
+    /// console keeps running.
+    pub jam: Option<u16>,
+    /// Changes of the number of scanlines per frame (rate-limited).
+    pub scanline_warnings: Vec<ScanlineWarning>,
+    /// Writes to the locations of the TIA without register (see
+    /// `UnknownRegisterPolicy`).
+    pub unknown_register_writes: Vec<UnknownRegisterWrite>,
+    /// Accesses to the cartridge its mapper couldn't serve (see
+    /// `MapperFault`), once per address.
+    pub mapper_faults: Vec<MapperFault>
+}
+
+impl UpdateSummary {
+    /// Check whether a frame was completed.
+    pub fn has_new_frame(&self) -> bool {
+        self.frames > 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cartridge::Cartridge;
+    use crate::console::{Console, UnknownRegisterPolicy};
+    use crate::assembler::assemble;
+
+    #[test]
+    fn test_update_summary() {
+        // NOP, an unknown opcode, NOP and JMP $F000.
+        let mut rom = vec![0x_EA, 0x_03, 0x_EA, 0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        let summary = console.run_frame();
+        assert!(summary.has_new_frame());
+        assert_eq!(summary.frames, 1);
+        assert!(summary.instructions > 0);
+        assert!(summary.cycles > summary.instructions);
+        assert_eq!(summary.unknown_opcodes.first(), Some(&(0x_F001, 0x_03)));
+        assert_eq!(summary.jam, None);
+        assert_eq!(summary.breakpoint, None);
+
+        // The simulation stops before the instruction at the breakpoint and
+        // resumes past it.
+        console.add_breakpoint(0x_F002);
+
+        let summary = console.run_frame();
+        assert!(!summary.has_new_frame());
+        assert_eq!(summary.breakpoint, Some(0x_F002));
+
+        let summary = console.run_frame();
+        assert_eq!(summary.breakpoint, Some(0x_F002));
+        assert_eq!(summary.instructions, 4);
+
+        console.remove_breakpoint(0x_F002);
+        let summary = console.run_frame();
+        assert_eq!(summary.frames, 1);
+        assert_eq!(summary.breakpoint, None);
+    }
+
+    #[test]
+    fn test_scanline_warnings() {
+        // A vertical sync every 262 or 263 scanlines in turn.
+        let mut rom = assemble("
+            start:
+                lda #2
+                sta $00  ; VSYNC
+                sta $02  ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #0
+            first_loop:
+                sta $02
+                dex
+                bne first_loop
+                lda $80
+                eor #1
+                sta $80
+                clc
+                adc #3
+                tax
+            second_loop:
+                sta $02
+                dex
+                bne second_loop
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+
+        let mut warnings = Vec::new();
+        for _ in 0..130 {
+            warnings.extend(console.run_frame().scanline_warnings);
+        }
+
+        // The scanline count changes every frame but it's reported once per
+        // second.
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[1].frame - warnings[0].frame, 60);
+        assert_eq!(warnings[0].suppressed, 0);
+        assert_eq!(warnings[1].suppressed, 59);
+
+        let warning = warnings[1];
+        assert_eq!(warning.scanlines + warning.previous_scanlines, 262 + 263);
+        assert_eq!(
+            warning.to_string(),
+            format!(
+                "frame {} has {} scanlines instead of {} (59 more changes since the last warning)",
+                warning.frame, warning.scanlines, warning.previous_scanlines
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_register_writes() {
+        // Writes to $2D (and its mirror $6D) and $3F, and a known register.
+        let mut rom = assemble("
+            start:
+                lda #$42
+                sta $2D
+                sta $6D
+                sta $3F
+                sta $09
+                jmp start
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        assert_eq!(console.unknown_register_policy(), UnknownRegisterPolicy::Ignore);
+        assert!(console.run_frame().unknown_register_writes.is_empty());
+
+        // Each location is reported once.
+        console.set_unknown_register_policy(UnknownRegisterPolicy::LogOnce);
+        let writes = console.run_frame().unknown_register_writes;
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].address + writes[1].address, 0x_2D + 0x_3F);
+        assert!(console.run_frame().unknown_register_writes.is_empty());
+
+        // Each write is reported.
+        console.set_unknown_register_policy(UnknownRegisterPolicy::LogAlways);
+        let summary = console.run_frame();
+        assert!(summary.unknown_register_writes.len() > 100);
+
+        // The simulation stops right after the instruction that wrote.
+        console.set_unknown_register_policy(UnknownRegisterPolicy::Break);
+        let summary = console.run_frame();
+        assert!(!summary.has_new_frame());
+        assert_eq!(summary.unknown_register_writes.len(), 1);
+
+        let write = summary.unknown_register_writes[0];
+        assert_eq!(console.program_counter(), write.program_counter + 2);
+        assert_eq!(write.value, 0x_42);
+        assert_eq!(
+            write.to_string(),
+            format!("$42 written to the unknown register ${:02X} by the instruction at ${:04X}", write.address, write.program_counter)
+        );
+
+        let summary = console.run_frame();
+        assert_eq!(summary.unknown_register_writes.len(), 1);
+        assert_eq!(summary.instructions, 1);
+    }
+}
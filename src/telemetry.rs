@@ -0,0 +1,227 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::frame::Frame;
+use crate::overlay::{draw_text, fill_rectangle, text_width, LINE_HEIGHT};
+
+// The rates are measured over intervals of (at least) one second of real time.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Performance statistics of the simulation.
+///
+/// This structure is a read-only snapshot of the counters of the console (the
+/// totals since the beginning of the simulation and the position of the beam)
+/// along with the rates at which they progressed during the last second of
+/// real time. Frontends can display them, and tests can assert on the timing.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Telemetry {
+    /// Number of CPU cycles executed.
+    pub cycles: u64,
+    /// Number of color cycles executed (3 per CPU cycle).
+    pub color_cycles: u64,
+    /// Number of instructions executed.
+    pub instructions: u64,
+    /// Number of frames completed.
+    pub frames: u64,
+    /// Current scanline of the beam.
+    pub scanline: u32,
+    /// Current color cycle of the beam within the scanline.
+    pub scanline_cycle: u32,
+    /// Simulated time (the time it took the real console to execute the
+    /// cycles).
+    pub emulated_time: Duration,
+    /// Simulated time as a timecode.
+    pub timecode: Timecode,
+    /// CPU cycles executed per second of real time.
+    pub cycles_per_second: f64,
+    /// Instructions executed per second of real time.
+    pub instructions_per_second: f64,
+    /// Frames completed per second of real time.
+    pub frames_per_second: f64
+}
+
+/// A timestamp in emulated time.
+///
+/// This structure locates a moment of the simulation since the console was
+/// turned on, in hours, minutes, seconds and frames within the second, as
+/// the time it took the real console to get there; it's the same from a run
+/// to another regardless of the speed of the emulation, so recordings,
+/// replays and bug reports can refer to it. It's displayed as
+/// `hh:mm:ss.ff`.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let timecode = console.timecode();
+/// println!("the glitch shows at {}", timecode);
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u8,
+    pub seconds: u8,
+    /// Number of the frame within the second (from 0 to 59 with NTSC).
+    pub frames: u8
+}
+
+impl Timecode {
+    /// Create a timecode.
+    ///
+    /// This function converts a number of seconds since the console was
+    /// turned on into a timecode, with the frames counted at the given frame
+    /// rate.
+    ///
+    pub fn new(seconds: f64, frame_rate: f64) -> Timecode {
+        let whole_seconds = seconds.max(0.0).floor();
+        let total_seconds = whole_seconds as u64;
+        let frames = ((seconds - whole_seconds) * frame_rate).floor() as u8;
+
+        Timecode {
+            hours: (total_seconds / 3600) as u32,
+            minutes: (total_seconds / 60 % 60) as u8,
+            seconds: (total_seconds % 60) as u8,
+            frames
+        }
+    }
+
+    /// Draw the timecode over a frame.
+    ///
+    /// This function draws the timecode in the bottom-right corner of the
+    /// frame, over a black background.
+    ///
+    pub fn render(&self, frame: &mut Frame) {
+        let text = self.to_string();
+        let (x, y) = (Frame::WIDTH - text_width(&text) - 1, Frame::HEIGHT - LINE_HEIGHT - 1);
+
+        fill_rectangle(frame, x - 1, y - 1, text_width(&text) + 1, LINE_HEIGHT + 1, (0, 0, 0));
+        draw_text(frame, x, y, &text, (255, 255, 255));
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}.{:02}", self.hours, self.minutes, self.seconds, self.frames)
+    }
+}
+
+// Measures the rates at which the counters progress in real time.
+pub(crate) struct RateMeter {
+    sample_time: Option<Instant>,
+    sample: (u128, u128, u64),
+    pub(crate) rates: (f64, f64, f64)
+}
+
+impl RateMeter {
+    pub(crate) fn new() -> RateMeter {
+        RateMeter {
+            sample_time: None,
+            sample: (0, 0, 0),
+            rates: (0.0, 0.0, 0.0)
+        }
+    }
+
+    // Take a sample of the counters (cycles, instructions and frames) and
+    // update the rates if the interval is elapsed.
+    pub(crate) fn sample(&mut self, now: Instant, counters: (u128, u128, u64)) {
+        let sample_time = match self.sample_time {
+            Some(sample_time) => sample_time,
+            None => {
+                self.sample_time = Some(now);
+                self.sample = counters;
+                return
+            }
+        };
+
+        let elapsed_time = now.saturating_duration_since(sample_time);
+        if elapsed_time < RATE_INTERVAL {
+            return
+        }
+
+        // The counters go back when a state is restored.
+        let seconds = elapsed_time.as_secs_f64();
+        self.rates = (
+            counters.0.saturating_sub(self.sample.0) as f64 / seconds,
+            counters.1.saturating_sub(self.sample.1) as f64 / seconds,
+            counters.2.saturating_sub(self.sample.2) as f64 / seconds
+        );
+
+        self.sample_time = Some(now);
+        self.sample = counters;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_telemetry() {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.run_frame();
+
+        // A frame is 262 scanlines of 76 CPU cycles; the JMP instruction takes
+        // 3 cycles and the last one may overlap the next frame.
+        let telemetry = console.telemetry();
+        assert_eq!(telemetry.frames, 1);
+        assert!(telemetry.cycles >= 262 * 76 && telemetry.cycles < 262 * 76 + 3);
+        assert_eq!(telemetry.color_cycles, telemetry.cycles * 3);
+        assert_eq!(telemetry.instructions, telemetry.cycles.div_ceil(3));
+        assert!(telemetry.emulated_time > Duration::from_millis(16));
+        assert!(telemetry.emulated_time < Duration::from_millis(17));
+        assert_eq!(telemetry.timecode, Timecode { hours: 0, minutes: 0, seconds: 0, frames: 1 });
+        assert_eq!(console.timecode().to_string(), "00:00:00.01");
+    }
+
+    #[test]
+    fn test_timecode() {
+        let timecode = Timecode::new(3725.5, 60.0);
+        assert_eq!(timecode, Timecode { hours: 1, minutes: 2, seconds: 5, frames: 30 });
+        assert_eq!(timecode.to_string(), "01:02:05.30");
+        assert!(Timecode::new(3725.0, 60.0) < timecode);
+
+        // The frames are counted at the actual frame rate.
+        assert_eq!(Timecode::new(60.0 + 0.999, 59.92).frames, 59);
+        assert_eq!(Timecode::new(3601.0 / 59.92, 59.92).to_string(), "00:01:00.05");
+
+        let mut frame = Frame {
+            number: 0,
+            pixels: [[(0, 0, 0); 160]; 192],
+            orientation: Default::default()
+        };
+        timecode.render(&mut frame);
+        assert!(frame.pixels[Frame::HEIGHT - 7..].iter().any(|line| line.contains(&(255, 255, 255))));
+    }
+
+    #[test]
+    fn test_rate_meter() {
+        let mut meter = RateMeter::new();
+        let now = Instant::now();
+
+        // The first sample only starts the measurement.
+        meter.sample(now, (100, 50, 1));
+        assert_eq!(meter.rates, (0.0, 0.0, 0.0));
+
+        // The rates are not updated before the interval is elapsed.
+        meter.sample(now + Duration::from_millis(500), (200, 100, 2));
+        assert_eq!(meter.rates, (0.0, 0.0, 0.0));
+
+        meter.sample(now + Duration::from_secs(2), (2100, 1050, 121));
+        assert_eq!(meter.rates, (1000.0, 500.0, 60.0));
+    }
+}
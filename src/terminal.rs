@@ -0,0 +1,390 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::console::Port;
+use crate::emulator::Emulator;
+use crate::frame::{Frame, spans};
+use crate::joystick::{Joystick, Direction};
+use crate::menu::{MenuInput, MenuAction};
+
+// Terminals only report the keys as they're typed (and repeated), not when
+// they're released; a key is held for a moment after it was last seen.
+const KEY_HOLD: Duration = Duration::from_millis(250);
+
+// How long the reset and select switches are held (in frames).
+const SWITCH_TAP_FRAMES: u32 = 5;
+
+// How long the loop waits between two ticks of the emulator.
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The keys of the terminal frontend.
+///
+/// The arrows (or WASD) move the joystick, the space bar (or Z) is the fire
+/// button, R and T are the reset and select switches, escape (or P) pauses
+/// the emulator, enter activates the items of the pause menu, and Q (or
+/// Ctrl-C) quits.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TerminalKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+    Enter,
+    Reset,
+    Select,
+    Pause,
+    Quit
+}
+
+/// Decode the keys typed in a terminal.
+///
+/// This function decodes the bytes read from a terminal in raw mode (the
+/// arrows are escape sequences) and returns the keys; the other keys are
+/// ignored. An escape that isn't followed by a sequence is the escape key.
+///
+pub fn parse_keys(bytes: &[u8]) -> Vec<TerminalKey> {
+    let mut keys = Vec::new();
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let key = match bytes[index] {
+            0x_1B if bytes.get(index + 1) == Some(&b'[') || bytes.get(index + 1) == Some(&b'O') => {
+                let key = match bytes.get(index + 2) {
+                    Some(b'A') => Some(TerminalKey::Up),
+                    Some(b'B') => Some(TerminalKey::Down),
+                    Some(b'C') => Some(TerminalKey::Right),
+                    Some(b'D') => Some(TerminalKey::Left),
+                    _ => None
+                };
+                index += 2;
+                key
+            },
+            0x_1B => Some(TerminalKey::Pause),
+            b'w' | b'W' => Some(TerminalKey::Up),
+            b's' | b'S' => Some(TerminalKey::Down),
+            b'a' | b'A' => Some(TerminalKey::Left),
+            b'd' | b'D' => Some(TerminalKey::Right),
+            b' ' | b'z' | b'Z' => Some(TerminalKey::Fire),
+            b'\r' | b'\n' => Some(TerminalKey::Enter),
+            b'r' | b'R' => Some(TerminalKey::Reset),
+            b't' | b'T' => Some(TerminalKey::Select),
+            b'p' | b'P' => Some(TerminalKey::Pause),
+            b'q' | b'Q' | 0x_03 => Some(TerminalKey::Quit),
+            _ => None
+        };
+
+        keys.extend(key);
+        index += 1;
+    }
+
+    keys
+}
+
+/// Render a frame for a terminal.
+///
+/// This function returns the frame drawn with half-block characters and 24-bit
+/// ANSI colors, the given number of columns wide; each character is two
+/// pixels of the output, one above the other, averaged from the pixels of the
+/// frame they cover. Like `Frame::to_text()`, there are about 3 lines for 10
+/// columns. The lines end with a carriage return and a line feed, as
+/// terminals in raw mode expect.
+///
+pub fn render_frame(frame: &Frame, columns: usize) -> String {
+    let columns = columns.max(1);
+    let lines = (columns * 3 / 10).max(1);
+
+    let column_spans = spans(Frame::WIDTH, columns);
+    let line_spans = spans(Frame::HEIGHT, lines * 2);
+
+    let average = |(left, right): (usize, usize), (top, bottom): (usize, usize)| {
+        let mut sums = [0_u32; 3];
+        for line in &frame.pixels[top..bottom] {
+            for (red, green, blue) in &line[left..right] {
+                sums[0] += *red as u32;
+                sums[1] += *green as u32;
+                sums[2] += *blue as u32;
+            }
+        }
+
+        let area = ((right - left) * (bottom - top)) as u32;
+        (sums[0] / area, sums[1] / area, sums[2] / area)
+    };
+
+    let mut text = String::new();
+    for pair in line_spans.chunks(2) {
+        // The colors are only written when they change.
+        let mut colors = None;
+        for column in column_spans.iter() {
+            let top = average(*column, pair[0]);
+            let bottom = average(*column, pair[1]);
+
+            if colors != Some((top, bottom)) {
+                text.push_str(&format!(
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    top.0, top.1, top.2, bottom.0, bottom.1, bottom.2
+                ));
+                colors = Some((top, bottom));
+            }
+            text.push('\u{2580}');
+        }
+        text.push_str("\x1b[0m\r\n");
+    }
+
+    text
+}
+
+/// A terminal used as the output and the input of the emulator.
+///
+/// Opening the terminal switches it to raw mode (the keys are read as they're
+/// typed, without being echoed) with `stty`, and to the alternate screen with
+/// the cursor hidden; it's restored when the terminal is dropped. The keys are
+/// read in the background.
+///
+/// It's what `run_in_terminal()` uses; it's exposed for frontends that want
+/// their own loop.
+///
+pub struct Terminal {
+    saved_mode: Option<String>,
+    keys: Receiver<Vec<u8>>
+}
+
+impl Terminal {
+    /// Open the terminal.
+    ///
+    /// This function fails if the terminal can't be switched to raw mode
+    /// (if the standard input isn't a terminal, for instance).
+    ///
+    pub fn open() -> io::Result<Terminal> {
+        let saved_mode = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 32];
+            let mut stdin = io::stdin();
+            while let Ok(count) = stdin.read(&mut buffer) {
+                if count == 0 || sender.send(buffer[..count].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+
+        Ok(Terminal {
+            saved_mode: Some(saved_mode.trim().to_string()),
+            keys
+        })
+    }
+
+    /// Size of the terminal (in columns and lines).
+    pub fn size(&self) -> io::Result<(usize, usize)> {
+        let size = stty(&["size"])?;
+        let mut numbers = size.split_whitespace().filter_map(|number| number.parse().ok());
+
+        match (numbers.next(), numbers.next()) {
+            (Some(lines), Some(columns)) => Ok((columns, lines)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "can't read the size of the terminal"))
+        }
+    }
+
+    /// Keys typed since the last call.
+    pub fn read_keys(&mut self) -> Vec<TerminalKey> {
+        self.keys.try_iter().flat_map(|bytes| parse_keys(&bytes)).collect()
+    }
+
+    /// Draw a frame at the top of the terminal (see `render_frame()`).
+    pub fn draw(&mut self, frame: &Frame, columns: usize) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[H")?;
+        stdout.write_all(render_frame(frame, columns).as_bytes())?;
+        stdout.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+
+        if let Some(mode) = self.saved_mode.take() {
+            let _ = stty(&[&mode]);
+        }
+    }
+}
+
+fn stty(arguments: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run the emulator in the terminal.
+///
+/// This function runs the emulator until the user quits, drawing the frames
+/// in the terminal (as large as it fits, see `render_frame()`) and reading
+/// the keys (see `TerminalKey`); they move the joystick plugged in the left
+/// port, if any. While the emulator is paused, the keys navigate the pause
+/// menu. The audio is dropped. It's meant to run the emulator over SSH.
+///
+#[cfg_attr(feature = "test-rom", doc = "```no_run")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{run_in_terminal, Cartridge, ConsoleBuilder, Emulator, TEST_ROM};
+/// # let cartridge = Cartridge::new(TEST_ROM.to_vec());
+/// let mut emulator = Emulator::new(ConsoleBuilder::new(cartridge).build());
+/// run_in_terminal(&mut emulator)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+pub fn run_in_terminal(emulator: &mut Emulator) -> io::Result<()> {
+    let mut terminal = Terminal::open()?;
+    let (width, height) = terminal.size().unwrap_or((80, 24));
+    let columns = width.min(height.saturating_sub(1) * 10 / 3).max(10);
+
+    let mut held_keys: Vec<(TerminalKey, Instant)> = Vec::new();
+
+    loop {
+        let now = Instant::now();
+        let mut is_menu_changed = false;
+
+        for key in terminal.read_keys() {
+            if emulator.is_paused() {
+                let input = match key {
+                    TerminalKey::Up => MenuInput::Up,
+                    TerminalKey::Down => MenuInput::Down,
+                    TerminalKey::Left => MenuInput::Left,
+                    TerminalKey::Right => MenuInput::Right,
+                    TerminalKey::Enter | TerminalKey::Fire => MenuInput::Select,
+                    TerminalKey::Pause => MenuInput::Back,
+                    TerminalKey::Quit => return Ok(()),
+                    _ => continue
+                };
+
+                let action = emulator.handle_menu_input(input).unwrap_or(None);
+                if action == Some(MenuAction::Quit) {
+                    return Ok(());
+                }
+                is_menu_changed = true;
+                continue;
+            }
+
+            match key {
+                TerminalKey::Pause => {
+                    emulator.pause();
+                    held_keys.clear();
+                    is_menu_changed = true;
+                },
+                TerminalKey::Quit => return Ok(()),
+                TerminalKey::Reset => emulator.console_mut().tap_reset(SWITCH_TAP_FRAMES),
+                TerminalKey::Select => emulator.console_mut().tap_select(SWITCH_TAP_FRAMES),
+                _ => {
+                    // Opposite directions can't be held at the same time.
+                    let opposite = match key {
+                        TerminalKey::Up => Some(TerminalKey::Down),
+                        TerminalKey::Down => Some(TerminalKey::Up),
+                        TerminalKey::Left => Some(TerminalKey::Right),
+                        TerminalKey::Right => Some(TerminalKey::Left),
+                        _ => None
+                    };
+                    held_keys.retain(|(held_key, _)| *held_key != key && Some(*held_key) != opposite);
+                    held_keys.push((key, now));
+                }
+            }
+        }
+
+        held_keys.retain(|(_, time)| now.duration_since(*time) < KEY_HOLD);
+        if let Some(mut joystick) = emulator.console_mut().controller_mut::<Joystick>(Port::Left) {
+            let is_held = |key| held_keys.iter().any(|(held_key, _)| *held_key == key);
+
+            for (key, direction) in [
+                (TerminalKey::Up, Direction::Up),
+                (TerminalKey::Down, Direction::Down),
+                (TerminalKey::Left, Direction::Left),
+                (TerminalKey::Right, Direction::Right)
+            ].iter() {
+                match is_held(*key) {
+                    true => joystick.press_direction(*direction),
+                    false => joystick.release_direction(*direction)
+                }
+            }
+
+            match is_held(TerminalKey::Fire) {
+                true => joystick.press_button(),
+                false => joystick.release_button()
+            }
+        }
+
+        let summary = emulator.tick();
+        emulator.console_mut().take_audio_samples();
+
+        if summary.has_new_frame() || is_menu_changed {
+            terminal.draw(&emulator.frame(), columns)?;
+        }
+
+        thread::sleep(TICK_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::Orientation;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys(b"\x1b[A\x1b[Dz q"), vec![
+            TerminalKey::Up,
+            TerminalKey::Left,
+            TerminalKey::Fire,
+            TerminalKey::Fire,
+            TerminalKey::Quit
+        ]);
+        assert_eq!(parse_keys(b"\x1bOC\r"), vec![TerminalKey::Right, TerminalKey::Enter]);
+        assert_eq!(parse_keys(b"\x1b"), vec![TerminalKey::Pause]);
+        assert_eq!(parse_keys(b"\x1b[5~x"), vec![]);
+    }
+
+    #[test]
+    fn test_render_frame() {
+        let mut frame = Frame {
+            number: 1,
+            pixels: [[(0, 0, 0); 160]; 192],
+            orientation: Orientation::default()
+        };
+
+        // A red top half and a blue bottom half.
+        frame.pixels[..96].iter_mut().for_each(|line| *line = [(255, 0, 0); 160]);
+        frame.pixels[96..].iter_mut().for_each(|line| *line = [(0, 0, 255); 160]);
+
+        // 10 columns make 3 lines of 2 pixels each; the middle one is split.
+        let text = render_frame(&frame, 10);
+        let lines = text.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], format!("\x1b[38;2;255;0;0;48;2;255;0;0m{}\x1b[0m", "\u{2580}".repeat(10)));
+        assert_eq!(lines[1], format!("\x1b[38;2;255;0;0;48;2;0;0;255m{}\x1b[0m", "\u{2580}".repeat(10)));
+        assert_eq!(lines[3], "");
+    }
+}
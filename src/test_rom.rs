@@ -0,0 +1,132 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+// The program of the test ROM, assembled at 0xF000 (see the source in the
+// unit test below).
+const PROGRAM: [u8; 66] = [
+    0x_78, 0x_D8, 0x_A2, 0x_FF, 0x_9A, 0x_A9, 0x_02, 0x_85, 0x_01, 0x_85, 0x_00, 0x_85, 0x_02, 0x_85, 0x_02, 0x_85,
+    0x_02, 0x_A9, 0x_00, 0x_85, 0x_00, 0x_A2, 0x_25, 0x_85, 0x_02, 0x_CA, 0x_D0, 0x_FB, 0x_E6, 0x_80, 0x_A4, 0x_80,
+    0x_24, 0x_0C, 0x_30, 0x_02, 0x_A0, 0x_00, 0x_A9, 0x_00, 0x_85, 0x_01, 0x_A2, 0x_C0, 0x_84, 0x_09, 0x_C8, 0x_85,
+    0x_02, 0x_CA, 0x_D0, 0x_F8, 0x_A9, 0x_02, 0x_85, 0x_01, 0x_A2, 0x_1E, 0x_85, 0x_02, 0x_CA, 0x_D0, 0x_FB, 0x_4C,
+    0x_05, 0x_F0
+];
+
+/// A tiny test ROM.
+///
+/// This ROM of 2K was written for this crate and is in the public domain; it
+/// draws a standard NTSC frame (262 scanlines, with the vertical sync) whose
+/// background is a rainbow scrolling by one color per frame, and the rainbow
+/// stops scrolling while the button of the left joystick is pressed. It's for
+/// the examples and the tests that need a working console without a ROM file
+/// (which are copyrighted, and not on the machine running the tests); it's
+/// compiled with the `test-rom` feature, enabled by default.
+///
+/// ```
+/// use atari_2600::{Cartridge, Console, TEST_ROM};
+///
+/// let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// console.run_frame();
+/// console.run_frame();
+///
+/// assert_eq!(console.sync_lines(), Some(262));
+/// ```
+///
+pub const TEST_ROM: [u8; 2048] = test_rom();
+
+const fn test_rom() -> [u8; 2048] {
+    let mut rom = [0; 2048];
+
+    let mut index = 0;
+    while index < PROGRAM.len() {
+        rom[index] = PROGRAM[index];
+        index += 1;
+    }
+
+    // Both the reset vector and the IRQ vector point to the program.
+    rom[0x_07FC] = 0x_00;
+    rom[0x_07FD] = 0x_F0;
+    rom[0x_07FE] = 0x_00;
+    rom[0x_07FF] = 0x_F0;
+
+    rom
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::{Console, Port};
+    use crate::joystick::Joystick;
+
+    #[test]
+    fn test_test_rom() {
+        let source = "
+                sei
+                cld
+                ldx #$FF
+                txs
+            frame:
+                lda #2
+                sta $01   ; VBLANK
+                sta $00   ; VSYNC
+                sta $02   ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #37
+            vblank:
+                sta $02
+                dex
+                bne vblank
+                inc $80   ; frame counter
+                ldy $80
+                bit $0C   ; INPT4
+                bmi released
+                ldy #0
+            released:
+                lda #0
+                sta $01
+                ldx #192
+            picture:
+                sty $09   ; COLUBK
+                iny
+                sta $02
+                dex
+                bne picture
+                lda #2
+                sta $01
+                ldx #30
+            overscan:
+                sta $02
+                dex
+                bne overscan
+                jmp frame
+        ";
+        assert_eq!(assemble(source).unwrap(), PROGRAM);
+
+        let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+        console.plug_controller(Port::Left, Box::new(Joystick::new()));
+        for _ in 0..3 {
+            assert!(console.run_frame().scanline_warnings.is_empty());
+        }
+        assert_eq!(console.sync_lines(), Some(262));
+
+        // The rainbow scrolls, unless the button is pressed.
+        let frame = console.frame();
+        console.run_frame();
+        assert!(console.frame().pixels != frame.pixels);
+
+        console.controller_mut::<Joystick>(Port::Left).unwrap().press_button();
+        console.run_frame();
+        let frame = console.frame();
+        console.run_frame();
+        assert!(console.frame().pixels == frame.pixels);
+    }
+}
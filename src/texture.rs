@@ -0,0 +1,175 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::frame::Frame;
+
+/// The layout of the pixels of a texture.
+///
+/// The components are listed in the order of the bytes in memory, regardless
+/// of the endianness of the host.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    /// 4 bytes per pixel; red, green, blue and alpha.
+    Rgba8,
+    /// 4 bytes per pixel; blue, green, red and alpha.
+    Bgra8,
+    /// 4 bytes per pixel; alpha, red, green and blue.
+    Argb8,
+    /// 3 bytes per pixel; red, green and blue.
+    Rgb8,
+    /// 2 bytes per pixel, 5 bits of red, 6 bits of green and 5 bits of blue,
+    /// stored as a little-endian 16-bit word.
+    Rgb565
+}
+
+impl PixelFormat {
+    /// Number of bytes of a pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 | PixelFormat::Argb8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgb565 => 2
+        }
+    }
+
+    fn write_pixel(&self, pixel: &mut [u8], (red, green, blue): (u8, u8, u8)) {
+        match self {
+            PixelFormat::Rgba8 => pixel.copy_from_slice(&[red, green, blue, 0x_FF]),
+            PixelFormat::Bgra8 => pixel.copy_from_slice(&[blue, green, red, 0x_FF]),
+            PixelFormat::Argb8 => pixel.copy_from_slice(&[0x_FF, red, green, blue]),
+            PixelFormat::Rgb8 => pixel.copy_from_slice(&[red, green, blue]),
+            PixelFormat::Rgb565 => {
+                let word = (red as u16 >> 3) << 11 | (green as u16 >> 2) << 5 | blue as u16 >> 3;
+                pixel.copy_from_slice(&word.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// A frame converted to the pixels of a texture.
+///
+/// GUI toolkits and graphics APIs each expect the pixels of their textures in
+/// a given layout; this structure holds the pixels of a frame in the layout of
+/// the host, ready to be uploaded to a texture. The lines are `stride` bytes
+/// apart, which may be more than the width of a line when the host pads them.
+///
+#[cfg_attr(all(feature = "test-rom", feature = "frontend"), doc = "```")]
+#[cfg_attr(not(all(feature = "test-rom", feature = "frontend")), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Emulator, PixelFormat, Texture, TEST_ROM};
+/// # let emulator = Emulator::new(Console::new(Cartridge::new(TEST_ROM.to_vec())));
+/// # fn upload_texture(_width: usize, _height: usize, _stride: usize, _pixels: &[u8]) {}
+/// let texture = Texture::from_frame(&emulator.frame(), PixelFormat::Bgra8);
+/// upload_texture(texture.width, texture.height, texture.stride, &texture.pixels);
+/// ```
+///
+/// See `Emulator::set_frame_callback()` to receive the frames as textures as
+/// soon as they're completed.
+///
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+    pub format: PixelFormat,
+    pub pixels: Vec<u8>
+}
+
+impl Texture {
+    /// Convert a frame to a texture.
+    ///
+    /// This function converts a frame to a texture of the given format, with
+    /// lines that aren't padded.
+    ///
+    pub fn from_frame(frame: &Frame, format: PixelFormat) -> Texture {
+        Texture::with_stride(frame, format, Frame::WIDTH * format.bytes_per_pixel())
+    }
+
+    /// Convert a frame to a texture with padded lines.
+    ///
+    /// This function is like `from_frame()` but the lines are the given number
+    /// of bytes apart; the padding is filled with zeros. The stride must be at
+    /// least as large as a line.
+    ///
+    pub fn with_stride(frame: &Frame, format: PixelFormat, stride: usize) -> Texture {
+        let mut pixels = vec![0; stride * Frame::HEIGHT];
+        write_frame(frame, format, &mut pixels, stride);
+
+        Texture {
+            width: Frame::WIDTH,
+            height: Frame::HEIGHT,
+            stride,
+            format,
+            pixels
+        }
+    }
+}
+
+/// Write the pixels of a frame to a buffer.
+///
+/// This function converts a frame to the given format and writes it to a
+/// buffer whose lines are `stride` bytes apart, typically the mapped memory
+/// of a texture; the padding of the lines is left untouched.
+///
+/// It panics if the stride is smaller than a line, or if the buffer is too
+/// small to hold the frame.
+///
+pub fn write_frame(frame: &Frame, format: PixelFormat, buffer: &mut [u8], stride: usize) {
+    let bytes_per_pixel = format.bytes_per_pixel();
+    assert!(stride >= Frame::WIDTH * bytes_per_pixel, "stride of {} bytes is smaller than a line", stride);
+    assert!(buffer.len() >= stride * (Frame::HEIGHT - 1) + Frame::WIDTH * bytes_per_pixel, "buffer is too small to hold a frame");
+
+    for (line, pixels) in buffer.chunks_mut(stride).zip(frame.pixels.iter()) {
+        for (pixel, color) in line.chunks_exact_mut(bytes_per_pixel).zip(pixels.iter()) {
+            format.write_pixel(pixel, *color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_texture() {
+        let mut frame = Frame { number: 1, pixels: [[(0, 0, 0); 160]; 192], orientation: Default::default() };
+        frame.pixels[0][0] = (0x_12, 0x_34, 0x_56);
+        frame.pixels[1][159] = (0x_FF, 0x_80, 0x_08);
+
+        let texture = Texture::from_frame(&frame, PixelFormat::Rgba8);
+        assert_eq!((texture.width, texture.height, texture.stride), (160, 192, 640));
+        assert_eq!(texture.pixels.len(), 640 * 192);
+        assert_eq!(&texture.pixels[0..4], &[0x_12, 0x_34, 0x_56, 0x_FF]);
+        assert_eq!(&texture.pixels[640 + 159 * 4..640 * 2], &[0x_FF, 0x_80, 0x_08, 0x_FF]);
+
+        let texture = Texture::from_frame(&frame, PixelFormat::Bgra8);
+        assert_eq!(&texture.pixels[0..4], &[0x_56, 0x_34, 0x_12, 0x_FF]);
+
+        let texture = Texture::from_frame(&frame, PixelFormat::Argb8);
+        assert_eq!(&texture.pixels[0..4], &[0x_FF, 0x_12, 0x_34, 0x_56]);
+
+        let texture = Texture::from_frame(&frame, PixelFormat::Rgb8);
+        assert_eq!(texture.stride, 480);
+        assert_eq!(&texture.pixels[480 + 159 * 3..480 * 2], &[0x_FF, 0x_80, 0x_08]);
+
+        let texture = Texture::from_frame(&frame, PixelFormat::Rgb565);
+        assert_eq!(texture.stride, 320);
+        assert_eq!(&texture.pixels[320 + 159 * 2..320 * 2], &[0x_01, 0x_FC]); // red 31, green 32, blue 1
+
+        // The padding of the lines is left empty.
+        let texture = Texture::with_stride(&frame, PixelFormat::Rgba8, 1024);
+        assert_eq!(texture.pixels.len(), 1024 * 192);
+        assert_eq!(&texture.pixels[1024 + 159 * 4..1024 + 160 * 4], &[0x_FF, 0x_80, 0x_08, 0x_FF]);
+        assert!(texture.pixels[640..1024].iter().all(|&byte| byte == 0));
+
+        // The last line doesn't need to be padded.
+        let mut buffer = vec![0x_AA; 1024 * 191 + 640];
+        write_frame(&frame, PixelFormat::Rgba8, &mut buffer, 1024);
+        assert_eq!(&buffer[0..4], &[0x_12, 0x_34, 0x_56, 0x_FF]);
+        assert!(buffer[640..1024].iter().all(|&byte| byte == 0x_AA));
+    }
+}
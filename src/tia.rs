@@ -0,0 +1,703 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::console::{Player, TvStandard, TiaRevision};
+use crate::location::*;
+use crate::video::{create_pixel, pixel_layers};
+use crate::tia_state::PixelLayers;
+use crate::palette::{ColorCache, ObjectColors, COLOR_REGISTERS};
+use crate::audio::{AudioChannel, mix_channels, AUDIO_PHASE0_CYCLES, AUDIO_PHASE1_CYCLES};
+
+pub(crate) const HORIZONTAL_CYCLES: u32 = 228;
+
+// The number of color cycles between the write to HMOVE and the first step of
+// the motion counter, and between two steps.
+const MOTION_DELAY: u32 = 6;
+const MOTION_STEP_CYCLES: u32 = 4;
+
+// The horizontal motion registers, in the order of the moving objects (the
+// players, the missiles and the ball).
+const MOTION_REGISTERS: [u16; 5] = [HMP0, HMP1, HMM0, HMM1, HMBL];
+
+/// The horizontal motion started by a write to HMOVE.
+///
+/// Writing HMOVE starts a 4-bit counter which steps every 4 color cycles, 16
+/// times. Each moving object has a latch which is set by HMOVE and cleared
+/// when the counter matches its motion value (the high nibble of its HMxx
+/// register with the top bit inverted); until then, it receives an extra
+/// clock at each step, which moves it one pixel to the left if it's not
+/// already clocked (outside the horizontal blank, the extra clocks are lost).
+///
+/// Written at the start of the scanline, HMOVE also extends the horizontal
+/// blank by 8 pixels (the black "comb" at the left edge of the screen) during
+/// which the objects aren't clocked; that's why the motion values go from 7
+/// pixels to the left to 8 pixels to the right. Written later, the extra
+/// clocks fall partially (early HMOVE) or entirely (mid-line) outside the
+/// horizontal blank, or into the horizontal blank of the next scanline (late
+/// HMOVE, without comb); the motion then depends on the cycle of the write,
+/// which several games rely on.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub(crate) struct Motion {
+    /// Color cycles until the next step of the counter.
+    pub(crate) delay: u32,
+    /// Value of the counter (from 0 to 15).
+    pub(crate) counter: u8,
+    /// Whether each object is still being moved.
+    pub(crate) latches: [bool; 5],
+    /// Motion values when HMOVE was written.
+    pub(crate) values: [u8; 5]
+}
+
+/// The TIA chip (Television Interface Adaptor).
+///
+/// The TIA holds its registers (from 0x_00 to 0x_3D), the beam and the
+/// positions of the moving objects, the frame being drawn and the last
+/// completed frame, and the audio channels. It's addressed with the canonical
+/// addresses of the location module; the decoding of the bus is left to the
+/// console.
+///
+/// The TIA must be stepped once per color cycle (three times per CPU cycle).
+///
+pub(crate) struct Tia {
+    pub(crate) registers: [u8; 62], // from 0x_00 to 0x_3D
+
+    // The old copies of the graphics of the players (GRP0 and GRP1) and of the
+    // ball (ENABL) which are displayed instead of the new copies when vertical
+    // delay is enabled; the new copies are the registers themselves.
+    pub(crate) old_players_graphics: [u8; 2],
+    pub(crate) old_ball_enable: u8,
+
+    // The horizontal position counters of the players, the missiles and the
+    // ball (in pixels from the left edge of the screen).
+    pub(crate) players_position: [u32; 2],
+    pub(crate) missiles_position: [u32; 2],
+    pub(crate) ball_position: u32,
+
+    pub(crate) scanline: u32,
+    pub(crate) scanline_cycle: u32,
+
+    pub(crate) is_vsync: bool,
+    pub(crate) cpu_halt: bool,
+
+    // The horizontal motion in progress, if any, and whether the horizontal
+    // blank of the current scanline is extended by HMOVE.
+    pub(crate) motion: Option<Motion>,
+    pub(crate) is_hmove_blank: bool,
+
+    // The objects receiving an extra clock of HMOVE on the current color
+    // cycle.
+    pub(crate) motion_clocks: [bool; 5],
+
+    // The color cycles during which the first copy of each player isn't
+    // drawn, after it was reset in the visible area.
+    pub(crate) hidden_first_copies: [u32; 2],
+
+    // The strobe registers don't hold data; the values written to them land
+    // here and are never read.
+    strobe_latch: u8,
+
+    // The number of scanlines since the end of the last vertical sync (none
+    // until the first one), and the number of scanlines between the last two
+    // ends of vertical sync during the frame being drawn and the last
+    // completed frame, if it ended during the frame.
+    pub(crate) lines_since_vsync: Option<u32>,
+    pub(crate) vsync_lines: Option<u32>,
+    pub(crate) pending_vsync_lines: Option<u32>,
+
+    // The frame being drawn and the last completed frame.
+    pub(crate) framebuffer: Box<[[(u8, u8, u8); 160]; 192]>,
+    pub(crate) pending_framebuffer: Box<[[(u8, u8, u8); 160]; 192]>,
+
+    // The layers of the frame being drawn and of the last completed frame,
+    // if they're captured.
+    pub(crate) layers: Option<Box<[[PixelLayers; 160]; 192]>>,
+    pub(crate) pending_layers: Option<Box<[[PixelLayers; 160]; 192]>>,
+
+    pub(crate) audio_channels: [AudioChannel; 2],
+
+    // The TV standard, which decides of the number of scanlines and of the
+    // colors, and the colors of the objects, resolved when the color
+    // registers change.
+    pub(crate) tv_standard: TvStandard,
+    color_cache: ColorCache,
+
+    // The revision of the chip, which decides of its quirks; it's a setting
+    // of the console, not part of its state.
+    pub(crate) revision: TiaRevision,
+
+    // The settings of the output of the audio; they aren't part of the state
+    // of the console.
+    pub(crate) audio_volume: f32,
+    pub(crate) muted_audio_channels: [bool; 2]
+}
+
+impl Tia {
+    pub(crate) fn new() -> Tia {
+        let mut tia = Tia {
+            registers: [0; 62],
+
+            old_players_graphics: [0; 2],
+            old_ball_enable: 0,
+
+            players_position: [0; 2],
+            missiles_position: [0; 2],
+            ball_position: 0,
+
+            scanline: 0,
+            scanline_cycle: 0,
+
+            is_vsync: false,
+            cpu_halt: false,
+
+            motion: None,
+            is_hmove_blank: false,
+            motion_clocks: [false; 5],
+            hidden_first_copies: [0; 2],
+
+            strobe_latch: 0,
+
+            lines_since_vsync: None,
+            vsync_lines: None,
+            pending_vsync_lines: None,
+
+            framebuffer: Box::new([[(0, 0, 0); 160]; 192]),
+            pending_framebuffer: Box::new([[(0, 0, 0); 160]; 192]),
+
+            layers: None,
+            pending_layers: None,
+
+            audio_channels: [AudioChannel::default(); 2],
+
+            tv_standard: TvStandard::Ntsc,
+            color_cache: ColorCache::default(),
+
+            revision: TiaRevision::Original,
+
+            audio_volume: 1.0,
+            muted_audio_channels: [false; 2]
+        };
+
+        // The input ports INPT4 and INPT5 are pulled up and read high until
+        // something (a button usually) grounds them.
+        tia.registers[INPT4 as usize] = 0b1000_0000;
+        tia.registers[INPT5 as usize] = 0b1000_0000;
+
+        tia
+    }
+
+    /// Colors of the objects (see the palette module).
+    pub(crate) fn object_colors(&self) -> ObjectColors {
+        self.color_cache.colors(self.tv_standard, COLOR_REGISTERS.map(|address| self.register(address)))
+    }
+
+    /// Value of a register as it's stored.
+    pub(crate) fn register(&self, address: u16) -> u8 {
+        self.registers[address as usize]
+    }
+
+    /// Bits of a read register which are driven by the TIA.
+    ///
+    /// This function returns the mask of the bits the TIA drives when a read
+    /// register is read (bit 7 and 6 of the collision latches, bit 7 of the
+    /// input ports); the other bits are left to the data bus.
+    ///
+    pub(crate) fn driven_bits(address: u16) -> u8 {
+        match address {
+            CXM0P..=CXPPMM if address != CXBLPF => 0b1100_0000,
+            _ => 0b1000_0000
+        }
+    }
+
+    /// Read a read register of the TIA.
+    ///
+    /// This function returns the bits of a read register (0x_30 to 0x_3D)
+    /// which are driven by the TIA; the other bits read as 0.
+    ///
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        self.registers[address as usize] & Tia::driven_bits(address)
+    }
+
+    /// Write a write register of the TIA.
+    ///
+    /// This function writes a register (0x_00 to 0x_2C) and triggers its
+    /// strobe, if any.
+    ///
+    pub(crate) fn write(&mut self, address: u16, value: u8) {
+        *self.register_mut(address) = value;
+    }
+
+    /// Write register of the TIA, triggering its strobe.
+    ///
+    /// This function triggers the strobe of a write register (WSYNC, RESP0,
+    /// GRP0, CXCLR, etc.) and returns the register to be written. The pure
+    /// strobes (all of them but GRP0 and GRP1) are triggered whatever the
+    /// value written is, and they don't keep it; a scratch location is
+    /// returned instead, so the register always reads 0.
+    ///
+    pub(crate) fn register_mut(&mut self, address: u16) -> &mut u8 {
+        let is_strobe = match address {
+            WSYNC  => { self.wait_for_leading_edge_of_horizontal_blank(); true },
+            RSYNC  => { self.reset_horizontal_sync_counter(); true },
+            RESP0  => { self.reset_player(0); true },
+            RESP1  => { self.reset_player(1); true },
+            RESM0  => { self.missiles_position[0] = self.reset_position(false); true },
+            RESM1  => { self.missiles_position[1] = self.reset_position(false); true },
+            RESBL  => { self.ball_position = self.reset_position(false); true },
+            GRP0   => { self.latch_old_graphics(Player::One); false },
+            GRP1   => { self.latch_old_graphics(Player::Two); false },
+            HMOVE  => { self.apply_horizontal_motion(); true },
+            HMCLR  => { self.clear_horizontal_motion_registers(); true },
+            CXCLR  => { self.clear_collision_latches(); true },
+            _ => false
+        };
+
+        match is_strobe {
+            true => &mut self.strobe_latch,
+            false => &mut self.registers[address as usize]
+        }
+    }
+
+    /// Update the vertical sync.
+    ///
+    /// This function checks for a change in the VSYNC bit, and moves the beam
+    /// to the end of the vertical sync if it was switched off; the number of
+    /// scanlines since the previous vertical sync is measured then. It must be
+    /// called once per CPU cycle.
+    ///
+    pub(crate) fn update_vertical_sync(&mut self) {
+        let vsync_bit = self.registers[VSYNC as usize] & 0b_0000_0010 > 0;
+        if self.is_vsync && !vsync_bit {
+            self.scanline = 2;
+
+            self.vsync_lines = self.lines_since_vsync;
+            self.lines_since_vsync = Some(0);
+        }
+        self.is_vsync = vsync_bit;
+    }
+
+    /// Advance the TIA by one color cycle.
+    ///
+    /// This function draws the pixel under the beam, clocks the audio
+    /// channels, and moves the beam. It returns the audio sample produced
+    /// during this cycle (two per scanline), if any, and whether the frame was
+    /// completed, in which case the frame is kept aside until the next one is
+    /// completed.
+    ///
+    pub(crate) fn step(&mut self) -> (Option<f32>, bool) {
+        self.clock_motion();
+
+        // Draw the current pixel if the beam is on a drawable area; the
+        // horizontal blank extended by HMOVE is drawn black.
+        if self.is_beam_drawing() {
+            let (line, pixel) = self.beam_position();
+            self.framebuffer[line][pixel] = match self.is_hmove_blank && pixel < 8 {
+                true => (0, 0, 0),
+                false => create_pixel(self, pixel)
+            };
+
+            if self.layers.is_some() {
+                let pixel_layers = pixel_layers(self, pixel);
+                if let Some(layers) = self.layers.as_mut() {
+                    layers[line][pixel] = pixel_layers;
+                }
+            }
+        }
+
+        for cycles in self.hidden_first_copies.iter_mut() {
+            *cycles = cycles.saturating_sub(1);
+        }
+
+        let sample = self.clock_audio();
+        let mut is_frame_completed = false;
+
+        self.scanline_cycle += 1;
+        if self.scanline_cycle >= HORIZONTAL_CYCLES {
+
+            // TODO; Trigger WSYNc perhaps releasing CPU halt.
+            self.cpu_halt = false;
+            self.is_hmove_blank = false;
+
+            self.scanline += 1;
+            if let Some(lines) = self.lines_since_vsync.as_mut() {
+                *lines += 1;
+            }
+
+            if self.scanline >= self.tv_standard.scanlines() {
+                // Keep the frame aside until the next one is completed, and
+                // clear out the framebuffer for debugging purpose.
+                self.pending_framebuffer = std::mem::replace(&mut self.framebuffer, Box::new([[(0, 0, 0); 160]; 192]));
+                if let Some(layers) = self.layers.as_mut() {
+                    self.pending_layers = Some(std::mem::replace(layers, Box::new([[PixelLayers::default(); 160]; 192])));
+                }
+                self.pending_vsync_lines = self.vsync_lines.take();
+
+                self.scanline = 0;
+                is_frame_completed = true;
+            }
+
+            self.scanline_cycle = 0;
+        }
+
+        (sample, is_frame_completed)
+    }
+
+    fn clock_audio(&mut self) -> Option<f32> {
+        let controls = [self.registers[AUDC0 as usize], self.registers[AUDC1 as usize]];
+
+        if AUDIO_PHASE0_CYCLES.contains(&self.scanline_cycle) {
+            let frequencies = [self.registers[AUDF0 as usize], self.registers[AUDF1 as usize]];
+            for (channel, (control, frequency)) in self.audio_channels.iter_mut().zip(controls.iter().zip(frequencies.iter())) {
+                channel.phase0(*control, *frequency);
+            }
+
+            None
+        }
+        else if AUDIO_PHASE1_CYCLES.contains(&self.scanline_cycle) {
+            let mut left = self.audio_channels[0].phase1(controls[0], self.registers[AUDV0 as usize]);
+            let mut right = self.audio_channels[1].phase1(controls[1], self.registers[AUDV1 as usize]);
+
+            // Muted channels keep running, only their output is discarded.
+            if self.muted_audio_channels[0] {
+                left = 0;
+            }
+            if self.muted_audio_channels[1] {
+                right = 0;
+            }
+
+            Some(mix_channels(left, right) * self.audio_volume)
+        }
+        else {
+            None
+        }
+    }
+
+    pub(crate) fn is_horizontal_blank(&self) -> bool {
+        self.scanline_cycle < 68
+    }
+
+    fn is_beam_drawing(&self) -> bool {
+
+        // todo; rename this function
+        let a = self.scanline >= 3 + 37 && self.scanline < 3 + 37 + 192;
+        let b = !self.is_horizontal_blank();
+
+        a && b
+    }
+
+    fn beam_position(&self) -> (usize, usize) { // return current normalized line and "pixel"
+
+        assert!(self.is_beam_drawing());
+
+        let line = self.scanline - (3 + 37);
+        let pixel = self.scanline_cycle - 68;
+
+        (line as usize, pixel as usize)
+    }
+
+    fn wait_for_leading_edge_of_horizontal_blank(&mut self) {
+        // TODO; To be implemented.
+        self.cpu_halt = true;
+    }
+
+    fn reset_horizontal_sync_counter(&mut self) {
+        // TODO; To be implemented.
+        // panic!("not implemented yet");
+
+// 10h - RESP0 <strobe> - Reset player 0
+// 11h - RESP1 <strobe> - Reset player 1
+// 12h - RESM0 <strobe> - Reset missile 0
+// 13h - RESM1 <strobe> - Reset missile 1
+// 14h - RESBL <strobe> - Reset ball
+// Writing any value to these addresses sets the associated objects horizontal
+// position equal to the current position of the cathode ray beam, if the write
+// takes place anywhere within horizontal blanking then the position is set to
+// the left edge of the screen (plus a few pixels towards right: 3 pixels for P0/P1, and only 2 pixels for M0/M1/BL).
+// Note: Because of opcode execution times, it is usually necessary to adjust
+//the resulting position to the desired value by subsequently using the Horizontal Motion function.
+    }
+
+    fn reset_position(&self, is_player: bool) -> u32 {
+        if self.is_horizontal_blank() {
+            // If the strobe register is triggered during horizontal blanking,
+            // the position will become at the very left of the screen edge plus
+            // 3 pixels for players, and 2 pixels for missiles and the ball.
+            if is_player { 3 } else { 2 }
+        }
+        else {
+            self.scanline_cycle - 68
+        }
+    }
+
+    fn reset_player(&mut self, index: usize) {
+        // The copies of a player are started by its position counter, but
+        // the first copy is started when the counter wraps around, not when
+        // it's reset; so a player reset in the visible area misses its first
+        // copy until the beam comes back to its position on the next scanline
+        // (the other copies are drawn, which the 48-pixel bitmaps rely on).
+        self.players_position[index] = self.reset_position(true);
+        if !self.is_horizontal_blank() {
+            self.hidden_first_copies[index] = HORIZONTAL_CYCLES;
+        }
+    }
+
+    fn latch_old_graphics(&mut self, player: Player) {
+        // Writing the graphics of a player copies the new graphics of the other
+        // player into its old copy, and writing GRP1 also copies the new ENABL
+        // into the old one. The old copies are the ones displayed when
+        // vertical delay is enabled; this cross-latching is what allows the
+        // six-digit score routines to update both players 'at once'.
+        match player {
+            Player::One => {
+                self.old_players_graphics[1] = self.registers[GRP1 as usize];
+            },
+            Player::Two => {
+                self.old_players_graphics[0] = self.registers[GRP0 as usize];
+                self.old_ball_enable = self.registers[ENABL as usize];
+            }
+        }
+    }
+
+    fn apply_horizontal_motion(&mut self) {
+        // During the horizontal blank, the blank is extended by 8 pixels and
+        // the objects miss the 8 clocks that would have moved them back to
+        // where they were; the motion values compensate for it.
+        if self.is_horizontal_blank() {
+            self.is_hmove_blank = true;
+            for object in 0..5 {
+                let position = self.object_position_mut(object);
+                *position = (*position + 8) % 160;
+            }
+        }
+
+        self.motion = Some(Motion {
+            delay: MOTION_DELAY,
+            counter: 0,
+            latches: [true; 5],
+            values: MOTION_REGISTERS.map(|address| self.registers[address as usize] >> 4)
+        });
+    }
+
+    fn clear_horizontal_motion_registers(&mut self) {
+        for address in MOTION_REGISTERS {
+            self.registers[address as usize] = 0;
+        }
+    }
+
+    // Advance the horizontal motion by one color cycle.
+    fn clock_motion(&mut self) {
+        self.motion_clocks = [false; 5];
+
+        let mut motion = match self.motion {
+            Some(motion) => motion,
+            None => return
+        };
+
+        if motion.delay > 0 {
+            motion.delay -= 1;
+            self.motion = Some(motion);
+            return;
+        }
+
+        // The original TIA compares the counter with the motion registers as
+        // they are, so writing them during the motion changes it (the Cosmic
+        // Ark starfield); the later revision uses the values of when HMOVE
+        // was written.
+        let blank_end = if self.is_hmove_blank { 76 } else { 68 };
+        for (object, address) in MOTION_REGISTERS.iter().enumerate() {
+            if !motion.latches[object] {
+                continue;
+            }
+
+            let value = match self.revision {
+                TiaRevision::Original => self.registers[*address as usize] >> 4,
+                TiaRevision::Late => motion.values[object]
+            };
+
+            if motion.counter == value ^ 0b1000 {
+                motion.latches[object] = false;
+            }
+            else {
+                self.motion_clocks[object] = true;
+                if self.scanline_cycle < blank_end {
+                    let position = self.object_position_mut(object);
+                    *position = (*position + 159) % 160;
+                }
+            }
+        }
+
+        motion.counter += 1;
+        motion.delay = MOTION_STEP_CYCLES - 1;
+        self.motion = if motion.counter < 16 { Some(motion) } else { None };
+    }
+
+    fn object_position_mut(&mut self, object: usize) -> &mut u32 {
+        match object {
+            0 | 1 => &mut self.players_position[object],
+            2 | 3 => &mut self.missiles_position[object - 2],
+            _ => &mut self.ball_position
+        }
+    }
+
+    pub(crate) fn clear_collision_latches(&mut self) {
+        // Reset all collision-related bits to 0.
+        self.registers[CXM0P as usize]  = 0x0000_0000;
+        self.registers[CXM1P as usize]  = 0x0000_0000;
+        self.registers[CXP0FB as usize] = 0x0000_0000;
+        self.registers[CXP1FB as usize] = 0x0000_0000;
+        self.registers[CXM0FB as usize] = 0x0000_0000;
+        self.registers[CXM1FB as usize] = 0x0000_0000;
+        self.registers[CXBLPF as usize] = 0x0000_0000;
+        self.registers[CXPPMM as usize] = 0x0000_0000;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tia() {
+        let mut tia = Tia::new();
+
+        // Only the high bits of the read registers are driven.
+        tia.registers[CXM0P as usize] = 0b1111_1111;
+        tia.registers[CXBLPF as usize] = 0b1111_1111;
+        assert_eq!(tia.read(CXM0P), 0b1100_0000);
+        assert_eq!(tia.read(CXBLPF), 0b1000_0000);
+        assert_eq!(tia.read(INPT4), 0b1000_0000);
+
+        // Writing the strobes triggers them.
+        tia.write(CXCLR, 0);
+        assert_eq!(tia.read(CXM0P), 0);
+
+        tia.write(WSYNC, 0);
+        assert!(tia.cpu_halt);
+
+        tia.scanline_cycle = 68 + 40;
+        tia.write(RESP0, 0);
+        assert_eq!(tia.players_position[0], 40);
+
+        // Whatever the value is; and the strobes don't keep it.
+        for strobe in [WSYNC, RSYNC, RESP0, RESP1, RESM0, RESM1, RESBL, HMOVE, HMCLR, CXCLR] {
+            tia.write(strobe, 0b1111_1111);
+            assert_eq!(tia.register(strobe), 0);
+        }
+        assert_eq!(tia.players_position[0], 40);
+        tia.cpu_halt = false;
+
+        // Stepping to the end of the scanline releases the CPU, and a frame is
+        // completed every 262 scanlines.
+        while tia.scanline_cycle != 0 {
+            assert!(!tia.step().1);
+        }
+        assert!(!tia.cpu_halt);
+
+        let mut samples = 0;
+        let mut color_cycles = 0;
+        loop {
+            let (sample, is_frame_completed) = tia.step();
+            samples += sample.is_some() as u32;
+            color_cycles += 1;
+
+            if is_frame_completed {
+                break;
+            }
+        }
+        assert_eq!(color_cycles, (TvStandard::Ntsc.scanlines() - 1) * HORIZONTAL_CYCLES);
+        assert_eq!(samples, (TvStandard::Ntsc.scanlines() - 1) * 2);
+        assert_eq!(tia.scanline, 0);
+    }
+
+    #[test]
+    fn test_horizontal_motion() {
+        // Run a scanline with HMOVE written at the given color cycle, and
+        // return the motion of the first player.
+        fn hmove(revision: TiaRevision, value: u8, cycle: u32) -> i32 {
+            let mut tia = Tia::new();
+            tia.revision = revision;
+            tia.scanline = 100;
+            tia.players_position[0] = 80;
+            tia.write(HMP0, value);
+
+            while tia.scanline_cycle != cycle {
+                tia.step();
+            }
+            tia.write(HMOVE, 0);
+            while tia.motion.is_some() {
+                tia.step();
+            }
+
+            tia.players_position[0] as i32 - 80
+        }
+
+        // At the start of the scanline, the objects move by the motion
+        // values, from 7 pixels to the left to 8 pixels to the right.
+        assert_eq!(hmove(TiaRevision::Original, 0x_70, 0), -7);
+        assert_eq!(hmove(TiaRevision::Original, 0x_10, 0), -1);
+        assert_eq!(hmove(TiaRevision::Original, 0x_00, 0), 0);
+        assert_eq!(hmove(TiaRevision::Original, 0x_F0, 0), 1);
+        assert_eq!(hmove(TiaRevision::Original, 0x_80, 0), 8);
+
+        // In the middle of the scanline, the objects don't move.
+        assert_eq!(hmove(TiaRevision::Original, 0x_70, 90), 0);
+        assert_eq!(hmove(TiaRevision::Original, 0x_80, 90), 0);
+
+        // Late in the horizontal blank, only the first steps of the motion
+        // move them, and the compensation moves them to the right.
+        assert_eq!(hmove(TiaRevision::Original, 0x_70, 45), 1);
+
+        // At the end of the scanline (the cycle 74 of the CPU), the motion
+        // happens on the next scanline without the 8 pixels of compensation;
+        // a motion value of 8 pixels to the right doesn't move them.
+        assert_eq!(hmove(TiaRevision::Original, 0x_80, 222), 0);
+        assert_eq!(hmove(TiaRevision::Original, 0x_00, 222), -8);
+
+        // The horizontal blank is extended by 8 black pixels, only when HMOVE
+        // is written during the horizontal blank.
+        let mut tia = Tia::new();
+        tia.scanline = 100;
+        tia.write(COLUBK, 0x_0E);
+        tia.write(HMOVE, 0);
+        for _ in 0..HORIZONTAL_CYCLES + 100 {
+            tia.step();
+        }
+        tia.write(HMOVE, 0);
+        for _ in 100..HORIZONTAL_CYCLES {
+            tia.step();
+        }
+        assert!(tia.framebuffer[60][..8].iter().all(|pixel| *pixel == (0, 0, 0)));
+        assert!(tia.framebuffer[60][8] != (0, 0, 0));
+        assert!(tia.framebuffer[61][0] != (0, 0, 0));
+
+        // The original TIA compares the counter with the motion registers as
+        // they are, so changing them during the motion changes it; the later
+        // revision doesn't.
+        for (revision, motion) in [(TiaRevision::Original, -7), (TiaRevision::Late, 0)] {
+            let mut tia = Tia::new();
+            tia.revision = revision;
+            tia.players_position[0] = 80;
+            tia.write(HMP0, 0x_00);
+            tia.write(HMOVE, 0);
+            for _ in 0..20 {
+                tia.step();
+            }
+            tia.write(HMP0, 0x_70);
+            while tia.motion.is_some() {
+                tia.step();
+            }
+            assert_eq!(tia.players_position[0] as i32 - 80, motion);
+        }
+
+        // HMCLR clears the motion registers.
+        tia.write(HMP0, 0x_70);
+        tia.write(HMBL, 0x_70);
+        tia.write(HMCLR, 0);
+        assert_eq!((tia.register(HMP0), tia.register(HMBL)), (0, 0));
+    }
+}
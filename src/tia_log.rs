@@ -0,0 +1,270 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+
+use crate::console::TvStandard;
+use crate::frame::{Frame, Orientation};
+use crate::tia::{Tia, HORIZONTAL_CYCLES};
+
+/// An error that occurred while reading a log of TIA writes.
+#[derive(Debug)]
+pub struct TiaLogError {
+    /// Number of the line with the error (starting at 1).
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for TiaLogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TiaLogError {}
+
+/// A write to a register of the TIA.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TiaWrite {
+    /// When the TIA sees the write, in color cycles since the start of the
+    /// log.
+    pub cycle: u64,
+    /// Address of the write register (0x00 to 0x2C).
+    pub address: u16,
+    pub value: u8
+}
+
+/// A log of the writes to the registers of the TIA.
+///
+/// This structure is a recording of the bus activity of the TIA; the writes
+/// to its registers, timed in color cycles. It's replayed through the TIA
+/// alone with `replay()`, without a CPU, and the frames are produced from
+/// the writes only, which validates the video pipeline independently of the
+/// timing of the CPU. A log comes from another emulator or from a logic
+/// analyzer plugged on a console (read with `parse()`), or from this
+/// emulator (see `Console::start_tia_log()`), in which case the replayed
+/// frames must be identical to the frames of the console.
+///
+/// Its text form has a line per write, made of the cycle (in decimal), the
+/// address and the value (in hexadecimal). An optional first line gives the
+/// position of the beam when the log starts (at the first cycle of the
+/// frame by default). Blank lines and comments (starting with `#`) are
+/// ignored.
+///
+/// ```text
+/// beam 0 0
+/// 0 00 02
+/// 684 00 00
+/// 1254 09 84
+/// ```
+///
+/// The TIA starts with its registers cleared and its objects at the left of
+/// the screen; the logs that don't start at power-on need a few frames to
+/// settle.
+///
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct TiaLog {
+    /// Scanline of the beam when the log starts.
+    pub scanline: u32,
+    /// Color cycle of the beam (within the scanline) when the log starts.
+    pub scanline_cycle: u32,
+    /// The writes, in chronological order.
+    pub writes: Vec<TiaWrite>
+}
+
+impl TiaLog {
+    /// Create an empty log starting with the beam at the given position.
+    pub fn new(scanline: u32, scanline_cycle: u32) -> TiaLog {
+        TiaLog {
+            scanline,
+            scanline_cycle,
+            writes: Vec::new()
+        }
+    }
+
+    /// Read a log from its text form.
+    ///
+    /// This function reads a log as it's formatted by `Display`; it fails if
+    /// a line is malformed, if an address isn't a write register, or if the
+    /// writes aren't in chronological order.
+    ///
+    pub fn parse(text: &str) -> Result<TiaLog, TiaLogError> {
+        let mut log = TiaLog::default();
+
+        for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+            let error = |message: &str| TiaLogError { line: number, message: message.to_string() };
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields[0] == "beam" {
+                if fields.len() != 3 || !log.writes.is_empty() {
+                    return Err(error("the position of the beam must be the first line"));
+                }
+
+                log.scanline = fields[1].parse().map_err(|_| error("invalid scanline"))?;
+                log.scanline_cycle = fields[2].parse().ok()
+                    .filter(|cycle| *cycle < HORIZONTAL_CYCLES)
+                    .ok_or_else(|| error("invalid color cycle"))?;
+
+                continue;
+            }
+
+            if fields.len() != 3 {
+                return Err(error("expected a cycle, an address and a value"));
+            }
+
+            let cycle = fields[0].parse::<u64>().map_err(|_| error("invalid cycle"))?;
+            let address = u16::from_str_radix(fields[1], 16).ok()
+                .filter(|address| *address <= 0x_2C)
+                .ok_or_else(|| error("invalid address (must be a write register)"))?;
+            let value = u8::from_str_radix(fields[2], 16).map_err(|_| error("invalid value"))?;
+
+            if log.writes.last().is_some_and(|write| write.cycle > cycle) {
+                return Err(error("the writes are out of order"));
+            }
+
+            log.writes.push(TiaWrite { cycle, address, value });
+        }
+
+        Ok(log)
+    }
+
+    /// Replay the log through the TIA.
+    ///
+    /// This function feeds the writes to a TIA of the given standard at their
+    /// cycle and returns the frames it completes, up to the frame following
+    /// the last write. The vertical sync is checked once per CPU cycle as it
+    /// is in the console (on the cycles that are multiples of 3), and the
+    /// WSYNC writes don't halt anything; the timing of the writes is the one
+    /// of the log.
+    ///
+    pub fn replay(&self, tv_standard: TvStandard) -> Vec<Frame> {
+        let mut tia = Tia::new();
+        tia.tv_standard = tv_standard;
+        tia.scanline = self.scanline;
+        tia.scanline_cycle = self.scanline_cycle;
+
+        let mut frames = Vec::new();
+        let mut writes = self.writes.iter().peekable();
+        let mut cycle = 0;
+
+        loop {
+            while let Some(write) = writes.next_if(|write| write.cycle <= cycle) {
+                tia.write(write.address, write.value);
+            }
+
+            if cycle % 3 == 0 {
+                tia.update_vertical_sync();
+            }
+
+            if tia.step().1 {
+                frames.push(Frame {
+                    number: frames.len() as u64 + 1,
+                    pixels: *tia.pending_framebuffer,
+                    orientation: Orientation::default()
+                });
+
+                if writes.peek().is_none() {
+                    break;
+                }
+            }
+
+            cycle += 1;
+        }
+
+        frames
+    }
+}
+
+impl fmt::Display for TiaLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "beam {} {}", self.scanline, self.scanline_cycle)?;
+        for write in &self.writes {
+            writeln!(f, "{} {:02X} {:02X}", write.cycle, write.address, write.value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_tia_log() {
+        // A frame with a vertical sync, a background changing color on each
+        // line, and a player in the middle of the screen.
+        let mut rom = assemble("
+            frame:
+                lda #2
+                sta $00   ; VSYNC
+                sta $02   ; WSYNC
+                sta $02
+                sta $02
+                lda #0
+                sta $00
+                ldx #0
+            line:
+                stx $09   ; COLUBK
+                cpx #100
+                bne skip
+                sta $10   ; RESP0
+                lda #$FF
+                sta $1B   ; GRP0
+                lda #$1E
+                sta $06   ; COLUP0
+            skip:
+                sta $02
+                inx
+                bne line
+                sta $02
+                sta $02
+                sta $02
+                sta $02
+                jmp frame
+        ").unwrap();
+        rom.resize(4096, 0x_EA);
+
+        // Replaying the writes the console made produces the same frames.
+        let mut console = Console::new(Cartridge::new(rom));
+        console.start_tia_log();
+        console.run_frame();
+        console.run_frame();
+        let first_frame = console.frame();
+        console.run_frame();
+        let log = console.stop_tia_log().unwrap();
+        assert!(console.stop_tia_log().is_none());
+
+        let frames = log.replay(TvStandard::Ntsc);
+        assert_eq!(frames.len(), 3);
+        assert!(frames[1].pixels == first_frame.pixels);
+        assert!(frames[2].pixels == console.frame().pixels);
+
+        // And so does the text form of the log.
+        let text = log.to_string();
+        assert!(text.starts_with("beam 0 0\n6 00 02\n"));
+        let other_log = TiaLog::parse(&format!("# Recorded by the emulator.\n\n{}", text)).unwrap();
+        assert_eq!(other_log, log);
+
+        assert_eq!(TiaLog::parse("10 09 84\n5 09 00").unwrap_err().to_string(), "line 2: the writes are out of order");
+        assert_eq!(TiaLog::parse("10 30 00").unwrap_err().line, 1);
+        assert!(TiaLog::parse("beam 0 228").is_err());
+        assert!(TiaLog::parse("10 09").is_err());
+
+        // Without writes, the TIA completes the current frame.
+        let frames = TiaLog::new(261, 0).replay(TvStandard::Ntsc);
+        assert_eq!(frames.len(), 1);
+    }
+}
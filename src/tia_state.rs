@@ -0,0 +1,131 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+/// The horizontal placement of a moving object.
+///
+/// The position is the value of the horizontal position counter of the object
+/// (in pixels from the left edge of the screen) and the width is the width of
+/// each copy, as configured by NUSIZx (players and missiles) or CTRLPF (ball).
+/// The copies are the horizontal positions where the object is drawn; they
+/// wrap around the right edge of the screen.
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct ObjectState {
+    pub position: u32,
+    pub width: u32,
+    pub copies: Vec<u32>
+}
+
+impl ObjectState {
+    pub(crate) fn new(position: u32, width: u32, offsets: &[u32]) -> ObjectState {
+        ObjectState {
+            position,
+            width,
+            copies: offsets.iter().map(|offset| (position + offset) % 160).collect()
+        }
+    }
+}
+
+/// A snapshot of the state of the TIA.
+///
+/// This structure is meant for debuggers and overlays; it exposes the
+/// placement of the moving objects (the players, the missiles and the ball)
+/// so their bounding boxes can be drawn over the frame.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, TEST_ROM};
+/// # let console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// # fn draw_rectangle(_x: u32, _width: u32) {}
+/// let state = console.tia_state();
+/// for copy in &state.players[0].copies {
+///     draw_rectangle(*copy, state.players[0].width);
+/// }
+/// ```
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiaState {
+    pub players: [ObjectState; 2],
+    pub missiles: [ObjectState; 2],
+    pub ball: ObjectState
+}
+
+/// The layers of a pixel, before the priorities are applied.
+///
+/// This structure tells which of the playfield and the moving objects cover a
+/// pixel; only the one with the highest priority is visible in the frame. It's
+/// meant for a layer debugger, which draws each layer separately (see
+/// `Console::set_layer_capture()`).
+///
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PixelLayers {
+    pub playfield: bool,
+    pub players: [bool; 2],
+    pub missiles: [bool; 2],
+    pub ball: bool
+}
+
+impl PixelLayers {
+    /// Collisions at the pixel.
+    ///
+    /// This function returns the bits the pixel sets in the eight collision
+    /// latches of the TIA, in order (CXM0P, CXM1P, CXP0FB, CXP1FB, CXM0FB,
+    /// CXM1FB, CXBLPF and CXPPMM); the bit 7 and 6 of each of them are the
+    /// collisions between two layers.
+    ///
+    pub fn collisions(&self) -> [u8; 8] {
+        let [p0, p1] = self.players;
+        let [m0, m1] = self.missiles;
+        let (bl, pf) = (self.ball, self.playfield);
+
+        let latch = |bit7: bool, bit6: bool| (bit7 as u8) << 7 | (bit6 as u8) << 6;
+        [
+            latch(m0 && p1, m0 && p0),
+            latch(m1 && p0, m1 && p1),
+            latch(p0 && pf, p0 && bl),
+            latch(p1 && pf, p1 && bl),
+            latch(m0 && pf, m0 && bl),
+            latch(m1 && pf, m1 && bl),
+            latch(bl && pf, false),
+            latch(p0 && p1, m0 && m1)
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_object_state() {
+        let object = ObjectState::new(150, 8, &[0, 16, 32]);
+        assert_eq!(object.copies, vec![150, 6, 22]);
+    }
+
+    #[test]
+    fn test_pixel_layers() {
+        assert_eq!(PixelLayers::default().collisions(), [0; 8]);
+
+        let layers = PixelLayers {
+            playfield: true,
+            players: [true, false],
+            missiles: [false, true],
+            ball: true
+        };
+        assert_eq!(layers.collisions(), [
+            0b0000_0000,
+            0b1000_0000,
+            0b1100_0000,
+            0b0000_0000,
+            0b0000_0000,
+            0b1100_0000,
+            0b1000_0000,
+            0b0000_0000
+        ]);
+    }
+}
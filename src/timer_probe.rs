@@ -0,0 +1,199 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use crate::cartridge::Cartridge;
+use crate::rom_builder::RomBuilder;
+use crate::console::Console;
+use crate::location::{INTIM, INSTAT, TIM1T, TIM8T, TIM64T, T1024T};
+
+// The probe stores the value it reads in the first byte of the RAM.
+const RESULT: u8 = 0x_80;
+
+/// The register of the timer read by a probe.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimerOutput {
+    /// The value of the timer.
+    Intim,
+    /// The underflow flag of the timer (bit 7 of INSTAT).
+    Instat
+}
+
+/// A probe of the timer of the PIA.
+///
+/// This structure describes a tiny synthetic ROM which writes a value to one
+/// of the timer registers (TIM1T, TIM8T, TIM64T or T1024T), waits a given
+/// number of cycles and reads INTIM or INSTAT. It's meant to exercise the
+/// corner cases of the timer; reading it right after it's written, and around
+/// the underflow.
+///
+/// The delay is the number of cycles between the cycle the timer is written
+/// and the cycle it's read; since both are done by absolute instructions, the
+/// shortest delay is 4 cycles, and 5 cycles can't be made.
+///
+/// ```
+/// # use atari_2600::{TimerOutput, TimerProbe};
+/// let probe = TimerProbe { interval: 8, value: 2, delay: 17, output: TimerOutput::Intim };
+/// assert_eq!(probe.observe(), probe.expected());
+/// ```
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimerProbe {
+    pub interval: u32,
+    pub value: u8,
+    pub delay: u32,
+    pub output: TimerOutput
+}
+
+impl TimerProbe {
+    /// Generate the ROM of the probe.
+    ///
+    /// This function generates a 4k ROM which runs the probe and stores the
+    /// value read at the first byte of the RAM (0x80), then loops forever.
+    ///
+    pub fn rom(&self) -> Cartridge {
+        let code = self.code();
+        let address = 0x_F000 + code.len() as u16;
+
+        RomBuilder::new()
+            .data(0x_F000, &code)
+            .code(address, "end: jmp end")
+            .build()
+            .unwrap()
+    }
+
+    /// The value read on the hardware.
+    ///
+    /// This function returns the value the probe reads on the hardware. Once
+    /// written, the timer is decremented on the next cycle, then once per
+    /// interval. When it decrements past 0, it underflows; it's set to 0xFF,
+    /// the underflow flag is set, and it's decremented every cycle from then
+    /// on.
+    ///
+    pub fn expected(&self) -> u8 {
+        let underflow_delay = 1 + self.value as u32 * self.interval;
+
+        match self.output {
+            TimerOutput::Intim if self.delay < underflow_delay => {
+                self.value - 1 - ((self.delay - 1) / self.interval) as u8
+            },
+            TimerOutput::Intim => 0x_FF - ((self.delay - underflow_delay) % 256) as u8,
+            TimerOutput::Instat if self.delay < underflow_delay => 0b0000_0000,
+            TimerOutput::Instat => 0b1000_0000
+        }
+    }
+
+    /// The value read on the emulator.
+    ///
+    /// This function runs the ROM of the probe on a console and returns the
+    /// value it reads; for INSTAT, only the underflow flag is kept.
+    ///
+    pub fn observe(&self) -> u8 {
+        let mut console = Console::new(self.rom());
+
+        let end = 0x_F000 + self.code().len() as u16;
+        while console.program_counter() != end {
+            console.step_instruction();
+        }
+
+        match self.output {
+            TimerOutput::Intim => console.memory(RESULT as u16),
+            TimerOutput::Instat => console.memory(RESULT as u16) & 0b1000_0000
+        }
+    }
+
+    fn code(&self) -> Vec<u8> {
+        let register = match self.interval {
+            1 => TIM1T,
+            8 => TIM8T,
+            64 => TIM64T,
+            1024 => T1024T,
+            _ => panic!("timer interval {} isn't 1, 8, 64 or 1024", self.interval)
+        };
+        assert!(self.delay == 4 || self.delay >= 6, "timer can't be read {} cycles after it's written", self.delay);
+
+        let output = match self.output {
+            TimerOutput::Intim => INTIM,
+            TimerOutput::Instat => INSTAT
+        };
+
+        let mut code = vec![
+            0x_A9, self.value,                                   // LDA #value
+            0x_8D, register as u8, (register >> 8) as u8         // STA TIMxT
+        ];
+
+        // The read is the 4th cycle of the LDA instruction; the remaining
+        // cycles are padded with NOP (2 cycles) and BIT (3 cycles).
+        let mut padding = self.delay - 4;
+        if padding % 2 == 1 {
+            code.extend_from_slice(&[0x_24, RESULT]);            // BIT result
+            padding -= 3;
+        }
+        code.resize(code.len() + padding as usize / 2, 0x_EA);   // NOP
+
+        code.extend_from_slice(&[
+            0x_AD, output as u8, (output >> 8) as u8,            // LDA output
+            0x_85, RESULT                                        // STA result
+        ]);
+
+        code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timer_probes() {
+        let probe = |interval, value, delay, output| TimerProbe { interval, value, delay, output };
+
+        let probes = [
+            // The timer is read right after it's written; it was decremented
+            // once immediately, and then once per interval.
+            probe(1, 100, 4, TimerOutput::Intim),
+            probe(8, 100, 4, TimerOutput::Intim),
+            probe(64, 100, 4, TimerOutput::Intim),
+            probe(1024, 100, 4, TimerOutput::Intim),
+            probe(1, 100, 6, TimerOutput::Intim),
+            probe(8, 100, 7, TimerOutput::Intim),
+
+            // Around the interval boundaries.
+            probe(8, 100, 8, TimerOutput::Intim),
+            probe(8, 100, 9, TimerOutput::Intim),
+            probe(8, 100, 10, TimerOutput::Intim),
+            probe(64, 3, 64, TimerOutput::Intim),
+            probe(64, 3, 65, TimerOutput::Intim),
+            probe(64, 3, 66, TimerOutput::Intim),
+
+            // Around the underflow; the timer is then decremented every cycle.
+            probe(8, 2, 16, TimerOutput::Intim),
+            probe(8, 2, 17, TimerOutput::Intim),
+            probe(8, 2, 18, TimerOutput::Intim),
+            probe(8, 2, 30, TimerOutput::Intim),
+            probe(1, 5, 6, TimerOutput::Intim),
+            probe(1, 5, 7, TimerOutput::Intim),
+            probe(1, 0, 4, TimerOutput::Intim),
+            probe(1024, 0, 4, TimerOutput::Intim),
+            probe(8, 2, 16, TimerOutput::Instat),
+            probe(8, 2, 17, TimerOutput::Instat),
+            probe(8, 2, 18, TimerOutput::Instat),
+            probe(1024, 0, 4, TimerOutput::Instat),
+            probe(1, 100, 300, TimerOutput::Intim)
+        ];
+
+        for probe in probes.iter() {
+            assert_eq!(probe.observe(), probe.expected(), "{:?}", probe);
+        }
+
+        // The documented values.
+        assert_eq!(probe(64, 100, 4, TimerOutput::Intim).expected(), 99);
+        assert_eq!(probe(1, 100, 4, TimerOutput::Intim).expected(), 96);
+        assert_eq!(probe(8, 2, 17, TimerOutput::Intim).expected(), 0x_FF);
+        assert_eq!(probe(8, 2, 18, TimerOutput::Intim).expected(), 0x_FE);
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::console::Console;
+use crate::analysis::{Operand, decode, is_write};
+
+/// The registers of the CPU.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Register {
+    Accumulator,
+    X,
+    Y,
+    StackPointer
+}
+
+/// The status flags of the CPU.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flag {
+    Negative,
+    Overflow,
+    Break,
+    Decimal,
+    Interrupt,
+    Zero,
+    Carry
+}
+
+/// The filter of a trace.
+///
+/// By default, every instruction is traced; the filter narrows it down to the
+/// instructions executed within an address range, within some banks, when
+/// registers or flags have a given value, or to the instructions that write
+/// the TIA registers. All the conditions must be met for an instruction to be
+/// traced. It keeps the traces of long sessions manageable.
+///
+/// ```
+/// # use atari_2600::{Register, TraceFilter};
+/// let filter = TraceFilter::new()
+///     .address_range(0x_F100..=0x_F1FF)
+///     .register(Register::X, 0)
+///     .tia_writes_only();
+/// ```
+///
+#[derive(Clone, Default, Debug)]
+pub struct TraceFilter {
+    addresses: Option<RangeInclusive<u16>>,
+    banks: Option<Vec<usize>>,
+    registers: Vec<(Register, u8)>,
+    flags: Vec<(Flag, bool)>,
+    is_tia_writes_only: bool
+}
+
+impl TraceFilter {
+    /// Create a filter that traces every instruction.
+    pub fn new() -> TraceFilter {
+        TraceFilter::default()
+    }
+
+    /// Only trace the instructions within an address range.
+    pub fn address_range(mut self, addresses: RangeInclusive<u16>) -> TraceFilter {
+        self.addresses = Some(addresses);
+        self
+    }
+
+    /// Only trace the instructions of a bank.
+    ///
+    /// This function can be called several times to trace several banks.
+    ///
+    pub fn bank(mut self, bank: usize) -> TraceFilter {
+        self.banks.get_or_insert_with(Vec::new).push(bank);
+        self
+    }
+
+    /// Only trace when a register has a given value.
+    pub fn register(mut self, register: Register, value: u8) -> TraceFilter {
+        self.registers.push((register, value));
+        self
+    }
+
+    /// Only trace when a flag has a given value.
+    pub fn flag(mut self, flag: Flag, value: bool) -> TraceFilter {
+        self.flags.push((flag, value));
+        self
+    }
+
+    /// Only trace the instructions writing the TIA registers.
+    pub fn tia_writes_only(mut self) -> TraceFilter {
+        self.is_tia_writes_only = true;
+        self
+    }
+
+    fn matches(&self, console: &Console) -> bool {
+        if let Some(addresses) = &self.addresses {
+            if !addresses.contains(&console.cpu.program_counter) {
+                return false;
+            }
+        }
+
+        if let Some(banks) = &self.banks {
+            if !banks.contains(&console.current_bank()) {
+                return false;
+            }
+        }
+
+        let is_register_matching = |(register, value): &(Register, u8)| match register {
+            Register::Accumulator => console.cpu.accumulator == *value,
+            Register::X => console.cpu.x_register == *value,
+            Register::Y => console.cpu.y_register == *value,
+            Register::StackPointer => console.cpu.stack_pointer == *value
+        };
+
+        let is_flag_matching = |(flag, value): &(Flag, bool)| match flag {
+            Flag::Negative => console.cpu.negative_flag == *value,
+            Flag::Overflow => console.cpu.overflow_flag == *value,
+            Flag::Break => console.cpu.break_flag == *value,
+            Flag::Decimal => console.cpu.decimal_flag == *value,
+            Flag::Interrupt => console.cpu.interrupt_flag == *value,
+            Flag::Zero => console.cpu.zero_flag == *value,
+            Flag::Carry => console.cpu.carry_flag == *value
+        };
+
+        if !self.registers.iter().all(is_register_matching) || !self.flags.iter().all(is_flag_matching) {
+            return false;
+        }
+
+        if self.is_tia_writes_only {
+            // The TIA is selected when A12 and A7 are clear.
+            return match write_address(console) {
+                Some(address) => address & 0x_1080 == 0,
+                None => false
+            };
+        }
+
+        true
+    }
+}
+
+fn write_address(console: &Console) -> Option<u16> {
+    // Compute the address the next instruction writes to, if it writes to the
+    // memory, from the current state of the registers.
+    let pc = console.cpu.program_counter;
+    let opcode = console.memory(pc);
+    if !is_write(opcode) {
+        return None;
+    }
+
+    let byte = |address: u16| console.memory(address) as u16;
+    let word = |address: u16| byte(address) | byte(address.wrapping_add(1)) << 8;
+    let (bbb, cc) = ((opcode >> 2) & 0b111, opcode & 0b11);
+
+    // STX and LDX index with Y instead of X.
+    let index = match (cc, bbb) {
+        (0b01, 0b110) | (0b01, 0b100) => console.cpu.y_register,
+        (0b10, 0b101) | (0b10, 0b111) if opcode == 0x_96 || opcode == 0x_B6 || opcode == 0x_BE => console.cpu.y_register,
+        (_, 0b101) | (_, 0b111) | (0b01, 0b000) => console.cpu.x_register,
+        _ => 0
+    } as u16;
+
+    let address = match decode(opcode)? {
+        Operand::ZeroPage => (byte(pc + 1) + index) & 0x_00FF,
+        Operand::Absolute => word(pc + 1).wrapping_add(index),
+        Operand::IndexedIndirect if bbb == 0b000 => word((byte(pc + 1) + index) & 0x_00FF),
+        Operand::IndexedIndirect => word(byte(pc + 1)).wrapping_add(index),
+        _ => return None
+    };
+
+    Some(address)
+}
+
+// A traced instruction; the position of the beam, the bytes of the
+// instruction and the registers and flags of the CPU right before it's
+// executed. It's formatted as a line of the trace.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TraceEntry {
+    frame: u64,
+    scanline: u32,
+    scanline_cycle: u32,
+    bank: usize,
+    address: u16,
+    bytes: [u8; 3],
+    size: usize,
+    registers: [u8; 4],
+    flags: [bool; 7]
+}
+
+impl TraceEntry {
+    /// Capture the instruction the console is about to execute.
+    pub(crate) fn capture(console: &Console) -> TraceEntry {
+        let address = console.cpu.program_counter;
+        let opcode = console.memory(address);
+        let size = decode(opcode).map_or(1, |operand| 1 + operand.size());
+
+        let mut bytes = [0; 3];
+        for (offset, byte) in bytes.iter_mut().enumerate().take(size) {
+            *byte = console.memory(address.wrapping_add(offset as u16));
+        }
+
+        let telemetry = console.telemetry();
+        TraceEntry {
+            frame: telemetry.frames,
+            scanline: telemetry.scanline,
+            scanline_cycle: telemetry.scanline_cycle,
+            bank: console.current_bank(),
+            address,
+            bytes,
+            size,
+            registers: [
+                console.cpu.accumulator,
+                console.cpu.x_register,
+                console.cpu.y_register,
+                console.cpu.stack_pointer
+            ],
+            flags: [
+                console.cpu.negative_flag,
+                console.cpu.overflow_flag,
+                console.cpu.break_flag,
+                console.cpu.decimal_flag,
+                console.cpu.interrupt_flag,
+                console.cpu.zero_flag,
+                console.cpu.carry_flag
+            ]
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // frame, scanline, cycle, bank:address, bytes, registers and flags.
+        //
+        //      1  40  68 0:F000 85 09      A:0E X:00 Y:00 SP:FF P:nv-bdIZc
+        let bytes = self.bytes[..self.size].iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let letters = ['N', 'V', 'B', 'D', 'I', 'Z', 'C'];
+        let mut flags = self.flags.iter()
+            .zip(letters.iter())
+            .map(|(flag, letter)| if *flag { *letter } else { letter.to_ascii_lowercase() })
+            .collect::<String>();
+        flags.insert(2, '-');
+
+        let [accumulator, x, y, stack_pointer] = self.registers;
+        write!(
+            f,
+            "{:>6} {:>3} {:>3} {}:{:04X} {:<8}   A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{}",
+            self.frame, self.scanline, self.scanline_cycle,
+            self.bank, self.address, bytes,
+            accumulator, x, y, stack_pointer,
+            flags
+        )
+    }
+}
+
+// An ongoing trace.
+pub(crate) struct Tracer {
+    writer: Box<dyn Write + Send>,
+    filter: TraceFilter,
+    count: u64,
+    error: Option<io::Error>
+}
+
+impl Tracer {
+    pub(crate) fn new(writer: Box<dyn Write + Send>, filter: TraceFilter) -> Tracer {
+        Tracer {
+            writer,
+            filter,
+            count: 0,
+            error: None
+        }
+    }
+
+    /// Trace the instruction the console is about to execute.
+    pub(crate) fn trace(&mut self, console: &Console) {
+        if self.error.is_some() || !self.filter.matches(console) {
+            return;
+        }
+
+        match writeln!(self.writer, "{}", TraceEntry::capture(console)) {
+            Ok(_) => self.count += 1,
+            Err(error) => self.error = Some(error)
+        }
+    }
+
+    /// Stop the trace.
+    pub(crate) fn finish(mut self) -> io::Result<u64> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        self.writer.flush()?;
+        Ok(self.count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::cartridge::Cartridge;
+
+    // A writer whose content remains accessible once it's given to the
+    // console.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(rom: &[u8], filter: TraceFilter, instructions: usize) -> Vec<String> {
+        let mut rom = rom.to_vec();
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        let writer = SharedWriter::default();
+        console.start_trace(writer.clone(), filter);
+        for _ in 0..instructions {
+            console.step_instruction();
+        }
+        let count = console.stop_trace().unwrap();
+
+        let text = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let lines = text.lines().map(String::from).collect::<Vec<_>>();
+        assert_eq!(lines.len() as u64, count);
+
+        lines
+    }
+
+    #[test]
+    fn test_trace() {
+        // LDX #$02, STA COLUBK, STA $80,X, DEX, BNE $F002, JMP $F000.
+        let rom = [
+            0x_A2, 0x_02, 0x_85, 0x_09, 0x_95, 0x_80, 0x_CA, 0x_D0, 0x_F9, 0x_4C, 0x_00, 0x_F0
+        ];
+
+        let lines = trace(&rom, TraceFilter::new(), 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("0:F000 A2 02      A:00 X:00 Y:00 SP:FF P:NV-BDIZC"), "{}", lines[0]);
+        assert!(lines[1].contains("0:F002 85 09      A:00 X:02"), "{}", lines[1]);
+
+        // Only the loop; twice, then once more after the jump.
+        let lines = trace(&rom, TraceFilter::new().address_range(0x_F002..=0x_F008), 12);
+        assert_eq!(lines.len(), 9);
+
+        // Only when X is 1.
+        let lines = trace(&rom, TraceFilter::new().register(Register::X, 1), 12);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("0:F007"), "{}", lines[0]);
+
+        // Only when the zero flag is set in the loop; after DEX reaches 0.
+        let filter = TraceFilter::new().address_range(0x_F002..=0x_F008).flag(Flag::Zero, true);
+        let lines = trace(&rom, filter, 10);
+        assert!(lines[0].contains("0:F007"), "{}", lines[0]);
+
+        // Only the TIA writes; STA $80,X writes the RAM.
+        let lines = trace(&rom, TraceFilter::new().tia_writes_only(), 12);
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.contains("85 09")));
+
+        // Only a bank that isn't executed.
+        let lines = trace(&rom, TraceFilter::new().bank(1), 12);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_write_address() {
+        let mut rom = vec![
+            0x_9D, 0x_00, 0x_10, // STA $1000,X
+            0x_96, 0x_F0,        // STX $F0,Y
+            0x_91, 0x_80,        // STA ($80),Y
+            0x_81, 0x_80,        // STA ($80,X)
+            0x_A5, 0x_80         // LDA $80
+        ];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.cpu.x_register = 0x_02;
+        console.cpu.y_register = 0x_20;
+        *console.memory_mut(0x_80) = 0x_00;
+        *console.memory_mut(0x_81) = 0x_02;
+        *console.memory_mut(0x_82) = 0x_2A;
+        *console.memory_mut(0x_83) = 0x_00;
+
+        let mut addresses = Vec::new();
+        for _ in 0..5 {
+            addresses.push(write_address(&console));
+            console.cpu.program_counter += decode(console.memory(console.cpu.program_counter)).unwrap().size() as u16 + 1;
+        }
+
+        assert_eq!(addresses, vec![Some(0x_1002), Some(0x_0010), Some(0x_0220), Some(0x_002A), None]);
+    }
+}
@@ -7,33 +7,211 @@
 // Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, November 2020
 
 use crate::Console;
+use crate::Port;
 use crate::Controller;
+use crate::state::{StateWriter, StateError};
 
-/// Brief description.
+// The pins of the port A for each step of the horizontal and vertical
+// movements (given for the left controller, the right controller uses the 4
+// less significant pins). Mice send a 2-bit gray code for each axis whereas the
+// CX22 trackball sends a direction bit and a toggling motion bit.
+const AMIGA_MOUSE_HORIZONTAL: [u8; 4] = [0x_00, 0x_10, 0x_50, 0x_40];
+const AMIGA_MOUSE_VERTICAL: [u8; 4] = [0x_00, 0x_80, 0x_A0, 0x_20];
+const ATARI_MOUSE_HORIZONTAL: [u8; 4] = [0x_00, 0x_10, 0x_30, 0x_20];
+const ATARI_MOUSE_VERTICAL: [u8; 4] = [0x_00, 0x_80, 0x_C0, 0x_40];
+const TRACKBALL_HORIZONTAL: [[u8; 2]; 2] = [[0x_40, 0x_00], [0x_C0, 0x_80]];
+const TRACKBALL_VERTICAL: [[u8; 2]; 2] = [[0x_00, 0x_10], [0x_20, 0x_30]];
+
+/// The encodings of the pointing devices.
+///
+/// The trackball and the mice of the home computers of that era can all be
+/// plugged to the console, but they encode the movements differently and games
+/// (or hacks) were written for a specific one.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrackballMode {
+    /// The CX22 trackball (in trackball mode).
+    Trackball,
+    /// The mouse of the Amiga computers.
+    AmigaMouse,
+    /// The mouse of the Atari ST computers.
+    AtariMouse
+}
+
+/// The trackball controller.
 ///
-/// Long description.
+/// The trackball (and the mice) reports movements on the pins of the port A
+/// (SWCHA) and the button on the input port INPT4 (left controller) or INPT5
+/// (right controller). Movements are reported one step at a time, so the
+/// frontend should move the trackball in small increments, ideally several
+/// times per frame.
+///
+#[cfg_attr(feature = "test-rom", doc = "```")]
+#[cfg_attr(not(feature = "test-rom"), doc = "```ignore")]
+/// # use atari_2600::{Cartridge, Console, Port, Trackball, TrackballMode, TEST_ROM};
+/// # let mut console = Console::new(Cartridge::new(TEST_ROM.to_vec()));
+/// let mut trackball = Trackball::new(TrackballMode::AmigaMouse);
+/// trackball.move_by(1, 0);
+///
+/// console.plug_controller(Port::Left, Box::new(trackball));
+/// ```
 ///
 pub struct Trackball {
-    console: Option<*mut Console>
+    port: Port,
+    mode: TrackballMode,
+    horizontal_count: i32,
+    vertical_count: i32,
+    is_moving_left: bool,
+    is_moving_down: bool,
+    button: bool
 }
 
 impl Trackball {
+    /// Create a trackball.
+    ///
+    /// This function creates a trackball (or a mouse) with the given encoding
+    /// of the movements.
+    ///
+    pub fn new(mode: TrackballMode) -> Trackball {
+        Trackball {
+            port: Port::Left,
+            mode,
+            horizontal_count: 0,
+            vertical_count: 0,
+            is_moving_left: false,
+            is_moving_down: false,
+            button: false
+        }
+    }
+
+    /// Encoding of the movements.
+    pub fn mode(&self) -> TrackballMode {
+        self.mode
+    }
+
+    /// Move the trackball.
+    ///
+    /// This function moves the trackball by the given number of steps
+    /// horizontally (positive is right) and vertically (positive is down).
+    ///
+    pub fn move_by(&mut self, horizontal: i32, vertical: i32) {
+        if horizontal != 0 {
+            self.is_moving_left = horizontal < 0;
+        }
+        if vertical != 0 {
+            self.is_moving_down = vertical > 0;
+        }
+
+        self.horizontal_count = self.horizontal_count.wrapping_add(horizontal);
+        self.vertical_count = self.vertical_count.wrapping_add(vertical);
+
+    }
+
+    /// Press the button.
+    pub fn press_button(&mut self) {
+        self.button = true;
+    }
+
+    /// Release the button.
+    pub fn release_button(&mut self) {
+        self.button = false;
+    }
+
+    /// Pins of the port A (for the left controller) encoding the movements.
+    fn pins(&self) -> u8 {
+        let horizontal = (self.horizontal_count & 0b11) as usize;
+        let vertical = (self.vertical_count & 0b11) as usize;
+
+        match self.mode {
+            TrackballMode::Trackball => {
+                TRACKBALL_HORIZONTAL[self.is_moving_left as usize][horizontal & 0b1] |
+                TRACKBALL_VERTICAL[self.is_moving_down as usize][vertical & 0b1]
+            },
+            TrackballMode::AmigaMouse => {
+                AMIGA_MOUSE_HORIZONTAL[horizontal] | AMIGA_MOUSE_VERTICAL[vertical]
+            },
+            TrackballMode::AtariMouse => {
+                ATARI_MOUSE_HORIZONTAL[horizontal] | ATARI_MOUSE_VERTICAL[vertical]
+            }
+        }
+    }
 }
 
 impl Controller for Trackball {
-    fn plugged(&mut self, console: *mut Console) {
-        self.console = Some(console);
+    fn plugged(&mut self, port: Port) {
+        self.port = port;
     }
 
     fn unplugged(&mut self) {
-        self.console = None;
+    }
+
+    fn apply(&mut self, console: &mut Console) {
+        let pins = self.pins() >> 4;
+        let (first_pin, input) = match self.port {
+            Port::Left => (4, 4),
+            Port::Right => (0, 5)
+        };
+
+        for pin in 0..4 {
+            console.drive_port_a_pin(first_pin + pin, pins & (1 << pin) != 0);
+        }
+
+        console.set_input_level(input, !self.button);
+    }
+
+    fn save_state(&self) -> StateWriter {
+        let mut state = StateWriter::new();
+        state.write_i32(self.horizontal_count);
+        state.write_i32(self.vertical_count);
+        state.write_bool(self.is_moving_left);
+        state.write_bool(self.is_moving_down);
+        state.write_bool(self.button);
+
+        state
+    }
+
+    fn load_state(&mut self, state: &StateWriter) -> Result<(), StateError> {
+        let mut reader = state.reader();
+        self.horizontal_count = reader.read_i32()?;
+        self.vertical_count = reader.read_i32()?;
+        self.is_moving_left = reader.read_bool()?;
+        self.is_moving_down = reader.read_bool()?;
+        self.button = reader.read_bool()?;
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::location::SWCHA;
 
     #[test]
     fn test_trackball() {
+        let mut console = Console::new(Cartridge::new(vec![]));
+
+        // The mice send a gray code for each axis.
+        console.plug_controller(Port::Left, Box::new(Trackball::new(TrackballMode::AmigaMouse)));
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_00);
+
+        console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(1, 0);
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_10);
+        console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(1, 0);
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_50);
+        console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(0, 1);
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_D0);
+
+        console.plug_controller(Port::Right, Box::new(Trackball::new(TrackballMode::AtariMouse)));
+        console.controller_mut::<Trackball>(Port::Right).unwrap().move_by(-1, 0);
+        assert_eq!(console.memory(SWCHA) & 0b0000_1111, 0x_02);
+
+        // The trackball sends a direction and a motion bit.
+        console.plug_controller(Port::Left, Box::new(Trackball::new(TrackballMode::Trackball)));
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_40);
+
+        console.controller_mut::<Trackball>(Port::Left).unwrap().move_by(-1, 0);
+        assert_eq!(console.memory(SWCHA) & 0b1111_0000, 0x_80);
     }
 }
@@ -19,10 +19,217 @@ pub(crate) fn byte_to_boolean_array(value: u8) -> [bool; 8] {
     ]
 }
 
+/// Compute the MD5 digest of some bytes.
+///
+/// The MD5 digest of the ROM is how games are identified by the ROM database
+/// (like most emulators do); it's not used for anything security-related.
+///
+pub(crate) fn md5(bytes: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+        5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20,
+        4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+        6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21
+    ];
+
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+
+    // Pad the message with a single bit, then zeros, then the length of the
+    // message (in bits) so its length is a multiple of 512 bits.
+    let mut message = bytes.to_vec();
+    message.push(0x_80);
+    while message.len() % 64 != 56 {
+        message.push(0x_00);
+    }
+    message.extend_from_slice(&((bytes.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x_67452301, 0x_EFCDAB89, 0x_98BADCFE, 0x_10325476];
+
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in 0..64 {
+            let (f, g) = match i {
+                0..=15  => ((b & c) | (!b & d), i),
+                16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _       => (c ^ (b | !d), (7 * i) % 16)
+            };
+
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g]);
+
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0; 16];
+    for (index, word) in state.iter().enumerate() {
+        digest[index * 4..index * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+
+    digest
+}
+
+/// Initial value of the FNV-1a hash.
+pub(crate) const FNV_OFFSET: u64 = 0x_CBF2_9CE4_8422_2325;
+
+/// Hash some bytes with FNV-1a.
+///
+/// This function continues the hash with the given bytes, starting from
+/// `FNV_OFFSET`; it's fast and good enough to detect differences between
+/// states, but not for anything security-related.
+///
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x_0000_0100_0000_01B3);
+    }
+
+    hash
+}
+
+/// Compute the CRC-32 of some bytes.
+///
+/// This function computes the checksum used by PNG images and BPS patches
+/// (the one of zlib, with the reversed 0xEDB88320 polynomial).
+///
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0x_FFFFFFFF_u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0x_EDB88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Compute the SHA-1 digest of some bytes.
+///
+/// The SHA-1 digest of the ROM is how most ROM catalogues (No-Intro, etc.)
+/// identify games; it's not used for anything security-related.
+///
+pub(crate) fn sha1(bytes: &[u8]) -> [u8; 20] {
+    // Pad the message like MD5 does, but with the length in big endian.
+    let mut message = bytes.to_vec();
+    message.push(0x_80);
+    while message.len() % 64 != 56 {
+        message.push(0x_00);
+    }
+    message.extend_from_slice(&((bytes.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    let mut state: [u32; 5] = [0x_67452301, 0x_EFCDAB89, 0x_98BADCFE, 0x_10325476, 0x_C3D2E1F0];
+
+    for chunk in message.chunks(64) {
+        let mut words = [0_u32; 80];
+        for (word, bytes) in words.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19  => ((b & c) | (!b & d), 0x_5A827999),
+                20..=39 => (b ^ c ^ d, 0x_6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x_8F1BBCDC),
+                _       => (b ^ c ^ d, 0x_CA62C1D6)
+            };
+
+            let temp = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+        state[4] = state[4].wrapping_add(e);
+    }
+
+    let mut digest = [0; 20];
+    for (index, word) in state.iter().enumerate() {
+        digest[index * 4..index * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+/// Format bytes as a lowercase hexadecimal string.
+pub(crate) fn to_hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_md5() {
+        assert_eq!(to_hex_string(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(to_hex_string(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            to_hex_string(&md5(b"The quick brown fox jumps over the lazy dog")),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(to_hex_string(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(to_hex_string(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            to_hex_string(&sha1(b"The quick brown fox jumps over the lazy dog")),
+            "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12"
+        );
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0x_CBF2_9CE4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0x_AF63_DC4C_8601_EC8C);
+        assert_eq!(fnv1a(fnv1a(FNV_OFFSET, b"foo"), b"bar"), fnv1a(FNV_OFFSET, b"foobar"));
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0x_00000000);
+        assert_eq!(crc32(b"IEND"), 0x_AE426082);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x_414FA339);
+    }
+
     #[test]
     fn test_byte_to_boolean_array() {
         assert_eq!(
@@ -10,118 +10,389 @@
 //!
 //! TODO; Write the description.
 //!
-use crate::color::{background_color};
 use crate::playfield::{
     playfield_mirror_mode,
     playfield_priority,
-    playfield_color, playfield_left_color, playfield_right_color,
     playfield_score_mode,
     playfield_bits
 };
-use crate::console::Console;
-
-fn draw_playfield(console: &Console, scanline: &mut [(u8, u8, u8); 160]) {
-    // The playfield can be drawn above or under the other objects, but it's not
-    // the responsibility of this function (it's the responsibility of the
-    // caller).
-
-    // Basically, there are 2x2 modes which are independent and thus resulting
-    // in 4 different code paths.
-    // If the "score mode" is activated, the color used to draw the playfield
-    // becomes the color of player 1 & 2, where color of player 1 will be used
-    // to draw the left side of the playfield, and color of player 2 will be
-    // used to draw the right side.
-    // If the "mirror mode" is used, the right side of the playfield becomes
-    // the left side flipped horizontally.
-    let score_mode = playfield_score_mode(console);
-    let mirror_mode = playfield_mirror_mode(console);
-
-    // We retrieve the data of the playfield (the bits that defines whether
-    // the playfield is display on some pixels or not).
-    let bits = playfield_bits(console);
-
-    // Draw the left side of the playground.
-    let color = match score_mode {
-        true  => playfield_left_color(console),
-        false => playfield_color(console)
-    };
+use crate::sprite::{player_bits, player_copies, player_width, is_player_mirrored};
+use crate::missile::{is_missile_enabled, missile_width};
+use crate::ball::{is_ball_enabled, ball_width};
+use crate::tia::Tia;
+use crate::tia_state::PixelLayers;
+use crate::console::Player;
 
-    for (index, bit) in bits.iter().enumerate() {
-        if *bit {
-            scanline[index * 4 + 0] = color;
-            scanline[index * 4 + 1] = color;
-            scanline[index * 4 + 2] = color;
-            scanline[index * 4 + 3] = color;
+fn is_playfield_pixel(tia: &Tia, pixel: usize) -> bool {
+    // The playfield is 20 bits wide and each bit covers 4 pixels; the right
+    // side of the screen repeats the bits, or flip them horizontally if the
+    // "mirror mode" is used.
+    let bits = playfield_bits(tia);
+    let index = match pixel < 80 {
+        true => pixel / 4,
+        false => match playfield_mirror_mode(tia) {
+            true  => 19 - (pixel - 80) / 4,
+            false => (pixel - 80) / 4
         }
-    }
+    };
+
+    bits[index]
+}
 
-    // Draw the right side of the playground.
-    let color = match score_mode {
-        true  => playfield_right_color(console),
-        false => playfield_color(console)
+fn player_pixel(tia: &Tia, player: Player, pixel: usize) -> Option<(u8, u8, u8)> {
+    let (index, color) = match player {
+        Player::One => (0, tia.object_colors().player0),
+        Player::Two => (1, tia.object_colors().player1)
     };
 
-    if mirror_mode {
-        for (index, bit) in bits.iter().rev().enumerate() {
-            if *bit {
-                scanline[80 + index * 4 + 0] = color;
-                scanline[80 + index * 4 + 1] = color;
-                scanline[80 + index * 4 + 2] = color;
-                scanline[80 + index * 4 + 3] = color;
-            }
-        }
-    } else {
-        for (index, bit) in bits.iter().enumerate() {
-            if *bit {
-                scanline[80 + index * 4 + 0] = color;
-                scanline[80 + index * 4 + 1] = color;
-                scanline[80 + index * 4 + 2] = color;
-                scanline[80 + index * 4 + 3] = color;
-            }
+    // Each copy of the player draws its 8 bits from the high one (or from
+    // the low one when it's mirrored), stretched to the width of the player.
+    let width = player_width(tia, player);
+    let bits = player_bits(tia, player);
+    let is_visible = player_copies(tia, player).iter().enumerate().any(|(copy, offset)| {
+        let pixel = (pixel as u32 + 320 - tia.players_position[index] - offset) % 160;
+        let is_hidden = copy == 0 && tia.hidden_first_copies[index] > 0;
+        let bit = (pixel * 8 / width) as usize;
+
+        pixel < width && !is_hidden && match is_player_mirrored(tia, player) {
+            true  => bits[bit],
+            false => bits[7 - bit]
         }
-    }
+    });
+
+    if is_visible { Some(color) } else { None }
+}
+
+fn missile_pixel(tia: &Tia, player: Player, pixel: usize) -> Option<(u8, u8, u8)> {
+    let (index, color) = match player {
+        Player::One => (0, tia.object_colors().player0),
+        Player::Two => (1, tia.object_colors().player1)
+    };
+
+    // The missile is repeated like its player, but not stretched when the
+    // player is.
+    let copies = match player_width(tia, player) {
+        8 => player_copies(tia, player),
+        _ => &[0]
+    };
+    let width = missile_width(tia, player);
+    let is_visible = is_missile_enabled(tia, player) && copies.iter().any(|offset| {
+        let position = (tia.missiles_position[index] + offset) % 160;
+        is_object_pixel(tia, 2 + index, position, width, pixel)
+    });
+
+    if is_visible { Some(color) } else { None }
 }
 
-fn draw_sprites(_console: &Console, _scanline: &mut [(u8, u8, u8); 160]) {
-    // TODO; To be implemented.
+fn ball_pixel(tia: &Tia, pixel: usize) -> Option<(u8, u8, u8)> {
+    let is_visible = is_ball_enabled(tia) &&
+        is_object_pixel(tia, 4, tia.ball_position, ball_width(tia), pixel);
+
+    if is_visible { Some(tia.object_colors().playfield) } else { None }
 }
 
-fn draw_missiles(_console: &Console, _scanline: &mut [(u8, u8, u8); 160]) {
-    // TODO; To be implemented.
+fn is_object_pixel(tia: &Tia, object: usize, position: u32, width: u32, pixel: usize) -> bool {
+    // The missiles and the ball are drawn from their position, 1, 2, 4 or 8
+    // pixels wide. When an extra clock of HMOVE reaches a narrow one (1 or 2
+    // pixels) on the pixel right before it, it starts drawing one pixel
+    // early; that's the odd shapes of the stars of Cosmic Ark.
+    let offset = (pixel as u32 + 160 - position) % 160;
+    offset < width || (width < 4 && offset == 159 && tia.motion_clocks[object])
 }
 
-fn draw_ball(_console: &Console, _scanline: &mut [(u8, u8, u8); 160]) {
-    // TODO; To be implemented.
+/// The color registers a pixel is drawn with.
+///
+/// The missiles are drawn with the color of their player, and the ball with
+/// the color of the playfield.
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PixelColor {
+    Player0,
+    Player1,
+    Playfield,
+    Background
 }
 
-pub(crate) fn create_scanline(console: &Console) -> [(u8, u8, u8); 160] {
+/// Resolve the priority of the objects covering a pixel.
+///
+/// This function is the priority encoder of the TIA; it tells which of the
+/// objects covering a pixel is visible, by its color register. The objects
+/// are drawn in the following order (from the top).
+///
+/// | Normal      | Playfield priority | Score mode, left | Score mode, right |
+/// |-------------|--------------------|------------------|-------------------|
+/// | P0, M0      | PF, BL             | P0, M0, PF       | P0, M0            |
+/// | P1, M1      | P0, M0             | P1, M1           | P1, M1, PF        |
+/// | PF, BL      | P1, M1             | BL               | BL                |
+/// | BK          | BK                 | BK               | BK                |
+///
+/// In score mode, the playfield is drawn with the color of the player of its
+/// half of the screen, and it's ranked along with it since it goes through
+/// the same path; the ball keeps the color and the rank of the playfield. The
+/// priority bit takes precedence over the score mode.
+///
+pub(crate) fn resolve_priority(layers: &PixelLayers, is_score_mode: bool, is_playfield_priority: bool, is_left_half: bool) -> PixelColor {
+    let player0 = layers.players[0] || layers.missiles[0];
+    let player1 = layers.players[1] || layers.missiles[1];
 
-    // First, create and fill the entire scanline with the background color.
-    let background_colorr = background_color(console);
-    let mut scanline = [background_colorr; 160];
+    if is_playfield_priority {
+        return match () {
+            _ if layers.playfield || layers.ball => PixelColor::Playfield,
+            _ if player0 => PixelColor::Player0,
+            _ if player1 => PixelColor::Player1,
+            _ => PixelColor::Background
+        };
+    }
 
-    let playfield_priority = playfield_priority(console);
+    let (playfield0, playfield1) = match is_score_mode {
+        true  => (layers.playfield && is_left_half, layers.playfield && !is_left_half),
+        false => (false, false)
+    };
 
-    if playfield_priority {
-        draw_playfield(console, &mut scanline);
-        draw_sprites(console, &mut scanline);
-        draw_missiles(console, &mut scanline);
-        draw_ball(console, &mut scanline);
+    match () {
+        _ if player0 || playfield0 => PixelColor::Player0,
+        _ if player1 || playfield1 => PixelColor::Player1,
+        _ if layers.ball || (layers.playfield && !is_score_mode) => PixelColor::Playfield,
+        _ => PixelColor::Background
     }
-    else {
-        draw_sprites(console, &mut scanline);
-        draw_missiles(console, &mut scanline);
-        draw_ball(console, &mut scanline);
-        draw_playfield(console, &mut scanline);
+}
+
+/// Compute the color of a pixel of the current scanline.
+///
+/// The pixel is computed from the TIA registers as they are when the beam
+/// reaches it, so a register changed in the middle of a scanline only affects
+/// the pixels drawn afterward; several games rely on it to draw more than the
+/// TIA can display at once (six-digit scores, status bars, etc.). The objects
+/// covering the pixel are ranked by `resolve_priority()`.
+///
+pub(crate) fn create_pixel(tia: &Tia, pixel: usize) -> (u8, u8, u8) {
+    let layers = pixel_layers(tia, pixel);
+    let colors = tia.object_colors();
+
+    match resolve_priority(&layers, playfield_score_mode(tia), playfield_priority(tia), pixel < 80) {
+        PixelColor::Player0 => colors.player0,
+        PixelColor::Player1 => colors.player1,
+        PixelColor::Playfield => colors.playfield,
+        PixelColor::Background => colors.background
     }
+}
 
-    scanline
+/// Compute the layers of a pixel of the current scanline.
+///
+/// This function tells which objects cover the pixel, before the priorities
+/// are applied; the objects are computed like in `create_pixel()`.
+///
+pub(crate) fn pixel_layers(tia: &Tia, pixel: usize) -> PixelLayers {
+    PixelLayers {
+        playfield: is_playfield_pixel(tia, pixel),
+        players: [
+            player_pixel(tia, Player::One, pixel).is_some(),
+            player_pixel(tia, Player::Two, pixel).is_some()
+        ],
+        missiles: [
+            missile_pixel(tia, Player::One, pixel).is_some(),
+            missile_pixel(tia, Player::Two, pixel).is_some()
+        ],
+        ball: ball_pixel(tia, pixel).is_some()
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+    use crate::location::{COLUP0, COLUP1, COLUPF, COLUBK, CTRLPF, PF2, ENAM0, ENABL, NUSIZ0, HMOVE};
+    use crate::tia::HORIZONTAL_CYCLES;
+
+    fn create_console() -> Console {
+        let mut rom = vec![0x_4C, 0x_00, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        *console.memory_mut(COLUP0) = 0x_1E;
+        *console.memory_mut(COLUP1) = 0x_44;
+        *console.memory_mut(COLUPF) = 0x_86;
+        *console.memory_mut(COLUBK) = 0x_00;
+
+        console
+    }
 
     #[test]
-    fn test_video() {
+    fn test_score_mode_split() {
+        let mut console = create_console();
+        let left_color = console.tia.object_colors().player0;
+        let right_color = console.tia.object_colors().player1;
+
+        // The last bit of PF2 covers pixels 76 to 79 on the left side and
+        // pixels 80 to 83 on the right side when the playfield is mirrored.
+        *console.memory_mut(PF2) = 0b1000_0000;
+        *console.memory_mut(CTRLPF) = 0b0000_0011;
+
+        assert_eq!(create_pixel(&console.tia, 75), console.tia.object_colors().background);
+        assert_eq!(create_pixel(&console.tia, 76), left_color);
+        assert_eq!(create_pixel(&console.tia, 79), left_color);
+        assert_eq!(create_pixel(&console.tia, 80), right_color);
+        assert_eq!(create_pixel(&console.tia, 83), right_color);
+        assert_eq!(create_pixel(&console.tia, 84), console.tia.object_colors().background);
+
+        // The score mode is ignored when the playfield has priority.
+        *console.memory_mut(CTRLPF) = 0b0000_0111;
+        assert_eq!(create_pixel(&console.tia, 79), console.tia.object_colors().playfield);
+        assert_eq!(create_pixel(&console.tia, 80), console.tia.object_colors().playfield);
+
+        // Without the score mode, both sides use the color of the playfield.
+        *console.memory_mut(CTRLPF) = 0b0000_0001;
+        assert_eq!(create_pixel(&console.tia, 79), console.tia.object_colors().playfield);
+        assert_eq!(create_pixel(&console.tia, 80), console.tia.object_colors().playfield);
+    }
+
+    #[test]
+    fn test_priority_truth_table() {
+        use PixelColor::*;
+
+        // All combinations of objects, with and without the score mode and
+        // the priority bit, on both halves of the screen.
+        for bits in 0..64_u8 {
+            let layers = PixelLayers {
+                players: [bits & 1 != 0, bits & 4 != 0],
+                missiles: [bits & 2 != 0, bits & 8 != 0],
+                ball: bits & 16 != 0,
+                playfield: bits & 32 != 0
+            };
+            let player0 = bits & 0b00_0011 != 0;
+            let player1 = bits & 0b00_1100 != 0;
+            let ball = layers.ball;
+            let playfield = layers.playfield;
+
+            for &is_left_half in [true, false].iter() {
+                let normal = match () {
+                    _ if player0 => Player0,
+                    _ if player1 => Player1,
+                    _ if ball || playfield => Playfield,
+                    _ => Background
+                };
+                assert_eq!(resolve_priority(&layers, false, false, is_left_half), normal, "{:06b}", bits);
+
+                // The priority bit takes precedence over the score mode.
+                let priority = match () {
+                    _ if ball || playfield => Playfield,
+                    _ if player0 => Player0,
+                    _ if player1 => Player1,
+                    _ => Background
+                };
+                assert_eq!(resolve_priority(&layers, false, true, is_left_half), priority, "{:06b}", bits);
+                assert_eq!(resolve_priority(&layers, true, true, is_left_half), priority, "{:06b}", bits);
+
+                let score = match () {
+                    _ if player0 || (playfield && is_left_half) => Player0,
+                    _ if player1 || (playfield && !is_left_half) => Player1,
+                    _ if ball => Playfield,
+                    _ => Background
+                };
+                assert_eq!(resolve_priority(&layers, true, false, is_left_half), score, "{:06b}", bits);
+            }
+        }
+
+        // The playfield of the score mode goes under the first player on the
+        // right half, and above the second one on the left half.
+        let layers = PixelLayers { playfield: true, players: [true, true], ..PixelLayers::default() };
+        assert_eq!(resolve_priority(&layers, true, false, false), Player0);
+        let layers = PixelLayers { playfield: true, players: [false, true], ..PixelLayers::default() };
+        assert_eq!(resolve_priority(&layers, true, false, true), Player0);
+        assert_eq!(resolve_priority(&layers, true, false, false), Player1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mid_scanline_changes() {
+        let mut console = create_console();
+        let left_color = console.tia.object_colors().player0;
+        let right_color = console.tia.object_colors().player1;
+
+        *console.memory_mut(PF2) = 0b1000_0000;
+        *console.memory_mut(CTRLPF) = 0b0000_0011;
+
+        // Move the beam to the pixel 78 of a visible scanline.
+        console.tia.scanline = 50;
+        console.tia.scanline_cycle = 68 + 78;
+
+        // Draw the pixels 78 and 79, then change the colors of the players and
+        // draw the pixels 80 and 81; the right side uses the new color.
+        console.execute_color_cycle();
+        console.execute_color_cycle();
+        *console.memory_mut(COLUP0) = 0x_C8;
+        *console.memory_mut(COLUP1) = 0x_C8;
+        let new_color = console.tia.object_colors().player1;
+        console.execute_color_cycle();
+        console.execute_color_cycle();
+
+        // Disable the playfield right at the boundary; only the pixels drawn
+        // afterward are affected.
+        *console.memory_mut(PF2) = 0b0000_0000;
+        console.execute_color_cycle();
+        console.execute_color_cycle();
+
+        let line = &console.tia.framebuffer[10];
+        assert_eq!(line[78], left_color);
+        assert_eq!(line[79], left_color);
+        assert_eq!(line[80], new_color);
+        assert_eq!(line[81], new_color);
+        assert_eq!(line[82], console.tia.object_colors().background);
+        assert_eq!(line[83], console.tia.object_colors().background);
+        assert_ne!(new_color, right_color);
+    }
+
+    #[test]
+    fn test_missile_and_ball_width() {
+        // Draw a scanline with the first missile or the ball at the given
+        // position and width (the size bits), with HMOVE written on the given
+        // color cycle, and return the pixels it covers.
+        fn draw(is_ball: bool, position: u32, size: u8, hmove: Option<u32>) -> Vec<usize> {
+            let mut tia = Tia::new();
+            tia.scanline = 100;
+            tia.write(COLUP0, 0x_1E);
+            tia.write(COLUPF, 0x_86);
+            match is_ball {
+                true  => tia.write(ENABL, 0b0000_0010),
+                false => tia.write(ENAM0, 0b0000_0010)
+            }
+            tia.write(NUSIZ0, size << 4);
+            tia.write(CTRLPF, size << 4);
+            tia.missiles_position[0] = position;
+            tia.ball_position = position;
+
+            for cycle in 0..HORIZONTAL_CYCLES {
+                if Some(cycle) == hmove {
+                    tia.write(HMOVE, 0);
+                }
+                tia.step();
+            }
+
+            let background = tia.object_colors().background;
+            (0..160).filter(|pixel| tia.framebuffer[60][*pixel] != background).collect()
+        }
+
+        let check = |position, size, hmove, pixels: &[usize]| {
+            assert_eq!(draw(false, position, size, hmove), pixels, "missile at {}", position);
+            assert_eq!(draw(true, position, size, hmove), pixels, "ball at {}", position);
+        };
+
+        // They're 1, 2, 4 or 8 pixels wide from their position, and they wrap
+        // around the right edge.
+        check(41, 0, None, &[41]);
+        check(41, 1, None, &[41, 42]);
+        check(41, 2, None, &[41, 42, 43, 44]);
+        check(41, 3, None, &[41, 42, 43, 44, 45, 46, 47, 48]);
+        check(159, 1, None, &[0, 159]);
+
+        // Written on the color cycle 98, HMOVE clocks the objects on the
+        // pixels 36, 40, 44 and so on (HMM0 and HMBL are cleared, so they get
+        // 8 clocks); the narrow objects starting right after one of them start
+        // one pixel early, but not the wide ones.
+        check(41, 0, Some(98), &[40, 41]);
+        check(41, 1, Some(98), &[40, 41, 42]);
+        check(41, 2, Some(98), &[41, 42, 43, 44]);
+        check(42, 0, Some(98), &[42]);
+        check(69, 0, Some(98), &[69]);
+    }
+}
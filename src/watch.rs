@@ -0,0 +1,62 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+/// A change of a watched RAM location.
+///
+/// The values of the watched RAM locations are compared at the end of each
+/// frame, and this structure describes one that changed during the frame
+/// (intermediate values are not reported).
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RamChange {
+    /// Frame during which the value changed.
+    pub frame: u64,
+    /// Address of the RAM location (from 0x80 to 0xFF).
+    pub address: u16,
+    /// Value at the end of the previous frame.
+    pub old_value: u8,
+    /// Value at the end of the frame.
+    pub new_value: u8
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cartridge::Cartridge;
+    use crate::console::Console;
+
+    #[test]
+    fn test_ram_watches() {
+        // LDA #$2A, STA $81, INC $80 and JMP $F004.
+        let mut rom = vec![0x_A9, 0x_2A, 0x_85, 0x_81, 0x_E6, 0x_80, 0x_4C, 0x_04, 0x_F0];
+        rom.resize(4096, 0x_EA);
+
+        let mut console = Console::new(Cartridge::new(rom));
+        console.watch_ram(0x_80);
+        console.watch_ram(0x_81);
+        console.watch_ram(0x_82);
+
+        let summary = console.run_frame();
+        assert_eq!(summary.ram_changes.len(), 2);
+        assert_eq!(summary.ram_changes[0].frame, 1);
+        assert_eq!(summary.ram_changes[0].address, 0x_80);
+        assert_eq!(summary.ram_changes[0].old_value, 0);
+        assert_eq!(summary.ram_changes[1].address, 0x_81);
+        assert_eq!(summary.ram_changes[1].new_value, 0x_2A);
+
+        // Only the locations that changed during the frame are reported.
+        let old_value = summary.ram_changes[0].new_value;
+        let summary = console.run_frame();
+        assert_eq!(summary.ram_changes.len(), 1);
+        assert_eq!(summary.ram_changes[0].frame, 2);
+        assert_eq!(summary.ram_changes[0].old_value, old_value);
+
+        console.unwatch_ram(0x_80);
+        let summary = console.run_frame();
+        assert!(summary.ram_changes.is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright (c) 2020 - Jonathan De Wachter
+//
+// This source file is part of Atari 2600 Emulator which is released under the
+// MIT license. Please refer to the LICENSE file that can be found at the root
+// of the project directory.
+//
+// Written by Jonathan De Wachter <dewachter.jonathan@gmail.com>, December 2020
+
+//! A snapshot of the public API of the crate.
+//!
+//! This test lists what the crate exposes, starting from `src/lib.rs`; its
+//! public modules and its re-exports (along with the features they depend
+//! on), then the public items of the files they come from (the functions with
+//! their signature, the types with their public fields and their variants,
+//! the traits with their methods). Only the items of the public modules and
+//! the re-exported items are listed, with their methods; what the private
+//! modules don't re-export isn't part of the API. The list is compared with
+//! the snapshot of `tests/public-api.txt`, so a change of the public API
+//! doesn't go unnoticed before a release. When the change is intended, the
+//! snapshot is updated by running the test with `UPDATE_PUBLIC_API=1`, and
+//! the diff of the snapshot is reviewed with the rest.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const SNAPSHOT: &str = "tests/public-api.txt";
+
+// The keywords of the items whose signature ends at their body.
+const ITEMS: [&str; 10] = ["fn ", "const fn ", "struct ", "enum ", "trait ", "const ", "static ", "type ", "use ", "mod "];
+
+// Strip the comment ending a line.
+fn strip_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(index) => line[..index].trim_end(),
+        None => line
+    }
+}
+
+// Depth of the braces at the start of each line, the braces of the strings,
+// of the characters and of the comments ignored.
+fn brace_depths(source: &str) -> Vec<usize> {
+    let mut depths = Vec::new();
+    let mut depth = 0_usize;
+    let mut is_string = false;
+
+    for line in source.lines() {
+        depths.push(depth);
+
+        let characters = line.chars().collect::<Vec<_>>();
+        let mut index = 0;
+        while index < characters.len() {
+            match (is_string, characters[index]) {
+                (true, '\\') => index += 1,
+                (true, '"') => is_string = false,
+                (true, _) => (),
+                (false, '"') => is_string = true,
+                (false, '/') if characters.get(index + 1) == Some(&'/') => break,
+                (false, '\'') if characters.get(index + 1) == Some(&'\\') => index += 3,
+                (false, '\'') if characters.get(index + 2) == Some(&'\'') => index += 2,
+                (false, '{') => depth += 1,
+                (false, '}') => depth = depth.saturating_sub(1),
+                (false, _) => ()
+            }
+            index += 1;
+        }
+    }
+
+    depths
+}
+
+// Name of an item from its signature.
+fn item_name(signature: &str) -> String {
+    let mut item = signature.trim_start_matches("pub ");
+    for keyword in ITEMS {
+        if let Some(rest) = item.strip_prefix(keyword) {
+            item = rest;
+            break;
+        }
+    }
+
+    item.chars().take_while(|character| character.is_alphanumeric() || *character == '_').collect()
+}
+
+// Name of the type an implementation block is for.
+fn impl_type(context: &str) -> String {
+    let mut rest = context.trim_start_matches("impl");
+
+    // Skip the generic parameters of the block.
+    if rest.starts_with('<') {
+        let mut depth = 0;
+        for (index, character) in rest.char_indices() {
+            match character {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => ()
+            }
+            if depth == 0 {
+                rest = &rest[index + 1..];
+                break;
+            }
+        }
+    }
+
+    if let Some(index) = rest.find(" for ") {
+        rest = &rest[index + 5..];
+    }
+
+    let path = rest.trim().trim_start_matches('&').split('<').next().unwrap_or_default();
+    path.rsplit("::").next().unwrap_or_default().trim().to_string()
+}
+
+// The public items of a source file, along with the name of the item they
+// belong to (themselves for the top-level items, their type for the fields,
+// the variants and the methods). The items which only exist for some
+// features are prefixed with their condition, and those of the unit tests
+// are left out.
+fn public_items(source: &str) -> Vec<(String, String)> {
+    // The unit tests are at the end of the files.
+    let lines = source.lines()
+        .take_while(|line| line.trim() != "mod test {")
+        .collect::<Vec<_>>();
+    let depths = brace_depths(source);
+
+    // Join the lines of a signature, up to its body or its end.
+    let signature = |start: usize| {
+        let mut signature = String::new();
+        for line in &lines[start..] {
+            let line = strip_comment(line.trim());
+            if !signature.is_empty() && !signature.ends_with('(') && !signature.ends_with('<') && !line.starts_with(')') && !line.starts_with('>') {
+                signature.push(' ');
+            }
+            signature.push_str(line);
+            let item = signature.trim_start_matches("pub ");
+            let end = match item {
+                _ if item.starts_with("use ") => signature.find(';'),
+                _ if (item.starts_with("const ") && !item.starts_with("const fn")) || item.starts_with("static ") => signature.find(" ="),
+                _ => signature.find(['{', ';'])
+            };
+            if let Some(end) = end {
+                signature.truncate(end);
+                break;
+            }
+        }
+        signature.trim_end().trim_end_matches(',').to_string()
+    };
+
+    let mut items = Vec::new();
+    let mut context = String::new();
+    let mut owner = String::new();
+    let mut block: Option<(String, usize)> = None;
+    let mut condition: Option<String> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let depth = depths[index];
+        let line = strip_comment(line.trim());
+
+        // The condition of an item is among the attributes preceding it.
+        if line.starts_with("#[cfg(") {
+            condition = Some(line.to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let prefix = match condition.take() {
+            Some(condition) if condition == "#[cfg(test)]" => continue,
+            Some(condition) => format!("{} ", condition),
+            None => String::new()
+        };
+
+        if let Some((kind, block_depth)) = &block {
+            if depth == block_depth + 1 && line.starts_with('}') {
+                block = None;
+            }
+            else if depth == block_depth + 1 {
+                match kind.as_str() {
+                    "enum" => items.push((owner.clone(), format!("    {}{}", prefix, line.trim_end_matches(',')))),
+                    _ if ITEMS.iter().any(|item| line.starts_with(item)) => items.push((owner.clone(), format!("    {}{}", prefix, signature(index)))),
+                    _ => ()
+                }
+            }
+            continue;
+        }
+
+        if depth == 0 && line.starts_with("impl") {
+            context = signature(index);
+            continue;
+        }
+
+        let item = match line.strip_prefix("pub ") {
+            Some(item) => item,
+            None => continue
+        };
+
+        if ITEMS.iter().any(|keyword| item.starts_with(keyword)) {
+            for kind in ["enum", "trait"] {
+                if item.starts_with(&format!("{} ", kind)) {
+                    block = Some((kind.to_string(), depth));
+                }
+            }
+
+            match depth {
+                0 => {
+                    owner = item_name(&signature(index));
+                    items.push((owner.clone(), format!("{}{}", prefix, signature(index))));
+                },
+                _ => items.push((impl_type(&context), format!("{} => {}{}", context, prefix, signature(index))))
+            }
+        }
+        else {
+            // A public field.
+            items.push((owner.clone(), format!("    {}{}", prefix, line.trim_end_matches(','))));
+        }
+    }
+
+    items
+}
+
+// The public API of the crate; the public modules and the re-exports of the
+// crate root, then the items they expose, file by file.
+fn public_api() -> String {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let root = fs::read_to_string(directory.join("lib.rs")).unwrap();
+
+    // The names exposed by each module; none means all of them.
+    let mut modules: BTreeMap<String, Option<Vec<String>>> = BTreeMap::new();
+
+    let mut api = String::from("[lib.rs]\n");
+    for (_, item) in public_items(&root) {
+        api.push_str(&item);
+        api.push('\n');
+
+        let item = item.rsplit("pub ").next().unwrap();
+        if let Some(module) = item.strip_prefix("mod ") {
+            modules.insert(module.to_string(), None);
+        }
+        else if let Some(path) = item.strip_prefix("use ") {
+            let (module, names) = path.split_once("::").unwrap();
+            let names = names.trim_matches(['{', '}']).split(',').map(|name| name.trim().to_string());
+            if let Some(exposed) = modules.entry(module.to_string()).or_insert_with(|| Some(Vec::new())) {
+                exposed.extend(names);
+            }
+        }
+    }
+
+    for (module, names) in modules {
+        let path = directory.join(format!("{}.rs", module));
+        let items = public_items(&fs::read_to_string(&path).unwrap());
+
+        if let Some(names) = &names {
+            for name in names {
+                assert!(items.iter().any(|(owner, _)| owner == name), "{} isn't an item of {}.rs", name, module);
+            }
+        }
+
+        api.push_str(&format!("[{}.rs]\n", module));
+        for (owner, item) in items {
+            if names.as_ref().is_none_or(|names| names.contains(&owner)) {
+                api.push_str(&item);
+                api.push('\n');
+            }
+        }
+    }
+
+    api
+}
+
+#[test]
+fn test_public_api() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT);
+    let api = public_api();
+
+    if env::var_os("UPDATE_PUBLIC_API").is_some() {
+        fs::write(&path, &api).unwrap();
+        return;
+    }
+
+    let snapshot = fs::read_to_string(&path).unwrap_or_default();
+    let removed = snapshot.lines().filter(|line| !api.lines().any(|other| other == *line)).collect::<Vec<_>>();
+    let added = api.lines().filter(|line| !snapshot.lines().any(|other| other == *line)).collect::<Vec<_>>();
+
+    assert!(snapshot == api,
+        "the public API changed (run the test with UPDATE_PUBLIC_API=1 if it's intended)\nremoved:\n{}\nadded:\n{}",
+        removed.join("\n"), added.join("\n"));
+}